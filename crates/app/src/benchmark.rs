@@ -0,0 +1,223 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use camera::CameraState;
+use glam::Vec3;
+use inject::DI;
+use log::info;
+use math::{Position, Rotation};
+use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+use statistics::RendererStatistics;
+
+/// Command line configuration for benchmark mode.
+/// Benchmark mode is enabled with `--benchmark path.json --frames N`. An optional
+/// `--benchmark-output file` controls where results are written.
+#[derive(Debug, Clone)]
+pub struct BenchmarkConfig {
+    pub path: PathBuf,
+    pub frames: u32,
+    pub output: PathBuf,
+}
+
+impl BenchmarkConfig {
+    const DEFAULT_FRAMES: u32 = 1000;
+
+    /// Parse the benchmark configuration from command line arguments. Returns `None` if
+    /// benchmark mode was not requested.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Option<Self>> {
+        let mut path = None;
+        let mut frames = Self::DEFAULT_FRAMES;
+        let mut output = PathBuf::from("benchmark_results.csv");
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing value for argument {arg}"))
+            };
+            match arg.as_str() {
+                "--benchmark" => path = Some(PathBuf::from(value()?)),
+                "--frames" => frames = value()?.parse()?,
+                "--benchmark-output" => output = PathBuf::from(value()?),
+                _ => {}
+            }
+        }
+
+        if frames == 0 {
+            bail!("Benchmark needs to run for at least one frame");
+        }
+
+        Ok(path.map(|path| Self {
+            path,
+            frames,
+            output,
+        }))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    pub position: Vec3,
+    /// Pitch, yaw and roll in radians.
+    pub rotation: Vec3,
+}
+
+/// A camera path the benchmark flies along. Keyframes are spaced evenly over the
+/// duration of the benchmark.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    pub fn load(path: &PathBuf) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let camera_path: Self = serde_json::from_reader(std::io::BufReader::new(file))?;
+        if camera_path.keyframes.is_empty() {
+            bail!("Camera path {path:?} has no keyframes");
+        }
+        Ok(camera_path)
+    }
+
+    /// Sample the path at `t` in [0, 1], linearly interpolating between keyframes.
+    pub fn sample(&self, t: f32) -> CameraKeyframe {
+        let segments = self.keyframes.len() - 1;
+        if segments == 0 {
+            return self.keyframes[0];
+        }
+        let t = t.clamp(0.0, 1.0) * segments as f32;
+        let index = (t.floor() as usize).min(segments - 1);
+        let local_t = t - index as f32;
+        let from = self.keyframes[index];
+        let to = self.keyframes[index + 1];
+        CameraKeyframe {
+            position: from.position.lerp(to.position, local_t),
+            rotation: from.rotation.lerp(to.rotation, local_t),
+        }
+    }
+}
+
+/// Runs the benchmark by moving the camera along a path and recording frame timings.
+#[derive(Debug)]
+pub struct Benchmark {
+    config: BenchmarkConfig,
+    path: CameraPath,
+    frame: u32,
+    frame_times: Vec<Duration>,
+    /// Index of each recorded frame in [`RendererStatistics`], used to look up its GPU timings.
+    statistics_frames: Vec<u64>,
+    /// GPU time of each section, by the index of the measured frame in [`RendererStatistics`].
+    section_times: BTreeMap<String, HashMap<u64, Duration>>,
+}
+
+impl Benchmark {
+    pub fn new(config: BenchmarkConfig) -> Result<Self> {
+        let path = CameraPath::load(&config.path)?;
+        info!("Starting benchmark {:?} for {} frames", config.path, config.frames);
+        Ok(Self {
+            frame_times: Vec::with_capacity(config.frames as usize),
+            statistics_frames: Vec::with_capacity(config.frames as usize),
+            section_times: BTreeMap::new(),
+            config,
+            path,
+            frame: 0,
+        })
+    }
+
+    /// Move the camera to the position for the current frame.
+    /// # DI Access
+    /// - Write [`CameraState`]
+    pub fn before_frame(&mut self, bus: &EventBus<DI>) {
+        let t = self.frame as f32 / (self.config.frames - 1).max(1) as f32;
+        let keyframe = self.path.sample(t);
+        let di = bus.data().read().unwrap();
+        let mut camera = di.write_sync::<CameraState>().unwrap();
+        camera.set_position(Position(keyframe.position));
+        camera.set_rotation(Rotation(keyframe.rotation));
+    }
+
+    /// Record statistics for the frame that was just rendered.
+    /// Returns true once the benchmark is finished.
+    /// # DI Access
+    /// - Read [`RendererStatistics`]
+    pub fn after_frame(&mut self, bus: &EventBus<DI>) -> bool {
+        let di = bus.data().read().unwrap();
+        let statistics = di.read_sync::<RendererStatistics>().unwrap();
+        // The first frame time includes startup, so we skip it.
+        if self.frame > 0 {
+            self.frame_times.push(statistics.frame_time());
+            self.statistics_frames.push(statistics.frame_index());
+        }
+        // GPU timings are only measured every few frames, and become available a few frames
+        // after they were measured.
+        for (name, frame, time) in statistics.section_measurements() {
+            self.section_times
+                .entry(name.to_owned())
+                .or_default()
+                .insert(frame, time);
+        }
+        self.frame += 1;
+        self.frame >= self.config.frames
+    }
+
+    /// Write the results to the output file and log a summary. Each row holds the frame time
+    /// and the GPU time of every section in milliseconds. Cells of sections that were not
+    /// measured in a frame are left empty.
+    pub fn finish(&self) -> Result<()> {
+        let summary = self.summary();
+        info!("Benchmark finished: {summary}");
+        let mut output = String::from("frame,frame_time_ms");
+        for name in self.section_times.keys() {
+            write!(output, ",{name}_ms")?;
+        }
+        output.push('\n');
+        let frames = self.frame_times.iter().zip(&self.statistics_frames);
+        for (frame, (time, statistics_frame)) in frames.enumerate() {
+            write!(output, "{},{:.4}", frame + 1, time.as_secs_f64() * 1000.0)?;
+            for times in self.section_times.values() {
+                output.push(',');
+                if let Some(time) = times.get(statistics_frame) {
+                    write!(output, "{:.4}", time.as_secs_f64() * 1000.0)?;
+                }
+            }
+            output.push('\n');
+        }
+        writeln!(output, "# {summary}")?;
+        std::fs::write(&self.config.output, output)?;
+        info!("Benchmark results written to {:?}", self.config.output);
+        Ok(())
+    }
+
+    fn summary(&self) -> String {
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        let mut sorted = self.frame_times.clone();
+        sorted.sort();
+        if sorted.is_empty() {
+            return "no frames recorded".to_owned();
+        }
+        let average = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+        let p99 = sorted[((sorted.len() - 1) as f64 * 0.99) as usize];
+        let worst = *sorted.last().unwrap();
+        let gpu_times = self
+            .section_times
+            .get("all_render")
+            .map(|times| times.values().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let gpu_average = match gpu_times.is_empty() {
+            true => Duration::ZERO,
+            false => gpu_times.iter().sum::<Duration>() / gpu_times.len() as u32,
+        };
+        format!(
+            "frames: {}, avg: {:.3} ms ({:.1} fps), p99: {:.3} ms, min fps: {:.1}, avg gpu: {:.3} ms",
+            sorted.len(),
+            ms(average),
+            1.0 / average.as_secs_f64(),
+            ms(p99),
+            1.0 / worst.as_secs_f64(),
+            ms(gpu_average)
+        )
+    }
+}
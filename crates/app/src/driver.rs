@@ -1,25 +1,34 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use assets::storage::AssetStorage;
-use assets::TerrainLoadInfo;
+use assets::{TerrainOptions, TerrainSource};
 use derivative::Derivative;
+use error::publish_warn;
 use events::Tick;
 use futures::executor::block_on;
+use gfx::Presentation;
 use glam::Vec3;
+use gui::editor::prefs::{EditorPrefs, EDITOR_PREFS_FILE};
+use hot_reload::ShaderCompilerConfig;
 use inject::DI;
 use input::{
     ButtonState, InputEvent, InputState, Key, KeyState, MouseButtonState, MouseDelta,
     MousePosition, ScrollInfo,
 };
+use log::{error, info};
 use math::{Position, Rotation};
+use pass::GpuWork;
 use phobos::PipelineStage;
-use scheduler::EventBus;
+use scheduler::{EventBus, Plugin};
 use statistics::RendererStatistics;
-use winit::event::{ElementState, Event, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use winit::event::{Event, MouseScrollDelta, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::Window;
-use world::World;
+use world::{AntiAliasing, World};
 
+use crate::benchmark::{Benchmark, BenchmarkConfig};
+use crate::launch::LaunchOptions;
 use crate::renderer::AppRenderer;
+use crate::script::{ScriptConfig, ScriptRunner};
 use crate::window::AppWindow;
 
 /// Main application driver. Holds core modules such as the renderer,
@@ -30,17 +39,67 @@ pub struct Driver {
     pub bus: EventBus<DI>,
     renderer: AppRenderer,
     window: AppWindow,
+    benchmark: Option<Benchmark>,
+    script: Option<ScriptRunner>,
+}
+
+/// Terrain that is opened if no other terrain was opened before.
+fn default_terrain(options: TerrainOptions) -> TerrainSource {
+    TerrainSource {
+        height_path: "data/heightmaps/mountain.png".into(),
+        texture_path: "data/textures/blank.png".into(),
+        resize_texture: false,
+        detail_path: None,
+        options,
+    }
+}
+
+/// Returns the terrain that was opened last, or the default terrain if it cannot be opened.
+fn startup_terrain(bus: &EventBus<DI>, options: TerrainOptions) -> TerrainSource {
+    let prefs = EditorPrefs::load_or_default(EDITOR_PREFS_FILE).unwrap_or_else(|e| {
+        error!("Could not load editor preferences: {e}");
+        EditorPrefs::default()
+    });
+    let Some(last) = prefs.recent_terrains.into_iter().next() else {
+        return default_terrain(options);
+    };
+    match last.missing_file() {
+        None => last,
+        Some(path) => {
+            let path = path.display();
+            publish_warn!(
+                bus,
+                source = "terrain",
+                "Could not find {path} of the last opened terrain, opening the default terrain instead."
+            );
+            default_terrain(options)
+        }
+    }
 }
 
 impl Driver {
     /// Initialize the application driver with a window and event loop.
-    pub fn init(event_loop: &EventLoop<()>, window: Window) -> Result<Driver> {
+    /// If a benchmark configuration is given, the driver runs the benchmark and exits when it completes.
+    /// If a script is given, the driver runs its commands while rendering frames.
+    /// Plugins are initialized after the core systems, see [`Plugin`].
+    pub fn init(
+        event_loop: &EventLoop<()>,
+        window: Window,
+        launch: LaunchOptions,
+        benchmark: Option<BenchmarkConfig>,
+        script: Option<ScriptConfig>,
+        plugins: Vec<Box<dyn Plugin<DI>>>,
+    ) -> Result<Driver> {
+        if launch.safe {
+            info!("Starting in safe mode");
+        }
+
         // Create event bus and dependency injection module.
         let inject = DI::new();
         let mut bus = EventBus::new(inject.clone());
 
         // Initialize subsystems
-        let (frame, surface, ctx) = gfx::initialize(&window, &bus)?;
+        let (frame, surface, ctx) = gfx::initialize(&window, launch.validation, &bus)?;
         input::initialize(&mut bus);
         camera::initialize(
             Position(Vec3::new(0.0, 200.0, 0.0)),
@@ -50,7 +109,16 @@ impl Driver {
         )?;
 
         world::initialize(&bus)?;
-        hot_reload::initialize(ctx.pipelines.clone(), "shaders/", true, &mut bus)?;
+        hot_reload::initialize(
+            ctx.pipelines.clone(),
+            "shaders/",
+            true,
+            launch.watch_shaders,
+            launch.shader_debounce_ms,
+            launch.shader_reflection,
+            ShaderCompilerConfig::default(),
+            &mut bus,
+        )?;
         assets::initialize(bus.clone())?;
 
         let renderer = AppRenderer::new(ctx.clone(), &window, event_loop, bus.clone())?;
@@ -59,6 +127,7 @@ impl Driver {
         pass::initialize(&bus);
         time::initialize(&bus)?;
         brush::initialize(&bus)?;
+        statistics::initialize(&bus);
 
         {
             let mut inject = inject.write().unwrap();
@@ -66,29 +135,68 @@ impl Driver {
             inject.put_sync::<RendererStatistics>(statistics);
         }
 
+        for mut plugin in plugins {
+            info!("Initializing plugin {:?}", plugin.name());
+            plugin
+                .initialize(&mut bus)
+                .with_context(|| format!("Could not initialize plugin {:?}", plugin.name()))?;
+        }
+
         {
             let inject = inject.read().unwrap();
             let mut world = inject.write_sync::<World>().unwrap();
+            if !launch.upscaling {
+                world.options.anti_aliasing = AntiAliasing::None;
+            }
             let assets = inject.get::<AssetStorage>().unwrap();
-            world.terrain = Some(assets.load(TerrainLoadInfo::FromHeightmap {
-                height_path: "data/heightmaps/mountain.png".into(),
-                texture_path: "data/textures/blank.png".into(),
-                options: world.terrain_options,
-            }));
+            let terrain = startup_terrain(&bus, world.terrain_options);
+            world.terrain_options = terrain.options;
+            world.terrain = Some(assets.load(terrain.load_info()));
+            world.terrain_source = Some(terrain);
         }
 
         // Create an initial submit batch for the first frame
         let _ = renderer.new_submit_batch();
 
+        let benchmark = benchmark.map(Benchmark::new).transpose()?;
+        let script = script.map(ScriptRunner::new).transpose()?;
+
         Ok(Driver {
             bus,
             renderer,
             window,
+            benchmark,
+            script,
         })
     }
 
+    /// Recreate the swapchain if a different present mode was requested.
+    /// # DI Access
+    /// - Write [`Presentation`]
+    fn apply_present_mode(&mut self) -> Result<()> {
+        let (request, current) = {
+            let inject = self.bus.data().read().unwrap();
+            let mut presentation = inject.write_sync::<Presentation>().unwrap();
+            (presentation.request.take(), presentation.current)
+        };
+        let Some(mode) = request else { return Ok(()) };
+        let applied = self.window.set_present_mode(mode, current)?;
+        {
+            let inject = self.bus.data().read().unwrap();
+            inject.write_sync::<Presentation>().unwrap().current = applied;
+        }
+        if applied == mode {
+            info!("Switched present mode to {mode:?}");
+        } else {
+            let bus = &self.bus;
+            publish_warn!(bus, "Could not switch present mode to {mode:?}, keeping {applied:?}.");
+        }
+        Ok(())
+    }
+
     /// Process one frame. This will update the UI and render the world.
     async fn process_frame(&mut self) -> Result<()> {
+        self.apply_present_mode()?;
         self.window.request_redraw();
         self.window
             .new_frame(|window, mut ifc| {
@@ -100,9 +208,20 @@ impl Driver {
                         .write_sync::<RendererStatistics>()
                         .unwrap()
                         .new_frame();
+                    inject.write_sync::<InputState>().unwrap().new_frame();
+                    inject.get::<AssetStorage>().unwrap().next_frame();
                 }
 
+                if let Some(benchmark) = &mut self.benchmark {
+                    benchmark.before_frame(&self.bus);
+                }
+                if let Some(script) = &mut self.script {
+                    script.before_frame(&self.bus)?;
+                }
+
+                world::fit_terrain_options(&self.bus);
                 self.bus.publish(Tick)?;
+                world::update_terrain_bounds(&self.bus)?;
 
                 let inject = self.bus.data().read().unwrap();
                 let world = inject.read_sync::<World>().unwrap();
@@ -116,6 +235,16 @@ impl Driver {
         Ok(())
     }
 
+    /// Submit all queued GPU work and wait for it to complete before the application exits.
+    fn shutdown(&self) -> Result<()> {
+        let drained = GpuWork::drain(&self.bus)?;
+        if drained > 0 {
+            info!("Submitted {drained} queued GPU submissions before exiting");
+        }
+        self.renderer.gfx().device.wait_idle()?;
+        Ok(())
+    }
+
     /// Process a winit event. This forwards events to the input and UI systems, as well as
     /// renders a frame when a redraw is requested.
     pub fn process_event(&mut self, event: Event<()>) -> Result<ControlFlow> {
@@ -130,7 +259,7 @@ impl Driver {
                     WindowEvent::Moved(_) => {}
                     WindowEvent::CloseRequested => {
                         if window_id == self.window.id() {
-                            self.renderer.gfx().device.wait_idle()?;
+                            self.shutdown()?;
                             return Ok(ControlFlow::Exit);
                         }
                     }
@@ -143,34 +272,30 @@ impl Driver {
                     WindowEvent::KeyboardInput {
                         input,
                         ..
-                    } => match input.virtual_keycode {
-                        None => {}
-                        Some(VirtualKeyCode::Escape) => match input.state {
-                            ElementState::Pressed => {
-                                self.bus.publish(InputEvent::Button(KeyState {
-                                    state: ButtonState::Pressed,
-                                    button: Key::Escape,
-                                }))?;
-                            }
-                            ElementState::Released => {
-                                self.bus.publish(InputEvent::Button(KeyState {
-                                    state: ButtonState::Released,
-                                    button: Key::Escape,
-                                }))?;
-                            }
-                        },
-                        Some(_) => {}
-                    },
-                    WindowEvent::ModifiersChanged(state) => {
-                        if state.shift() {
+                    } => {
+                        // Modifiers are published from ModifiersChanged instead
+                        let key = input.virtual_keycode.map(Key::from);
+                        if let Some(key) = key.filter(|key| !key.is_modifier()) {
                             self.bus.publish(InputEvent::Button(KeyState {
-                                state: ButtonState::Pressed,
-                                button: Key::Shift,
+                                state: input.state.into(),
+                                button: key,
                             }))?;
-                        } else {
+                        }
+                    }
+                    WindowEvent::ModifiersChanged(state) => {
+                        let modifiers = [
+                            (Key::Shift, state.shift()),
+                            (Key::Control, state.ctrl()),
+                            (Key::Alt, state.alt()),
+                        ];
+                        for (key, pressed) in modifiers {
                             self.bus.publish(InputEvent::Button(KeyState {
-                                state: ButtonState::Released,
-                                button: Key::Shift,
+                                state: if pressed {
+                                    ButtonState::Pressed
+                                } else {
+                                    ButtonState::Released
+                                },
+                                button: key,
                             }))?;
                         }
                     }
@@ -227,10 +352,28 @@ impl Driver {
                         button,
                         ..
                     } => {
+                        let state = state.into();
+                        let button = button.into();
                         self.bus.publish(InputEvent::MouseButton(MouseButtonState {
-                            state: state.into(),
-                            button: button.into(),
+                            state,
+                            button,
                         }))?;
+                        // The input system counts the click, it cannot publish events itself
+                        // while it handles one.
+                        if state == ButtonState::Pressed {
+                            let count = self
+                                .bus
+                                .data()
+                                .read()
+                                .unwrap()
+                                .read_sync::<InputState>()
+                                .unwrap()
+                                .last_click_count(button);
+                            self.bus.publish(InputEvent::MouseClick {
+                                button,
+                                count,
+                            })?;
+                        }
                     }
                     WindowEvent::TouchpadMagnify {
                         ..
@@ -261,6 +404,23 @@ impl Driver {
             Event::RedrawRequested(_) => {
                 // TODO: Multi-window
                 block_on(self.process_frame())?;
+                if let Some(benchmark) = &mut self.benchmark {
+                    if benchmark.after_frame(&self.bus) {
+                        benchmark.finish()?;
+                        self.shutdown()?;
+                        return Ok(ControlFlow::Exit);
+                    }
+                }
+                if let Some(script) = &mut self.script {
+                    if script.after_frame(&self.bus) {
+                        info!("Script finished");
+                        if script.exit_when_finished() {
+                            self.shutdown()?;
+                            return Ok(ControlFlow::Exit);
+                        }
+                        self.script = None;
+                    }
+                }
             }
             _ => (),
         };
@@ -0,0 +1,53 @@
+/// Options that control which subsystems are enabled at startup.
+/// Passing `--safe` starts the application in the most minimal configuration, which helps
+/// isolate whether a subsystem is at fault when diagnosing crashes.
+#[derive(Debug, Copy, Clone)]
+pub struct LaunchOptions {
+    /// Set if the application was started with `--safe`.
+    pub safe: bool,
+    /// Enable the Vulkan validation layers. Enabled by default in debug builds.
+    pub validation: bool,
+    /// Watch the shader directory and reload shaders when they change on disk.
+    pub watch_shaders: bool,
+    /// Time in milliseconds a shader file must go without changes before it is reloaded.
+    pub shader_debounce_ms: u64,
+    /// Compile shaders with extra SPIR-V reflection info. Enabled by default in debug builds.
+    pub shader_reflection: bool,
+    /// Upscale the scene with FSR2. If disabled, the scene is rendered at native resolution.
+    pub upscaling: bool,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            safe: false,
+            validation: cfg!(debug_assertions),
+            watch_shaders: true,
+            shader_debounce_ms: 150,
+            shader_reflection: cfg!(debug_assertions),
+            upscaling: true,
+        }
+    }
+}
+
+impl LaunchOptions {
+    /// Safe mode disables validation, shader hot-reload, shader reflection info and upscaling.
+    pub fn safe() -> Self {
+        Self {
+            safe: true,
+            validation: false,
+            watch_shaders: false,
+            shader_debounce_ms: 150,
+            shader_reflection: false,
+            upscaling: false,
+        }
+    }
+
+    /// Parse launch options from command line arguments. Unknown arguments are ignored.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        match args.into_iter().any(|arg| arg == "--safe") {
+            true => Self::safe(),
+            false => Self::default(),
+        }
+    }
+}
@@ -10,10 +10,17 @@ use error::publish_error;
 use log::error;
 use winit::event_loop::ControlFlow;
 
+use crate::benchmark::BenchmarkConfig;
 use crate::driver::Driver;
+use crate::launch::LaunchOptions;
+use crate::script::ScriptConfig;
+use crate::window::WindowConfig;
 
+mod benchmark;
 mod driver;
+mod launch;
 mod renderer;
+mod script;
 mod window;
 
 fn main() -> Result<!> {
@@ -29,10 +36,14 @@ fn main() -> Result<!> {
         .build()?;
     let _guard = runtime.enter();
 
+    let benchmark = BenchmarkConfig::from_args(std::env::args().skip(1))?;
+    let launch = LaunchOptions::from_args(std::env::args().skip(1));
+    let script = ScriptConfig::from_args(std::env::args().skip(1))?;
+
     // Create window
-    let (event_loop, window) = window::create_window()?;
+    let (event_loop, window) = window::create_window(&WindowConfig::default())?;
     // Create application driver
-    let mut driver = Some(Driver::init(&event_loop, window)?);
+    let mut driver = Some(Driver::init(&event_loop, window, launch, benchmark, script, vec![])?);
 
     // Run the app driver on the event loop
     event_loop.run(move |event, _, control_flow| {
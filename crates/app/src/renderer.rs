@@ -2,18 +2,22 @@ use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use brush::brushes::color::{linear_to_srgb, srgb_to_linear};
+use error::{publish_error, publish_info, publish_success};
 use gfx::SharedContext;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
 use inject::DI;
 use layout::backends::svg::SVGWriter;
 use layout::gv;
 use layout::gv::GraphBuilder;
-use pass::GpuWork;
+use pass::{FrameCapture, FrameGraph, FrameGraphDump, GpuWork, RenderToFileEvent};
 use phobos::domain::All;
+use phobos::graph::pass_graph::BuiltPassGraph;
 use phobos::sync::submit_batch::SubmitBatch;
 use phobos::{
-    CommandBuffer, GraphViz, InFlightContext, IncompleteCmdBuffer, PassBuilder,
-    RecordGraphToCommandBuffer,
+    vk, CommandBuffer, DefaultAllocator, GraphViz, InFlightContext, IncompleteCmdBuffer,
+    PassBuilder, RecordGraphToCommandBuffer,
 };
 use renderer::ui_integration::UIIntegration;
 use renderer::world_renderer::WorldRenderer;
@@ -22,7 +26,11 @@ use statistics::{RendererStatistics, TimedCommandBuffer};
 use winit::event::WindowEvent;
 use winit::event_loop::EventLoop;
 use winit::window::Window;
-use world::World;
+use world::{AntiAliasing, World};
+
+/// Number of frames rendered for a capture with jittered anti-aliasing, see
+/// [`AppRenderer::render_offscreen`].
+const CAPTURE_FRAMES: u32 = 8;
 
 /// Stores the graphics and context, as well as the world and GUI renderers.
 #[derive(Debug)]
@@ -58,6 +66,30 @@ where
     }
 }
 
+/// Average each block of `factor` by `factor` pixels of a supersampled capture into one pixel.
+/// The color channels are sRGB encoded, so they are averaged in linear space.
+fn downsample(image: &RgbaImage, factor: u32) -> RgbaImage {
+    let count = (factor * factor) as f32;
+    RgbaImage::from_fn(image.width() / factor, image.height() / factor, |x, y| {
+        let mut sum = [0.0; 4];
+        for dy in 0..factor {
+            for dx in 0..factor {
+                let pixel = image.get_pixel(x * factor + dx, y * factor + dy);
+                for (sum, value) in sum.iter_mut().zip(pixel.0).take(3) {
+                    *sum += srgb_to_linear(value);
+                }
+                sum[3] += pixel[3] as f32 / 255.0;
+            }
+        }
+        Rgba([
+            linear_to_srgb(sum[0] / count),
+            linear_to_srgb(sum[1] / count),
+            linear_to_srgb(sum[2] / count),
+            (sum[3] / count * 255.0).round() as u8,
+        ])
+    })
+}
+
 impl AppRenderer {
     /// Initialize the application rendering system with an existing graphics context.
     pub fn new(
@@ -107,6 +139,134 @@ impl AppRenderer {
         old_batch.ok_or_else(|| anyhow!("No previous submit batch set"))
     }
 
+    /// Builds the frame graph. If a dump was requested with
+    /// [`DumpFrameGraphEvent`](pass::DumpFrameGraphEvent), the graph is also written to the
+    /// requested file. Writing and reporting the result is done on a separate thread, since
+    /// the frame holds locks that the message handlers may need.
+    /// # DI Access
+    /// - Write [`FrameGraphDump`]
+    fn build_graph<'cb>(
+        &self,
+        graph: FrameGraph<'cb>,
+    ) -> Result<BuiltPassGraph<'cb, All, RendererStatistics, DefaultAllocator>> {
+        let path = {
+            let di = self.bus.data().read().unwrap();
+            let mut dump = di.write_sync::<FrameGraphDump>().unwrap();
+            dump.path.take()
+        };
+        let Some(path) = path else { return graph.build() };
+        let (graph, dot) = graph.build_with_dot()?;
+        let bus = self.bus.clone();
+        tokio::task::spawn_blocking(move || match std::fs::write(&path, dot) {
+            Ok(_) => {
+                publish_info!(bus, "Wrote frame graph to {}", path.display());
+            }
+            Err(e) => {
+                publish_error!(bus, "Could not write frame graph to {}: {e}", path.display());
+            }
+        });
+        Ok(graph)
+    }
+
+    /// Renders the scene at the resolution requested with
+    /// [`RenderToFileEvent`](pass::RenderToFileEvent) and writes it to a PNG file. The UI is not
+    /// rendered into the image. Like the frame graph dump, writing and reporting the result is
+    /// done on a separate thread.
+    /// # DI Access
+    /// - Write [`FrameCapture`]
+    fn render_to_file(&mut self, world: &World, ifc: &mut InFlightContext) {
+        let request = {
+            let di = self.bus.data().read().unwrap();
+            let mut capture = di.write_sync::<FrameCapture>().unwrap();
+            capture.request.take()
+        };
+        let Some(request) = request else { return };
+        let image = self.capture(world, ifc, &request);
+        let bus = self.bus.clone();
+        tokio::task::spawn_blocking(move || {
+            let path = request.path;
+            let result = image.and_then(|image| {
+                DynamicImage::ImageRgba8(image)
+                    .into_rgb8()
+                    .save_with_format(&path, ImageFormat::Png)
+                    .map_err(Into::into)
+            });
+            match result {
+                Ok(_) => {
+                    publish_success!(bus, "Saved render to {}", path.display());
+                }
+                Err(e) => {
+                    publish_error!(bus, "Could not render to {}: {e}", path.display());
+                }
+            }
+        });
+    }
+
+    /// Render a single frame at the requested resolution without the UI, and read back the
+    /// result. Supersampled captures are rendered without FSR2 or TAA at a multiple of the
+    /// resolution and downsampled. The output resolution is restored afterwards. FSR2 is not
+    /// reconfigured for a supersampled capture, so it keeps its history.
+    fn capture(
+        &mut self,
+        world: &World,
+        ifc: &mut InFlightContext,
+        request: &RenderToFileEvent,
+    ) -> Result<RgbaImage> {
+        let factor = request.supersample.max(1);
+        let max = self.gfx.device.properties().limits.max_image_dimension2_d;
+        // The rendered resolution includes the supersampling factor
+        let width = request.width.saturating_mul(factor);
+        let height = request.height.saturating_mul(factor);
+        if width == 0 || height == 0 || width > max || height > max {
+            bail!("Resolution {width}x{height} is not supported, the maximum is {max}x{max}");
+        }
+        let previous = self.renderer.output_resolution();
+        if factor > 1 {
+            self.renderer
+                .set_anti_aliasing_override(Some(AntiAliasing::None));
+        }
+        let image = self
+            .renderer
+            .set_output_resolution(world, width, height)
+            .and_then(|_| self.render_offscreen(world, ifc));
+        self.renderer.set_anti_aliasing_override(None);
+        self.renderer
+            .set_output_resolution(world, previous.width, previous.height)?;
+        Ok(downsample(&image?, factor))
+    }
+
+    /// Render the world without the UI, wait for it to complete and read back the output.
+    /// Changing the output resolution resets the history of FSR2 and TAA, so with jittered
+    /// anti-aliasing the frame is rendered [`CAPTURE_FRAMES`] times to let the history
+    /// converge before it is read back.
+    fn render_offscreen(&mut self, world: &World, ifc: &mut InFlightContext) -> Result<RgbaImage> {
+        let frames = match self.renderer.anti_aliasing(world).jittered() {
+            true => CAPTURE_FRAMES,
+            false => 1,
+        };
+        for _ in 0..frames {
+            let (graph, bindings) = self.renderer.redraw_world(world)?;
+            let mut graph = graph.build()?;
+            let cmd = self.gfx.exec.on_domain::<All, _>(
+                Some(self.gfx.pipelines.clone()),
+                Some(self.gfx.descriptors.clone()),
+            )?;
+            // Timings are not measured for the capture, so it does not use up the queries of
+            // the frame.
+            let mut statistics = RendererStatistics::new(self.gfx.clone(), 1, 1)?;
+            let cmd = graph
+                .record(cmd, &bindings, ifc, self.gfx.debug_messenger.clone(), &mut statistics)?
+                .finish()?;
+            self.gfx.exec.submit(cmd)?.wait()?;
+        }
+        // Nothing samples the output after it is rendered, so it is still a color attachment.
+        let output = self
+            .renderer
+            .read_output(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)?;
+        RgbaImage::from_raw(output.width, output.height, output.data)
+            .ok_or_else(|| anyhow!("Unexpected output format {:?}", output.format))
+    }
+
     /// Render a single frame to the window. This will render both the UI and the scene.
     /// Returns a command buffer that must be passed to phobos as this frame's command buffer.
     pub fn render(
@@ -116,7 +276,8 @@ impl AppRenderer {
         bus: &EventBus<DI>,
         ifc: &mut InFlightContext,
     ) -> Result<CommandBuffer<All>> {
-        self.renderer.update_output_image(&mut self.ui)?;
+        self.render_to_file(world, ifc);
+        self.renderer.update_output_image(world, &mut self.ui)?;
         let (mut graph, mut bindings) = self.renderer.redraw_world(world)?;
         let swapchain = graph.swapchain_resource();
         // Record UI commands
@@ -124,7 +285,7 @@ impl AppRenderer {
         // Add a present pass to the graph.
         let present_pass = PassBuilder::present("present", &graph.latest_version(&swapchain)?);
         graph.add_pass(present_pass);
-        let mut graph = graph.build()?;
+        let mut graph = self.build_graph(graph)?;
 
         // Bind the swapchain resource.
         bindings.bind_image("swapchain", ifc.swapchain_image.as_ref().unwrap());
@@ -144,3 +305,28 @@ impl AppRenderer {
         cmd.end_section(&mut statistics, "all_render")?.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsampling_averages_blocks_in_linear_space() {
+        let image = RgbaImage::from_fn(4, 2, |x, _| match x % 2 {
+            0 => Rgba([0, 0, 0, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        });
+        let downsampled = downsample(&image, 2);
+        assert_eq!(downsampled.dimensions(), (2, 1));
+        // Half white in linear space is lighter than half of the sRGB value
+        let expected = linear_to_srgb(0.5);
+        assert_eq!(downsampled.get_pixel(0, 0), &Rgba([expected, expected, expected, 255]));
+        assert_eq!(downsampled.get_pixel(1, 0), downsampled.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn downsampling_by_one_keeps_the_image() {
+        let image = RgbaImage::from_fn(3, 3, |x, y| Rgba([x as u8 * 80, y as u8 * 80, 10, 200]));
+        assert_eq!(downsample(&image, 1), image);
+    }
+}
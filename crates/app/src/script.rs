@@ -0,0 +1,418 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use assets::storage::AssetStorage;
+use assets::{TerrainLoadInfo, TerrainOptions, TerrainSource};
+use brush::{
+    BeginStrokeEvent, BrushSettings, BrushType, EndStrokeEvent, FlattenTerrainEvent, RedoEvent,
+    StrokeAtEvent, UndoEvent,
+};
+use camera::{CameraPose, CameraState, CameraTransitionEvent};
+use glam::Vec3;
+use inject::DI;
+use log::info;
+use math::Rotation;
+use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+use time::TimeControl;
+use world::World;
+
+/// Command line configuration for script mode.
+/// Script mode is enabled with `--script path.json`.
+#[derive(Debug, Clone)]
+pub struct ScriptConfig {
+    pub path: PathBuf,
+}
+
+impl ScriptConfig {
+    /// Parse the script configuration from command line arguments. Returns `None` if
+    /// script mode was not requested.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Option<Self>> {
+        let mut path = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--script" {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing value for argument {arg}"))?;
+                path = Some(PathBuf::from(value));
+            }
+        }
+        Ok(path.map(|path| Self {
+            path,
+        }))
+    }
+}
+
+/// A single action a script can take.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ScriptCommand {
+    /// Open a terrain from files, replacing the current terrain.
+    LoadTerrain(TerrainSource),
+    /// Change the options of the current terrain. This regenerates the terrain mesh.
+    SetTerrainOptions(TerrainOptions),
+    /// Set the direction of the sun, as pitch, yaw and roll in radians.
+    SetSunDirection(Vec3),
+    /// Move the camera. With a duration in seconds the camera moves there smoothly, note that
+    /// camera transitions run in wall clock time and are not deterministic.
+    SetCamera {
+        pose: CameraPose,
+        #[serde(default)]
+        duration: Option<f32>,
+    },
+    BeginStroke {
+        brush: BrushType,
+        settings: BrushSettings,
+    },
+    /// Apply the active brush stroke at a world position.
+    StrokeAt(Vec3),
+    EndStroke,
+    /// Set the entire base heightmap to a single height, see [`FlattenTerrainEvent`].
+    Flatten(f32),
+    Undo,
+    Redo,
+}
+
+/// A command that runs at the start of a frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledCommand {
+    /// Index of the frame to run the command on, the first frame is frame 0.
+    pub frame: u32,
+    pub command: ScriptCommand,
+}
+
+/// A sequence of commands to drive the application with, for recording demos and for
+/// integration tests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Script {
+    /// Time advances by exactly `1 / frame_rate` seconds every frame while the script runs, so
+    /// animation does not depend on the actual frame rate.
+    #[serde(default = "Script::default_frame_rate")]
+    pub frame_rate: u32,
+    /// Exit the application after the frame of the last command.
+    #[serde(default)]
+    pub exit: bool,
+    pub commands: Vec<ScheduledCommand>,
+}
+
+impl Script {
+    fn default_frame_rate() -> u32 {
+        60
+    }
+
+    /// Time every frame advances by.
+    pub fn frame_time(&self) -> Duration {
+        Duration::from_secs(1) / self.frame_rate
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Self::from_reader(std::io::BufReader::new(file))
+            .map_err(|e| anyhow!("Could not load script {}: {e}", path.display()))
+    }
+
+    /// Parse a script from JSON, and sort its commands by frame.
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self> {
+        let mut script: Self = serde_json::from_reader(reader)?;
+        if script.frame_rate == 0 {
+            bail!("the frame rate is zero");
+        }
+        // Commands on the same frame keep the order they were listed in.
+        script.commands.sort_by_key(|command| command.frame);
+        Ok(script)
+    }
+}
+
+/// Runs a script by publishing the events for its commands at the start of each frame.
+/// Time is paused while the script runs and advanced by a fixed step every frame.
+#[derive(Debug)]
+pub struct ScriptRunner {
+    script: Script,
+    /// Index of the next command to run.
+    next: usize,
+    frame: u32,
+    /// Time of the first frame. Brush strokes are timed from here in fixed steps, like
+    /// everything else driven by [`TimeControl`].
+    start: Instant,
+}
+
+impl ScriptRunner {
+    pub fn new(config: ScriptConfig) -> Result<Self> {
+        let script = Script::load(&config.path)?;
+        info!("Running script {:?} with {} commands", config.path, script.commands.len());
+        Ok(Self::from_script(script))
+    }
+
+    fn from_script(script: Script) -> Self {
+        Self {
+            script,
+            next: 0,
+            frame: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// Time of the current frame, advancing by exactly one frame time every frame.
+    fn frame_instant(&self) -> Instant {
+        self.start + self.script.frame_time() * self.frame
+    }
+
+    /// Step time and run all commands scheduled for the current frame.
+    /// # DI Access
+    /// - Write [`TimeControl`]
+    /// - Write [`World`]
+    /// - Write [`CameraState`]
+    /// - Read [`AssetStorage`]
+    pub fn before_frame(&mut self, bus: &EventBus<DI>) -> Result<()> {
+        {
+            let di = bus.data().read().unwrap();
+            let mut control = di.write_sync::<TimeControl>().unwrap();
+            control.paused = true;
+            control.step = Some(self.script.frame_time());
+        }
+        while let Some(scheduled) = self.script.commands.get(self.next) {
+            if scheduled.frame > self.frame {
+                break;
+            }
+            let command = scheduled.command.clone();
+            self.next += 1;
+            run_command(bus, command, self.frame_instant())?;
+        }
+        Ok(())
+    }
+
+    /// Returns true once every command has run. Time runs normally again after this.
+    /// # DI Access
+    /// - Write [`TimeControl`]
+    pub fn after_frame(&mut self, bus: &EventBus<DI>) -> bool {
+        self.frame += 1;
+        let finished = self.next >= self.script.commands.len();
+        if finished {
+            let di = bus.data().read().unwrap();
+            let mut control = di.write_sync::<TimeControl>().unwrap();
+            control.paused = false;
+            control.step = None;
+        }
+        finished
+    }
+
+    /// Whether the application should exit once the script is finished.
+    pub fn exit_when_finished(&self) -> bool {
+        self.script.exit
+    }
+}
+
+/// Run a command of a frame that started at `time`.
+/// # DI Access
+/// - Write [`World`]
+/// - Write [`CameraState`]
+/// - Read [`AssetStorage`]
+fn run_command(bus: &EventBus<DI>, command: ScriptCommand, time: Instant) -> Result<()> {
+    match command {
+        ScriptCommand::LoadTerrain(source) => {
+            let di = bus.data().read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            let mut world = di.write_sync::<World>().unwrap();
+            world.terrain_options = source.options;
+            world.terrain = Some(assets.load(source.load_info()));
+            world.terrain_source = Some(source);
+        }
+        ScriptCommand::SetTerrainOptions(options) => {
+            let di = bus.data().read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            let mut world = di.write_sync::<World>().unwrap();
+            world.terrain_options = options;
+            if let Some(source) = &mut world.terrain_source {
+                source.options = options;
+            }
+            if let Some(old) = world.terrain.take() {
+                world.terrain = Some(assets.load(TerrainLoadInfo::FromNewMesh {
+                    old,
+                    options,
+                }));
+            }
+        }
+        ScriptCommand::SetSunDirection(rotation) => {
+            let di = bus.data().read().unwrap();
+            di.write_sync::<World>().unwrap().sun_direction = Rotation(rotation);
+        }
+        ScriptCommand::SetCamera {
+            pose,
+            duration: None,
+        } => {
+            let di = bus.data().read().unwrap();
+            di.write_sync::<CameraState>().unwrap().set_pose(pose);
+        }
+        ScriptCommand::SetCamera {
+            pose,
+            duration: Some(duration),
+        } => bus.publish(CameraTransitionEvent {
+            target: pose,
+            duration: Duration::from_secs_f32(duration),
+        })?,
+        ScriptCommand::BeginStroke {
+            brush,
+            settings,
+        } => bus.publish(BeginStrokeEvent {
+            settings,
+            brush,
+            time: Some(time),
+        })?,
+        ScriptCommand::StrokeAt(position) => bus.publish(StrokeAtEvent {
+            position,
+            time: Some(time),
+        })?,
+        ScriptCommand::EndStroke => bus.publish(EndStrokeEvent)?,
+        ScriptCommand::Flatten(height) => bus.publish(FlattenTerrainEvent {
+            height,
+        })?,
+        ScriptCommand::Undo => bus.publish(UndoEvent)?,
+        ScriptCommand::Redo => bus.publish(RedoEvent)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use scheduler::{EventContext, StoredSystem, System, TestBus};
+
+    use super::*;
+
+    const SCRIPT: &str = r#"{
+        "frame_rate": 30,
+        "commands": [
+            { "frame": 2, "command": "Undo" },
+            { "frame": 0, "command": { "SetSunDirection": [0.5, 0.0, 0.0] } },
+            { "frame": 2, "command": "Redo" },
+            { "frame": 1, "command": { "StrokeAt": [1.0, 2.0, 3.0] } }
+        ]
+    }"#;
+
+    /// Records the brush events a script publishes.
+    #[derive(Default)]
+    struct Recorder {
+        strokes: Vec<(Vec3, Option<Instant>)>,
+        undos: u32,
+    }
+
+    struct RecorderSystem;
+
+    impl System<DI> for RecorderSystem {
+        fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+            event_bus.subscribe(system, handle_stroke_at);
+            event_bus.subscribe(system, handle_undo);
+        }
+    }
+
+    fn handle_stroke_at(
+        _system: &mut RecorderSystem,
+        event: &StrokeAtEvent,
+        ctx: &mut EventContext<DI>,
+    ) -> Result<()> {
+        let di = ctx.read().unwrap();
+        let mut recorder = di.write_sync::<Recorder>().unwrap();
+        recorder.strokes.push((event.position, event.time));
+        Ok(())
+    }
+
+    fn handle_undo(
+        _system: &mut RecorderSystem,
+        _event: &UndoEvent,
+        ctx: &mut EventContext<DI>,
+    ) -> Result<()> {
+        let di = ctx.read().unwrap();
+        di.write_sync::<Recorder>().unwrap().undos += 1;
+        Ok(())
+    }
+
+    fn test_bus() -> TestBus {
+        TestBus::new()
+            .with(World::new())
+            .with(CameraState::default())
+            .with(TimeControl::default())
+            .with(Recorder::default())
+            .with_system(RecorderSystem)
+    }
+
+    #[test]
+    fn scripts_are_sorted_by_frame() {
+        let script = Script::from_reader(SCRIPT.as_bytes()).unwrap();
+        assert_eq!(script.frame_rate, 30);
+        assert!(!script.exit);
+        let frames = script
+            .commands
+            .iter()
+            .map(|command| command.frame)
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec![0, 1, 2, 2]);
+        // Commands on the same frame keep their order
+        assert!(matches!(script.commands[2].command, ScriptCommand::Undo));
+        assert!(matches!(script.commands[3].command, ScriptCommand::Redo));
+    }
+
+    #[test]
+    fn invalid_scripts_are_rejected() {
+        let zero_rate = r#"{ "frame_rate": 0, "commands": [] }"#;
+        assert!(Script::from_reader(zero_rate.as_bytes()).is_err());
+        let unknown_command = r#"{ "commands": [{ "frame": 0, "command": "Explode" }] }"#;
+        assert!(Script::from_reader(unknown_command.as_bytes()).is_err());
+        let defaults = Script::from_reader(r#"{ "commands": [] }"#.as_bytes()).unwrap();
+        assert_eq!(defaults.frame_rate, 60);
+    }
+
+    #[test]
+    fn script_path_is_read_from_arguments() {
+        let args = ["app", "--script", "demo.json"].map(str::to_owned);
+        let config = ScriptConfig::from_args(args).unwrap().unwrap();
+        assert_eq!(config.path, PathBuf::from("demo.json"));
+        let no_script = ScriptConfig::from_args(["app".to_owned()]).unwrap();
+        assert!(no_script.is_none());
+        assert!(ScriptConfig::from_args(["--script".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn commands_run_on_their_frame_with_fixed_time_steps() {
+        let bus = test_bus();
+        let mut runner = ScriptRunner::from_script(Script::from_reader(SCRIPT.as_bytes()).unwrap());
+        let start = runner.start;
+
+        runner.before_frame(&bus).unwrap();
+        let sun = bus.read(|world: &World| world.sun_direction.0);
+        assert_eq!(sun, Vec3::new(0.5, 0.0, 0.0));
+        let step = bus.read(|control: &TimeControl| (control.paused, control.step));
+        assert_eq!(step, (true, Some(Duration::from_secs(1) / 30)));
+        assert!(bus.read(|recorder: &Recorder| recorder.strokes.is_empty()));
+        assert!(!runner.after_frame(&bus));
+
+        runner.before_frame(&bus).unwrap();
+        let strokes = bus.read(|recorder: &Recorder| recorder.strokes.clone());
+        let frame_time = Duration::from_secs(1) / 30;
+        assert_eq!(strokes, vec![(Vec3::new(1.0, 2.0, 3.0), Some(start + frame_time))]);
+        assert!(!runner.after_frame(&bus));
+
+        runner.before_frame(&bus).unwrap();
+        assert_eq!(bus.read(|recorder: &Recorder| recorder.undos), 1);
+        assert!(runner.after_frame(&bus));
+        // Time runs normally again once the script is finished
+        let step = bus.read(|control: &TimeControl| (control.paused, control.step));
+        assert_eq!(step, (false, None));
+    }
+
+    #[test]
+    fn camera_commands_without_duration_move_the_camera() {
+        let bus = test_bus();
+        let pose = CameraPose {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            rotation: Vec3::new(0.1, 0.2, 0.0),
+            fov: 60.0,
+            ortho_height: 250.0,
+        };
+        let command = ScriptCommand::SetCamera {
+            pose,
+            duration: None,
+        };
+        run_command(&bus, command, Instant::now()).unwrap();
+        assert_eq!(bus.read(|camera: &CameraState| camera.pose()), pose);
+    }
+}
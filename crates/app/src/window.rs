@@ -1,17 +1,54 @@
+use std::path::{Path, PathBuf};
+
 use anyhow::Result;
-use gfx::SharedContext;
+use gfx::{PresentMode, SharedContext};
+use log::warn;
 use phobos::domain::ExecutionDomain;
 use phobos::sync::submit_batch::SubmitBatch;
 use phobos::{Allocator, DefaultAllocator, FrameManager, InFlightContext, Surface};
+use winit::dpi::LogicalSize;
 use winit::event_loop::{EventLoop, EventLoopBuilder};
-use winit::window::{Window, WindowBuilder, WindowId};
+use winit::window::{Icon, Window, WindowBuilder, WindowId};
+
+/// Settings used to create the main window.
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    pub title: String,
+    /// PNG image to use as the window icon. If not set, the platform default is used.
+    pub icon: Option<PathBuf>,
+    /// Initial size of the window in logical pixels.
+    pub size: LogicalSize<f64>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Andromeda".to_owned(),
+            icon: None,
+            size: LogicalSize::new(1920.0, 1080.0),
+        }
+    }
+}
+
+fn load_icon(path: &Path) -> Result<Icon> {
+    let image = image::open(path)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+}
 
 /// Create the winit window and event loop.
-pub fn create_window() -> Result<(EventLoop<()>, Window)> {
+pub fn create_window(config: &WindowConfig) -> Result<(EventLoop<()>, Window)> {
     let event_loop = EventLoopBuilder::new().build();
+    // A missing or broken icon is not a reason to not start the application.
+    let icon = config.icon.as_deref().and_then(|path| {
+        load_icon(path)
+            .map_err(|e| warn!("Could not load window icon {}: {e}", path.display()))
+            .ok()
+    });
     let window = WindowBuilder::new()
-        .with_title("Andromeda")
-        .with_inner_size(winit::dpi::LogicalSize::new(1920.0, 1080.0))
+        .with_title(&config.title)
+        .with_window_icon(icon)
+        .with_inner_size(config.size)
         .build(&event_loop)?;
     Ok((event_loop, window))
 }
@@ -20,7 +57,8 @@ pub fn create_window() -> Result<(EventLoop<()>, Window)> {
 /// winit window.
 #[derive(Debug)]
 pub struct AppWindow<A: Allocator = DefaultAllocator> {
-    frame: FrameManager<A>,
+    /// Only empty while the swapchain is recreated, see [`AppWindow::set_present_mode`].
+    frame: Option<FrameManager<A>>,
     window: Window,
     surface: Surface,
     gfx: SharedContext,
@@ -35,7 +73,7 @@ impl<A: Allocator> AppWindow<A> {
         gfx: SharedContext,
     ) -> Self {
         Self {
-            frame,
+            frame: Some(frame),
             window,
             surface,
             gfx,
@@ -50,7 +88,8 @@ impl<A: Allocator> AppWindow<A> {
         &mut self,
         func: F,
     ) -> Result<()> {
-        self.frame
+        let frame = self.frame.as_mut().expect("Frame manager missing");
+        frame
             .new_frame(self.gfx.exec.clone(), &self.window, &self.surface, |ifc| {
                 func(&self.window, ifc)
             })
@@ -67,3 +106,28 @@ impl<A: Allocator> AppWindow<A> {
         self.window.request_redraw();
     }
 }
+
+impl AppWindow {
+    /// Recreate the swapchain with a different present mode. Waits for the device to be idle,
+    /// since the frames in flight are destroyed with the old swapchain. If the swapchain cannot
+    /// be created with the new mode, it is recreated with `previous` instead. Returns the mode
+    /// the swapchain was created with.
+    pub fn set_present_mode(
+        &mut self,
+        present_mode: PresentMode,
+        previous: PresentMode,
+    ) -> Result<PresentMode> {
+        self.gfx.device.wait_idle()?;
+        drop(self.frame.take());
+        let create = |mode| gfx::create_frame_manager(&self.window, &self.surface, &self.gfx, mode);
+        let (frame, mode) = match create(present_mode) {
+            Ok(frame) => (frame, present_mode),
+            Err(e) => {
+                warn!("Could not recreate the swapchain with present mode {present_mode:?}: {e}");
+                (create(previous)?, previous)
+            }
+        };
+        self.frame = Some(frame);
+        Ok(mode)
+    }
+}
@@ -1,11 +1,71 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
 use anyhow::Result;
 use inject::DI;
 use scheduler::EventBus;
 
+/// File an asset was loaded from, see [`Asset::source`].
+pub struct AssetSource<I> {
+    pub path: PathBuf,
+    /// Creates the load info to load the asset from the file again.
+    pub reload_info: Box<dyn Fn() -> I + Send + Sync>,
+}
+
+/// Reports how far along an asset load is, see [`AssetStorage::progress`](crate::storage::AssetStorage::progress).
+/// The default reporter discards all progress.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressReporter {
+    state: Option<Arc<Mutex<(f32, String)>>>,
+}
+
+impl ProgressReporter {
+    /// Create a reporter that stores the last reported progress.
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Some(Arc::new(Mutex::new((0.0, String::new())))),
+        }
+    }
+
+    /// Report the progress of the load as a value between 0.0 and 1.0, together with a label
+    /// describing the current stage.
+    pub fn report(&self, progress: f32, stage: impl Into<String>) {
+        if let Some(state) = &self.state {
+            *state.lock().unwrap() = (progress.clamp(0.0, 1.0), stage.into());
+        }
+    }
+
+    /// Returns the last reported progress and stage, or `None` for the default reporter.
+    pub fn get(&self) -> Option<(f32, String)> {
+        self.state
+            .as_ref()
+            .map(|state| state.lock().unwrap().clone())
+    }
+}
+
 pub trait Asset {
     type LoadInfo: Send + 'static;
 
     fn load(info: Self::LoadInfo, bus: EventBus<DI>) -> Result<Self>
     where
         Self: Sized;
+
+    /// Load the asset while reporting progress. Loaders that take long should implement this,
+    /// by default this calls [`Asset::load`] without reporting anything.
+    fn load_with_progress(
+        info: Self::LoadInfo,
+        bus: EventBus<DI>,
+        _progress: &ProgressReporter,
+    ) -> Result<Self>
+    where
+        Self: Sized, {
+        Self::load(info, bus)
+    }
+
+    /// Returns the file an asset loaded with `info` is read from, if any. Assets with a source
+    /// are loaded again when the file is modified, see
+    /// [`AssetReloadedEvent`](crate::reload::AssetReloadedEvent).
+    fn source(_info: &Self::LoadInfo) -> Option<AssetSource<Self::LoadInfo>> {
+        None
+    }
 }
@@ -6,14 +6,17 @@ use gfx::SharedContext;
 use inject::DI;
 pub use resources::*;
 use scheduler::EventBus;
+pub use units::*;
 
 use crate::storage::AssetStorage;
 
 pub mod asset;
 pub mod handle;
+pub mod reload;
 pub mod resources;
 pub mod storage;
 pub mod texture;
+pub mod units;
 
 pub fn initialize(mut bus: EventBus<DI>) -> Result<()> {
     let gfx = bus
@@ -23,7 +26,11 @@ pub fn initialize(mut bus: EventBus<DI>) -> Result<()> {
         .get::<SharedContext>()
         .cloned()
         .unwrap();
-    NormalMap::init_pipelines(gfx, &mut bus)?;
+    NormalMap::init_pipelines(gfx.clone(), &mut bus)?;
+    Heightmap::init_pipelines(gfx.clone(), &mut bus)?;
+    DetailNormalMap::init_pipelines(gfx.clone(), &mut bus)?;
+    SplatMap::init_pipelines(gfx.clone(), &mut bus)?;
+    DerivedMaps::init_pipelines(gfx, &mut bus)?;
     AssetStorage::new_in_inject(bus);
     Ok(())
 }
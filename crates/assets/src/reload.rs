@@ -0,0 +1,223 @@
+//! Reloading assets when the files they were loaded from are modified.
+
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use hot_reload::file_watcher::async_watch;
+use inject::DI;
+use log::{error, info};
+use scheduler::{Event, EventBus};
+use slotmap::{Key, KeyData};
+
+use crate::asset::{Asset, AssetSource};
+use crate::handle::Handle;
+use crate::storage::AssetStorage;
+
+/// Files are reloaded once they were not modified for this long, since editors often write a
+/// file several times when saving it.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Published after an asset was loaded again because its file was modified. The handle now
+/// refers to the new version of the asset.
+pub struct AssetReloadedEvent<A> {
+    pub handle: Handle<A>,
+}
+
+impl<A: 'static> Event for AssetReloadedEvent<A> {}
+
+/// Starts reloading an asset.
+type ReloadFn = Box<dyn Fn(&EventBus<DI>) + Send + Sync>;
+
+/// Handle of a watched asset with its type erased.
+type AssetKey = (TypeId, KeyData);
+
+fn asset_key<A: 'static>(handle: Handle<A>) -> AssetKey {
+    (TypeId::of::<A>(), handle.data())
+}
+
+/// Keeps track of the files assets were loaded from.
+#[derive(Default)]
+pub(crate) struct AssetWatcher {
+    /// Reload functions of the assets loaded from each file, keyed by canonical path.
+    files: HashMap<PathBuf, Vec<(AssetKey, ReloadFn)>>,
+    /// Directories that are being watched. Files are watched through their directory, so they
+    /// are still picked up when an editor replaces a file instead of writing to it.
+    directories: HashSet<PathBuf>,
+    /// Modification times of files the application wrote itself, keyed by canonical path.
+    /// Modifications with these times do not reload anything.
+    written: HashMap<PathBuf, SystemTime>,
+}
+
+impl AssetWatcher {
+    /// Stop reloading an asset, for example because it was deleted.
+    pub(crate) fn unwatch<A: 'static>(&mut self, handle: Handle<A>) {
+        let key = asset_key(handle);
+        self.files.retain(|_, reloads| {
+            reloads.retain(|(asset, _)| *asset != key);
+            !reloads.is_empty()
+        });
+    }
+
+    /// Returns true if the last modification of `file` was made by the application.
+    fn is_own_write(&self, file: &Path) -> bool {
+        let modified = std::fs::metadata(file).and_then(|metadata| metadata.modified());
+        match (self.written.get(file), modified) {
+            (Some(written), Ok(modified)) => *written == modified,
+            _ => false,
+        }
+    }
+}
+
+/// Returns the canonical directory of a file, and the canonical path of the file inside it.
+/// The file itself does not have to exist.
+fn canonical_file(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let directory = std::fs::canonicalize(directory).ok()?;
+    let file = directory.join(path.file_name()?);
+    Some((directory, file))
+}
+
+impl AssetStorage {
+    /// Load the asset behind `handle` again from `source` every time its file is modified.
+    pub(crate) fn watch_source<A: Asset + Send + 'static>(
+        &self,
+        handle: Handle<A>,
+        source: AssetSource<A::LoadInfo>,
+    ) {
+        let Some((directory, file)) = canonical_file(&source.path) else {
+            error!("Cannot watch {} for changes", source.path.display());
+            return;
+        };
+        let reload_info = source.reload_info;
+        let reload: ReloadFn = Box::new(move |bus| {
+            let info = reload_info();
+            let bus = bus.clone();
+            tokio::task::spawn_blocking(move || Self::asset_reload_task(handle, info, bus));
+        });
+
+        let mut watcher = self.watcher.write().unwrap();
+        watcher
+            .files
+            .entry(file)
+            .or_default()
+            .push((asset_key(handle), reload));
+        if watcher.directories.insert(directory.clone()) {
+            let bus = self.bus.clone();
+            tokio::spawn(async move {
+                let result = async_watch(&directory, false, RELOAD_DEBOUNCE, |path| {
+                    Self::file_modified(&bus, &path)
+                })
+                .await;
+                if let Err(e) = result {
+                    error!("Stopped watching {} for asset changes: {e}", directory.display());
+                }
+            });
+        }
+    }
+
+    /// Tells the watcher that the application itself wrote the file at `path`, so assets loaded
+    /// from it are not reloaded because of this write. Must be called after the file is written.
+    /// Later modifications by other programs still reload the assets.
+    pub fn mark_written(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+        let (Some((_, file)), Ok(modified)) = (canonical_file(path), modified) else {
+            error!("Cannot read the modification time of {}", path.display());
+            return;
+        };
+        self.watcher.write().unwrap().written.insert(file, modified);
+    }
+
+    fn file_modified(bus: &EventBus<DI>, path: &Path) {
+        let di = bus.data().read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        let watcher = assets.watcher.read().unwrap();
+        let Some(reloads) = watcher.files.get(path) else { return };
+        if watcher.is_own_write(path) {
+            return;
+        }
+        info!("Reloading assets loaded from {}", path.display());
+        for (_, reload) in reloads {
+            reload(bus);
+        }
+    }
+
+    fn asset_reload_task<A: Asset + Send + 'static>(
+        handle: Handle<A>,
+        info: A::LoadInfo,
+        bus: EventBus<DI>,
+    ) {
+        // Load the asset first so we don't hold the DI lock for long
+        let result = A::load(info, bus.clone());
+        let replaced = {
+            let di = bus.data().read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            assets.replace_loaded(handle, result)
+        };
+        if replaced {
+            let _ = bus.publish(AssetReloadedEvent {
+                handle,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_watched_through_their_directory() {
+        let (directory, file) = canonical_file(Path::new("does_not_exist.png")).unwrap();
+        assert_eq!(directory, std::env::current_dir().unwrap().canonicalize().unwrap());
+        assert_eq!(file, directory.join("does_not_exist.png"));
+        assert!(canonical_file(Path::new("no/such/directory/file.png")).is_none());
+    }
+
+    #[test]
+    fn unwatching_removes_only_that_asset() {
+        let mut watcher = AssetWatcher::default();
+        let file = PathBuf::from("heights.png");
+        let first = Handle::<u32>::from(KeyData::from_ffi(1));
+        let second = Handle::<u32>::from(KeyData::from_ffi(2));
+        let other_type = Handle::<u64>::from(KeyData::from_ffi(1));
+        for key in [asset_key(first), asset_key(second), asset_key(other_type)] {
+            let reload: ReloadFn = Box::new(|_| {});
+            watcher
+                .files
+                .entry(file.clone())
+                .or_default()
+                .push((key, reload));
+        }
+        watcher.unwatch(first);
+        let remaining = watcher.files[&file]
+            .iter()
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, vec![asset_key(second), asset_key(other_type)]);
+        watcher.unwatch(second);
+        watcher.unwatch(other_type);
+        assert!(watcher.files.is_empty());
+    }
+
+    #[test]
+    fn own_writes_are_recognized_by_modification_time() {
+        let path = std::env::temp_dir().join("andromeda_own_write_test.txt");
+        std::fs::write(&path, "written").unwrap();
+        let mut watcher = AssetWatcher::default();
+        assert!(!watcher.is_own_write(&path));
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        watcher.written.insert(path.clone(), modified);
+        assert!(watcher.is_own_write(&path));
+        watcher
+            .written
+            .insert(path.clone(), modified - Duration::from_secs(1));
+        assert!(!watcher.is_own_write(&path));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, Result};
+use error::publish_success;
+use gfx::util::paired_image_view::PairedImageView;
+use gfx::util::sampler::create_raw_sampler_with;
+use gfx::SharedContext;
+use half::f16;
+use hot_reload::IntoDynamic;
+use inject::DI;
+use log::info;
+use phobos::domain::{Compute, ExecutionDomain};
+use phobos::prelude::ComputePipelineBuilder;
+use phobos::{
+    vk, ComputeCmdBuffer, ComputeSupport, Image, IncompleteCmdBuffer, IncompleteCommandBuffer,
+    PipelineStage, Sampler,
+};
+use scheduler::EventBus;
+
+use crate::asset::Asset;
+use crate::handle::Handle;
+use crate::storage::AssetStorage;
+use crate::texture::format::{Grayscale, TextureFormat};
+use crate::texture::{Texture, TextureLoadInfo};
+use crate::{Heightmap, NormalParams, TerrainOptions};
+
+pub type DerivedMapFormat = Grayscale<f16>;
+
+/// Maps derived from the base heightmap, for use as masks by brushes and shaders.
+/// These do not update by themselves, call [`DerivedMaps::record_update`] after the heights
+/// were modified.
+#[derive(Debug)]
+pub struct DerivedMaps {
+    /// Angle between the terrain and the horizontal plane, in radians.
+    pub slope: Texture<DerivedMapFormat>,
+    /// Laplacian of the terrain height in 1/m. This is positive where the terrain is concave,
+    /// such as in valleys, and negative where it is convex, such as on ridges.
+    pub curvature: Texture<DerivedMapFormat>,
+}
+
+pub enum DerivedMapsLoadInfo {
+    FromHeightmap {
+        heights: Handle<Heightmap>,
+        /// Options of the terrain the heightmap belongs to, these determine the scale
+        /// of the slope and curvature.
+        options: TerrainOptions,
+    },
+}
+
+impl Asset for DerivedMaps {
+    type LoadInfo = DerivedMapsLoadInfo;
+
+    fn load(info: Self::LoadInfo, bus: EventBus<DI>) -> Result<Self>
+    where
+        Self: Sized, {
+        match info {
+            DerivedMapsLoadInfo::FromHeightmap {
+                heights,
+                options,
+            } => load_from_heights(heights, options, bus),
+        }
+    }
+}
+
+impl DerivedMaps {
+    pub(crate) fn init_pipelines(ctx: SharedContext, bus: &mut EventBus<DI>) -> Result<()> {
+        ComputePipelineBuilder::new("terrain_derived")
+            .persistent()
+            .into_dynamic()
+            .set_shader("shaders/src/terrain_derived.cs.hlsl")
+            .build(bus, ctx.pipelines)
+    }
+
+    /// Record commands to recompute both maps from the heightmap. The maps are expected to be
+    /// in the shader read only layout, and are transitioned back to it.
+    pub fn record_update<'q, D: ExecutionDomain + ComputeSupport>(
+        &self,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        heights: &Heightmap,
+        options: &TerrainOptions,
+        sampler: &Sampler,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        record_derive(
+            cmd,
+            &self.slope.image,
+            &self.curvature.image,
+            heights,
+            options,
+            sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )
+    }
+}
+
+fn allocate_image(ctx: &mut SharedContext, heights: &Heightmap) -> Result<PairedImageView> {
+    let image = Image::new(
+        ctx.device.clone(),
+        &mut ctx.allocator,
+        heights.image.width(),
+        heights.image.height(),
+        vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+        DerivedMapFormat::VK_FORMAT,
+        vk::SampleCountFlags::TYPE_1,
+    )?;
+    PairedImageView::new(image, vk::ImageAspectFlags::COLOR)
+}
+
+fn record_derive<'q, D: ExecutionDomain + ComputeSupport>(
+    mut cmd: IncompleteCommandBuffer<'q, D>,
+    slope: &PairedImageView,
+    curvature: &PairedImageView,
+    heights: &Heightmap,
+    options: &TerrainOptions,
+    sampler: &Sampler,
+    layout: vk::ImageLayout,
+) -> Result<IncompleteCommandBuffer<'q, D>> {
+    let dispatches_x = (slope.width() as f32 / 32.0).ceil() as u32;
+    let dispatches_y = (slope.height() as f32 / 32.0).ceil() as u32;
+    let params = NormalParams::new(options, slope.width(), slope.height());
+    for image in [slope, curvature] {
+        cmd = cmd.transition_image(
+            &image.view,
+            PipelineStage::ALL_COMMANDS,
+            PipelineStage::COMPUTE_SHADER,
+            layout,
+            vk::ImageLayout::GENERAL,
+            vk::AccessFlags2::MEMORY_READ,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        );
+    }
+    let mut cmd = cmd
+        .bind_compute_pipeline("terrain_derived")?
+        .bind_storage_image(0, 0, &slope.view)?
+        .bind_storage_image(0, 1, &curvature.view)?
+        .bind_sampled_image(0, 2, &heights.image.image.view, sampler)?
+        .push_constant(vk::ShaderStageFlags::COMPUTE, 0, &params)
+        .dispatch(dispatches_x, dispatches_y, 1)?;
+    for image in [slope, curvature] {
+        cmd = cmd.transition_image(
+            &image.view,
+            PipelineStage::COMPUTE_SHADER,
+            PipelineStage::ALL_COMMANDS,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            vk::AccessFlags2::MEMORY_READ,
+        );
+    }
+    Ok(cmd)
+}
+
+fn load_from_heights(
+    heights: Handle<Heightmap>,
+    options: TerrainOptions,
+    bus: EventBus<DI>,
+) -> Result<DerivedMaps> {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    assets
+        .with_when_ready(heights, |heights| {
+            let di = bus.data().read().unwrap();
+            let mut ctx = di.get::<SharedContext>().cloned().unwrap();
+            let slope = allocate_image(&mut ctx, heights)?;
+            let curvature = allocate_image(&mut ctx, heights)?;
+            let sampler = create_raw_sampler_with(&ctx, options.border_mode.address_mode())?;
+            let cmd = ctx.exec.on_domain::<Compute, _>(
+                Some(ctx.pipelines.clone()),
+                Some(ctx.descriptors.clone()),
+            )?;
+            let cmd = record_derive(
+                cmd,
+                &slope,
+                &curvature,
+                heights,
+                &options,
+                &sampler,
+                vk::ImageLayout::UNDEFINED,
+            )?;
+            ctx.exec.submit(cmd.finish()?)?.wait()?;
+            let slope = Texture::load(
+                TextureLoadInfo::FromRawGpu {
+                    image: slope,
+                },
+                bus.clone(),
+            )?;
+            let curvature = Texture::load(
+                TextureLoadInfo::FromRawGpu {
+                    image: curvature,
+                },
+                bus.clone(),
+            )?;
+            info!("Generated slope and curvature maps");
+            publish_success!(
+                bus,
+                source = "asset",
+                "Successfully generated slope and curvature maps."
+            );
+            Ok(DerivedMaps {
+                slope,
+                curvature,
+            })
+        })
+        .ok_or_else(|| anyhow!("Error generating derived maps: invalid heightmap handle."))?
+}
@@ -0,0 +1,108 @@
+use anyhow::Result;
+use gfx::util::paired_image_view::PairedImageView;
+use gfx::SharedContext;
+use hot_reload::IntoDynamic;
+use inject::DI;
+use phobos::domain::Compute;
+use phobos::prelude::ComputePipelineBuilder;
+use phobos::{vk, ComputeCmdBuffer, Image, IncompleteCmdBuffer, PipelineStage};
+use scheduler::EventBus;
+
+use crate::asset::Asset;
+use crate::texture::format::{Rgba, TextureFormat};
+use crate::texture::{Texture, TextureLoadInfo};
+
+pub type DetailNormalMapFormat = Rgba<u8>;
+
+/// Tangent space normal perturbations that are painted on top of the terrain normals.
+/// This adds fine surface detail without changing the geometry of the terrain.
+/// Unlike the [`NormalMap`](crate::NormalMap), it is never regenerated from the heightmap.
+#[derive(Debug)]
+pub struct DetailNormalMap {
+    pub image: Texture<DetailNormalMapFormat>,
+}
+
+pub enum DetailNormalMapLoadInfo {
+    /// Create a detail normal map without any perturbations.
+    Flat {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl Asset for DetailNormalMap {
+    type LoadInfo = DetailNormalMapLoadInfo;
+
+    fn load(info: Self::LoadInfo, bus: EventBus<DI>) -> Result<Self>
+    where
+        Self: Sized, {
+        match info {
+            DetailNormalMapLoadInfo::Flat {
+                width,
+                height,
+            } => load_flat(width, height, bus),
+        }
+    }
+}
+
+impl DetailNormalMap {
+    pub(crate) fn init_pipelines(ctx: SharedContext, bus: &mut EventBus<DI>) -> Result<()> {
+        ComputePipelineBuilder::new("detail_normal_clear")
+            .persistent()
+            .into_dynamic()
+            .set_shader("shaders/src/detail_normal_clear.cs.hlsl")
+            .build(bus, ctx.pipelines)
+    }
+}
+
+fn load_flat(width: u32, height: u32, bus: EventBus<DI>) -> Result<DetailNormalMap> {
+    let di = bus.data().read().unwrap();
+    let mut ctx = di.get::<SharedContext>().cloned().unwrap();
+    let image = Image::new(
+        ctx.device.clone(),
+        &mut ctx.allocator,
+        width,
+        height,
+        vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+        DetailNormalMapFormat::VK_FORMAT,
+        vk::SampleCountFlags::TYPE_1,
+    )?;
+    let image = PairedImageView::new(image, vk::ImageAspectFlags::COLOR)?;
+    let cmd = ctx
+        .exec
+        .on_domain::<Compute, _>(Some(ctx.pipelines.clone()), Some(ctx.descriptors.clone()))?;
+    let dispatches_x = (width as f32 / 32.0).ceil() as u32;
+    let dispatches_y = (height as f32 / 32.0).ceil() as u32;
+    let cmd = cmd
+        .transition_image(
+            &image.view,
+            PipelineStage::TOP_OF_PIPE,
+            PipelineStage::COMPUTE_SHADER,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::AccessFlags2::NONE,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        )
+        .bind_compute_pipeline("detail_normal_clear")?
+        .bind_storage_image(0, 0, &image.view)?
+        .dispatch(dispatches_x, dispatches_y, 1)?
+        .transition_image(
+            &image.view,
+            PipelineStage::COMPUTE_SHADER,
+            PipelineStage::BOTTOM_OF_PIPE,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            vk::AccessFlags2::NONE,
+        );
+    ctx.exec.submit(cmd.finish()?)?.wait()?;
+    let image = Texture::load(
+        TextureLoadInfo::FromRawGpu {
+            image,
+        },
+        bus.clone(),
+    )?;
+    Ok(DetailNormalMap {
+        image,
+    })
+}
@@ -1,27 +1,158 @@
 use std::fmt::Debug;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
-use half::f16;
+use anyhow::{anyhow, ensure, Result};
+use gfx::util::sampler::create_raw_sampler;
+use gfx::SharedContext;
+use glam::Vec2;
+use hot_reload::IntoDynamic;
 use inject::DI;
-use log::trace;
-use phobos::vk;
+use log::{trace, warn};
+use phobos::domain::Compute;
+use phobos::prelude::ComputePipelineBuilder;
+use phobos::{vk, Buffer, ComputeCmdBuffer, Fence, IncompleteCmdBuffer, MemoryType, Sampler};
 use rayon::prelude::*;
 use scheduler::EventBus;
 
-use crate::asset::Asset;
+use crate::asset::{Asset, AssetSource, ProgressReporter};
+use crate::storage::AssetStorage;
 use crate::texture::format::Grayscale;
 use crate::texture::pixel::LumaPixel;
 use crate::texture::{Texture, TextureLoadInfo};
+use crate::Uv;
 
-pub type HeightmapFormat = Grayscale<f16>;
+/// Heights are stored as full floats, so 16-bit source images keep all of their precision.
+pub type HeightmapFormat = Grayscale<f32>;
 
 #[derive(Debug)]
 pub struct Heightmap {
     pub image: Texture<HeightmapFormat>,
 }
 
+/// Smallest and largest value in a heightmap. Heights are normalized, so these are not yet
+/// scaled by the vertical scale of the terrain.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeightRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl HeightRange {
+    /// Range of a freshly loaded heightmap. This is a conservative bound when the actual range
+    /// is not known.
+    pub const NORMALIZED: HeightRange = HeightRange {
+        min: -1.0,
+        max: 1.0,
+    };
+}
+
+/// Height range that is being computed on the GPU, see [`Heightmap::compute_range`].
+/// Owns everything the GPU uses until the computation finished. Dropping it waits for the GPU.
+pub struct PendingHeightRange {
+    ctx: SharedContext,
+    buffer: Buffer,
+    fence: Fence,
+    _sampler: Sampler,
+}
+
+impl Debug for PendingHeightRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingHeightRange").finish_non_exhaustive()
+    }
+}
+
+impl PendingHeightRange {
+    /// Returns the height range if the GPU finished computing it, without waiting for it.
+    pub fn poll(&mut self) -> Result<Option<HeightRange>> {
+        // SAFETY: The fence is alive for the duration of this call.
+        let done = unsafe { self.ctx.device.get_fence_status(self.fence.handle())? };
+        if !done {
+            return Ok(None);
+        }
+        // The fence is signaled, so this returns immediately
+        self.fence.wait()?;
+        let mut view = self.buffer.view_full();
+        let range = view.mapped_slice::<u32>()?;
+        Ok(Some(HeightRange {
+            min: from_order_preserving(range[0]),
+            max: from_order_preserving(range[1]),
+        }))
+    }
+}
+
+impl Drop for PendingHeightRange {
+    fn drop(&mut self) {
+        // The GPU may still write to the buffer, which is freed after this. This only blocks
+        // if the range is discarded before it was computed.
+        if let Err(e) = self.fence.wait() {
+            warn!("Could not wait for the height range computation: {e}");
+        }
+    }
+}
+
+/// Inverse of the `order_preserving` encoding in `height_range.cs.hlsl`.
+fn from_order_preserving(bits: u32) -> f32 {
+    if bits & 0x8000_0000 != 0 {
+        f32::from_bits(bits & 0x7fff_ffff)
+    } else {
+        f32::from_bits(!bits)
+    }
+}
+
+/// Heights of a heightmap copied to the CPU, to look up the height of the terrain without
+/// going through the GPU. See [`Heightmap::height_query`].
+#[derive(Debug, Clone)]
+pub struct HeightQuery {
+    width: u32,
+    height: u32,
+    heights: Vec<f32>,
+}
+
+impl HeightQuery {
+    /// Create a query from the heights of a heightmap, stored row by row.
+    pub fn new(width: u32, height: u32, heights: Vec<f32>) -> Result<Self> {
+        ensure!(width > 0 && height > 0, "cannot query the heights of an empty heightmap");
+        ensure!(
+            heights.len() == width as usize * height as usize,
+            "{} heights do not match a heightmap of {width}x{height}",
+            heights.len()
+        );
+        Ok(Self {
+            width,
+            height,
+            heights,
+        })
+    }
+
+    fn texel(&self, x: u32, y: u32) -> f32 {
+        self.heights[(y * self.width + x) as usize]
+    }
+
+    /// Returns the height at terrain uv coordinates `uv`, interpolated between the four nearest
+    /// texels. Coordinates outside of the terrain are clamped to its edge. Heights are
+    /// normalized, so they are not yet scaled by the vertical scale of the terrain.
+    pub fn height_at(&self, uv: Uv) -> f32 {
+        let last = Vec2::new((self.width - 1) as f32, (self.height - 1) as f32);
+        let texel = uv.0.clamp(Vec2::ZERO, Vec2::ONE) * last;
+        let x0 = (texel.x.floor() as u32).min(self.width - 1);
+        let y0 = (texel.y.floor() as u32).min(self.height - 1);
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let t = texel - Vec2::new(x0 as f32, y0 as f32);
+        let top = self.texel(x0, y0) * (1.0 - t.x) + self.texel(x1, y0) * t.x;
+        let bottom = self.texel(x0, y1) * (1.0 - t.x) + self.texel(x1, y1) * t.x;
+        top * (1.0 - t.y) + bottom * t.y
+    }
+
+    /// Distance in uv coordinates between the centers of two adjacent texels.
+    pub fn texel_size(&self) -> Vec2 {
+        1.0 / Vec2::new(self.width.max(2) as f32 - 1.0, self.height.max(2) as f32 - 1.0)
+    }
+}
+
 pub struct HeightmapLoadInfo {
+    /// Any image format can be used. Integer images are normalized, float images such as
+    /// `.exr` files keep their heights, including negative ones.
     pub path: PathBuf,
 }
 
@@ -31,20 +162,188 @@ impl Asset for Heightmap {
     fn load(info: Self::LoadInfo, bus: EventBus<DI>) -> Result<Self>
     where
         Self: Sized, {
-        load_from_image(info, bus)
+        load_from_image(info, bus, &ProgressReporter::default())
+    }
+
+    fn load_with_progress(
+        info: Self::LoadInfo,
+        bus: EventBus<DI>,
+        progress: &ProgressReporter,
+    ) -> Result<Self>
+    where
+        Self: Sized, {
+        load_from_image(info, bus, progress)
+    }
+
+    fn source(info: &Self::LoadInfo) -> Option<AssetSource<Self::LoadInfo>> {
+        let path = info.path.clone();
+        Some(AssetSource {
+            path: path.clone(),
+            reload_info: Box::new(move || HeightmapLoadInfo {
+                path: path.clone(),
+            }),
+        })
+    }
+}
+
+impl Heightmap {
+    pub(crate) fn init_pipelines(ctx: SharedContext, bus: &mut EventBus<DI>) -> Result<()> {
+        ComputePipelineBuilder::new("height_range")
+            .persistent()
+            .into_dynamic()
+            .set_shader("shaders/src/height_range.cs.hlsl")
+            .build(bus, ctx.pipelines)
     }
+
+    /// Starts computing the smallest and largest height in the heightmap on the GPU. This does
+    /// not wait for the GPU, poll the returned range until it is done.
+    pub fn compute_range(&self, bus: &EventBus<DI>) -> Result<PendingHeightRange> {
+        let mut ctx = bus
+            .data()
+            .read()
+            .unwrap()
+            .get::<SharedContext>()
+            .cloned()
+            .unwrap();
+        let buffer = Buffer::new(
+            ctx.device.clone(),
+            &mut ctx.allocator,
+            2 * std::mem::size_of::<u32>() as u64,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            MemoryType::GpuToCpu,
+        )?;
+        let mut view = buffer.view_full();
+        // Start with an empty range so any height value replaces it
+        view.mapped_slice::<u32>()?.copy_from_slice(&[u32::MAX, 0]);
+        let sampler = create_raw_sampler(&ctx)?;
+        let dispatches_x = (self.image.width() as f32 / 16.0).ceil() as u32;
+        let dispatches_y = (self.image.height() as f32 / 16.0).ceil() as u32;
+        let cmd = ctx
+            .exec
+            .on_domain::<Compute, _>(Some(ctx.pipelines.clone()), Some(ctx.descriptors.clone()))?
+            .bind_compute_pipeline("height_range")?
+            .bind_sampled_image(0, 0, &self.image.image.view, &sampler)?
+            .bind_storage_buffer(0, 1, &view)?
+            .dispatch(dispatches_x, dispatches_y, 1)?
+            .finish()?;
+        let fence = ctx.exec.submit(cmd)?;
+        Ok(PendingHeightRange {
+            ctx,
+            buffer,
+            fence,
+            _sampler: sampler,
+        })
+    }
+
+    /// Copies the heights to the CPU so they can be queried without the GPU.
+    /// This reads back the heightmap, see [`Texture::read_back`].
+    pub fn height_query(&self, bus: &EventBus<DI>) -> Result<HeightQuery> {
+        let data = self.image.read_back(bus)?;
+        HeightQuery::new(self.image.width(), self.image.height(), data.into_raw())
+    }
+
+    /// Saves the heightmap as a 16-bit grayscale image. Heightmaps are normalized when they are
+    /// loaded, so the heights are stored relative to the highest point. Returns the scale the
+    /// heights were stored at, the vertical scale of the terrain has to be multiplied by this
+    /// to get the same terrain after loading the saved file.
+    /// This reads back the heightmap, see [`Texture::read_back`].
+    pub fn save(&self, path: impl AsRef<Path>, bus: &EventBus<DI>) -> Result<f32> {
+        let data = self.image.read_back(bus)?;
+        let (pixels, scale) = encode_heights(data.as_raw_slice());
+        let image: image::ImageBuffer<image::Luma<u16>, _> =
+            image::ImageBuffer::from_raw(self.image.width(), self.image.height(), pixels)
+                .ok_or_else(|| anyhow!("heightmap data does not match its size"))?;
+        image.save(&path)?;
+        mark_written(bus, path);
+        Ok(scale)
+    }
+
+    /// Exports the heightmap at its native resolution, in a format picked from the extension
+    /// of `path`. PNG files are saved as 16-bit grayscale like [`Self::save`]. EXR files store the
+    /// heights as 32-bit floats without rescaling them. Returns the scale the heights were stored
+    /// at like [`Self::save`], this is always one for EXR files.
+    /// This reads back the heightmap, see [`Texture::read_back`].
+    pub fn export(&self, path: impl AsRef<Path>, bus: &EventBus<DI>) -> Result<f32> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("png") => self.save(path, bus),
+            Some("exr") => {
+                let data = self.image.read_back(bus)?;
+                let pixels = data
+                    .as_raw_slice()
+                    .par_iter()
+                    .flat_map_iter(|height| [*height; 3])
+                    .collect();
+                // The EXR encoder does not support grayscale images
+                let image =
+                    image::Rgb32FImage::from_raw(self.image.width(), self.image.height(), pixels)
+                        .ok_or_else(|| anyhow!("heightmap data does not match its size"))?;
+                image.save(path)?;
+                mark_written(bus, path);
+                Ok(1.0)
+            }
+            _ => Err(anyhow!("heightmaps can only be exported to .png or .exr files")),
+        }
+    }
+}
+
+/// Keeps the asset watcher from reloading assets because of a file the heightmap was saved to.
+/// # DI Access
+/// - Read [`AssetStorage`]
+fn mark_written(bus: &EventBus<DI>, path: impl AsRef<Path>) {
+    let di = bus.data().read().unwrap();
+    di.get::<AssetStorage>().unwrap().mark_written(path);
+}
+
+/// Largest value a heightmap is encoded with.
+const MAX_ENCODED_HEIGHT: f32 = u16::MAX as f32;
+
+/// Encodes heights as 16-bit values, so that [`normalize_height`] restores them after loading.
+/// Heights below zero cannot be stored, so if there are any the terrain is shifted up until
+/// its lowest point is at zero. Returns the encoded heights and the scale they are stored at.
+fn encode_heights(heights: &[f32]) -> (Vec<u16>, f32) {
+    let (min, max) = heights.par_iter().map(|&height| (height, height)).reduce(
+        || (0.0, 0.0),
+        |(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)),
+    );
+    let scale = if max > min {
+        max - min
+    } else {
+        1.0
+    };
+    let pixels = heights
+        .par_iter()
+        .map(|height| ((height - min) / scale * MAX_ENCODED_HEIGHT).round() as u16)
+        .collect();
+    (pixels, scale)
+}
+
+/// Replaces NaN and infinite heights, which float images such as EXR files may contain, with
+/// zero. Returns the number of heights that were replaced.
+fn sanitize_heights(data: &mut [LumaPixel<f32>]) -> usize {
+    data.par_iter_mut()
+        .filter(|value| !value.is_finite())
+        .map(|value| **value = 0.0)
+        .count()
 }
 
 // Normalizes height values in the height map to [-1, 1] based on the most extreme value
-fn normalize_height(_width: u32, _height: u32, data: &mut [LumaPixel<f16>]) -> Result<()> {
+fn normalize_height(_width: u32, _height: u32, data: &mut [LumaPixel<f32>]) -> Result<()> {
+    let invalid = sanitize_heights(data);
+    if invalid > 0 {
+        warn!("Heightmap contains {invalid} NaN or infinite heights, they were set to zero");
+    }
     trace!("Normalizing heightmap data");
     // Find the largest absolute value in the dataset, and take the absolute value of it.
     let extreme_val = data
         .par_iter()
-        .max_by(|lhs, rhs| lhs.to_f32().abs().total_cmp(&rhs.to_f32().abs()))
+        .max_by(|lhs, rhs| lhs.abs().total_cmp(&rhs.abs()))
         .unwrap();
-    let extreme_val = f16::from_f32(extreme_val.to_f32().abs());
-    let extreme_val_inverse = f16::ONE / extreme_val;
+    let extreme_val_inverse = 1.0 / extreme_val.abs();
     // Now divide every height value by this extreme value
     data.par_iter_mut().for_each(|value| {
         **value *= extreme_val_inverse;
@@ -52,18 +351,130 @@ fn normalize_height(_width: u32, _height: u32, data: &mut [LumaPixel<f16>]) -> R
     Ok(())
 }
 
-fn load_from_image(info: HeightmapLoadInfo, bus: EventBus<DI>) -> Result<Heightmap> {
+fn load_from_image(
+    info: HeightmapLoadInfo,
+    bus: EventBus<DI>,
+    progress: &ProgressReporter,
+) -> Result<Heightmap> {
     let tex_info = TextureLoadInfo::FromPath {
         path: info.path,
         cpu_postprocess: Some(normalize_height),
         usage_flags: Some(vk::ImageUsageFlags::STORAGE),
+        resize: None,
     };
     // Because we only load one image, we can get away with not doing this in another
     // async task through the asset system. This also makes it a bit more ergonomic to
     // access the image inside the heightmap because we don't need to go through two layers of
     // handles.
-    let image = Texture::load(tex_info, bus)?;
+    let image = Texture::load_with_progress(tex_info, bus, progress)?;
     Ok(Heightmap {
         image,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::format::TextureFormat;
+    use crate::texture::loader::decode_file;
+
+    /// Mirrors `order_preserving` in `height_range.cs.hlsl`.
+    fn order_preserving(value: f32) -> u32 {
+        let bits = value.to_bits();
+        if bits & 0x8000_0000 != 0 {
+            !bits
+        } else {
+            bits | 0x8000_0000
+        }
+    }
+
+    #[test]
+    fn order_preserving_encoding_round_trips() {
+        for value in [-1.0, -0.25, -0.0, 0.0, 0.5, 1.0, 37.5] {
+            assert_eq!(from_order_preserving(order_preserving(value)), value);
+        }
+    }
+
+    #[test]
+    fn encode_heights_keeps_normalized_heights() {
+        let heights = [0.0, 0.25, 0.5, 1.0];
+        let (pixels, scale) = encode_heights(&heights);
+        assert_eq!(scale, 1.0);
+        assert_eq!(pixels, vec![0, 16384, 32768, 65535]);
+    }
+
+    #[test]
+    fn encode_heights_shifts_negative_heights() {
+        let heights = [-0.5, 0.0, 1.5];
+        let (pixels, scale) = encode_heights(&heights);
+        assert_eq!(scale, 2.0);
+        assert_eq!(pixels, vec![0, 16384, 65535]);
+    }
+
+    #[test]
+    fn sixteen_bit_heightmaps_keep_precision() {
+        let path = std::env::temp_dir().join("andromeda_heightmap_16bit.png");
+        let heights = vec![60000u16, 60001, 65534, 65535];
+        let image: image::ImageBuffer<image::Luma<u16>, _> =
+            image::ImageBuffer::from_raw(2, 2, heights).unwrap();
+        image.save(&path).unwrap();
+
+        let buffer = std::fs::read(&path).unwrap();
+        let (width, height, data) = decode_file::<HeightmapFormat>(
+            buffer,
+            &path,
+            Some(normalize_height),
+            None,
+            &ProgressReporter::default(),
+        )
+        .unwrap();
+        // The uploaded data holds one 32-bit float per texel, as R32_SFLOAT expects
+        let bytes: &[u8] = bytemuck::cast_slice(data.as_raw_slice());
+        assert_eq!(bytes.len(), width as usize * height as usize * 4);
+        let normalized: &[f32] = bytemuck::cast_slice(bytes);
+        // Adjacent 16-bit values must not be rounded to the same height
+        for pair in normalized.windows(2) {
+            assert!(pair[0] < pair[1], "{pair:?}");
+        }
+        assert!((normalized[3] - 1.0).abs() < 1e-6, "{}", normalized[3]);
+    }
+
+    #[test]
+    fn exr_heightmaps_keep_float_heights() {
+        let path = std::env::temp_dir().join("andromeda_heightmap_float.exr");
+        let heights = [-250.0f32, 0.5, f32::NAN, 1000.0];
+        let pixels = heights.iter().flat_map(|&height| [height; 3]).collect();
+        let image = image::Rgb32FImage::from_raw(2, 2, pixels).unwrap();
+        image.save(&path).unwrap();
+
+        let image = image::open(&path).unwrap();
+        let mut data = HeightmapFormat::from_dynamic_image(image);
+        normalize_height(2, 2, data.as_mut_pixel_slice()).unwrap();
+        let normalized = data.as_raw_slice();
+        // Negative heights are kept, and the NaN is replaced instead of spreading
+        assert!((normalized[0] + 0.25).abs() < 1e-6, "{}", normalized[0]);
+        assert!((normalized[1] - 0.0005).abs() < 1e-6, "{}", normalized[1]);
+        assert_eq!(normalized[2], 0.0);
+        assert!((normalized[3] - 1.0).abs() < 1e-6, "{}", normalized[3]);
+    }
+
+    #[test]
+    fn height_query_interpolates_between_texels() {
+        let query = HeightQuery::new(2, 2, vec![0.0, 1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(query.height_at(Uv::new(Vec2::new(0.0, 0.0))), 0.0);
+        assert_eq!(query.height_at(Uv::new(Vec2::new(1.0, 1.0))), 3.0);
+        assert_eq!(query.height_at(Uv::new(Vec2::new(0.5, 0.0))), 0.5);
+        assert_eq!(query.height_at(Uv::new(Vec2::new(0.5, 0.5))), 1.5);
+        // Positions outside of the terrain use the height at its edge
+        assert_eq!(query.height_at(Uv::new(Vec2::new(2.0, -1.0))), 1.0);
+        assert!(HeightQuery::new(2, 2, vec![0.0; 3]).is_err());
+    }
+
+    #[test]
+    fn order_preserving_encoding_keeps_order() {
+        let values = [-2.0, -1.0, -0.5, 0.0, 0.25, 1.0, 3.0];
+        for pair in values.windows(2) {
+            assert!(order_preserving(pair[0]) < order_preserving(pair[1]), "{pair:?}");
+        }
+    }
+}
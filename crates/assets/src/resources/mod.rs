@@ -1,9 +1,15 @@
+pub use derived_maps::*;
+pub use detail_normal_map::*;
 pub use heightmap::*;
 pub use normal_map::*;
+pub use splat_map::*;
 pub use terrain::*;
 pub use terrain_plane::*;
 
+pub mod derived_maps;
+pub mod detail_normal_map;
 pub mod heightmap;
 pub mod normal_map;
+pub mod splat_map;
 pub mod terrain;
 pub mod terrain_plane;
@@ -1,8 +1,9 @@
 use anyhow::{anyhow, Result};
 use error::publish_success;
 use gfx::util::paired_image_view::PairedImageView;
-use gfx::util::sampler::create_raw_sampler;
+use gfx::util::sampler::create_raw_sampler_with;
 use gfx::SharedContext;
+use glam::Vec2;
 use hot_reload::IntoDynamic;
 use inject::DI;
 use log::info;
@@ -16,21 +17,59 @@ use crate::handle::Handle;
 use crate::storage::AssetStorage;
 use crate::texture::format::{Rgba, TextureFormat};
 use crate::texture::{Texture, TextureLoadInfo};
-use crate::{Heightmap, HeightmapFormat};
+use crate::{Heightmap, HeightmapFormat, TerrainOptions};
 
 pub type NormalMapFormat = Rgba<u8>;
 
 #[derive(Debug)]
 pub struct NormalMap {
     pub image: Texture<NormalMapFormat>,
+    /// Parameters the normals were derived with. Brushes recompute normals with these, so
+    /// edited areas match the rest of the map until it is regenerated.
+    pub params: NormalParams,
 }
 
 pub enum NormalMapLoadInfo {
     FromHeightmap {
         heights: Handle<Heightmap>,
+        /// Options of the terrain the heightmap belongs to, these determine the scale
+        /// of the normals.
+        options: TerrainOptions,
     },
 }
 
+/// Push constants used to derive normals from a heightmap.
+/// Kept in sync with `NormalParams` in `terrain_normal.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NormalParams {
+    /// Distance between two heightmap texel centers in meters, along x and z.
+    pub texel_spacing: Vec2,
+    /// Height in meters of a heightmap value of 1.
+    pub vertical_scale: f32,
+    /// Multiplier on the slope of the normals.
+    pub strength: f32,
+    /// Border mode of the terrain, see [`BorderMode::shader_value`](crate::BorderMode::shader_value).
+    pub border_mode: u32,
+    /// The shader aligns the struct to the alignment of `texel_spacing`.
+    _padding: u32,
+}
+
+impl NormalParams {
+    /// Parameters for a heightmap of `width` by `height` texels. The options are fitted to the
+    /// heightmap first, so the texel spacing matches the terrain mesh.
+    pub fn new(options: &TerrainOptions, width: u32, height: u32) -> Self {
+        let options = options.fit_to_heightmap(width, height);
+        Self {
+            texel_spacing: options.texel_spacing(width, height),
+            vertical_scale: options.vertical_scale,
+            strength: options.normal_strength,
+            border_mode: options.border_mode.shader_value(),
+            _padding: 0,
+        }
+    }
+}
+
 impl Asset for NormalMap {
     type LoadInfo = NormalMapLoadInfo;
 
@@ -40,7 +79,8 @@ impl Asset for NormalMap {
         match info {
             NormalMapLoadInfo::FromHeightmap {
                 heights,
-            } => load_from_heights(heights, bus),
+                options,
+            } => load_from_heights(heights, options, bus),
         }
     }
 }
@@ -71,7 +111,11 @@ fn allocate_image(
     PairedImageView::new(image, vk::ImageAspectFlags::COLOR)
 }
 
-fn load_from_heights(heights: Handle<Heightmap>, bus: EventBus<DI>) -> Result<NormalMap> {
+fn load_from_heights(
+    heights: Handle<Heightmap>,
+    options: TerrainOptions,
+    bus: EventBus<DI>,
+) -> Result<NormalMap> {
     let di = bus.data().read().unwrap();
     let assets = di.get::<AssetStorage>().unwrap();
     assets
@@ -79,13 +123,14 @@ fn load_from_heights(heights: Handle<Heightmap>, bus: EventBus<DI>) -> Result<No
             let di = bus.data().read().unwrap();
             let mut ctx = di.get::<SharedContext>().cloned().unwrap();
             let image = allocate_image(&mut ctx, &heights.image)?;
-            let sampler = create_raw_sampler(&ctx)?;
+            let sampler = create_raw_sampler_with(&ctx, options.border_mode.address_mode())?;
             let cmd = ctx.exec.on_domain::<Compute, _>(
                 Some(ctx.pipelines.clone()),
                 Some(ctx.descriptors.clone()),
             )?;
             let dispatches_x = (image.width() as f32 / 32.0).ceil() as u32;
             let dispatches_y = (image.height() as f32 / 32.0).ceil() as u32;
+            let params = NormalParams::new(&options, image.width(), image.height());
             let cmd = cmd
                 .transition_image(
                     &image.view,
@@ -99,6 +144,7 @@ fn load_from_heights(heights: Handle<Heightmap>, bus: EventBus<DI>) -> Result<No
                 .bind_compute_pipeline("terrain_normals")?
                 .bind_storage_image(0, 0, &image.view)?
                 .bind_sampled_image(0, 1, &heights.image.image.view, &sampler)?
+                .push_constant(vk::ShaderStageFlags::COMPUTE, 0, &params)
                 .dispatch(dispatches_x, dispatches_y, 1)?
                 .transition_image(
                     &image.view,
@@ -118,9 +164,10 @@ fn load_from_heights(heights: Handle<Heightmap>, bus: EventBus<DI>) -> Result<No
                 bus.clone(),
             )?;
             info!("Generated normal map");
-            publish_success!(bus, "Successfully generated normal map.");
+            publish_success!(bus, source = "asset", "Successfully generated normal map.");
             Ok(NormalMap {
                 image,
+                params,
             })
         })
         .ok_or_else(|| anyhow!("Error generating normal map: invalid heightmap handle."))?
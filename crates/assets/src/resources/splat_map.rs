@@ -0,0 +1,122 @@
+use anyhow::Result;
+use gfx::util::paired_image_view::PairedImageView;
+use gfx::SharedContext;
+use hot_reload::IntoDynamic;
+use inject::DI;
+use phobos::domain::Compute;
+use phobos::prelude::ComputePipelineBuilder;
+use phobos::{vk, ComputeCmdBuffer, Image, IncompleteCmdBuffer, PipelineStage};
+use scheduler::EventBus;
+
+use crate::asset::Asset;
+use crate::texture::format::{Rgba, TextureFormat};
+use crate::texture::{Texture, TextureLoadInfo};
+
+pub type SplatMapFormat = Rgba<u8>;
+
+/// Weights of the material layers the terrain is shaded with, one layer per channel. The
+/// weights of a texel sum to one. The first layer is the diffuse map of the terrain, the
+/// others are the materials of the world.
+#[derive(Debug)]
+pub struct SplatMap {
+    pub image: Texture<SplatMapFormat>,
+}
+
+pub enum SplatMapLoadInfo {
+    /// Create a splat map that only shows the first layer.
+    Uniform {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl Asset for SplatMap {
+    type LoadInfo = SplatMapLoadInfo;
+
+    fn load(info: Self::LoadInfo, bus: EventBus<DI>) -> Result<Self>
+    where
+        Self: Sized, {
+        match info {
+            SplatMapLoadInfo::Uniform {
+                width,
+                height,
+            } => load_uniform(width, height, bus),
+        }
+    }
+}
+
+impl SplatMap {
+    /// Number of material layers, one for every channel of the map.
+    pub const CHANNELS: u32 = 4;
+
+    /// Name of the layer stored in `channel`, as shown to the user.
+    pub fn channel_name(channel: u32) -> String {
+        match channel {
+            0 => "Diffuse map".to_owned(),
+            _ => format!("Material {channel}"),
+        }
+    }
+
+    pub(crate) fn init_pipelines(ctx: SharedContext, bus: &mut EventBus<DI>) -> Result<()> {
+        ComputePipelineBuilder::new("splat_clear")
+            .persistent()
+            .into_dynamic()
+            .set_shader("shaders/src/splat_clear.cs.hlsl")
+            .build(bus, ctx.pipelines)
+    }
+}
+
+fn load_uniform(width: u32, height: u32, bus: EventBus<DI>) -> Result<SplatMap> {
+    let di = bus.data().read().unwrap();
+    let mut ctx = di.get::<SharedContext>().cloned().unwrap();
+    let image = Image::new(
+        ctx.device.clone(),
+        &mut ctx.allocator,
+        width,
+        height,
+        vk::ImageUsageFlags::STORAGE
+            | vk::ImageUsageFlags::SAMPLED
+            | vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::TRANSFER_DST,
+        SplatMapFormat::VK_FORMAT,
+        vk::SampleCountFlags::TYPE_1,
+    )?;
+    let image = PairedImageView::new(image, vk::ImageAspectFlags::COLOR)?;
+    let cmd = ctx
+        .exec
+        .on_domain::<Compute, _>(Some(ctx.pipelines.clone()), Some(ctx.descriptors.clone()))?;
+    let dispatches_x = (width as f32 / 32.0).ceil() as u32;
+    let dispatches_y = (height as f32 / 32.0).ceil() as u32;
+    let cmd = cmd
+        .transition_image(
+            &image.view,
+            PipelineStage::TOP_OF_PIPE,
+            PipelineStage::COMPUTE_SHADER,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::AccessFlags2::NONE,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        )
+        .bind_compute_pipeline("splat_clear")?
+        .bind_storage_image(0, 0, &image.view)?
+        .dispatch(dispatches_x, dispatches_y, 1)?
+        .transition_image(
+            &image.view,
+            PipelineStage::COMPUTE_SHADER,
+            PipelineStage::BOTTOM_OF_PIPE,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            vk::AccessFlags2::NONE,
+        );
+    ctx.exec.submit(cmd.finish()?)?.wait()?;
+    let image = Texture::load(
+        TextureLoadInfo::FromRawGpu {
+            image,
+        },
+        bus.clone(),
+    )?;
+    Ok(SplatMap {
+        image,
+    })
+}
@@ -1,29 +1,100 @@
 use std::fmt::Debug;
+use std::ops::Range;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::{anyhow, Result};
-use glam::{Vec2, Vec3};
+use glam::{IVec2, Vec2, Vec3};
 use inject::DI;
+use phobos::vk;
 use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
 
-use crate::asset::Asset;
+use crate::asset::{Asset, ProgressReporter};
 use crate::handle::Handle;
 use crate::storage::AssetStorage;
-use crate::texture::format::{SRgba, TextureFormat};
+use crate::texture::format::{EncodedSRgba, TextureFormat};
 use crate::texture::{Texture, TextureLoadInfo};
-use crate::{Heightmap, HeightmapLoadInfo, NormalMap, NormalMapLoadInfo, TerrainPlane};
+use crate::{
+    DerivedMaps, DerivedMapsLoadInfo, DetailNormalMap, DetailNormalMapLoadInfo, HeightRange,
+    Heightmap, HeightmapLoadInfo, NormalMap, NormalMapLoadInfo, SplatMap, SplatMapLoadInfo,
+    TerrainPlane, TexelRadius, Uv, WorldRadius,
+};
 
-#[derive(Debug, Copy, Clone)]
+/// The diffuse map can be painted on with the color brush, so it is stored as a storage
+/// compatible format.
+pub type DiffuseMapFormat = EncodedSRgba<u8>;
+
+/// How base terrain textures are extended past their edges. This is honored by the samplers of
+/// the terrain, by normal and derived map generation, and by brushes used near the border.
+/// Kept in sync with `border.hlsl`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BorderMode {
+    /// Extend the edge texels, so the terrain plateaus at its border.
+    #[default]
+    Clamp,
+    /// Continue at the opposite edge. Brush strokes crossing the border are continued on the
+    /// other side, which keeps the terrain tileable.
+    Wrap,
+    /// Reflect the terrain at its border.
+    Mirror,
+}
+
+impl BorderMode {
+    pub const ALL: [BorderMode; 3] = [BorderMode::Clamp, BorderMode::Wrap, BorderMode::Mirror];
+
+    /// Value identifying this mode in shaders.
+    pub fn shader_value(self) -> u32 {
+        match self {
+            BorderMode::Clamp => 0,
+            BorderMode::Wrap => 1,
+            BorderMode::Mirror => 2,
+        }
+    }
+
+    /// Sampler address mode matching this border mode.
+    pub fn address_mode(self) -> vk::SamplerAddressMode {
+        match self {
+            BorderMode::Clamp => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            BorderMode::Wrap => vk::SamplerAddressMode::REPEAT,
+            BorderMode::Mirror => vk::SamplerAddressMode::MIRRORED_REPEAT,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TerrainOptions {
-    /// Width and height of the terrain plane in meters.
-    pub horizontal_scale: f32,
-    /// Vertical scaling. The most extreme point of the terrain will have this as its height.
+    /// Size of the terrain plane in meters along the x and z axis.
+    /// When a terrain is loaded, the z extent is recomputed from the x extent so the terrain
+    /// has the same aspect ratio as its heightmap.
+    pub horizontal_scale: Vec2,
+    /// Vertical scaling in meters. Heights in the heightmap are normalized, so the most extreme
+    /// point of the terrain will have this as its height.
     pub vertical_scale: f32,
     /// Number of patches the terrain mesh will be divided in in each direction.
     pub patch_resolution: u32,
+    /// Number of times the detail heightmap is repeated over the terrain in each direction.
+    pub detail_tiling: f32,
+    /// Height of the detail layer. The most extreme point of the detail heightmap adds this
+    /// to the height of the base heightmap.
+    pub detail_strength: f32,
+    /// Multiplier on the slope of the normals derived from the base heightmap.
+    /// At 1.0 the normals match the geometry of the terrain.
+    #[serde(default = "default_normal_strength")]
+    pub normal_strength: f32,
+    /// How the base terrain textures are extended past their edges.
+    #[serde(default)]
+    pub border_mode: BorderMode,
 }
 
 impl TerrainOptions {
+    /// Returns a copy of these options with the z extent adjusted to match the aspect ratio
+    /// of a heightmap with the given dimensions. The x extent is kept as-is.
+    pub fn fit_to_heightmap(mut self, width: u32, height: u32) -> Self {
+        self.horizontal_scale.y = self.horizontal_scale.x * height as f32 / width as f32;
+        self
+    }
+
     #[inline]
     pub fn patch_coords(&self, patch_x: u32, patch_y: u32) -> Vec2 {
         let resolution = self.patch_resolution as f32;
@@ -31,8 +102,8 @@ impl TerrainOptions {
         let x = patch_x as f32;
         let y = patch_y as f32;
         Vec2::new(
-            x * patch_size + patch_size / 2.0 - resolution * patch_size / 2.0,
-            y * patch_size + patch_size / 2.0 - resolution * patch_size / 2.0,
+            x * patch_size.x + patch_size.x / 2.0 - resolution * patch_size.x / 2.0,
+            y * patch_size.y + patch_size.y / 2.0 - resolution * patch_size.y / 2.0,
         )
     }
 
@@ -44,6 +115,27 @@ impl TerrainOptions {
         Vec2::new(x / (resolution - 1.0), y / (resolution - 1.0))
     }
 
+    /// Distance in meters between the centers of two adjacent texels of a base terrain
+    /// texture with the given dimensions.
+    pub fn texel_spacing(&self, width: u32, height: u32) -> Vec2 {
+        let texels = Vec2::new(width.max(2) as f32 - 1.0, height.max(2) as f32 - 1.0);
+        Vec2::new(self.max_x() - self.min_x(), self.max_y() - self.min_y()) / texels
+    }
+
+    /// Whether the normal map generated with these options differs from one generated
+    /// with `other`.
+    pub fn normals_differ(&self, other: &TerrainOptions) -> bool {
+        self.slopes_differ(other) || self.normal_strength != other.normal_strength
+    }
+
+    /// Whether the slope of the terrain in world space differs from a terrain with `other`
+    /// as its options, given the same heightmap.
+    pub fn slopes_differ(&self, other: &TerrainOptions) -> bool {
+        self.horizontal_scale != other.horizontal_scale
+            || self.vertical_scale != other.vertical_scale
+            || self.border_mode != other.border_mode
+    }
+
     /// Returns the smallest x coordinate, this has uv.x == 0
     #[inline]
     pub fn min_x(&self) -> f32 {
@@ -68,7 +160,48 @@ impl TerrainOptions {
         self.patch_coords(0, self.patch_resolution - 1).y
     }
 
-    pub fn uv_at(&self, world_pos: Vec3) -> Vec2 {
+    /// Returns the world-space bounding box `(min, max)` of the terrain mesh, given the range
+    /// of its heightmap. The detail layer is not included in the range, so the box is grown
+    /// by the detail strength in both directions.
+    pub fn aabb(&self, range: HeightRange) -> (Vec3, Vec3) {
+        let half_extent = self.horizontal_scale / 2.0;
+        let min = Vec3::new(
+            -half_extent.x,
+            range.min * self.vertical_scale - self.detail_strength,
+            -half_extent.y,
+        );
+        let max = Vec3::new(
+            half_extent.x,
+            range.max * self.vertical_scale + self.detail_strength,
+            half_extent.y,
+        );
+        (min, max)
+    }
+
+    /// Converts uv coordinates on the base heightmap to uv coordinates on the detail heightmap.
+    /// The detail heightmap repeats, so a tile spans its texels edge to edge, without the
+    /// half texel offset used by the base maps.
+    #[inline]
+    pub fn detail_uv(&self, uv: Uv) -> Vec2 {
+        (uv.0 * self.detail_tiling).fract()
+    }
+
+    /// Returns the texel on the detail heightmap that is located at terrain uv coordinates `uv`.
+    pub fn detail_texel_at_uv(&self, uv: Uv, width: u32, height: u32) -> IVec2 {
+        let size = Vec2::new(width as f32, height as f32);
+        let texel = (self.detail_uv(uv) * size).floor().as_ivec2();
+        // Guard against floating point error pushing us onto the next tile.
+        texel.clamp(IVec2::ZERO, size.as_ivec2() - 1)
+    }
+
+    /// Returns the terrain uv coordinates at a world position.
+    ///
+    /// Terrain uvs run from 0 to 1 over the terrain mesh, and both ends refer to the *center*
+    /// of the edge texels of the base heightmap, normal map and diffuse map. Texel `i` of a
+    /// texture with `n` texels is therefore located at `uv = i / (n - 1)`. Use [`texel_at_uv`]
+    /// to find the texel at a uv coordinate. Shaders follow the same convention through
+    /// `terrain_uv.hlsl`.
+    pub fn uv_at(&self, world_pos: Vec3) -> Uv {
         // First compute outer bounds of the terrain mesh
         let min_x = self.min_x();
         let min_y = self.min_y();
@@ -84,36 +217,113 @@ impl TerrainOptions {
         // in the [-0.5, 0.5] range, so we need to remap them to [0, 1]. Since this range is
         // of the same size, we can just add 0.5
         let uv = Vec2::new(world_pos.x / dx, world_pos.z / dy);
-        uv + 0.5
+        Uv::new(uv + 0.5)
     }
 
-    /// Assumes square texture
+    /// Converts a radius in world space to a radius in texels on the given texture.
+    /// If the texel density differs between the two axes, the largest radius is returned so
+    /// the result covers the full area.
     pub fn texel_radius<F: TextureFormat>(
         &self,
         center: Vec3,
-        radius: f32,
+        radius: WorldRadius,
+        texture: &Texture<F>,
+    ) -> TexelRadius {
+        // Texel centers span the full uv range, so there is one texel less than the size.
+        let texels_per_uv = Vec2::new(
+            texture.width().saturating_sub(1) as f32,
+            texture.height().saturating_sub(1) as f32,
+        );
+        self.scaled_texel_radius(center, radius, texels_per_uv)
+    }
+
+    /// Converts a radius in world space to a radius in texels on the detail heightmap.
+    pub fn detail_texel_radius<F: TextureFormat>(
+        &self,
+        center: Vec3,
+        radius: WorldRadius,
         texture: &Texture<F>,
-    ) -> u32 {
+    ) -> TexelRadius {
+        let size = Vec2::new(texture.width() as f32, texture.height() as f32);
+        self.scaled_texel_radius(center, radius, size * self.detail_tiling)
+    }
+
+    fn scaled_texel_radius(
+        &self,
+        center: Vec3,
+        radius: WorldRadius,
+        texels_per_uv: Vec2,
+    ) -> TexelRadius {
+        debug_assert!(radius.0 >= 0.0, "negative world radius {}", radius.0);
         let center_uv = self.uv_at(center);
-        let edge_uv = self.uv_at(center + Vec3::new(radius, 0.0, 0.0));
-        let uv_diff = (edge_uv - center_uv).abs();
-        let uv_length = uv_diff.length();
-        (texture.width() as f32 * uv_length).ceil() as u32
+        let edge_uv = self.uv_at(center + Vec3::new(radius.0, 0.0, radius.0));
+        let texels = (edge_uv.0 - center_uv.0).abs() * texels_per_uv;
+        TexelRadius(texels.max_element().ceil() as u32)
     }
 }
 
-#[derive(Debug)]
+/// Returns the texel on a texture of the given size that is located at terrain uv coordinates
+/// `uv`. See [`TerrainOptions::uv_at`] for the uv convention. The result is not clamped to the
+/// texture, since brushes may be centered slightly outside of it.
+pub fn texel_at_uv(uv: Uv, width: u32, height: u32) -> IVec2 {
+    let last = Vec2::new(width.saturating_sub(1) as f32, height.saturating_sub(1) as f32);
+    (uv.0 * last).round().as_ivec2()
+}
+
+#[derive(Debug, Clone)]
 pub struct Terrain {
     pub height_map: Handle<Heightmap>,
     pub normal_map: Handle<NormalMap>,
-    pub diffuse_map: Handle<Texture<SRgba<u8>>>,
+    pub diffuse_map: Handle<Texture<DiffuseMapFormat>>,
     pub mesh: Handle<TerrainPlane>,
+    /// High frequency heightmap that is tiled over the terrain and added to the base heightmap.
+    pub detail_map: Option<Handle<Heightmap>>,
+    /// Painted normal perturbations, blended over the normals computed from the heightmap.
+    pub detail_normal_map: Handle<DetailNormalMap>,
+    /// Painted weights of the material layers the terrain is shaded with.
+    pub splat_map: Handle<SplatMap>,
+    /// Slope and curvature of the base heightmap.
+    pub derived_maps: Handle<DerivedMaps>,
+    /// Options the terrain mesh was generated with, fitted to the heightmap dimensions.
+    pub options: TerrainOptions,
 }
 
 impl Terrain {
+    /// Returns the progress of the first map or mesh of this terrain that is still being
+    /// generated, or `None` once all of them are ready.
+    pub fn load_progress(&self, assets: &AssetStorage) -> Option<(f32, String)> {
+        let stages = [
+            (assets.progress(self.normal_map), "Computing normals"),
+            (assets.progress(self.mesh), "Building mesh"),
+            (assets.progress(self.diffuse_map), "Loading texture"),
+            (assets.progress(self.derived_maps), "Computing slope and curvature"),
+        ];
+        stages
+            .into_iter()
+            .find_map(|(progress, stage)| progress.map(|progress| label_stage(progress, stage)))
+    }
+
+    /// Delete a terrain together with the maps and mesh it owns, see
+    /// [`AssetStorage::schedule_delete`]. A terrain that is still loading is cancelled.
+    pub fn schedule_delete(handle: Handle<Terrain>, assets: &AssetStorage) {
+        let terrain = assets.with_if_ready(handle, |terrain| terrain.clone());
+        assets.schedule_delete(handle);
+        let Some(terrain) = terrain else { return };
+        assets.schedule_delete(terrain.height_map);
+        assets.schedule_delete(terrain.normal_map);
+        assets.schedule_delete(terrain.diffuse_map);
+        assets.schedule_delete(terrain.mesh);
+        if let Some(detail_map) = terrain.detail_map {
+            assets.schedule_delete(detail_map);
+        }
+        assets.schedule_delete(terrain.detail_normal_map);
+        assets.schedule_delete(terrain.splat_map);
+        assets.schedule_delete(terrain.derived_maps);
+    }
+
     pub fn with_if_ready<F, R>(&self, assets: &AssetStorage, f: F) -> Option<R>
     where
-        F: FnOnce(&Heightmap, &NormalMap, &Texture<SRgba<u8>>, &TerrainPlane) -> R, {
+        F: FnOnce(&Heightmap, &NormalMap, &Texture<DiffuseMapFormat>, &TerrainPlane) -> R, {
         assets
             .with_if_ready(self.height_map, |heights| {
                 assets.with_if_ready(self.normal_map, |normals| {
@@ -129,7 +339,7 @@ impl Terrain {
 
     pub fn with_when_ready<F, R>(&self, bus: &EventBus<DI>, f: F) -> Option<R>
     where
-        F: FnOnce(&Heightmap, &NormalMap, &Texture<SRgba<u8>>, &TerrainPlane) -> R, {
+        F: FnOnce(&Heightmap, &NormalMap, &Texture<DiffuseMapFormat>, &TerrainPlane) -> R, {
         let di = bus.data().read().unwrap();
         let assets = di.get::<AssetStorage>().unwrap();
         assets
@@ -146,11 +356,50 @@ impl Terrain {
     }
 }
 
+fn default_normal_strength() -> f32 {
+    1.0
+}
+
+/// Files and options a terrain is created from, used to remember recently opened terrains.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerrainSource {
+    pub height_path: PathBuf,
+    pub texture_path: PathBuf,
+    /// Resize the texture to the resolution of the heightmap when it is loaded.
+    #[serde(default)]
+    pub resize_texture: bool,
+    #[serde(default)]
+    pub detail_path: Option<PathBuf>,
+    pub options: TerrainOptions,
+}
+
+impl TerrainSource {
+    /// Returns the first file of this terrain that does not exist, if any.
+    pub fn missing_file(&self) -> Option<&PathBuf> {
+        [Some(&self.height_path), Some(&self.texture_path), self.detail_path.as_ref()]
+            .into_iter()
+            .flatten()
+            .find(|path| !path.exists())
+    }
+
+    pub fn load_info(&self) -> TerrainLoadInfo {
+        TerrainLoadInfo::FromHeightmap {
+            height_path: self.height_path.clone(),
+            texture_path: self.texture_path.clone(),
+            resize_texture: self.resize_texture,
+            detail_path: self.detail_path.clone(),
+            options: self.options,
+        }
+    }
+}
+
 pub enum TerrainLoadInfo {
     // Create a new terrain
     FromHeightmap {
         height_path: PathBuf,
         texture_path: PathBuf,
+        resize_texture: bool,
+        detail_path: Option<PathBuf>,
         options: TerrainOptions,
     },
     // Only recreate the mesh associated with the terrain
@@ -158,54 +407,180 @@ pub enum TerrainLoadInfo {
         old: Handle<Terrain>,
         options: TerrainOptions,
     },
+    // Replace the detail heightmap of the terrain, or remove it if no path is given
+    WithDetailMap {
+        old: Handle<Terrain>,
+        detail_path: Option<PathBuf>,
+    },
+    // Replace the diffuse map of the terrain, keeping the heightmap and all other maps
+    WithDiffuseMap {
+        old: Handle<Terrain>,
+        texture_path: PathBuf,
+        resize_texture: bool,
+    },
 }
 
 impl Asset for Terrain {
     type LoadInfo = TerrainLoadInfo;
 
     fn load(info: Self::LoadInfo, bus: EventBus<DI>) -> Result<Self>
+    where
+        Self: Sized, {
+        Self::load_with_progress(info, bus, &ProgressReporter::default())
+    }
+
+    fn load_with_progress(
+        info: Self::LoadInfo,
+        bus: EventBus<DI>,
+        progress: &ProgressReporter,
+    ) -> Result<Self>
     where
         Self: Sized, {
         match info {
             TerrainLoadInfo::FromHeightmap {
                 height_path,
                 texture_path,
+                resize_texture,
+                detail_path,
                 options,
-            } => load_from_files(height_path, texture_path, options, bus),
+            } => load_from_files(
+                height_path,
+                texture_path,
+                resize_texture,
+                detail_path,
+                options,
+                bus,
+                progress,
+            ),
             TerrainLoadInfo::FromNewMesh {
                 old,
                 options,
             } => load_new_mesh(old, options, bus),
+            TerrainLoadInfo::WithDetailMap {
+                old,
+                detail_path,
+            } => load_detail_map(old, detail_path, bus),
+            TerrainLoadInfo::WithDiffuseMap {
+                old,
+                texture_path,
+                resize_texture,
+            } => load_diffuse_map(old, texture_path, resize_texture, bus),
         }
     }
 }
 
+/// Load the diffuse map of a terrain whose heightmap has the given size. The diffuse map is
+/// mapped over the terrain through the terrain uvs, so it is aligned to the heightmap as long
+/// as both have the same aspect ratio, whatever its resolution.
+fn load_diffuse_texture(
+    assets: &AssetStorage,
+    path: PathBuf,
+    resize: bool,
+    heightmap_size: (u32, u32),
+) -> Handle<Texture<DiffuseMapFormat>> {
+    assets.load(TextureLoadInfo::FromPath {
+        path,
+        cpu_postprocess: None,
+        usage_flags: Some(vk::ImageUsageFlags::STORAGE),
+        resize: resize.then_some(heightmap_size),
+    })
+}
+
+/// Interval at which the progress of the heightmap of a loading terrain is polled.
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Labels an asset that does not report a stage itself with `stage`.
+fn label_stage((value, label): (f32, String), stage: &str) -> (f32, String) {
+    if label.is_empty() {
+        (value, stage.to_owned())
+    } else {
+        (value, label)
+    }
+}
+
+/// Blocks until an asset is no longer loading, reporting its progress scaled into `range`.
+/// The DI lock is only held while polling, so the rest of the application is not blocked.
+fn wait_with_progress<A: Asset + Send + 'static>(
+    bus: &EventBus<DI>,
+    handle: Handle<A>,
+    progress: &ProgressReporter,
+    range: Range<f32>,
+    stage: &str,
+) {
+    loop {
+        let current = {
+            let di = bus.data().read().unwrap();
+            di.get::<AssetStorage>().unwrap().progress(handle)
+        };
+        let Some(current) = current else { return };
+        let (value, label) = label_stage(current, stage);
+        progress.report(range.start + value * (range.end - range.start), label);
+        std::thread::sleep(PROGRESS_POLL_INTERVAL);
+    }
+}
+
 fn load_from_files(
     heightmap_path: PathBuf,
     texture_path: PathBuf,
+    resize_texture: bool,
+    detail_path: Option<PathBuf>,
     options: TerrainOptions,
     bus: EventBus<DI>,
+    progress: &ProgressReporter,
 ) -> Result<Terrain> {
+    let heights = {
+        let di = bus.data().read().unwrap();
+        di.get::<AssetStorage>().unwrap().load(HeightmapLoadInfo {
+            path: heightmap_path,
+        })
+    };
+    wait_with_progress(&bus, heights, progress, 0.0..1.0, "Loading heightmap");
     let di = bus.data().read().unwrap();
     let assets = di.get::<AssetStorage>().unwrap();
-    let heights = assets.load(HeightmapLoadInfo {
-        path: heightmap_path,
-    });
+    // We need the dimensions of the heightmap to generate a mesh with the correct aspect ratio.
+    let (width, height) = assets
+        .with_when_ready(heights, |heights| (heights.image.width(), heights.image.height()))
+        .ok_or_else(|| anyhow!("error creating terrain: heightmap failed to load"))?;
+    let options = options.fit_to_heightmap(width, height);
 
-    let texture: Handle<Texture<SRgba<u8>>> = assets.load(TextureLoadInfo::FromPath {
-        path: texture_path,
-        cpu_postprocess: None,
-        usage_flags: None,
-    });
+    let texture = load_diffuse_texture(assets, texture_path, resize_texture, (width, height));
     let normal_map = assets.load(NormalMapLoadInfo::FromHeightmap {
         heights,
+        options,
+    });
+    let derived_maps = assets.load(DerivedMapsLoadInfo::FromHeightmap {
+        heights,
+        options,
+    });
+    let detail_map = detail_path.map(|path| {
+        assets.load(HeightmapLoadInfo {
+            path,
+        })
+    });
+    // The detail normals match the resolution of the base normal map
+    let detail_normal_map = assets.load(DetailNormalMapLoadInfo::Flat {
+        width,
+        height,
+    });
+    // Like the detail normals, the splat map matches the resolution of the heightmap
+    let splat_map = assets.load(SplatMapLoadInfo::Uniform {
+        width,
+        height,
     });
     let mesh = assets.load(options);
+    // The terrain is ready while the maps and mesh are still being generated, their progress
+    // is reported by `Terrain::load_progress`.
+    progress.report(1.0, "Done");
     Ok(Terrain {
         height_map: heights,
         normal_map,
         diffuse_map: texture,
         mesh,
+        detail_map,
+        detail_normal_map,
+        splat_map,
+        derived_maps,
+        options,
     })
 }
 
@@ -220,13 +595,282 @@ fn load_new_mesh(
         .with_when_ready(old, |terrain| {
             let di = bus.data().read().unwrap();
             let assets = di.get::<AssetStorage>().unwrap();
+            let options = assets
+                .with_when_ready(terrain.height_map, |heights| {
+                    options.fit_to_heightmap(heights.image.width(), heights.image.height())
+                })
+                .ok_or_else(|| anyhow!("error creating terrain: heightmap is invalid"))?;
             let mesh = assets.load(options);
+            // Normals depend on the scale of the terrain, so regenerate them if it changed.
+            let normal_map = if options.normals_differ(&terrain.options) {
+                assets.load(NormalMapLoadInfo::FromHeightmap {
+                    heights: terrain.height_map,
+                    options,
+                })
+            } else {
+                terrain.normal_map
+            };
+            let derived_maps = if options.slopes_differ(&terrain.options) {
+                assets.load(DerivedMapsLoadInfo::FromHeightmap {
+                    heights: terrain.height_map,
+                    options,
+                })
+            } else {
+                terrain.derived_maps
+            };
             Ok(Terrain {
                 height_map: terrain.height_map,
-                normal_map: terrain.normal_map,
+                normal_map,
                 diffuse_map: terrain.diffuse_map,
                 mesh,
+                detail_map: terrain.detail_map,
+                detail_normal_map: terrain.detail_normal_map,
+                splat_map: terrain.splat_map,
+                derived_maps,
+                options,
+            })
+        })
+        .ok_or_else(|| anyhow!("error creating terrain from old terrain: old terrain is invalid"))?
+}
+
+fn load_detail_map(
+    old: Handle<Terrain>,
+    detail_path: Option<PathBuf>,
+    bus: EventBus<DI>,
+) -> Result<Terrain> {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    assets
+        .with_when_ready(old, |terrain| {
+            let detail_map = detail_path.map(|path| {
+                assets.load(HeightmapLoadInfo {
+                    path,
+                })
+            });
+            Terrain {
+                height_map: terrain.height_map,
+                normal_map: terrain.normal_map,
+                diffuse_map: terrain.diffuse_map,
+                mesh: terrain.mesh,
+                detail_map,
+                detail_normal_map: terrain.detail_normal_map,
+                splat_map: terrain.splat_map,
+                derived_maps: terrain.derived_maps,
+                options: terrain.options,
+            }
+        })
+        .ok_or_else(|| anyhow!("error creating terrain from old terrain: old terrain is invalid"))
+}
+
+fn load_diffuse_map(
+    old: Handle<Terrain>,
+    texture_path: PathBuf,
+    resize_texture: bool,
+    bus: EventBus<DI>,
+) -> Result<Terrain> {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    assets
+        .with_when_ready(old, |terrain| {
+            let di = bus.data().read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            let size = assets
+                .with_when_ready(terrain.height_map, |heights| {
+                    (heights.image.width(), heights.image.height())
+                })
+                .ok_or_else(|| anyhow!("error creating terrain: heightmap is invalid"))?;
+            Ok(Terrain {
+                height_map: terrain.height_map,
+                normal_map: terrain.normal_map,
+                diffuse_map: load_diffuse_texture(assets, texture_path, resize_texture, size),
+                mesh: terrain.mesh,
+                detail_map: terrain.detail_map,
+                detail_normal_map: terrain.detail_normal_map,
+                splat_map: terrain.splat_map,
+                derived_maps: terrain.derived_maps,
+                options: terrain.options,
             })
         })
         .ok_or_else(|| anyhow!("error creating terrain from old terrain: old terrain is invalid"))?
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn non_square_options() -> TerrainOptions {
+        TerrainOptions {
+            horizontal_scale: Vec2::new(512.0, 512.0),
+            vertical_scale: 100.0,
+            patch_resolution: 32,
+            detail_tiling: 1.0,
+            detail_strength: 0.0,
+            normal_strength: 1.0,
+            border_mode: BorderMode::Clamp,
+        }
+        .fit_to_heightmap(2048, 1024)
+    }
+
+    fn assert_uv_eq(lhs: Vec2, rhs: Vec2) {
+        assert!((lhs - rhs).abs().max_element() < 1e-5, "{lhs} != {rhs}");
+    }
+
+    #[test]
+    fn fit_to_heightmap_keeps_aspect() {
+        let options = non_square_options();
+        assert_eq!(options.horizontal_scale, Vec2::new(512.0, 256.0));
+    }
+
+    #[test]
+    fn aabb_covers_mesh_and_height_range() {
+        let options = TerrainOptions {
+            detail_strength: 2.0,
+            ..non_square_options()
+        };
+        let (min, max) = options.aabb(HeightRange {
+            min: -0.5,
+            max: 0.25,
+        });
+        assert_eq!(min, Vec3::new(-256.0, -52.0, -128.0));
+        assert_eq!(max, Vec3::new(256.0, 27.0, 128.0));
+        // The outermost vertices are half a patch beyond the outermost patch centers.
+        assert!(min.x < options.min_x() && max.x > options.max_x());
+        assert!(min.z < options.min_y() && max.z > options.max_y());
+    }
+
+    #[test]
+    fn uv_at_non_square_corners() {
+        let options = non_square_options();
+        let (min_x, max_x) = (options.min_x(), options.max_x());
+        let (min_y, max_y) = (options.min_y(), options.max_y());
+        assert_uv_eq(options.uv_at(Vec3::new(min_x, 0.0, min_y)).0, Vec2::new(0.0, 0.0));
+        assert_uv_eq(options.uv_at(Vec3::new(max_x, 0.0, min_y)).0, Vec2::new(1.0, 0.0));
+        assert_uv_eq(options.uv_at(Vec3::new(min_x, 0.0, max_y)).0, Vec2::new(0.0, 1.0));
+        assert_uv_eq(options.uv_at(Vec3::new(max_x, 0.0, max_y)).0, Vec2::new(1.0, 1.0));
+        assert_uv_eq(options.uv_at(Vec3::ZERO).0, Vec2::new(0.5, 0.5));
+    }
+
+    #[test]
+    fn uv_at_non_square_matches_patch_uvs() {
+        let options = non_square_options();
+        let last = options.patch_resolution - 1;
+        for (x, y) in [(0, 0), (last, 0), (0, last), (last, last)] {
+            let coords = options.patch_coords(x, y);
+            let uv = options.uv_at(Vec3::new(coords.x, 0.0, coords.y));
+            assert_uv_eq(uv.0, options.patch_uvs(x, y));
+        }
+    }
+
+    #[test]
+    fn clicked_position_brushes_expected_texel() {
+        let options = non_square_options();
+        // A 2048x1024 heightmap has its edge texel centers on the edges of the terrain.
+        let (width, height) = (2048, 1024);
+        let (min_x, max_x) = (options.min_x(), options.max_x());
+        let (min_y, max_y) = (options.min_y(), options.max_y());
+        let texel =
+            |x: f32, z: f32| texel_at_uv(options.uv_at(Vec3::new(x, 0.0, z)), width, height);
+        assert_eq!(texel(min_x, min_y), IVec2::new(0, 0));
+        assert_eq!(texel(max_x, max_y), IVec2::new(2047, 1023));
+        // Texel 100 is a hundred texel widths away from the first texel center.
+        let texel_size = options.texel_spacing(width, height);
+        let clicked = Vec2::new(min_x, min_y) + texel_size * Vec2::new(100.0, 50.0);
+        assert_eq!(texel(clicked.x, clicked.y), IVec2::new(100, 50));
+        // Clicking just short of halfway to the next texel still hits the same texel.
+        let clicked = clicked + texel_size * 0.45;
+        assert_eq!(texel(clicked.x, clicked.y), IVec2::new(100, 50));
+    }
+
+    #[test]
+    fn texel_spacing_spans_terrain() {
+        let options = non_square_options();
+        let spacing = options.texel_spacing(2048, 1024);
+        let extent =
+            Vec2::new(options.max_x() - options.min_x(), options.max_y() - options.min_y());
+        assert_uv_eq(spacing * Vec2::new(2047.0, 1023.0), extent);
+        assert!(!options.normals_differ(&options));
+        let steeper = TerrainOptions {
+            vertical_scale: 200.0,
+            ..options
+        };
+        assert!(steeper.normals_differ(&options));
+    }
+
+    #[test]
+    fn normal_params_use_fitted_spacing() {
+        // Options that were not fitted to the heightmap yet, the depth is still square
+        let options = TerrainOptions {
+            horizontal_scale: Vec2::new(512.0, 512.0),
+            ..non_square_options()
+        };
+        let params = crate::NormalParams::new(&options, 2048, 1024);
+        assert_uv_eq(params.texel_spacing, non_square_options().texel_spacing(2048, 1024));
+    }
+
+    #[test]
+    fn world_radius_covers_texel_spacing() {
+        let options = non_square_options();
+        let (width, height) = (2048, 1024);
+        let spacing = options.texel_spacing(width, height);
+        let texels_per_uv = Vec2::new(width as f32 - 1.0, height as f32 - 1.0);
+        // The radius is rounded up so the brush covers the full area.
+        let radius = WorldRadius::new(spacing.min_element() * 9.5);
+        let texels = options.scaled_texel_radius(Vec3::ZERO, radius, texels_per_uv);
+        assert_eq!(texels, TexelRadius(10));
+        let radius = WorldRadius::new(spacing.min_element() * 10.5);
+        let texels = options.scaled_texel_radius(Vec3::ZERO, radius, texels_per_uv);
+        assert_eq!(texels, TexelRadius(11));
+    }
+
+    #[test]
+    fn detail_texel_wraps() {
+        let options = TerrainOptions {
+            detail_tiling: 4.0,
+            ..non_square_options()
+        };
+        assert_eq!(options.detail_texel_at_uv(Uv(Vec2::ZERO), 256, 256), IVec2::ZERO);
+        assert_eq!(
+            options.detail_texel_at_uv(Uv(Vec2::new(0.25, 0.125)), 256, 256),
+            IVec2::new(0, 128)
+        );
+    }
+
+    #[test]
+    fn detail_uv_wraps() {
+        let options = TerrainOptions {
+            detail_tiling: 4.0,
+            ..non_square_options()
+        };
+        assert_uv_eq(options.detail_uv(Uv::new(Vec2::new(0.1, 0.3))), Vec2::new(0.4, 0.2));
+        assert_uv_eq(options.detail_uv(Uv::new(Vec2::new(0.5, 0.75))), Vec2::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn normal_strength_only_changes_normals() {
+        let options = non_square_options();
+        let stronger = TerrainOptions {
+            normal_strength: 2.0,
+            ..options
+        };
+        assert!(stronger.normals_differ(&options));
+        assert!(!stronger.slopes_differ(&options));
+        let taller = TerrainOptions {
+            vertical_scale: 200.0,
+            ..options
+        };
+        assert!(taller.normals_differ(&options));
+        assert!(taller.slopes_differ(&options));
+    }
+
+    #[test]
+    fn border_mode_changes_slopes() {
+        let options = non_square_options();
+        let wrapping = TerrainOptions {
+            border_mode: BorderMode::Wrap,
+            ..options
+        };
+        assert!(wrapping.slopes_differ(&options));
+        assert!(wrapping.normals_differ(&options));
+        assert_eq!(wrapping.border_mode.address_mode(), vk::SamplerAddressMode::REPEAT);
+    }
+}
@@ -1,14 +1,20 @@
-use anyhow::Result;
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+
+use anyhow::{anyhow, Result};
 use error::publish_error;
 use inject::{ErasedStorage, DI};
 use log::error;
-use scheduler::EventBus;
+use phobos::wsi::frame::FRAMES_IN_FLIGHT;
+use scheduler::{Event, EventBus};
 use slotmap::HopSlotMap;
 use tokio::task::JoinHandle;
 use util::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-use crate::asset::Asset;
+use crate::asset::{Asset, ProgressReporter};
 use crate::handle::Handle;
+use crate::reload::AssetWatcher;
 
 /// Either a reference to an asset, or a marker indicating that the asset is still loading.
 pub enum AssetRef<'a, A> {
@@ -17,6 +23,22 @@ pub enum AssetRef<'a, A> {
     Ready(&'a A),
 }
 
+/// Published once an asset finished loading. Subscribe to the event of a specific asset type
+/// to be notified of the assets of that type, without having to wait on them.
+pub struct AssetLoadedEvent<A> {
+    pub handle: Handle<A>,
+}
+
+/// Published once an asset failed to load, see [`AssetLoadedEvent`].
+pub struct AssetFailedEvent<A> {
+    pub handle: Handle<A>,
+    pub error: String,
+}
+
+impl<A: 'static> Event for AssetLoadedEvent<A> {}
+
+impl<A: 'static> Event for AssetFailedEvent<A> {}
+
 /// A message sent from the asset loading thread when it finishes.
 #[derive(Debug, Copy, Clone)]
 enum AssetLoadMessage {
@@ -29,15 +51,36 @@ type AssetMessageReceiver = tokio::sync::broadcast::Receiver<AssetLoadMessage>;
 
 // An entry in the asset storage
 enum AssetEntry<A: Send + 'static> {
-    Pending(JoinHandle<()>, AssetMessageReceiver),
+    Pending {
+        task: JoinHandle<()>,
+        /// Kept so waiters can be notified when the load is cancelled.
+        sender: AssetMessageSender,
+        receiver: AssetMessageReceiver,
+        progress: ProgressReporter,
+    },
     Failed(anyhow::Error),
     Ready(A),
 }
 
+/// Message of a caught panic, if it has one.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Number of frames a replaced asset is kept alive for, so frames in flight can finish using it.
+const RETIRE_FRAMES: u32 = FRAMES_IN_FLIGHT as u32 + 1;
+
 /// Holds all assets and exposes utilities to load them asynchronously
 pub struct AssetStorage {
     inner: RwLock<AssetStorageInner>,
-    bus: EventBus<DI>,
+    pub(crate) watcher: RwLock<AssetWatcher>,
+    /// Replaced assets together with the number of frames they are kept alive for.
+    retired: RwLock<Vec<(u32, Box<dyn Any + Send>)>>,
+    pub(crate) bus: EventBus<DI>,
 }
 
 /// Can be used to wait on an asset, or check its status.
@@ -47,6 +90,12 @@ enum PollResult {
     Ready,
 }
 
+/// Number of assets of a type that are `(pending, ready, failed)`.
+pub type AssetStatusCounts = (usize, usize, usize);
+
+/// Counts the asset states in the container of a single asset type.
+type StatusCounter = fn(&ErasedStorage) -> AssetStatusCounts;
+
 /// Stores all assets of a given type.
 struct AssetContainer<A: Send + 'static> {
     items: HopSlotMap<Handle<A>, AssetEntry<A>>,
@@ -55,6 +104,30 @@ struct AssetContainer<A: Send + 'static> {
 #[derive(Default)]
 struct AssetStorageInner {
     containers: ErasedStorage,
+    /// Since the containers are type-erased, we keep a counter function for every asset type
+    /// so the status of all types can be queried at once. Keyed by asset type name.
+    status_counters: HashMap<&'static str, StatusCounter>,
+}
+
+/// Strips the module paths from a type name, so `assets::texture::Texture<assets::Rgba<u8>>`
+/// becomes `Texture<Rgba<u8>>`.
+fn short_type_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    // Start of the current path segment in `result`
+    let mut segment = 0;
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            result.truncate(segment);
+            continue;
+        }
+        result.push(c);
+        if !(c.is_alphanumeric() || c == '_') {
+            segment = result.len();
+        }
+    }
+    result
 }
 
 impl<A: Send + 'static> AssetEntry<A> {
@@ -62,7 +135,9 @@ impl<A: Send + 'static> AssetEntry<A> {
     /// Promise object.
     pub fn as_ref(&self) -> AssetRef<A> {
         match self {
-            AssetEntry::Pending(_, _) => AssetRef::Pending,
+            AssetEntry::Pending {
+                ..
+            } => AssetRef::Pending,
             AssetEntry::Failed(err) => AssetRef::Failed(err),
             AssetEntry::Ready(asset) => AssetRef::Ready(asset),
         }
@@ -85,9 +160,35 @@ impl<A: Send + 'static> AssetContainer<A> {
     pub fn new() -> Self {
         Self::default()
     }
+
+    pub fn status_counts(&self) -> AssetStatusCounts {
+        self.items
+            .values()
+            .fold((0, 0, 0), |(pending, ready, failed), entry| match entry {
+                AssetEntry::Pending {
+                    ..
+                } => (pending + 1, ready, failed),
+                AssetEntry::Ready(_) => (pending, ready + 1, failed),
+                AssetEntry::Failed(_) => (pending, ready, failed + 1),
+            })
+    }
+
+    /// Counts the asset states in the container of this type, if it exists.
+    fn status_counts_erased(containers: &ErasedStorage) -> AssetStatusCounts {
+        containers
+            .read_sync::<AssetContainer<A>>()
+            .map(|container| container.status_counts())
+            .unwrap_or_default()
+    }
 }
 
 impl AssetStorageInner {
+    /// Registers the status counter for a new asset type.
+    fn register_container<A: Send + 'static>(&mut self) {
+        self.status_counters
+            .insert(std::any::type_name::<A>(), AssetContainer::<A>::status_counts_erased);
+    }
+
     /// Create a new container for a given asset type and acquire a reader lock to it.
     /// Calls the given function with this reader lock.
     fn with_new_container<A, F, R>(&mut self, f: F) -> R
@@ -97,6 +198,7 @@ impl AssetStorageInner {
         // Create a new container and put it inside the registry
         self.containers
             .put_sync::<AssetContainer<A>>(AssetContainer::new());
+        self.register_container::<A>();
         // Acquire a reader lock and pass it to the callback
         let container = self.containers.read_sync::<AssetContainer<A>>().unwrap();
         f(container)
@@ -110,6 +212,7 @@ impl AssetStorageInner {
         F: FnOnce(RwLockWriteGuard<AssetContainer<A>>) -> R, {
         self.containers
             .put_sync::<AssetContainer<A>>(AssetContainer::new());
+        self.register_container::<A>();
         let container = self.containers.write_sync::<AssetContainer<A>>().unwrap();
         f(container)
     }
@@ -119,7 +222,7 @@ impl AssetStorage {
     // Simple logging for now, we can add an event for this later and let systems subscribe to it.
     fn report_failure(bus: &EventBus<DI>, error: &anyhow::Error) {
         error!("Error loading asset: {error}");
-        publish_error!(bus, "Error loading asset: {error}");
+        publish_error!(bus, source = "asset", "Error loading asset: {error}");
     }
 
     /// Acquire a read lock to the asset container and call the given callback with this lock.
@@ -174,32 +277,42 @@ impl AssetStorage {
         }
     }
 
+    /// Store the result of loading an asset and notify all threads waiting on it. Returns the
+    /// error message if the load failed, or `None` if the load was cancelled.
     fn resolve_asset_load<A: Asset + Send + 'static>(
         &self,
         key: Handle<A>,
         result: Result<A>,
         sender: AssetMessageSender,
-    ) {
+    ) -> Option<Result<(), String>> {
         self.with_mut_container(|mut container| {
-            // We can unwrap because insert_with_key returns first.
-            // We guarantee this, because this `with_mut_container` blocks until
+            // The entry always exists here unless it was deleted, because insert_with_key returns
+            // first. We guarantee this, because this `with_mut_container` blocks until
             // the calling `load()` returns.
-            let entry = container.items.get_mut(key).unwrap();
-            *entry = match result {
+            let entry = container.items.get_mut(key)?;
+            // If the load was cancelled, waiters were already notified and the result is dropped,
+            // freeing any resources it allocated. It was never visible, so it cannot be in use.
+            if !matches!(entry, AssetEntry::Pending { .. }) {
+                return None;
+            }
+            let (new_entry, status) = match result {
                 Ok(value) => {
                     // We can send this message before updating the stored asset, because we are in a lock.
                     // The task waiting for the sender will have to wait until this lock is released anyway,
                     // so it can't race with the insertion below.
                     sender.send(AssetLoadMessage::Success).unwrap();
-                    AssetEntry::Ready(value)
+                    (AssetEntry::Ready(value), Ok(()))
                 }
                 Err(err) => {
                     Self::report_failure(&self.bus, &err);
                     sender.send(AssetLoadMessage::Fail).unwrap();
-                    AssetEntry::Failed(err)
+                    let message = err.to_string();
+                    (AssetEntry::Failed(err), Err(message))
                 }
             };
-        });
+            *entry = new_entry;
+            Some(status)
+        })
     }
 
     fn asset_load_task<A: Asset + Send + 'static>(
@@ -207,13 +320,35 @@ impl AssetStorage {
         info: A::LoadInfo,
         bus: EventBus<DI>,
         sender: AssetMessageSender,
+        progress: ProgressReporter,
     ) {
-        // First load the asset so we don't hold the DI lock for long
-        let result = A::load(info, bus.clone());
-        let di = bus.data().read().unwrap();
-        let assets = di.get::<AssetStorage>().unwrap();
-        // Put the loaded asset in the storage and send a message to all threads waiting on it.
-        assets.resolve_asset_load(key, result, sender);
+        // First load the asset so we don't hold the DI lock for long. A panicking load is
+        // treated as a failed one, otherwise the entry stays pending and its waiters never wake.
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            A::load_with_progress(info, bus.clone(), &progress)
+        }))
+        .unwrap_or_else(|panic| Err(anyhow!("Asset load panicked: {}", panic_message(&*panic))));
+        let status = {
+            let di = bus.data().read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            // Put the loaded asset in the storage and send a message to all threads waiting on it.
+            assets.resolve_asset_load(key, result, sender)
+        };
+        // Events are published without holding any locks, so handlers can access the asset.
+        match status {
+            Some(Ok(())) => {
+                let _ = bus.publish(AssetLoadedEvent {
+                    handle: key,
+                });
+            }
+            Some(Err(error)) => {
+                let _ = bus.publish(AssetFailedEvent {
+                    handle: key,
+                    error,
+                });
+            }
+            None => {}
+        }
     }
 
     fn insert_with_key<A: Asset + Send + 'static>(
@@ -224,9 +359,63 @@ impl AssetStorage {
         // This channel will be used by with_when_ready to wait for the asset to be loaded.
         let (tx, rx) = tokio::sync::broadcast::channel(1);
         // Spawn a background task for loading the asset. We keep the JoinHandle so we can allow canceling the task instead of detaching it.
-        let task = tokio::task::spawn_blocking(move || Self::asset_load_task(key, info, bus, tx));
+        let sender = tx.clone();
+        let progress = ProgressReporter::new();
+        let reporter = progress.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            Self::asset_load_task(key, info, bus, tx, reporter)
+        });
         // The receiver is stored in the asset entry so we can wait on it.
-        AssetEntry::Pending(task, rx)
+        AssetEntry::Pending {
+            task,
+            sender,
+            receiver: rx,
+            progress,
+        }
+    }
+
+    /// Replace a loaded asset with a new version of it. The old version may still be used by
+    /// frames in flight, so it is dropped after a few frames, see [`Self::next_frame`].
+    /// Assets that are still loading are not replaced, and if the new version failed to load
+    /// the old one is kept. Returns whether the asset was replaced.
+    pub(crate) fn replace_loaded<A: Asset + Send + 'static>(
+        &self,
+        handle: Handle<A>,
+        result: Result<A>,
+    ) -> bool {
+        let value = match result {
+            Ok(value) => value,
+            Err(err) => {
+                Self::report_failure(&self.bus, &err);
+                return false;
+            }
+        };
+        let old = self.with_mut_container(|mut container| {
+            let entry = container.items.get_mut(handle)?;
+            match entry {
+                AssetEntry::Pending {
+                    ..
+                } => None,
+                _ => Some(std::mem::replace(entry, AssetEntry::Ready(value))),
+            }
+        });
+        match old {
+            None => false,
+            Some(AssetEntry::Ready(old)) => {
+                self.retire(old);
+                true
+            }
+            Some(_) => true,
+        }
+    }
+
+    /// Keep an asset alive until no frame in flight can be using it anymore. This also works
+    /// for GPU resources that are not stored as assets, such as scratch images of brushes.
+    pub fn retire<A: Send + 'static>(&self, asset: A) {
+        self.retired
+            .write()
+            .unwrap()
+            .push((RETIRE_FRAMES, Box::new(asset)));
     }
 
     /// Check the status of an asset and obtain an awaitable receiver that can be used to
@@ -242,7 +431,10 @@ impl AssetStorage {
                     //   until the asset is inserted, and it will be in the Ready state
                     // * If the message was not yet received, this is guaranteed to receive it at some point in the
                     //   future
-                    AssetEntry::Pending(_, rx) => PollResult::Pending(rx.resubscribe()),
+                    AssetEntry::Pending {
+                        receiver: rx,
+                        ..
+                    } => PollResult::Pending(rx.resubscribe()),
                     AssetEntry::Failed(_) => PollResult::Failed,
                     AssetEntry::Ready(_) => PollResult::Ready,
                 },
@@ -256,6 +448,8 @@ impl AssetStorage {
     pub fn new_in_inject(bus: EventBus<DI>) {
         let this = Self {
             inner: RwLock::with_name(AssetStorageInner::default(), "AssetStorage"),
+            watcher: RwLock::with_name(AssetWatcher::default(), "AssetWatcher"),
+            retired: RwLock::with_name(Vec::new(), "RetiredAssets"),
             bus: bus.clone(),
         };
         // Synchronization is handled internally already, so we do not use
@@ -337,17 +531,85 @@ impl AssetStorage {
         self.with_if_ready(handle, |_| {}).is_some()
     }
 
+    /// Returns the progress of an asset that is still loading as a value between 0.0 and 1.0,
+    /// together with the stage it is in. Returns `None` if the asset is not loading.
+    /// Assets that do not report progress stay at 0.0 until they are loaded.
+    pub fn progress<A: Asset + Send + 'static>(&self, handle: Handle<A>) -> Option<(f32, String)> {
+        self.with_container(|container| match container.items.get(handle)? {
+            AssetEntry::Pending {
+                progress,
+                ..
+            } => progress.get(),
+            _ => None,
+        })
+    }
+
+    /// Returns the number of assets of type `A` that are `(pending, ready, failed)`.
+    pub fn status_counts<A: Asset + Send + 'static>(&self) -> AssetStatusCounts {
+        self.with_container::<A, _, _>(|container| container.status_counts())
+    }
+
+    /// Returns the number of assets that are `(pending, ready, failed)` for every asset type
+    /// that was used so far, keyed by the name of the asset type.
+    pub fn all_status_counts(&self) -> HashMap<String, AssetStatusCounts> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .status_counters
+            .iter()
+            .map(|(name, counter)| (short_type_name(name), counter(&inner.containers)))
+            .collect()
+    }
+
     /// Load a new asset and return a handle to it. This will spawn a new blocking task in a background thread.
     /// This means that this function is not blocking, and returns a handle immediately.
+    /// If the asset is loaded from a file, it is loaded again whenever the file is modified.
     pub fn load<A: Asset + Send + 'static>(&self, info: A::LoadInfo) -> Handle<A> {
+        let source = A::source(&info);
         // Acquire a writer lock to the container, since we need to insert a new key
-        self.with_mut_container(|mut container| {
+        let handle = self.with_mut_container(|mut container| {
             container
                 .items
                 .insert_with_key(|key| Self::insert_with_key(key, info, self.bus.clone()))
+        });
+        if let Some(source) = source {
+            self.watch_source(handle, source);
+        }
+        handle
+    }
+
+    /// Cancel loading an asset. Threads waiting on it in [`Self::with_when_ready`] return `None`,
+    /// and the asset is marked as failed. A load that already started cannot be interrupted, so
+    /// its result is dropped as soon as it finishes.
+    /// Returns whether the load was cancelled, cancelling an asset that is not loading does nothing.
+    pub fn cancel_load<A: Send + 'static>(&self, handle: Handle<A>) -> bool {
+        self.with_mut_container(|mut container| {
+            let Some(entry) = container.items.get_mut(handle) else { return false };
+            let AssetEntry::Pending {
+                task,
+                sender,
+                ..
+            } = entry
+            else {
+                return false;
+            };
+            // Prevents the task from running if it was not started yet.
+            task.abort();
+            // The receiver in the entry is still alive here, so this cannot fail.
+            sender.send(AssetLoadMessage::Fail).unwrap();
+            *entry = AssetEntry::Failed(anyhow!("Asset load was cancelled"));
+            true
         })
     }
 
+    /// Drops replaced assets once no frame in flight can be using them anymore. Must be called
+    /// once per frame.
+    pub fn next_frame(&self) {
+        self.retired.write().unwrap().retain_mut(|(frames, _)| {
+            *frames = frames.saturating_sub(1);
+            *frames > 0
+        });
+    }
+
     /// Frees up memory used by asset entries that failed to load.
     pub fn clear_failed_assets<A: Send + 'static>(&self) {
         self.with_mut_container::<A, _, _>(|mut container| {
@@ -358,7 +620,20 @@ impl AssetStorage {
         });
     }
 
-    /// Immediately delete an asset.
+    /// Delete an asset. The handle becomes invalid immediately, but the asset itself is only
+    /// dropped once no frame in flight can be using it anymore, see [`Self::next_frame`].
+    /// Assets that are still loading are cancelled, see [`Self::cancel_load`].
+    pub fn schedule_delete<A: Send + 'static>(&self, handle: Handle<A>) {
+        self.cancel_load(handle);
+        self.watcher.write().unwrap().unwatch(handle);
+        let entry = self.with_mut_container(|mut container| container.items.remove(handle));
+        if let Some(AssetEntry::Ready(asset)) = entry {
+            self.retire(asset);
+        }
+    }
+
+    /// Immediately delete an asset. Prefer [`Self::schedule_delete`], unless the asset is known
+    /// to be idle.
     /// # Safety
     /// This is marked unsafe because the asset could still be in use on the GPU when this is called.
     pub unsafe fn delete_asset<A: Send + 'static>(&self, handle: Handle<A>) {
@@ -370,16 +645,20 @@ impl AssetStorage {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
     use std::time::Duration;
 
     use anyhow::bail;
     use inject::DI;
     use log::info;
-    use scheduler::EventBus;
+    use scheduler::{EventBus, EventContext, StoredSystem, System};
     use tokio::time::sleep;
 
-    use crate::asset::Asset;
-    use crate::storage::AssetStorage;
+    use crate::asset::{Asset, ProgressReporter};
+    use crate::handle::Handle;
+    use crate::storage::{
+        short_type_name, AssetFailedEvent, AssetLoadedEvent, AssetStorage, RETIRE_FRAMES,
+    };
 
     struct MyAsset {
         data: String,
@@ -392,12 +671,28 @@ mod tests {
             info!("Hi");
             if info == "fail" {
                 bail!("invalid load info");
+            } else if info == "panic" {
+                panic!("load panicked");
+            } else if info == "slow" {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(MyAsset {
+                    data: info,
+                })
             } else {
                 Ok(MyAsset {
                     data: info,
                 })
             }
         }
+
+        fn load_with_progress(
+            info: Self::LoadInfo,
+            bus: EventBus<DI>,
+            progress: &ProgressReporter,
+        ) -> anyhow::Result<Self> {
+            progress.report(0.5, "Sleeping");
+            Self::load(info, bus)
+        }
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -427,4 +722,187 @@ mod tests {
         // Should have failed by now
         assert!(assets.with_if_ready(handle, |_| {}).is_none());
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_load_panic() {
+        let inject = DI::new();
+        let bus = EventBus::new(inject.clone());
+        AssetStorage::new_in_inject(bus);
+        let handle = {
+            let di = inject.read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            assets.load::<MyAsset>("panic".to_owned())
+        };
+        // Waiters are woken up instead of blocking forever
+        let waiter = tokio::task::spawn_blocking(move || {
+            let di = inject.read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            let result = assets.with_when_ready(handle, |_| {});
+            (result, assets.status_counts::<MyAsset>())
+        });
+        let (result, counts) = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("waiting on a panicked load must not block")
+            .unwrap();
+        assert!(result.is_none());
+        assert_eq!(counts, (0, 0, 1));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_status_counts() {
+        let inject = DI::new();
+        let bus = EventBus::new(inject.clone());
+        AssetStorage::new_in_inject(bus);
+        let di = inject.read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        assets.load::<MyAsset>("success".to_owned());
+        assets.load::<MyAsset>("success".to_owned());
+        assets.load::<MyAsset>("fail".to_owned());
+        // Wait for loads to be completed
+        sleep(Duration::from_secs(1)).await;
+        assert_eq!(assets.status_counts::<MyAsset>(), (0, 2, 1));
+        let all = assets.all_status_counts();
+        assert_eq!(all.get("MyAsset"), Some(&(0, 2, 1)));
+    }
+
+    /// Records the handles of all loaded and failed assets.
+    #[derive(Default, Clone)]
+    struct LoadEvents {
+        loaded: Arc<Mutex<Vec<Handle<MyAsset>>>>,
+        failed: Arc<Mutex<Vec<(Handle<MyAsset>, String)>>>,
+    }
+
+    impl System<DI> for LoadEvents {
+        fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+            event_bus.subscribe(system, handle_loaded);
+            event_bus.subscribe(system, handle_failed);
+        }
+    }
+
+    fn handle_loaded(
+        events: &mut LoadEvents,
+        event: &AssetLoadedEvent<MyAsset>,
+        _ctx: &mut EventContext<DI>,
+    ) -> anyhow::Result<()> {
+        events.loaded.lock().unwrap().push(event.handle);
+        Ok(())
+    }
+
+    fn handle_failed(
+        events: &mut LoadEvents,
+        event: &AssetFailedEvent<MyAsset>,
+        _ctx: &mut EventContext<DI>,
+    ) -> anyhow::Result<()> {
+        let error = event.error.clone();
+        events.failed.lock().unwrap().push((event.handle, error));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_load_events() {
+        let inject = DI::new();
+        let bus = EventBus::new(inject.clone());
+        let events = LoadEvents::default();
+        bus.add_system(events.clone());
+        AssetStorage::new_in_inject(bus);
+        let (success, fail) = {
+            let di = inject.read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            (
+                assets.load::<MyAsset>("success".to_owned()),
+                assets.load::<MyAsset>("fail".to_owned()),
+            )
+        };
+        // Wait for loads to be completed
+        sleep(Duration::from_secs(1)).await;
+        assert_eq!(*events.loaded.lock().unwrap(), vec![success]);
+        assert_eq!(*events.failed.lock().unwrap(), vec![(fail, "invalid load info".to_owned())]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_schedule_delete() {
+        let inject = DI::new();
+        let bus = EventBus::new(inject.clone());
+        AssetStorage::new_in_inject(bus);
+        let di = inject.read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        let handle = assets.load::<MyAsset>("success".to_owned());
+        // Wait for load to be completed
+        sleep(Duration::from_secs(1)).await;
+        assets.schedule_delete(handle);
+        assert!(assets.with(handle, |_| {}).is_none());
+        // The asset is kept alive for the frames in flight
+        for _ in 1..RETIRE_FRAMES {
+            assets.next_frame();
+            assert_eq!(assets.retired.read().unwrap().len(), 1);
+        }
+        assets.next_frame();
+        assert!(assets.retired.read().unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_progress() {
+        let inject = DI::new();
+        let bus = EventBus::new(inject.clone());
+        AssetStorage::new_in_inject(bus);
+        let di = inject.read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        let handle = assets.load::<MyAsset>("slow".to_owned());
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(assets.progress(handle), Some((0.5, "Sleeping".to_owned())));
+        // Wait for load to be completed
+        sleep(Duration::from_secs(1)).await;
+        assert!(assets.is_ready(handle));
+        assert_eq!(assets.progress(handle), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancel_load() {
+        let inject = DI::new();
+        let bus = EventBus::new(inject.clone());
+        AssetStorage::new_in_inject(bus);
+        let (slow, done) = {
+            let di = inject.read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            (
+                assets.load::<MyAsset>("slow".to_owned()),
+                assets.load::<MyAsset>("success".to_owned()),
+            )
+        };
+        // Start waiting on the slow asset before it is cancelled
+        let waiter = {
+            let inject = inject.clone();
+            tokio::task::spawn_blocking(move || {
+                let di = inject.read().unwrap();
+                let assets = di.get::<AssetStorage>().unwrap();
+                assets.with_when_ready(slow, |_| {})
+            })
+        };
+        sleep(Duration::from_millis(100)).await;
+        {
+            let di = inject.read().unwrap();
+            let assets = di.get::<AssetStorage>().unwrap();
+            assert!(assets.cancel_load(slow));
+            // Cancelling a finished load does nothing
+            assert!(!assets.cancel_load(done));
+            assert!(assets.is_ready(done));
+        }
+        assert!(waiter.await.unwrap().is_none());
+        // Wait for the cancelled load to finish, its result must be dropped
+        sleep(Duration::from_secs(1)).await;
+        let di = inject.read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        assert!(!assets.is_ready(slow));
+        assert_eq!(assets.status_counts::<MyAsset>(), (0, 1, 1));
+    }
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!(short_type_name("assets::Heightmap"), "Heightmap");
+        assert_eq!(
+            short_type_name("assets::texture::Texture<assets::texture::format::Rgba<u8>>"),
+            "Texture<Rgba<u8>>"
+        );
+        assert_eq!(short_type_name("(a::B, c::D)"), "(B, D)");
+    }
 }
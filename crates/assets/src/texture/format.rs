@@ -4,6 +4,7 @@ use half::f16;
 use image::DynamicImage;
 use phobos::vk;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::slice::ParallelSlice;
 
 use crate::texture::buffer::ImageBuffer;
 use crate::texture::pixel::{LumaPixel, Pixel, RgbPixel, RgbaPixel};
@@ -37,6 +38,15 @@ pub struct SRgba<T> {
     _marker: PhantomData<T>,
 }
 
+/// sRGB encoded RGBA data, stored in a UNORM image.
+/// sRGB formats generally cannot be used as storage images, so textures that are painted on
+/// use this format instead. Shaders reading or writing these textures have to convert between
+/// sRGB and linear themselves, using the functions in `color_space.hlsl`.
+#[derive(Debug)]
+pub struct EncodedSRgba<T> {
+    _marker: PhantomData<T>,
+}
+
 impl TextureFormat for Grayscale<u8> {
     type Pixel = LumaPixel<u8>;
     const VK_FORMAT: vk::Format = vk::Format::R8_UNORM;
@@ -62,6 +72,15 @@ impl TextureFormat for Grayscale<f32> {
     const VK_FORMAT: vk::Format = vk::Format::R32_SFLOAT;
 
     fn from_dynamic_image(img: DynamicImage) -> ImageBuffer<Self::Pixel> {
+        // Float images, such as EXR files, keep their values instead of being quantized.
+        if matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)) {
+            let raw = img.into_rgb32f().into_raw();
+            let luma = raw
+                .par_chunks_exact(3)
+                .map(|rgb| 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2])
+                .collect::<Vec<_>>();
+            return ImageBuffer::from_raw(luma);
+        }
         let img = img.into_luma16();
         let raw = img.into_raw();
         let as_fp = raw.into_par_iter().map(|px| px as f32).collect::<Vec<_>>();
@@ -113,3 +132,13 @@ impl TextureFormat for SRgba<u8> {
         ImageBuffer::from_raw(img.into_raw())
     }
 }
+
+impl TextureFormat for EncodedSRgba<u8> {
+    type Pixel = RgbaPixel<u8>;
+    const VK_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+    fn from_dynamic_image(img: DynamicImage) -> ImageBuffer<Self::Pixel> {
+        let img = img.into_rgba8();
+        ImageBuffer::from_raw(img.into_raw())
+    }
+}
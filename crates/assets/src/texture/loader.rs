@@ -1,29 +1,35 @@
 use std::io::Cursor;
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use error::publish_success;
 use gfx::{upload_image, SharedContext};
+use image::imageops::FilterType;
+use image::{GenericImageView, ImageFormat};
 use inject::DI;
 use log::{info, trace};
 use phobos::vk;
 use scheduler::EventBus;
 use thread::io::read_file;
 
+use crate::asset::ProgressReporter;
+use crate::texture::buffer::ImageBuffer;
 use crate::texture::format::TextureFormat;
 use crate::texture::{Texture, TextureLoadInfo};
 
 pub(crate) fn load<F: TextureFormat>(
     info: TextureLoadInfo<F>,
     bus: EventBus<DI>,
+    progress: &ProgressReporter,
 ) -> Result<Texture<F>> {
     match info {
         TextureLoadInfo::FromPath {
             path,
             cpu_postprocess,
             usage_flags,
-        } => load_from_file(path, cpu_postprocess, usage_flags, bus),
+            resize,
+        } => load_from_file(path, cpu_postprocess, usage_flags, resize, bus, progress),
         TextureLoadInfo::FromRawGpu {
             image,
         } => Ok(Texture {
@@ -37,7 +43,9 @@ fn load_from_file<F: TextureFormat>(
     path: PathBuf,
     cpu_postprocess: Option<fn(u32, u32, &mut [F::Pixel]) -> Result<()>>,
     usage_flags: Option<vk::ImageUsageFlags>,
+    resize: Option<(u32, u32)>,
     bus: EventBus<DI>,
+    progress: &ProgressReporter,
 ) -> Result<Texture<F>> {
     let ctx = bus
         .data()
@@ -48,29 +56,61 @@ fn load_from_file<F: TextureFormat>(
         .unwrap();
 
     trace!("Loading texture {path:?}");
+    progress.report(0.0, "Reading file");
     let buffer = read_file(path.clone())?;
-    let reader = image::io::Reader::new(Cursor::new(buffer)).with_guessed_format()?;
-    let image = reader.decode()?;
-    let width = image.width();
-    let height = image.height();
-    trace!("texture size is {width}x{height}");
-    trace!("texture color type is {:?}", image.color());
-    let mut data = F::from_dynamic_image(image);
-    if let Some(f) = cpu_postprocess {
-        f(width, height, data.as_mut_pixel_slice())?;
-    }
+    let (width, height, data) = decode_file::<F>(buffer, &path, cpu_postprocess, resize, progress)?;
+    progress.report(0.8, "Uploading to GPU");
     let image = upload_image(
         ctx,
         data.as_raw_slice(),
         width,
         height,
         F::VK_FORMAT,
-        vk::ImageUsageFlags::SAMPLED | usage_flags.unwrap_or_default(),
+        // Transfer source usage allows reading the texture back, for example to save it.
+        vk::ImageUsageFlags::SAMPLED
+            | vk::ImageUsageFlags::TRANSFER_SRC
+            | usage_flags.unwrap_or_default(),
     )?;
     info!("Successfully loaded texture {path:?}");
-    publish_success!(bus, "Successfully loaded texture {path:?}");
+    publish_success!(bus, source = "asset", "Successfully loaded texture {path:?}");
     Ok(Texture {
         image,
         marker: PhantomData,
     })
 }
+
+/// Decodes the contents of an image file into the data that is uploaded to the GPU, in the
+/// layout of `F`. Returns the width and height of the image together with the data.
+pub(crate) fn decode_file<F: TextureFormat>(
+    buffer: Vec<u8>,
+    path: &Path,
+    cpu_postprocess: Option<fn(u32, u32, &mut [F::Pixel]) -> Result<()>>,
+    resize: Option<(u32, u32)>,
+    progress: &ProgressReporter,
+) -> Result<(u32, u32, ImageBuffer<F::Pixel>)> {
+    let mut reader = image::io::Reader::new(Cursor::new(buffer)).with_guessed_format()?;
+    // Fall back to the extension for formats that cannot be recognized from their contents
+    if reader.format().is_none() {
+        if let Ok(format) = ImageFormat::from_path(path) {
+            reader.set_format(format);
+        }
+    }
+    progress.report(0.1, "Decoding image");
+    let mut image = reader.decode()?;
+    if let Some((width, height)) = resize {
+        if (width, height) != image.dimensions() {
+            trace!("resizing texture from {:?} to {width}x{height}", image.dimensions());
+            image = image.resize_exact(width, height, FilterType::Triangle);
+        }
+    }
+    let width = image.width();
+    let height = image.height();
+    trace!("texture size is {width}x{height}");
+    trace!("texture color type is {:?}", image.color());
+    progress.report(0.6, "Processing image");
+    let mut data = F::from_dynamic_image(image);
+    if let Some(f) = cpu_postprocess {
+        f(width, height, data.as_mut_pixel_slice())?;
+    }
+    Ok((width, height, data))
+}
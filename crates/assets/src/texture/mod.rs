@@ -1,20 +1,23 @@
 use std::marker::PhantomData;
 use std::path::PathBuf;
 
-use anyhow::Result;
-use gfx::PairedImageView;
+use anyhow::{ensure, Result};
+use gfx::{PairedImageView, SharedContext};
 use inject::DI;
-use phobos::vk;
+use phobos::domain::All;
+use phobos::{vk, Buffer, IncompleteCmdBuffer, MemoryType, PipelineStage};
 use scheduler::EventBus;
 
-use crate::asset::Asset;
+use crate::asset::{Asset, AssetSource, ProgressReporter};
+use crate::texture::buffer::ImageBuffer;
 use crate::texture::format::TextureFormat;
+use crate::texture::pixel::Pixel;
 
 pub mod buffer;
 pub mod format;
 pub mod pixel;
 
-mod loader;
+pub(crate) mod loader;
 
 #[derive(Debug)]
 pub struct Texture<F: TextureFormat> {
@@ -29,6 +32,8 @@ pub enum TextureLoadInfo<F: TextureFormat> {
         cpu_postprocess: Option<fn(u32, u32, &mut [F::Pixel]) -> Result<()>>,
         // Additional usage flags
         usage_flags: Option<vk::ImageUsageFlags>,
+        // Resize the image to this width and height before processing and uploading it.
+        resize: Option<(u32, u32)>,
     },
     FromRawGpu {
         image: PairedImageView,
@@ -41,7 +46,40 @@ impl<F: TextureFormat + 'static> Asset for Texture<F> {
     fn load(info: Self::LoadInfo, bus: EventBus<DI>) -> Result<Self>
     where
         Self: Sized, {
-        loader::load(info, bus)
+        loader::load(info, bus, &ProgressReporter::default())
+    }
+
+    fn load_with_progress(
+        info: Self::LoadInfo,
+        bus: EventBus<DI>,
+        progress: &ProgressReporter,
+    ) -> Result<Self>
+    where
+        Self: Sized, {
+        loader::load(info, bus, progress)
+    }
+
+    fn source(info: &Self::LoadInfo) -> Option<AssetSource<Self::LoadInfo>> {
+        let TextureLoadInfo::FromPath {
+            path,
+            cpu_postprocess,
+            usage_flags,
+            resize,
+        } = info
+        else {
+            return None;
+        };
+        let (path, cpu_postprocess, usage_flags, resize) =
+            (path.clone(), *cpu_postprocess, *usage_flags, *resize);
+        Some(AssetSource {
+            path: path.clone(),
+            reload_info: Box::new(move || TextureLoadInfo::FromPath {
+                path: path.clone(),
+                cpu_postprocess,
+                usage_flags,
+                resize,
+            }),
+        })
     }
 }
 
@@ -53,4 +91,134 @@ impl<F: TextureFormat> Texture<F> {
     pub fn height(&self) -> u32 {
         self.image.height()
     }
+
+    /// Copies the contents of the texture back to the CPU. This waits for the GPU to finish,
+    /// so any work that modifies the texture must already be submitted. The texture must be in
+    /// the shader read only layout and have been created with transfer source usage, which
+    /// is the case for all textures loaded from a file.
+    pub fn read_back(&self, bus: &EventBus<DI>) -> Result<ImageBuffer<F::Pixel>> {
+        self.read_back_rect(
+            bus,
+            vk::Rect2D {
+                offset: vk::Offset2D::default(),
+                extent: vk::Extent2D {
+                    width: self.width(),
+                    height: self.height(),
+                },
+            },
+        )
+    }
+
+    /// Copies a single texel back to the CPU and returns its channels, see [`Texture::read_back`].
+    pub fn read_texel(
+        &self,
+        bus: &EventBus<DI>,
+        x: u32,
+        y: u32,
+    ) -> Result<Vec<<F::Pixel as Pixel>::SubPixel>> {
+        let rect = vk::Rect2D {
+            offset: vk::Offset2D {
+                x: x as i32,
+                y: y as i32,
+            },
+            extent: vk::Extent2D {
+                width: 1,
+                height: 1,
+            },
+        };
+        Ok(self.read_back_rect(bus, rect)?.into_raw())
+    }
+
+    /// Copies a region of the texture back to the CPU, see [`Texture::read_back`].
+    pub fn read_back_rect(
+        &self,
+        bus: &EventBus<DI>,
+        rect: vk::Rect2D,
+    ) -> Result<ImageBuffer<F::Pixel>> {
+        ensure!(
+            rect.offset.x >= 0
+                && rect.offset.y >= 0
+                && rect.offset.x as u32 + rect.extent.width <= self.width()
+                && rect.offset.y as u32 + rect.extent.height <= self.height(),
+            "Readback region {rect:?} is out of bounds for a texture of {}x{}",
+            self.width(),
+            self.height()
+        );
+        let mut ctx = bus
+            .data()
+            .read()
+            .unwrap()
+            .get::<SharedContext>()
+            .cloned()
+            .unwrap();
+        let view = &self.image.view;
+        let byte_size = rect.extent.width as u64
+            * rect.extent.height as u64
+            * std::mem::size_of::<F::Pixel>() as u64;
+        let buffer = Buffer::new(
+            ctx.device.clone(),
+            &mut ctx.allocator,
+            byte_size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryType::GpuToCpu,
+        )?;
+        let mut buffer_view = buffer.view_full();
+
+        let cmd = ctx.exec.on_domain::<All, _>(None, None)?.transition_image(
+            view,
+            PipelineStage::ALL_COMMANDS,
+            PipelineStage::TRANSFER,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::AccessFlags2::MEMORY_WRITE,
+            vk::AccessFlags2::TRANSFER_READ,
+        );
+        let region = vk::BufferImageCopy {
+            buffer_offset: buffer_view.offset(),
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: view.aspect(),
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D {
+                x: rect.offset.x,
+                y: rect.offset.y,
+                z: 0,
+            },
+            image_extent: vk::Extent3D {
+                width: rect.extent.width,
+                height: rect.extent.height,
+                depth: 1,
+            },
+        };
+        // SAFETY: The command buffer is in the recording state, and both the image and the
+        // buffer outlive the submission since we wait for it below.
+        unsafe {
+            ctx.device.cmd_copy_image_to_buffer(
+                cmd.handle(),
+                view.image(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer.handle(),
+                std::slice::from_ref(&region),
+            );
+        }
+        let cmd = cmd
+            .transition_image(
+                view,
+                PipelineStage::TRANSFER,
+                PipelineStage::ALL_COMMANDS,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::AccessFlags2::TRANSFER_READ,
+                vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            )
+            .finish()?;
+        ctx.exec.submit(cmd)?.wait()?;
+
+        let data = buffer_view.mapped_slice::<F::Pixel>()?;
+        Ok(ImageBuffer::from_raw(bytemuck::cast_slice(data).to_vec()))
+    }
 }
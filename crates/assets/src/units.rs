@@ -0,0 +1,85 @@
+//! Units used at the boundary between the brushes and the terrain.
+//!
+//! The terrain uses three coordinate systems:
+//! - World space, in meters. Y is up and the terrain lies in the XZ plane, centered on the
+//!   origin. Heights in the heightmap are normalized, the world height of a point is its value
+//!   in the heightmap multiplied by [`TerrainOptions::vertical_scale`].
+//! - Terrain uv space, running from 0 to 1 over the terrain mesh. See [`TerrainOptions::uv_at`].
+//! - Texel space of a single texture. Textures of the terrain do not all share the same size,
+//!   so texel coordinates and radii are only meaningful together with the texture they were
+//!   computed for.
+//!
+//! Converting between these spaces always needs the [`TerrainOptions`] of the terrain, so the
+//! types below can only be converted through it.
+
+use glam::{IVec2, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::texture::format::TextureFormat;
+use crate::texture::Texture;
+use crate::TerrainOptions;
+
+/// A radius in world space, in meters along the terrain plane.
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WorldRadius(pub f32);
+
+impl WorldRadius {
+    /// # Panics
+    /// In debug builds, if `meters` is negative or not finite.
+    pub fn new(meters: f32) -> Self {
+        debug_assert!(meters.is_finite() && meters >= 0.0, "invalid world radius {meters}");
+        Self(meters)
+    }
+
+    /// Convert this radius to a radius in texels on a base terrain texture, see
+    /// [`TerrainOptions::texel_radius`].
+    pub fn to_texels<F: TextureFormat>(
+        self,
+        options: &TerrainOptions,
+        center: Vec3,
+        texture: &Texture<F>,
+    ) -> TexelRadius {
+        options.texel_radius(center, self, texture)
+    }
+
+    /// Convert this radius to a radius in texels on the detail heightmap, see
+    /// [`TerrainOptions::detail_texel_radius`].
+    pub fn to_detail_texels<F: TextureFormat>(
+        self,
+        options: &TerrainOptions,
+        center: Vec3,
+        texture: &Texture<F>,
+    ) -> TexelRadius {
+        options.detail_texel_radius(center, self, texture)
+    }
+}
+
+/// A radius in texels, on the texture it was computed for.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TexelRadius(pub u32);
+
+/// Terrain uv coordinates, see [`TerrainOptions::uv_at`] for the convention.
+/// Values outside of the `[0, 1]` range are valid and lie outside of the terrain.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Uv(pub Vec2);
+
+impl Uv {
+    /// # Panics
+    /// In debug builds, if either coordinate is not finite.
+    pub fn new(uv: Vec2) -> Self {
+        debug_assert!(uv.is_finite(), "invalid terrain uv {uv}");
+        Self(uv)
+    }
+
+    /// Terrain uv coordinates of a world position, see [`TerrainOptions::uv_at`].
+    pub fn from_world(options: &TerrainOptions, world_pos: Vec3) -> Self {
+        options.uv_at(world_pos)
+    }
+
+    /// The texel located at these coordinates on a base terrain texture of the given size,
+    /// see [`texel_at_uv`](crate::texel_at_uv).
+    pub fn texel(self, width: u32, height: u32) -> IVec2 {
+        crate::texel_at_uv(self, width, height)
+    }
+}
@@ -0,0 +1,136 @@
+//! Baking the lighting of the terrain into its diffuse map, see [`BakeLightingEvent`].
+//!
+//! [`BakeLightingEvent`]: crate::BakeLightingEvent
+
+use anyhow::{bail, ensure, Result};
+use assets::texture::Texture;
+use assets::{DetailNormalMap, DiffuseMapFormat, Heightmap, NormalMap, TerrainOptions};
+use gfx::Samplers;
+use glam::Vec3;
+use inject::DI;
+use phobos::{vk, ComputeCmdBuffer, ImageView, IncompleteCommandBuffer, PipelineStage};
+use scheduler::EventBus;
+use world::World;
+
+use crate::undo::BrushTarget;
+use crate::util::{
+    get_terrain_info, prepare_for_read, prepare_for_write, submit_brush_work,
+    with_ready_detail_map, with_ready_detail_normal_map, with_ready_terrain, BrushDomain,
+};
+
+/// Push constants of the lighting bake shader.
+/// Kept in sync with `PC` in `bake_lighting.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct BakeParams {
+    sun_direction: [f32; 3],
+    ambient: f32,
+    detail_tiling: f32,
+    detail_strength: f32,
+    horizontal_scale: [f32; 2],
+}
+
+/// Terrain textures modified by baking the lighting.
+pub const BAKE_TARGETS: &[BrushTarget] = &[BrushTarget::Color];
+
+/// Textures the terrain normal is composed from, see `terrain_normal` in
+/// `terrain_surface.hlsl`.
+struct BakeNormals<'a> {
+    normals: &'a NormalMap,
+    /// View of the detail heightmap, `None` if the terrain has no detail layer.
+    detail: Option<&'a ImageView>,
+    detail_normals: &'a DetailNormalMap,
+    heights: &'a Heightmap,
+}
+
+fn record_bake<'q, D: BrushDomain>(
+    bus: &EventBus<DI>,
+    cmd: IncompleteCommandBuffer<'q, D>,
+    sun_direction: Vec3,
+    ambient: f32,
+    options: &TerrainOptions,
+    normals: &BakeNormals,
+    colors: &Texture<DiffuseMapFormat>,
+) -> Result<IncompleteCommandBuffer<'q, D>> {
+    let di = bus.data().read().unwrap();
+    let samplers = di.get::<Samplers>().unwrap();
+    // Without a detail layer we still need to bind something, so bind the base heightmap and
+    // disable the detail layer, like the terrain pass does.
+    let (detail_view, detail_strength) = match normals.detail {
+        None => (&normals.heights.image.image.view, 0.0),
+        Some(view) => (view, options.detail_strength),
+    };
+    let cmd = prepare_for_write(colors, cmd, PipelineStage::FRAGMENT_SHADER);
+    let dispatches_x = (colors.width() as f32 / 16.0).ceil() as u32;
+    let dispatches_y = (colors.height() as f32 / 16.0).ceil() as u32;
+    let cmd = cmd
+        .bind_compute_pipeline("bake_lighting")?
+        .bind_storage_image(0, 0, &colors.image.view)?
+        .bind_sampled_image(0, 1, &normals.normals.image.image.view, &samplers.linear)?
+        .bind_sampled_image(0, 2, detail_view, &samplers.linear)?
+        .bind_sampled_image(0, 3, &normals.detail_normals.image.image.view, &samplers.linear)?
+        .push_constant(
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            &BakeParams {
+                sun_direction: sun_direction.to_array(),
+                ambient,
+                detail_tiling: options.detail_tiling,
+                detail_strength,
+                horizontal_scale: options.horizontal_scale.to_array(),
+            },
+        )
+        .dispatch(dispatches_x, dispatches_y, 1)?;
+    Ok(prepare_for_read(
+        colors,
+        cmd,
+        PipelineStage::FRAGMENT_SHADER,
+        vk::AccessFlags2::SHADER_SAMPLED_READ,
+    ))
+}
+
+/// Multiply the diffuse map of the terrain with the light it currently receives from the sun,
+/// so the terrain keeps its shading when lighting is disabled. `ambient` is added to the
+/// light of every texel, so slopes facing away from the sun do not turn black.
+/// The light is computed from the same normal the terrain is shaded with, including the
+/// detail layer and the painted detail normals.
+/// # DI Access
+/// - Read [`World`]
+/// - Read [`Samplers`]
+/// - Write [`GpuWork`](pass::GpuWork)
+pub fn bake_lighting(bus: &EventBus<DI>, ambient: f32) -> Result<()> {
+    ensure!(
+        ambient.is_finite() && ambient >= 0.0,
+        "Cannot bake with an ambient light of {ambient}."
+    );
+    let (Some(terrain), options) = get_terrain_info(bus) else {
+        bail!("There is no terrain to bake the lighting of.")
+    };
+    // Matches the direction the terrain is shaded with, see WorldRenderer
+    let sun_direction = {
+        let di = bus.data().read().unwrap();
+        let world = di.read_sync::<World>().unwrap();
+        -world.sun_direction.front_direction()
+    };
+    let detail = with_ready_detail_map(bus, terrain, |detail| detail.image.image.view.clone()).ok();
+    with_ready_detail_normal_map(bus, terrain, |detail_normals| {
+        with_ready_terrain(bus, terrain, |heights, normals, colors, _| {
+            let normals = BakeNormals {
+                normals,
+                detail: detail.as_ref(),
+                detail_normals,
+                heights,
+            };
+            submit_brush_work!(bus, [&colors.image.view], |cmd| record_bake(
+                bus,
+                cmd,
+                sun_direction,
+                ambient,
+                &options,
+                &normals,
+                colors
+            ));
+            Ok(())
+        })
+    })?
+}
@@ -1,17 +1,192 @@
-use anyhow::Result;
-use glam::{Vec3, Vec4};
+use std::f32::consts::FRAC_PI_2;
+
+use anyhow::{bail, Result};
+use assets::texture::Texture;
+use assets::{texel_at_uv, DerivedMapFormat, DiffuseMapFormat};
+use gfx::Samplers;
+use glam::{IVec2, Vec3, Vec4};
 use inject::DI;
+use phobos::{vk, ComputeCmdBuffer, IncompleteCommandBuffer, PipelineStage, Sampler};
 use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
 
+use crate::undo::BrushTarget;
+use crate::util::{
+    dispatch_patch_rect, get_terrain_info, position_on_terrain, prepare_for_read,
+    prepare_for_write, submit_brush_work, with_ready_derived_maps, with_ready_terrain, BrushDomain,
+};
 use crate::{Brush, BrushSettings};
 
-#[derive(Copy, Clone, Debug, Default)]
+/// Push constants of the color brush shader.
+/// Kept in sync with `PC` in `color_brush.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct ColorBrushParams {
+    center: IVec2,
+    weight: f32,
+    size: u32,
+    color: [f32; 4],
+    border_mode: u32,
+    max_slope: f32,
+}
+
+/// Paints the diffuse map of the terrain.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Color {
+    /// Color to paint with, in linear RGB.
     pub color: Vec4,
+    /// Steepest slope that is painted on, in radians. Steeper terrain is masked out using the
+    /// slope map of the terrain, see [`DerivedMaps`](assets::DerivedMaps).
+    #[serde(default = "Color::default_max_slope")]
+    pub max_slope: f32,
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self {
+            color: Vec4::ONE,
+            max_slope: Self::default_max_slope(),
+        }
+    }
+}
+
+/// Converts a single sRGB encoded channel to linear.
+pub fn srgb_to_linear(channel: u8) -> f32 {
+    let channel = channel as f32 / 255.0;
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a single linear channel to sRGB, rounding to the nearest representable value
+/// like a write to a UNORM image does.
+pub fn linear_to_srgb(channel: f32) -> u8 {
+    let channel = channel.clamp(0.0, 1.0);
+    let srgb = if channel <= 0.0031308 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0).round() as u8
+}
+
+impl Color {
+    /// Paint regardless of the slope.
+    fn default_max_slope() -> f32 {
+        FRAC_PI_2
+    }
+
+    /// Create a color brush from an sRGB color, such as the one from the color picker.
+    pub fn from_srgb(srgb: [u8; 3]) -> Self {
+        Self {
+            color: Vec4::new(
+                srgb_to_linear(srgb[0]),
+                srgb_to_linear(srgb[1]),
+                srgb_to_linear(srgb[2]),
+                1.0,
+            ),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the sRGB encoded color this brush paints with.
+    pub fn to_srgb(&self) -> [u8; 3] {
+        [linear_to_srgb(self.color.x), linear_to_srgb(self.color.y), linear_to_srgb(self.color.z)]
+    }
+
+    fn record_paint<'q, D: BrushDomain>(
+        cmd: IncompleteCommandBuffer<'q, D>,
+        params: &ColorBrushParams,
+        texture: &Texture<DiffuseMapFormat>,
+        slope: &Texture<DerivedMapFormat>,
+        sampler: &Sampler,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let cmd = prepare_for_write(texture, cmd, PipelineStage::FRAGMENT_SHADER);
+        let cmd = cmd
+            .bind_compute_pipeline("color_brush")?
+            .bind_storage_image(0, 0, &texture.image.view)?
+            .bind_sampled_image(0, 1, &slope.image.view, sampler)?
+            .push_constant(vk::ShaderStageFlags::COMPUTE, 0, params);
+        let cmd = dispatch_patch_rect(cmd, params.size, 16)?;
+        Ok(prepare_for_read(
+            texture,
+            cmd,
+            PipelineStage::FRAGMENT_SHADER,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+        ))
+    }
+
+    fn apply_to_texture(
+        &self,
+        bus: &EventBus<DI>,
+        params: &ColorBrushParams,
+        texture: &Texture<DiffuseMapFormat>,
+        slope: &Texture<DerivedMapFormat>,
+    ) -> Result<()> {
+        let di = bus.data().read().unwrap();
+        let samplers = di.get::<Samplers>().unwrap();
+        submit_brush_work!(bus, [&texture.image.view], |cmd| Self::record_paint(
+            cmd,
+            params,
+            texture,
+            slope,
+            &samplers.linear
+        ));
+        Ok(())
+    }
 }
 
 impl Brush for Color {
+    fn targets(&self, _settings: &BrushSettings) -> &'static [BrushTarget] {
+        &[BrushTarget::Color]
+    }
+
     fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()> {
-        todo!()
+        if !position_on_terrain(position) {
+            return Ok(());
+        }
+
+        let (terrain, terrain_options) = get_terrain_info(bus);
+        let uv = terrain_options.uv_at(position);
+        // If no terrain handle was set, we cannot reasonably use a brush on it
+        let Some(terrain) = terrain else { bail!("Used brush but terrain handle is not set.") };
+        with_ready_derived_maps(bus, terrain, |maps| {
+            with_ready_terrain(bus, terrain, |_, _, texture, _| {
+                let radius = terrain_options.texel_radius(position, settings.radius, texture);
+                let params = ColorBrushParams {
+                    center: texel_at_uv(uv, texture.width(), texture.height()),
+                    weight: settings.weight,
+                    size: radius.0,
+                    color: self.color.to_array(),
+                    border_mode: terrain_options.border_mode.shader_value(),
+                    max_slope: self.max_slope,
+                };
+                self.apply_to_texture(bus, &params, texture, &maps.slope)
+            })
+        })?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picked_color_round_trips() {
+        // Every sRGB value picked in the GUI must be painted back exactly.
+        for value in 0..=255u8 {
+            let brush = Color::from_srgb([value, value, value]);
+            assert_eq!(brush.to_srgb(), [value; 3]);
+        }
+    }
+
+    #[test]
+    fn conversion_is_not_linear() {
+        // Middle gray in sRGB is much darker in linear space.
+        let linear = srgb_to_linear(128);
+        assert!((linear - 0.2158).abs() < 1e-3, "{linear}");
+        assert_eq!(linear_to_srgb(linear), 128);
     }
 }
@@ -0,0 +1,116 @@
+use anyhow::{bail, Result};
+use assets::{texel_at_uv, BorderMode, DetailNormalMap, TexelRadius};
+use glam::{IVec2, Vec3};
+use inject::DI;
+use phobos::{vk, ComputeCmdBuffer, IncompleteCommandBuffer, PipelineStage};
+use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+
+use crate::undo::BrushTarget;
+use crate::util::{
+    dispatch_patch_rect, get_terrain_info, position_on_terrain, prepare_for_read,
+    prepare_for_write, submit_brush_work, with_ready_detail_normal_map, BrushDomain,
+};
+use crate::{Brush, BrushSettings};
+
+/// Push constants of the detail normal brush shader.
+/// Kept in sync with `PC` in `detail_normal_brush.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct DetailNormalBrushParams {
+    center: IVec2,
+    weight: f32,
+    size: u32,
+    strength: f32,
+    border_mode: u32,
+}
+
+/// Paints fine bumps into the detail normal map of the terrain. This changes the shading of
+/// the surface, but not its shape. Inverting the brush flattens the painted bumps again.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DetailNormal {
+    /// Steepness of the painted bumps.
+    pub strength: f32,
+}
+
+impl Default for DetailNormal {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+        }
+    }
+}
+
+impl DetailNormal {
+    fn record_paint<'q, D: BrushDomain>(
+        &self,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
+        weight: f32,
+        border: BorderMode,
+        normals: &DetailNormalMap,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let params = DetailNormalBrushParams {
+            center,
+            weight,
+            size: radius.0,
+            strength: self.strength,
+            border_mode: border.shader_value(),
+        };
+        let cmd = prepare_for_write(&normals.image, cmd, PipelineStage::FRAGMENT_SHADER);
+        let cmd = cmd
+            .bind_compute_pipeline("detail_normal_brush")?
+            .bind_storage_image(0, 0, &normals.image.image.view)?
+            .push_constant(vk::ShaderStageFlags::COMPUTE, 0, &params);
+        let cmd = dispatch_patch_rect(cmd, radius.0, 16)?;
+        Ok(prepare_for_read(
+            &normals.image,
+            cmd,
+            PipelineStage::FRAGMENT_SHADER,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+        ))
+    }
+
+    fn apply_to_texture(
+        &self,
+        bus: &EventBus<DI>,
+        center: IVec2,
+        radius: TexelRadius,
+        settings: &BrushSettings,
+        border: BorderMode,
+        normals: &DetailNormalMap,
+    ) -> Result<()> {
+        let weight = match settings.invert {
+            true => -settings.weight,
+            false => settings.weight,
+        };
+        submit_brush_work!(bus, [&normals.image.image.view], |cmd| self
+            .record_paint(cmd, center, radius, weight, border, normals));
+        Ok(())
+    }
+}
+
+impl Brush for DetailNormal {
+    fn targets(&self, _settings: &BrushSettings) -> &'static [BrushTarget] {
+        &[BrushTarget::DetailNormals]
+    }
+
+    fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()> {
+        if !position_on_terrain(position) {
+            return Ok(());
+        }
+
+        let (terrain, terrain_options) = get_terrain_info(bus);
+        let uv = terrain_options.uv_at(position);
+        // If no terrain handle was set, we cannot reasonably use a brush on it
+        let Some(terrain) = terrain else { bail!("Used brush but terrain handle is not set.") };
+        with_ready_detail_normal_map(bus, terrain, |normals| {
+            let radius = terrain_options.texel_radius(position, settings.radius, &normals.image);
+            let (width, height) = (normals.image.width(), normals.image.height());
+            let center = texel_at_uv(uv, width, height);
+            let border = terrain_options.border_mode;
+            self.apply_to_texture(bus, center, radius, settings, border, normals)
+        })?
+    }
+}
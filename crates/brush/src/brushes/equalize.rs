@@ -1,45 +1,149 @@
 use anyhow::{bail, Result};
-use assets::{Heightmap, NormalMap, TerrainOptions};
-use gfx::SharedContext;
-use glam::{Vec2, Vec3};
+use assets::storage::AssetStorage;
+use assets::{texel_at_uv, BorderMode, Heightmap, NormalMap, TexelRadius, Uv};
+use gfx::{PairedImageView, SharedContext};
+use glam::{IVec2, Vec3};
 use inject::DI;
-use pass::GpuWork;
-use phobos::domain::All;
-use phobos::{
-    vk, CommandBuffer, ComputeCmdBuffer, IncompleteCmdBuffer, IncompleteCommandBuffer,
-    PipelineStage,
-};
+use phobos::{vk, ComputeCmdBuffer, Image, IncompleteCommandBuffer, PipelineStage};
 use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
 use world::World;
 
+use crate::undo::BrushTarget;
 use crate::util::{
-    dispatch_patch_rect, get_terrain_info, position_on_terrain, prepare_for_read,
-    prepare_for_write, update_normals_around_patch, with_ready_terrain,
+    dispatch_patch_rect, get_terrain_info, height_views, position_on_terrain, prepare_for_read,
+    prepare_for_write, submit_brush_work, update_normals_around_patch, with_ready_detail_map,
+    with_ready_terrain, BrushDomain,
 };
-use crate::{Brush, BrushSettings};
+use crate::{Brush, BrushSettings, HeightLayer};
+
+/// Push constants of the shader computing local means.
+/// Kept in sync with `PC` in `equalize_mean.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct EqualizeMeanParams {
+    center: IVec2,
+    size: u32,
+    border_mode: u32,
+}
+
+/// Push constants of the equalize brush shader.
+/// Kept in sync with `PC` in `equalize_brush.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct EqualizeBrushParams {
+    center: IVec2,
+    size: u32,
+    border_mode: u32,
+    weight: f32,
+}
 
-#[derive(Copy, Clone, Debug, Default)]
+/// Smooths out local height differences by pulling every texel towards the mean height of its
+/// neighbourhood. Inverting pushes texels away from the mean instead, sharpening the terrain.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Equalize {}
 
 impl Equalize {
-    fn record_height_update<'q>(
+    /// Fraction of the way texels move towards their local mean in one stamp. Negative when
+    /// inverted, which moves texels away from the mean.
+    fn stamp_weight(settings: &BrushSettings) -> f32 {
+        if settings.invert {
+            -settings.weight
+        } else {
+            settings.weight
+        }
+    }
+
+    /// Size of the image holding the local means of a patch. The patch spans `size / 2` texels
+    /// on each side of its center, see `inside_patch_rect` in `equalize_brush.cs.hlsl`.
+    fn means_extent(radius: TexelRadius) -> u32 {
+        radius.0 / 2 * 2 + 1
+    }
+
+    /// Allocate the image the local means of a patch are written to.
+    /// # DI Access
+    /// - Read [`SharedContext`]
+    fn allocate_means(bus: &EventBus<DI>, radius: TexelRadius) -> Result<PairedImageView> {
+        let di = bus.data().read().unwrap();
+        let mut ctx = di.get::<SharedContext>().cloned().unwrap();
+        let extent = Self::means_extent(radius);
+        let image = Image::new(
+            ctx.device.clone(),
+            &mut ctx.allocator,
+            extent,
+            extent,
+            vk::ImageUsageFlags::STORAGE,
+            vk::Format::R32_SFLOAT,
+            vk::SampleCountFlags::TYPE_1,
+        )?;
+        PairedImageView::new(image, vk::ImageAspectFlags::COLOR)
+    }
+
+    fn record_height_update<'q, D: BrushDomain>(
         &self,
-        cmd: IncompleteCommandBuffer<'q, All>,
-        uv: Vec2,
-        radius: u32,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
+        weight: f32,
+        border: BorderMode,
         heights: &Heightmap,
-    ) -> Result<IncompleteCommandBuffer<'q, All>> {
+        means: &PairedImageView,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
         // We are going to write to this image in a compute shader, so submit a barrier for this first.
         let cmd =
             prepare_for_write(&heights.image, cmd, PipelineStage::TESSELLATION_EVALUATION_SHADER);
+        // The means are only ever used inside this command buffer
+        let cmd = cmd.transition_image(
+            &means.view,
+            PipelineStage::TOP_OF_PIPE,
+            PipelineStage::COMPUTE_SHADER,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::AccessFlags2::NONE,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        );
+        // Compute the local mean of every texel first, so the blend below never reads a
+        // neighbour that was already moved towards its mean.
+        let cmd = cmd
+            .bind_compute_pipeline("equalize_mean")?
+            .bind_storage_image(0, 0, &heights.image.image.view)?
+            .bind_storage_image(0, 1, &means.view)?
+            .push_constant(
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &EqualizeMeanParams {
+                    center,
+                    size: radius.0,
+                    border_mode: border.shader_value(),
+                },
+            );
+        let cmd = dispatch_patch_rect(cmd, Self::means_extent(radius), 16)?;
+        let cmd = cmd.transition_image(
+            &means.view,
+            PipelineStage::COMPUTE_SHADER,
+            PipelineStage::COMPUTE_SHADER,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            vk::AccessFlags2::SHADER_STORAGE_READ,
+        );
         // Bind the pipeline we will use to update the heightmap
-        let cmd = cmd.bind_compute_pipeline("blur_brush")?;
-        // Bind the image to the descriptor, push our uvs to the shader and dispatch our compute shader
-        let mut cmd = cmd
+        let cmd = cmd.bind_compute_pipeline("equalize_brush")?;
+        // Bind the images to the descriptor, push the brush center to the shader and dispatch our compute shader
+        let cmd = cmd
             .bind_storage_image(0, 0, &heights.image.image.view)?
-            .push_constant(vk::ShaderStageFlags::COMPUTE, 0, &uv)
-            .push_constant(vk::ShaderStageFlags::COMPUTE, 8, &radius);
-        let cmd = dispatch_patch_rect(cmd, radius, 16)?;
+            .bind_storage_image(0, 1, &means.view)?
+            .push_constant(
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &EqualizeBrushParams {
+                    center,
+                    size: radius.0,
+                    border_mode: border.shader_value(),
+                    weight,
+                },
+            );
+        let cmd = dispatch_patch_rect(cmd, Self::means_extent(radius), 16)?;
         Ok(prepare_for_read(
             &heights.image,
             cmd,
@@ -48,17 +152,17 @@ impl Equalize {
         ))
     }
 
-    fn record_normals_update<'q>(
+    fn record_normals_update<'q, D: BrushDomain>(
         &self,
         bus: &EventBus<DI>,
-        cmd: IncompleteCommandBuffer<'q, All>,
-        uv: Vec2,
-        radius: u32,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
         heights: &Heightmap,
         normals: &NormalMap,
-    ) -> Result<IncompleteCommandBuffer<'q, All>> {
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
         let cmd = prepare_for_write(&normals.image, cmd, PipelineStage::FRAGMENT_SHADER);
-        let cmd = update_normals_around_patch(bus, cmd, uv, radius, heights, normals)?;
+        let cmd = update_normals_around_patch(bus, cmd, center, radius, heights, normals)?;
         Ok(prepare_for_read(
             &normals.image,
             cmd,
@@ -67,39 +171,47 @@ impl Equalize {
         ))
     }
 
-    fn record_update_commands(
+    fn record_update_commands<'q, D: BrushDomain>(
         &self,
         bus: &EventBus<DI>,
-        cmd: IncompleteCommandBuffer<All>,
-        uv: Vec2,
-        radius: u32,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
+        weight: f32,
+        border: BorderMode,
         heights: &Heightmap,
-        normals: &NormalMap,
-    ) -> Result<CommandBuffer<All>> {
-        let cmd = self.record_height_update(cmd, uv, radius, heights)?;
-        let cmd = self.record_normals_update(bus, cmd, uv, radius, heights, normals)?;
-        cmd.finish()
+        normals: Option<&NormalMap>,
+        means: &PairedImageView,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let cmd = self.record_height_update(cmd, center, radius, weight, border, heights, means)?;
+        let cmd = match normals {
+            None => cmd,
+            Some(normals) => {
+                self.record_normals_update(bus, cmd, center, radius, heights, normals)?
+            }
+        };
+        Ok(cmd)
     }
 
     fn apply_to_terrain(
         &self,
         bus: &EventBus<DI>,
-        position: Vec3,
-        uv: Vec2,
-        settings: BrushSettings,
-        options: TerrainOptions,
+        center: IVec2,
+        radius: TexelRadius,
+        weight: f32,
+        border: BorderMode,
         heights: &Heightmap,
-        normals: &NormalMap,
+        normals: Option<&NormalMap>,
     ) -> Result<()> {
-        // Allocate a command buffer and submit it to the current batch
+        let means = Self::allocate_means(bus, radius)?;
+        // Record the update and submit it on the selected queue
+        submit_brush_work!(bus, height_views(heights, normals), |cmd| self
+            .record_update_commands(
+                bus, cmd, center, radius, weight, border, heights, normals, &means
+            ));
+        // The means are only freed once the GPU is done with them
         let di = bus.data().read().unwrap();
-        let ctx = di.get::<SharedContext>().cloned().unwrap();
-        let cmd = ctx
-            .exec
-            .on_domain::<All, _>(Some(ctx.pipelines.clone()), Some(ctx.descriptors.clone()))?;
-        let radius = options.texel_radius(position, settings.radius, &heights.image);
-        let cmd = self.record_update_commands(bus, cmd, uv, radius, &heights, &normals)?;
-        GpuWork::with_batch(bus, move |batch| batch.submit(cmd))??;
+        di.get::<AssetStorage>().unwrap().retire(means);
         Ok(())
     }
 
@@ -107,21 +219,44 @@ impl Equalize {
         &self,
         bus: &EventBus<DI>,
         position: Vec3,
-        uv: Vec2,
+        uv: Uv,
         settings: BrushSettings,
     ) -> Result<()> {
         // Grab the terrain info from the world
         let (terrain, terrain_options) = get_terrain_info(bus);
         // If no terrain handle was set, we cannot reasonably use a brush on it
         let Some(terrain) = terrain else { bail!("Used brush but terrain handle is not set.") };
-        with_ready_terrain(bus, terrain, |heights, normals, _, _| {
-            self.apply_to_terrain(bus, position, uv, settings, terrain_options, heights, normals)
-        })?;
+        let weight = Self::stamp_weight(&settings);
+        match settings.layer {
+            HeightLayer::Base => with_ready_terrain(bus, terrain, |heights, normals, _, _| {
+                let radius =
+                    terrain_options.texel_radius(position, settings.radius, &heights.image);
+                let center = texel_at_uv(uv, heights.image.width(), heights.image.height());
+                let border = terrain_options.border_mode;
+                self.apply_to_terrain(bus, center, radius, weight, border, heights, Some(normals))
+            })?,
+            HeightLayer::Detail => with_ready_detail_map(bus, terrain, |detail| {
+                let (width, height) = (detail.image.width(), detail.image.height());
+                let center = terrain_options.detail_texel_at_uv(uv, width, height);
+                let radius =
+                    terrain_options.detail_texel_radius(position, settings.radius, &detail.image);
+                // The detail layer has no normal map, its normals are computed while shading.
+                // It is tiled over the terrain, so strokes always wrap around its border.
+                self.apply_to_terrain(bus, center, radius, weight, BorderMode::Wrap, detail, None)
+            })??,
+        }
         Ok(())
     }
 }
 
 impl Brush for Equalize {
+    fn targets(&self, settings: &BrushSettings) -> &'static [BrushTarget] {
+        match settings.layer {
+            HeightLayer::Base => &[BrushTarget::Height(HeightLayer::Base), BrushTarget::Normals],
+            HeightLayer::Detail => &[BrushTarget::Height(HeightLayer::Detail)],
+        }
+    }
+
     fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()> {
         if !position_on_terrain(position) {
             return Ok(());
@@ -141,3 +276,48 @@ impl Brush for Equalize {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use assets::TexelRadius;
+
+    use super::Equalize;
+    use crate::util::patch_workgroups;
+    use crate::BrushSettings;
+
+    fn settings(invert: bool) -> BrushSettings {
+        BrushSettings {
+            radius: Default::default(),
+            weight: 0.25,
+            invert,
+            once: false,
+            layer: Default::default(),
+            spacing: 0.0,
+        }
+    }
+
+    #[test]
+    fn inverting_negates_the_weight() {
+        assert_eq!(Equalize::stamp_weight(&settings(false)), 0.25);
+        assert_eq!(Equalize::stamp_weight(&settings(true)), -0.25);
+    }
+
+    #[test]
+    fn means_cover_the_patch() {
+        // Every invocation with |offset| <= size / 2 writes a mean, see inside_patch_rect
+        assert_eq!(Equalize::means_extent(TexelRadius(1)), 1);
+        assert_eq!(Equalize::means_extent(TexelRadius(15)), 15);
+        assert_eq!(Equalize::means_extent(TexelRadius(16)), 17);
+    }
+
+    #[test]
+    fn dispatch_covers_the_patch() {
+        for size in [1, 15, 16, 17, 32, 100] {
+            let extent = Equalize::means_extent(TexelRadius(size));
+            let workgroups = patch_workgroups(extent, 16);
+            assert!(workgroups * 16 >= extent, "{size}");
+            // No workgroup is entirely outside of the patch
+            assert!((workgroups - 1) * 16 < extent, "{size}");
+        }
+    }
+}
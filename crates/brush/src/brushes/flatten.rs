@@ -0,0 +1,204 @@
+use anyhow::{bail, Result};
+use assets::{texel_at_uv, BorderMode, Heightmap, NormalMap, TexelRadius, Uv};
+use glam::{IVec2, Vec3};
+use inject::DI;
+use phobos::{vk, ComputeCmdBuffer, IncompleteCommandBuffer, PipelineStage};
+use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+
+use crate::height::WeightFunction;
+use crate::set_value::{pick_value, BrushValue, ValueKind};
+use crate::undo::BrushTarget;
+use crate::util::{
+    dispatch_patch_rect, get_terrain_info, height_views, position_on_terrain, prepare_for_read,
+    prepare_for_write, submit_brush_work, update_normals_around_patch, with_ready_detail_map,
+    with_ready_terrain, BrushDomain,
+};
+use crate::{Brush, BrushSettings, HeightLayer};
+
+/// Push constants of the flatten brush shader.
+/// Kept in sync with `PC` in `flatten_brush.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct FlattenBrushParams {
+    center: IVec2,
+    weight: f32,
+    size: u32,
+    weight_param: f32,
+    border_mode: u32,
+    target_height: f32,
+    weight_kind: u32,
+}
+
+/// Moves the terrain towards a single height, to build plateaus and roads.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Flatten {
+    /// Height to level the terrain to, as stored in the heightmap. If `None`, the height under
+    /// the first stamp of each stroke is used.
+    pub target_height: Option<f32>,
+    pub weight_fn: WeightFunction,
+}
+
+impl Flatten {
+    fn record_height_update<'q, D: BrushDomain>(
+        &self,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
+        weight: f32,
+        target_height: f32,
+        border: BorderMode,
+        heights: &Heightmap,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let cmd =
+            prepare_for_write(&heights.image, cmd, PipelineStage::TESSELLATION_EVALUATION_SHADER);
+        let params = FlattenBrushParams {
+            center,
+            weight,
+            size: radius.0,
+            weight_param: self.weight_fn.param(),
+            border_mode: border.shader_value(),
+            target_height,
+            weight_kind: self.weight_fn.shader_value(),
+        };
+        let cmd = cmd
+            .bind_compute_pipeline("flatten_brush")?
+            .bind_storage_image(0, 0, &heights.image.image.view)?
+            .push_constant(vk::ShaderStageFlags::COMPUTE, 0, &params);
+        let cmd = dispatch_patch_rect(cmd, radius.0, 16)?;
+        Ok(prepare_for_read(
+            &heights.image,
+            cmd,
+            PipelineStage::COMPUTE_SHADER,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+        ))
+    }
+
+    fn record_update_commands<'q, D: BrushDomain>(
+        &self,
+        bus: &EventBus<DI>,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
+        weight: f32,
+        target_height: f32,
+        border: BorderMode,
+        heights: &Heightmap,
+        normals: Option<&NormalMap>,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let cmd =
+            self.record_height_update(cmd, center, radius, weight, target_height, border, heights)?;
+        let Some(normals) = normals else { return Ok(cmd) };
+        let cmd = prepare_for_write(&normals.image, cmd, PipelineStage::FRAGMENT_SHADER);
+        let cmd = update_normals_around_patch(bus, cmd, center, radius, heights, normals)?;
+        Ok(prepare_for_read(
+            &normals.image,
+            cmd,
+            PipelineStage::BOTTOM_OF_PIPE,
+            vk::AccessFlags2::NONE,
+        ))
+    }
+
+    fn apply_at_uv(
+        &self,
+        bus: &EventBus<DI>,
+        position: Vec3,
+        uv: Uv,
+        target_height: f32,
+        settings: &BrushSettings,
+    ) -> Result<()> {
+        let (terrain, terrain_options) = get_terrain_info(bus);
+        // If no terrain handle was set, we cannot reasonably use a brush on it
+        let Some(terrain) = terrain else { bail!("Used brush but terrain handle is not set.") };
+        let weight = settings.weight;
+        match settings.layer {
+            HeightLayer::Base => with_ready_terrain(bus, terrain, |heights, normals, _, _| {
+                let radius =
+                    terrain_options.texel_radius(position, settings.radius, &heights.image);
+                let center = texel_at_uv(uv, heights.image.width(), heights.image.height());
+                let border = terrain_options.border_mode;
+                let written = height_views(heights, Some(normals));
+                submit_brush_work!(bus, written, |cmd| self.record_update_commands(
+                    bus,
+                    cmd,
+                    center,
+                    radius,
+                    weight,
+                    target_height,
+                    border,
+                    heights,
+                    Some(normals)
+                ));
+                Ok(())
+            })?,
+            HeightLayer::Detail => with_ready_detail_map(bus, terrain, |detail| {
+                let (width, height) = (detail.image.width(), detail.image.height());
+                let center = terrain_options.detail_texel_at_uv(uv, width, height);
+                let radius =
+                    terrain_options.detail_texel_radius(position, settings.radius, &detail.image);
+                // The detail layer is tiled over the terrain, see SmoothHeight
+                let border = BorderMode::Wrap;
+                submit_brush_work!(bus, height_views(detail, None), |cmd| self
+                    .record_update_commands(
+                        bus,
+                        cmd,
+                        center,
+                        radius,
+                        weight,
+                        target_height,
+                        border,
+                        detail,
+                    None
+                ));
+                Ok(())
+            })??,
+        }
+        Ok(())
+    }
+}
+
+impl Brush for Flatten {
+    fn decal_shader(&self) -> &'static str {
+        "shaders/src/height_brush_decal.fs.hlsl"
+    }
+
+    fn decal_data(&self) -> Option<[f32; 4]> {
+        Some(self.weight_fn.decal_data())
+    }
+
+    fn targets(&self, settings: &BrushSettings) -> &'static [BrushTarget] {
+        match settings.layer {
+            HeightLayer::Base => &[BrushTarget::Height(HeightLayer::Base), BrushTarget::Normals],
+            HeightLayer::Detail => &[BrushTarget::Height(HeightLayer::Detail)],
+        }
+    }
+
+    /// Picks the target height from the terrain under the first stamp of the stroke.
+    fn before_stamp(
+        &mut self,
+        bus: &EventBus<DI>,
+        position: Vec3,
+        settings: &BrushSettings,
+    ) -> Result<()> {
+        if self.target_height.is_some() {
+            return Ok(());
+        }
+        if let Some(BrushValue::Height(height)) =
+            pick_value(bus, position, ValueKind::Height, settings.layer)?
+        {
+            self.target_height = Some(height);
+        }
+        Ok(())
+    }
+
+    fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()> {
+        if !position_on_terrain(position) {
+            return Ok(());
+        }
+        // The first stamp was not on the terrain, so there is no height to flatten to yet
+        let Some(target_height) = self.target_height else { return Ok(()) };
+        let (_, options) = get_terrain_info(bus);
+        let uv = options.uv_at(position);
+        self.apply_at_uv(bus, position, uv, target_height, settings)
+    }
+}
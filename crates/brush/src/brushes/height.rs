@@ -1,29 +1,32 @@
 use anyhow::{bail, Result};
-use assets::{Heightmap, NormalMap, TerrainOptions};
-use gfx::SharedContext;
-use glam::{Vec2, Vec3};
+use assets::{texel_at_uv, BorderMode, Heightmap, NormalMap, TexelRadius, Uv};
+use glam::{IVec2, Vec3};
 use inject::DI;
-use pass::GpuWork;
-use phobos::domain::All;
-use phobos::{
-    vk, CommandBuffer, ComputeCmdBuffer, IncompleteCmdBuffer, IncompleteCommandBuffer,
-    PipelineStage,
-};
+use phobos::{vk, ComputeCmdBuffer, IncompleteCommandBuffer, PipelineStage};
 use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
 use strum_macros::Display;
-use time::Time;
 use world::World;
 
+use crate::undo::BrushTarget;
 use crate::util::{
-    dispatch_patch_rect, get_terrain_info, position_on_terrain, prepare_for_read,
-    prepare_for_write, update_normals_around_patch, with_ready_terrain,
+    dispatch_patch_rect, get_terrain_info, height_views, position_on_terrain, prepare_for_read,
+    prepare_for_write, submit_brush_work, update_normals_around_patch, with_ready_detail_map,
+    with_ready_terrain, BrushDomain,
 };
-use crate::{Brush, BrushSettings};
+use crate::{Brush, BrushSettings, HeightLayer};
 
-#[derive(Debug, Copy, Clone, PartialEq, Display)]
+/// Falloff curve of a brush, from its center to its radius.
+#[derive(Debug, Copy, Clone, PartialEq, Display, Serialize, Deserialize)]
 pub enum WeightFunction {
     // Gaussian curve with given standard deviation
     Gaussian(f32),
+    // Full weight up to the given fraction of the radius, then linearly down to zero
+    Linear(f32),
+    // Full weight up to the given fraction of the radius, then smoothly down to zero
+    Smoothstep(f32),
+    // Full weight in the entire brush area
+    Constant,
 }
 
 impl Default for WeightFunction {
@@ -34,8 +37,70 @@ impl Default for WeightFunction {
     }
 }
 
+impl WeightFunction {
+    /// Each falloff curve with its default parameter.
+    pub const ALL: [WeightFunction; 4] = [
+        WeightFunction::Gaussian(0.3),
+        WeightFunction::Linear(0.0),
+        WeightFunction::Smoothstep(0.5),
+        WeightFunction::Constant,
+    ];
+
+    /// Value identifying this curve in shaders, see `weight_function.hlsl`.
+    pub fn shader_value(self) -> u32 {
+        match self {
+            WeightFunction::Gaussian(_) => 0,
+            WeightFunction::Linear(_) => 1,
+            WeightFunction::Smoothstep(_) => 2,
+            WeightFunction::Constant => 3,
+        }
+    }
+
+    /// Parameter of the curve passed to shaders along with [`WeightFunction::shader_value`].
+    pub fn param(self) -> f32 {
+        match self {
+            WeightFunction::Gaussian(sigma) => sigma,
+            WeightFunction::Linear(hardness) | WeightFunction::Smoothstep(hardness) => hardness,
+            WeightFunction::Constant => 0.0,
+        }
+    }
+
+    /// Data for `height_brush_decal.fs.hlsl`, so the decal shows the falloff of the brush.
+    pub fn decal_data(self) -> [f32; 4] {
+        [self.param(), self.shader_value() as f32, 0.0, 0.0]
+    }
+
+    /// Returns an error message if the parameter of the curve is out of range.
+    pub fn validate(self) -> Result<(), String> {
+        match self {
+            WeightFunction::Gaussian(sigma) if !sigma.is_finite() || sigma <= 0.0 => {
+                Err(format!("invalid standard deviation {sigma}"))
+            }
+            WeightFunction::Linear(hardness) | WeightFunction::Smoothstep(hardness)
+                if !(0.0..1.0).contains(&hardness) =>
+            {
+                Err(format!("invalid hardness {hardness}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Push constants of the height brush shader.
+/// Kept in sync with `PC` in `height_brush.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct HeightBrushParams {
+    center: IVec2,
+    weight: f32,
+    size: u32,
+    weight_param: f32,
+    border_mode: u32,
+    weight_kind: u32,
+}
+
 /// Simple height brush that smoothly changes the height in the applied area
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SmoothHeight {
     pub weight_fn: WeightFunction,
 }
@@ -50,39 +115,35 @@ impl SmoothHeight {
         settings
     }
 
-    fn record_height_update<'q>(
+    fn record_height_update<'q, D: BrushDomain>(
         &self,
-        bus: &EventBus<DI>,
-        cmd: IncompleteCommandBuffer<'q, All>,
-        uv: Vec2,
-        radius: u32,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
         settings: &BrushSettings,
+        border: BorderMode,
         heights: &Heightmap,
-    ) -> Result<IncompleteCommandBuffer<'q, All>> {
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
         // We are going to write to this image in a compute shader, so submit a barrier for this first.
         let cmd =
             prepare_for_write(&heights.image, cmd, PipelineStage::TESSELLATION_EVALUATION_SHADER);
         // Bind the pipeline we will use to update the heightmap
         let cmd = cmd.bind_compute_pipeline("height_brush")?;
-        // Scale weight with frametime for consistency across runs and different frame rates
-        let weight = {
-            let di = bus.data().read().unwrap();
-            let time = di.read_sync::<Time>().unwrap();
-            settings.weight * time.delta.as_secs_f32()
+        let params = HeightBrushParams {
+            center,
+            // The weight was already scaled to this stamp by the stroke timer
+            weight: settings.weight,
+            size: radius.0,
+            weight_param: self.weight_fn.param(),
+            border_mode: border.shader_value(),
+            weight_kind: self.weight_fn.shader_value(),
         };
 
-        // Bind the image to the descriptor, push our uvs to the shader and dispatch our compute shader
-        let mut cmd = cmd
+        // Bind the image to the descriptor, push the brush parameters to the shader and dispatch our compute shader
+        let cmd = cmd
             .bind_storage_image(0, 0, &heights.image.image.view)?
-            .push_constant(vk::ShaderStageFlags::COMPUTE, 0, &uv)
-            .push_constant(vk::ShaderStageFlags::COMPUTE, 8, &weight)
-            .push_constant(vk::ShaderStageFlags::COMPUTE, 12, &radius);
-        match self.weight_fn {
-            WeightFunction::Gaussian(sigma) => {
-                cmd = cmd.push_constant(vk::ShaderStageFlags::COMPUTE, 16, &sigma);
-            }
-        };
-        let cmd = dispatch_patch_rect(cmd, radius, 16)?;
+            .push_constant(vk::ShaderStageFlags::COMPUTE, 0, &params);
+        let cmd = dispatch_patch_rect(cmd, radius.0, 16)?;
         Ok(prepare_for_read(
             &heights.image,
             cmd,
@@ -91,17 +152,17 @@ impl SmoothHeight {
         ))
     }
 
-    fn record_normals_update<'q>(
+    fn record_normals_update<'q, D: BrushDomain>(
         &self,
         bus: &EventBus<DI>,
-        cmd: IncompleteCommandBuffer<'q, All>,
-        uv: Vec2,
-        radius: u32,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
         heights: &Heightmap,
         normals: &NormalMap,
-    ) -> Result<IncompleteCommandBuffer<'q, All>> {
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
         let cmd = prepare_for_write(&normals.image, cmd, PipelineStage::FRAGMENT_SHADER);
-        let cmd = update_normals_around_patch(bus, cmd, uv, radius, heights, normals)?;
+        let cmd = update_normals_around_patch(bus, cmd, center, radius, heights, normals)?;
         Ok(prepare_for_read(
             &normals.image,
             cmd,
@@ -110,42 +171,41 @@ impl SmoothHeight {
         ))
     }
 
-    fn record_update_commands(
+    fn record_update_commands<'q, D: BrushDomain>(
         &self,
         bus: &EventBus<DI>,
-        cmd: IncompleteCommandBuffer<All>,
-        uv: Vec2,
-        radius: u32,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
         settings: &BrushSettings,
+        border: BorderMode,
         heights: &Heightmap,
-        normals: &NormalMap,
-    ) -> Result<CommandBuffer<All>> {
-        let cmd = self.record_height_update(bus, cmd, uv, radius, settings, heights)?;
-        let cmd = self.record_normals_update(bus, cmd, uv, radius, heights, normals)?;
-        cmd.finish()
+        normals: Option<&NormalMap>,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let cmd = self.record_height_update(cmd, center, radius, settings, border, heights)?;
+        let cmd = match normals {
+            None => cmd,
+            Some(normals) => {
+                self.record_normals_update(bus, cmd, center, radius, heights, normals)?
+            }
+        };
+        Ok(cmd)
     }
 
     fn apply_to_terrain(
         &self,
         bus: &EventBus<DI>,
-        position: Vec3,
-        uv: Vec2,
+        center: IVec2,
+        radius: TexelRadius,
         settings: BrushSettings,
-        options: TerrainOptions,
+        border: BorderMode,
         heights: &Heightmap,
-        normals: &NormalMap,
+        normals: Option<&NormalMap>,
     ) -> Result<()> {
         let settings = Self::invert_weight(settings);
-        // Allocate a command buffer and submit it to the current batch
-        let di = bus.data().read().unwrap();
-        let ctx = di.get::<SharedContext>().cloned().unwrap();
-        let cmd = ctx
-            .exec
-            .on_domain::<All, _>(Some(ctx.pipelines.clone()), Some(ctx.descriptors.clone()))?;
-        let radius = options.texel_radius(position, settings.radius, &heights.image);
-        let cmd =
-            self.record_update_commands(bus, cmd, uv, radius, &settings, &heights, &normals)?;
-        GpuWork::with_batch(bus, move |batch| batch.submit(cmd))??;
+        // Record the update and submit it on the selected queue
+        submit_brush_work!(bus, height_views(heights, normals), |cmd| self
+            .record_update_commands(bus, cmd, center, radius, &settings, border, heights, normals));
         Ok(())
     }
 
@@ -153,16 +213,32 @@ impl SmoothHeight {
         &self,
         bus: &EventBus<DI>,
         position: Vec3,
-        uv: Vec2,
+        uv: Uv,
         settings: BrushSettings,
     ) -> Result<()> {
         // Grab the terrain info from the world
         let (terrain, terrain_options) = get_terrain_info(bus);
         // If no terrain handle was set, we cannot reasonably use a brush on it
         let Some(terrain) = terrain else { bail!("Used brush but terrain handle is not set.") };
-        with_ready_terrain(bus, terrain, |heights, normals, _, _| {
-            self.apply_to_terrain(bus, position, uv, settings, terrain_options, heights, normals)
-        })?;
+        match settings.layer {
+            HeightLayer::Base => with_ready_terrain(bus, terrain, |heights, normals, _, _| {
+                let radius =
+                    terrain_options.texel_radius(position, settings.radius, &heights.image);
+                let center = texel_at_uv(uv, heights.image.width(), heights.image.height());
+                let border = terrain_options.border_mode;
+                self.apply_to_terrain(bus, center, radius, settings, border, heights, Some(normals))
+            })?,
+            HeightLayer::Detail => with_ready_detail_map(bus, terrain, |detail| {
+                let (width, height) = (detail.image.width(), detail.image.height());
+                let center = terrain_options.detail_texel_at_uv(uv, width, height);
+                let radius =
+                    terrain_options.detail_texel_radius(position, settings.radius, &detail.image);
+                // The detail layer has no normal map, its normals are computed while shading.
+                // It is tiled over the terrain, so strokes always wrap around its border.
+                let border = BorderMode::Wrap;
+                self.apply_to_terrain(bus, center, radius, settings, border, detail, None)
+            })??,
+        }
         Ok(())
     }
 }
@@ -173,9 +249,14 @@ impl Brush for SmoothHeight {
     }
 
     fn decal_data(&self) -> Option<[f32; 4]> {
-        Some(match self.weight_fn {
-            WeightFunction::Gaussian(sigma) => [sigma, 0.0, 0.0, 0.0],
-        })
+        Some(self.weight_fn.decal_data())
+    }
+
+    fn targets(&self, settings: &BrushSettings) -> &'static [BrushTarget] {
+        match settings.layer {
+            HeightLayer::Base => &[BrushTarget::Height(HeightLayer::Base), BrushTarget::Normals],
+            HeightLayer::Detail => &[BrushTarget::Height(HeightLayer::Detail)],
+        }
     }
 
     fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()> {
@@ -1,7 +1,17 @@
 pub use color::Color;
+pub use detail_normal::DetailNormal;
 pub use equalize::Equalize;
+pub use flatten::Flatten;
 pub use height::SmoothHeight;
+pub use set_value::SetValue;
+pub use splat::Splat;
+pub use stamp::Stamp;
 
 pub mod color;
+pub mod detail_normal;
 pub mod equalize;
+pub mod flatten;
 pub mod height;
+pub mod set_value;
+pub mod splat;
+pub mod stamp;
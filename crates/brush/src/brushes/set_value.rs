@@ -0,0 +1,282 @@
+use anyhow::{bail, Result};
+use assets::handle::Handle;
+use assets::texture::format::TextureFormat;
+use assets::texture::Texture;
+use assets::{
+    texel_at_uv, BorderMode, Heightmap, NormalMap, Terrain, TerrainOptions, TexelRadius, Uv,
+};
+use glam::{IVec2, Vec3, Vec4};
+use inject::DI;
+use pass::GpuWork;
+use phobos::{vk, ComputeCmdBuffer, IncompleteCommandBuffer, PipelineStage};
+use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::undo::BrushTarget;
+use crate::util::{
+    dispatch_patch_rect, get_terrain_info, height_views, position_on_terrain, prepare_for_read,
+    prepare_for_write, submit_brush_work, update_normals_around_patch, with_ready_detail_map,
+    with_ready_terrain, BrushDomain,
+};
+use crate::{Brush, BrushSettings, Color, HeightLayer};
+
+/// Push constants of the set value brush shaders.
+/// Kept in sync with `PC` in `set_value_brush.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct SetValueParams {
+    pub center: IVec2,
+    pub size: u32,
+    pub border_mode: u32,
+    pub value: [f32; 4],
+}
+
+/// Kind of terrain value a [`SetValue`] brush writes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Display, Serialize, Deserialize)]
+pub enum ValueKind {
+    #[default]
+    Height,
+    Color,
+}
+
+impl ValueKind {
+    pub const ALL: [ValueKind; 2] = [ValueKind::Height, ValueKind::Color];
+}
+
+/// Value written by a [`SetValue`] brush, usually picked from the terrain with the eyedropper.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BrushValue {
+    /// Height as stored in the heightmap, before the vertical scale of the terrain is applied.
+    Height(f32),
+    /// Linear RGB color.
+    Color(Vec4),
+}
+
+impl Default for BrushValue {
+    fn default() -> Self {
+        BrushValue::Height(0.0)
+    }
+}
+
+impl BrushValue {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            BrushValue::Height(_) => ValueKind::Height,
+            BrushValue::Color(_) => ValueKind::Color,
+        }
+    }
+
+    /// Default value of a kind, used when switching the kind of a brush.
+    pub fn default_of(kind: ValueKind) -> Self {
+        match kind {
+            ValueKind::Height => BrushValue::Height(0.0),
+            ValueKind::Color => BrushValue::Color(Vec4::ONE),
+        }
+    }
+}
+
+/// Sets every texel inside the brush to the same value. Together with the eyedropper this
+/// paints terrain to the exact height or color of another spot.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetValue {
+    pub value: BrushValue,
+}
+
+impl SetValue {
+    fn record_set<'q, D: BrushDomain, F: TextureFormat>(
+        &self,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
+        border: BorderMode,
+        texture: &Texture<F>,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        // Heightmaps are read by the terrain mesh and written to the normal map afterwards,
+        // color maps are only read while shading.
+        let (pipeline, value, last_use, next_use) = match self.value {
+            BrushValue::Height(height) => (
+                "set_height_brush",
+                Vec4::new(height, 0.0, 0.0, 0.0),
+                PipelineStage::TESSELLATION_EVALUATION_SHADER,
+                PipelineStage::COMPUTE_SHADER,
+            ),
+            BrushValue::Color(color) => (
+                "set_color_brush",
+                color,
+                PipelineStage::FRAGMENT_SHADER,
+                PipelineStage::FRAGMENT_SHADER,
+            ),
+        };
+        let cmd = prepare_for_write(texture, cmd, last_use);
+        let cmd = cmd
+            .bind_compute_pipeline(pipeline)?
+            .bind_storage_image(0, 0, &texture.image.view)?
+            .push_constant(
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &SetValueParams {
+                    center,
+                    size: radius.0,
+                    border_mode: border.shader_value(),
+                    value: value.to_array(),
+                },
+            );
+        let cmd = dispatch_patch_rect(cmd, radius.0, 16)?;
+        Ok(prepare_for_read(texture, cmd, next_use, vk::AccessFlags2::SHADER_SAMPLED_READ))
+    }
+
+    fn record_base_height<'q, D: BrushDomain>(
+        &self,
+        bus: &EventBus<DI>,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        center: IVec2,
+        radius: TexelRadius,
+        border: BorderMode,
+        heights: &Heightmap,
+        normals: &NormalMap,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let cmd = self.record_set(cmd, center, radius, border, &heights.image)?;
+        let cmd = prepare_for_write(&normals.image, cmd, PipelineStage::FRAGMENT_SHADER);
+        let cmd = update_normals_around_patch(bus, cmd, center, radius, heights, normals)?;
+        Ok(prepare_for_read(
+            &normals.image,
+            cmd,
+            PipelineStage::BOTTOM_OF_PIPE,
+            vk::AccessFlags2::NONE,
+        ))
+    }
+
+    fn apply_height(
+        &self,
+        bus: &EventBus<DI>,
+        position: Vec3,
+        uv: Uv,
+        settings: &BrushSettings,
+        options: TerrainOptions,
+        terrain: Handle<Terrain>,
+    ) -> Result<()> {
+        match settings.layer {
+            HeightLayer::Base => with_ready_terrain(bus, terrain, |heights, normals, _, _| {
+                let radius = options.texel_radius(position, settings.radius, &heights.image);
+                let center = texel_at_uv(uv, heights.image.width(), heights.image.height());
+                let border = options.border_mode;
+                let written = height_views(heights, Some(normals));
+                submit_brush_work!(bus, written, |cmd| self
+                    .record_base_height(bus, cmd, center, radius, border, heights, normals));
+                Ok(())
+            }),
+            HeightLayer::Detail => with_ready_detail_map(bus, terrain, |detail| {
+                let (width, height) = (detail.image.width(), detail.image.height());
+                let center = options.detail_texel_at_uv(uv, width, height);
+                let radius = options.detail_texel_radius(position, settings.radius, &detail.image);
+                // The detail layer is tiled over the terrain, see SmoothHeight
+                let border = BorderMode::Wrap;
+                submit_brush_work!(bus, height_views(detail, None), |cmd| self.record_set(
+                    cmd,
+                    center,
+                    radius,
+                    border,
+                    &detail.image
+                ));
+                Ok(())
+            })?,
+        }
+    }
+}
+
+impl Brush for SetValue {
+    fn targets(&self, settings: &BrushSettings) -> &'static [BrushTarget] {
+        match (self.value, settings.layer) {
+            (BrushValue::Color(_), _) => &[BrushTarget::Color],
+            (BrushValue::Height(_), HeightLayer::Base) => {
+                &[BrushTarget::Height(HeightLayer::Base), BrushTarget::Normals]
+            }
+            (BrushValue::Height(_), HeightLayer::Detail) => {
+                &[BrushTarget::Height(HeightLayer::Detail)]
+            }
+        }
+    }
+
+    fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()> {
+        if !position_on_terrain(position) {
+            return Ok(());
+        }
+
+        let (terrain, options) = get_terrain_info(bus);
+        let uv = options.uv_at(position);
+        // If no terrain handle was set, we cannot reasonably use a brush on it
+        let Some(terrain) = terrain else { bail!("Used brush but terrain handle is not set.") };
+        match self.value {
+            BrushValue::Height(_) => {
+                self.apply_height(bus, position, uv, settings, options, terrain)
+            }
+            BrushValue::Color(_) => with_ready_terrain(bus, terrain, |_, _, texture, _| {
+                let radius = options.texel_radius(position, settings.radius, texture);
+                let center = texel_at_uv(uv, texture.width(), texture.height());
+                let border = options.border_mode;
+                submit_brush_work!(bus, [&texture.image.view], |cmd| self
+                    .record_set(cmd, center, radius, border, texture));
+                Ok(())
+            }),
+        }
+    }
+}
+
+/// Returns the texel of a texture at `uv`, clamped to the texture.
+fn texel_in_texture<F: TextureFormat>(uv: Uv, texture: &Texture<F>) -> (u32, u32) {
+    let max = IVec2::new(texture.width() as i32 - 1, texture.height() as i32 - 1);
+    let texel = texel_at_uv(uv, texture.width(), texture.height()).clamp(IVec2::ZERO, max);
+    (texel.x as u32, texel.y as u32)
+}
+
+/// Reads the value of the terrain at a position, for the eyedropper. Queued brush work is
+/// completed first, so the value includes every finished stamp.
+/// Returns `None` if the position is not on the terrain.
+/// # DI Access
+/// - Read [`World`](world::World)
+/// - Write [`GpuWork`]
+pub fn pick_value(
+    bus: &EventBus<DI>,
+    position: Vec3,
+    kind: ValueKind,
+    layer: HeightLayer,
+) -> Result<Option<BrushValue>> {
+    if !position_on_terrain(position) {
+        return Ok(None);
+    }
+    let (Some(terrain), options) = get_terrain_info(bus) else {
+        bail!("There is no terrain to pick from.")
+    };
+    GpuWork::flush(bus)?;
+    GpuWork::wait_async(bus)?;
+    let uv = options.uv_at(position);
+    let value = match (kind, layer) {
+        (ValueKind::Height, HeightLayer::Base) => {
+            with_ready_terrain(bus, terrain, |heights, _, _, _| -> Result<BrushValue> {
+                let (x, y) = texel_in_texture(uv, &heights.image);
+                let height = heights.image.read_texel(bus, x, y)?[0];
+                Ok(BrushValue::Height(height))
+            })?
+        }
+        (ValueKind::Height, HeightLayer::Detail) => {
+            with_ready_detail_map(bus, terrain, |detail| -> Result<BrushValue> {
+                let (width, height) = (detail.image.width(), detail.image.height());
+                // The detail layer is tiled, so its texels wrap around
+                let texel = options.detail_texel_at_uv(uv, width, height);
+                let x = texel.x.rem_euclid(width as i32) as u32;
+                let y = texel.y.rem_euclid(height as i32) as u32;
+                let height = detail.image.read_texel(bus, x, y)?[0];
+                Ok(BrushValue::Height(height))
+            })??
+        }
+        (ValueKind::Color, _) => {
+            with_ready_terrain(bus, terrain, |_, _, texture, _| -> Result<BrushValue> {
+                let (x, y) = texel_in_texture(uv, texture);
+                let srgb = texture.read_texel(bus, x, y)?;
+                Ok(BrushValue::Color(Color::from_srgb([srgb[0], srgb[1], srgb[2]]).color))
+            })?
+        }
+    };
+    Ok(Some(value))
+}
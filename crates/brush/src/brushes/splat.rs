@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+use assets::{texel_at_uv, BorderMode, SplatMap, TexelRadius};
+use glam::{IVec2, Vec3};
+use inject::DI;
+use phobos::{vk, ComputeCmdBuffer, IncompleteCommandBuffer, PipelineStage};
+use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+
+use crate::undo::BrushTarget;
+use crate::util::{
+    dispatch_patch_rect, get_terrain_info, position_on_terrain, prepare_for_read,
+    prepare_for_write, submit_brush_work, with_ready_splat_map, BrushDomain,
+};
+use crate::{Brush, BrushSettings};
+
+/// Push constants of the splat brush shader.
+/// Kept in sync with `PC` in `splat_brush.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct SplatBrushParams {
+    center: IVec2,
+    weight: f32,
+    size: u32,
+    channel: u32,
+    border_mode: u32,
+}
+
+/// Paints the weight of a single material layer into the splat map of the terrain. The other
+/// layers lose weight so all layers keep summing to one. Inverting the brush removes the
+/// layer instead, in favor of the other layers that are already present.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Splat {
+    /// Layer to paint, see [`SplatMap::channel_name`].
+    pub channel: u32,
+}
+
+impl Default for Splat {
+    /// The first layer is the diffuse map, which already covers a new terrain.
+    fn default() -> Self {
+        Self {
+            channel: 1,
+        }
+    }
+}
+
+impl Splat {
+    fn params(
+        &self,
+        center: IVec2,
+        radius: TexelRadius,
+        settings: &BrushSettings,
+        border: BorderMode,
+    ) -> SplatBrushParams {
+        let weight = match settings.invert {
+            true => -settings.weight,
+            false => settings.weight,
+        };
+        SplatBrushParams {
+            center,
+            weight,
+            size: radius.0,
+            // Layers that do not exist would only take weight from the existing ones
+            channel: self.channel.min(SplatMap::CHANNELS - 1),
+            border_mode: border.shader_value(),
+        }
+    }
+
+    fn record_paint<'q, D: BrushDomain>(
+        cmd: IncompleteCommandBuffer<'q, D>,
+        params: &SplatBrushParams,
+        splat: &SplatMap,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let cmd = prepare_for_write(&splat.image, cmd, PipelineStage::FRAGMENT_SHADER);
+        let cmd = cmd
+            .bind_compute_pipeline("splat_brush")?
+            .bind_storage_image(0, 0, &splat.image.image.view)?
+            .push_constant(vk::ShaderStageFlags::COMPUTE, 0, params);
+        let cmd = dispatch_patch_rect(cmd, params.size, 16)?;
+        Ok(prepare_for_read(
+            &splat.image,
+            cmd,
+            PipelineStage::FRAGMENT_SHADER,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+        ))
+    }
+}
+
+impl Brush for Splat {
+    fn targets(&self, _settings: &BrushSettings) -> &'static [BrushTarget] {
+        &[BrushTarget::Splat]
+    }
+
+    fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()> {
+        if !position_on_terrain(position) {
+            return Ok(());
+        }
+
+        let (terrain, terrain_options) = get_terrain_info(bus);
+        let uv = terrain_options.uv_at(position);
+        // If no terrain handle was set, we cannot reasonably use a brush on it
+        let Some(terrain) = terrain else { bail!("Used brush but terrain handle is not set.") };
+        with_ready_splat_map(bus, terrain, |splat| {
+            let radius = terrain_options.texel_radius(position, settings.radius, &splat.image);
+            let center = texel_at_uv(uv, splat.image.width(), splat.image.height());
+            let params = self.params(center, radius, settings, terrain_options.border_mode);
+            submit_brush_work!(bus, [&splat.image.image.view], |cmd| Self::record_paint(
+                cmd, &params, splat
+            ));
+            Ok(())
+        })?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_select_an_existing_layer() {
+        let settings = BrushSettings {
+            weight: 0.5,
+            ..Default::default()
+        };
+        let brush = Splat {
+            channel: 2,
+        };
+        let params = brush.params(IVec2::ZERO, TexelRadius(8), &settings, BorderMode::Clamp);
+        assert_eq!(params.channel, 2);
+        assert_eq!(params.weight, 0.5);
+        let brush = Splat {
+            channel: 7,
+        };
+        let params = brush.params(IVec2::ZERO, TexelRadius(8), &settings, BorderMode::Clamp);
+        assert_eq!(params.channel, SplatMap::CHANNELS - 1);
+    }
+}
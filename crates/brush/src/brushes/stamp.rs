@@ -0,0 +1,233 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use assets::handle::Handle;
+use assets::storage::AssetStorage;
+use assets::texture::format::Grayscale;
+use assets::texture::{Texture, TextureLoadInfo};
+use assets::{texel_at_uv, BorderMode, Heightmap, NormalMap, TexelRadius, Uv, WorldRadius};
+use gfx::Samplers;
+use glam::{IVec2, Vec3};
+use inject::DI;
+use phobos::{vk, ComputeCmdBuffer, IncompleteCommandBuffer, PipelineStage};
+use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+use world::World;
+
+use crate::undo::BrushTarget;
+use crate::util::{
+    dispatch_patch_rect, get_terrain_info, height_views, position_on_terrain, prepare_for_read,
+    prepare_for_write, submit_brush_work, update_normals_around_patch, with_ready_detail_map,
+    with_ready_terrain, BrushDomain,
+};
+use crate::{Brush, BrushSettings, HeightLayer};
+
+/// Format of stamp images. Grayscale values are sampled in [0, 1], black leaves the terrain
+/// untouched.
+pub type StampFormat = Grayscale<u16>;
+pub type StampImage = Texture<StampFormat>;
+
+/// Push constants of the stamp brush shader.
+/// Kept in sync with `PC` in `stamp_brush.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct StampBrushParams {
+    center: IVec2,
+    weight: f32,
+    size: u32,
+    border_mode: u32,
+    rotation: f32,
+    scale: f32,
+    extent: u32,
+}
+
+/// Imprints a grayscale image onto the heightmap, to place repeated features such as craters
+/// or hills. The image covers the brush area and is added to the terrain, scaled by the weight.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamp {
+    /// Image to imprint, see [`Stamp::load_image`]. Images are not saved with presets.
+    #[serde(skip)]
+    pub image: Option<Handle<StampImage>>,
+    /// Rotation of the image around the brush center, in radians.
+    pub rotation: f32,
+    /// Size of the image relative to the brush diameter.
+    pub scale: f32,
+}
+
+impl Default for Stamp {
+    fn default() -> Self {
+        Self {
+            image: None,
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Stamp {
+    /// Start loading a stamp image from a file.
+    /// # DI Access
+    /// - Read [`AssetStorage`]
+    pub fn load_image(bus: &EventBus<DI>, path: PathBuf) -> Handle<StampImage> {
+        let di = bus.data().read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        assets.load(TextureLoadInfo::FromPath {
+            path,
+            cpu_postprocess: None,
+            usage_flags: None,
+            resize: None,
+        })
+    }
+
+    fn record_height_update<'q, D: BrushDomain>(
+        bus: &EventBus<DI>,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        params: &StampBrushParams,
+        heights: &Heightmap,
+        image: &StampImage,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let di = bus.data().read().unwrap();
+        let samplers = di.get::<Samplers>().unwrap();
+        let cmd =
+            prepare_for_write(&heights.image, cmd, PipelineStage::TESSELLATION_EVALUATION_SHADER);
+        let cmd = cmd
+            .bind_compute_pipeline("stamp_brush")?
+            .bind_storage_image(0, 0, &heights.image.image.view)?
+            .bind_sampled_image(0, 1, &image.image.view, &samplers.linear)?
+            .push_constant(vk::ShaderStageFlags::COMPUTE, 0, params);
+        // A stamp scaled up reaches beyond the brush radius
+        let cmd = dispatch_patch_rect(cmd, params.extent, 16)?;
+        Ok(prepare_for_read(
+            &heights.image,
+            cmd,
+            PipelineStage::COMPUTE_SHADER,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+        ))
+    }
+
+    fn record_update_commands<'q, D: BrushDomain>(
+        bus: &EventBus<DI>,
+        cmd: IncompleteCommandBuffer<'q, D>,
+        params: &StampBrushParams,
+        heights: &Heightmap,
+        normals: Option<&NormalMap>,
+        image: &StampImage,
+    ) -> Result<IncompleteCommandBuffer<'q, D>> {
+        let cmd = Self::record_height_update(bus, cmd, params, heights, image)?;
+        let Some(normals) = normals else { return Ok(cmd) };
+        let cmd = prepare_for_write(&normals.image, cmd, PipelineStage::FRAGMENT_SHADER);
+        let extent = TexelRadius(params.extent);
+        let cmd = update_normals_around_patch(bus, cmd, params.center, extent, heights, normals)?;
+        Ok(prepare_for_read(
+            &normals.image,
+            cmd,
+            PipelineStage::BOTTOM_OF_PIPE,
+            vk::AccessFlags2::NONE,
+        ))
+    }
+
+    fn params(
+        &self,
+        center: IVec2,
+        radius: TexelRadius,
+        extent: TexelRadius,
+        weight: f32,
+        border: BorderMode,
+    ) -> StampBrushParams {
+        StampBrushParams {
+            center,
+            weight,
+            size: radius.0,
+            border_mode: border.shader_value(),
+            rotation: self.rotation,
+            scale: self.scale,
+            extent: extent.0,
+        }
+    }
+
+    fn apply_at_uv(
+        &self,
+        bus: &EventBus<DI>,
+        position: Vec3,
+        uv: Uv,
+        settings: BrushSettings,
+        image: &StampImage,
+    ) -> Result<()> {
+        let (terrain, terrain_options) = get_terrain_info(bus);
+        let Some(terrain) = terrain else { bail!("Used brush but terrain handle is not set.") };
+        // Inverting digs the image into the terrain instead of raising it
+        let weight = if settings.invert {
+            -settings.weight
+        } else {
+            settings.weight
+        };
+        let footprint = self.footprint(&settings);
+        match settings.layer {
+            HeightLayer::Base => with_ready_terrain(bus, terrain, |heights, normals, _, _| {
+                let radius =
+                    terrain_options.texel_radius(position, settings.radius, &heights.image);
+                let extent = terrain_options.texel_radius(position, footprint, &heights.image);
+                let center = texel_at_uv(uv, heights.image.width(), heights.image.height());
+                let params =
+                    self.params(center, radius, extent, weight, terrain_options.border_mode);
+                let written = height_views(heights, Some(normals));
+                submit_brush_work!(bus, written, |cmd| Self::record_update_commands(
+                    bus,
+                    cmd,
+                    &params,
+                    heights,
+                    Some(normals),
+                    image
+                ));
+                Ok(())
+            })?,
+            HeightLayer::Detail => with_ready_detail_map(bus, terrain, |detail| {
+                let (width, height) = (detail.image.width(), detail.image.height());
+                let center = terrain_options.detail_texel_at_uv(uv, width, height);
+                let radius =
+                    terrain_options.detail_texel_radius(position, settings.radius, &detail.image);
+                let extent =
+                    terrain_options.detail_texel_radius(position, footprint, &detail.image);
+                // The detail layer is tiled over the terrain, see SmoothHeight
+                let params = self.params(center, radius, extent, weight, BorderMode::Wrap);
+                submit_brush_work!(bus, height_views(detail, None), |cmd| {
+                    Self::record_update_commands(bus, cmd, &params, detail, None, image)
+                });
+                Ok(())
+            })??,
+        }
+        Ok(())
+    }
+}
+
+impl Brush for Stamp {
+    fn targets(&self, settings: &BrushSettings) -> &'static [BrushTarget] {
+        match settings.layer {
+            HeightLayer::Base => &[BrushTarget::Height(HeightLayer::Base), BrushTarget::Normals],
+            HeightLayer::Detail => &[BrushTarget::Height(HeightLayer::Detail)],
+        }
+    }
+
+    fn footprint(&self, settings: &BrushSettings) -> WorldRadius {
+        // The image covers the brush area scaled by `scale`
+        WorldRadius::new(settings.radius.0 * self.scale.max(1.0))
+    }
+
+    fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()> {
+        if !position_on_terrain(position) {
+            return Ok(());
+        }
+        let Some(image) = self.image else { bail!("The stamp brush has no image.") };
+
+        let di = bus.data().read().unwrap();
+        let uv = {
+            let world = di.read_sync::<World>().unwrap();
+            world.terrain_options.uv_at(position)
+        };
+        let assets = di.get::<AssetStorage>().unwrap();
+        // Stamping is skipped until the image finished loading
+        assets
+            .with_if_ready(image, |image| self.apply_at_uv(bus, position, uv, *settings, image))
+            .unwrap_or(Ok(()))
+    }
+}
@@ -0,0 +1,106 @@
+//! Saving the edited terrain to disk.
+
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use assets::handle::Handle;
+use assets::storage::AssetStorage;
+use assets::{Terrain, TerrainSource};
+use image::RgbaImage;
+use inject::DI;
+use pass::GpuWork;
+use scheduler::EventBus;
+use world::World;
+
+use crate::util::{get_terrain_info, with_ready_detail_map, with_ready_terrain};
+
+/// Name of the saved heightmap inside the commit directory.
+pub const HEIGHTMAP_FILE: &str = "heightmap.png";
+/// Name of the saved diffuse map inside the commit directory.
+pub const TEXTURE_FILE: &str = "texture.png";
+/// Name of the saved detail heightmap inside the commit directory.
+pub const DETAIL_FILE: &str = "detail.png";
+
+/// Save the terrain textures to `directory` and reload the terrain from the saved files, so
+/// the world refers to them from now on. Queued brush work is completed first, so the files
+/// include every finished stroke.
+///
+/// The normal map is derived from the heightmap when loading, so it is not saved. Painted
+/// detail normals cannot be loaded from a file, and are reset by the reload.
+/// # DI Access
+/// - Write [`World`]
+/// - Write [`GpuWork`]
+/// - Read [`AssetStorage`]
+pub fn commit_terrain(bus: &EventBus<DI>, directory: &Path) -> Result<TerrainSource> {
+    let (Some(terrain), mut options) = get_terrain_info(bus) else {
+        return Err(anyhow!("there is no terrain to commit"));
+    };
+    std::fs::create_dir_all(directory)?;
+    GpuWork::flush(bus)?;
+    GpuWork::wait_async(bus)?;
+
+    let height_path = directory.join(HEIGHTMAP_FILE);
+    let texture_path = directory.join(TEXTURE_FILE);
+    let height_scale = with_ready_terrain(bus, terrain, |heights, _, texture, _| {
+        let pixels = texture.read_back(bus)?.into_bytes();
+        let image = RgbaImage::from_raw(texture.width(), texture.height(), pixels)
+            .ok_or_else(|| anyhow!("texture data does not match its size"))?;
+        image.save(&texture_path)?;
+        {
+            let di = bus.data().read().unwrap();
+            di.get::<AssetStorage>().unwrap().mark_written(&texture_path);
+        }
+        heights.save(&height_path, bus)
+    })?;
+    options.vertical_scale *= height_scale;
+
+    let detail_path = if has_detail_map(bus, terrain) {
+        let path = directory.join(DETAIL_FILE);
+        options.detail_strength *=
+            with_ready_detail_map(bus, terrain, |detail| detail.save(&path, bus))??;
+        Some(path)
+    } else {
+        None
+    };
+
+    let source = TerrainSource {
+        height_path,
+        texture_path,
+        resize_texture: false,
+        detail_path,
+        options,
+    };
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    let mut world = di.write_sync::<World>().unwrap();
+    world.terrain_options = source.options;
+    world.terrain = Some(assets.load(source.load_info()));
+    world.terrain_source = Some(source.clone());
+    Ok(source)
+}
+
+fn has_detail_map(bus: &EventBus<DI>, terrain: Handle<Terrain>) -> bool {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    assets
+        .with_when_ready(terrain, |terrain| terrain.detail_map.is_some())
+        .unwrap_or(false)
+}
+
+/// Export the heightmap of the terrain to `path` at its native resolution, see
+/// [`Heightmap::export`](assets::Heightmap::export). Queued brush work is completed first, so
+/// the file includes every finished stroke. Returns the scale the heights were stored at.
+/// # DI Access
+/// - Write [`GpuWork`]
+/// - Read [`AssetStorage`]
+pub fn export_heightmap(bus: &EventBus<DI>, path: &Path) -> Result<f32> {
+    let (Some(terrain), _) = get_terrain_info(bus) else {
+        return Err(anyhow!("there is no terrain to export"));
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    GpuWork::flush(bus)?;
+    GpuWork::wait_async(bus)?;
+    with_ready_terrain(bus, terrain, |heights, _, _, _| heights.export(path, bus))
+}
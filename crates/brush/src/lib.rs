@@ -1,17 +1,55 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
 use ::util::mouse_position::WorldMousePosition;
 use ::util::SafeUnwrap;
 use anyhow::Result;
+use assets::reload::AssetReloadedEvent;
+use assets::storage::AssetStorage;
+use assets::{Heightmap, WorldRadius};
 pub use brushes::*;
 use enum_dispatch::enum_dispatch;
+use error::{publish_error, publish_success};
 use events::DragWorldView;
 use gfx::SharedContext;
 use glam::Vec3;
 use hot_reload::IntoDynamic;
 use inject::DI;
-use phobos::ComputePipelineBuilder;
+use log::error;
+use phobos::{vk, ComputePipelineBuilder};
 use scheduler::{Event, EventBus, EventContext, StoredSystem, System};
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+use world::{PropSettings, World};
+
+use crate::bake::{bake_lighting, BakeParams, BAKE_TARGETS};
+use crate::color::ColorBrushParams;
+use crate::commit::{commit_terrain, export_heightmap};
+use crate::detail_normal::DetailNormalBrushParams;
+use crate::equalize::{EqualizeBrushParams, EqualizeMeanParams};
+use crate::flatten::FlattenBrushParams;
+use crate::height::HeightBrushParams;
+use crate::reset::{
+    flatten_terrain, refresh_reloaded_heightmap, reset_terrain_to_source, RESET_TARGETS,
+};
+use crate::scatter::scatter;
+use crate::set_value::{pick_value, BrushValue, SetValueParams, ValueKind};
+use crate::splat::SplatBrushParams;
+use crate::stamp::StampBrushParams;
+use crate::stroke::{stroke_segment, StrokeTimer};
+use crate::undo::{BrushTarget, UndoStack};
+use crate::util::{
+    get_terrain_info, position_on_terrain, update_derived_maps, NormalRecomputeParams,
+};
 
+pub mod bake;
 pub mod brushes;
+pub mod commit;
+pub mod presets;
+pub mod reset;
+pub mod scatter;
+pub mod stroke;
+pub mod undo;
 pub mod util;
 
 type BrushEventReceiver = tokio::sync::mpsc::Receiver<BrushEvent>;
@@ -37,6 +75,18 @@ impl System<DI> for BrushSystem {
         event_bus.subscribe(system, handle_drag_world_view);
         event_bus.subscribe(system, handle_begin_stroke);
         event_bus.subscribe(system, handle_end_stroke);
+        event_bus.subscribe(system, handle_stroke_at);
+        event_bus.subscribe(system, handle_undo);
+        event_bus.subscribe(system, handle_redo);
+        event_bus.subscribe(system, handle_commit_terrain);
+        event_bus.subscribe(system, handle_export_heightmap);
+        event_bus.subscribe(system, handle_pick_brush_value);
+        event_bus.subscribe(system, handle_reset_terrain_to_source);
+        event_bus.subscribe(system, handle_flatten_terrain);
+        event_bus.subscribe(system, handle_bake_lighting);
+        event_bus.subscribe(system, handle_scatter_props);
+        event_bus.subscribe(system, handle_heightmap_reloaded);
+        event_bus.subscribe(system, handle_clear_history);
     }
 }
 
@@ -44,11 +94,16 @@ impl System<DI> for BrushSystem {
 /// must have the same name as the corresponding brush implementation struct.
 /// The brush structs are allowed to have fields inside with extra options.
 #[enum_dispatch]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BrushType {
     SmoothHeight,
     Equalize,
     Color,
+    DetailNormal,
+    Splat,
+    SetValue,
+    Flatten,
+    Stamp,
 }
 
 impl BrushType {
@@ -67,43 +122,239 @@ pub trait Brush {
         None
     }
 
+    /// Terrain textures this brush writes to with the given settings. The regions of these
+    /// textures a stroke modifies are snapshotted so the stroke can be undone.
+    fn targets(&self, settings: &BrushSettings) -> &'static [BrushTarget];
+
+    /// Radius in world space around the stamp position this brush may modify.
+    fn footprint(&self, settings: &BrushSettings) -> WorldRadius {
+        settings.radius
+    }
+
+    /// Called before every stamp of a stroke. Changes to the brush last until the stroke
+    /// ends, which lets a brush configure itself from the terrain at the start of a stroke.
+    fn before_stamp(
+        &mut self,
+        _bus: &EventBus<DI>,
+        _position: Vec3,
+        _settings: &BrushSettings,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Apply a single stamp of the brush. The weight in `settings` has already been scaled to
+    /// the amount for this stamp, see [`StrokeTimer`].
     fn apply(&self, bus: &EventBus<DI>, position: Vec3, settings: &BrushSettings) -> Result<()>;
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+/// Heightmap layer of the terrain a height brush is applied to.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Display, Serialize, Deserialize)]
+pub enum HeightLayer {
+    #[default]
+    Base,
+    Detail,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct BrushSettings {
-    pub radius: f32,
+    /// Radius of the brush in world space. Brushes convert it to texels on the texture they
+    /// modify through the terrain options, since terrain textures differ in size.
+    pub radius: WorldRadius,
+    /// Strength of the brush per second of stroke time.
     pub weight: f32,
     pub invert: bool,
     // Only do one tick of the brush per location, instead of
     // stacking up multiple on every mouse position
     pub once: bool,
+    #[serde(default)]
+    pub layer: HeightLayer,
+    /// Distance between stamps along a stroke, as a fraction of the radius. When the stroke
+    /// moves further than this between two positions, stamps are added in between so fast
+    /// strokes stay continuous. Zero only stamps at the positions of the stroke.
+    #[serde(default = "BrushSettings::default_spacing")]
+    pub spacing: f32,
+}
+
+impl BrushSettings {
+    fn default_spacing() -> f32 {
+        0.25
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct BeginStrokeEvent {
     pub settings: BrushSettings,
     pub brush: BrushType,
+    /// Time the stroke started at, see [`StrokeAtEvent::time`].
+    pub time: Option<Instant>,
 }
 
 pub struct EndStrokeEvent;
 
+/// Apply the active brush stroke at a world position, as if the world view was dragged over it.
+/// This lets brushes be driven without the mouse, for example from a script.
+#[derive(Debug, Copy, Clone)]
+pub struct StrokeAtEvent {
+    pub position: Vec3,
+    /// Time of the stamp, the brush weight is scaled by the time since the previous stamp.
+    /// Defaults to the time the event is handled. Scripts pass the time of their frame, so
+    /// strokes do not depend on the actual frame rate.
+    pub time: Option<Instant>,
+}
+
+/// Undo the last brush stroke.
+pub struct UndoEvent;
+
+/// Redo the last undone brush stroke.
+pub struct RedoEvent;
+
+/// Save the terrain textures to the directory at `path`, and reload the terrain from the saved
+/// files. See [`commit_terrain`].
+pub struct CommitTerrainEvent {
+    pub path: PathBuf,
+}
+
+/// Export the base heightmap to the file at `path`, see [`export_heightmap`].
+pub struct ExportHeightmapEvent {
+    pub path: PathBuf,
+}
+
+/// Read the terrain value under the mouse cursor for the eyedropper. The value is stored in
+/// [`PickedBrushValue`] once it has been read.
+pub struct PickBrushValueEvent {
+    pub kind: ValueKind,
+    pub layer: HeightLayer,
+}
+
+/// Upload the heightmap the terrain was opened from again, discarding all edits to the base
+/// heightmap. This can be undone like a brush stroke.
+pub struct ResetTerrainToSourceEvent;
+
+/// Set the entire base heightmap to a single height. This can be undone like a brush stroke.
+pub struct FlattenTerrainEvent {
+    /// Height as stored in the heightmap, before the vertical scale of the terrain is applied.
+    pub height: f32,
+}
+
+/// Multiply the diffuse map of the terrain with its current lighting, see [`bake_lighting`].
+/// This can be undone like a brush stroke.
+pub struct BakeLightingEvent {
+    /// Light added to every texel, on top of the light received from the sun.
+    pub ambient: f32,
+}
+
+/// Scatter props over the terrain with `settings`, replacing the props that were scattered
+/// before. See [`scatter`].
+pub struct ScatterPropsEvent {
+    pub settings: PropSettings,
+}
+
+/// Forget all brush strokes and edits that could be undone or redone, for example because the
+/// terrain they were made on was replaced.
+pub struct ClearHistoryEvent;
+
+/// Value last read by the eyedropper, see [`PickBrushValueEvent`]. Take the value out to apply it
+/// to a brush.
+/// Access through DI.
+#[derive(Debug, Default)]
+pub struct PickedBrushValue {
+    pub value: Option<BrushValue>,
+}
+
 impl Event for BeginStrokeEvent {}
 impl Event for EndStrokeEvent {}
+impl Event for StrokeAtEvent {}
+impl Event for UndoEvent {}
+impl Event for RedoEvent {}
+impl Event for CommitTerrainEvent {}
+impl Event for ExportHeightmapEvent {}
+impl Event for PickBrushValueEvent {}
+impl Event for ResetTerrainToSourceEvent {}
+impl Event for FlattenTerrainEvent {}
+impl Event for BakeLightingEvent {}
+impl Event for ScatterPropsEvent {}
+impl Event for ClearHistoryEvent {}
 
 #[derive(Debug)]
 enum BrushEvent {
     BeginStroke {
         settings: BrushSettings,
         brush: BrushType,
+        time: Instant,
+    },
+    StrokeAt {
+        position: Vec3,
+        time: Instant,
     },
-    StrokeAt(Vec3),
     EndStroke,
+    Undo,
+    Redo,
+    Commit {
+        path: PathBuf,
+    },
+    ExportHeightmap {
+        path: PathBuf,
+    },
+    Pick {
+        position: Vec3,
+        kind: ValueKind,
+        layer: HeightLayer,
+    },
+    ResetToSource,
+    Flatten {
+        height: f32,
+    },
+    BakeLighting {
+        ambient: f32,
+    },
+    ScatterProps {
+        settings: PropSettings,
+    },
+    HeightmapReloaded,
+    ClearHistory,
+}
+
+/// Run an edit of the terrain that modifies `targets` as a single undoable transaction.
+fn edit_in_transaction(
+    bus: &EventBus<DI>,
+    history: &mut UndoStack,
+    targets: &[BrushTarget],
+    edit: impl FnOnce(&EventBus<DI>) -> Result<()>,
+) -> Result<()> {
+    let (Some(terrain), _) = get_terrain_info(bus) else { return Ok(()) };
+    history.begin_transaction(terrain, targets)?;
+    match edit(bus) {
+        Ok(_) => {
+            history.end_stroke();
+            Ok(())
+        }
+        // Nothing was modified, so there is nothing to undo
+        Err(e) => {
+            history.cancel();
+            Err(e)
+        }
+    }
+}
+
+/// Run an edit of the entire base heightmap as a single undoable transaction, and update
+/// everything derived from the heightmap afterwards.
+fn edit_base_heightmap(
+    bus: &EventBus<DI>,
+    history: &mut UndoStack,
+    edit: impl FnOnce(&EventBus<DI>) -> Result<()>,
+) -> Result<()> {
+    edit_in_transaction(bus, history, RESET_TARGETS, edit)?;
+    invalidate_terrain_bounds(bus);
+    update_derived_maps(bus)
 }
 
 fn brush_task(bus: EventBus<DI>, mut recv: BrushEventReceiver) {
     let mut current_settings = BrushSettings::default();
     let mut current_brush = None;
+    let mut timer = StrokeTimer::new(Instant::now());
+    // Last position of the current stroke, stamps are interpolated from here
+    let mut previous_position = None;
+    let mut history = UndoStack::new(&bus);
 
     // While the sender is not dropped, we can keep waiting for events
     while let Some(event) = recv.blocking_recv() {
@@ -111,24 +362,235 @@ fn brush_task(bus: EventBus<DI>, mut recv: BrushEventReceiver) {
             BrushEvent::BeginStroke {
                 settings,
                 brush,
+                time,
             } => {
                 current_brush = Some(brush);
                 current_settings = settings;
+                timer = StrokeTimer::new(time);
+                previous_position = None;
+                if let (Some(terrain), _) = get_terrain_info(&bus) {
+                    history.begin_stroke(terrain, &brush, &settings);
+                }
             }
-            BrushEvent::StrokeAt(position) => {
+            BrushEvent::StrokeAt {
+                position,
+                time,
+            } => {
                 // Only actually stroke if a brush is active
-                match &current_brush {
+                match &mut current_brush {
                     None => {}
-                    Some(brush) => brush.apply(&bus, position, &current_settings).safe_unwrap(),
+                    Some(brush) => {
+                        let step = current_settings.radius.0 * current_settings.spacing;
+                        let positions = stroke_segment(previous_position, position, step);
+                        previous_position = Some(position).filter(|p| position_on_terrain(*p));
+                        // Spread the weight of this stamp over the interpolated stamps, so
+                        // the total amount applied does not depend on the spacing.
+                        let weight = timer.stamp_weight(current_settings.weight, time);
+                        let settings = BrushSettings {
+                            weight: weight / positions.len() as f32,
+                            ..current_settings
+                        };
+                        for position in positions {
+                            brush.before_stamp(&bus, position, &settings).safe_unwrap();
+                            history
+                                .before_stamp(position, brush.footprint(&settings))
+                                .safe_unwrap();
+                            brush.apply(&bus, position, &settings).safe_unwrap()
+                        }
+                    }
                 }
             }
             BrushEvent::EndStroke => {
-                current_brush = None;
+                previous_position = None;
+                let base_heights = BrushTarget::Height(HeightLayer::Base);
+                let modified_heights = current_brush.take().map_or(false, |brush| {
+                    brush.targets(&current_settings).contains(&base_heights)
+                });
+                history.end_stroke();
+                invalidate_terrain_bounds(&bus);
+                if modified_heights {
+                    update_derived_maps(&bus).safe_unwrap();
+                }
+            }
+            // Strokes are never undone halfway through
+            BrushEvent::Undo if current_brush.is_none() => match history.undo() {
+                Ok(true) => {
+                    invalidate_terrain_bounds(&bus);
+                    update_derived_maps(&bus).safe_unwrap();
+                }
+                Ok(false) => {}
+                Err(e) => error!("Could not undo brush stroke: {e}"),
+            },
+            BrushEvent::Redo if current_brush.is_none() => match history.redo() {
+                Ok(true) => {
+                    invalidate_terrain_bounds(&bus);
+                    update_derived_maps(&bus).safe_unwrap();
+                }
+                Ok(false) => {}
+                Err(e) => error!("Could not redo brush stroke: {e}"),
+            },
+            BrushEvent::Undo | BrushEvent::Redo => {}
+            // Since brush work is queued from this task, no stroke can modify the terrain
+            // while it is being read back.
+            BrushEvent::Commit {
+                path,
+            } if current_brush.is_none() => match commit_terrain(&bus, &path) {
+                Ok(_) => {
+                    // The history refers to the textures of the old terrain
+                    history.clear();
+                    let path = path.display();
+                    publish_success!(bus, source = "terrain", "Committed terrain to {path}");
+                }
+                Err(e) => {
+                    let path = path.display();
+                    publish_error!(
+                        bus,
+                        source = "terrain",
+                        "Could not commit terrain to {path}: {e}"
+                    );
+                }
+            },
+            BrushEvent::Commit {
+                ..
+            } => {
+                error!("Cannot commit the terrain in the middle of a brush stroke.");
+            }
+            BrushEvent::ExportHeightmap {
+                path,
+            } if current_brush.is_none() => match export_heightmap(&bus, &path) {
+                // The heights did not fit in the file as they are, so the terrain only looks
+                // the same with a different vertical scale.
+                Ok(scale) if scale != 1.0 => {
+                    let path = path.display();
+                    publish_success!(
+                        bus,
+                        source = "terrain",
+                        "Exported heightmap to {path}. Multiply the vertical scale by {scale} \
+                         when loading it."
+                    );
+                }
+                Ok(_) => {
+                    let path = path.display();
+                    publish_success!(bus, source = "terrain", "Exported heightmap to {path}");
+                }
+                Err(e) => {
+                    let path = path.display();
+                    publish_error!(
+                        bus,
+                        source = "terrain",
+                        "Could not export heightmap to {path}: {e}"
+                    );
+                }
+            },
+            BrushEvent::ExportHeightmap {
+                ..
+            } => {
+                error!("Cannot export the heightmap in the middle of a brush stroke.");
+            }
+            BrushEvent::Pick {
+                position,
+                kind,
+                layer,
+            } => match pick_value(&bus, position, kind, layer) {
+                Ok(None) => {}
+                Ok(Some(value)) => {
+                    let di = bus.data().read().unwrap();
+                    di.write_sync::<PickedBrushValue>().unwrap().value = Some(value);
+                }
+                Err(e) => error!("Could not pick terrain value: {e}"),
+            },
+            BrushEvent::ResetToSource if current_brush.is_none() => {
+                match edit_base_heightmap(&bus, &mut history, reset_terrain_to_source) {
+                    Ok(_) => {
+                        publish_success!(
+                            bus,
+                            source = "terrain",
+                            "Reset the terrain to its source"
+                        );
+                    }
+                    Err(e) => {
+                        publish_error!(bus, source = "terrain", "Could not reset the terrain: {e}");
+                    }
+                }
+            }
+            BrushEvent::Flatten {
+                height,
+            } if current_brush.is_none() => {
+                let flatten = |bus: &EventBus<DI>| flatten_terrain(bus, height);
+                if let Err(e) = edit_base_heightmap(&bus, &mut history, flatten) {
+                    publish_error!(bus, source = "terrain", "Could not flatten the terrain: {e}");
+                }
+            }
+            BrushEvent::BakeLighting {
+                ambient,
+            } if current_brush.is_none() => {
+                let bake = |bus: &EventBus<DI>| bake_lighting(bus, ambient);
+                match edit_in_transaction(&bus, &mut history, BAKE_TARGETS, bake) {
+                    Ok(_) => {
+                        publish_success!(
+                            bus,
+                            source = "terrain",
+                            "Baked the lighting into the terrain color"
+                        );
+                    }
+                    Err(e) => {
+                        publish_error!(bus, source = "terrain", "Could not bake the lighting: {e}");
+                    }
+                }
+            }
+            // Props are scattered on the heights of the finished strokes
+            BrushEvent::ScatterProps {
+                settings,
+            } if current_brush.is_none() => match scatter(&bus, &settings) {
+                Ok(count) => {
+                    publish_success!(bus, source = "props", "Scattered {count} props");
+                }
+                Err(e) => {
+                    publish_error!(bus, source = "props", "Could not scatter props: {e}");
+                }
+            },
+            BrushEvent::ResetToSource
+            | BrushEvent::Flatten {
+                ..
+            } => {
+                error!("Cannot reset the terrain in the middle of a brush stroke.");
+            }
+            BrushEvent::BakeLighting {
+                ..
+            } => {
+                error!("Cannot bake the lighting in the middle of a brush stroke.");
             }
+            BrushEvent::ScatterProps {
+                ..
+            } => {
+                error!("Cannot scatter props in the middle of a brush stroke.");
+            }
+            BrushEvent::HeightmapReloaded => match refresh_reloaded_heightmap(&bus) {
+                // The history holds edits of the heightmap before it was reloaded
+                Ok(_) => history.clear(),
+                Err(e) => {
+                    publish_error!(
+                        bus,
+                        source = "terrain",
+                        "Could not update the terrain after its heightmap was reloaded: {e}"
+                    );
+                }
+            },
+            BrushEvent::ClearHistory => history.clear(),
         }
     }
 }
 
+/// The terrain was modified, so its height range may have changed.
+/// # DI Access
+/// - Write [`World`]
+fn invalidate_terrain_bounds(bus: &EventBus<DI>) {
+    let di = bus.data().read().unwrap();
+    di.write_sync::<World>()
+        .unwrap()
+        .invalidate_terrain_bounds();
+}
+
 fn handle_drag_world_view(
     system: &mut BrushSystem,
     _drag: &DragWorldView,
@@ -139,9 +601,10 @@ fn handle_drag_world_view(
     match mouse.world_space {
         None => {}
         Some(pos) => {
-            system
-                .event_sender
-                .blocking_send(BrushEvent::StrokeAt(pos))?;
+            system.event_sender.blocking_send(BrushEvent::StrokeAt {
+                position: pos,
+                time: Instant::now(),
+            })?;
         }
     };
     Ok(())
@@ -155,6 +618,7 @@ fn handle_begin_stroke(
     system.event_sender.blocking_send(BrushEvent::BeginStroke {
         settings: stroke.settings,
         brush: stroke.brush,
+        time: stroke.time.unwrap_or_else(Instant::now),
     })?;
     Ok(())
 }
@@ -168,6 +632,159 @@ fn handle_end_stroke(
     Ok(())
 }
 
+fn handle_stroke_at(
+    system: &mut BrushSystem,
+    stroke: &StrokeAtEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system.event_sender.blocking_send(BrushEvent::StrokeAt {
+        position: stroke.position,
+        time: stroke.time.unwrap_or_else(Instant::now),
+    })?;
+    Ok(())
+}
+
+fn handle_undo(
+    system: &mut BrushSystem,
+    _event: &UndoEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system.event_sender.blocking_send(BrushEvent::Undo)?;
+    Ok(())
+}
+
+fn handle_redo(
+    system: &mut BrushSystem,
+    _event: &RedoEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system.event_sender.blocking_send(BrushEvent::Redo)?;
+    Ok(())
+}
+
+fn handle_commit_terrain(
+    system: &mut BrushSystem,
+    event: &CommitTerrainEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system.event_sender.blocking_send(BrushEvent::Commit {
+        path: event.path.clone(),
+    })?;
+    Ok(())
+}
+
+fn handle_export_heightmap(
+    system: &mut BrushSystem,
+    event: &ExportHeightmapEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system
+        .event_sender
+        .blocking_send(BrushEvent::ExportHeightmap {
+            path: event.path.clone(),
+        })?;
+    Ok(())
+}
+
+/// # DI Access
+/// - Read [`World`]
+/// - Read [`AssetStorage`]
+fn handle_heightmap_reloaded(
+    system: &mut BrushSystem,
+    event: &AssetReloadedEvent<Heightmap>,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let (Some(terrain), _) = get_terrain_info(ctx.bus()) else { return Ok(()) };
+    let heights = {
+        let di = ctx.read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        assets.with_if_ready(terrain, |terrain| terrain.height_map)
+    };
+    // Detail maps and heightmaps of other terrains do not affect anything else
+    if heights == Some(event.handle) {
+        system
+            .event_sender
+            .blocking_send(BrushEvent::HeightmapReloaded)?;
+    }
+    Ok(())
+}
+
+fn handle_clear_history(
+    system: &mut BrushSystem,
+    _event: &ClearHistoryEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system.event_sender.blocking_send(BrushEvent::ClearHistory)?;
+    Ok(())
+}
+
+fn handle_pick_brush_value(
+    system: &mut BrushSystem,
+    event: &PickBrushValueEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let mouse = di.read_sync::<WorldMousePosition>().unwrap();
+    if let Some(position) = mouse.world_space {
+        system.event_sender.blocking_send(BrushEvent::Pick {
+            position,
+            kind: event.kind,
+            layer: event.layer,
+        })?;
+    }
+    Ok(())
+}
+
+fn handle_reset_terrain_to_source(
+    system: &mut BrushSystem,
+    _event: &ResetTerrainToSourceEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system
+        .event_sender
+        .blocking_send(BrushEvent::ResetToSource)?;
+    Ok(())
+}
+
+fn handle_flatten_terrain(
+    system: &mut BrushSystem,
+    event: &FlattenTerrainEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system.event_sender.blocking_send(BrushEvent::Flatten {
+        height: event.height,
+    })?;
+    Ok(())
+}
+
+fn handle_bake_lighting(
+    system: &mut BrushSystem,
+    event: &BakeLightingEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system
+        .event_sender
+        .blocking_send(BrushEvent::BakeLighting {
+            ambient: event.ambient,
+        })?;
+    Ok(())
+}
+
+fn handle_scatter_props(
+    system: &mut BrushSystem,
+    event: &ScatterPropsEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system
+        .event_sender
+        .blocking_send(BrushEvent::ScatterProps {
+            settings: event.settings,
+        })?;
+    Ok(())
+}
+
+/// Brush pipelines validate their shaders against the bindings and push constants the brushes
+/// supply, so a mismatch is reported when the shader is compiled.
 fn create_brush_pipeline(bus: &EventBus<DI>) -> Result<()> {
     let di = bus.data().read().unwrap();
     let gfx = di.get::<SharedContext>().cloned().unwrap();
@@ -176,16 +793,100 @@ fn create_brush_pipeline(bus: &EventBus<DI>) -> Result<()> {
         .persistent()
         .into_dynamic()
         .set_shader("shaders/src/height_brush.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<HeightBrushParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("flatten_brush")
+        .persistent()
+        .into_dynamic()
+        .set_shader("shaders/src/flatten_brush.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<FlattenBrushParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("stamp_brush")
+        .persistent()
+        .into_dynamic()
+        .set_shader("shaders/src/stamp_brush.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_binding(0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .expect_push_constants(std::mem::size_of::<StampBrushParams>() as u32)
         .build(bus, gfx.pipelines.clone())?;
     ComputePipelineBuilder::new("normal_recompute")
         .persistent()
         .into_dynamic()
         .set_shader("shaders/src/normal_recompute.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_binding(0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .expect_push_constants(std::mem::size_of::<NormalRecomputeParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("equalize_mean")
+        .persistent()
+        .into_dynamic()
+        .set_shader("shaders/src/equalize_mean.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_binding(0, 1, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<EqualizeMeanParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("equalize_brush")
+        .persistent()
+        .into_dynamic()
+        .set_shader("shaders/src/equalize_brush.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_binding(0, 1, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<EqualizeBrushParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("color_brush")
+        .persistent()
+        .into_dynamic()
+        .set_shader("shaders/src/color_brush.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_binding(0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .expect_push_constants(std::mem::size_of::<ColorBrushParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("detail_normal_brush")
+        .persistent()
+        .into_dynamic()
+        .set_shader("shaders/src/detail_normal_brush.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<DetailNormalBrushParams>() as u32)
         .build(bus, gfx.pipelines.clone())?;
-    ComputePipelineBuilder::new("blur_brush")
+    ComputePipelineBuilder::new("splat_brush")
         .persistent()
         .into_dynamic()
-        .set_shader("shaders/src/blur_brush.cs.hlsl")
+        .set_shader("shaders/src/splat_brush.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<SplatBrushParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("set_height_brush")
+        .persistent()
+        .into_dynamic()
+        .set_shader_entry("shaders/src/set_value_brush.cs.hlsl", "set_height")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<SetValueParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("set_color_brush")
+        .persistent()
+        .into_dynamic()
+        .set_shader_entry("shaders/src/set_value_brush.cs.hlsl", "set_color")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<SetValueParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("fill_height")
+        .persistent()
+        .into_dynamic()
+        .set_shader_entry("shaders/src/set_value_brush.cs.hlsl", "fill_height")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_push_constants(std::mem::size_of::<SetValueParams>() as u32)
+        .build(bus, gfx.pipelines.clone())?;
+    ComputePipelineBuilder::new("bake_lighting")
+        .persistent()
+        .into_dynamic()
+        .set_shader("shaders/src/bake_lighting.cs.hlsl")
+        .expect_binding(0, 0, vk::DescriptorType::STORAGE_IMAGE)
+        .expect_binding(0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .expect_binding(0, 2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .expect_binding(0, 3, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .expect_push_constants(std::mem::size_of::<BakeParams>() as u32)
         .build(bus, gfx.pipelines)?;
     Ok(())
 }
@@ -194,8 +895,131 @@ pub fn initialize(bus: &EventBus<DI>) -> Result<()> {
     let (tx, rx) = tokio::sync::mpsc::channel(4);
     let system = BrushSystem::new(tx);
     bus.add_system(system);
+    bus.data()
+        .write()
+        .unwrap()
+        .put_sync(PickedBrushValue::default());
     create_brush_pipeline(bus)?;
     let bus = bus.clone();
     tokio::task::spawn_blocking(|| brush_task(bus, rx));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use scheduler::TestBus;
+
+    use super::*;
+
+    #[test]
+    fn push_constants_match_shader_layouts() {
+        // Offsets of the last member plus its size, as laid out in the shaders
+        assert_eq!(std::mem::size_of::<HeightBrushParams>(), 28);
+        assert_eq!(std::mem::size_of::<FlattenBrushParams>(), 32);
+        assert_eq!(std::mem::size_of::<StampBrushParams>(), 32);
+        assert_eq!(std::mem::size_of::<NormalRecomputeParams>(), 36);
+        assert_eq!(std::mem::size_of::<EqualizeMeanParams>(), 16);
+        assert_eq!(std::mem::size_of::<EqualizeBrushParams>(), 20);
+        assert_eq!(std::mem::size_of::<ColorBrushParams>(), 40);
+        assert_eq!(std::mem::size_of::<DetailNormalBrushParams>(), 24);
+        assert_eq!(std::mem::size_of::<SplatBrushParams>(), 24);
+        assert_eq!(std::mem::size_of::<SetValueParams>(), 32);
+        assert_eq!(std::mem::size_of::<BakeParams>(), 32);
+    }
+
+    #[test]
+    fn events_are_forwarded_to_the_brush_thread_in_order() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let bus = TestBus::new().with_system(BrushSystem::new(tx));
+        bus.publish(BeginStrokeEvent {
+            settings: BrushSettings::default(),
+            brush: BrushType::new(Equalize {}),
+            time: None,
+        })
+        .unwrap();
+        bus.publish(EndStrokeEvent).unwrap();
+        bus.publish(UndoEvent).unwrap();
+        bus.publish(RedoEvent).unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(BrushEvent::BeginStroke {
+                brush: BrushType::Equalize(_),
+                ..
+            })
+        ));
+        assert!(matches!(rx.try_recv(), Ok(BrushEvent::EndStroke)));
+        assert!(matches!(rx.try_recv(), Ok(BrushEvent::Undo)));
+        assert!(matches!(rx.try_recv(), Ok(BrushEvent::Redo)));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn strokes_at_a_position_are_forwarded_to_the_brush_thread() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let bus = TestBus::new().with_system(BrushSystem::new(tx));
+        bus.publish(StrokeAtEvent {
+            position: Vec3::ONE,
+            time: None,
+        })
+        .unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(BrushEvent::StrokeAt {
+                position,
+                ..
+            }) if position == Vec3::ONE
+        ));
+        let time = Instant::now() + Duration::from_secs(1);
+        bus.publish(StrokeAtEvent {
+            position: Vec3::ZERO,
+            time: Some(time),
+        })
+        .unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(BrushEvent::StrokeAt {
+                time: forwarded,
+                ..
+            }) if forwarded == time
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn terrain_resets_are_forwarded_to_the_brush_thread() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let bus = TestBus::new().with_system(BrushSystem::new(tx));
+        bus.publish(FlattenTerrainEvent {
+            height: 0.25,
+        })
+        .unwrap();
+        bus.publish(ResetTerrainToSourceEvent).unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(BrushEvent::Flatten {
+                height
+            }) if height == 0.25
+        ));
+        assert!(matches!(rx.try_recv(), Ok(BrushEvent::ResetToSource)));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn lighting_bakes_are_forwarded_to_the_brush_thread() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let bus = TestBus::new().with_system(BrushSystem::new(tx));
+        bus.publish(BakeLightingEvent {
+            ambient: 0.2,
+        })
+        .unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(BrushEvent::BakeLighting {
+                ambient
+            }) if ambient == 0.2
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+}
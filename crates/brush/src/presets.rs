@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use assets::{SplatMap, WorldRadius};
+use serde::{Deserialize, Serialize};
+
+use crate::height::WeightFunction;
+use crate::set_value::BrushValue;
+use crate::{BrushSettings, BrushType, Equalize, HeightLayer, SmoothHeight};
+
+/// A named brush configuration, storing both the global brush settings and the
+/// parameters of the brush itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrushPreset {
+    pub name: String,
+    pub settings: BrushSettings,
+    pub brush: BrushType,
+}
+
+impl BrushPreset {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("Brush preset name cannot be empty");
+        }
+        let settings = &self.settings;
+        if !settings.radius.0.is_finite() || settings.radius.0 <= 0.0 {
+            bail!("Brush preset {:?} has invalid radius {}", self.name, settings.radius.0);
+        }
+        if !settings.weight.is_finite() || settings.weight <= 0.0 {
+            bail!("Brush preset {:?} has invalid weight {}", self.name, settings.weight);
+        }
+        match &self.brush {
+            BrushType::SmoothHeight(brush) => {
+                if let Err(e) = brush.weight_fn.validate() {
+                    bail!("Brush preset {:?} has {e}", self.name);
+                }
+            }
+            BrushType::Equalize(_) => {}
+            BrushType::Flatten(brush) => {
+                if brush
+                    .target_height
+                    .map_or(false, |height| !height.is_finite())
+                {
+                    bail!(
+                        "Brush preset {:?} has invalid target height {:?}",
+                        self.name,
+                        brush.target_height
+                    );
+                }
+                if let Err(e) = brush.weight_fn.validate() {
+                    bail!("Brush preset {:?} has {e}", self.name);
+                }
+            }
+            BrushType::Color(brush) => {
+                if !brush.color.is_finite() {
+                    bail!("Brush preset {:?} has invalid color {}", self.name, brush.color);
+                }
+                if !brush.max_slope.is_finite() {
+                    bail!(
+                        "Brush preset {:?} has invalid maximum slope {}",
+                        self.name,
+                        brush.max_slope
+                    );
+                }
+            }
+            BrushType::DetailNormal(brush) => {
+                if !brush.strength.is_finite() || brush.strength < 0.0 {
+                    bail!("Brush preset {:?} has invalid strength {}", self.name, brush.strength);
+                }
+            }
+            BrushType::Splat(brush) => {
+                if brush.channel >= SplatMap::CHANNELS {
+                    bail!("Brush preset {:?} has invalid layer {}", self.name, brush.channel);
+                }
+            }
+            BrushType::Stamp(brush) => {
+                if !brush.rotation.is_finite() {
+                    bail!("Brush preset {:?} has invalid rotation {}", self.name, brush.rotation);
+                }
+                if !brush.scale.is_finite() || brush.scale <= 0.0 {
+                    bail!("Brush preset {:?} has invalid scale {}", self.name, brush.scale);
+                }
+            }
+            BrushType::SetValue(brush) => {
+                let valid = match brush.value {
+                    BrushValue::Height(height) => height.is_finite(),
+                    BrushValue::Color(color) => color.is_finite(),
+                };
+                if !valid {
+                    bail!("Brush preset {:?} has invalid value {:?}", self.name, brush.value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A collection of brush presets that can be saved to and loaded from disk.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrushPresets {
+    pub presets: Vec<BrushPreset>,
+}
+
+impl BrushPresets {
+    /// Presets shipped with the editor, used when no preset file exists yet.
+    pub fn builtin() -> Self {
+        Self {
+            presets: vec![
+                BrushPreset {
+                    name: "Soft raise".to_owned(),
+                    settings: BrushSettings {
+                        radius: WorldRadius(48.0),
+                        weight: 0.5,
+                        invert: false,
+                        once: false,
+                        layer: HeightLayer::Base,
+                        spacing: 0.25,
+                    },
+                    brush: BrushType::new(SmoothHeight {
+                        weight_fn: WeightFunction::Gaussian(0.35),
+                    }),
+                },
+                BrushPreset {
+                    name: "Sharp raise".to_owned(),
+                    settings: BrushSettings {
+                        radius: WorldRadius(16.0),
+                        weight: 2.0,
+                        invert: false,
+                        once: false,
+                        layer: HeightLayer::Base,
+                        spacing: 0.25,
+                    },
+                    brush: BrushType::new(SmoothHeight {
+                        weight_fn: WeightFunction::Gaussian(0.1),
+                    }),
+                },
+                BrushPreset {
+                    name: "Gentle smooth".to_owned(),
+                    settings: BrushSettings {
+                        radius: WorldRadius(32.0),
+                        weight: 0.3,
+                        invert: false,
+                        once: false,
+                        layer: HeightLayer::Base,
+                        spacing: 0.25,
+                    },
+                    brush: BrushType::new(Equalize::default()),
+                },
+            ],
+        }
+    }
+
+    /// Validates every preset, and makes sure all names are unique.
+    pub fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for preset in &self.presets {
+            preset.validate()?;
+            if !names.insert(preset.name.as_str()) {
+                bail!("Duplicate brush preset {:?}", preset.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load presets from a file. The loaded presets are validated before being returned.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let presets: Self = serde_json::from_reader(std::io::BufReader::new(file))?;
+        presets.validate()?;
+        Ok(presets)
+    }
+
+    /// Load presets from a file, falling back to the builtin presets if the file does not exist.
+    pub fn load_or_builtin(path: impl AsRef<Path>) -> Result<Self> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::builtin())
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.validate()?;
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BrushPreset> {
+        self.presets.iter().find(|preset| preset.name == name)
+    }
+
+    /// Insert a preset, replacing any existing preset with the same name.
+    pub fn insert(&mut self, preset: BrushPreset) {
+        match self.presets.iter_mut().find(|p| p.name == preset.name) {
+            None => self.presets.push(preset),
+            Some(existing) => *existing = preset,
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.presets.retain(|preset| preset.name != name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4;
+
+    use super::*;
+    use crate::{Color, Flatten, Splat};
+
+    #[test]
+    fn presets_round_trip() {
+        let mut presets = BrushPresets::builtin();
+        presets.insert(BrushPreset {
+            name: "Paint".to_owned(),
+            settings: BrushSettings {
+                radius: WorldRadius(12.345678),
+                weight: 0.1,
+                invert: true,
+                once: true,
+                layer: HeightLayer::Detail,
+                spacing: 0.1,
+            },
+            brush: BrushType::new(Color {
+                color: Vec4::new(0.1, 0.2, 0.3, 1.0),
+                max_slope: 0.5,
+            }),
+        });
+        let json = serde_json::to_string(&presets).unwrap();
+        let loaded: BrushPresets = serde_json::from_str(&json).unwrap();
+        assert_eq!(presets, loaded);
+    }
+
+    #[test]
+    fn builtin_presets_are_valid() {
+        BrushPresets::builtin().validate().unwrap();
+    }
+
+    #[test]
+    fn invalid_presets_are_rejected() {
+        let mut presets = BrushPresets::builtin();
+        presets.presets[0].settings.radius = WorldRadius(-1.0);
+        assert!(presets.validate().is_err());
+
+        let mut presets = BrushPresets::builtin();
+        let duplicate = presets.presets[0].clone();
+        presets.presets.push(duplicate);
+        assert!(presets.validate().is_err());
+    }
+
+    #[test]
+    fn falloff_curves_are_validated() {
+        let preset = |weight_fn| BrushPreset {
+            name: "Falloff".to_owned(),
+            settings: BrushPresets::builtin().presets[0].settings,
+            brush: BrushType::new(SmoothHeight {
+                weight_fn,
+            }),
+        };
+        for weight_fn in WeightFunction::ALL {
+            preset(weight_fn).validate().unwrap();
+        }
+        assert!(preset(WeightFunction::Gaussian(0.0)).validate().is_err());
+        assert!(preset(WeightFunction::Linear(1.0)).validate().is_err());
+        assert!(preset(WeightFunction::Smoothstep(-0.5)).validate().is_err());
+    }
+
+    #[test]
+    fn flatten_presets_need_a_finite_target_height() {
+        let preset = |target_height| BrushPreset {
+            name: "Plateau".to_owned(),
+            settings: BrushPresets::builtin().presets[0].settings,
+            brush: BrushType::new(Flatten {
+                target_height,
+                weight_fn: WeightFunction::default(),
+            }),
+        };
+        preset(None).validate().unwrap();
+        preset(Some(0.25)).validate().unwrap();
+        assert!(preset(Some(f32::NAN)).validate().is_err());
+    }
+
+    #[test]
+    fn splat_presets_need_an_existing_layer() {
+        let preset = |channel| BrushPreset {
+            name: "Snow".to_owned(),
+            settings: BrushPresets::builtin().presets[0].settings,
+            brush: BrushType::new(Splat {
+                channel,
+            }),
+        };
+        preset(SplatMap::CHANNELS - 1).validate().unwrap();
+        assert!(preset(SplatMap::CHANNELS).validate().is_err());
+    }
+}
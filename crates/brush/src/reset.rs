@@ -0,0 +1,264 @@
+//! Resetting the entire base heightmap of the terrain, see [`FlattenTerrainEvent`] and
+//! [`ResetTerrainToSourceEvent`].
+//!
+//! [`FlattenTerrainEvent`]: crate::FlattenTerrainEvent
+//! [`ResetTerrainToSourceEvent`]: crate::ResetTerrainToSourceEvent
+
+use anyhow::{anyhow, bail, ensure, Result};
+use assets::asset::Asset;
+use assets::storage::AssetStorage;
+use assets::{Heightmap, HeightmapLoadInfo, NormalMap, TexelRadius};
+use gfx::SharedContext;
+use glam::IVec2;
+use inject::DI;
+use pass::GpuWork;
+use phobos::{vk, ComputeCmdBuffer, IncompleteCommandBuffer, PipelineStage};
+use scheduler::EventBus;
+use world::World;
+
+use crate::set_value::SetValueParams;
+use crate::undo::BrushTarget;
+use crate::util::{
+    get_terrain_info, height_views, prepare_for_read, prepare_for_write, submit_brush_work,
+    update_derived_maps, update_normals_around_patch, with_ready_terrain, BrushDomain,
+};
+use crate::HeightLayer;
+
+/// Terrain textures modified by resetting the heightmap.
+pub const RESET_TARGETS: &[BrushTarget] =
+    &[BrushTarget::Height(HeightLayer::Base), BrushTarget::Normals];
+
+/// Recompute the entire normal map after the heightmap was replaced.
+fn record_all_normals<'q, D: BrushDomain>(
+    bus: &EventBus<DI>,
+    cmd: IncompleteCommandBuffer<'q, D>,
+    heights: &Heightmap,
+    normals: &NormalMap,
+) -> Result<IncompleteCommandBuffer<'q, D>> {
+    let (width, height) = (heights.image.width(), heights.image.height());
+    let center = IVec2::new(width as i32 / 2, height as i32 / 2);
+    let radius = TexelRadius(width.max(height));
+    let cmd = prepare_for_write(&normals.image, cmd, PipelineStage::FRAGMENT_SHADER);
+    let cmd = update_normals_around_patch(bus, cmd, center, radius, heights, normals)?;
+    Ok(prepare_for_read(
+        &normals.image,
+        cmd,
+        PipelineStage::BOTTOM_OF_PIPE,
+        vk::AccessFlags2::NONE,
+    ))
+}
+
+fn record_flatten<'q, D: BrushDomain>(
+    bus: &EventBus<DI>,
+    cmd: IncompleteCommandBuffer<'q, D>,
+    height: f32,
+    heights: &Heightmap,
+    normals: &NormalMap,
+) -> Result<IncompleteCommandBuffer<'q, D>> {
+    let cmd = prepare_for_write(&heights.image, cmd, PipelineStage::TESSELLATION_EVALUATION_SHADER);
+    let dispatches_x = (heights.image.width() as f32 / 16.0).ceil() as u32;
+    let dispatches_y = (heights.image.height() as f32 / 16.0).ceil() as u32;
+    let cmd = cmd
+        .bind_compute_pipeline("fill_height")?
+        .bind_storage_image(0, 0, &heights.image.image.view)?
+        .push_constant(
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            &SetValueParams {
+                // Only the value is used when filling the whole heightmap
+                center: IVec2::ZERO,
+                size: 0,
+                border_mode: 0,
+                value: [height, 0.0, 0.0, 0.0],
+            },
+        )
+        .dispatch(dispatches_x, dispatches_y, 1)?;
+    let cmd = prepare_for_read(
+        &heights.image,
+        cmd,
+        PipelineStage::COMPUTE_SHADER,
+        vk::AccessFlags2::SHADER_SAMPLED_READ,
+    );
+    record_all_normals(bus, cmd, heights, normals)
+}
+
+fn record_reset<'q, D: BrushDomain>(
+    bus: &EventBus<DI>,
+    cmd: IncompleteCommandBuffer<'q, D>,
+    source: &Heightmap,
+    heights: &Heightmap,
+    normals: &NormalMap,
+) -> Result<IncompleteCommandBuffer<'q, D>> {
+    let ctx = bus
+        .data()
+        .read()
+        .unwrap()
+        .get::<SharedContext>()
+        .cloned()
+        .unwrap();
+    let (source, target) = (&source.image.image.view, &heights.image.image.view);
+    let cmd = cmd
+        .transition_image(
+            source,
+            D::supported_stages(PipelineStage::ALL_COMMANDS),
+            PipelineStage::TRANSFER,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::AccessFlags2::NONE,
+            vk::AccessFlags2::TRANSFER_READ,
+        )
+        .transition_image(
+            target,
+            D::supported_stages(PipelineStage::TESSELLATION_EVALUATION_SHADER),
+            PipelineStage::TRANSFER,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::AccessFlags2::NONE,
+            vk::AccessFlags2::TRANSFER_WRITE,
+        );
+    let subresource = vk::ImageSubresourceLayers {
+        aspect_mask: target.aspect(),
+        mip_level: 0,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+    let region = vk::ImageCopy {
+        src_subresource: subresource,
+        src_offset: vk::Offset3D::default(),
+        dst_subresource: subresource,
+        dst_offset: vk::Offset3D::default(),
+        extent: vk::Extent3D {
+            width: target.width(),
+            height: target.height(),
+            depth: 1,
+        },
+    };
+    // SAFETY: The command buffer is in the recording state. Both heightmaps have the same size,
+    // which is checked before the source is uploaded.
+    unsafe {
+        ctx.device.cmd_copy_image(
+            cmd.handle(),
+            source.image(),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            target.image(),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            std::slice::from_ref(&region),
+        );
+    }
+    let cmd = cmd
+        .transition_image(
+            source,
+            PipelineStage::TRANSFER,
+            D::supported_stages(PipelineStage::ALL_COMMANDS),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags2::TRANSFER_READ,
+            vk::AccessFlags2::NONE,
+        )
+        .transition_image(
+            target,
+            PipelineStage::TRANSFER,
+            PipelineStage::COMPUTE_SHADER,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags2::TRANSFER_WRITE,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+        );
+    record_all_normals(bus, cmd, heights, normals)
+}
+
+/// Set the entire base heightmap to `height`, as stored in the heightmap before the vertical
+/// scale of the terrain is applied.
+/// # DI Access
+/// - Read [`World`]
+/// - Write [`GpuWork`]
+pub fn flatten_terrain(bus: &EventBus<DI>, height: f32) -> Result<()> {
+    ensure!(height.is_finite(), "Cannot flatten the terrain to {height}.");
+    let (Some(terrain), _) = get_terrain_info(bus) else {
+        bail!("There is no terrain to flatten.")
+    };
+    with_ready_terrain(bus, terrain, |heights, normals, _, _| {
+        let written = height_views(heights, Some(normals));
+        submit_brush_work!(bus, written, |cmd| record_flatten(bus, cmd, height, heights, normals));
+        Ok(())
+    })
+}
+
+/// Upload the heightmap the terrain was opened from again, discarding all edits to the base
+/// heightmap. The file is loaded again, so this fails if it was moved or resized since.
+/// # DI Access
+/// - Read [`World`]
+/// - Write [`GpuWork`]
+pub fn reset_terrain_to_source(bus: &EventBus<DI>) -> Result<()> {
+    let (Some(terrain), _) = get_terrain_info(bus) else {
+        bail!("There is no terrain to reset.")
+    };
+    let path = {
+        let di = bus.data().read().unwrap();
+        let world = di.read_sync::<World>().unwrap();
+        world
+            .terrain_source
+            .as_ref()
+            .map(|source| source.height_path.clone())
+            .ok_or_else(|| anyhow!("The terrain was not opened from a file."))?
+    };
+    let source = Heightmap::load(
+        HeightmapLoadInfo {
+            path,
+        },
+        bus.clone(),
+    )?;
+    with_ready_terrain(bus, terrain, |heights, normals, _, _| {
+        ensure!(
+            source.image.width() == heights.image.width()
+                && source.image.height() == heights.image.height(),
+            "The source heightmap was resized since the terrain was opened."
+        );
+        // The source is only read, but is owned by the graphics queue like the terrain
+        let mut images = height_views(heights, Some(normals));
+        images.push(&source.image.image.view);
+        submit_brush_work!(bus, images, |cmd| record_reset(bus, cmd, &source, heights, normals));
+        Ok(())
+    })?;
+    // The source image is dropped at the end of this function, so the copy has to finish first
+    GpuWork::flush(bus)?;
+    GpuWork::wait_async(bus)?;
+    Ok(())
+}
+
+/// Recompute the maps derived from the base heightmap after it was reloaded because its file
+/// was modified. If the file was resized, the whole terrain is opened again instead, since the
+/// mesh and all other maps depend on the size of the heightmap.
+/// # DI Access
+/// - Write [`World`]
+/// - Read [`AssetStorage`]
+/// - Write [`GpuWork`]
+pub fn refresh_reloaded_heightmap(bus: &EventBus<DI>) -> Result<()> {
+    let (Some(terrain), _) = get_terrain_info(bus) else { return Ok(()) };
+    let resized = with_ready_terrain(bus, terrain, |heights, normals, _, _| {
+        if heights.image.width() != normals.image.width()
+            || heights.image.height() != normals.image.height()
+        {
+            return Ok(true);
+        }
+        submit_brush_work!(bus, [&normals.image.image.view], |cmd| record_all_normals(
+            bus, cmd, heights, normals
+        ));
+        Ok::<_, anyhow::Error>(false)
+    })?;
+    {
+        let di = bus.data().read().unwrap();
+        let mut world = di.write_sync::<World>().unwrap();
+        world.invalidate_terrain_bounds();
+        if resized {
+            let source = world
+                .terrain_source
+                .clone()
+                .ok_or_else(|| anyhow!("The terrain was not opened from a file."))?;
+            let assets = di.get::<AssetStorage>().unwrap();
+            world.terrain = Some(assets.load(source.load_info()));
+            return Ok(());
+        }
+    }
+    update_derived_maps(bus)
+}
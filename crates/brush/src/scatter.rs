@@ -0,0 +1,33 @@
+//! Scattering props over the terrain.
+
+use anyhow::{anyhow, Result};
+use inject::DI;
+use pass::GpuWork;
+use scheduler::EventBus;
+use world::{scatter_props, PropInstances, PropSettings};
+
+use crate::util::{get_terrain_info, with_ready_terrain};
+
+/// Scatter props over the terrain, replacing the props that were scattered before. Queued
+/// brush work is completed first, so the props are snapped to the terrain including every
+/// finished stroke. Returns the number of props.
+/// # DI Access
+/// - Read [`World`](world::World)
+/// - Write [`GpuWork`]
+/// - Read [`AssetStorage`](assets::storage::AssetStorage)
+/// - Write [`PropInstances`]
+pub fn scatter(bus: &EventBus<DI>, settings: &PropSettings) -> Result<usize> {
+    let (Some(terrain), options) = get_terrain_info(bus) else {
+        return Err(anyhow!("there is no terrain to scatter props on"));
+    };
+    GpuWork::flush(bus)?;
+    GpuWork::wait_async(bus)?;
+    let heights = with_ready_terrain(bus, terrain, |heights, _, _, _| heights.height_query(bus))?;
+    let transforms = scatter_props(settings, &options, &heights);
+    let count = transforms.len();
+    let di = bus.data().read().unwrap();
+    di.write_sync::<PropInstances>()
+        .unwrap()
+        .set(terrain, transforms);
+    Ok(count)
+}
@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use glam::Vec3;
+
+use crate::util::position_on_terrain;
+
+/// Converts the brush weight, which is an amount per second, into the amount applied by a
+/// single stamp. Each stamp is weighted by the real time since the previous stamp of the stroke,
+/// so the total amount applied only depends on how long the stroke lasts, and not on the frame
+/// rate or how often the brush stamps.
+#[derive(Debug, Copy, Clone)]
+pub struct StrokeTimer {
+    last_stamp: Instant,
+}
+
+impl StrokeTimer {
+    /// Upper bound on the time a single stamp accounts for. Brushes that only apply while
+    /// moving would otherwise dump everything accumulated during a pause into a single stamp.
+    pub const MAX_STAMP_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Start timing a stroke that began at `start`.
+    pub fn new(start: Instant) -> Self {
+        Self {
+            last_stamp: start,
+        }
+    }
+
+    /// Returns the weight to use for a stamp at `time`.
+    pub fn stamp_weight(&mut self, weight: f32, time: Instant) -> f32 {
+        let elapsed = time
+            .saturating_duration_since(self.last_stamp)
+            .min(Self::MAX_STAMP_INTERVAL);
+        self.last_stamp = self.last_stamp.max(time);
+        weight * elapsed.as_secs_f32()
+    }
+}
+
+/// Upper bound on the number of stamps a single stroke segment is split into, so a tiny
+/// spacing or a jump across the terrain cannot stall the brush thread.
+pub const MAX_SEGMENT_STAMPS: usize = 64;
+
+/// Returns the positions to stamp at when a stroke moves from `previous` to `position`. The
+/// segment between them is split into stamps at most `step` apart, ending at `position`.
+/// Only `position` is returned at the start of a stroke, or when either position is outside
+/// of the terrain.
+pub fn stroke_segment(previous: Option<Vec3>, position: Vec3, step: f32) -> Vec<Vec3> {
+    let Some(previous) = previous else { return vec![position] };
+    let spaced = step > 0.0;
+    if !spaced || !position_on_terrain(previous) || !position_on_terrain(position) {
+        return vec![position];
+    }
+    let count = (previous.distance(position) / step).ceil() as usize;
+    let count = count.clamp(1, MAX_SEGMENT_STAMPS);
+    (1..=count)
+        .map(|i| previous.lerp(position, i as f32 / count as f32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEIGHT: f32 = 1.5;
+
+    /// Simulates a stroke at a fixed frame rate, stamping `stamps_per_frame` times every frame.
+    /// Returns the total height applied to the center of the brush.
+    fn total_height(fps: u32, stamps_per_frame: u32, duration: Duration) -> f32 {
+        let start = Instant::now();
+        let mut timer = StrokeTimer::new(start);
+        let frame_time = Duration::from_secs(1) / fps;
+        let frames = (duration.as_secs_f64() * fps as f64).round() as u32;
+        (1..=frames)
+            .flat_map(|frame| {
+                std::iter::repeat(start + frame_time * frame).take(stamps_per_frame as usize)
+            })
+            .map(|time| timer.stamp_weight(WEIGHT, time))
+            .sum()
+    }
+
+    fn assert_close(lhs: f32, rhs: f32) {
+        assert!((lhs - rhs).abs() < 1e-3, "{lhs} != {rhs}");
+    }
+
+    #[test]
+    fn stroke_is_framerate_independent() {
+        let duration = Duration::from_secs(2);
+        let slow = total_height(30, 1, duration);
+        let fast = total_height(120, 1, duration);
+        assert_close(slow, fast);
+        assert_close(slow, WEIGHT * duration.as_secs_f32());
+    }
+
+    #[test]
+    fn stroke_is_independent_of_stamp_count() {
+        let duration = Duration::from_secs(2);
+        let single = total_height(30, 1, duration);
+        let multiple = total_height(120, 4, duration);
+        assert_close(single, multiple);
+    }
+
+    #[test]
+    fn pauses_are_clamped() {
+        let start = Instant::now();
+        let mut timer = StrokeTimer::new(start);
+        let weight = timer.stamp_weight(WEIGHT, start + Duration::from_secs(5));
+        assert_close(weight, WEIGHT * StrokeTimer::MAX_STAMP_INTERVAL.as_secs_f32());
+    }
+
+    #[test]
+    fn fast_strokes_are_filled_in() {
+        let start = Vec3::new(0.0, 0.0, 0.0);
+        let end = Vec3::new(10.0, 0.0, 0.0);
+        let positions = stroke_segment(Some(start), end, 2.5);
+        assert_eq!(
+            positions,
+            [Vec3::new(2.5, 0.0, 0.0), Vec3::new(5.0, 0.0, 0.0), Vec3::new(7.5, 0.0, 0.0), end]
+        );
+        // Short moves and disabled spacing stamp once, at the new position
+        assert_eq!(stroke_segment(Some(start), Vec3::new(1.0, 0.0, 0.0), 2.5).len(), 1);
+        assert_eq!(stroke_segment(Some(start), end, 0.0), [end]);
+        assert_eq!(stroke_segment(None, end, 2.5), [end]);
+    }
+
+    #[test]
+    fn segments_leaving_the_terrain_are_not_filled_in() {
+        let outside = Vec3::splat(f32::INFINITY);
+        assert_eq!(stroke_segment(Some(outside), Vec3::ZERO, 1.0), [Vec3::ZERO]);
+        let far = Vec3::new(1.0e6, 0.0, 0.0);
+        assert_eq!(stroke_segment(Some(Vec3::ZERO), far, 1.0).len(), MAX_SEGMENT_STAMPS);
+    }
+}
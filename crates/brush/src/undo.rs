@@ -0,0 +1,644 @@
+//! Undo and redo of brush strokes.
+//!
+//! Before a stroke modifies a terrain texture the brush declares in [`Brush::targets`], the
+//! region it modifies is copied into a buffer. Textures are split into tiles of [`TILE_SIZE`]
+//! texels, and every stamp of a stroke captures the tiles it touches that were not captured
+//! by an earlier stamp, so a stroke only copies its bounding region. Undoing the stroke swaps the buffers with the
+//! current contents of the regions, after which the buffers hold the state after the stroke.
+//! Redoing the stroke swaps them back again, so a single transaction serves both directions.
+//!
+//! The amount of strokes kept is limited both in count ([`UNDO_LIMIT`]) and in memory
+//! ([`UNDO_MEMORY_LIMIT`]). Buffers that are no longer needed are retired through the
+//! [`AssetStorage`], since a frame in flight may still be copying from them.
+
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+use anyhow::{bail, Result};
+use assets::handle::Handle;
+use assets::storage::AssetStorage;
+use assets::texture::format::TextureFormat;
+use assets::{
+    texel_at_uv, DetailNormalMapFormat, DiffuseMapFormat, HeightmapFormat, NormalMapFormat,
+    SplatMapFormat, Terrain, TerrainOptions, WorldRadius,
+};
+use gfx::SharedContext;
+use glam::{IVec2, UVec2, Vec3};
+use inject::DI;
+use phobos::{vk, Buffer, ImageView, IncompleteCommandBuffer, PipelineStage};
+use scheduler::EventBus;
+
+use crate::util::{
+    get_terrain_info, position_on_terrain, submit_brush_work, with_ready_detail_map,
+    with_ready_detail_normal_map, with_ready_splat_map, with_ready_terrain, BrushDomain,
+};
+use crate::{Brush, BrushSettings, BrushType, HeightLayer};
+
+/// Maximum amount of strokes that can be undone.
+pub const UNDO_LIMIT: usize = 16;
+/// Maximum amount of memory in bytes the snapshots of strokes that can be undone take up.
+/// The oldest strokes are forgotten first, but the last stroke can always be undone.
+pub const UNDO_MEMORY_LIMIT: u64 = 1 << 30;
+/// Size in texels of the square tiles strokes capture terrain textures in.
+pub const TILE_SIZE: u32 = 128;
+/// Texels around the brush radius that are captured as well. Normals are recomputed slightly
+/// outside of the brush, and texel radii are rounded.
+const STAMP_MARGIN: i32 = 6;
+
+/// Terrain texture a brush writes to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BrushTarget {
+    /// One of the heightmap layers.
+    Height(HeightLayer),
+    /// The normal map derived from the base heightmap.
+    Normals,
+    /// The diffuse map.
+    Color,
+    /// The painted detail normal map.
+    DetailNormals,
+    /// The weights of the material layers.
+    Splat,
+}
+
+/// Copy of rectangles of a single terrain texture, packed into a buffer in order.
+#[derive(Debug)]
+struct Snapshot {
+    target: BrushTarget,
+    regions: Vec<vk::Rect2D>,
+    buffer: Buffer,
+    /// Size of the buffer in bytes.
+    size: u64,
+}
+
+/// Snapshots of all texture regions modified by a single stroke.
+#[derive(Debug)]
+struct Transaction {
+    terrain: Handle<Terrain>,
+    /// Textures whose tiles are captured before every stamp of a stroke.
+    targets: Vec<BrushTarget>,
+    /// Tiles of the targets that were already captured.
+    captured: HashSet<(BrushTarget, UVec2)>,
+    snapshots: Vec<Snapshot>,
+}
+
+impl Transaction {
+    /// Memory taken up by all snapshots in bytes.
+    fn size(&self) -> u64 {
+        self.snapshots.iter().map(|snapshot| snapshot.size).sum()
+    }
+}
+
+/// Remove the oldest entries until at most `count` entries remain and their total size is at
+/// most `memory`. The newest entry is never removed. Returns the removed entries.
+fn evict_oldest<T>(
+    entries: &mut VecDeque<T>,
+    count: usize,
+    memory: u64,
+    size: impl Fn(&T) -> u64,
+) -> Vec<T> {
+    let mut total = entries.iter().map(&size).sum::<u64>();
+    let mut evicted = vec![];
+    while entries.len() > 1 && (entries.len() > count || total > memory) {
+        let Some(oldest) = entries.pop_front() else { break };
+        total -= size(&oldest);
+        evicted.push(oldest);
+    }
+    evicted
+}
+
+#[derive(Debug)]
+pub struct UndoStack {
+    bus: EventBus<DI>,
+    /// Transaction of the stroke in progress, pushed to the undo stack once it ends.
+    current: Option<Transaction>,
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+}
+
+impl UndoStack {
+    pub fn new(bus: &EventBus<DI>) -> Self {
+        Self {
+            bus: bus.clone(),
+            current: None,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Start the transaction of a stroke. Nothing is captured until the first stamp, see
+    /// [`UndoStack::before_stamp`].
+    pub fn begin_stroke(
+        &mut self,
+        terrain: Handle<Terrain>,
+        brush: &BrushType,
+        settings: &BrushSettings,
+    ) {
+        self.cancel();
+        self.current = Some(Transaction {
+            terrain,
+            targets: brush.targets(settings).to_vec(),
+            captured: HashSet::new(),
+            snapshots: vec![],
+        });
+    }
+
+    /// Capture the tiles of all textures the stroke writes to that a stamp at `position`
+    /// touches, before the stamp modifies them. `radius` is the radius in world space the
+    /// stamp may modify, see [`Brush::footprint`].
+    pub fn before_stamp(&mut self, position: Vec3, radius: WorldRadius) -> Result<()> {
+        let Some(transaction) = &mut self.current else { return Ok(()) };
+        // Brushes skip stamps outside of the terrain
+        if !position_on_terrain(position) {
+            return Ok(());
+        }
+        let (_, options) = get_terrain_info(&self.bus);
+        let terrain = transaction.terrain;
+        for target in transaction.targets.clone() {
+            let captured = &mut transaction.captured;
+            let tiles = with_target(&self.bus, terrain, target, |view, texel_size| {
+                let size = UVec2::new(view.width(), view.height());
+                let (min, max) = stamp_bounds(&options, target, position, radius, size);
+                let regions = tiles_in_bounds(min, max, size)
+                    .into_iter()
+                    .filter(|tile| captured.insert((target, *tile)))
+                    .map(|tile| tile_rect(tile, size))
+                    .collect::<Vec<_>>();
+                if regions.is_empty() {
+                    return Ok(None);
+                }
+                snapshot(&self.bus, target, view, texel_size, regions).map(Some)
+            })?;
+            transaction.snapshots.extend(tiles);
+        }
+        Ok(())
+    }
+
+    /// Snapshot `targets` entirely before an edit that is not a brush stroke, such as
+    /// flattening the terrain. The edit is undone as a single step after
+    /// [`UndoStack::end_stroke`].
+    pub fn begin_transaction(
+        &mut self,
+        terrain: Handle<Terrain>,
+        targets: &[BrushTarget],
+    ) -> Result<()> {
+        self.cancel();
+        let snapshots = targets
+            .iter()
+            .map(|target| {
+                with_target(&self.bus, terrain, *target, |view, texel_size| {
+                    let rect = vk::Rect2D {
+                        offset: vk::Offset2D::default(),
+                        extent: vk::Extent2D {
+                            width: view.width(),
+                            height: view.height(),
+                        },
+                    };
+                    snapshot(&self.bus, *target, view, texel_size, vec![rect])
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.current = Some(Transaction {
+            terrain,
+            targets: vec![],
+            captured: HashSet::new(),
+            snapshots,
+        });
+        Ok(())
+    }
+
+    /// Finish the transaction of the current stroke or edit. A new stroke discards everything
+    /// that could be redone.
+    pub fn end_stroke(&mut self) {
+        let Some(transaction) = self.current.take() else { return };
+        // The stroke never touched the terrain
+        if transaction.snapshots.is_empty() {
+            return;
+        }
+        let redo = std::mem::take(&mut self.redo);
+        self.retire(redo);
+        self.undo.push_back(transaction);
+        let evicted =
+            evict_oldest(&mut self.undo, UNDO_LIMIT, UNDO_MEMORY_LIMIT, Transaction::size);
+        self.retire(evicted);
+    }
+
+    /// Discard the transaction of the current edit without adding it to the undo stack, for
+    /// example because the edit failed before it modified the terrain.
+    pub fn cancel(&mut self) {
+        let current = self.current.take();
+        self.retire(current);
+    }
+
+    /// Forget all strokes, for example after the terrain was replaced.
+    pub fn clear(&mut self) {
+        self.cancel();
+        let undo = std::mem::take(&mut self.undo);
+        let redo = std::mem::take(&mut self.redo);
+        self.retire(undo);
+        self.retire(redo);
+    }
+
+    /// Restore the terrain to before the last stroke. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> Result<bool> {
+        let Some(mut transaction) = self.undo.pop_back() else { return Ok(false) };
+        swap_transaction(&self.bus, &mut transaction)?;
+        self.redo.push(transaction);
+        Ok(true)
+    }
+
+    /// Apply the last undone stroke again. Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> Result<bool> {
+        let Some(mut transaction) = self.redo.pop() else { return Ok(false) };
+        swap_transaction(&self.bus, &mut transaction)?;
+        self.undo.push_back(transaction);
+        Ok(true)
+    }
+
+    /// Keep the buffers of discarded transactions alive until the GPU is done with them.
+    fn retire(&self, transactions: impl IntoIterator<Item = Transaction>) {
+        let di = self.bus.data().read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        for transaction in transactions {
+            assets.retire(transaction);
+        }
+    }
+}
+
+/// Size of a single texel of a terrain texture in bytes.
+fn texel_size<F: TextureFormat>() -> u64 {
+    std::mem::size_of::<F::Pixel>() as u64
+}
+
+/// Calls `f` with the image view and texel size of a terrain texture.
+fn with_target<R>(
+    bus: &EventBus<DI>,
+    terrain: Handle<Terrain>,
+    target: BrushTarget,
+    f: impl FnOnce(&ImageView, u64) -> Result<R>,
+) -> Result<R> {
+    match target {
+        BrushTarget::Height(HeightLayer::Base) => {
+            with_ready_terrain(bus, terrain, |heights, _, _, _| {
+                f(&heights.image.image.view, texel_size::<HeightmapFormat>())
+            })
+        }
+        BrushTarget::Normals => with_ready_terrain(bus, terrain, |_, normals, _, _| {
+            f(&normals.image.image.view, texel_size::<NormalMapFormat>())
+        }),
+        BrushTarget::Color => with_ready_terrain(bus, terrain, |_, _, texture, _| {
+            f(&texture.image.view, texel_size::<DiffuseMapFormat>())
+        }),
+        BrushTarget::Height(HeightLayer::Detail) => {
+            with_ready_detail_map(bus, terrain, |detail| {
+                f(&detail.image.image.view, texel_size::<HeightmapFormat>())
+            })?
+        }
+        BrushTarget::DetailNormals => with_ready_detail_normal_map(bus, terrain, |normals| {
+            f(&normals.image.image.view, texel_size::<DetailNormalMapFormat>())
+        })?,
+        BrushTarget::Splat => with_ready_splat_map(bus, terrain, |splat| {
+            f(&splat.image.image.view, texel_size::<SplatMapFormat>())
+        })?,
+    }
+}
+
+/// Size in bytes of `regions` of a texture with texels of `texel_size` bytes.
+fn regions_size(regions: &[vk::Rect2D], texel_size: u64) -> u64 {
+    regions
+        .iter()
+        .map(|rect| rect.extent.width as u64 * rect.extent.height as u64 * texel_size)
+        .sum()
+}
+
+/// Returns the texels `(min, max)` of `target`, which has `size` texels, that a stamp at
+/// `position` may modify. The bounds may lie outside of the texture.
+fn stamp_bounds(
+    options: &TerrainOptions,
+    target: BrushTarget,
+    position: Vec3,
+    radius: WorldRadius,
+    size: UVec2,
+) -> (IVec2, IVec2) {
+    let uv = options.uv_at(position);
+    let edge = options.uv_at(position + Vec3::new(radius.0, 0.0, radius.0));
+    let uv_radius = (edge.0 - uv.0).abs();
+    // Same conversions as the brushes use, see `TerrainOptions::texel_radius`
+    let (center, texel_radius) = match target {
+        BrushTarget::Height(HeightLayer::Detail) => (
+            options.detail_texel_at_uv(uv, size.x, size.y),
+            uv_radius * size.as_vec2() * options.detail_tiling,
+        ),
+        _ => (
+            texel_at_uv(uv, size.x, size.y),
+            uv_radius * (size.max(UVec2::ONE) - UVec2::ONE).as_vec2(),
+        ),
+    };
+    let radius = texel_radius.max_element().ceil() as i32 + STAMP_MARGIN;
+    (center - radius, center + radius)
+}
+
+/// Tiles along one axis of `size` texels that overlap the texels from `min` to `max`.
+/// Texels outside of the texture wrap around, since brushes on repeating borders and the
+/// detail layer wrap as well.
+fn axis_tiles(min: i32, max: i32, size: u32) -> BTreeSet<u32> {
+    let last = (size.max(1) - 1) / TILE_SIZE;
+    if max - min + 1 >= size as i32 {
+        return (0..=last).collect();
+    }
+    let min = min.rem_euclid(size as i32) as u32 / TILE_SIZE;
+    let max = max.rem_euclid(size as i32) as u32 / TILE_SIZE;
+    if min <= max {
+        (min..=max).collect()
+    } else {
+        (0..=max).chain(min..=last).collect()
+    }
+}
+
+/// Tiles of a texture with `size` texels that overlap the texels from `min` to `max`.
+fn tiles_in_bounds(min: IVec2, max: IVec2, size: UVec2) -> Vec<UVec2> {
+    let xs = axis_tiles(min.x, max.x, size.x);
+    let ys = axis_tiles(min.y, max.y, size.y);
+    ys.iter()
+        .flat_map(|&y| xs.iter().map(move |&x| UVec2::new(x, y)))
+        .collect()
+}
+
+/// Texels covered by a tile, tiles at the edges of the texture may be smaller.
+fn tile_rect(tile: UVec2, size: UVec2) -> vk::Rect2D {
+    let offset = tile * TILE_SIZE;
+    let extent = (size - offset).min(UVec2::splat(TILE_SIZE));
+    vk::Rect2D {
+        offset: vk::Offset2D {
+            x: offset.x as i32,
+            y: offset.y as i32,
+        },
+        extent: vk::Extent2D {
+            width: extent.x,
+            height: extent.y,
+        },
+    }
+}
+
+/// Allocate a buffer that holds `regions` of a texture with texels of `texel_size` bytes.
+fn allocate_snapshot(
+    bus: &EventBus<DI>,
+    regions: &[vk::Rect2D],
+    texel_size: u64,
+) -> Result<Buffer> {
+    let mut ctx = bus
+        .data()
+        .read()
+        .unwrap()
+        .get::<SharedContext>()
+        .cloned()
+        .unwrap();
+    Ok(Buffer::new_device_local(
+        ctx.device.clone(),
+        &mut ctx.allocator,
+        regions_size(regions, texel_size),
+        vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+    )?)
+}
+
+/// Copy `regions` of the terrain texture `view` into a newly allocated buffer.
+fn snapshot(
+    bus: &EventBus<DI>,
+    target: BrushTarget,
+    view: &ImageView,
+    texel_size: u64,
+    regions: Vec<vk::Rect2D>,
+) -> Result<Snapshot> {
+    let buffer = allocate_snapshot(bus, &regions, texel_size)?;
+    submit_brush_work!(bus, [view], |cmd| record_swap(
+        bus, cmd, view, &regions, texel_size, &buffer, None
+    ));
+    Ok(Snapshot {
+        target,
+        size: regions_size(&regions, texel_size),
+        regions,
+        buffer,
+    })
+}
+
+/// Swap the contents of all snapshots in a transaction with the terrain textures.
+fn swap_transaction(bus: &EventBus<DI>, transaction: &mut Transaction) -> Result<()> {
+    let terrain = transaction.terrain;
+    for snapshot in &mut transaction.snapshots {
+        let current = with_target(bus, terrain, snapshot.target, |view, texel_size| {
+            let fits = snapshot.regions.iter().all(|rect| {
+                rect.offset.x as u32 + rect.extent.width <= view.width()
+                    && rect.offset.y as u32 + rect.extent.height <= view.height()
+            });
+            if !fits {
+                bail!("Cannot undo {:?}, the texture was resized.", snapshot.target);
+            }
+            let current = allocate_snapshot(bus, &snapshot.regions, texel_size)?;
+            submit_brush_work!(bus, [view], |cmd| record_swap(
+                bus,
+                cmd,
+                view,
+                &snapshot.regions,
+                texel_size,
+                &current,
+                Some(&snapshot.buffer)
+            ));
+            Ok(current)
+        })?;
+        // The previous contents are still being copied into the texture
+        let previous = std::mem::replace(&mut snapshot.buffer, current);
+        let di = bus.data().read().unwrap();
+        di.get::<AssetStorage>().unwrap().retire(previous);
+    }
+    Ok(())
+}
+
+fn buffer_image_copy(view: &ImageView, rect: vk::Rect2D, offset: u64) -> vk::BufferImageCopy {
+    vk::BufferImageCopy {
+        buffer_offset: offset,
+        // Texels are tightly packed
+        buffer_row_length: 0,
+        buffer_image_height: 0,
+        image_subresource: vk::ImageSubresourceLayers {
+            aspect_mask: view.aspect(),
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        image_offset: vk::Offset3D {
+            x: rect.offset.x,
+            y: rect.offset.y,
+            z: 0,
+        },
+        image_extent: vk::Extent3D {
+            width: rect.extent.width,
+            height: rect.extent.height,
+            depth: 1,
+        },
+    }
+}
+
+/// Copies `regions` of `target` into `save`, and then copies `restore` into the same regions
+/// if set. `target` is expected to be in the shader read only layout, and is transitioned back
+/// to it.
+fn record_swap<'q, D: BrushDomain>(
+    bus: &EventBus<DI>,
+    cmd: IncompleteCommandBuffer<'q, D>,
+    target: &ImageView,
+    regions: &[vk::Rect2D],
+    texel_size: u64,
+    save: &Buffer,
+    restore: Option<&Buffer>,
+) -> Result<IncompleteCommandBuffer<'q, D>> {
+    let ctx = bus
+        .data()
+        .read()
+        .unwrap()
+        .get::<SharedContext>()
+        .cloned()
+        .unwrap();
+    let mut offset = 0;
+    let copies = regions
+        .iter()
+        .map(|rect| {
+            let copy = buffer_image_copy(target, *rect, offset);
+            offset += regions_size(std::slice::from_ref(rect), texel_size);
+            copy
+        })
+        .collect::<Vec<_>>();
+    let cmd = cmd.transition_image(
+        target,
+        D::supported_stages(PipelineStage::ALL_COMMANDS),
+        PipelineStage::TRANSFER,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::AccessFlags2::MEMORY_WRITE,
+        vk::AccessFlags2::TRANSFER_READ,
+    );
+    // SAFETY: The command buffer is in the recording state. The buffers are retired through
+    // the asset storage, so they outlive the submission.
+    unsafe {
+        ctx.device.cmd_copy_image_to_buffer(
+            cmd.handle(),
+            target.image(),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            save.handle(),
+            &copies,
+        );
+    }
+    let Some(restore) = restore else {
+        return Ok(cmd.transition_image(
+            target,
+            PipelineStage::TRANSFER,
+            D::supported_stages(PipelineStage::ALL_COMMANDS),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags2::TRANSFER_READ,
+            vk::AccessFlags2::MEMORY_READ,
+        ));
+    };
+    let cmd = cmd.transition_image(
+        target,
+        PipelineStage::TRANSFER,
+        PipelineStage::TRANSFER,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::AccessFlags2::TRANSFER_READ,
+        vk::AccessFlags2::TRANSFER_WRITE,
+    );
+    // SAFETY: See above.
+    unsafe {
+        ctx.device.cmd_copy_buffer_to_image(
+            cmd.handle(),
+            restore.handle(),
+            target.image(),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &copies,
+        );
+    }
+    Ok(cmd.transition_image(
+        target,
+        PipelineStage::TRANSFER,
+        D::supported_stages(PipelineStage::ALL_COMMANDS),
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        vk::AccessFlags2::TRANSFER_WRITE,
+        vk::AccessFlags2::MEMORY_READ,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4;
+
+    use super::*;
+    use crate::set_value::BrushValue;
+    use crate::{Color, SetValue, SmoothHeight};
+
+    #[test]
+    fn height_brush_targets_follow_layer() {
+        let brush = BrushType::new(SmoothHeight::default());
+        let base = BrushSettings::default();
+        assert_eq!(
+            brush.targets(&base),
+            &[BrushTarget::Height(HeightLayer::Base), BrushTarget::Normals]
+        );
+        let detail = BrushSettings {
+            layer: HeightLayer::Detail,
+            ..base
+        };
+        assert_eq!(brush.targets(&detail), &[BrushTarget::Height(HeightLayer::Detail)]);
+    }
+
+    #[test]
+    fn color_brush_only_targets_diffuse_map() {
+        let brush = BrushType::new(Color::default());
+        assert_eq!(brush.targets(&BrushSettings::default()), &[BrushTarget::Color]);
+    }
+
+    #[test]
+    fn set_value_brush_targets_follow_value() {
+        let height = BrushType::new(SetValue {
+            value: BrushValue::Height(0.5),
+        });
+        let detail = BrushSettings {
+            layer: HeightLayer::Detail,
+            ..Default::default()
+        };
+        assert_eq!(height.targets(&detail), &[BrushTarget::Height(HeightLayer::Detail)]);
+        let color = BrushType::new(SetValue {
+            value: BrushValue::Color(Vec4::ONE),
+        });
+        // Colors are painted on the diffuse map, whatever the height layer
+        assert_eq!(color.targets(&detail), &[BrushTarget::Color]);
+    }
+
+    #[test]
+    fn stamp_captures_overlapping_tiles() {
+        let size = UVec2::new(300, 200);
+        let tiles = tiles_in_bounds(IVec2::new(100, 10), IVec2::new(140, 20), size);
+        assert_eq!(tiles, [UVec2::new(0, 0), UVec2::new(1, 0)]);
+        // Edge tiles only cover the remaining texels
+        let rect = tile_rect(UVec2::new(2, 1), size);
+        assert_eq!((rect.offset.x, rect.offset.y), (256, 128));
+        assert_eq!((rect.extent.width, rect.extent.height), (44, 72));
+    }
+
+    #[test]
+    fn stamp_tiles_wrap_around_edges() {
+        assert_eq!(axis_tiles(-10, 10, 300), BTreeSet::from([0, 2]));
+        assert_eq!(axis_tiles(290, 310, 300), BTreeSet::from([0, 2]));
+        // A stamp larger than the texture covers all of it
+        assert_eq!(axis_tiles(-500, 500, 300), BTreeSet::from([0, 1, 2]));
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted_first() {
+        let mut entries = VecDeque::from([1, 2, 3, 4]);
+        assert_eq!(evict_oldest(&mut entries, 3, u64::MAX, |size| *size), [1]);
+        assert_eq!(entries, [2, 3, 4]);
+        assert_eq!(evict_oldest(&mut entries, 3, 7, |size| *size), [2]);
+        assert_eq!(entries, [3, 4]);
+        // The newest entry is kept, even if it is too large by itself
+        assert_eq!(evict_oldest(&mut entries, 3, 1, |size| *size), [3]);
+        assert_eq!(entries, [4]);
+    }
+}
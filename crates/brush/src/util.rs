@@ -1,17 +1,117 @@
-use anyhow::Result;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
 use assets::handle::Handle;
 use assets::storage::AssetStorage;
-use assets::texture::format::{SRgba, TextureFormat};
+use assets::texture::format::TextureFormat;
 use assets::texture::Texture;
-use assets::{Heightmap, NormalMap, Terrain, TerrainOptions, TerrainPlane};
+use assets::{
+    DerivedMaps, DetailNormalMap, DiffuseMapFormat, Heightmap, NormalMap, NormalParams, SplatMap,
+    Terrain, TerrainOptions, TerrainPlane, TexelRadius,
+};
 use gfx::Samplers;
-use glam::{Vec2, Vec3};
+use glam::{IVec2, Vec3};
 use inject::DI;
-use phobos::domain::ExecutionDomain;
-use phobos::{vk, ComputeCmdBuffer, ComputeSupport, IncompleteCommandBuffer, PipelineStage};
+use phobos::domain::{All, Compute, ExecutionDomain};
+use phobos::{
+    vk, ComputeCmdBuffer, ComputeSupport, ImageView, IncompleteCmdBuffer, IncompleteCommandBuffer,
+    PipelineStage,
+};
 use scheduler::EventBus;
 use world::World;
 
+/// Execution domain brush work can be recorded on, depending on the selected [`WorkQueue`].
+///
+/// [`WorkQueue`]: pass::WorkQueue
+pub trait BrushDomain: ExecutionDomain + ComputeSupport {
+    /// Replace pipeline stages the queue of this domain does not support with a stage it does.
+    /// Barriers against graphics stages become barriers against all commands on the compute queue,
+    /// the graphics queue is synchronized with it through a semaphore instead.
+    fn supported_stages(stages: PipelineStage) -> PipelineStage;
+}
+
+impl BrushDomain for All {
+    fn supported_stages(stages: PipelineStage) -> PipelineStage {
+        stages
+    }
+}
+
+impl BrushDomain for Compute {
+    fn supported_stages(stages: PipelineStage) -> PipelineStage {
+        let compute_stages = PipelineStage::TOP_OF_PIPE
+            | PipelineStage::COMPUTE_SHADER
+            | PipelineStage::TRANSFER
+            | PipelineStage::BOTTOM_OF_PIPE
+            | PipelineStage::ALL_COMMANDS;
+        if compute_stages.contains(stages) {
+            stages
+        } else {
+            PipelineStage::ALL_COMMANDS
+        }
+    }
+}
+
+/// Maximum time brush work waits for the submit batch of the next frame. If the renderer does
+/// not release it in time, the work is skipped instead of blocking the brush forever.
+pub(crate) const BATCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Record brush work and submit it on the queue selected in [`GpuWork`](pass::GpuWork).
+/// The recording expression is expanded once for every [`BrushDomain`], with `$cmd` bound to a
+/// new command buffer. It must evaluate to a `Result` of the recorded command buffer.
+/// `$images` iterates over the views of all images the work writes to, so their ownership can
+/// be transferred to the compute queue.
+/// Errors are returned from the enclosing function, including a
+/// [`BatchTimeoutError`](pass::BatchTimeoutError) if the submit batch was not available
+/// within [`BATCH_TIMEOUT`].
+macro_rules! submit_brush_work {
+    ($bus:expr, $images:expr, |$cmd:ident| $record:expr) => {{
+        let bus: &scheduler::EventBus<inject::DI> = $bus;
+        let (ctx, queue) = {
+            let di = bus.data().read().unwrap();
+            let ctx = di.get::<gfx::SharedContext>().cloned().unwrap();
+            let queue = di.read_sync::<pass::GpuWork>().unwrap().queue();
+            (ctx, queue)
+        };
+        match queue {
+            pass::WorkQueue::Graphics => {
+                let $cmd = ctx.exec.on_domain::<phobos::domain::All, _>(
+                    Some(ctx.pipelines.clone()),
+                    Some(ctx.descriptors.clone()),
+                )?;
+                let cmd = phobos::IncompleteCmdBuffer::finish($record?)?;
+                pass::GpuWork::with_batch_timeout(
+                    bus,
+                    $crate::util::BATCH_TIMEOUT,
+                    move |batch| Ok(batch.submit(cmd)?),
+                )?;
+            }
+            pass::WorkQueue::AsyncCompute => {
+                let $cmd = ctx.exec.on_domain::<phobos::domain::Compute, _>(
+                    Some(ctx.pipelines.clone()),
+                    Some(ctx.descriptors.clone()),
+                )?;
+                let cmd = phobos::IncompleteCmdBuffer::finish($record?)?;
+                let images = IntoIterator::into_iter($images)
+                    .map(pass::SharedImage::new)
+                    .collect::<Vec<_>>();
+                pass::GpuWork::submit_async(bus, cmd, &images)?;
+            }
+        }
+    }};
+}
+
+pub(crate) use submit_brush_work;
+
+/// Views of the heightmap and, if set, the normal map a height brush writes to.
+pub fn height_views<'a>(
+    heights: &'a Heightmap,
+    normals: Option<&'a NormalMap>,
+) -> Vec<&'a ImageView> {
+    std::iter::once(&heights.image.image.view)
+        .chain(normals.map(|normals| &normals.image.image.view))
+        .collect()
+}
+
 /// Returns true if the position is on the terrain mesh, false if outside.
 pub fn position_on_terrain(position: Vec3) -> bool {
     // If any of the values inside the position are NaN or infinite, the position is outside
@@ -30,7 +130,7 @@ pub fn get_terrain_info(bus: &EventBus<DI>) -> (Option<Handle<Terrain>>, Terrain
 
 pub fn with_ready_terrain<F, R>(bus: &EventBus<DI>, handle: Handle<Terrain>, f: F) -> R
 where
-    F: FnOnce(&Heightmap, &NormalMap, &Texture<SRgba<u8>>, &TerrainPlane) -> R, {
+    F: FnOnce(&Heightmap, &NormalMap, &Texture<DiffuseMapFormat>, &TerrainPlane) -> R, {
     let di = bus.data().read().unwrap();
     let assets = di.get::<AssetStorage>().unwrap();
     // Note that this wait should complete instantly, since without a loaded
@@ -45,15 +145,77 @@ where
         .unwrap()
 }
 
+/// Calls `f` with the detail heightmap of the terrain.
+/// Fails if the terrain has no detail layer.
+pub fn with_ready_detail_map<F, R>(bus: &EventBus<DI>, handle: Handle<Terrain>, f: F) -> Result<R>
+where
+    F: FnOnce(&Heightmap) -> R, {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    let detail = assets
+        .with_when_ready(handle, |terrain| terrain.detail_map)
+        .flatten();
+    let Some(detail) = detail else { bail!("Terrain has no detail layer.") };
+    assets
+        .with_when_ready(detail, f)
+        .ok_or_else(|| anyhow!("Detail heightmap failed to load."))
+}
+
+/// Calls `f` with the detail normal map of the terrain.
+pub fn with_ready_detail_normal_map<F, R>(
+    bus: &EventBus<DI>,
+    handle: Handle<Terrain>,
+    f: F,
+) -> Result<R>
+where
+    F: FnOnce(&DetailNormalMap) -> R, {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    let detail_normals = assets
+        .with_when_ready(handle, |terrain| terrain.detail_normal_map)
+        .ok_or_else(|| anyhow!("Terrain failed to load."))?;
+    assets
+        .with_when_ready(detail_normals, f)
+        .ok_or_else(|| anyhow!("Detail normal map failed to load."))
+}
+
+/// Calls `f` with the splat map of the terrain.
+pub fn with_ready_splat_map<F, R>(bus: &EventBus<DI>, handle: Handle<Terrain>, f: F) -> Result<R>
+where
+    F: FnOnce(&SplatMap) -> R, {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    let splat = assets
+        .with_when_ready(handle, |terrain| terrain.splat_map)
+        .ok_or_else(|| anyhow!("Terrain failed to load."))?;
+    assets
+        .with_when_ready(splat, f)
+        .ok_or_else(|| anyhow!("Splat map failed to load."))
+}
+
+/// Calls `f` with the slope and curvature maps of the terrain.
+pub fn with_ready_derived_maps<F, R>(bus: &EventBus<DI>, handle: Handle<Terrain>, f: F) -> Result<R>
+where
+    F: FnOnce(&DerivedMaps) -> R, {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    let derived_maps = assets
+        .with_when_ready(handle, |terrain| terrain.derived_maps)
+        .ok_or_else(|| anyhow!("Terrain failed to load."))?;
+    assets
+        .with_when_ready(derived_maps, f)
+        .ok_or_else(|| anyhow!("Derived maps failed to load."))
+}
+
 /// Transition image to correct layout with an execution barrier to COMPUTE RW
-pub fn prepare_for_write<'q, D: ExecutionDomain, F: TextureFormat>(
+pub fn prepare_for_write<'q, D: BrushDomain, F: TextureFormat>(
     texture: &Texture<F>,
     cmd: IncompleteCommandBuffer<'q, D>,
     src: PipelineStage,
 ) -> IncompleteCommandBuffer<'q, D> {
     cmd.transition_image(
         &texture.image.view,
-        src,
+        D::supported_stages(src),
         PipelineStage::COMPUTE_SHADER,
         vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         vk::ImageLayout::GENERAL,
@@ -63,7 +225,7 @@ pub fn prepare_for_write<'q, D: ExecutionDomain, F: TextureFormat>(
 }
 
 /// Transition image to correct layout with an execution barrier from COMPUTE RW
-pub fn prepare_for_read<'q, D: ExecutionDomain, F: TextureFormat>(
+pub fn prepare_for_read<'q, D: BrushDomain, F: TextureFormat>(
     texture: &Texture<F>,
     cmd: IncompleteCommandBuffer<'q, D>,
     dst_stage: PipelineStage,
@@ -72,7 +234,7 @@ pub fn prepare_for_read<'q, D: ExecutionDomain, F: TextureFormat>(
     cmd.transition_image(
         &texture.image.view,
         PipelineStage::COMPUTE_SHADER,
-        dst_stage,
+        D::supported_stages(dst_stage),
         vk::ImageLayout::GENERAL,
         vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
@@ -80,17 +242,32 @@ pub fn prepare_for_read<'q, D: ExecutionDomain, F: TextureFormat>(
     )
 }
 
+/// Number of workgroups along each axis needed to cover `radius` texels.
+pub fn patch_workgroups(radius: u32, local_size: u32) -> u32 {
+    (radius as f32 / local_size as f32).ceil() as u32
+}
+
 pub fn dispatch_patch_rect<C: ComputeCmdBuffer>(cmd: C, radius: u32, local_size: u32) -> Result<C> {
-    let invocations = (radius as f32 / local_size as f32).ceil() as u32;
+    let invocations = patch_workgroups(radius, local_size);
     cmd.dispatch(invocations, invocations, 1)
 }
 
+/// Push constants of the shader recomputing normals around a brush.
+/// Kept in sync with `PC` in `normal_recompute.cs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct NormalRecomputeParams {
+    normal: NormalParams,
+    center: IVec2,
+    size: u32,
+}
+
 /// Does no synchronization of accesses to `heights` and `normals`
-pub fn update_normals_around_patch<'q, D: ExecutionDomain + ComputeSupport>(
+pub fn update_normals_around_patch<'q, D: BrushDomain>(
     bus: &EventBus<DI>,
     cmd: IncompleteCommandBuffer<'q, D>,
-    uv: Vec2,
-    patch_radius: u32,
+    center: IVec2,
+    patch_radius: TexelRadius,
     heights: &Heightmap,
     normals: &NormalMap,
 ) -> Result<IncompleteCommandBuffer<'q, D>> {
@@ -100,12 +277,52 @@ pub fn update_normals_around_patch<'q, D: ExecutionDomain + ComputeSupport>(
     let sampler = &samplers.linear;
     // Add a small radius around the brush range because the normals around the entire area
     // also need to be updated
-    let size = patch_radius + 4;
+    let size = patch_radius.0 + 4;
+    // Derive normals with the same scale the normal map was generated with
+    let params = normals.params;
     let cmd = cmd.bind_compute_pipeline("normal_recompute")?;
     let cmd = cmd
         .bind_storage_image(0, 0, &normals.image.image.view)?
         .bind_sampled_image(0, 1, &heights.image.image.view, sampler)?
-        .push_constant(vk::ShaderStageFlags::COMPUTE, 0, &uv)
-        .push_constant(vk::ShaderStageFlags::COMPUTE, 8, &size);
+        .push_constant(
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            &NormalRecomputeParams {
+                normal: params,
+                center,
+                size,
+            },
+        );
     dispatch_patch_rect(cmd, size, 16)
 }
+
+/// Recompute the slope and curvature maps of the terrain after its base heightmap changed.
+/// # DI Access
+/// - Read [`World`]
+/// - Read [`AssetStorage`]
+/// - Write [`GpuWork`](pass::GpuWork)
+pub fn update_derived_maps(bus: &EventBus<DI>) -> Result<()> {
+    let (Some(terrain), _) = get_terrain_info(bus) else { return Ok(()) };
+    // The options in the world are not fitted to the heightmap, use the ones of the terrain.
+    let options = {
+        let di = bus.data().read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        assets
+            .with_when_ready(terrain, |terrain| terrain.options)
+            .ok_or_else(|| anyhow!("Terrain failed to load."))?
+    };
+    with_ready_derived_maps(bus, terrain, |maps| {
+        with_ready_terrain(bus, terrain, |heights, _, _, _| {
+            let di = bus.data().read().unwrap();
+            let samplers = di.get::<Samplers>().unwrap();
+            let written = [&maps.slope.image.view, &maps.curvature.image.view];
+            submit_brush_work!(bus, written, |cmd| maps.record_update(
+                cmd,
+                heights,
+                &options,
+                &samplers.raw
+            ));
+            Ok(())
+        })
+    })?
+}
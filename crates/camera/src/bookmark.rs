@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use scheduler::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::CameraPose;
+
+/// A named camera pose the camera can jump back to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub pose: CameraPose,
+}
+
+/// Store the current camera pose as a bookmark, replacing any bookmark with the same name.
+#[derive(Debug, Clone)]
+pub struct SaveBookmarkEvent {
+    pub name: String,
+}
+
+impl Event for SaveBookmarkEvent {}
+
+/// Move the camera to a bookmark. With a transition duration the camera moves there smoothly,
+/// otherwise it snaps to the bookmark.
+#[derive(Debug, Clone)]
+pub struct RecallBookmarkEvent {
+    /// Index of the bookmark in the order bookmarks were saved.
+    pub index: usize,
+    pub transition: Option<Duration>,
+}
+
+impl Event for RecallBookmarkEvent {}
+
+/// Remove a bookmark. The bookmarks after it move up by one.
+#[derive(Debug, Clone)]
+pub struct DeleteBookmarkEvent {
+    /// Index of the bookmark in the order bookmarks were saved.
+    pub index: usize,
+}
+
+impl Event for DeleteBookmarkEvent {}
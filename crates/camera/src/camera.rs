@@ -1,15 +1,77 @@
+use std::time::Duration;
+
 use anyhow::Result;
+use events::Tick;
 use glam::{Mat4, Vec3};
 use inject::DI;
-use input::{ButtonState, InputEvent, InputState, Key, MouseButton, MouseDelta, ScrollInfo};
+use input::{actions, ButtonState, InputEvent, InputState, Key, MouseDelta, ScrollInfo};
 use math::{Position, Rotation};
 use scheduler::{Event, EventBus, EventContext, StoredSystem, System};
+use time::Time;
+
+use crate::transition::CameraTransition;
+use crate::{CameraPose, CameraTransitionEvent};
+
+/// The projection used to render the camera's view.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    /// Perspective projection with a vertical field of view in degrees.
+    Perspective {
+        fov: f32,
+    },
+    /// Orthographic projection showing a region of the given height in meters.
+    Orthographic {
+        height: f32,
+    },
+}
+
+impl Projection {
+    /// Converts the depth an orthographic projection writes for a point to the depth a
+    /// perspective projection with the same near and far planes writes for it. Both map the
+    /// near plane to 0 and the far plane to 1. Kept in sync with `ortho_depth.fs.hlsl`.
+    pub fn perspective_depth(orthographic_depth: f32, near: f32, far: f32) -> f32 {
+        far * orthographic_depth / (near + orthographic_depth * (far - near))
+    }
+}
+
+/// Settings for velocity based camera movement. Without smoothing the camera moves in direct
+/// response to input.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraSmoothing {
+    pub enabled: bool,
+    /// Rate at which the camera reaches the speed of the keyboard input, per second.
+    pub acceleration: f32,
+    /// Rate at which the camera slows down without keyboard input, per second. Mouse movement
+    /// is eased in at the same rate.
+    pub damping: f32,
+}
+
+impl Default for CameraSmoothing {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            acceleration: 8.0,
+            damping: 6.0,
+        }
+    }
+}
 
 #[derive(Debug, Copy, Clone)]
 pub struct CameraState {
     position: Position,
     rotation: Rotation,
     fov: f32,
+    /// Height of the view in orthographic mode. Kept while in perspective mode so switching back
+    /// restores the zoom level.
+    ortho_height: f32,
+    orthographic: bool,
+    smoothing: CameraSmoothing,
+    /// Velocity of the camera from keyboard movement, in meters per second.
+    velocity: Vec3,
+    /// Movement from mouse input that was not applied yet while smoothing.
+    pending_position: Vec3,
+    /// Rotation from mouse input that was not applied yet while smoothing.
+    pending_rotation: Vec3,
 }
 
 #[derive(Debug)]
@@ -22,6 +84,7 @@ impl Event for EnableCameraEvent {}
 #[derive(Debug, Clone, Default)]
 pub struct Camera {
     enable_controls: bool,
+    transition: Option<CameraTransition>,
 }
 
 impl Camera {
@@ -44,11 +107,24 @@ impl Default for CameraState {
             position: Default::default(),
             rotation: Default::default(),
             fov: 90.0,
+            ortho_height: CameraState::DEFAULT_ORTHO_HEIGHT,
+            orthographic: false,
+            smoothing: CameraSmoothing::default(),
+            velocity: Vec3::ZERO,
+            pending_position: Vec3::ZERO,
+            pending_rotation: Vec3::ZERO,
         }
     }
 }
 
 impl CameraState {
+    const DEFAULT_ORTHO_HEIGHT: f32 = 1000.0;
+    /// Pitch the camera looks down at a focused point with, in radians.
+    const FOCUS_PITCH: f32 = -std::f32::consts::FRAC_PI_4;
+    /// Range of the orthographic height in meters.
+    pub const MIN_ORTHO_HEIGHT: f32 = 1.0;
+    pub const MAX_ORTHO_HEIGHT: f32 = 100000.0;
+
     fn clamp_rotation(rot: Rotation) -> Rotation {
         const MAX_ANGLE: f32 = std::f32::consts::PI / 2.0 - 0.0001;
         const UNBOUNDED: f32 = f32::MAX;
@@ -90,6 +166,95 @@ impl CameraState {
         self.fov
     }
 
+    /// Get the projection of the camera. The field of view of perspective mode is kept while
+    /// in orthographic mode, and is still used for camera poses.
+    pub fn projection(&self) -> Projection {
+        if self.orthographic {
+            Projection::Orthographic {
+                height: self.ortho_height,
+            }
+        } else {
+            Projection::Perspective {
+                fov: self.fov,
+            }
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        self.orthographic
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        match projection {
+            Projection::Perspective {
+                fov,
+            } => {
+                self.orthographic = false;
+                self.fov = fov;
+            }
+            Projection::Orthographic {
+                height,
+            } => {
+                self.orthographic = true;
+                self.set_ortho_height(height);
+            }
+        }
+    }
+
+    /// Switch between orthographic and perspective projection, keeping the field of view and
+    /// orthographic height of the other mode.
+    pub fn set_orthographic(&mut self, orthographic: bool) {
+        self.orthographic = orthographic;
+    }
+
+    pub fn set_ortho_height(&mut self, height: f32) {
+        self.ortho_height = height.clamp(Self::MIN_ORTHO_HEIGHT, Self::MAX_ORTHO_HEIGHT);
+    }
+
+    pub(crate) fn default_ortho_height() -> f32 {
+        Self::DEFAULT_ORTHO_HEIGHT
+    }
+
+    pub fn smoothing(&self) -> CameraSmoothing {
+        self.smoothing
+    }
+
+    /// Change the smoothing settings. When smoothing is disabled, movement that was still in
+    /// progress is applied immediately.
+    pub fn set_smoothing(&mut self, smoothing: CameraSmoothing) {
+        self.smoothing = smoothing;
+        if !smoothing.enabled {
+            self.update_position(Position(self.pending_position));
+            self.update_rotation(Rotation(self.pending_rotation));
+            self.stop();
+        }
+    }
+
+    /// Discard the velocity and any smoothed movement that was not applied yet.
+    pub fn stop(&mut self) {
+        self.velocity = Vec3::ZERO;
+        self.pending_position = Vec3::ZERO;
+        self.pending_rotation = Vec3::ZERO;
+    }
+
+    /// Get a pose that looks at `target` from `distance` away. The camera keeps its yaw and
+    /// looks down at the target. Moving an orthographic camera closer does not zoom in, so in
+    /// orthographic mode the view is as high as the field of view is wide at `distance`. This
+    /// frames the target the same in both modes.
+    pub fn focus_pose(&self, target: Vec3, distance: f32) -> CameraPose {
+        let rotation = Rotation(Vec3::new(Self::FOCUS_PITCH, self.rotation.yaw(), 0.0));
+        let ortho_height = match self.orthographic {
+            true => 2.0 * distance * (self.fov.to_radians() / 2.0).tan(),
+            false => self.ortho_height,
+        };
+        CameraPose {
+            position: target - rotation.front_direction() * distance,
+            rotation: rotation.0,
+            fov: self.fov,
+            ortho_height: ortho_height.clamp(Self::MIN_ORTHO_HEIGHT, Self::MAX_ORTHO_HEIGHT),
+        }
+    }
+
     pub fn set_position(&mut self, pos: Position) {
         self.position = pos;
     }
@@ -107,36 +272,155 @@ impl CameraState {
         self.rotation = Self::clamp_rotation(self.rotation);
     }
 
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov;
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            position: self.position.0,
+            rotation: self.rotation.0,
+            fov: self.fov,
+            ortho_height: self.ortho_height,
+        }
+    }
+
+    /// Move the camera to a pose. The projection mode is kept, but both its field of view and
+    /// orthographic height are taken from the pose.
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        self.stop();
+        self.set_position(Position(pose.position));
+        self.set_rotation(Rotation(pose.rotation));
+        self.set_fov(pose.fov);
+        self.set_ortho_height(pose.ortho_height);
+    }
+
     pub fn update_fov(&mut self, fov: f32) {
         self.fov += fov;
     }
 
+    /// Move the camera by mouse input. While smoothing, the movement is eased in over the
+    /// next ticks.
+    fn translate(&mut self, delta: Vec3) {
+        match self.smoothing.enabled {
+            true => self.pending_position += delta,
+            false => self.update_position(Position(delta)),
+        }
+    }
+
+    /// Rotate the camera by mouse input. While smoothing, the rotation is eased in over the
+    /// next ticks.
+    fn rotate(&mut self, delta: Vec3) {
+        match self.smoothing.enabled {
+            true => self.pending_rotation += delta,
+            false => self.update_rotation(Rotation(delta)),
+        }
+    }
+
     fn handle_move(&mut self, mouse: &MouseDelta) -> Result<()> {
         const SPEED: f32 = 5.0;
         let delta = self.up() * (mouse.y as f32) + self.right() * (-mouse.x as f32);
-        self.update_position(Position(delta * SPEED));
+        self.translate(delta * SPEED);
         Ok(())
     }
 
     fn handle_rotate(&mut self, mouse: &MouseDelta) -> Result<()> {
         const SPEED: f32 = 0.01;
         let delta = Vec3::new(-mouse.y as f32, mouse.x as f32, 0.0);
-        self.update_rotation(Rotation(delta * SPEED));
+        self.rotate(delta * SPEED);
         Ok(())
     }
 
     fn handle_scroll(&mut self, scroll: ScrollInfo) -> Result<()> {
         const SPEED: f32 = 50.0;
+        // Factor the orthographic height is multiplied with per scroll step.
+        const ZOOM_STEP: f32 = 0.9;
+        // Moving an orthographic camera along its view direction does not change the view,
+        // so zoom by changing the height of the view instead.
+        if self.orthographic {
+            self.set_ortho_height(self.ortho_height * ZOOM_STEP.powf(scroll.delta_y));
+            return Ok(());
+        }
         let delta = self.front() * scroll.delta_y;
-        self.update_position(Position(delta * SPEED));
+        self.translate(delta * SPEED);
         Ok(())
     }
 
+    /// Keyboard flycam movement through the `camera.*` input actions. By default WASD moves in
+    /// the horizontal plane, Q/E move down and up and holding shift boosts the movement speed.
+    /// Returns the velocity the input asks for, in meters per second.
+    fn fly_velocity(&self, input: &InputState) -> Vec3 {
+        // Speed in meters per second at the reference height
+        const SPEED: f32 = 50.0;
+        const BOOST: f32 = 4.0;
+        // Above this height, the speed scales linearly with the height of the camera.
+        // Since we have no way to query the terrain height on the CPU, the height is measured
+        // from the base plane of the terrain.
+        const REFERENCE_HEIGHT: f32 = 100.0;
+
+        let pressed = |action| input.is_action_active(action);
+        let axis = |positive, negative| match (pressed(positive), pressed(negative)) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        };
+
+        // Project front vector on the horizontal plane so looking up or down does not change
+        // the height when moving forward.
+        let front = Vec3::new(self.front().x, 0.0, self.front().z).normalize_or_zero();
+        let right = self.right();
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let direction = front * axis(actions::CAMERA_FORWARD, actions::CAMERA_BACK)
+            + right * axis(actions::CAMERA_RIGHT, actions::CAMERA_LEFT)
+            + up * axis(actions::CAMERA_UP, actions::CAMERA_DOWN);
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            return Vec3::ZERO;
+        }
+
+        let height_factor = (self.position.0.y.abs() / REFERENCE_HEIGHT).max(1.0);
+        let boost = if pressed(actions::CAMERA_BOOST) {
+            BOOST
+        } else {
+            1.0
+        };
+        direction * SPEED * height_factor * boost
+    }
+
+    /// Move the camera for a frame that took `delta`, with the velocity asked for by the
+    /// keyboard input. While smoothing, the velocity of the camera accelerates towards it and
+    /// mouse movement that was not applied yet is eased in. The exponential decay keeps this
+    /// independent of the frame rate.
+    fn advance(&mut self, target_velocity: Vec3, delta: Duration) {
+        let dt = delta.as_secs_f32();
+        if !self.smoothing.enabled {
+            self.update_position(Position(target_velocity * dt));
+            return;
+        }
+
+        let rate = match target_velocity == Vec3::ZERO {
+            true => self.smoothing.damping,
+            false => self.smoothing.acceleration,
+        };
+        self.velocity = self
+            .velocity
+            .lerp(target_velocity, 1.0 - (-rate * dt).exp());
+        self.update_position(Position(self.velocity * dt));
+
+        let t = 1.0 - (-self.smoothing.damping * dt).exp();
+        let position = self.pending_position * t;
+        let rotation = self.pending_rotation * t;
+        self.pending_position -= position;
+        self.pending_rotation -= rotation;
+        self.update_position(Position(position));
+        self.update_rotation(Rotation(rotation));
+    }
+
     pub fn handle_event(&mut self, event: &InputEvent, input: &InputState) -> Result<()> {
         match event {
             InputEvent::MouseMove(delta) => {
-                if input.get_mouse_key(MouseButton::Middle) == ButtonState::Pressed {
-                    if input.get_key(Key::Shift) == ButtonState::Pressed {
+                if input.is_action_active(actions::CAMERA_ROTATE) {
+                    if input.is_action_active(actions::CAMERA_PAN) {
                         self.handle_move(delta)?;
                     } else {
                         self.handle_rotate(delta)?;
@@ -144,7 +428,12 @@ impl CameraState {
                 }
             }
             InputEvent::Scroll(scroll) => {
-                self.handle_scroll(*scroll)?;
+                // Scrolling with a modifier held is used to adjust the brush instead
+                let modifier = input.get_key(Key::Control) == ButtonState::Pressed
+                    || input.get_key(Key::Alt) == ButtonState::Pressed;
+                if !modifier {
+                    self.handle_scroll(*scroll)?;
+                }
             }
             _ => {}
         }
@@ -159,6 +448,8 @@ impl System<DI> for Camera {
         Self: Sized, {
         event_bus.subscribe(system, handle_input_event);
         event_bus.subscribe(system, handle_enabled_event);
+        event_bus.subscribe(system, handle_tick_event);
+        event_bus.subscribe(system, handle_transition_event);
     }
 }
 
@@ -188,6 +479,45 @@ fn handle_input_event(
     Ok(())
 }
 
+/// # DI Access
+/// - Read [`CameraState`]
+fn handle_transition_event(
+    camera: &mut Camera,
+    event: &CameraTransitionEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let state = di.read_sync::<CameraState>().unwrap();
+    camera.transition = Some(CameraTransition::new(state.pose(), event.target, event.duration));
+    Ok(())
+}
+
+/// # DI Access
+/// - Write [`CameraState`]
+/// - Read [`InputState`]
+/// - Read [`Time`]
+fn handle_tick_event(camera: &mut Camera, _event: &Tick, ctx: &mut EventContext<DI>) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let mut state = di.write_sync::<CameraState>().unwrap();
+    let time = di.read_sync::<Time>().unwrap();
+    // A running transition takes over the camera until it is finished
+    if let Some(transition) = &mut camera.transition {
+        let (pose, done) = transition.advance(time.real_delta);
+        state.set_pose(pose);
+        if done {
+            camera.transition = None;
+        }
+        return Ok(());
+    }
+    let input = di.read_sync::<InputState>().unwrap();
+    let target_velocity = match camera.enable_controls {
+        true => state.fly_velocity(&input),
+        false => Vec3::ZERO,
+    };
+    state.advance(target_velocity, time.real_delta);
+    Ok(())
+}
+
 pub fn initialize(
     position: Position,
     rotation: Rotation,
@@ -199,9 +529,136 @@ pub fn initialize(
         position,
         rotation,
         fov,
+        ..Default::default()
     };
     bus.data_mut().write().unwrap().put_sync(state);
     // Add the camera controller system
     bus.add_system(Camera::new());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smoothed() -> CameraState {
+        let mut state = CameraState::default();
+        state.set_smoothing(CameraSmoothing {
+            enabled: true,
+            ..Default::default()
+        });
+        state
+    }
+
+    #[test]
+    fn smoothing_is_frame_rate_independent() {
+        let target = Vec3::new(10.0, 0.0, 0.0);
+        let mut slow = smoothed();
+        let mut fast = smoothed();
+        slow.advance(target, Duration::from_millis(20));
+        for _ in 0..4 {
+            fast.advance(target, Duration::from_millis(5));
+        }
+        assert!((slow.velocity - fast.velocity).length() < 1e-4);
+    }
+
+    #[test]
+    fn disabling_smoothing_applies_pending_movement() {
+        let mut state = smoothed();
+        state.translate(Vec3::new(0.0, 5.0, 0.0));
+        assert_eq!(state.position().0, Vec3::ZERO);
+        state.set_smoothing(CameraSmoothing::default());
+        assert_eq!(state.position().0, Vec3::new(0.0, 5.0, 0.0));
+    }
+
+    fn scroll(state: &mut CameraState, delta_y: f32) {
+        state
+            .handle_scroll(ScrollInfo {
+                delta_x: 0.0,
+                delta_y,
+            })
+            .unwrap();
+    }
+
+    fn ortho_height(state: &CameraState) -> f32 {
+        match state.projection() {
+            Projection::Orthographic {
+                height,
+            } => height,
+            Projection::Perspective {
+                ..
+            } => panic!("camera is not orthographic"),
+        }
+    }
+
+    #[test]
+    fn orthographic_scroll_zooms_without_moving() {
+        let mut state = CameraState::default();
+        state.set_projection(Projection::Orthographic {
+            height: 100.0,
+        });
+        scroll(&mut state, 1.0);
+        assert!((ortho_height(&state) - 90.0).abs() < 1e-4);
+        scroll(&mut state, -1.0);
+        assert!((ortho_height(&state) - 100.0).abs() < 1e-4);
+        assert_eq!(state.position().0, Vec3::ZERO);
+    }
+
+    #[test]
+    fn orthographic_height_is_clamped() {
+        let mut state = CameraState::default();
+        state.set_projection(Projection::Orthographic {
+            height: 2.0,
+        });
+        scroll(&mut state, 100.0);
+        assert_eq!(ortho_height(&state), CameraState::MIN_ORTHO_HEIGHT);
+        scroll(&mut state, -1000.0);
+        assert_eq!(ortho_height(&state), CameraState::MAX_ORTHO_HEIGHT);
+        state.set_ortho_height(0.0);
+        assert_eq!(ortho_height(&state), CameraState::MIN_ORTHO_HEIGHT);
+    }
+
+    #[test]
+    fn switching_projection_keeps_orthographic_height() {
+        let mut state = CameraState::default();
+        state.set_projection(Projection::Orthographic {
+            height: 250.0,
+        });
+        state.set_orthographic(false);
+        assert!(!state.is_orthographic());
+        state.set_orthographic(true);
+        assert_eq!(ortho_height(&state), 250.0);
+    }
+
+    #[test]
+    fn focus_frames_the_target_in_orthographic_mode() {
+        let mut state = CameraState::default();
+        let target = Vec3::new(10.0, 0.0, 0.0);
+        // A field of view of 90 degrees is twice as high as the distance
+        let pose = state.focus_pose(target, 100.0);
+        assert_eq!(pose.ortho_height, CameraState::DEFAULT_ORTHO_HEIGHT);
+        state.set_orthographic(true);
+        let pose = state.focus_pose(target, 100.0);
+        assert!((pose.ortho_height - 200.0).abs() < 1e-3, "{}", pose.ortho_height);
+        assert!((pose.position.distance(target) - 100.0).abs() < 1e-3);
+        // Transitions tween the height along with the rest of the pose
+        let halfway = state.pose().lerp(&pose, 0.5);
+        assert!((halfway.ortho_height - 600.0).abs() < 1e-3, "{}", halfway.ortho_height);
+        state.set_pose(pose);
+        assert!((ortho_height(&state) - 200.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn orthographic_depth_converts_to_perspective_depth() {
+        let (near, far) = (0.1, 10000.0);
+        let orthographic = Mat4::orthographic_rh(-1.0, 1.0, -1.0, 1.0, near, far);
+        let perspective = Mat4::perspective_rh(1.0, 1.0, near, far);
+        for distance in [near, 1.0, 25.0, 800.0, 5000.0, far] {
+            let point = Vec3::new(0.0, 0.0, -distance);
+            let converted =
+                Projection::perspective_depth(orthographic.project_point3(point).z, near, far);
+            let expected = perspective.project_point3(point).z;
+            assert!((converted - expected).abs() < 1e-4, "{distance}: {converted} != {expected}");
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use scheduler::Event;
+
+/// What the camera is framed on by a [`FocusEvent`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FocusTarget {
+    /// The center of the terrain, framing all of it.
+    Terrain,
+    /// The point of the terrain under the cursor.
+    Cursor,
+}
+
+/// Move the camera to frame the terrain or a point on it. The camera moves there smoothly.
+/// The handler reads the world, so this must not be published while the world is locked, such
+/// as from the editor.
+#[derive(Debug, Copy, Clone)]
+pub struct FocusEvent {
+    pub target: FocusTarget,
+}
+
+impl Event for FocusEvent {}
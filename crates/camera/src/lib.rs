@@ -1,3 +1,9 @@
+pub use bookmark::*;
 pub use camera::*;
+pub use focus::*;
+pub use transition::*;
 
+pub mod bookmark;
 pub mod camera;
+pub mod focus;
+pub mod transition;
@@ -0,0 +1,116 @@
+use std::f32::consts::{PI, TAU};
+use std::time::Duration;
+
+use glam::Vec3;
+use scheduler::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::CameraState;
+
+/// Position, rotation and projection of the camera.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub position: Vec3,
+    /// Pitch, yaw and roll in radians.
+    pub rotation: Vec3,
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+    /// Height of the view in meters in orthographic mode, see
+    /// [`Projection::Orthographic`](crate::Projection::Orthographic).
+    /// Poses saved before this was stored use the default height.
+    #[serde(default = "CameraState::default_ortho_height")]
+    pub ortho_height: f32,
+}
+
+impl CameraPose {
+    /// Interpolates between two poses. The yaw is interpolated along the shortest arc, so
+    /// the camera never spins around more than half a turn.
+    pub fn lerp(&self, target: &CameraPose, t: f32) -> CameraPose {
+        let mut target_rotation = target.rotation;
+        let yaw_diff = (target.rotation.y - self.rotation.y + PI).rem_euclid(TAU) - PI;
+        target_rotation.y = self.rotation.y + yaw_diff;
+        CameraPose {
+            position: self.position.lerp(target.position, t),
+            rotation: self.rotation.lerp(target_rotation, t),
+            fov: self.fov + (target.fov - self.fov) * t,
+            ortho_height: self.ortho_height + (target.ortho_height - self.ortho_height) * t,
+        }
+    }
+}
+
+/// Smoothly move the camera to a new pose over the given duration.
+#[derive(Debug, Clone)]
+pub struct CameraTransitionEvent {
+    pub target: CameraPose,
+    pub duration: Duration,
+}
+
+impl Event for CameraTransitionEvent {}
+
+/// An in-progress transition between two camera poses.
+#[derive(Debug, Clone)]
+pub(crate) struct CameraTransition {
+    from: CameraPose,
+    to: CameraPose,
+    elapsed: Duration,
+    duration: Duration,
+}
+
+impl CameraTransition {
+    pub fn new(from: CameraPose, to: CameraPose, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            elapsed: Duration::ZERO,
+            duration,
+        }
+    }
+
+    /// Advance the transition by `delta`. Returns the pose the camera should have, and
+    /// whether the transition is finished.
+    pub fn advance(&mut self, delta: Duration) -> (CameraPose, bool) {
+        self.elapsed += delta;
+        if self.elapsed >= self.duration {
+            return (self.to, true);
+        }
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        // Ease in and out so the camera does not start or stop abruptly
+        let t = t * t * (3.0 - 2.0 * t);
+        (self.from.lerp(&self.to, t), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pose(yaw: f32) -> CameraPose {
+        CameraPose {
+            position: Vec3::ZERO,
+            rotation: Vec3::new(0.0, yaw, 0.0),
+            fov: 90.0,
+            ortho_height: 1000.0,
+        }
+    }
+
+    #[test]
+    fn lerp_takes_shortest_yaw_arc() {
+        // Going from just below a full turn to just above zero should pass through a full turn,
+        // not spin back through half a turn.
+        let from = pose(TAU - 0.1);
+        let to = pose(0.1);
+        let halfway = from.lerp(&to, 0.5);
+        assert!((halfway.rotation.y - TAU).abs() < 1e-4, "{}", halfway.rotation.y);
+    }
+
+    #[test]
+    fn transition_ends_at_target() {
+        let mut transition =
+            CameraTransition::new(pose(0.0), pose(1.0), Duration::from_millis(500));
+        let (_, done) = transition.advance(Duration::from_millis(250));
+        assert!(!done);
+        let (end, done) = transition.advance(Duration::from_millis(300));
+        assert!(done);
+        assert_eq!(end, pose(1.0));
+    }
+}
@@ -10,50 +10,108 @@ pub enum MessageLevel {
 pub struct MessageEvent {
     pub level: MessageLevel,
     pub message: String,
+    /// Subsystem this message originated from, e.g. "shader", "asset" or "gpu".
+    /// Used to group messages together.
+    pub source: Option<&'static str>,
+    /// Additional content that is too long to show in the message itself, such as full compiler output.
+    pub details: Option<String>,
+}
+
+impl MessageEvent {
+    pub fn new(level: MessageLevel, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            source: None,
+            details: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: &'static str) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
 }
 
 impl Event for MessageEvent {}
 
 #[macro_export]
 macro_rules! publish_error {
+    ($bus:ident, source = $source:expr, $fmt:expr) => {
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Error, format!($fmt)).with_source($source));
+    };
+
+    ($bus:ident, source = $source:expr, $fmt:expr, $($args:tt)*) => {
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Error, format!($fmt, $($args)*)).with_source($source));
+    };
+
     ($bus:ident, $fmt:expr) => {
-        let _ = $bus.publish($crate::MessageEvent { level: $crate::MessageLevel::Error, message: format!($fmt) });
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Error, format!($fmt)));
     };
 
     ($bus:ident, $fmt:expr, $($args:tt)*) => {
-        let _ = $bus.publish($crate::MessageEvent { level: $crate::MessageLevel::Error, message: format!($fmt, $($args)*) });
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Error, format!($fmt, $($args)*)));
     };
 }
 
 #[macro_export]
 macro_rules! publish_success {
+    ($bus:ident, source = $source:expr, $fmt:expr) => {
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Success, format!($fmt)).with_source($source));
+    };
+
+    ($bus:ident, source = $source:expr, $fmt:expr, $($args:tt)*) => {
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Success, format!($fmt, $($args)*)).with_source($source));
+    };
+
     ($bus:ident, $fmt:expr) => {
-        let _ = $bus.publish($crate::MessageEvent { level: $crate::MessageLevel::Success, message: format!($fmt) });
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Success, format!($fmt)));
     };
 
     ($bus:ident, $fmt:expr, $($args:tt)*) => {
-        let _ = $bus.publish($crate::MessageEvent { level: $crate::MessageLevel::Success, message: format!($fmt, $($args)*) });
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Success, format!($fmt, $($args)*)));
     };
 }
 
 #[macro_export]
 macro_rules! publish_info {
+    ($bus:ident, source = $source:expr, $fmt:expr) => {
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Info, format!($fmt)).with_source($source));
+    };
+
+    ($bus:ident, source = $source:expr, $fmt:expr, $($args:tt)*) => {
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Info, format!($fmt, $($args)*)).with_source($source));
+    };
+
     ($bus:ident, $fmt:expr) => {
-        let _ = $bus.publish($crate::MessageEvent { level: $crate::MessageLevel::Info, message: format!($fmt) });
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Info, format!($fmt)));
     };
 
     ($bus:ident, $fmt:expr, $($args:tt)*) => {
-        let _ = $bus.publish($crate::MessageEvent { level: $crate::MessageLevel::Info, message: format!($fmt, $($args)*) });
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Info, format!($fmt, $($args)*)));
     };
 }
 
 #[macro_export]
 macro_rules! publish_warn {
+    ($bus:ident, source = $source:expr, $fmt:expr) => {
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Warning, format!($fmt)).with_source($source));
+    };
+
+    ($bus:ident, source = $source:expr, $fmt:expr, $($args:tt)*) => {
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Warning, format!($fmt, $($args)*)).with_source($source));
+    };
+
     ($bus:ident, $fmt:expr) => {
-        let _ = $bus.publish($crate::MessageEvent { level: $crate::MessageLevel::Warning, message: format!($fmt) });
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Warning, format!($fmt)));
     };
 
     ($bus:ident, $fmt:expr, $($args:tt)*) => {
-        let _ = $bus.publish($crate::MessageEvent { level: $crate::MessageLevel::Warning, message: format!($fmt, $($args)*) });
+        let _ = $bus.publish($crate::MessageEvent::new($crate::MessageLevel::Warning, format!($fmt, $($args)*)));
     };
 }
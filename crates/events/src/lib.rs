@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use input::MousePosition;
 use scheduler::Event;
 
@@ -5,6 +7,16 @@ pub struct Tick;
 
 impl Event for Tick {}
 
+/// Published at a fixed rate by the time system, zero or more times per [`Tick`]. Use this for
+/// updates that must step deterministically regardless of frame rate.
+#[derive(Debug, Copy, Clone)]
+pub struct FixedTick {
+    /// Time step of this tick, this is the same for every tick at a given rate.
+    pub dt: Duration,
+}
+
+impl Event for FixedTick {}
+
 /// Primary button click on the world view
 #[derive(Debug, Copy, Clone)]
 pub struct DragWorldView {
@@ -1,17 +1,23 @@
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use inject::DI;
+use log::warn;
 use phobos::fsr2::FfxFsr2InitializationFlagBits;
 use phobos::{
-    vk, Allocator, AppBuilder, AppSettings, DebugMessenger, DefaultAllocator, DescriptorCache,
-    Device, ExecutionManager, FrameManager, GPURequirements, PhysicalDevice, PipelineCache,
-    QueueRequest, QueueType, Sampler, Surface, Swapchain, VkInstance, WindowInterface,
+    Allocator, AppBuilder, AppSettings, DebugMessenger, DefaultAllocator, DescriptorCache, Device,
+    ExecutionManager, FrameManager, GPURequirements, PhysicalDevice, PipelineCache, QueueRequest,
+    QueueType, Sampler, Surface, Swapchain, VkInstance, WindowInterface,
 };
+pub use present::*;
 use scheduler::EventBus;
 pub use util::*;
 use winit::window::Window;
 
+use crate::selection::{describe_devices, required_features, MIN_VIDEO_MEMORY};
+
+pub mod present;
+mod selection;
 pub mod state;
 pub mod util;
 
@@ -32,27 +38,24 @@ pub struct Samplers {
     pub raw: Sampler,
 }
 
-fn fill_app_settings<W: WindowInterface>(window: &W) -> AppSettings<W> {
-    let features = vk::PhysicalDeviceFeatures {
-        fill_mode_non_solid: vk::TRUE,
-        tessellation_shader: vk::TRUE,
-        sampler_anisotropy: vk::TRUE,
-        independent_blend: vk::TRUE,
-        ..Default::default()
-    };
-
-    // Allows wireframe polygon mode
-
+/// If `dedicated_queues` is set, separate transfer and compute queues are requested.
+/// Otherwise these may share a queue family with graphics.
+fn fill_app_settings<W: WindowInterface>(
+    window: &W,
+    validation: bool,
+    dedicated_queues: bool,
+    present_mode: PresentMode,
+) -> AppSettings<W> {
     AppBuilder::new()
         .version((0, 0, 1))
         .name("Andromeda")
-        .validation(cfg!(debug_assertions))
+        .validation(validation)
         .window(window)
-        .present_mode(vk::PresentModeKHR::MAILBOX)
+        .present_mode(present_mode.into())
         .scratch_size(8 * 1024 * 1024u64)
         .gpu(GPURequirements {
             dedicated: false,
-            min_video_memory: 1024 * 1024 * 1024,
+            min_video_memory: MIN_VIDEO_MEMORY,
             min_dedicated_video_memory: 0,
             queues: vec![
                 QueueRequest {
@@ -60,15 +63,15 @@ fn fill_app_settings<W: WindowInterface>(window: &W) -> AppSettings<W> {
                     queue_type: QueueType::Graphics,
                 },
                 QueueRequest {
-                    dedicated: true,
+                    dedicated: dedicated_queues,
                     queue_type: QueueType::Transfer,
                 },
                 QueueRequest {
-                    dedicated: true,
+                    dedicated: dedicated_queues,
                     queue_type: QueueType::Compute,
                 },
             ],
-            features,
+            features: required_features(),
             ..Default::default()
         })
         .fsr2_display_size(16, 16)
@@ -80,23 +83,63 @@ fn fill_app_settings<W: WindowInterface>(window: &W) -> AppSettings<W> {
         .build()
 }
 
-/// Injects the graphics context into the DI system, and returns the frame manager and surface
+/// Selects a physical device, preferring one with dedicated transfer and compute queues.
+/// If no such device exists, queues shared with graphics are accepted instead. Returns the
+/// settings matching the selected device.
+fn select_physical_device<'w>(
+    instance: &VkInstance,
+    surface: &Surface,
+    settings: AppSettings<'w, Window>,
+    window: &'w Window,
+    validation: bool,
+    present_mode: PresentMode,
+) -> Result<(PhysicalDevice, AppSettings<'w, Window>)> {
+    match PhysicalDevice::select(instance, Some(surface), &settings) {
+        Ok(device) => return Ok((device, settings)),
+        Err(e) => {
+            warn!("No GPU with dedicated transfer and compute queues ({e}), using shared queues.")
+        }
+    }
+    let settings = fill_app_settings(window, validation, false, present_mode);
+    match PhysicalDevice::select(instance, Some(surface), &settings) {
+        Ok(device) => Ok((device, settings)),
+        Err(e) => Err(anyhow!(
+            "Could not find a compatible GPU ({e}).\n{}",
+            describe_devices(instance)
+        )),
+    }
+}
+
+/// Injects the graphics context into the DI system, and returns the frame manager and surface.
+/// If `validation` is set, the Vulkan validation layers are enabled together with a debug messenger.
+/// The present mode is read from [`GRAPHICS_SETTINGS_FILE`], and checked against the present
+/// modes the surface supports.
 pub fn initialize(
     window: &Window,
+    validation: bool,
     bus: &EventBus<DI>,
 ) -> Result<(FrameManager, Surface, SharedContext)> {
-    let settings = fill_app_settings(window);
+    let graphics_settings = GraphicsSettings::load_or_default(GRAPHICS_SETTINGS_FILE)
+        .unwrap_or_else(|e| {
+            warn!("Could not load graphics settings from {GRAPHICS_SETTINGS_FILE}: {e}");
+            GraphicsSettings::default()
+        });
+    let requested = graphics_settings.present_mode;
+    let settings = fill_app_settings(window, validation, true, requested);
     let instance = VkInstance::new(&settings)?;
-    #[cfg(debug_assertions)]
-    let debug_messenger = Some(Arc::new(DebugMessenger::new(&instance)?));
-    #[cfg(not(debug_assertions))]
-    let debug_messenger = None;
-    let (surface, physical_device) = {
+    let debug_messenger = match validation {
+        true => Some(Arc::new(DebugMessenger::new(&instance)?)),
+        false => None,
+    };
+    let (surface, physical_device, mut settings, presentation) = {
         let mut surface = Surface::new(&instance, &settings)?;
-        let physical_device = PhysicalDevice::select(&instance, Some(&surface), &settings)?;
+        let (physical_device, settings) =
+            select_physical_device(&instance, &surface, settings, window, validation, requested)?;
         surface.query_details(&physical_device)?;
-        (surface, physical_device)
+        let presentation = Presentation::new(surface.present_modes(), requested);
+        (surface, physical_device, settings, presentation)
     };
+    settings.present_mode = Some(presentation.current.into());
 
     let device = Device::new(&instance, &physical_device, &settings)?;
     let allocator = DefaultAllocator::new(&instance, &device, &physical_device)?;
@@ -130,6 +173,22 @@ pub fn initialize(
     };
 
     bus.data().write().unwrap().put(samplers);
+    bus.data().write().unwrap().put_sync(presentation);
+    bus.add_system(PresentationSystem);
 
     Ok((frame, surface, gfx))
 }
+
+/// Create a new frame manager with a swapchain using the given present mode. Only one swapchain
+/// can exist for a surface, so the previous frame manager must be destroyed first, after
+/// waiting for the device to be idle.
+pub fn create_frame_manager(
+    window: &Window,
+    surface: &Surface,
+    gfx: &SharedContext,
+    present_mode: PresentMode,
+) -> Result<FrameManager> {
+    let settings = fill_app_settings(window, false, false, present_mode);
+    let swapchain = Swapchain::new(&gfx.instance, gfx.device.clone(), &settings, surface)?;
+    FrameManager::new(gfx.device.clone(), gfx.allocator.clone(), &settings, swapchain)
+}
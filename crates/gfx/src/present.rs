@@ -0,0 +1,221 @@
+use std::path::Path;
+
+use anyhow::Result;
+use inject::DI;
+use log::warn;
+use phobos::vk;
+use scheduler::{Event, EventBus, EventContext, StoredSystem, System};
+use serde::{Deserialize, Serialize};
+
+/// File graphics settings are saved to and loaded from.
+pub const GRAPHICS_SETTINGS_FILE: &str = "data/graphics_settings.json";
+
+/// How rendered frames are presented to the window.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresentMode {
+    /// Wait for vertical blank, limiting the frame rate to the refresh rate of the display.
+    /// This is always supported, and uses the least power.
+    Fifo,
+    /// Present the latest frame at vertical blank without waiting for it, so there is no
+    /// tearing and no frame rate limit.
+    Mailbox,
+    /// Present immediately, which may cause tearing.
+    Immediate,
+}
+
+impl PresentMode {
+    pub const ALL: [PresentMode; 3] =
+        [PresentMode::Fifo, PresentMode::Mailbox, PresentMode::Immediate];
+
+    /// Whether frames are synchronized to the vertical blank of the display.
+    pub fn vsync(self) -> bool {
+        self == PresentMode::Fifo
+    }
+
+    fn from_vk(mode: vk::PresentModeKHR) -> Option<Self> {
+        match mode {
+            vk::PresentModeKHR::FIFO => Some(PresentMode::Fifo),
+            vk::PresentModeKHR::MAILBOX => Some(PresentMode::Mailbox),
+            vk::PresentModeKHR::IMMEDIATE => Some(PresentMode::Immediate),
+            _ => None,
+        }
+    }
+}
+
+impl From<PresentMode> for vk::PresentModeKHR {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+            PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+            PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+        }
+    }
+}
+
+/// Graphics settings that persist across sessions. These are read when the graphics context
+/// is initialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub present_mode: PresentMode,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::Mailbox,
+        }
+    }
+}
+
+impl GraphicsSettings {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    /// Load settings from a file, falling back to the defaults if the file does not exist.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+/// Present modes supported by the surface, and the one in use. Access through DI.
+#[derive(Debug, Clone)]
+pub struct Presentation {
+    pub current: PresentMode,
+    pub supported: Vec<PresentMode>,
+    /// Present mode the swapchain is recreated with before the next frame.
+    pub request: Option<PresentMode>,
+}
+
+impl Presentation {
+    pub(crate) fn new(supported: &[vk::PresentModeKHR], requested: PresentMode) -> Self {
+        let supported = supported
+            .iter()
+            .filter_map(|mode| PresentMode::from_vk(*mode))
+            .collect::<Vec<_>>();
+        let mut presentation = Self {
+            current: PresentMode::Fifo,
+            supported,
+            request: None,
+        };
+        presentation.current = presentation.choose(requested);
+        presentation
+    }
+
+    /// Get the present mode to use for a requested mode. If the requested mode is not
+    /// supported, the other mode without vsync is tried before falling back to FIFO, which
+    /// every surface supports.
+    pub fn choose(&self, requested: PresentMode) -> PresentMode {
+        if self.supported.contains(&requested) {
+            return requested;
+        }
+        let alternative = match requested {
+            PresentMode::Fifo => None,
+            PresentMode::Mailbox => Some(PresentMode::Immediate),
+            PresentMode::Immediate => Some(PresentMode::Mailbox),
+        };
+        let mode = alternative
+            .filter(|mode| self.supported.contains(mode))
+            .unwrap_or(PresentMode::Fifo);
+        warn!("Present mode {requested:?} is not supported, using {mode:?} instead.");
+        mode
+    }
+}
+
+/// Change the present mode. The swapchain is recreated before the next frame, and the mode
+/// is saved to [`GRAPHICS_SETTINGS_FILE`].
+#[derive(Debug, Copy, Clone)]
+pub struct SetPresentModeEvent {
+    pub present_mode: PresentMode,
+}
+
+impl Event for SetPresentModeEvent {}
+
+pub(crate) struct PresentationSystem;
+
+impl System<DI> for PresentationSystem {
+    fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+        event_bus.subscribe(system, handle_set_present_mode);
+    }
+}
+
+/// # DI Access
+/// - Write [`Presentation`]
+fn handle_set_present_mode(
+    _system: &mut PresentationSystem,
+    event: &SetPresentModeEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let mut presentation = di.write_sync::<Presentation>().unwrap();
+    let mode = presentation.choose(event.present_mode);
+    presentation.request = (mode != presentation.current).then_some(mode);
+    let settings = GraphicsSettings {
+        present_mode: event.present_mode,
+    };
+    if let Err(e) = settings.save(GRAPHICS_SETTINGS_FILE) {
+        warn!("Could not save graphics settings to {GRAPHICS_SETTINGS_FILE}: {e}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_supported(supported: &[PresentMode]) -> Presentation {
+        Presentation {
+            current: PresentMode::Fifo,
+            supported: supported.to_vec(),
+            request: None,
+        }
+    }
+
+    #[test]
+    fn supported_mode_is_chosen() {
+        let presentation = with_supported(&PresentMode::ALL);
+        for mode in PresentMode::ALL {
+            assert_eq!(presentation.choose(mode), mode);
+        }
+    }
+
+    #[test]
+    fn unsupported_mode_falls_back_to_other_mode_without_vsync() {
+        let presentation = with_supported(&[PresentMode::Fifo, PresentMode::Immediate]);
+        assert_eq!(presentation.choose(PresentMode::Mailbox), PresentMode::Immediate);
+        let presentation = with_supported(&[PresentMode::Fifo, PresentMode::Mailbox]);
+        assert_eq!(presentation.choose(PresentMode::Immediate), PresentMode::Mailbox);
+    }
+
+    #[test]
+    fn unsupported_mode_falls_back_to_fifo() {
+        let presentation = with_supported(&[PresentMode::Fifo]);
+        assert_eq!(presentation.choose(PresentMode::Mailbox), PresentMode::Fifo);
+        assert_eq!(presentation.choose(PresentMode::Immediate), PresentMode::Fifo);
+        // FIFO is always chosen when requested, even if the surface did not report it
+        assert_eq!(with_supported(&[]).choose(PresentMode::Fifo), PresentMode::Fifo);
+    }
+
+    #[test]
+    fn unknown_present_modes_are_ignored() {
+        let supported = [vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::MAILBOX];
+        let presentation = Presentation::new(&supported, PresentMode::Mailbox);
+        assert_eq!(presentation.supported, vec![PresentMode::Mailbox]);
+        assert_eq!(presentation.current, PresentMode::Mailbox);
+    }
+}
@@ -0,0 +1,93 @@
+use std::ffi::CStr;
+use std::fmt::Write;
+
+use phobos::{vk, VkInstance};
+
+/// Minimum amount of device local memory a GPU needs to have.
+pub(crate) const MIN_VIDEO_MEMORY: usize = 1024 * 1024 * 1024;
+
+/// Device features the renderer relies on.
+pub(crate) fn required_features() -> vk::PhysicalDeviceFeatures {
+    vk::PhysicalDeviceFeatures {
+        // Allows wireframe polygon mode
+        fill_mode_non_solid: vk::TRUE,
+        tessellation_shader: vk::TRUE,
+        sampler_anisotropy: vk::TRUE,
+        independent_blend: vk::TRUE,
+        ..Default::default()
+    }
+}
+
+fn missing_features(available: &vk::PhysicalDeviceFeatures) -> Vec<&'static str> {
+    [
+        ("fillModeNonSolid", available.fill_mode_non_solid),
+        ("tessellationShader", available.tessellation_shader),
+        ("samplerAnisotropy", available.sampler_anisotropy),
+        ("independentBlend", available.independent_blend),
+    ]
+    .into_iter()
+    .filter(|(_, supported)| *supported != vk::TRUE)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+/// Builds a human readable report of what is required from a GPU, and what every GPU in the
+/// system provides. This is used to explain why no suitable device could be selected.
+pub(crate) fn describe_devices(instance: &VkInstance) -> String {
+    let mut report = format!(
+        "Required: a graphics queue that can present, compute and transfer support (dedicated \
+         queues are preferred but not required), {} MiB of video memory and the features \
+         fillModeNonSolid, tessellationShader, samplerAnisotropy and independentBlend.\n",
+        MIN_VIDEO_MEMORY / (1024 * 1024)
+    );
+    // SAFETY: The instance is valid for the duration of this call, and we only query properties.
+    let devices = match unsafe { instance.enumerate_physical_devices() } {
+        Ok(devices) => devices,
+        Err(e) => {
+            let _ = writeln!(report, "Available: could not enumerate devices ({e})");
+            return report;
+        }
+    };
+    if devices.is_empty() {
+        report.push_str("Available: no Vulkan capable devices found");
+        return report;
+    }
+
+    report.push_str("Available:");
+    for device in devices {
+        // SAFETY: `device` was just returned by the instance.
+        let (properties, features, memory, families) = unsafe {
+            (
+                instance.get_physical_device_properties(device),
+                instance.get_physical_device_features(device),
+                instance.get_physical_device_memory_properties(device),
+                instance.get_physical_device_queue_family_properties(device),
+            )
+        };
+        // SAFETY: The driver fills the name with a null terminated string.
+        let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }.to_string_lossy();
+        let video_memory: u64 = memory.memory_heaps[..memory.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+        let _ = write!(
+            report,
+            "\n- {name} ({:?}, {} MiB video memory)",
+            properties.device_type,
+            video_memory / (1024 * 1024)
+        );
+        for (index, family) in families.iter().enumerate() {
+            let _ = write!(
+                report,
+                "\n    queue family {index}: {:?} x{}",
+                family.queue_flags, family.queue_count
+            );
+        }
+        let missing = missing_features(&features);
+        if !missing.is_empty() {
+            let _ = write!(report, "\n    missing features: {}", missing.join(", "));
+        }
+    }
+    report
+}
@@ -37,6 +37,8 @@ pub struct RenderState {
     pub far: f32,
     /// Camera FOV
     pub fov: f32,
+    /// Whether the projection matrix is orthographic. Depth is linear in that case.
+    pub orthographic: bool,
     /// Previous projection-view matrix
     pub previous_pv: Mat4,
 }
@@ -3,8 +3,17 @@ use phobos::{vk, Sampler};
 
 use crate::SharedContext;
 
-/// Create a sampler with no interpolation or anisotropic filtering.
+/// Create a sampler with no interpolation or anisotropic filtering, that clamps to the edge.
 pub fn create_raw_sampler(ctx: &SharedContext) -> Result<Sampler> {
+    create_raw_sampler_with(ctx, vk::SamplerAddressMode::CLAMP_TO_EDGE)
+}
+
+/// Create a sampler with no interpolation or anisotropic filtering, using `address_mode`
+/// on all axes.
+pub fn create_raw_sampler_with(
+    ctx: &SharedContext,
+    address_mode: vk::SamplerAddressMode,
+) -> Result<Sampler> {
     Sampler::new(
         ctx.device.clone(),
         vk::SamplerCreateInfo {
@@ -14,9 +23,9 @@ pub fn create_raw_sampler(ctx: &SharedContext) -> Result<Sampler> {
             mag_filter: vk::Filter::NEAREST,
             min_filter: vk::Filter::NEAREST,
             mipmap_mode: vk::SamplerMipmapMode::NEAREST,
-            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
-            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
-            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
             mip_lod_bias: 0.0,
             anisotropy_enable: vk::FALSE,
             max_anisotropy: 0.0,
@@ -30,8 +39,17 @@ pub fn create_raw_sampler(ctx: &SharedContext) -> Result<Sampler> {
     )
 }
 
-/// Create a sampler with linear interpolation and anisotropic filtering enabled
+/// Create a sampler with linear interpolation and anisotropic filtering enabled, that repeats.
 pub fn create_linear_sampler(ctx: &SharedContext) -> Result<Sampler> {
+    create_linear_sampler_with(ctx, vk::SamplerAddressMode::REPEAT)
+}
+
+/// Create a sampler with linear interpolation and anisotropic filtering enabled, using
+/// `address_mode` on all axes.
+pub fn create_linear_sampler_with(
+    ctx: &SharedContext,
+    address_mode: vk::SamplerAddressMode,
+) -> Result<Sampler> {
     Sampler::new(
         ctx.device.clone(),
         vk::SamplerCreateInfo {
@@ -41,9 +59,9 @@ pub fn create_linear_sampler(ctx: &SharedContext) -> Result<Sampler> {
             mag_filter: vk::Filter::LINEAR,
             min_filter: vk::Filter::LINEAR,
             mipmap_mode: vk::SamplerMipmapMode::NEAREST,
-            address_mode_u: vk::SamplerAddressMode::REPEAT,
-            address_mode_v: vk::SamplerAddressMode::REPEAT,
-            address_mode_w: vk::SamplerAddressMode::REPEAT,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
             mip_lod_bias: 0.0,
             anisotropy_enable: vk::TRUE,
             max_anisotropy: 8.0,
@@ -1,13 +1,25 @@
+use std::f32::consts::FRAC_PI_2;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
 use anyhow::Result;
+use assets::SplatMap;
 use brush::brushes::*;
 use brush::height::WeightFunction;
-use brush::{BeginStrokeEvent, Brush, BrushSettings, BrushType, EndStrokeEvent};
+use brush::presets::{BrushPreset, BrushPresets};
+use brush::set_value::{BrushValue, ValueKind};
+use brush::{
+    BeginStrokeEvent, Brush, BrushSettings, BrushType, EndStrokeEvent, HeightLayer,
+    PickBrushValueEvent, PickedBrushValue,
+};
 use egui::{Checkbox, Context, Frame, PointerButton, Response, Slider, Ui};
+use error::{MessageEvent, MessageLevel};
 use events::DragWorldView;
 use inject::DI;
-use input::{ButtonState, InputState, Key, MousePosition};
+use input::{actions, ButtonState, InputState, MousePosition, ScrollInfo};
 use scheduler::EventBus;
 
+use crate::editor::prefs::{BrushScrollModifiers, EditorPrefs, ScrollModifier, EDITOR_PREFS_FILE};
 use crate::editor::{BrushDecalInfo, WorldOverlayInfo};
 use crate::widgets::aligned_label::aligned_label_with;
 use crate::widgets::toolbar::Toolbar;
@@ -17,6 +29,87 @@ pub struct BrushWidget {
     pub bus: EventBus<DI>,
     pub settings: BrushSettings,
     pub active_brush: Option<BrushType>,
+    pub presets: BrushPresets,
+    /// Name used when saving the current brush as a preset.
+    pub preset_name: String,
+    /// Messages to show to the user. Since the editor is the sink for [`MessageEvent`],
+    /// these cannot be published while the editor is being drawn.
+    pub messages: Vec<MessageEvent>,
+    /// Whether the cursor was over the world view during the last frame.
+    pub hovered: bool,
+    /// If set, the next click on the world view picks a value for the active brush with the
+    /// eyedropper instead of painting. Holding Alt while clicking does the same.
+    pub eyedropper: bool,
+}
+
+/// File brush presets are saved to and loaded from.
+pub const BRUSH_PRESETS_FILE: &str = "data/brush_presets.json";
+
+/// Range the brush radius can be set to, in meters.
+pub const RADIUS_RANGE: RangeInclusive<f32> = 1.0..=128.0;
+/// Range the brush weight can be set to.
+pub const WEIGHT_RANGE: RangeInclusive<f32> = 0.01..=5.0;
+/// Range of the stamp spacing along strokes, as a fraction of the radius.
+pub const SPACING_RANGE: RangeInclusive<f32> = 0.05..=1.0;
+/// Factor a brush setting is scaled by for each scroll step.
+const SCROLL_FACTOR: f32 = 1.1;
+
+/// Scale a brush setting by one scroll step in the direction of `delta`,
+/// clamped to `range`.
+fn scroll_value(value: f32, delta: f32, range: RangeInclusive<f32>) -> f32 {
+    (value * SCROLL_FACTOR.powf(delta.signum())).clamp(*range.start(), *range.end())
+}
+
+/// Lets the user pick the image of a stamp brush from a file.
+fn show_stamp_image(ui: &mut Ui, bus: &EventBus<DI>, brush: &mut Stamp) {
+    let path_id = ui.make_persistent_id("stamp_image_path");
+    let mut path = ui.data_mut(|data| data.get_temp_mut_or_default::<String>(path_id).clone());
+    aligned_label_with(ui, "Image", |ui| {
+        if ui
+            .add_enabled(!path.trim().is_empty(), egui::Button::new("Load"))
+            .on_hover_text("Grayscale image, black leaves the terrain untouched")
+            .clicked()
+        {
+            brush.image = Some(Stamp::load_image(bus, PathBuf::from(path.trim())));
+        }
+        ui.text_edit_singleline(&mut path);
+    });
+    ui.data_mut(|data| data.insert_temp(path_id, path));
+    if brush.image.is_none() {
+        ui.label("Load an image to use the stamp brush.");
+    }
+}
+
+/// Show a dropdown to pick the falloff curve of a brush, and sliders for its parameters.
+fn show_weight_function(ui: &mut Ui, weight_fn: &mut WeightFunction) {
+    aligned_label_with(ui, "Weight function", |ui| {
+        egui::ComboBox::from_id_source("brush_weight_fn")
+            .selected_text(format!("{weight_fn}"))
+            .show_ui(ui, |ui| {
+                for curve in WeightFunction::ALL {
+                    let selected = weight_fn.shader_value() == curve.shader_value();
+                    // Keep the parameter when the curve does not change
+                    if ui.selectable_label(selected, format!("{curve}")).clicked() && !selected {
+                        *weight_fn = curve;
+                    }
+                }
+            });
+    });
+    // Display options for each weight function separately
+    match weight_fn {
+        WeightFunction::Gaussian(stddev) => {
+            aligned_label_with(ui, "Standard deviation", |ui| {
+                ui.add(Slider::new(stddev, 0.0001f32..=0.40f32));
+            });
+        }
+        WeightFunction::Linear(hardness) | WeightFunction::Smoothstep(hardness) => {
+            aligned_label_with(ui, "Hardness", |ui| {
+                ui.add(Slider::new(hardness, 0.0f32..=0.95f32))
+                    .on_hover_text("Fraction of the radius that gets the full strength");
+            });
+        }
+        WeightFunction::Constant => {}
+    }
 }
 
 impl BrushWidget {
@@ -27,12 +120,144 @@ impl BrushWidget {
                 self.bus.publish(BeginStrokeEvent {
                     settings: self.settings,
                     brush: *brush,
+                    time: None,
                 })?;
             }
         }
         Ok(())
     }
 
+    fn apply_preset(&mut self, name: &str) {
+        if let Some(preset) = self.presets.get(name) {
+            self.settings = preset.settings;
+            self.active_brush = Some(preset.brush);
+            self.preset_name = preset.name.clone();
+        }
+    }
+
+    fn save_preset(&mut self) {
+        let Some(brush) = self.active_brush else { return };
+        let preset = BrushPreset {
+            name: self.preset_name.trim().to_owned(),
+            settings: self.settings,
+            brush,
+        };
+        if let Err(e) = preset.validate() {
+            self.messages.push(
+                MessageEvent::new(MessageLevel::Error, format!("Could not save brush preset: {e}"))
+                    .with_source("brush"),
+            );
+            return;
+        }
+        self.presets.insert(preset);
+        let message = match self.presets.save(BRUSH_PRESETS_FILE) {
+            Ok(_) => MessageEvent::new(
+                MessageLevel::Success,
+                format!("Saved brush preset {:?}", self.preset_name),
+            ),
+            Err(e) => {
+                MessageEvent::new(MessageLevel::Error, format!("Could not save brush presets: {e}"))
+            }
+        };
+        self.messages.push(message.with_source("brush"));
+    }
+
+    fn show_presets(&mut self, ui: &mut Ui) {
+        aligned_label_with(ui, "Preset", |ui| {
+            let mut selected = None;
+            egui::ComboBox::from_id_source("brush_preset")
+                .selected_text(self.preset_name.as_str())
+                .show_ui(ui, |ui| {
+                    for preset in &self.presets.presets {
+                        if ui
+                            .selectable_label(preset.name == self.preset_name, &preset.name)
+                            .clicked()
+                        {
+                            selected = Some(preset.name.clone());
+                        }
+                    }
+                });
+            if let Some(name) = selected {
+                self.apply_preset(&name);
+            }
+        });
+        aligned_label_with(ui, "Save as", |ui| {
+            let can_save = self.active_brush.is_some() && !self.preset_name.trim().is_empty();
+            if ui
+                .add_enabled(can_save, egui::Button::new("Save"))
+                .clicked()
+            {
+                self.save_preset();
+            }
+            ui.text_edit_singleline(&mut self.preset_name);
+        });
+    }
+
+    fn show_scroll_modifiers(&mut self, ui: &mut Ui, prefs: &mut EditorPrefs) {
+        let old = prefs.brush_scroll;
+        let modifier_combo = |ui: &mut Ui, id: &str, value: &mut ScrollModifier| {
+            egui::ComboBox::from_id_source(id)
+                .selected_text(format!("{value:?} + scroll"))
+                .show_ui(ui, |ui| {
+                    for modifier in ScrollModifier::ALL {
+                        ui.selectable_value(value, modifier, format!("{modifier:?} + scroll"));
+                    }
+                });
+        };
+        aligned_label_with(ui, "Radius shortcut", |ui| {
+            modifier_combo(ui, "brush_scroll_radius", &mut prefs.brush_scroll.radius);
+        });
+        aligned_label_with(ui, "Strength shortcut", |ui| {
+            modifier_combo(ui, "brush_scroll_weight", &mut prefs.brush_scroll.weight);
+        });
+        if prefs.brush_scroll != old {
+            if let Err(e) = prefs.save(EDITOR_PREFS_FILE) {
+                self.messages.push(
+                    MessageEvent::new(
+                        MessageLevel::Error,
+                        format!("Could not save editor preferences: {e}"),
+                    )
+                    .with_source("brush"),
+                );
+            }
+        }
+    }
+
+    /// Kind of value the eyedropper picks for the active brush, if it can use one.
+    fn eyedropper_kind(&self) -> Option<ValueKind> {
+        match self.active_brush? {
+            BrushType::SetValue(brush) => Some(brush.value.kind()),
+            BrushType::Color(_) => Some(ValueKind::Color),
+            BrushType::Flatten(brush) if brush.target_height.is_some() => Some(ValueKind::Height),
+            _ => None,
+        }
+    }
+
+    fn show_eyedropper(&mut self, ui: &mut Ui) {
+        aligned_label_with(ui, "Eyedropper", |ui| {
+            ui.toggle_value(&mut self.eyedropper, "Pick")
+                .on_hover_text("Click the terrain to pick a value, or hold Alt and click.");
+        });
+    }
+
+    /// Apply the last value picked with the eyedropper to the active brush.
+    fn apply_picked_value(&mut self) {
+        let value = {
+            let di = self.bus.data().read().unwrap();
+            let mut picked = di.write_sync::<PickedBrushValue>().unwrap();
+            picked.value.take()
+        };
+        let Some(value) = value else { return };
+        match (&mut self.active_brush, value) {
+            (Some(BrushType::SetValue(brush)), value) => brush.value = value,
+            (Some(BrushType::Color(brush)), BrushValue::Color(color)) => brush.color = color,
+            (Some(BrushType::Flatten(brush)), BrushValue::Height(height)) => {
+                brush.target_height = Some(height)
+            }
+            _ => {}
+        }
+    }
+
     fn end_stroke(&self) -> Result<()> {
         {
             let di = self.bus.data().read().unwrap();
@@ -45,7 +270,8 @@ impl BrushWidget {
 }
 
 impl BrushWidget {
-    pub fn show(&mut self, ctx: &Context) -> Result<()> {
+    pub fn show(&mut self, ctx: &Context, prefs: &mut EditorPrefs) -> Result<()> {
+        self.apply_picked_value();
         egui::Window::new("Brush toolbar")
             .movable(true)
             .resizable(true)
@@ -65,6 +291,12 @@ impl BrushWidget {
                                 .size(toolbar_button_size)
                                 .tool("↕", "Height brush", SmoothHeight::default())
                                 .tool("↔", "Equalizer brush", Equalize::default())
+                                .tool("🖌", "Color brush", Color::default())
+                                .tool("≈", "Detail normal brush", DetailNormal::default())
+                                .tool("▦", "Splat brush", Splat::default())
+                                .tool("=", "Set value brush", SetValue::default())
+                                .tool("▁", "Flatten brush", Flatten::default())
+                                .tool("⛰", "Stamp brush", Stamp::default())
                                 .show(ui);
                         });
                     });
@@ -80,19 +312,45 @@ impl BrushWidget {
                         });
                         ui.separator();
                     };
+                    heading_separator(ui, "Presets");
+                    Frame::central_panel(ui.style()).show(ui, |ui| {
+                        self.show_presets(ui);
+                    });
+                    ui.separator();
                     heading_separator(ui, "Global settings");
                     Frame::central_panel(ui.style()).show(ui, |ui| {
                         aligned_label_with(ui, "Radius", |ui| {
-                            ui.add(Slider::new(&mut self.settings.radius, 1.0..=128.0));
+                            ui.add(Slider::new(&mut self.settings.radius.0, RADIUS_RANGE));
                         });
                         aligned_label_with(ui, "Strength", |ui| {
-                            ui.add(Slider::new(&mut self.settings.weight, 0.01..=5.0));
+                            ui.add(Slider::new(&mut self.settings.weight, WEIGHT_RANGE));
                         });
                         aligned_label_with(ui, "Use when still", |ui| {
                             let mut inverted = !self.settings.once;
                             ui.add(Checkbox::without_text(&mut inverted));
                             self.settings.once = !inverted;
                         });
+                        aligned_label_with(ui, "Spacing", |ui| {
+                            ui.add(Slider::new(&mut self.settings.spacing, SPACING_RANGE))
+                                .on_hover_text(
+                                    "Distance between stamps of fast strokes, as a fraction of \
+                                     the radius",
+                                );
+                        });
+                        aligned_label_with(ui, "Layer", |ui| {
+                            egui::ComboBox::from_id_source("brush_layer")
+                                .selected_text(format!("{}", self.settings.layer))
+                                .show_ui(ui, |ui| {
+                                    for layer in [HeightLayer::Base, HeightLayer::Detail] {
+                                        ui.selectable_value(
+                                            &mut self.settings.layer,
+                                            layer,
+                                            format!("{layer}"),
+                                        );
+                                    }
+                                });
+                        });
+                        self.show_scroll_modifiers(ui, prefs);
                     });
                     ui.separator();
                     heading_separator(ui, "Brush settings");
@@ -103,30 +361,121 @@ impl BrushWidget {
                                 // For this reason, I've added an additional type hint in each case to make using this easier.
                                 BrushType::SmoothHeight(brush) => {
                                     let brush: &mut SmoothHeight = brush;
-                                    aligned_label_with(ui, "Weight function", |ui| {
-                                        egui::ComboBox::from_id_source("brush_weight_fn")
-                                            .selected_text(format!("{}", brush.weight_fn))
+                                    show_weight_function(ui, &mut brush.weight_fn);
+                                }
+                                BrushType::Equalize(brush) => {}
+                                BrushType::Flatten(brush) => {
+                                    let brush: &mut Flatten = brush;
+                                    aligned_label_with(ui, "Fixed height", |ui| {
+                                        let mut fixed = brush.target_height.is_some();
+                                        ui.add(Checkbox::without_text(&mut fixed)).on_hover_text(
+                                            "Otherwise the height under the cursor at the \
+                                             start of each stroke is used",
+                                        );
+                                        match (fixed, brush.target_height) {
+                                            (true, None) => brush.target_height = Some(0.0),
+                                            (false, Some(_)) => brush.target_height = None,
+                                            _ => {}
+                                        }
+                                    });
+                                    if let Some(height) = &mut brush.target_height {
+                                        aligned_label_with(ui, "Target height", |ui| {
+                                            ui.add(egui::DragValue::new(height).speed(0.001));
+                                        });
+                                    }
+                                    show_weight_function(ui, &mut brush.weight_fn);
+                                }
+                                BrushType::Stamp(brush) => {
+                                    let brush: &mut Stamp = brush;
+                                    show_stamp_image(ui, &self.bus, brush);
+                                    aligned_label_with(ui, "Rotation", |ui| {
+                                        ui.drag_angle(&mut brush.rotation);
+                                    });
+                                    aligned_label_with(ui, "Scale", |ui| {
+                                        ui.add(Slider::new(&mut brush.scale, 0.1..=4.0));
+                                    });
+                                }
+                                BrushType::Color(brush) => {
+                                    let brush: &mut Color = brush;
+                                    aligned_label_with(ui, "Color", |ui| {
+                                        // The picker works in sRGB, while the brush paints in
+                                        // linear space.
+                                        let mut srgb = brush.to_srgb();
+                                        if ui.color_edit_button_srgb(&mut srgb).changed() {
+                                            brush.color = Color::from_srgb(srgb).color;
+                                        }
+                                    });
+                                    aligned_label_with(ui, "Max slope", |ui| {
+                                        ui.drag_angle(&mut brush.max_slope);
+                                        brush.max_slope = brush.max_slope.clamp(0.0, FRAC_PI_2);
+                                    });
+                                }
+                                BrushType::DetailNormal(brush) => {
+                                    let brush: &mut DetailNormal = brush;
+                                    aligned_label_with(ui, "Bump strength", |ui| {
+                                        ui.add(Slider::new(&mut brush.strength, 0.0..=8.0));
+                                    });
+                                }
+                                BrushType::Splat(brush) => {
+                                    let brush: &mut Splat = brush;
+                                    aligned_label_with(ui, "Material", |ui| {
+                                        egui::ComboBox::from_id_source("splat_channel")
+                                            .selected_text(SplatMap::channel_name(brush.channel))
+                                            .show_ui(ui, |ui| {
+                                                for channel in 0..SplatMap::CHANNELS {
+                                                    ui.selectable_value(
+                                                        &mut brush.channel,
+                                                        channel,
+                                                        SplatMap::channel_name(channel),
+                                                    );
+                                                }
+                                            });
+                                    });
+                                }
+                                BrushType::SetValue(brush) => {
+                                    let brush: &mut SetValue = brush;
+                                    aligned_label_with(ui, "Value", |ui| {
+                                        let mut kind = brush.value.kind();
+                                        egui::ComboBox::from_id_source("brush_value_kind")
+                                            .selected_text(format!("{kind}"))
                                             .show_ui(ui, |ui| {
-                                                ui.selectable_value(
-                                                    &mut brush.weight_fn,
-                                                    WeightFunction::Gaussian(0.3),
-                                                    "Gaussian",
-                                                );
+                                                for option in ValueKind::ALL {
+                                                    ui.selectable_value(
+                                                        &mut kind,
+                                                        option,
+                                                        format!("{option}"),
+                                                    );
+                                                }
                                             });
+                                        if kind != brush.value.kind() {
+                                            brush.value = BrushValue::default_of(kind);
+                                        }
                                     });
-                                    // Display options for each weight function separately
-                                    match &mut brush.weight_fn {
-                                        WeightFunction::Gaussian(stddev) => {
-                                            aligned_label_with(ui, "Standard deviation", |ui| {
-                                                ui.add(Slider::new(stddev, 0.0001f32..=0.40f32));
+                                    match &mut brush.value {
+                                        BrushValue::Height(height) => {
+                                            aligned_label_with(ui, "Height", |ui| {
+                                                ui.add(egui::DragValue::new(height).speed(0.001));
+                                            });
+                                        }
+                                        BrushValue::Color(color) => {
+                                            aligned_label_with(ui, "Color", |ui| {
+                                                let mut srgb = Color {
+                                                    color: *color,
+                                                    ..Color::default()
+                                                }
+                                                .to_srgb();
+                                                if ui.color_edit_button_srgb(&mut srgb).changed() {
+                                                    *color = Color::from_srgb(srgb).color;
+                                                }
                                             });
                                         }
                                     }
                                 }
-                                BrushType::Equalize(brush) => {}
-                                BrushType::Color(brush) => {}
                             }
                         }
+                        if self.eyedropper_kind().is_some() {
+                            self.show_eyedropper(ui);
+                        }
                     });
                 });
             });
@@ -146,13 +495,45 @@ impl BrushWidget {
         Ok(())
     }
 
+    /// Adjust the brush radius or weight when scrolling over the world view with one of the
+    /// configured modifiers held. The decal is updated from the new settings on the next frame.
+    pub fn handle_scroll(
+        &mut self,
+        scroll: &ScrollInfo,
+        input: &InputState,
+        modifiers: BrushScrollModifiers,
+    ) {
+        if !self.hovered || self.active_brush.is_none() || scroll.delta_y == 0.0 {
+            return;
+        }
+        let delta = scroll.delta_y as f32;
+        if input.get_key(modifiers.radius.key()) == ButtonState::Pressed {
+            self.settings.radius.0 = scroll_value(self.settings.radius.0, delta, RADIUS_RANGE);
+        } else if input.get_key(modifiers.weight.key()) == ButtonState::Pressed {
+            self.settings.weight = scroll_value(self.settings.weight, delta, WEIGHT_RANGE);
+        }
+    }
+
     pub fn control(&mut self, response: &Response) -> Result<()> {
+        self.hovered = response.hovered();
         let di = self.bus.data().read().unwrap();
         let input = di.read_sync::<InputState>().unwrap();
 
-        if input.get_key(Key::Escape) == ButtonState::Pressed {
+        if input.is_action_active(actions::BRUSH_CANCEL) {
             self.active_brush = None;
         }
+        // Picking a value replaces the stroke this click would have started
+        let picking = self.eyedropper || input.is_action_active(actions::BRUSH_PICK);
+        let clicked = response.clicked_by(PointerButton::Primary)
+            || response.drag_started_by(PointerButton::Primary);
+        if let Some(kind) = self.eyedropper_kind().filter(|_| picking && clicked) {
+            self.eyedropper = false;
+            self.bus.publish(PickBrushValueEvent {
+                kind,
+                layer: self.settings.layer,
+            })?;
+            return Ok(());
+        }
         // If a drag was started, begin the brush stroke
         if response.drag_started_by(PointerButton::Primary) {
             self.settings.invert = false;
@@ -188,3 +569,22 @@ impl BrushWidget {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_value_steps_in_scroll_direction() {
+        assert!(scroll_value(32.0, 1.0, RADIUS_RANGE) > 32.0);
+        assert!(scroll_value(32.0, -1.0, RADIUS_RANGE) < 32.0);
+        // The size of a scroll step does not matter, only its direction
+        assert_eq!(scroll_value(32.0, 0.2, RADIUS_RANGE), scroll_value(32.0, 3.0, RADIUS_RANGE));
+    }
+
+    #[test]
+    fn scroll_value_is_clamped() {
+        assert_eq!(scroll_value(128.0, 1.0, RADIUS_RANGE), 128.0);
+        assert_eq!(scroll_value(0.01, -1.0, WEIGHT_RANGE), 0.01);
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use camera::{CameraBookmark, DeleteBookmarkEvent, RecallBookmarkEvent, SaveBookmarkEvent};
+use inject::DI;
+use scheduler::EventBus;
+use world::World;
+
+/// Time the camera takes to fly to a bookmark.
+const TRANSITION_DURATION: Duration = Duration::from_millis(750);
+
+/// Number keys that jump to the first nine bookmarks.
+const SHORTCUT_KEYS: [egui::Key; 9] = [
+    egui::Key::Num1,
+    egui::Key::Num2,
+    egui::Key::Num3,
+    egui::Key::Num4,
+    egui::Key::Num5,
+    egui::Key::Num6,
+    egui::Key::Num7,
+    egui::Key::Num8,
+    egui::Key::Num9,
+];
+
+/// Change to the camera bookmarks requested in the bookmarks window. The bookmarks are part of
+/// the world, which is locked while the editor is shown, so requests are only published once
+/// the lock is released.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookmarkRequest {
+    /// Store the current camera pose under a name.
+    Save(String),
+    /// Fly to the bookmark at an index.
+    Recall(usize),
+    /// Remove the bookmark at an index.
+    Delete(usize),
+}
+
+impl BookmarkRequest {
+    pub fn publish(self, bus: &EventBus<DI>) -> Result<()> {
+        match self {
+            BookmarkRequest::Save(name) => bus.publish(SaveBookmarkEvent {
+                name,
+            })?,
+            BookmarkRequest::Recall(index) => bus.publish(RecallBookmarkEvent {
+                index,
+                transition: Some(TRANSITION_DURATION),
+            })?,
+            BookmarkRequest::Delete(index) => bus.publish(DeleteBookmarkEvent {
+                index,
+            })?,
+        };
+        Ok(())
+    }
+}
+
+/// Move bookmarks that older versions kept in the editor preferences into the world.
+/// Bookmarks the world already has a bookmark with the same name for are dropped.
+pub fn migrate(world: &mut World, bookmarks: Vec<CameraBookmark>) {
+    for bookmark in bookmarks {
+        if world
+            .camera_bookmarks
+            .iter()
+            .all(|existing| existing.name != bookmark.name)
+        {
+            world.save_camera_bookmark(bookmark.name, bookmark.pose);
+        }
+    }
+}
+
+fn handle_shortcuts(context: &egui::Context, world: &World, requests: &mut Vec<BookmarkRequest>) {
+    if context.wants_keyboard_input() {
+        return;
+    }
+    let count = world.camera_bookmarks.len().min(SHORTCUT_KEYS.len());
+    for (index, key) in SHORTCUT_KEYS.iter().enumerate().take(count) {
+        if context.input(|input| input.key_pressed(*key)) {
+            requests.push(BookmarkRequest::Recall(index));
+        }
+    }
+}
+
+/// Show the camera bookmarks. Bookmarks are part of the world, so they are saved with the scene.
+/// Changes are added to `requests`, see [`BookmarkRequest`].
+pub fn show(context: &egui::Context, world: &World, requests: &mut Vec<BookmarkRequest>) {
+    handle_shortcuts(context, world, requests);
+    egui::Window::new("Camera bookmarks")
+        .resizable(true)
+        .movable(true)
+        .show(context, |ui| {
+            let name_id = ui.make_persistent_id("camera_bookmark_name");
+            let mut name =
+                ui.data_mut(|data| data.get_temp_mut_or_default::<String>(name_id).clone());
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut name);
+                if ui
+                    .add_enabled(!name.trim().is_empty(), egui::Button::new("Save"))
+                    .clicked()
+                {
+                    requests.push(BookmarkRequest::Save(name.trim().to_owned()));
+                    name.clear();
+                }
+            });
+            ui.data_mut(|data| data.insert_temp(name_id, name));
+
+            ui.separator();
+            for (index, bookmark) in world.camera_bookmarks.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let label = match SHORTCUT_KEYS.get(index) {
+                        Some(_) => format!("[{}] {}", index + 1, bookmark.name),
+                        None => bookmark.name.clone(),
+                    };
+                    ui.label(label);
+                    if ui.button("Jump").clicked() {
+                        requests.push(BookmarkRequest::Recall(index));
+                    }
+                    if ui.button("Delete").clicked() {
+                        requests.push(BookmarkRequest::Delete(index));
+                    }
+                });
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use camera::CameraPose;
+    use glam::Vec3;
+
+    use super::*;
+    use crate::editor::prefs::EditorPrefs;
+
+    fn bookmark(name: &str, x: f32) -> CameraBookmark {
+        CameraBookmark {
+            name: name.to_owned(),
+            pose: CameraPose {
+                position: Vec3::new(x, 0.0, 0.0),
+                rotation: Vec3::ZERO,
+                fov: 90.0,
+                ortho_height: 1000.0,
+            },
+        }
+    }
+
+    #[test]
+    fn bookmarks_from_old_preferences_are_moved_into_the_world() {
+        let old = serde_json::json!({
+            "averaging_window": 30,
+            "camera_bookmarks": [bookmark("a", 1.0), bookmark("b", 2.0)],
+        });
+        let mut prefs: EditorPrefs = serde_json::from_value(old).unwrap();
+        let mut world = World::new();
+        world.save_camera_bookmark("b".into(), bookmark("b", 3.0).pose);
+        migrate(&mut world, std::mem::take(&mut prefs.camera_bookmarks));
+        assert_eq!(world.camera_bookmarks, vec![bookmark("b", 3.0), bookmark("a", 1.0)]);
+        // Once migrated, the bookmarks are no longer written to the preferences
+        let saved = serde_json::to_string(&prefs).unwrap();
+        assert!(!saved.contains("camera_bookmarks"));
+    }
+}
@@ -1,13 +1,53 @@
 use anyhow::Result;
-use camera::EnableCameraEvent;
+use camera::{CameraState, EnableCameraEvent};
+use egui::{Checkbox, Slider};
 use inject::DI;
 use scheduler::EventBus;
 
-/// Enable the camera controls when this widget is hovered
+use crate::widgets::aligned_label::aligned_label_with;
+
+/// Enable the camera controls when this widget is hovered, and no other widget is
+/// taking keyboard input.
 pub fn enable_camera_over(response: &egui::Response, bus: &EventBus<DI>) -> Result<()> {
     let hover = response.hovered();
+    let typing = response.ctx.wants_keyboard_input();
     bus.publish(EnableCameraEvent {
-        enabled: hover,
+        enabled: hover && !typing,
     })?;
     Ok(())
 }
+
+/// Shows the settings of the camera controller.
+/// # DI Access
+/// - Write [`CameraState`]
+pub fn show(context: &egui::Context, bus: &EventBus<DI>) {
+    let di = bus.data().read().unwrap();
+    let mut camera = di.write_sync::<CameraState>().unwrap();
+    let mut smoothing = camera.smoothing();
+    egui::Window::new("Camera")
+        .resizable(true)
+        .movable(true)
+        .show(context, |ui| {
+            aligned_label_with(ui, "Smooth movement", |ui| {
+                ui.add(Checkbox::without_text(&mut smoothing.enabled))
+                    .on_hover_text(
+                        "Accelerate and slow down the camera instead of moving instantly",
+                    );
+            });
+            aligned_label_with(ui, "Acceleration", |ui| {
+                ui.add_enabled(
+                    smoothing.enabled,
+                    Slider::new(&mut smoothing.acceleration, 0.5..=32.0).logarithmic(true),
+                );
+            });
+            aligned_label_with(ui, "Damping", |ui| {
+                ui.add_enabled(
+                    smoothing.enabled,
+                    Slider::new(&mut smoothing.damping, 0.5..=32.0).logarithmic(true),
+                );
+            });
+        });
+    if smoothing != camera.smoothing() {
+        camera.set_smoothing(smoothing);
+    }
+}
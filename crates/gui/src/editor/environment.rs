@@ -8,6 +8,10 @@ pub fn show(context: &egui::Context, world: &mut World) {
         .movable(true)
         .show(context, |ui| {
             Drag::new("Sun direction", &mut world.sun_direction).show(ui);
+            Drag::new("Sun speed", &mut world.sun_speed)
+                .suffix(" rad/s")
+                .speed(0.01)
+                .show(ui);
             egui::CollapsingHeader::new("Atmosphere").show(ui, |ui| {
                 Drag::new("Planet radius", &mut world.atmosphere.planet_radius)
                     .suffix(" km")
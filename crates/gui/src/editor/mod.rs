@@ -1,30 +1,43 @@
 use std::time::Duration;
 
 use anyhow::Result;
-use brush::BrushSettings;
+use assets::WorldRadius;
+use brush::presets::BrushPresets;
+use brush::{BrushSettings, HeightLayer, RedoEvent, UndoEvent};
 use derivative::Derivative;
 use egui_notify::{ToastLevel, Toasts};
 use error::{MessageEvent, MessageLevel};
 use events::Tick;
+use hot_reload::ReloadAllShadersEvent;
 use inject::DI;
+use input::{InputEvent, InputState};
+use log::{error, info};
 use scheduler::{EventBus, EventContext, StoredSystem, System};
 use util::SafeUnwrap;
 use world::World;
 
-use crate::editor::brushes::BrushWidget;
+use crate::editor::brushes::{BrushWidget, BRUSH_PRESETS_FILE};
+use crate::editor::camera_bookmarks::BookmarkRequest;
+use crate::editor::prefs::{EditorPrefs, EDITOR_PREFS_FILE};
 
 pub mod brushes;
+pub mod camera_bookmarks;
 pub mod camera_controller;
 pub mod environment;
 pub mod performance;
+pub mod prefs;
+pub mod props;
 pub mod render_options;
+pub mod scene;
+pub mod target_viewer;
 pub mod terrain_options;
+pub mod time_control;
 pub mod world_view;
 
 #[derive(Debug)]
 pub struct BrushDecalInfo {
-    /// Radius of the brush decal, in texels on the heightmap.
-    pub radius: f32,
+    /// Radius of the brush decal in world space.
+    pub radius: WorldRadius,
     /// Extra data that is passed to the shader if present.
     /// Note that if this is present, the data MUST be used in the shader.
     pub data: Option<[f32; 4]>,
@@ -47,6 +60,11 @@ pub struct Editor {
     notify: Toasts,
     bus: EventBus<DI>,
     brush_widget: BrushWidget,
+    prefs: EditorPrefs,
+    /// Amount of time a single step advances by while time is paused.
+    time_step: Duration,
+    /// Bookmark changes made while the editor was shown, published once the world is unlocked.
+    bookmark_requests: Vec<BookmarkRequest>,
 }
 
 impl Editor {
@@ -59,32 +77,111 @@ impl Editor {
             brush_widget: BrushWidget {
                 bus,
                 settings: BrushSettings {
-                    radius: 32.0,
+                    radius: WorldRadius(32.0),
                     weight: 1.0,
                     invert: false,
                     once: false,
+                    layer: HeightLayer::Base,
+                    spacing: 0.25,
                 },
                 active_brush: None,
+                presets: BrushPresets::load_or_builtin(BRUSH_PRESETS_FILE).unwrap_or_else(|e| {
+                    error!("Could not load brush presets: {e}");
+                    BrushPresets::builtin()
+                }),
+                preset_name: String::new(),
+                messages: vec![],
+                hovered: false,
+                eyedropper: false,
             },
+            prefs: EditorPrefs::load_or_default(EDITOR_PREFS_FILE).unwrap_or_else(|e| {
+                error!("Could not load editor preferences: {e}");
+                EditorPrefs::default()
+            }),
+            time_step: Duration::from_secs_f32(1.0 / 60.0),
+            bookmark_requests: vec![],
+        }
+    }
+
+    /// Move bookmarks saved in the editor preferences by older versions into the world.
+    fn migrate_bookmarks(&mut self, world: &mut World) {
+        if self.prefs.camera_bookmarks.is_empty() {
+            return;
+        }
+        let bookmarks = std::mem::take(&mut self.prefs.camera_bookmarks);
+        info!(
+            "Moving {} camera bookmarks from the editor preferences into the world",
+            bookmarks.len()
+        );
+        camera_bookmarks::migrate(world, bookmarks);
+        if let Err(e) = self.prefs.save(EDITOR_PREFS_FILE) {
+            error!("Could not save editor preferences: {e}");
         }
     }
 
     pub fn show(&mut self, world: &mut World) {
+        if let Some(message) = scene::show_menu(&self.context, &self.bus, world) {
+            self.show_message(message);
+        }
         egui::CentralPanel::default().show(&self.context, |ui| {
             ui.heading("Editor");
 
             world_view::show(&self.context, &self.bus, &mut self.brush_widget);
             environment::show(&self.context, world);
-            render_options::show(&self.context, world);
-            terrain_options::show(&self.context, &self.bus, world);
-            performance::show(&self.context, &self.bus);
-            self.brush_widget.show(&self.context).safe_unwrap();
+            props::show(&self.context, &self.bus, world);
+            render_options::show(&self.context, &self.bus, world);
+            terrain_options::show(&self.context, &self.bus, world, &mut self.prefs);
+            performance::show(&self.context, &self.bus, &mut self.prefs);
+            target_viewer::show(&self.context, &self.bus);
+            time_control::show(&self.context, &self.bus, &mut self.time_step);
+            camera_controller::show(&self.context, &self.bus);
+            camera_bookmarks::show(&self.context, world, &mut self.bookmark_requests);
+            self.brush_widget
+                .show(&self.context, &mut self.prefs)
+                .safe_unwrap();
         });
 
+        for message in std::mem::take(&mut self.brush_widget.messages) {
+            self.show_message(message);
+        }
+
+        if self.context.input(|input| input.key_pressed(egui::Key::F5)) {
+            self.bus.publish(ReloadAllShadersEvent).safe_unwrap();
+        }
+
+        // Ctrl+Z undoes the last brush stroke, Ctrl+Shift+Z or Ctrl+Y redoes it
+        let (undo, redo) = self.context.input(|input| {
+            let command = input.modifiers.command;
+            let z = command && input.key_pressed(egui::Key::Z);
+            let y = command && input.key_pressed(egui::Key::Y);
+            (z && !input.modifiers.shift, y || (z && input.modifiers.shift))
+        });
+        if undo {
+            self.bus.publish(UndoEvent).safe_unwrap();
+        } else if redo {
+            self.bus.publish(RedoEvent).safe_unwrap();
+        }
+
         // Show all notifications
         self.notify.show(&self.context);
         self.context.request_repaint();
     }
+
+    fn show_message(&mut self, event: MessageEvent) {
+        // Toasts have no room for an expander, so the full details end up in the log instead.
+        if let Some(details) = &event.details {
+            info!("{}: {}", event.source.unwrap_or("message"), details);
+        }
+        let message = match event.source {
+            None => event.message,
+            Some(source) => format!("[{source}] {}", event.message),
+        };
+        self.notify
+            .basic(message)
+            .set_level(to_toast_level(event.level))
+            .set_closable(true)
+            .set_duration(Some(Duration::from_secs(3)));
+    }
 }
 
 impl System<DI> for Editor {
@@ -92,6 +189,7 @@ impl System<DI> for Editor {
     where
         Self: Sized, {
         event_bus.subscribe(system, handle_editor_tick);
+        event_bus.subscribe(system, handle_input_event);
         event_bus.subscribe_sink(system, handle_error_sink);
     }
 }
@@ -103,9 +201,33 @@ fn handle_editor_tick(
     _event: &Tick,
     ctx: &mut EventContext<DI>,
 ) -> Result<()> {
-    let inject = ctx.read().unwrap();
-    let mut world = inject.write_sync::<World>().unwrap();
-    editor.show(&mut world);
+    {
+        let inject = ctx.read().unwrap();
+        let mut world = inject.write_sync::<World>().unwrap();
+        editor.migrate_bookmarks(&mut world);
+        editor.show(&mut world);
+    }
+    // Bookmark events write to the world, so they are published after releasing it
+    for request in std::mem::take(&mut editor.bookmark_requests) {
+        request.publish(ctx.bus()).safe_unwrap();
+    }
+    Ok(())
+}
+
+/// # DI Access
+/// - Read [`InputState`]
+fn handle_input_event(
+    editor: &mut Editor,
+    event: &InputEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    if let InputEvent::Scroll(scroll) = event {
+        let inject = ctx.read().unwrap();
+        let input = inject.read_sync::<InputState>().unwrap();
+        editor
+            .brush_widget
+            .handle_scroll(scroll, &input, editor.prefs.brush_scroll);
+    }
     Ok(())
 }
 
@@ -123,11 +245,6 @@ fn handle_error_sink(
     event: MessageEvent,
     _ctx: &mut EventContext<DI>,
 ) -> Result<()> {
-    editor
-        .notify
-        .basic(event.message)
-        .set_level(to_toast_level(event.level))
-        .set_closable(true)
-        .set_duration(Some(Duration::from_secs(3)));
+    editor.show_message(event);
     Ok(())
 }
@@ -1,10 +1,16 @@
 use std::time::Duration;
 
-use egui::Ui;
+use assets::storage::AssetStorage;
+use egui::{ProgressBar, Ui};
 use inject::DI;
+use log::error;
+use pass::{GpuWork, WorkQueue};
 use scheduler::EventBus;
-use statistics::RendererStatistics;
+use statistics::{DumpTimingsEvent, RendererStatistics};
+use time::Time;
+use util::SafeUnwrap;
 
+use crate::editor::prefs::{EditorPrefs, AVERAGING_WINDOWS, EDITOR_PREFS_FILE};
 use crate::widgets::aligned_label::aligned_label_with;
 
 fn show_duration(ui: &mut Ui, duration: &Duration) {
@@ -13,29 +19,139 @@ fn show_duration(ui: &mut Ui, duration: &Duration) {
     ui.label(format!("{:.2} ms", ms));
 }
 
-pub fn show(context: &egui::Context, bus: &EventBus<DI>) {
+fn show_averaging_window(ui: &mut Ui, prefs: &mut EditorPrefs) {
+    let mut changed = false;
+    aligned_label_with(ui, "averaging window", |ui| {
+        for frames in AVERAGING_WINDOWS {
+            changed |= ui
+                .selectable_value(&mut prefs.averaging_window, frames, format!("{frames}"))
+                .changed();
+        }
+    });
+    if changed {
+        if let Err(e) = prefs.save(EDITOR_PREFS_FILE) {
+            error!("Could not save editor preferences: {e}");
+        }
+    }
+}
+
+/// Shows how many assets of each type are still loading, loaded or failed.
+fn show_asset_status(ui: &mut Ui, assets: &AssetStorage) {
+    let mut counts = assets.all_status_counts().into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+    let pending: usize = counts.iter().map(|(_, (pending, _, _))| pending).sum();
+    let header = match pending {
+        0 => "Assets".to_owned(),
+        n => format!("Assets (loading {n})"),
+    };
+    // The header text changes with the pending count, so it cannot be used as the id
+    egui::CollapsingHeader::new(header)
+        .id_source("asset_status")
+        .show(ui, |ui| {
+            for (name, (pending, ready, failed)) in counts {
+                aligned_label_with(ui, name.as_str(), |ui| {
+                    ui.label(format!("{pending} pending, {ready} ready, {failed} failed"));
+                });
+            }
+        });
+}
+
+fn show_brush_queue(ui: &mut Ui, prefs: &mut EditorPrefs) {
+    let mut changed = false;
+    aligned_label_with(ui, "brush queue", |ui| {
+        for queue in WorkQueue::ALL {
+            let name = match queue {
+                WorkQueue::Graphics => "graphics",
+                WorkQueue::AsyncCompute => "async compute",
+            };
+            changed |= ui
+                .selectable_value(&mut prefs.brush_queue, queue, name)
+                .changed();
+        }
+    });
+    if changed {
+        if let Err(e) = prefs.save(EDITOR_PREFS_FILE) {
+            error!("Could not save editor preferences: {e}");
+        }
+    }
+}
+
+/// Shows how much brush and asset work is waiting to be submitted to the GPU.
+fn show_gpu_work(ui: &mut Ui, work: &GpuWork) {
+    aligned_label_with(ui, "pending gpu work", |ui| {
+        ui.label(format!("{} queued, {} submitted", work.pending(), work.submitted()));
+    });
+    if work.pending_async() > 0 {
+        aligned_label_with(ui, "async gpu work", |ui| {
+            ui.label(format!("{} in flight", work.pending_async()));
+        });
+    }
+}
+
+/// Shows the average GPU time of each pass, from the most to the least expensive. The bars
+/// show the share of each pass in the GPU time of the whole frame.
+fn show_pass_timings(ui: &mut Ui, stats: &RendererStatistics) {
+    let mut timings = stats.average_section_timings();
+    let Some(total) = timings.remove("all_render") else {
+        ui.label("Waiting for measurements");
+        return;
+    };
+    let mut timings = timings.into_iter().collect::<Vec<_>>();
+    timings.sort_by(|(_, lhs), (_, rhs)| rhs.total_cmp(lhs));
+    for (name, ms) in timings {
+        aligned_label_with(ui, name.as_str(), |ui| {
+            let fraction = (ms / total.max(f64::EPSILON)) as f32;
+            ui.add(ProgressBar::new(fraction).text(format!("{ms:.2} ms")));
+        });
+    }
+    ui.separator();
+    aligned_label_with(ui, "gpu time", |ui| {
+        ui.label(format!("{total:.2} ms"));
+    });
+}
+
+pub fn show(context: &egui::Context, bus: &EventBus<DI>, prefs: &mut EditorPrefs) {
     let di = bus.data().read().unwrap();
-    let stats = di.read_sync::<RendererStatistics>().unwrap();
+    let mut stats = di.write_sync::<RendererStatistics>().unwrap();
+    // The statistics are created after the editor, so the preference is applied every frame.
+    stats.set_averaging_window(prefs.averaging_window);
+    // The brush queue is applied every frame as well, so the saved preference is used on startup.
+    if let Err(e) = GpuWork::select_queue(bus, prefs.brush_queue) {
+        error!("Could not select the brush queue: {e}");
+    }
+    let mut dump_timings = false;
     egui::Window::new("Performance")
         .resizable(true)
         .movable(true)
         .show(context, |ui| {
             ui.collapsing("Pass timings", |ui| {
-                for (name, duration) in stats.section_timings() {
-                    if name != "all_render" {
-                        aligned_label_with(ui, name, |ui| {
-                            show_duration(ui, duration);
-                        });
-                    }
-                }
-                ui.separator();
-                let time = stats.section_timings().get("all_render").unwrap();
-                aligned_label_with(ui, "gpu time", |ui| {
-                    show_duration(ui, time);
-                });
+                show_pass_timings(ui, &stats);
             });
             aligned_label_with(ui, "frame time", |ui| {
                 show_duration(ui, &stats.average_frame_time());
             });
+            aligned_label_with(ui, "fps", |ui| {
+                let fps = di.read_sync::<Time>().unwrap().smoothed_fps();
+                ui.label(format!("{fps:.0}"));
+            });
+            aligned_label_with(ui, "1% low fps", |ui| {
+                ui.label(format!("{:.0}", stats.one_percent_low_fps()));
+            });
+            show_averaging_window(ui, prefs);
+            show_brush_queue(ui, prefs);
+            show_gpu_work(ui, &di.read_sync::<GpuWork>().unwrap());
+            show_asset_status(ui, di.get::<AssetStorage>().unwrap());
+            dump_timings = ui
+                .button("Export timings")
+                .on_hover_text("Write the recorded frame timings to timings.csv")
+                .clicked();
         });
+    // The statistics are read when handling the event, so the lock must be released first.
+    drop(stats);
+    if dump_timings {
+        bus.publish(DumpTimingsEvent {
+            path: "timings.csv".into(),
+        })
+        .safe_unwrap();
+    }
 }
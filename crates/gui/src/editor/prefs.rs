@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use anyhow::Result;
+use assets::TerrainSource;
+use camera::CameraBookmark;
+use input::Key;
+use pass::WorkQueue;
+use serde::{Deserialize, Serialize};
+
+/// File editor preferences are saved to and loaded from.
+pub const EDITOR_PREFS_FILE: &str = "data/editor_prefs.json";
+
+/// Frame counts offered for averaging the frame time in the performance panel.
+pub const AVERAGING_WINDOWS: [usize; 3] = [1, 30, 120];
+
+/// Maximum amount of terrains remembered in the recent terrains list.
+pub const MAX_RECENT_TERRAINS: usize = 8;
+
+/// Modifier key that can be held while scrolling to adjust a brush setting.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScrollModifier {
+    Control,
+    Alt,
+}
+
+impl ScrollModifier {
+    pub const ALL: [ScrollModifier; 2] = [ScrollModifier::Control, ScrollModifier::Alt];
+
+    pub fn key(self) -> Key {
+        match self {
+            ScrollModifier::Control => Key::Control,
+            ScrollModifier::Alt => Key::Alt,
+        }
+    }
+}
+
+/// Modifiers that change the brush radius and weight when scrolling over the world view.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrushScrollModifiers {
+    pub radius: ScrollModifier,
+    pub weight: ScrollModifier,
+}
+
+impl Default for BrushScrollModifiers {
+    fn default() -> Self {
+        Self {
+            radius: ScrollModifier::Control,
+            weight: ScrollModifier::Alt,
+        }
+    }
+}
+
+/// Editor preferences that persist across sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorPrefs {
+    /// Number of frames the displayed frame time is averaged over.
+    pub averaging_window: usize,
+    /// Modifiers used to adjust the active brush by scrolling.
+    pub brush_scroll: BrushScrollModifiers,
+    /// Recently opened terrains, most recent first. The first entry is opened on startup.
+    pub recent_terrains: Vec<TerrainSource>,
+    /// Queue brush strokes are submitted on.
+    pub brush_queue: WorkQueue,
+    /// Bookmarks saved by older versions, which kept them here instead of in the world. They
+    /// are moved into the world on startup.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub camera_bookmarks: Vec<CameraBookmark>,
+}
+
+impl Default for EditorPrefs {
+    fn default() -> Self {
+        Self {
+            averaging_window: 30,
+            brush_scroll: BrushScrollModifiers::default(),
+            recent_terrains: vec![],
+            brush_queue: WorkQueue::default(),
+            camera_bookmarks: vec![],
+        }
+    }
+}
+
+impl EditorPrefs {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    /// Load preferences from a file, falling back to the defaults if the file does not exist.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Move a terrain to the front of the recent terrains list, adding it if it was not
+    /// in the list yet. Terrains are identified by their heightmap.
+    pub fn remember_terrain(&mut self, source: TerrainSource) {
+        self.recent_terrains
+            .retain(|recent| recent.height_path != source.height_path);
+        self.recent_terrains.insert(0, source);
+        self.recent_terrains.truncate(MAX_RECENT_TERRAINS);
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assets::{BorderMode, TerrainOptions};
+    use glam::Vec2;
+
+    use super::*;
+
+    fn source(name: &str) -> TerrainSource {
+        TerrainSource {
+            height_path: format!("{name}.png").into(),
+            texture_path: "blank.png".into(),
+            resize_texture: false,
+            detail_path: None,
+            options: TerrainOptions {
+                horizontal_scale: Vec2::new(512.0, 512.0),
+                vertical_scale: 100.0,
+                patch_resolution: 32,
+                detail_tiling: 16.0,
+                detail_strength: 2.0,
+                normal_strength: 1.0,
+                border_mode: BorderMode::Clamp,
+            },
+        }
+    }
+
+    #[test]
+    fn remember_terrain_moves_to_front() {
+        let mut prefs = EditorPrefs::default();
+        prefs.remember_terrain(source("a"));
+        prefs.remember_terrain(source("b"));
+        prefs.remember_terrain(source("a"));
+        let names = prefs
+            .recent_terrains
+            .iter()
+            .map(|recent| recent.height_path.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a.png", "b.png"]);
+    }
+
+    #[test]
+    fn remember_terrain_is_bounded() {
+        let mut prefs = EditorPrefs::default();
+        for i in 0..MAX_RECENT_TERRAINS + 2 {
+            prefs.remember_terrain(source(&i.to_string()));
+        }
+        assert_eq!(prefs.recent_terrains.len(), MAX_RECENT_TERRAINS);
+        assert_eq!(prefs.recent_terrains[0], source(&(MAX_RECENT_TERRAINS + 1).to_string()));
+    }
+}
@@ -0,0 +1,85 @@
+use brush::ScatterPropsEvent;
+use egui::{DragValue, Slider};
+use inject::DI;
+use log::error;
+use scheduler::EventBus;
+use world::{PropInstances, PropMesh, World};
+
+use crate::widgets::aligned_label::aligned_label_with;
+use crate::widgets::drag::Drag;
+
+/// Lets the user edit the prop settings and scatter props over the terrain. Props are only
+/// scattered again when asked to, since the heightmap has to be read back for it.
+/// # DI Access
+/// - Write [`PropInstances`]
+pub fn show(context: &egui::Context, bus: &EventBus<DI>, world: &mut World) {
+    egui::Window::new("Props")
+        .resizable(true)
+        .movable(true)
+        .show(context, |ui| {
+            let props = &mut world.props;
+            aligned_label_with(ui, "Mesh", |ui| {
+                egui::ComboBox::from_id_source("prop_mesh")
+                    .selected_text(format!("{:?}", props.mesh))
+                    .show_ui(ui, |ui| {
+                        for mesh in PropMesh::ALL {
+                            ui.selectable_value(&mut props.mesh, mesh, format!("{mesh:?}"));
+                        }
+                    });
+            });
+            aligned_label_with(ui, "Color", |ui| {
+                ui.color_edit_button_rgb(props.color.as_mut());
+            });
+            ui.separator();
+            Drag::new("Density", &mut props.density)
+                .speed(0.1)
+                .suffix(" /ha")
+                .show(ui);
+            Drag::new("Scale", &mut props.scale)
+                .speed(0.1)
+                .suffix(" m")
+                .show(ui);
+            aligned_label_with(ui, "Scale jitter", |ui| {
+                ui.add(Slider::new(&mut props.scale_jitter, 0.0..=1.0))
+            });
+            aligned_label_with(ui, "Max slope", |ui| {
+                ui.add(Slider::new(&mut props.max_slope, 0.0..=90.0).suffix("°"))
+            });
+            Drag::new("Min height", &mut props.min_height)
+                .suffix(" m")
+                .show(ui);
+            Drag::new("Max height", &mut props.max_height)
+                .suffix(" m")
+                .show(ui);
+            aligned_label_with(ui, "Seed", |ui| ui.add(DragValue::new(&mut props.seed)));
+
+            let (mut scatter, mut clear) = (false, false);
+            ui.horizontal(|ui| {
+                scatter = ui
+                    .add_enabled(world.terrain.is_some(), egui::Button::new("Scatter"))
+                    .on_hover_text("Scatter props over the terrain with these settings")
+                    .clicked();
+                clear = ui.button("Clear").clicked();
+            });
+            // The props are written by the brush task, so they are not locked while the
+            // event is sent to it.
+            if scatter {
+                let event = ScatterPropsEvent {
+                    settings: world.props,
+                };
+                if let Err(e) = bus.publish(event) {
+                    error!("Could not scatter props: {e}");
+                }
+            }
+            let di = bus.data().read().unwrap();
+            let mut instances = di.write_sync::<PropInstances>().unwrap();
+            if clear {
+                instances.clear();
+            }
+            let count = match instances.terrain == world.terrain {
+                true => instances.transforms.len(),
+                false => 0,
+            };
+            ui.label(format!("{count} props"));
+        });
+}
@@ -1,18 +1,298 @@
-use egui::{Checkbox, Slider};
-use world::World;
+use assets::SplatMap;
+use camera::{CameraState, Projection};
+use egui::{Checkbox, DragValue, Slider};
+use gfx::{PresentMode, Presentation, SetPresentModeEvent};
+use glam::UVec2;
+use hot_reload::ReloadAllShadersEvent;
+use inject::DI;
+use pass::DumpFrameGraphEvent;
+use scheduler::EventBus;
+use util::SafeUnwrap;
+use world::{
+    AntiAliasing, DisplayTransform, FxaaQuality, TerrainShading, TessellationSpacing,
+    TonemapOperator, World,
+};
 
 use crate::widgets::aligned_label::aligned_label_with;
 
-pub fn show(context: &egui::Context, world: &mut World) {
+/// Lets the user switch between the tonemapped image and the raw HDR values, and pick the
+/// tonemapping curve.
+fn show_display_transform(ui: &mut egui::Ui, world: &mut World) {
+    aligned_label_with(ui, "Raw HDR", |ui| {
+        let mut raw = matches!(world.options.display_transform, DisplayTransform::Exposure { .. });
+        if ui.add(Checkbox::without_text(&mut raw)).changed() {
+            world.options.display_transform = match raw {
+                true => DisplayTransform::Exposure {
+                    exposure: 0.0,
+                },
+                false => DisplayTransform::Tonemap,
+            };
+        }
+    });
+    if let DisplayTransform::Exposure {
+        exposure,
+    } = &mut world.options.display_transform
+    {
+        aligned_label_with(ui, "Exposure", |ui| {
+            ui.add(Slider::new(exposure, -16.0..=16.0).suffix(" EV"));
+        });
+        return;
+    }
+    aligned_label_with(ui, "Tonemapping", |ui| {
+        egui::ComboBox::from_id_source("tonemap_operator")
+            .selected_text(format!("{:?}", world.options.tonemap_operator))
+            .show_ui(ui, |ui| {
+                for operator in TonemapOperator::ALL {
+                    ui.selectable_value(
+                        &mut world.options.tonemap_operator,
+                        operator,
+                        format!("{operator:?}"),
+                    );
+                }
+            });
+    });
+    aligned_label_with(ui, "Exposure", |ui| {
+        ui.add(Slider::new(&mut world.options.tonemap_exposure, -16.0..=16.0).suffix(" EV"));
+    });
+}
+
+/// Lets the user toggle bloom and tweak its threshold and intensity.
+fn show_bloom(ui: &mut egui::Ui, world: &mut World) {
+    aligned_label_with(ui, "Bloom", |ui| {
+        ui.add(Checkbox::without_text(&mut world.options.bloom));
+    });
+    aligned_label_with(ui, "Bloom threshold", |ui| {
+        ui.add_enabled(
+            world.options.bloom,
+            Slider::new(&mut world.options.bloom_threshold, 0.0..=16.0),
+        )
+        .on_hover_text("Brightness above which pixels start to glow");
+    });
+    aligned_label_with(ui, "Bloom intensity", |ui| {
+        ui.add_enabled(
+            world.options.bloom,
+            Slider::new(&mut world.options.bloom_intensity, 0.0..=1.0),
+        );
+    });
+}
+
+/// Lets the user pick the colors of the materials painted with the splat brush.
+fn show_materials(ui: &mut egui::Ui, world: &mut World) {
+    ui.collapsing("Materials", |ui| {
+        for (index, color) in world.materials.colors.iter_mut().enumerate() {
+            let channel = index as u32 + 1;
+            aligned_label_with(ui, SplatMap::channel_name(channel), |ui| {
+                ui.color_edit_button_rgb(color.as_mut());
+            });
+        }
+    });
+}
+
+/// Lets the user switch the camera to an orthographic projection and set its height.
+/// # DI Access
+/// - Write [`CameraState`]
+fn show_projection(ui: &mut egui::Ui, bus: &EventBus<DI>) {
+    let di = bus.data().read().unwrap();
+    let mut camera = di.write_sync::<CameraState>().unwrap();
+    aligned_label_with(ui, "Orthographic", |ui| {
+        let mut orthographic = camera.is_orthographic();
+        if ui
+            .add(Checkbox::without_text(&mut orthographic))
+            .on_hover_text("Scroll in the world view to zoom")
+            .changed()
+        {
+            camera.set_orthographic(orthographic);
+        }
+    });
+    if let Projection::Orthographic {
+        mut height,
+    } = camera.projection()
+    {
+        aligned_label_with(ui, "View height", |ui| {
+            let range = CameraState::MIN_ORTHO_HEIGHT..=CameraState::MAX_ORTHO_HEIGHT;
+            let slider = Slider::new(&mut height, range)
+                .logarithmic(true)
+                .suffix(" m");
+            if ui.add(slider).changed() {
+                camera.set_ortho_height(height);
+            }
+        });
+    }
+}
+
+/// Lets the user toggle vsync, which switches between the FIFO and mailbox present modes.
+/// # DI Access
+/// - Read [`Presentation`]
+fn show_vsync(ui: &mut egui::Ui, bus: &EventBus<DI>) {
+    let mode = {
+        let di = bus.data().read().unwrap();
+        let presentation = di.read_sync::<Presentation>().unwrap();
+        presentation.request.unwrap_or(presentation.current)
+    };
+    aligned_label_with(ui, "VSync", |ui| {
+        let mut vsync = mode.vsync();
+        if ui
+            .add(Checkbox::without_text(&mut vsync))
+            .on_hover_text("Limit the frame rate to the refresh rate of the display")
+            .changed()
+        {
+            let present_mode = match vsync {
+                true => PresentMode::Fifo,
+                false => PresentMode::Mailbox,
+            };
+            bus.publish(SetPresentModeEvent {
+                present_mode,
+            })
+            .safe_unwrap();
+        }
+    });
+}
+
+pub fn show(context: &egui::Context, bus: &EventBus<DI>, world: &mut World) {
     egui::Window::new("Render options")
         .resizable(true)
         .movable(true)
         .show(context, |ui| {
-            aligned_label_with(ui, "Tessellation level", |ui| {
+            aligned_label_with(ui, "Max tessellation level", |ui| {
                 ui.add(Slider::new(&mut world.options.tessellation_level, 1..=128));
             });
+            aligned_label_with(ui, "Target edge length", |ui| {
+                ui.add(
+                    Slider::new(&mut world.options.target_edge_length, 1.0..=64.0).suffix(" px"),
+                );
+            });
+            aligned_label_with(ui, "Tessellation spacing", |ui| {
+                egui::ComboBox::from_id_source("tessellation_spacing")
+                    .selected_text(format!("{:?}", world.options.tessellation_spacing))
+                    .show_ui(ui, |ui| {
+                        for spacing in TessellationSpacing::ALL {
+                            ui.selectable_value(
+                                &mut world.options.tessellation_spacing,
+                                spacing,
+                                format!("{spacing:?}"),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Fractional spacing reduces popping as the level of detail changes",
+                    );
+            });
             aligned_label_with(ui, "Wireframe", |ui| {
                 ui.add(Checkbox::without_text(&mut world.options.wireframe));
             });
+            aligned_label_with(ui, "Match view size", |ui| {
+                ui.add(Checkbox::without_text(&mut world.options.match_view_size))
+                    .on_hover_text("Render at the exact pixel size of the world view");
+            });
+            aligned_label_with(ui, "Render scale", |ui| {
+                ui.add_enabled(
+                    !world.options.match_view_size,
+                    Slider::new(&mut world.options.render_scale, 0.25..=2.0),
+                );
+            });
+            aligned_label_with(ui, "Anti-aliasing", |ui| {
+                egui::ComboBox::from_id_source("anti_aliasing")
+                    .selected_text(format!("{:?}", world.options.anti_aliasing))
+                    .show_ui(ui, |ui| {
+                        for aa in AntiAliasing::ALL {
+                            ui.selectable_value(
+                                &mut world.options.anti_aliasing,
+                                aa,
+                                format!("{aa:?}"),
+                            );
+                        }
+                    });
+            });
+            if world.options.anti_aliasing == AntiAliasing::Fxaa {
+                aligned_label_with(ui, "FXAA quality", |ui| {
+                    egui::ComboBox::from_id_source("fxaa_quality")
+                        .selected_text(format!("{:?}", world.options.fxaa_quality))
+                        .show_ui(ui, |ui| {
+                            for quality in FxaaQuality::ALL {
+                                ui.selectable_value(
+                                    &mut world.options.fxaa_quality,
+                                    quality,
+                                    format!("{quality:?}"),
+                                );
+                            }
+                        });
+                });
+            }
+            show_projection(ui, bus);
+            aligned_label_with(ui, "Depth prepass", |ui| {
+                ui.add(Checkbox::without_text(&mut world.options.depth_prepass));
+            });
+            aligned_label_with(ui, "Limit output resolution", |ui| {
+                let mut limit = world.options.max_output_resolution.is_some();
+                if ui.add(Checkbox::without_text(&mut limit)).changed() {
+                    world.options.max_output_resolution = limit.then_some(UVec2::new(1920, 1080));
+                }
+            });
+            if let Some(max) = &mut world.options.max_output_resolution {
+                aligned_label_with(ui, "Max output resolution", |ui| {
+                    // Inverted because of the right_to_left layout
+                    ui.add(
+                        DragValue::new(&mut max.y)
+                            .clamp_range(1..=8192)
+                            .suffix(" px"),
+                    );
+                    ui.add(
+                        DragValue::new(&mut max.x)
+                            .clamp_range(1..=8192)
+                            .suffix(" px"),
+                    );
+                });
+            }
+            show_vsync(ui, bus);
+            ui.separator();
+            aligned_label_with(ui, "Isolate terrain", |ui| {
+                let mut isolate = world.options.is_terrain_isolated();
+                if ui.add(Checkbox::without_text(&mut isolate)).changed() {
+                    world.options.set_isolate_terrain(isolate);
+                }
+            });
+            aligned_label_with(ui, "Atmosphere", |ui| {
+                ui.add(Checkbox::without_text(&mut world.options.atmosphere));
+            });
+            if !world.options.atmosphere {
+                aligned_label_with(ui, "Background", |ui| {
+                    ui.color_edit_button_rgb(world.options.background.as_mut());
+                });
+            }
+            aligned_label_with(ui, "Decals", |ui| {
+                ui.add(Checkbox::without_text(&mut world.options.decals));
+            });
+            aligned_label_with(ui, "Terrain shading", |ui| {
+                egui::ComboBox::from_id_source("terrain_shading")
+                    .selected_text(format!("{:?}", world.options.terrain_shading))
+                    .show_ui(ui, |ui| {
+                        for shading in TerrainShading::ALL {
+                            ui.selectable_value(
+                                &mut world.options.terrain_shading,
+                                shading,
+                                format!("{shading:?}"),
+                            );
+                        }
+                    });
+            });
+            show_materials(ui, world);
+            show_bloom(ui, world);
+            show_display_transform(ui, world);
+            if ui.button("Reload shaders (F5)").clicked() {
+                bus.publish(ReloadAllShadersEvent).safe_unwrap();
+            }
+            ui.collapsing("Debug", |ui| {
+                if ui
+                    .button("Dump frame graph")
+                    .on_hover_text("Write the frame graph to frame_graph.dot")
+                    .clicked()
+                {
+                    bus.publish(DumpFrameGraphEvent {
+                        path: "frame_graph.dot".into(),
+                    })
+                    .safe_unwrap();
+                }
+            });
         });
 }
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use assets::storage::AssetStorage;
+use assets::Terrain;
+use brush::ClearHistoryEvent;
+use camera::CameraState;
+use error::{publish_error, publish_success, MessageEvent, MessageLevel};
+use inject::DI;
+use scheduler::EventBus;
+use world::{World, SCENE_FILE};
+
+use crate::widgets::aligned_label::aligned_label_with;
+
+/// Shows the menu bar of the editor, with a file menu to save the world to and open it from a
+/// scene file. Returns a message to show if the scene was saved.
+pub fn show_menu(
+    context: &egui::Context,
+    bus: &EventBus<DI>,
+    world: &mut World,
+) -> Option<MessageEvent> {
+    let mut message = None;
+    egui::TopBottomPanel::top("menu_bar").show(context, |ui| {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                let id = egui::Id::new("scene_path");
+                let mut path = ui
+                    .data_mut(|data| data.get_temp::<String>(id))
+                    .unwrap_or_else(|| SCENE_FILE.to_owned());
+                aligned_label_with(ui, "Scene", |ui| ui.text_edit_singleline(&mut path));
+                let path_set = !path.trim().is_empty();
+                if ui
+                    .add_enabled(path_set, egui::Button::new("Save Scene"))
+                    .clicked()
+                {
+                    message = Some(save_scene(bus, world, PathBuf::from(path.trim())));
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(path_set, egui::Button::new("Open Scene"))
+                    .clicked()
+                {
+                    open_scene(bus, PathBuf::from(path.trim()));
+                    ui.close_menu();
+                }
+                ui.data_mut(|data| data.insert_temp(id, path));
+            });
+        });
+    });
+    message
+}
+
+/// Save the world together with the current camera pose.
+/// # DI Access
+/// - Read [`CameraState`]
+fn save_scene(bus: &EventBus<DI>, world: &mut World, path: PathBuf) -> MessageEvent {
+    world.camera = {
+        let di = bus.data().read().unwrap();
+        let camera = di.read_sync::<CameraState>().unwrap();
+        Some(camera.pose())
+    };
+    let message = match world.save(&path) {
+        Ok(_) => {
+            MessageEvent::new(MessageLevel::Success, format!("Saved scene to {}", path.display()))
+        }
+        Err(e) => MessageEvent::new(
+            MessageLevel::Error,
+            format!("Could not save scene to {}: {e}", path.display()),
+        ),
+    };
+    message.with_source("scene")
+}
+
+/// Replace the world with the scene in a file. This runs on a blocking task, since loading a
+/// scene publishes messages which must not happen from inside the editor.
+fn open_scene(bus: &EventBus<DI>, path: PathBuf) {
+    let bus = bus.clone();
+    tokio::task::spawn_blocking(move || {
+        let result = World::load(&path, &bus).and_then(|loaded| replace_world(&bus, loaded));
+        let path = path.display();
+        match result {
+            Ok(_) => {
+                publish_success!(bus, source = "scene", "Opened scene {path}");
+            }
+            Err(e) => {
+                publish_error!(bus, source = "scene", "Could not open scene {path}: {e}");
+            }
+        }
+    });
+}
+
+/// Replace the world with a loaded scene. The terrain of the previous world is deleted, and the
+/// undo history that refers to it is cleared. The camera is moved to the pose stored in the scene.
+/// # DI Access
+/// - Write [`World`]
+/// - Read [`AssetStorage`]
+/// - Write [`CameraState`]
+fn replace_world(bus: &EventBus<DI>, loaded: World) -> Result<()> {
+    let camera = loaded.camera;
+    let previous = {
+        let di = bus.data().read().unwrap();
+        let mut world = di.write_sync::<World>().unwrap();
+        std::mem::replace(&mut *world, loaded)
+    };
+    // The history refers to the textures of the previous terrain
+    bus.publish(ClearHistoryEvent)?;
+    let di = bus.data().read().unwrap();
+    if let Some(terrain) = previous.terrain {
+        let assets = di.get::<AssetStorage>().unwrap();
+        Terrain::schedule_delete(terrain, assets);
+    }
+    if let Some(pose) = camera {
+        di.write_sync::<CameraState>().unwrap().set_pose(pose);
+    }
+    Ok(())
+}
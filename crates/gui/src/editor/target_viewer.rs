@@ -0,0 +1,41 @@
+use egui::Vec2;
+use inject::DI;
+use scheduler::EventBus;
+
+use crate::util::target_viewer::TargetViewer;
+use crate::widgets::aligned_label::aligned_label_with;
+
+/// Lets the user pick a render target and shows it, scaled to fit the window.
+/// # DI Access
+/// - Write [`TargetViewer`]
+pub fn show(context: &egui::Context, bus: &EventBus<DI>) {
+    let inject = bus.data().read().unwrap();
+    let mut viewer = inject.write_sync::<TargetViewer>().unwrap();
+    egui::Window::new("Render targets")
+        .resizable(true)
+        .movable(true)
+        .default_open(false)
+        .show(context, |ui| {
+            aligned_label_with(ui, "Target", |ui| {
+                let selected = viewer.selected.clone().unwrap_or_else(|| "None".to_owned());
+                let TargetViewer {
+                    targets,
+                    selected: current,
+                    ..
+                } = &mut *viewer;
+                egui::ComboBox::from_id_source("target_viewer")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(current, None, "None");
+                        for target in targets.iter() {
+                            ui.selectable_value(current, Some(target.clone()), target.as_str());
+                        }
+                    });
+            });
+            if let Some(image) = viewer.handle {
+                let size: Vec2 = image.size.into();
+                let scale = (ui.available_width() / size.x).min(1.0);
+                ui.image(image.id, size * scale);
+            }
+        });
+}
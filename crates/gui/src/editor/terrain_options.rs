@@ -1,35 +1,106 @@
+use std::path::PathBuf;
+
 use assets::storage::AssetStorage;
-use assets::TerrainLoadInfo;
-use egui::Slider;
+use assets::{BorderMode, TerrainLoadInfo, TerrainSource};
+use brush::{
+    BakeLightingEvent, CommitTerrainEvent, ExportHeightmapEvent, FlattenTerrainEvent,
+    ResetTerrainToSourceEvent,
+};
+use egui::{DragValue, Response, Slider};
 use inject::DI;
+use log::error;
 use scheduler::EventBus;
 use world::World;
 
+use crate::editor::prefs::{EditorPrefs, EDITOR_PREFS_FILE};
 use crate::widgets::aligned_label::aligned_label_with;
-use crate::widgets::drag::Drag;
+use crate::widgets::drag::{edit_finished, Drag};
 
-pub fn show(context: &egui::Context, bus: &EventBus<DI>, world: &mut World) {
+pub fn show(
+    context: &egui::Context,
+    bus: &EventBus<DI>,
+    world: &mut World,
+    prefs: &mut EditorPrefs,
+) {
     egui::Window::new("Terrain options")
         .resizable(true)
         .movable(true)
         .show(context, |ui| {
-            let mut dirty =
-                Drag::new("Terrain horizontal scale", &mut world.terrain_options.horizontal_scale)
-                    .speed(1.0)
-                    .suffix(" m")
-                    .show(ui);
-            Drag::new("Terrain vertical scale", &mut world.terrain_options.vertical_scale)
+            show_terrain_source(ui, bus, world, prefs);
+            show_load_progress(ui, bus, world);
+            ui.separator();
+            let mut edit = TerrainEdit::default();
+            edit.track(
+                Drag::new(
+                    "Terrain horizontal scale",
+                    &mut world.terrain_options.horizontal_scale.x,
+                )
                 .speed(1.0)
                 .suffix(" m")
-                .show(ui);
-            dirty |= aligned_label_with(ui, "Patch resolution", |ui| {
-                ui.add(Slider::new(&mut world.terrain_options.patch_resolution, 1..=64))
-                    .changed()
-            })
-            .inner;
-
-            // If changed, generate new terrain
+                .show_response(ui),
+            );
+            aligned_label_with(ui, "Terrain depth", |ui| {
+                ui.label(format!("{:.1} m", world.terrain_options.horizontal_scale.y))
+            });
+            // The vertical scale and normal strength do not change the mesh, but the normal
+            // map has to be regenerated.
+            edit.track(
+                Drag::new("Terrain vertical scale", &mut world.terrain_options.vertical_scale)
+                    .speed(1.0)
+                    .suffix(" m")
+                    .show_response(ui),
+            );
+            edit.track(
+                Drag::new("Normal strength", &mut world.terrain_options.normal_strength)
+                    .speed(0.01)
+                    .show_response(ui),
+            );
+            if show_border_mode(ui, &mut world.terrain_options.border_mode) {
+                edit.changed = true;
+                edit.finished = true;
+            }
+            edit.track(
+                aligned_label_with(ui, "Patch resolution", |ui| {
+                    ui.add(Slider::new(&mut world.terrain_options.patch_resolution, 1..=64))
+                })
+                .inner,
+            );
+            // The detail options are applied while dragging, but only remembered with the
+            // terrain once the user finished editing them, so the preferences are not written
+            // every frame.
+            let detail_tiling =
+                Drag::new("Detail tiling", &mut world.terrain_options.detail_tiling)
+                    .speed(0.1)
+                    .show_response(ui);
+            let detail_strength =
+                Drag::new("Detail strength", &mut world.terrain_options.detail_strength)
+                    .speed(0.1)
+                    .suffix(" m")
+                    .show_response(ui);
+            if edit_finished(&detail_tiling) || edit_finished(&detail_strength) {
+                if let Some(source) = &mut world.terrain_source {
+                    source.options.detail_tiling = world.terrain_options.detail_tiling;
+                    source.options.detail_strength = world.terrain_options.detail_strength;
+                }
+            }
+            show_detail_map(ui, bus, world);
+            show_diffuse_map(ui, bus, world);
+            ui.separator();
+            show_commit(ui, bus);
+            show_export(ui, bus);
+            show_reset(ui, bus);
+
+            // Regenerating the terrain is expensive, so it only happens once the user finished
+            // editing instead of every frame of a drag.
+            let pending_id = ui.make_persistent_id("pending_terrain_edit");
+            let pending = ui.data_mut(|data| data.get_temp::<bool>(pending_id).unwrap_or(false))
+                || edit.changed;
+            let dirty = pending && edit.finished;
+            ui.data_mut(|data| data.insert_temp(pending_id, pending && !dirty));
             if dirty {
+                if let Some(source) = &mut world.terrain_source {
+                    source.options = world.terrain_options;
+                }
                 let di = bus.data().read().unwrap();
                 let assets = di.get::<AssetStorage>().unwrap();
                 match world.terrain.take() {
@@ -42,5 +113,361 @@ pub fn show(context: &egui::Context, bus: &EventBus<DI>, world: &mut World) {
                     }
                 }
             }
+            sync_recent_terrain(world, prefs);
+        });
+}
+
+/// Edits of the options that require the terrain to be regenerated.
+#[derive(Debug, Default)]
+struct TerrainEdit {
+    /// An option changed this frame.
+    changed: bool,
+    /// The user finished editing an option this frame.
+    finished: bool,
+}
+
+impl TerrainEdit {
+    fn track(&mut self, response: Response) {
+        self.changed |= response.changed();
+        self.finished |= edit_finished(&response);
+    }
+}
+
+fn save_prefs(prefs: &EditorPrefs) {
+    if let Err(e) = prefs.save(EDITOR_PREFS_FILE) {
+        error!("Could not save editor preferences: {e}");
+    }
+}
+
+/// Remember the files the terrain was created from as the most recently opened terrain.
+/// These change when a terrain is opened or committed, or an edit of its options finished.
+/// The preferences are only saved when they changed.
+fn sync_recent_terrain(world: &World, prefs: &mut EditorPrefs) {
+    let Some(source) = &world.terrain_source else { return };
+    if prefs.recent_terrains.first() != Some(source) {
+        prefs.remember_terrain(source.clone());
+        save_prefs(prefs);
+    }
+}
+
+/// Replace the terrain with a new one created from files.
+fn open_terrain(bus: &EventBus<DI>, world: &mut World, source: TerrainSource) {
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    world.terrain_options = source.options;
+    world.terrain = Some(assets.load(source.load_info()));
+    world.terrain_source = Some(source);
+}
+
+/// Shows a progress bar while the terrain, or any of the maps it is made of, is loading.
+fn show_load_progress(ui: &mut egui::Ui, bus: &EventBus<DI>, world: &World) {
+    let Some(terrain) = world.terrain else { return };
+    let di = bus.data().read().unwrap();
+    let assets = di.get::<AssetStorage>().unwrap();
+    let progress = assets.progress(terrain).or_else(|| {
+        assets
+            .with_if_ready(terrain, |terrain| terrain.load_progress(assets))
+            .flatten()
+    });
+    if let Some((progress, stage)) = progress {
+        ui.add(egui::ProgressBar::new(progress).text(stage));
+    }
+}
+
+/// Lets the user open a terrain from files, or switch to a recently opened terrain.
+fn show_terrain_source(
+    ui: &mut egui::Ui,
+    bus: &EventBus<DI>,
+    world: &mut World,
+    prefs: &EditorPrefs,
+) {
+    let mut open = None;
+    aligned_label_with(ui, "Recent terrains", |ui| {
+        let current = prefs
+            .recent_terrains
+            .first()
+            .map(|recent| recent.height_path.display().to_string())
+            .unwrap_or_default();
+        egui::ComboBox::from_id_source("recent_terrains")
+            .selected_text(current)
+            .show_ui(ui, |ui| {
+                for recent in &prefs.recent_terrains {
+                    // Files may have been moved or deleted since the terrain was opened.
+                    let label = recent.height_path.display().to_string();
+                    let response = match recent.missing_file() {
+                        None => ui.selectable_label(false, label),
+                        Some(path) => ui
+                            .add_enabled(false, egui::SelectableLabel::new(false, label))
+                            .on_disabled_hover_text(format!("{} is missing", path.display())),
+                    };
+                    if response.clicked() {
+                        open = Some(recent.clone());
+                    }
+                }
+            });
+    });
+
+    let heightmap_id = ui.make_persistent_id("open_heightmap_path");
+    let texture_id = ui.make_persistent_id("open_texture_path");
+    let resize_id = ui.make_persistent_id("open_resize_texture");
+    let (mut heightmap, mut texture, mut resize_texture) = ui.data_mut(|data| {
+        (
+            data.get_temp_mut_or_default::<String>(heightmap_id).clone(),
+            data.get_temp_mut_or_default::<String>(texture_id).clone(),
+            *data.get_temp_mut_or_default::<bool>(resize_id),
+        )
+    });
+    aligned_label_with(ui, "Heightmap", |ui| ui.text_edit_singleline(&mut heightmap));
+    aligned_label_with(ui, "Texture", |ui| ui.text_edit_singleline(&mut texture));
+    ui.checkbox(&mut resize_texture, "Resize texture to heightmap");
+    let can_open = !heightmap.trim().is_empty() && !texture.trim().is_empty();
+    if ui
+        .add_enabled(can_open, egui::Button::new("Open terrain"))
+        .clicked()
+    {
+        open = Some(TerrainSource {
+            height_path: PathBuf::from(heightmap.trim()),
+            texture_path: PathBuf::from(texture.trim()),
+            resize_texture,
+            detail_path: None,
+            options: world.terrain_options,
+        });
+    }
+    ui.data_mut(|data| {
+        data.insert_temp(heightmap_id, heightmap);
+        data.insert_temp(texture_id, texture);
+        data.insert_temp(resize_id, resize_texture);
+    });
+
+    if let Some(source) = open {
+        open_terrain(bus, world, source);
+    }
+}
+
+/// Returns true if the border mode was changed.
+fn show_border_mode(ui: &mut egui::Ui, border_mode: &mut BorderMode) -> bool {
+    aligned_label_with(ui, "Border mode", |ui| {
+        let mut changed = false;
+        egui::ComboBox::from_id_source("border_mode")
+            .selected_text(format!("{border_mode:?}"))
+            .show_ui(ui, |ui| {
+                for mode in BorderMode::ALL {
+                    changed |= ui
+                        .selectable_value(border_mode, mode, format!("{mode:?}"))
+                        .changed();
+                }
+            });
+        changed
+    })
+    .inner
+}
+
+/// Lets the user load or remove the detail heightmap of the terrain.
+fn show_detail_map(ui: &mut egui::Ui, bus: &EventBus<DI>, world: &mut World) {
+    let Some(terrain) = world.terrain else { return };
+    let path_id = ui.make_persistent_id("detail_heightmap_path");
+    let mut path = ui.data_mut(|data| data.get_temp_mut_or_default::<String>(path_id).clone());
+    let mut detail_path = None;
+    aligned_label_with(ui, "Detail heightmap", |ui| {
+        if ui.button("Remove").clicked() {
+            detail_path = Some(None);
+        }
+        if ui
+            .add_enabled(!path.trim().is_empty(), egui::Button::new("Load"))
+            .clicked()
+        {
+            detail_path = Some(Some(PathBuf::from(path.trim())));
+        }
+        ui.text_edit_singleline(&mut path);
+    });
+    ui.data_mut(|data| data.insert_temp(path_id, path));
+
+    if let Some(detail_path) = detail_path {
+        if let Some(source) = &mut world.terrain_source {
+            source.detail_path = detail_path.clone();
+        }
+        let di = bus.data().read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        world.terrain = Some(assets.load(TerrainLoadInfo::WithDetailMap {
+            old: terrain,
+            detail_path,
+        }));
+    }
+}
+
+/// Lets the user replace the color texture of the terrain, without reloading the heightmap.
+/// Unsaved color edits are lost.
+fn show_diffuse_map(ui: &mut egui::Ui, bus: &EventBus<DI>, world: &mut World) {
+    let Some(terrain) = world.terrain else { return };
+    let path_id = ui.make_persistent_id("diffuse_map_path");
+    let resize_id = ui.make_persistent_id("diffuse_map_resize");
+    let (mut path, mut resize_texture) = ui.data_mut(|data| {
+        (
+            data.get_temp_mut_or_default::<String>(path_id).clone(),
+            *data.get_temp_mut_or_default::<bool>(resize_id),
+        )
+    });
+    let mut load = false;
+    aligned_label_with(ui, "Color texture", |ui| {
+        load = ui
+            .add_enabled(!path.trim().is_empty(), egui::Button::new("Load"))
+            .clicked();
+        ui.checkbox(&mut resize_texture, "Resize")
+            .on_hover_text("Resize the texture to the resolution of the heightmap");
+        ui.text_edit_singleline(&mut path);
+    });
+
+    if load {
+        let texture_path = PathBuf::from(path.trim());
+        if let Some(source) = &mut world.terrain_source {
+            source.texture_path = texture_path.clone();
+            source.resize_texture = resize_texture;
+        }
+        let di = bus.data().read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        world.terrain = Some(assets.load(TerrainLoadInfo::WithDiffuseMap {
+            old: terrain,
+            texture_path,
+            resize_texture,
+        }));
+    }
+    ui.data_mut(|data| {
+        data.insert_temp(path_id, path);
+        data.insert_temp(resize_id, resize_texture);
+    });
+}
+
+/// Lets the user save the edited terrain to a directory.
+fn show_commit(ui: &mut egui::Ui, bus: &EventBus<DI>) {
+    let path_id = ui.make_persistent_id("commit_terrain_path");
+    let mut path = ui.data_mut(|data| data.get_temp_mut_or_default::<String>(path_id).clone());
+    let mut commit = false;
+    aligned_label_with(ui, "Commit directory", |ui| {
+        commit = ui
+            .add_enabled(!path.trim().is_empty(), egui::Button::new("Commit"))
+            .on_hover_text("Save the terrain textures and reopen the terrain from them")
+            .clicked();
+        ui.text_edit_singleline(&mut path);
+    });
+    if commit {
+        let event = CommitTerrainEvent {
+            path: PathBuf::from(path.trim()),
+        };
+        if let Err(e) = bus.publish(event) {
+            error!("Could not commit terrain: {e}");
+        }
+    }
+    ui.data_mut(|data| data.insert_temp(path_id, path));
+}
+
+/// Lets the user export the heightmap to a PNG or EXR file.
+fn show_export(ui: &mut egui::Ui, bus: &EventBus<DI>) {
+    let path_id = ui.make_persistent_id("export_heightmap_path");
+    let mut path = ui.data_mut(|data| data.get_temp_mut_or_default::<String>(path_id).clone());
+    let mut export = false;
+    aligned_label_with(ui, "Export heightmap", |ui| {
+        export = ui
+            .add_enabled(!path.trim().is_empty(), egui::Button::new("Export"))
+            .on_hover_text("Save the heightmap as a 16-bit .png or a 32-bit float .exr file")
+            .clicked();
+        ui.text_edit_singleline(&mut path);
+    });
+    if export {
+        let event = ExportHeightmapEvent {
+            path: PathBuf::from(path.trim()),
+        };
+        if let Err(e) = bus.publish(event) {
+            error!("Could not export heightmap: {e}");
+        }
+    }
+    ui.data_mut(|data| data.insert_temp(path_id, path));
+}
+
+/// Edit of an entire terrain texture that waits for the user to confirm it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum PendingReset {
+    Source,
+    Flatten,
+    BakeLighting,
+}
+
+/// Lets the user reset the heightmap to the file it was opened from, flatten it, or bake the
+/// lighting into the color texture. These overwrite edits, so they have to be confirmed first.
+fn show_reset(ui: &mut egui::Ui, bus: &EventBus<DI>) {
+    let height_id = ui.make_persistent_id("flatten_height");
+    let ambient_id = ui.make_persistent_id("bake_ambient");
+    let pending_id = ui.make_persistent_id("pending_reset");
+    let (mut height, mut ambient, mut pending) = ui.data_mut(|data| {
+        (
+            *data.get_temp_mut_or_default::<f32>(height_id),
+            data.get_temp::<f32>(ambient_id).unwrap_or(0.1),
+            data.get_temp::<PendingReset>(pending_id),
+        )
+    });
+    aligned_label_with(ui, "Flatten height", |ui| {
+        if ui.button("Flatten").clicked() {
+            pending = Some(PendingReset::Flatten);
+        }
+        ui.add(DragValue::new(&mut height).speed(0.01));
+    });
+    if ui
+        .button("Reset to source heightmap")
+        .on_hover_text("Load the heightmap the terrain was opened from again")
+        .clicked()
+    {
+        pending = Some(PendingReset::Source);
+    }
+    aligned_label_with(ui, "Ambient light", |ui| {
+        if ui
+            .button("Bake lighting")
+            .on_hover_text(
+                "Multiply the color texture with the current sun lighting. This overwrites the \
+                 color texture, use undo to restore it.",
+            )
+            .clicked()
+        {
+            pending = Some(PendingReset::BakeLighting);
+        }
+        ui.add(
+            DragValue::new(&mut ambient)
+                .speed(0.01)
+                .clamp_range(0.0..=1.0),
+        );
+    });
+    if let Some(reset) = pending {
+        let question = match reset {
+            PendingReset::Source => "Discard all edits to the heightmap?".to_owned(),
+            PendingReset::Flatten => format!("Flatten the heightmap to {height}?"),
+            PendingReset::BakeLighting => "Overwrite the color texture with lit colors?".to_owned(),
+        };
+        ui.horizontal(|ui| {
+            ui.label(question);
+            if ui.button("Confirm").clicked() {
+                let result = match reset {
+                    PendingReset::Source => bus.publish(ResetTerrainToSourceEvent),
+                    PendingReset::Flatten => bus.publish(FlattenTerrainEvent {
+                        height,
+                    }),
+                    PendingReset::BakeLighting => bus.publish(BakeLightingEvent {
+                        ambient,
+                    }),
+                };
+                if let Err(e) = result {
+                    error!("Could not reset terrain: {e}");
+                }
+                pending = None;
+            }
+            if ui.button("Cancel").clicked() {
+                pending = None;
+            }
         });
+    }
+    ui.data_mut(|data| {
+        data.insert_temp(height_id, height);
+        data.insert_temp(ambient_id, ambient);
+        match pending {
+            None => data.remove::<PendingReset>(pending_id),
+            Some(reset) => data.insert_temp(pending_id, reset),
+        }
+    });
 }
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use egui::{Button, DragValue};
+use inject::DI;
+use scheduler::EventBus;
+use time::TimeControl;
+
+use crate::widgets::aligned_label::aligned_label_with;
+
+/// Shows play, pause and step controls for time-driven animation.
+/// `step` is the amount of time a single step advances by.
+/// # DI Access
+/// - Write [`TimeControl`]
+pub fn show(context: &egui::Context, bus: &EventBus<DI>, step: &mut Duration) {
+    let di = bus.data().read().unwrap();
+    let mut control = di.write_sync::<TimeControl>().unwrap();
+    egui::Window::new("Time")
+        .resizable(true)
+        .movable(true)
+        .show(context, |ui| {
+            ui.horizontal(|ui| {
+                let label = match control.paused {
+                    true => "▶ Play",
+                    false => "⏸ Pause",
+                };
+                if ui.button(label).clicked() {
+                    control.paused = !control.paused;
+                }
+                if ui
+                    .add_enabled(control.paused, Button::new("⏭ Step"))
+                    .clicked()
+                {
+                    control.step = Some(*step);
+                }
+            });
+            aligned_label_with(ui, "Step size", |ui| {
+                let mut millis = step.as_secs_f32() * 1000.0;
+                let drag = DragValue::new(&mut millis)
+                    .clamp_range(1.0..=1000.0)
+                    .suffix(" ms");
+                if ui.add(drag).changed() {
+                    *step = Duration::from_secs_f32(millis / 1000.0);
+                }
+            });
+        });
+}
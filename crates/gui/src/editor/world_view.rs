@@ -28,6 +28,7 @@ pub fn show(context: &egui::Context, bus: &EventBus<DI>, brushes: &mut BrushWidg
             let inject = bus.data().read().unwrap();
             let mut provider = inject.write_sync::<ImageProvider>().unwrap();
             provider.size = size.into();
+            provider.pixels_per_point = context.pixels_per_point();
             provider.handle
         },
         |response| behaviour(response, bus, brushes),
@@ -5,6 +5,7 @@ use scheduler::EventBus;
 use crate::editor::{Editor, WorldOverlayInfo};
 use crate::util::image_provider::ImageProvider;
 use crate::util::size::USize;
+use crate::util::target_viewer::TargetViewer;
 
 pub mod editor;
 pub mod util;
@@ -16,6 +17,7 @@ pub fn initialize(ctx: egui::Context, bus: &mut EventBus<DI>) {
     inject.put_sync(ImageProvider {
         handle: None,
         size: USize::new(800, 600),
+        pixels_per_point: 1.0,
     });
 
     inject.put_sync(WorldMousePosition {
@@ -25,4 +27,5 @@ pub fn initialize(ctx: egui::Context, bus: &mut EventBus<DI>) {
     });
 
     inject.put_sync(WorldOverlayInfo::default());
+    inject.put_sync(TargetViewer::default());
 }
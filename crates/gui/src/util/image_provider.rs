@@ -3,5 +3,9 @@ use crate::util::size::USize;
 
 pub struct ImageProvider {
     pub handle: Option<Image>,
+    /// Size of the world view in points.
     pub size: USize,
+    /// Scale factor of the GUI at the time `size` was set, used to find the size of the
+    /// world view in pixels.
+    pub pixels_per_point: f32,
 }
@@ -2,3 +2,4 @@ pub mod image;
 pub mod image_provider;
 pub mod mouse_position;
 pub mod size;
+pub mod target_viewer;
@@ -0,0 +1,13 @@
+use crate::util::image::Image;
+
+/// State of the render target viewer. The renderer lists the targets it has registered, and
+/// draws the selected target into a displayable image. Access through DI.
+#[derive(Debug, Default)]
+pub struct TargetViewer {
+    /// Names of all render targets that can be viewed, sorted alphabetically.
+    pub targets: Vec<String>,
+    /// Name of the target to show, or `None` to not draw any target.
+    pub selected: Option<String>,
+    /// Image of the selected target.
+    pub handle: Option<Image>,
+}
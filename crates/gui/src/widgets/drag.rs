@@ -1,6 +1,6 @@
 use std::ops::{Add, Div, Mul, Sub};
 
-use egui::Ui;
+use egui::{Response, Ui};
 use glam::Vec3;
 use math::Rotation;
 
@@ -13,34 +13,33 @@ pub trait Draggable:
     + Add<Self, Output = Self>
     + Mul<f32, Output = Self>
     + Div<f32, Output = Self> {
-    fn drag(&mut self, ui: &mut Ui, speed: f64, digits: usize, suffix: &str) -> bool;
+    /// Returns the union of the responses of all widgets used to edit the value.
+    fn drag(&mut self, ui: &mut Ui, speed: f64, digits: usize, suffix: &str) -> Response;
 }
 
 impl Draggable for Vec3 {
-    fn drag(&mut self, ui: &mut Ui, speed: f64, digits: usize, suffix: &str) -> bool {
+    fn drag(&mut self, ui: &mut Ui, speed: f64, digits: usize, suffix: &str) -> Response {
         // The reason this is inverted is because of the right_to_left layout used when showing this.
-        let mut dirty = false;
-        dirty |= self.z.drag(ui, speed, digits, suffix);
-        dirty |= self.y.drag(ui, speed, digits, suffix);
-        dirty |= self.x.drag(ui, speed, digits, suffix);
-        dirty
+        let z = self.z.drag(ui, speed, digits, suffix);
+        let y = self.y.drag(ui, speed, digits, suffix);
+        let x = self.x.drag(ui, speed, digits, suffix);
+        z | y | x
     }
 }
 
 impl Draggable for Rotation {
-    fn drag(&mut self, ui: &mut Ui, _speed: f64, _digits: usize, _suffix: &str) -> bool {
+    fn drag(&mut self, ui: &mut Ui, _speed: f64, _digits: usize, _suffix: &str) -> Response {
         // TODO: Maybe make speed and digits work with this too
         // The reason this is inverted is because of the right_to_left layout used when showing this.
-        let mut dirty = false;
-        dirty |= ui.drag_angle(&mut self.0.z).changed();
-        dirty |= ui.drag_angle(&mut self.0.y).changed();
-        dirty |= ui.drag_angle(&mut self.0.x).changed();
-        dirty
+        let z = ui.drag_angle(&mut self.0.z);
+        let y = ui.drag_angle(&mut self.0.y);
+        let x = ui.drag_angle(&mut self.0.x);
+        z | y | x
     }
 }
 
 impl Draggable for f32 {
-    fn drag(&mut self, ui: &mut Ui, speed: f64, digits: usize, suffix: &str) -> bool {
+    fn drag(&mut self, ui: &mut Ui, speed: f64, digits: usize, suffix: &str) -> Response {
         ui.add(
             egui::DragValue::new(self)
                 .speed(speed)
@@ -48,10 +47,15 @@ impl Draggable for f32 {
                 .max_decimals(digits)
                 .suffix(suffix),
         )
-        .changed()
     }
 }
 
+/// Returns true once the user finished editing a value, when a drag is released or a typed
+/// value is confirmed. Use this for changes that are too expensive to apply every frame.
+pub fn edit_finished(response: &Response) -> bool {
+    response.drag_released() || response.lost_focus()
+}
+
 pub struct Drag<'v, 's, T, L>
 where
     T: Draggable,
@@ -112,7 +116,12 @@ where
     }
 
     /// Returns true if the value changed
-    pub fn show(mut self, ui: &mut egui::Ui) -> bool {
+    pub fn show(self, ui: &mut egui::Ui) -> bool {
+        self.show_response(ui).changed()
+    }
+
+    /// Returns the response of the widgets editing the value, see [`edit_finished`].
+    pub fn show_response(mut self, ui: &mut egui::Ui) -> Response {
         aligned_label_with(ui, self.label, |ui| {
             self.scaled_value = (*self.original_value - self.base) * self.scale;
             let response =
@@ -20,9 +20,12 @@ pub fn resizable_image_window(
             // Get the image of the correct size
             let image = get_image(remaining_size);
             if let Some(image) = image {
+                // Align to the pixel grid, so an image of the exact pixel size is not resampled
+                let rect =
+                    painter.round_rect_to_pixels(Rect::from_min_size(cursor.min, remaining_size));
                 painter.image(
                     image.id,
-                    Rect::from_min_size(cursor.min, remaining_size),
+                    rect,
                     Rect::from_min_max(Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0)),
                     Color32::WHITE,
                 );
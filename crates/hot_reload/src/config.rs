@@ -0,0 +1,74 @@
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+/// Include directory used if no other include directories are configured.
+pub const DEFAULT_INCLUDE_DIR: &str = "shaders/include";
+
+/// Environment variable that overrides the path to DXC.
+pub const DXC_ENV_VAR: &str = "ANDROMEDA_DXC";
+
+/// Environment variable that overrides the path to glslangValidator.
+pub const GLSLANG_ENV_VAR: &str = "ANDROMEDA_GLSLANG";
+
+/// Configuration of the shader compilers used by [`ShaderReload`](crate::ShaderReload).
+#[derive(Debug, Clone)]
+pub struct ShaderCompilerConfig {
+    /// Path to the DXC executable. If unset, the default location of the platform is used.
+    /// The `ANDROMEDA_DXC` environment variable takes precedence over this.
+    pub dxc_path: Option<PathBuf>,
+    /// Path to the glslangValidator executable. If unset, the default location of the
+    /// platform is used. The `ANDROMEDA_GLSLANG` environment variable takes precedence over
+    /// this.
+    pub glslang_path: Option<PathBuf>,
+    /// Directories searched for included files, in order.
+    pub include_dirs: Vec<PathBuf>,
+    /// Allow compiling mesh and task shaders. Only enable this if the device supports
+    /// `VK_EXT_mesh_shader`.
+    pub mesh_shaders: bool,
+}
+
+impl Default for ShaderCompilerConfig {
+    fn default() -> Self {
+        Self {
+            dxc_path: None,
+            glslang_path: None,
+            include_dirs: vec![PathBuf::from(DEFAULT_INCLUDE_DIR)],
+            mesh_shaders: false,
+        }
+    }
+}
+
+impl ShaderCompilerConfig {
+    /// Returns the path to the DXC executable.
+    pub fn dxc_path(&self) -> Result<PathBuf> {
+        if let Some(path) = env::var_os(DXC_ENV_VAR) {
+            return Ok(PathBuf::from(path));
+        }
+        if let Some(path) = &self.dxc_path {
+            return Ok(path.clone());
+        }
+        if cfg!(target_os = "linux") {
+            Ok(PathBuf::from("/usr/bin/dxc"))
+        } else {
+            Ok(env::var("VULKAN_SDK").map(|sdk| PathBuf::from(&sdk).join("Bin/dxc"))?)
+        }
+    }
+
+    /// Returns the path to the glslangValidator executable.
+    pub fn glslang_path(&self) -> Result<PathBuf> {
+        if let Some(path) = env::var_os(GLSLANG_ENV_VAR) {
+            return Ok(PathBuf::from(path));
+        }
+        if let Some(path) = &self.glslang_path {
+            return Ok(path.clone());
+        }
+        if cfg!(target_os = "linux") {
+            Ok(PathBuf::from("/usr/bin/glslangValidator"))
+        } else {
+            Ok(env::var("VULKAN_SDK")
+                .map(|sdk| PathBuf::from(&sdk).join("Bin/glslangValidator"))?)
+        }
+    }
+}
@@ -5,7 +5,9 @@ use inject::DI;
 use phobos::{vk, ComputePipelineBuilder, PipelineBuilder, PipelineCache};
 use scheduler::EventBus;
 
-use crate::AddShaderEvent;
+use crate::reflection::ExpectedLayout;
+use crate::registry::register_pipeline;
+use crate::{AddShaderEvent, ShaderReload, DEFAULT_ENTRY_POINT};
 
 pub trait IntoDynamic {
     type Target;
@@ -16,6 +18,7 @@ pub trait IntoDynamic {
 #[derive(Debug)]
 struct ShaderInfo {
     path: PathBuf,
+    entry: String,
     stage: vk::ShaderStageFlags,
     pipeline: String,
 }
@@ -24,28 +27,73 @@ struct ShaderInfo {
 pub struct DynamicPipelineBuilder {
     inner: PipelineBuilder,
     shaders: Vec<ShaderInfo>,
+    layout: ExpectedLayout,
 }
 
 #[derive(Debug)]
 pub struct DynamicComputePipelineBuilder {
     inner: ComputePipelineBuilder,
     shader: Option<ShaderInfo>,
+    layout: ExpectedLayout,
+}
+
+/// Register the expected layout of a pipeline with the shader reload system.
+/// # DI Access
+/// - Read [`ShaderReload`]
+fn expect_layout(bus: &EventBus<DI>, name: &str, layout: ExpectedLayout) {
+    // Nothing was expected, so nothing needs to be validated.
+    if layout == ExpectedLayout::default() {
+        return;
+    }
+    let di = bus.data().read().unwrap();
+    if let Some(reload) = di.get::<ShaderReload>() {
+        reload.expect_layout(name, layout);
+    }
 }
 
 impl DynamicPipelineBuilder {
     #[must_use]
-    pub fn attach_shader(mut self, path: impl Into<PathBuf>, stage: vk::ShaderStageFlags) -> Self {
+    pub fn attach_shader(self, path: impl Into<PathBuf>, stage: vk::ShaderStageFlags) -> Self {
+        self.attach_shader_entry(path, DEFAULT_ENTRY_POINT, stage)
+    }
+
+    /// Attach a shader using a specific entry point, so one file can host multiple shaders.
+    #[must_use]
+    pub fn attach_shader_entry(
+        mut self,
+        path: impl Into<PathBuf>,
+        entry: impl Into<String>,
+        stage: vk::ShaderStageFlags,
+    ) -> Self {
         self.shaders.push(ShaderInfo {
             path: path.into(),
+            entry: entry.into(),
             stage,
             pipeline: self.inner.name().into(),
         });
         self
     }
 
+    /// Expect the shaders to use a descriptor binding. Once a binding is expected, the shaders must
+    /// use exactly the expected bindings. Mismatches are reported when the shaders are compiled.
+    #[must_use]
+    pub fn expect_binding(mut self, set: u32, binding: u32, ty: vk::DescriptorType) -> Self {
+        self.layout.bindings.push((set, binding, ty));
+        self
+    }
+
+    /// Expect the shaders to read no more than `size` bytes of push constants.
+    #[must_use]
+    pub fn expect_push_constants(mut self, size: u32) -> Self {
+        self.layout.push_constant_size = Some(size);
+        self
+    }
+
     /// Builds the pipeline using hot-reloadable shaders. You do not need to call `add_named_pipeline()` anymore after this
     pub fn build(self, bus: &mut EventBus<DI>, mut cache: PipelineCache) -> Result<()> {
         // TODO: Add pipeline cache to DI?
+        register_pipeline(bus, self.inner.name())?;
+        expect_layout(bus, self.inner.name(), self.layout);
         let pci = self.inner.build();
         cache.create_named_pipeline(pci)?;
 
@@ -55,6 +103,7 @@ impl DynamicPipelineBuilder {
             .map(|shader| {
                 bus.publish(AddShaderEvent {
                     path: shader.path,
+                    entry: shader.entry,
                     stage: shader.stage,
                     pipeline: shader.pipeline,
                 })
@@ -73,28 +122,53 @@ impl IntoDynamic for PipelineBuilder {
         DynamicPipelineBuilder {
             inner: self,
             shaders: vec![],
+            layout: ExpectedLayout::default(),
         }
     }
 }
 
 impl DynamicComputePipelineBuilder {
     #[must_use]
-    pub fn set_shader(mut self, path: impl Into<PathBuf>) -> Self {
+    pub fn set_shader(self, path: impl Into<PathBuf>) -> Self {
+        self.set_shader_entry(path, DEFAULT_ENTRY_POINT)
+    }
+
+    /// Set the shader using a specific entry point, so one file can host multiple shaders.
+    #[must_use]
+    pub fn set_shader_entry(mut self, path: impl Into<PathBuf>, entry: impl Into<String>) -> Self {
         self.shader = Some(ShaderInfo {
             path: path.into(),
+            entry: entry.into(),
             stage: vk::ShaderStageFlags::COMPUTE,
             pipeline: self.inner.name().into(),
         });
         self
     }
 
+    /// See [`DynamicPipelineBuilder::expect_binding`]
+    #[must_use]
+    pub fn expect_binding(mut self, set: u32, binding: u32, ty: vk::DescriptorType) -> Self {
+        self.layout.bindings.push((set, binding, ty));
+        self
+    }
+
+    /// See [`DynamicPipelineBuilder::expect_push_constants`]
+    #[must_use]
+    pub fn expect_push_constants(mut self, size: u32) -> Self {
+        self.layout.push_constant_size = Some(size);
+        self
+    }
+
     pub fn build(self, bus: &EventBus<DI>, mut cache: PipelineCache) -> Result<()> {
+        register_pipeline(bus, self.inner.name())?;
+        expect_layout(bus, self.inner.name(), self.layout);
         let pci = self.inner.build();
         cache.create_named_compute_pipeline(pci)?;
 
         let shader = self.shader.expect("Must set a shader");
         bus.publish(AddShaderEvent {
             path: shader.path,
+            entry: shader.entry,
             stage: shader.stage,
             pipeline: shader.pipeline,
         })?;
@@ -109,6 +183,7 @@ impl IntoDynamic for ComputePipelineBuilder {
         DynamicComputePipelineBuilder {
             inner: self,
             shader: None,
+            layout: ExpectedLayout::default(),
         }
     }
 }
@@ -1,12 +1,15 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Result;
 use futures::channel::mpsc::{channel, Receiver};
 use futures::{SinkExt, StreamExt};
 use log::{error, info};
-use notify::Watcher;
+use notify::{EventKind, Watcher};
+use tokio::time::{sleep_until, Instant};
 
 pub fn create_async_watcher(
 ) -> Result<(notify::RecommendedWatcher, Receiver<Result<notify::Event>>)> {
@@ -26,10 +29,18 @@ pub fn create_async_watcher(
     Ok((watcher, rx))
 }
 
-pub async fn async_watch<P, F>(path: P, recursive: bool, callback: F) -> Result<()>
+/// Watch a path for modified files. Editors often write a file several times when saving it,
+/// so `callback` is only called once no further modifications to a file were seen for
+/// `debounce`. Files are debounced independently of each other.
+pub async fn async_watch<P, F>(
+    path: P,
+    recursive: bool,
+    debounce: Duration,
+    callback: F,
+) -> Result<()>
 where
     P: AsRef<Path> + Debug,
-    F: Fn(notify::Event), {
+    F: Fn(PathBuf), {
     let (mut watcher, mut rx) = create_async_watcher()?;
 
     info!("Starting file watcher for path {path:?}, recursive = {recursive:?}");
@@ -50,11 +61,41 @@ where
         },
     )?;
 
-    while let Some(res) = rx.next().await {
-        match res {
-            Ok(event) => (callback)(event),
-            Err(err) => {
-                error!("File watcher error: {err:?}");
+    // Time at which each modified file is considered settled
+    let mut pending = HashMap::<PathBuf, Instant>::new();
+    loop {
+        let next_deadline = pending.values().min().copied();
+        let settled = async {
+            match next_deadline {
+                Some(deadline) => sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            res = rx.next() => match res {
+                None => break,
+                Some(Ok(event)) => {
+                    if let EventKind::Modify(_) = event.kind {
+                        for path in event.paths {
+                            pending.insert(path, Instant::now() + debounce);
+                        }
+                    }
+                }
+                Some(Err(err)) => {
+                    error!("File watcher error: {err:?}");
+                }
+            },
+            _ = settled => {
+                let now = Instant::now();
+                let settled = pending
+                    .iter()
+                    .filter(|(_, deadline)| **deadline <= now)
+                    .map(|(path, _)| path.clone())
+                    .collect::<Vec<_>>();
+                for path in settled {
+                    pending.remove(&path);
+                    (callback)(path);
+                }
             }
         }
     }
@@ -0,0 +1,84 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// Returns the file name in an `#include "file"` or `#include <file>` directive, or `None` if
+/// the line is not an include directive.
+fn include_directive(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("include")?.trim();
+    let (open, close) = match rest.chars().next()? {
+        '"' => ('"', '"'),
+        '<' => ('<', '>'),
+        _ => return None,
+    };
+    let rest = rest.strip_prefix(open)?;
+    let end = rest.find(close)?;
+    Some(&rest[..end])
+}
+
+/// Resolve an included file the way the compilers do: relative to the including file first,
+/// then in the include directories.
+fn resolve_include(name: &str, including: &Path, include_dirs: &[PathBuf]) -> Option<PathBuf> {
+    including
+        .parent()
+        .into_iter()
+        .chain(include_dirs.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+        .and_then(|path| fs::canonicalize(path).ok())
+}
+
+/// Returns the canonical paths of every file included by a shader, directly or through other
+/// included files. Includes that cannot be found are skipped, the compiler reports those.
+pub fn find_includes(path: &Path, include_dirs: &[PathBuf]) -> Result<HashSet<PathBuf>> {
+    let mut includes = HashSet::new();
+    let mut stack = vec![path.to_path_buf()];
+    while let Some(file) = stack.pop() {
+        let source = fs::read_to_string(&file)?;
+        for name in source.lines().filter_map(include_directive) {
+            let Some(included) = resolve_include(name, &file, include_dirs) else { continue };
+            if includes.insert(included.clone()) {
+                stack.push(included);
+            }
+        }
+    }
+    Ok(includes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_directives_are_parsed() {
+        assert_eq!(include_directive("#include \"color_space.hlsl\""), Some("color_space.hlsl"));
+        assert_eq!(include_directive("  #  include <terrain_uv.hlsl>"), Some("terrain_uv.hlsl"));
+        assert_eq!(include_directive("// #include \"commented.hlsl\""), None);
+        assert_eq!(include_directive("#define INCLUDE 1"), None);
+        assert_eq!(include_directive("#include"), None);
+    }
+
+    #[test]
+    fn includes_are_found_recursively() {
+        let dir = std::env::temp_dir().join(format!("hot_reload_includes_{}", std::process::id()));
+        let include_dir = dir.join("include");
+        fs::create_dir_all(&include_dir).unwrap();
+        fs::write(dir.join("shader.hlsl"), "#include \"a.hlsl\"\n#include \"missing.hlsl\"\n")
+            .unwrap();
+        fs::write(include_dir.join("a.hlsl"), "#include \"b.hlsl\"\n").unwrap();
+        // Includes each other, which must not loop forever
+        fs::write(include_dir.join("b.hlsl"), "#include \"a.hlsl\"\n").unwrap();
+        fs::write(include_dir.join("unused.hlsl"), "").unwrap();
+
+        let includes = find_includes(&dir.join("shader.hlsl"), &[include_dir.clone()]).unwrap();
+        let expected = ["a.hlsl", "b.hlsl"]
+            .iter()
+            .map(|name| fs::canonicalize(include_dir.join(name)).unwrap())
+            .collect::<HashSet<_>>();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(includes, expected);
+    }
+}
@@ -1,83 +1,329 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::ffi::OsStr;
-use std::fmt::Debug;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
-use std::{env, fs};
+use std::time::Duration;
 
-use anyhow::{ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
+pub use config::*;
 pub use dynamic_pipeline_builder::*;
+use error::{publish_error, publish_success, MessageEvent, MessageLevel};
 use inject::DI;
-use log::info;
-use notify::EventKind;
+use log::{error, info};
 use phobos::{prelude as ph, vk, PipelineCache, PipelineType};
+pub use reflection::*;
+pub use registry::*;
 use scheduler::{Event, EventBus, EventContext, StoredSystem, System};
 use tokio::task::JoinHandle;
 use util::safe_error::SafeUnwrap;
 use util::RwLock;
 
+use crate::includes::find_includes;
+
+pub mod config;
 pub mod dynamic_pipeline_builder;
-mod file_watcher;
+pub mod file_watcher;
+mod includes;
+pub mod reflection;
+pub mod registry;
+
+/// Entry point used for shaders that do not specify one.
+pub const DEFAULT_ENTRY_POINT: &str = "main";
 
 pub struct AddShaderEvent {
     path: PathBuf,
+    entry: String,
     stage: vk::ShaderStageFlags,
     pipeline: String,
 }
 
 impl Event for AddShaderEvent {}
 
+/// Recompile every watched shader and reload all pipelines using them.
+pub struct ReloadAllShadersEvent;
+
+impl Event for ReloadAllShadersEvent {}
+
+/// Recompile the shaders of a single pipeline and reload it.
+pub struct ReloadPipelineEvent {
+    pub pipeline: String,
+}
+
+impl Event for ReloadPipelineEvent {}
+
+/// Source language of a shader file, which decides the compiler used for it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    /// Compiled with DXC.
+    Hlsl,
+    /// Compiled with glslangValidator.
+    Glsl,
+}
+
+impl ShaderLanguage {
+    /// File extensions of GLSL shaders. GLSL files are commonly named after their stage.
+    const GLSL_EXTENSIONS: [&'static str; 7] =
+        ["glsl", "vert", "frag", "comp", "tesc", "tese", "geom"];
+
+    /// Returns the language of a shader file based on its extension, or `None` if it is not
+    /// a known shader file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        if extension == "hlsl" {
+            Some(Self::Hlsl)
+        } else if Self::GLSL_EXTENSIONS.contains(&extension) {
+            Some(Self::Glsl)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct ShaderInfo {
     stage: vk::ShaderStageFlags,
+    /// Files in a pipeline may use different languages, so every shader remembers its own.
+    language: ShaderLanguage,
     pipelines: Vec<String>,
 }
 
+/// Identifies a shader by its file and entry point, so a single file can host multiple shaders.
+type ShaderKey = (PathBuf, String);
+
+/// The shader compiler rejected a shader.
+#[derive(Debug)]
+pub struct ShaderCompileError {
+    pub path: PathBuf,
+    /// Full output of the compiler.
+    pub diagnostic: String,
+}
+
+impl Display for ShaderCompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Error compiling shader {:?}: {}", self.path, self.diagnostic)
+    }
+}
+
+impl std::error::Error for ShaderCompileError {}
+
 #[derive(Debug)]
 pub struct ShaderReloadInner {
     pipelines: PipelineCache,
-    shaders: HashMap<PathBuf, ShaderInfo>,
+    shaders: HashMap<ShaderKey, ShaderInfo>,
+    /// Reflection info of every compiled shader stage, per pipeline.
+    reflections: HashMap<String, Vec<Reflection>>,
+    /// Layouts pipelines are validated against after compiling their shaders.
+    expected_layouts: HashMap<String, ExpectedLayout>,
+    /// Compile shaders with `-fspv-reflect`.
+    spirv_reflect: bool,
+    compiler: ShaderCompilerConfig,
+    /// Shaders that failed to compile the last time they were compiled.
+    failed: HashSet<ShaderKey>,
+    /// Shaders that include a file, directly or through other included files, by the
+    /// canonical path of the included file.
+    dependents: HashMap<PathBuf, HashSet<ShaderKey>>,
+    bus: EventBus<DI>,
     watch_tasks: Vec<JoinHandle<Result<()>>>,
 }
 
+/// First non-empty line of a compiler diagnostic.
+fn first_line(diagnostic: &str) -> &str {
+    diagnostic
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default()
+}
+
+/// Combine the errors of reloading `pipelines` pipelines into one error. Compile errors are
+/// shortened to the first line of the diagnostic, since they are reported separately.
+fn combine_reload_errors(pipelines: usize, failures: &[(String, anyhow::Error)]) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    let failed = failures
+        .iter()
+        .map(|(pipeline, _)| pipeline)
+        .collect::<HashSet<_>>()
+        .len();
+    let details = failures
+        .iter()
+        .map(|(pipeline, e)| match e.downcast_ref::<ShaderCompileError>() {
+            Some(e) => {
+                let file_name = e.path.file_name().unwrap_or_default().to_string_lossy();
+                format!("{pipeline:?}: {file_name}: {}", first_line(&e.diagnostic))
+            }
+            None => format!("{pipeline:?}: {e}"),
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+    bail!("{failed} of {pipelines} pipelines failed to reload: {details}")
+}
+
+fn merge_reflections(stages: &[Reflection]) -> Reflection {
+    let mut reflection = Reflection::default();
+    for stage in stages {
+        reflection.merge(stage);
+    }
+    reflection
+}
+
+impl ShaderReloadInner {
+    /// Check the reflection info of the shader stages of a pipeline against its expected
+    /// layout, once all of its shaders have been compiled.
+    fn validate_layout(&self, pipeline: &str, stages: &[Reflection]) -> Result<()> {
+        let Some(expected) = self.expected_layouts.get(pipeline) else { return Ok(()) };
+        let shader_count = self
+            .shaders
+            .values()
+            .filter(|info| info.pipelines.iter().any(|name| name == pipeline))
+            .count();
+        if stages.len() < shader_count {
+            return Ok(());
+        }
+        expected
+            .validate(&merge_reflections(stages))
+            .map_err(|e| anyhow!("Pipeline {pipeline:?} does not match its expected layout: {e}"))
+    }
+
+    /// Record the files included by a shader, so it is reloaded when one of them changes.
+    fn track_includes(&mut self, path: &Path, entry_point: &str) {
+        let key = (path.to_path_buf(), entry_point.to_owned());
+        let includes = match find_includes(path, &self.compiler.include_dirs) {
+            Ok(includes) => includes,
+            Err(e) => {
+                error!("Could not find the files included by {path:?}: {e}");
+                return;
+            }
+        };
+        for dependents in self.dependents.values_mut() {
+            dependents.remove(&key);
+        }
+        for include in includes {
+            self.dependents
+                .entry(include)
+                .or_default()
+                .insert(key.clone());
+        }
+    }
+
+    /// Report a failed compile of a shader to the user, or that a shader that failed before
+    /// compiles again.
+    fn report_compile_result(
+        &mut self,
+        path: &Path,
+        entry_point: &str,
+        error: Option<&anyhow::Error>,
+    ) {
+        let key = (path.to_path_buf(), entry_point.to_owned());
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let message = match error {
+            Some(e) => {
+                self.failed.insert(key);
+                let diagnostic = match e.downcast_ref::<ShaderCompileError>() {
+                    Some(e) => e.diagnostic.clone(),
+                    None => e.to_string(),
+                };
+                MessageEvent::new(
+                    MessageLevel::Error,
+                    format!("Could not compile {file_name}: {}", first_line(&diagnostic)),
+                )
+                .with_details(diagnostic)
+            }
+            None if self.failed.remove(&key) => {
+                MessageEvent::new(MessageLevel::Success, format!("{file_name} compiles again."))
+            }
+            None => return,
+        };
+        // The shader reload lock is held here, and message handlers may need it, so publish
+        // from another thread.
+        let bus = self.bus.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = bus.publish(message.with_source("shader"));
+        });
+    }
+
+    fn merged_reflection(&self, pipeline: &str) -> Option<Reflection> {
+        self.reflections
+            .get(pipeline)
+            .map(|stages| merge_reflections(stages))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ShaderReload {
     inner: Arc<RwLock<ShaderReloadInner>>,
 }
 
 impl ShaderReload {
+    /// Create the shader reload system. If `watch` is false, shaders are never reloaded
+    /// automatically, but can still be reloaded manually. Modified files are reloaded once
+    /// they were not modified for `debounce_ms` milliseconds, since editors often write a file
+    /// several times when saving it.
+    /// If `spirv_reflect` is set, shaders are compiled with extra reflection info, such as
+    /// the HLSL types of their resources.
+    /// Compile errors are reported to the user with a [`MessageEvent`] on `bus`.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pipelines: PipelineCache,
         path: impl Into<PathBuf>,
         recursive: bool,
+        watch: bool,
+        debounce_ms: u64,
+        spirv_reflect: bool,
+        compiler: ShaderCompilerConfig,
+        bus: EventBus<DI>,
     ) -> Result<Self> {
         let this = ShaderReload {
             inner: Arc::new(RwLock::new(ShaderReloadInner {
                 pipelines,
                 shaders: HashMap::default(),
+                reflections: HashMap::default(),
+                expected_layouts: HashMap::default(),
+                spirv_reflect,
+                compiler,
+                failed: HashSet::default(),
+                dependents: HashMap::default(),
+                bus,
                 watch_tasks: vec![],
             })),
         };
 
-        let copy = this.clone();
-        let watcher =
-            tokio::spawn(file_watcher::async_watch(path.into(), recursive, move |event| {
-                copy.handle_file_event(event);
-            }));
+        if watch {
+            let copy = this.clone();
+            let watcher = tokio::spawn(file_watcher::async_watch(
+                path.into(),
+                recursive,
+                Duration::from_millis(debounce_ms),
+                move |path| copy.handle_file_modified(path),
+            ));
 
-        this.inner.write().unwrap().watch_tasks.push(watcher);
+            this.inner.write().unwrap().watch_tasks.push(watcher);
+        }
 
         Ok(this)
     }
 
-    pub fn add_shader(&mut self, path: &PathBuf, stage: vk::ShaderStageFlags, pipeline: &String) {
+    pub fn add_shader(
+        &mut self,
+        path: &PathBuf,
+        entry_point: &str,
+        stage: vk::ShaderStageFlags,
+        pipeline: &String,
+    ) {
+        let Some(language) = ShaderLanguage::from_path(path) else {
+            error!("Pipeline {pipeline:?} uses {path:?}, which is not a known shader file type");
+            return;
+        };
         let mut inner = self.inner.write().unwrap();
-        info!("Pipeline {pipeline:?} added to watch for shader {path:?}");
-        let entry = inner.shaders.entry(fs::canonicalize(path.clone()).unwrap());
+        info!("Pipeline {pipeline:?} added to watch for shader {path:?}, entry {entry_point:?}");
+        let key = (fs::canonicalize(path.clone()).unwrap(), entry_point.to_owned());
+        let path = key.0.clone();
+        let entry = inner.shaders.entry(key);
         match entry {
             Entry::Occupied(entry) => {
                 entry.into_mut().pipelines.push(pipeline.clone());
@@ -85,46 +331,65 @@ impl ShaderReload {
             Entry::Vacant(entry) => {
                 entry.insert(ShaderInfo {
                     stage,
+                    language,
                     pipelines: vec![pipeline.clone()],
                 });
             }
         };
-        self.reload_pipeline(path.as_path(), pipeline, &mut inner.pipelines, stage)
+        Self::reload_pipeline(&mut inner, path.as_path(), entry_point, pipeline, stage, language)
             .safe_unwrap();
     }
 
-    pub fn handle_file_event(&self, event: notify::Event) {
-        let notify::Event {
-            kind,
-            paths,
-            ..
-        } = event;
-        if let EventKind::Modify(_) = kind {
-            for path in paths {
-                if path.extension().unwrap_or(OsStr::new("")) == "hlsl" {
-                    self.reload_file(path).safe_unwrap();
-                }
-            }
-        }
+    /// Validate the layout of a pipeline every time its shaders are compiled.
+    /// Must be called before the shaders are added to be checked on the first compile.
+    pub fn expect_layout(&self, pipeline: &str, layout: ExpectedLayout) {
+        let mut inner = self.inner.write().unwrap();
+        inner.expected_layouts.insert(pipeline.to_owned(), layout);
     }
 
-    fn get_dxc_path() -> Result<PathBuf> {
-        if cfg!(target_os = "linux") {
-            Ok(PathBuf::from("/usr/bin/dxc"))
-        } else {
-            Ok(env::var("VULKAN_SDK").map(|sdk| PathBuf::from(&sdk).join("Bin/dxc"))?)
+    /// Returns the shader stages, descriptor bindings and push constant ranges used by a pipeline,
+    /// or `None` if none of its shaders were compiled successfully.
+    pub fn pipeline_reflection(&self, pipeline: &str) -> Option<Reflection> {
+        self.inner.read().unwrap().merged_reflection(pipeline)
+    }
+
+    /// Reload the shaders in a modified file. Compiling is slow, so this is done on a separate
+    /// thread to not hold up the file watcher.
+    pub fn handle_file_modified(&self, path: PathBuf) {
+        if ShaderLanguage::from_path(&path).is_some() {
+            let this = self.clone();
+            tokio::task::spawn_blocking(move || this.reload_file(path).safe_unwrap());
         }
     }
 
-    fn get_output_path(path: &Path) -> Result<PathBuf> {
+    fn get_output_path(path: &Path, entry_point: &str) -> Result<PathBuf> {
         let prefix = path.parent().unwrap();
         fs::create_dir_all(prefix)?;
-        Ok(prefix
-            .join("out/")
-            .join(path.file_name().unwrap().to_str().unwrap().to_owned() + ".spv"))
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        // Every entry point is compiled to its own module
+        let out_name = match entry_point {
+            DEFAULT_ENTRY_POINT => format!("{file_name}.spv"),
+            entry_point => format!("{file_name}.{entry_point}.spv"),
+        };
+        Ok(prefix.join("out/").join(out_name))
     }
 
-    fn hlsl_profile(stage: vk::ShaderStageFlags) -> Result<String> {
+    /// Mesh and task shaders need a device extension, so they are only compiled if enabled.
+    fn ensure_stage_supported(
+        stage: vk::ShaderStageFlags,
+        config: &ShaderCompilerConfig,
+    ) -> Result<()> {
+        let mesh_stage =
+            stage == vk::ShaderStageFlags::MESH_EXT || stage == vk::ShaderStageFlags::TASK_EXT;
+        ensure!(
+            !mesh_stage || config.mesh_shaders,
+            "Shader stage {stage:?} requires mesh shaders, which are not enabled"
+        );
+        Ok(())
+    }
+
+    fn hlsl_profile(stage: vk::ShaderStageFlags, config: &ShaderCompilerConfig) -> Result<String> {
+        Self::ensure_stage_supported(stage, config)?;
         Ok(match stage {
             vk::ShaderStageFlags::VERTEX => "vs",
             vk::ShaderStageFlags::FRAGMENT => "ps",
@@ -133,12 +398,34 @@ impl ShaderReload {
             vk::ShaderStageFlags::TESSELLATION_CONTROL => "hs",
             // Tessellation evaluation in HLSL is a Domain Shader
             vk::ShaderStageFlags::TESSELLATION_EVALUATION => "ds",
-            _ => todo!(),
+            vk::ShaderStageFlags::GEOMETRY => "gs",
+            vk::ShaderStageFlags::MESH_EXT => "ms",
+            // Task shaders in HLSL are Amplification Shaders
+            vk::ShaderStageFlags::TASK_EXT => "as",
+            _ => bail!("Shader stage {stage:?} is not supported by the HLSL compiler"),
         }
         .to_owned()
             + "_6_7")
     }
 
+    fn glsl_stage(
+        stage: vk::ShaderStageFlags,
+        config: &ShaderCompilerConfig,
+    ) -> Result<&'static str> {
+        Self::ensure_stage_supported(stage, config)?;
+        Ok(match stage {
+            vk::ShaderStageFlags::VERTEX => "vert",
+            vk::ShaderStageFlags::FRAGMENT => "frag",
+            vk::ShaderStageFlags::COMPUTE => "comp",
+            vk::ShaderStageFlags::TESSELLATION_CONTROL => "tesc",
+            vk::ShaderStageFlags::TESSELLATION_EVALUATION => "tese",
+            vk::ShaderStageFlags::GEOMETRY => "geom",
+            vk::ShaderStageFlags::MESH_EXT => "mesh",
+            vk::ShaderStageFlags::TASK_EXT => "task",
+            _ => bail!("Shader stage {stage:?} is not supported by the GLSL compiler"),
+        })
+    }
+
     fn load_spirv_file(path: &Path) -> Result<Vec<u32>> {
         let mut f = File::open(path)?;
         let metadata = fs::metadata(path)?;
@@ -150,47 +437,124 @@ impl ShaderReload {
     }
 
     #[allow(clippy::suspicious_command_arg_space)]
-    fn compile_hlsl(path: &Path, stage: vk::ShaderStageFlags) -> Result<Vec<u32>> {
-        let out = Self::get_output_path(path)?;
-        let dxc = Self::get_dxc_path()?;
-        let output = Command::new(dxc)
-            // Entry point: 'main'
-            .arg("-E main")
+    fn compile_hlsl(
+        path: &Path,
+        entry_point: &str,
+        stage: vk::ShaderStageFlags,
+        config: &ShaderCompilerConfig,
+        spirv_reflect: bool,
+    ) -> Result<Vec<u32>> {
+        let out = Self::get_output_path(path, entry_point)?;
+        let dxc = config.dxc_path()?;
+        let mut command = Command::new(dxc);
+        // Emit SPIR-V reflection info. This causes DXC to emit the SPV_GOOGLE_hlsl_functionality1 extension,
+        // which would then have to be enabled in Vulkan. ash does not support it, so it is stripped
+        // from the module after reflecting it, see [`strip_reflection_info`].
+        if spirv_reflect {
+            command.arg("-fspv-reflect");
+        }
+        for dir in &config.include_dirs {
+            command.arg("-I").arg(dir);
+        }
+        let output = command
+            // Entry point in the HLSL source
+            .arg("-E ".to_owned() + entry_point)
+            // Pipelines always look for 'main', so rename the entry point in the SPIR-V module
+            .arg("-fspv-entrypoint-name=".to_owned() + DEFAULT_ENTRY_POINT)
             // Output file
             .arg("-Fo".to_owned() + out.to_str().unwrap())
             // HLSL version 2021
             .arg("-HV 2021")
             // HLSL profile depending on shader stage
-            .arg("-T ".to_owned() + &Self::hlsl_profile(stage)?)
-            // Emit SPIR-V reflection info.
-            // Note that we disable this for now, because this causes DXC to emit the SPV_GOOGLE_hlsl_functionality1 extension,
-            // which we then have to enable in Vulkan. This is possible, but not really desired and ash does not support it, so preferably
-            // reflection just works without this flag too.
-            // .arg("-fspv-reflect")
+            .arg("-T ".to_owned() + &Self::hlsl_profile(stage, config)?)
             // SPIR-V target env
             .arg("-fspv-target-env=vulkan1.3")
-            // Add include path
-            .arg("-I shaders/include")
             // Actually generate SPIR-V
             .arg("-spirv")
             // Our input file
             .arg(path)
             .output()?;
 
-        ensure!(
-            output.status.success(),
-            "Error compiling shader {path:?}: {}",
-            String::from_utf8(output.stderr).unwrap()
-        );
+        if !output.status.success() {
+            return Err(ShaderCompileError {
+                path: path.to_path_buf(),
+                diagnostic: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
+        }
+        Self::load_spirv_file(&out)
+    }
+
+    fn compile_glsl(
+        path: &Path,
+        entry_point: &str,
+        stage: vk::ShaderStageFlags,
+        config: &ShaderCompilerConfig,
+    ) -> Result<Vec<u32>> {
+        let out = Self::get_output_path(path, entry_point)?;
+        let glslang = config.glslang_path()?;
+        let mut command = Command::new(glslang);
+        // GLSL entry points are always called 'main', other functions in the source can be
+        // compiled as the entry point by renaming them.
+        if entry_point != DEFAULT_ENTRY_POINT {
+            command.arg("--source-entrypoint").arg(entry_point);
+        }
+        for dir in &config.include_dirs {
+            command.arg(format!("-I{}", dir.display()));
+        }
+        let output = command
+            // Generate SPIR-V for Vulkan
+            .arg("-V")
+            // Name of the entry point in the SPIR-V module
+            .arg("-e")
+            .arg(DEFAULT_ENTRY_POINT)
+            // Shader stage, the file extension is not always the stage
+            .arg("-S")
+            .arg(Self::glsl_stage(stage, config)?)
+            // SPIR-V target env
+            .arg("--target-env")
+            .arg("vulkan1.3")
+            // Output file
+            .arg("-o")
+            .arg(&out)
+            // Our input file
+            .arg(path)
+            .output()?;
+
+        // glslangValidator reports errors on stdout
+        if !output.status.success() {
+            return Err(ShaderCompileError {
+                path: path.to_path_buf(),
+                diagnostic: String::from_utf8_lossy(&output.stdout).into_owned(),
+            }
+            .into());
+        }
         Self::load_spirv_file(&out)
     }
 
+    fn compile_shader(
+        path: &Path,
+        entry_point: &str,
+        stage: vk::ShaderStageFlags,
+        language: ShaderLanguage,
+        config: &ShaderCompilerConfig,
+        spirv_reflect: bool,
+    ) -> Result<Vec<u32>> {
+        match language {
+            ShaderLanguage::Hlsl => {
+                Self::compile_hlsl(path, entry_point, stage, config, spirv_reflect)
+            }
+            ShaderLanguage::Glsl => Self::compile_glsl(path, entry_point, stage, config),
+        }
+    }
+
     fn reload_pipeline(
-        &self,
+        inner: &mut ShaderReloadInner,
         shader: &Path,
+        entry_point: &str,
         pipeline: &str,
-        pipelines: &mut ph::PipelineCache,
         stage: vk::ShaderStageFlags,
+        language: ShaderLanguage,
     ) -> Result<()> {
         info!("Reloading pipeline {pipeline:?}");
         // let mut file = File::open(shader).await?;
@@ -205,7 +569,31 @@ impl ShaderReload {
         // let mut compiler = shaderc::Compiler::new().unwrap();
         // let mut options = shaderc::CompileOptions::new().unwrap();
         // let result = compiler.compile_into_spirv(&source, kind, shader.file_name().unwrap().to_str().unwrap(), "main", Some(&options))?;
-        let binary = Self::compile_hlsl(shader, stage)?;
+        // Includes are tracked before compiling, so fixing an included file reloads the shader.
+        inner.track_includes(shader, entry_point);
+        let binary = Self::compile_shader(
+            shader,
+            entry_point,
+            stage,
+            language,
+            &inner.compiler,
+            inner.spirv_reflect,
+        );
+        inner.report_compile_result(shader, entry_point, binary.as_ref().err());
+        let binary = binary?;
+        let reflection = reflect_spirv(&binary)?;
+        let binary = strip_reflection_info(&binary)?;
+        // Validate the new stage before creating the pipeline, so a shader that no longer
+        // matches the layout the application expects keeps the previous pipeline.
+        let mut stages = inner
+            .reflections
+            .get(pipeline)
+            .cloned()
+            .unwrap_or_default();
+        stages.retain(|reflection| reflection.stages != stage);
+        stages.push(reflection);
+        inner.validate_layout(pipeline, &stages)?;
+        let pipelines = &mut inner.pipelines;
         match pipelines.pipeline_type(pipeline) {
             None => {}
             Some(PipelineType::Graphics) => {
@@ -232,43 +620,101 @@ impl ShaderReload {
             }
         }
 
+        inner.reflections.insert(pipeline.to_owned(), stages);
         Ok(())
     }
 
-    fn reload_file(&self, path: PathBuf) -> Result<()> {
-        // If our shader was an included shader, we naively reload all pipelines
-        let inner = self.inner.write().unwrap();
-        let mut pipelines = inner.pipelines.clone();
-        if path.to_str().unwrap().contains("shaders\\include\\") {
-            info!(
-                "Included shader {:?} changed. Reloading all pipelines.",
-                path.file_name().unwrap()
-            );
-            for (path, info) in &inner.shaders {
-                for pipeline in &info.pipelines {
-                    self.reload_pipeline(path, pipeline, &mut pipelines, info.stage)?;
+    /// Reload the pipelines of every shader in `shaders`. A pipeline that fails to reload does
+    /// not stop the others from being reloaded, the failures are combined into one error.
+    fn reload_shaders(
+        inner: &mut ShaderReloadInner,
+        shaders: &[(ShaderKey, ShaderInfo)],
+    ) -> Result<()> {
+        let mut pipelines = HashSet::new();
+        let mut failures = vec![];
+        for ((path, entry_point), info) in shaders {
+            for pipeline in &info.pipelines {
+                pipelines.insert(pipeline.as_str());
+                let result = Self::reload_pipeline(
+                    inner,
+                    path,
+                    entry_point,
+                    pipeline,
+                    info.stage,
+                    info.language,
+                );
+                if let Err(e) = result {
+                    error!("Could not reload pipeline {pipeline:?}: {e}");
+                    failures.push((pipeline.clone(), e));
                 }
             }
-            return Ok(());
         }
+        combine_reload_errors(pipelines.len(), &failures)
+    }
 
+    /// Recompile all watched shaders and reload every pipeline.
+    pub fn reload_all(&self) -> Result<()> {
+        let mut inner = self.inner.write().unwrap();
+        let shaders = inner.shaders.clone().into_iter().collect::<Vec<_>>();
+        Self::reload_shaders(&mut inner, &shaders)
+    }
+
+    /// Recompile all shaders used by a pipeline and reload it.
+    pub fn reload_pipeline_by_name(&self, pipeline: &str) -> Result<()> {
+        let mut inner = self.inner.write().unwrap();
+        let shaders = inner
+            .shaders
+            .iter()
+            .filter(|(_, info)| info.pipelines.iter().any(|name| name == pipeline))
+            .map(|(key, info)| {
+                // Only this pipeline is reloaded, even if the shader is shared
+                let info = ShaderInfo {
+                    pipelines: vec![pipeline.to_owned()],
+                    ..info.clone()
+                };
+                (key.clone(), info)
+            })
+            .collect::<Vec<_>>();
+        ensure!(!shaders.is_empty(), "Pipeline {pipeline:?} has no watched shaders");
+        Self::reload_shaders(&mut inner, &shaders)
+    }
+
+    fn reload_file(&self, path: PathBuf) -> Result<()> {
         // CLion always saves quickly files with a ~ suffix first for some reason, so we add a quick hack to ignore this temporary file
         if path.file_name().unwrap().to_str().unwrap().ends_with('~') {
             return Ok(());
         }
+        // Shaders and their includes are tracked by canonical path
+        let path = fs::canonicalize(&path).unwrap_or(path);
+
+        let mut inner = self.inner.write().unwrap();
         info!("Reloading shader file {:?}", path.file_name().unwrap());
-        // Get all involved pipelines
-        let info = inner
+        // Get all involved entry points and their pipelines
+        let mut shaders = inner
             .shaders
-            .get(path.as_path())
-            .ok_or_else(|| {
-                anyhow::anyhow!("Shader path not in watchlist: {:?}", path.file_name().unwrap())
-            })
-            .cloned()?;
-        for pipeline in &info.pipelines {
-            self.reload_pipeline(&path, pipeline, &mut pipelines, info.stage)?;
+            .iter()
+            .filter(|((shader_path, _), _)| shader_path == &path)
+            .map(|(key, info)| (key.clone(), info.clone()))
+            .collect::<Vec<_>>();
+        // If the file is included by other shaders, only those shaders need to be reloaded
+        if let Some(dependents) = inner.dependents.get(&path) {
+            info!(
+                "Included file {:?} changed, reloading {} shaders including it",
+                path.file_name().unwrap(),
+                dependents.len()
+            );
+            shaders.extend(
+                dependents
+                    .iter()
+                    .filter_map(|key| Some((key.clone(), inner.shaders.get(key)?.clone()))),
+            );
         }
-        Ok(())
+        ensure!(
+            !shaders.is_empty(),
+            "Shader path not in watchlist: {:?}",
+            path.file_name().unwrap()
+        );
+        Self::reload_shaders(&mut inner, &shaders)
     }
 }
 
@@ -277,6 +723,8 @@ impl System<DI> for ShaderReload {
     where
         Self: Sized, {
         event_bus.subscribe(system, handle_add_shader);
+        event_bus.subscribe(system, handle_reload_all_shaders);
+        event_bus.subscribe(system, handle_reload_pipeline);
     }
 }
 
@@ -285,19 +733,147 @@ fn handle_add_shader(
     event: &AddShaderEvent,
     _ctx: &mut EventContext<DI>,
 ) -> Result<()> {
-    state.add_shader(&event.path, event.stage, &event.pipeline);
+    state.add_shader(&event.path, &event.entry, event.stage, &event.pipeline);
+    Ok(())
+}
+
+/// Shader compilation is slow, so reloading is done on a separate thread.
+/// The result is reported with a [`MessageEvent`](error::MessageEvent).
+fn handle_reload_all_shaders(
+    state: &mut ShaderReload,
+    _event: &ReloadAllShadersEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let state = state.clone();
+    let bus = ctx.bus().clone();
+    tokio::task::spawn_blocking(move || match state.reload_all() {
+        Ok(_) => {
+            publish_success!(bus, source = "shader", "Reloaded all shaders.");
+        }
+        Err(e) => {
+            publish_error!(bus, source = "shader", "Error reloading shaders: {e}");
+        }
+    });
+    Ok(())
+}
+
+/// See [`handle_reload_all_shaders`]
+fn handle_reload_pipeline(
+    state: &mut ShaderReload,
+    event: &ReloadPipelineEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let state = state.clone();
+    let bus = ctx.bus().clone();
+    let pipeline = event.pipeline.clone();
+    tokio::task::spawn_blocking(move || match state.reload_pipeline_by_name(&pipeline) {
+        Ok(_) => {
+            publish_success!(bus, source = "shader", "Reloaded pipeline {pipeline:?}.");
+        }
+        Err(e) => {
+            publish_error!(bus, source = "shader", "Error reloading pipeline: {e}");
+        }
+    });
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn initialize(
     pipelines: PipelineCache,
     path: impl Into<PathBuf>,
     recursive: bool,
+    watch: bool,
+    debounce_ms: u64,
+    spirv_reflect: bool,
+    compiler: ShaderCompilerConfig,
     bus: &mut EventBus<DI>,
 ) -> Result<()> {
-    let state = ShaderReload::new(pipelines, path, recursive)?;
+    let state = ShaderReload::new(
+        pipelines,
+        path,
+        recursive,
+        watch,
+        debounce_ms,
+        spirv_reflect,
+        compiler,
+        bus.clone(),
+    )?;
     bus.add_system(state.clone());
     let mut di = bus.data().write().unwrap();
     di.put(state);
+    di.put_sync(PipelineRegistry::new());
     Ok(())
 }
+
+/// Returns the reflection info of a pipeline, see [`ShaderReload::pipeline_reflection`].
+/// # DI Access
+/// - Read [`ShaderReload`]
+pub fn pipeline_reflection(bus: &EventBus<DI>, pipeline: &str) -> Option<Reflection> {
+    let di = bus.data().read().unwrap();
+    di.get::<ShaderReload>()?.pipeline_reflection(pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shader_language_is_detected_from_the_extension() {
+        let language = |path: &str| ShaderLanguage::from_path(Path::new(path));
+        assert_eq!(language("shaders/src/terrain.vs.hlsl"), Some(ShaderLanguage::Hlsl));
+        assert_eq!(language("shaders/src/blur.comp"), Some(ShaderLanguage::Glsl));
+        assert_eq!(language("shaders/src/sky.frag"), Some(ShaderLanguage::Glsl));
+        assert_eq!(language("shaders/src/common.glsl"), Some(ShaderLanguage::Glsl));
+        assert_eq!(language("shaders/src/terrain.vs.hlsl~"), None);
+        assert_eq!(language("shaders/README.md"), None);
+        assert_eq!(language("shaders/src/noextension"), None);
+    }
+
+    #[test]
+    fn hlsl_profiles_include_the_shader_model() {
+        let config = ShaderCompilerConfig::default();
+        let profile = |stage| ShaderReload::hlsl_profile(stage, &config).unwrap();
+        assert_eq!(profile(vk::ShaderStageFlags::VERTEX), "vs_6_7");
+        assert_eq!(profile(vk::ShaderStageFlags::TESSELLATION_CONTROL), "hs_6_7");
+        assert_eq!(profile(vk::ShaderStageFlags::GEOMETRY), "gs_6_7");
+    }
+
+    #[test]
+    fn mesh_shader_stages_require_mesh_shaders() {
+        let mut config = ShaderCompilerConfig::default();
+        assert!(ShaderReload::hlsl_profile(vk::ShaderStageFlags::MESH_EXT, &config).is_err());
+        assert!(ShaderReload::glsl_stage(vk::ShaderStageFlags::TASK_EXT, &config).is_err());
+        config.mesh_shaders = true;
+        let profile = |stage| ShaderReload::hlsl_profile(stage, &config).unwrap();
+        assert_eq!(profile(vk::ShaderStageFlags::MESH_EXT), "ms_6_7");
+        assert_eq!(profile(vk::ShaderStageFlags::TASK_EXT), "as_6_7");
+    }
+
+    #[test]
+    fn unsupported_stages_are_errors() {
+        let config = ShaderCompilerConfig::default();
+        let stage = vk::ShaderStageFlags::RAYGEN_KHR;
+        assert!(ShaderReload::hlsl_profile(stage, &config).is_err());
+        assert!(ShaderReload::glsl_stage(stage, &config).is_err());
+    }
+
+    #[test]
+    fn reload_failures_are_combined() {
+        assert!(combine_reload_errors(3, &[]).is_ok());
+        let compile_error = ShaderCompileError {
+            path: PathBuf::from("shaders/src/sky.fs.hlsl"),
+            diagnostic: "\nsky.fs.hlsl:3: error: unknown type\nmore details".to_owned(),
+        };
+        let failures = [
+            ("sky".to_owned(), anyhow::Error::new(compile_error)),
+            ("sky".to_owned(), anyhow!("layout mismatch")),
+            ("terrain".to_owned(), anyhow!("layout mismatch")),
+        ];
+        let error = combine_reload_errors(3, &failures).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "2 of 3 pipelines failed to reload: \"sky\": sky.fs.hlsl: sky.fs.hlsl:3: error: \
+             unknown type; \"sky\": layout mismatch; \"terrain\": layout mismatch"
+        );
+    }
+}
@@ -0,0 +1,724 @@
+//! Minimal SPIR-V reflection of the resources used by a shader module.
+//! Only the information needed to validate pipeline layouts is extracted: descriptor bindings
+//! and push constant ranges.
+//!
+//! This is a best-effort check to catch shaders that drifted from the Rust side while editing
+//! them, not a full reflection library. Instructions that are not listed below are skipped, so
+//! resources declared through them are not reported.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, ensure, Result};
+use phobos::vk;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+// Opcodes
+const OP_NAME: u32 = 5;
+const OP_EXTENSION: u32 = 10;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_DECORATE_ID: u32 = 332;
+const OP_TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+const OP_DECORATE_STRING: u32 = 5632;
+const OP_MEMBER_DECORATE_STRING: u32 = 5633;
+
+// Decorations
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+const DECORATION_COUNTER_BUFFER: u32 = 5634;
+const DECORATION_USER_SEMANTIC: u32 = 5635;
+const DECORATION_USER_TYPE: u32 = 5636;
+
+// Storage classes
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+// Image dimensions
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// Extensions emitted by dxc when compiling with `-fspv-reflect`. These only carry extra
+/// reflection info, so they are removed before the module is handed to Vulkan.
+const REFLECTION_EXTENSIONS: [&str; 2] = ["SPV_GOOGLE_hlsl_functionality1", "SPV_GOOGLE_user_type"];
+
+/// A single descriptor binding used by a shader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub ty: vk::DescriptorType,
+    /// Number of descriptors in this binding. Zero for runtime-sized arrays.
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    /// Name of the resource in the shader source, if known.
+    pub name: Option<String>,
+    /// HLSL type of the resource, such as `rwtexture2d:<float>`.
+    /// Only available if the shader was compiled with reflection info.
+    pub user_type: Option<String>,
+}
+
+/// A range of push constants read by a shader stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PushConstantRange {
+    pub stages: vk::ShaderStageFlags,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl PushConstantRange {
+    /// One past the last byte read by the shader.
+    pub fn end(&self) -> u32 {
+        self.offset + self.size
+    }
+}
+
+/// Resources used by all shader stages of a pipeline.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reflection {
+    pub stages: vk::ShaderStageFlags,
+    /// Descriptor bindings, sorted by set and binding.
+    pub bindings: Vec<DescriptorBinding>,
+    pub push_constants: Vec<PushConstantRange>,
+}
+
+impl Reflection {
+    /// Find the binding at a set and binding index.
+    pub fn binding(&self, set: u32, binding: u32) -> Option<&DescriptorBinding> {
+        self.bindings
+            .iter()
+            .find(|b| b.set == set && b.binding == binding)
+    }
+
+    /// Combine the resources of another set of shader stages into this one.
+    pub fn merge(&mut self, other: &Reflection) {
+        self.stages |= other.stages;
+        for binding in &other.bindings {
+            match self
+                .bindings
+                .iter_mut()
+                .find(|b| b.set == binding.set && b.binding == binding.binding)
+            {
+                Some(existing) => existing.stages |= binding.stages,
+                None => self.bindings.push(binding.clone()),
+            }
+        }
+        self.bindings.sort_by_key(|b| (b.set, b.binding));
+        self.push_constants
+            .extend(other.push_constants.iter().copied());
+    }
+}
+
+/// The pipeline layout a pipeline is expected to have. This is checked against the reflection
+/// info of its shaders every time they are compiled, so a mismatch is reported as an error
+/// instead of showing up as validation layer messages when the pipeline is used.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpectedLayout {
+    pub bindings: Vec<(u32, u32, vk::DescriptorType)>,
+    /// Amount of push constant bytes supplied when the pipeline is used.
+    pub push_constant_size: Option<u32>,
+}
+
+impl ExpectedLayout {
+    /// Check the reflected resources against this layout. All mismatches are reported at once.
+    pub fn validate(&self, reflection: &Reflection) -> Result<()> {
+        let mut errors = vec![];
+        for &(set, binding, ty) in &self.bindings {
+            match reflection.binding(set, binding) {
+                None => {
+                    errors.push(format!("binding ({set}, {binding}) is not used by the shader"))
+                }
+                Some(found) if found.ty != ty => errors.push(format!(
+                    "binding ({set}, {binding}) is a {:?} in the shader, but {ty:?} was expected",
+                    found.ty
+                )),
+                Some(_) => {}
+            }
+        }
+        // Unused bindings are only reported if any bindings were expected
+        if !self.bindings.is_empty() {
+            for found in &reflection.bindings {
+                if !self
+                    .bindings
+                    .iter()
+                    .any(|&(set, binding, _)| set == found.set && binding == found.binding)
+                {
+                    errors.push(format!(
+                        "shader uses unexpected binding ({}, {}) ({:?})",
+                        found.set, found.binding, found.ty
+                    ));
+                }
+            }
+        }
+        if let Some(size) = self.push_constant_size {
+            for range in &reflection.push_constants {
+                if range.end() > size {
+                    errors.push(format!(
+                        "{:?} stage reads {} bytes of push constants, but only {size} are supplied",
+                        range.stages,
+                        range.end()
+                    ));
+                }
+            }
+        }
+        ensure!(errors.is_empty(), "{}", errors.join(", "));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Type {
+    Scalar {
+        width: u32,
+    },
+    Vector {
+        component: u32,
+        count: u32,
+    },
+    Matrix {
+        column: u32,
+        count: u32,
+    },
+    Image {
+        dim: u32,
+        sampled: u32,
+    },
+    Sampler,
+    SampledImage,
+    AccelerationStructure,
+    Array {
+        element: u32,
+        length: u32,
+    },
+    RuntimeArray {
+        element: u32,
+    },
+    Struct,
+    Pointer {
+        pointee: u32,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    types: HashMap<u32, Type>,
+    struct_members: HashMap<u32, Vec<u32>>,
+    constants: HashMap<u32, u32>,
+    /// Decorations on ids, as (decoration, first literal)
+    decorations: HashMap<u32, Vec<(u32, u32)>>,
+    member_offsets: HashMap<(u32, u32), u32>,
+    matrix_strides: HashMap<(u32, u32), u32>,
+    user_types: HashMap<u32, String>,
+    variables: Vec<(u32, u32, u32)>,
+    stages: vk::ShaderStageFlags,
+}
+
+/// Iterate over all instructions in a module as (opcode, operands).
+fn instructions(binary: &[u32]) -> Result<impl Iterator<Item = (u32, &[u32])>> {
+    ensure!(binary.len() >= HEADER_WORDS && binary[0] == SPIRV_MAGIC, "Not a SPIR-V module");
+    let mut words = &binary[HEADER_WORDS..];
+    let mut result = vec![];
+    while let Some(&first) = words.first() {
+        let count = (first >> 16) as usize;
+        ensure!(count > 0 && count <= words.len(), "Malformed SPIR-V instruction");
+        result.push((first & 0xffff, &words[1..count]));
+        words = &words[count..];
+    }
+    Ok(result.into_iter())
+}
+
+/// Decode a nul-terminated string literal.
+fn literal_string(words: &[u32]) -> String {
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&byte| byte != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn execution_model_stage(model: u32) -> vk::ShaderStageFlags {
+    match model {
+        0 => vk::ShaderStageFlags::VERTEX,
+        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => vk::ShaderStageFlags::GEOMETRY,
+        4 => vk::ShaderStageFlags::FRAGMENT,
+        5 => vk::ShaderStageFlags::COMPUTE,
+        _ => vk::ShaderStageFlags::empty(),
+    }
+}
+
+impl Module {
+    fn parse(binary: &[u32]) -> Result<Self> {
+        let mut module = Module::default();
+        for (opcode, ops) in instructions(binary)? {
+            match (opcode, ops) {
+                (OP_NAME, [target, name @ ..]) => {
+                    module.names.insert(*target, literal_string(name));
+                }
+                (OP_ENTRY_POINT, [model, ..]) => {
+                    module.stages |= execution_model_stage(*model);
+                }
+                (OP_DECORATE, [target, decoration, rest @ ..]) => {
+                    module
+                        .decorations
+                        .entry(*target)
+                        .or_default()
+                        .push((*decoration, rest.first().copied().unwrap_or(0)));
+                }
+                (OP_MEMBER_DECORATE, [ty, member, decoration, value, ..]) => match *decoration {
+                    DECORATION_OFFSET => {
+                        module.member_offsets.insert((*ty, *member), *value);
+                    }
+                    DECORATION_MATRIX_STRIDE => {
+                        module.matrix_strides.insert((*ty, *member), *value);
+                    }
+                    _ => {}
+                },
+                (OP_DECORATE_STRING, [target, DECORATION_USER_TYPE, value @ ..]) => {
+                    module.user_types.insert(*target, literal_string(value));
+                }
+                (OP_TYPE_INT | OP_TYPE_FLOAT, [result, width, ..]) => {
+                    module.types.insert(
+                        *result,
+                        Type::Scalar {
+                            width: *width,
+                        },
+                    );
+                }
+                (OP_TYPE_VECTOR, [result, component, count]) => {
+                    module.types.insert(
+                        *result,
+                        Type::Vector {
+                            component: *component,
+                            count: *count,
+                        },
+                    );
+                }
+                (OP_TYPE_MATRIX, [result, column, count]) => {
+                    module.types.insert(
+                        *result,
+                        Type::Matrix {
+                            column: *column,
+                            count: *count,
+                        },
+                    );
+                }
+                (OP_TYPE_IMAGE, [result, _, dim, _, _, _, sampled, ..]) => {
+                    module.types.insert(
+                        *result,
+                        Type::Image {
+                            dim: *dim,
+                            sampled: *sampled,
+                        },
+                    );
+                }
+                (OP_TYPE_SAMPLER, [result]) => {
+                    module.types.insert(*result, Type::Sampler);
+                }
+                (OP_TYPE_SAMPLED_IMAGE, [result, _]) => {
+                    module.types.insert(*result, Type::SampledImage);
+                }
+                (OP_TYPE_ACCELERATION_STRUCTURE, [result]) => {
+                    module.types.insert(*result, Type::AccelerationStructure);
+                }
+                (OP_TYPE_ARRAY, [result, element, length]) => {
+                    module.types.insert(
+                        *result,
+                        Type::Array {
+                            element: *element,
+                            length: *length,
+                        },
+                    );
+                }
+                (OP_TYPE_RUNTIME_ARRAY, [result, element]) => {
+                    module.types.insert(
+                        *result,
+                        Type::RuntimeArray {
+                            element: *element,
+                        },
+                    );
+                }
+                (OP_TYPE_STRUCT, [result, members @ ..]) => {
+                    module.types.insert(*result, Type::Struct);
+                    module.struct_members.insert(*result, members.to_vec());
+                }
+                (OP_TYPE_POINTER, [result, _, pointee]) => {
+                    module.types.insert(
+                        *result,
+                        Type::Pointer {
+                            pointee: *pointee,
+                        },
+                    );
+                }
+                // Only 32-bit constants are needed, for array lengths
+                (OP_CONSTANT, [_, result, value, ..]) => {
+                    module.constants.insert(*result, *value);
+                }
+                (OP_VARIABLE, [ty, result, storage, ..]) => {
+                    module.variables.push((*ty, *result, *storage));
+                }
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+
+    fn ty(&self, id: u32) -> Result<Type> {
+        self.types
+            .get(&id)
+            .copied()
+            .ok_or_else(|| anyhow!("Unknown SPIR-V type id {id}"))
+    }
+
+    fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations
+            .get(&id)?
+            .iter()
+            .find(|(d, _)| *d == decoration)
+            .map(|(_, value)| *value)
+    }
+
+    fn has_decoration(&self, id: u32, decoration: u32) -> bool {
+        self.decoration(id, decoration).is_some()
+    }
+
+    /// Size in bytes of a type, as laid out in a buffer or push constant block.
+    fn size_of(&self, ty: u32, matrix_stride: Option<u32>) -> Result<u32> {
+        Ok(match self.ty(ty)? {
+            Type::Scalar {
+                width,
+            } => width / 8,
+            Type::Vector {
+                component,
+                count,
+            } => self.size_of(component, None)? * count,
+            Type::Matrix {
+                column,
+                count,
+            } => match matrix_stride {
+                Some(stride) => stride * count,
+                None => self.size_of(column, None)? * count,
+            },
+            Type::Array {
+                element,
+                length,
+            } => {
+                let length = self.constants.get(&length).copied().unwrap_or(0);
+                let stride = match self.decoration(ty, DECORATION_ARRAY_STRIDE) {
+                    Some(stride) => stride,
+                    None => self.size_of(element, matrix_stride)?,
+                };
+                stride * length
+            }
+            Type::Struct => self.struct_range(ty)?.map_or(0, |(_, end)| end),
+            _ => bail!("Type id {ty} has no size"),
+        })
+    }
+
+    /// Returns the first and one past the last byte used by the members of a struct.
+    fn struct_range(&self, ty: u32) -> Result<Option<(u32, u32)>> {
+        let members = self
+            .struct_members
+            .get(&ty)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut range: Option<(u32, u32)> = None;
+        for (index, &member) in members.iter().enumerate() {
+            let key = (ty, index as u32);
+            let offset = self.member_offsets.get(&key).copied().unwrap_or(0);
+            let end = offset + self.size_of(member, self.matrix_strides.get(&key).copied())?;
+            range = Some(match range {
+                None => (offset, end),
+                Some((first, last)) => (first.min(offset), last.max(end)),
+            });
+        }
+        Ok(range)
+    }
+
+    /// Determine the descriptor type and count of a resource type.
+    fn descriptor(&self, ty: u32, storage: u32) -> Result<Option<(vk::DescriptorType, u32)>> {
+        let descriptor = match self.ty(ty)? {
+            Type::Array {
+                element,
+                length,
+            } => {
+                let length = self.constants.get(&length).copied().unwrap_or(0);
+                return Ok(self
+                    .descriptor(element, storage)?
+                    .map(|(ty, count)| (ty, count * length)));
+            }
+            Type::RuntimeArray {
+                element,
+            } => {
+                return Ok(self.descriptor(element, storage)?.map(|(ty, _)| (ty, 0)));
+            }
+            Type::Image {
+                dim: DIM_SUBPASS_DATA,
+                ..
+            } => vk::DescriptorType::INPUT_ATTACHMENT,
+            Type::Image {
+                dim: DIM_BUFFER,
+                sampled: 2,
+            } => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+            Type::Image {
+                dim: DIM_BUFFER,
+                ..
+            } => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+            Type::Image {
+                sampled: 2,
+                ..
+            } => vk::DescriptorType::STORAGE_IMAGE,
+            Type::Image {
+                ..
+            } => vk::DescriptorType::SAMPLED_IMAGE,
+            Type::Sampler => vk::DescriptorType::SAMPLER,
+            Type::SampledImage => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            Type::AccelerationStructure => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            Type::Struct if storage == STORAGE_STORAGE_BUFFER => vk::DescriptorType::STORAGE_BUFFER,
+            Type::Struct if self.has_decoration(ty, DECORATION_BUFFER_BLOCK) => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            Type::Struct if self.has_decoration(ty, DECORATION_BLOCK) => {
+                vk::DescriptorType::UNIFORM_BUFFER
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some((descriptor, 1)))
+    }
+
+    fn reflect(&self) -> Result<Reflection> {
+        let mut bindings: Vec<DescriptorBinding> = vec![];
+        let mut push_constants = vec![];
+        for &(pointer, id, storage) in &self.variables {
+            let pointee = match self.ty(pointer)? {
+                Type::Pointer {
+                    pointee,
+                } => pointee,
+                _ => continue,
+            };
+            match storage {
+                STORAGE_PUSH_CONSTANT => {
+                    if let Some((offset, end)) = self.struct_range(pointee)? {
+                        push_constants.push(PushConstantRange {
+                            stages: self.stages,
+                            offset,
+                            size: end - offset,
+                        });
+                    }
+                }
+                STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                    let Some(binding) = self.decoration(id, DECORATION_BINDING) else { continue };
+                    let set = self.decoration(id, DECORATION_DESCRIPTOR_SET).unwrap_or(0);
+                    let Some((ty, count)) = self.descriptor(pointee, storage)? else { continue };
+                    match bindings
+                        .iter_mut()
+                        .find(|b| b.set == set && b.binding == binding)
+                    {
+                        // A separate texture and sampler sharing a binding form a combined image sampler,
+                        // this is what dxc emits for [[vk::combinedImageSampler]].
+                        Some(existing) => {
+                            let pair = [existing.ty, ty];
+                            ensure!(
+                                pair.contains(&vk::DescriptorType::SAMPLED_IMAGE)
+                                    && pair.contains(&vk::DescriptorType::SAMPLER),
+                                "Binding ({set}, {binding}) is used by multiple resources"
+                            );
+                            existing.ty = vk::DescriptorType::COMBINED_IMAGE_SAMPLER;
+                        }
+                        None => bindings.push(DescriptorBinding {
+                            set,
+                            binding,
+                            ty,
+                            count,
+                            stages: self.stages,
+                            name: self.names.get(&id).cloned(),
+                            user_type: self.user_types.get(&id).cloned(),
+                        }),
+                    }
+                }
+                _ => {}
+            }
+        }
+        bindings.sort_by_key(|b| (b.set, b.binding));
+        Ok(Reflection {
+            stages: self.stages,
+            bindings,
+            push_constants,
+        })
+    }
+}
+
+/// Reflect the descriptor bindings and push constants used by a SPIR-V module.
+pub fn reflect_spirv(binary: &[u32]) -> Result<Reflection> {
+    Module::parse(binary)?.reflect()
+}
+
+/// Remove the `SPV_GOOGLE_hlsl_functionality1` and `SPV_GOOGLE_user_type` extensions and their
+/// decorations from a module. Using them would require enabling `VK_GOOGLE_hlsl_functionality1`
+/// on the device, while they are only useful for reflection.
+pub fn strip_reflection_info(binary: &[u32]) -> Result<Vec<u32>> {
+    let mut result = binary[..HEADER_WORDS.min(binary.len())].to_vec();
+    for (opcode, ops) in instructions(binary)? {
+        let strip = match (opcode, ops) {
+            (OP_EXTENSION, name) => REFLECTION_EXTENSIONS.contains(&literal_string(name).as_str()),
+            (OP_DECORATE_STRING | OP_DECORATE_ID, [_, decoration, ..])
+            | (OP_MEMBER_DECORATE_STRING, [_, _, decoration, ..]) => matches!(
+                *decoration,
+                DECORATION_COUNTER_BUFFER | DECORATION_USER_SEMANTIC | DECORATION_USER_TYPE
+            ),
+            _ => false,
+        };
+        if !strip {
+            result.push(((ops.len() as u32 + 1) << 16) | opcode);
+            result.extend_from_slice(ops);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Assembles a SPIR-V module from (opcode, operands) pairs.
+    fn assemble(instructions: &[(u32, Vec<u32>)]) -> Vec<u32> {
+        let mut words = vec![SPIRV_MAGIC, 0x0001_0600, 0, 100, 0];
+        for (opcode, ops) in instructions {
+            words.push(((ops.len() as u32 + 1) << 16) | opcode);
+            words.extend(ops);
+        }
+        words
+    }
+
+    fn string(s: &str) -> Vec<u32> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(s.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+
+    /// A compute shader with a storage image at (0, 0), a texture and sampler sharing (0, 1)
+    /// and a push constant block of an int2 and a float.
+    fn compute_module() -> Vec<(u32, Vec<u32>)> {
+        vec![
+            (OP_EXTENSION, string("SPV_GOOGLE_hlsl_functionality1")),
+            (OP_ENTRY_POINT, [vec![5, 1], string("main")].concat()),
+            (OP_NAME, [vec![20], string("heights")].concat()),
+            (OP_DECORATE, vec![20, DECORATION_BINDING, 0]),
+            (OP_DECORATE, vec![20, DECORATION_DESCRIPTOR_SET, 0]),
+            (
+                OP_DECORATE_STRING,
+                [vec![20, DECORATION_USER_TYPE], string("rwtexture2d:<float>")].concat(),
+            ),
+            (OP_DECORATE, vec![21, DECORATION_BINDING, 1]),
+            (OP_DECORATE, vec![21, DECORATION_DESCRIPTOR_SET, 0]),
+            (OP_DECORATE, vec![22, DECORATION_BINDING, 1]),
+            (OP_DECORATE, vec![22, DECORATION_DESCRIPTOR_SET, 0]),
+            (OP_MEMBER_DECORATE, vec![14, 0, DECORATION_OFFSET, 0]),
+            (OP_MEMBER_DECORATE, vec![14, 1, DECORATION_OFFSET, 8]),
+            (OP_DECORATE, vec![14, DECORATION_BLOCK]),
+            (OP_TYPE_INT, vec![2, 32, 1]),
+            (OP_TYPE_FLOAT, vec![3, 32]),
+            (OP_TYPE_VECTOR, vec![4, 2, 2]),
+            (OP_TYPE_IMAGE, vec![5, 3, 1, 0, 0, 0, 2, 0]),
+            (OP_TYPE_IMAGE, vec![6, 3, 1, 0, 0, 0, 1, 0]),
+            (OP_TYPE_SAMPLER, vec![7]),
+            (OP_TYPE_STRUCT, vec![14, 4, 3]),
+            (OP_TYPE_POINTER, vec![15, STORAGE_UNIFORM_CONSTANT, 5]),
+            (OP_TYPE_POINTER, vec![16, STORAGE_UNIFORM_CONSTANT, 6]),
+            (OP_TYPE_POINTER, vec![17, STORAGE_UNIFORM_CONSTANT, 7]),
+            (OP_TYPE_POINTER, vec![18, STORAGE_PUSH_CONSTANT, 14]),
+            (OP_VARIABLE, vec![15, 20, STORAGE_UNIFORM_CONSTANT]),
+            (OP_VARIABLE, vec![16, 21, STORAGE_UNIFORM_CONSTANT]),
+            (OP_VARIABLE, vec![17, 22, STORAGE_UNIFORM_CONSTANT]),
+            (OP_VARIABLE, vec![18, 23, STORAGE_PUSH_CONSTANT]),
+        ]
+    }
+
+    #[test]
+    fn reflects_bindings_and_push_constants() {
+        let reflection = reflect_spirv(&assemble(&compute_module())).unwrap();
+        assert_eq!(reflection.stages, vk::ShaderStageFlags::COMPUTE);
+        let heights = reflection.binding(0, 0).unwrap();
+        assert_eq!(heights.ty, vk::DescriptorType::STORAGE_IMAGE);
+        assert_eq!(heights.name.as_deref(), Some("heights"));
+        assert_eq!(heights.user_type.as_deref(), Some("rwtexture2d:<float>"));
+        assert_eq!(
+            reflection.binding(0, 1).unwrap().ty,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+        );
+        assert_eq!(
+            reflection.push_constants,
+            vec![PushConstantRange {
+                stages: vk::ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: 12,
+            }]
+        );
+    }
+
+    #[test]
+    fn strips_reflection_extensions() {
+        let module = assemble(&compute_module());
+        let stripped = strip_reflection_info(&module).unwrap();
+        let opcodes = instructions(&stripped)
+            .unwrap()
+            .map(|(opcode, _)| opcode)
+            .collect::<Vec<_>>();
+        assert!(!opcodes.contains(&OP_EXTENSION));
+        assert!(!opcodes.contains(&OP_DECORATE_STRING));
+        // Stripping does not change the resources of the module, only the names of their HLSL types.
+        let mut reflection = reflect_spirv(&module).unwrap();
+        reflection.bindings[0].user_type = None;
+        assert_eq!(reflect_spirv(&stripped).unwrap(), reflection);
+    }
+
+    #[test]
+    fn layout_mismatches_are_reported() {
+        let reflection = reflect_spirv(&assemble(&compute_module())).unwrap();
+        let expected = ExpectedLayout {
+            bindings: vec![
+                (0, 0, vk::DescriptorType::STORAGE_IMAGE),
+                (0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+            ],
+            push_constant_size: Some(12),
+        };
+        expected.validate(&reflection).unwrap();
+
+        let wrong = ExpectedLayout {
+            bindings: vec![(0, 0, vk::DescriptorType::SAMPLED_IMAGE)],
+            push_constant_size: Some(8),
+        };
+        let error = wrong.validate(&reflection).unwrap_err().to_string();
+        assert!(error.contains("binding (0, 0) is a STORAGE_IMAGE"));
+        assert!(error.contains("unexpected binding (0, 1)"));
+        assert!(error.contains("reads 12 bytes"));
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+
+use anyhow::{bail, Result};
+use inject::DI;
+use log::warn;
+use scheduler::EventBus;
+
+/// Keeps track of all pipeline names created through the dynamic pipeline builders.
+/// Creating two pipelines with the same name silently overwrites the first one in the pipeline cache,
+/// so this is reported as soon as the second pipeline is built.
+/// Access through DI.
+#[derive(Debug, Default)]
+pub struct PipelineRegistry {
+    names: BTreeSet<String>,
+}
+
+impl PipelineRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new pipeline name. In debug builds a duplicate name is an error, in release builds
+    /// only a warning is emitted.
+    pub fn register(&mut self, name: &str) -> Result<()> {
+        if !self.names.insert(name.to_owned()) {
+            if cfg!(debug_assertions) {
+                bail!("Pipeline {name:?} was already registered. Pipeline names must be unique.");
+            } else {
+                warn!("Pipeline {name:?} was already registered and will be overwritten.");
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if a pipeline with this name was registered.
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    /// Returns the names of all registered pipelines, in sorted order.
+    pub fn registered_pipelines(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(|name| name.as_str())
+    }
+}
+
+/// Register a pipeline name in the [`PipelineRegistry`] stored in DI.
+/// # DI Access
+/// - Write [`PipelineRegistry`]
+pub(crate) fn register_pipeline(bus: &EventBus<DI>, name: &str) -> Result<()> {
+    let di = bus.data().read().unwrap();
+    let mut registry = di.write_sync::<PipelineRegistry>().unwrap();
+    registry.register(name)
+}
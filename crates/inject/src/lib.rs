@@ -4,7 +4,7 @@
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
-pub use storage::ErasedStorage;
+pub use storage::{DiError, ErasedStorage};
 use util::RwLock;
 
 pub mod storage;
@@ -1,11 +1,42 @@
 use std::any::{Any, TypeId};
 use std::boxed::ThinBox;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::marker::Unsize;
 use std::ops::{Deref, DerefMut};
 
 use util::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
+/// Error returned by the fallible accessors of [`ErasedStorage`]. This is cheap to construct
+/// and does not capture a backtrace.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiError {
+    /// No object of this type was registered.
+    NotFound {
+        type_name: &'static str,
+    },
+}
+
+impl DiError {
+    fn not_found<T: ?Sized>() -> Self {
+        Self::NotFound {
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+}
+
+impl Display for DiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiError::NotFound {
+                type_name,
+            } => write!(f, "type `{type_name}` not found in DI"),
+        }
+    }
+}
+
+impl std::error::Error for DiError {}
+
 /// A registry is a container for type-erased structs. It can store
 /// any struct, or any `dyn Trait` object, which can then be queried again by calling
 /// `get::<T>()` for a regular struct or `get_dyn::<dyn Trait>()` for trait objects.
@@ -56,6 +87,11 @@ impl ErasedStorage {
         any.map(|value| value.downcast_ref::<T>().unwrap())
     }
 
+    /// Get the registered object for `T`, or an error naming `T` if it didn't exist.
+    pub fn try_get<T: 'static>(&self) -> Result<&T, DiError> {
+        self.get::<T>().ok_or_else(DiError::not_found::<T>)
+    }
+
     /// Acquire a reader lock to a synchronized object stored in the registry
     pub fn read_sync<T: 'static>(&self) -> Option<RwLockReadGuard<T>> {
         self.get::<RwLock<T>>().map(|lock| lock.read().unwrap())
@@ -66,12 +102,59 @@ impl ErasedStorage {
         self.get::<RwLock<T>>().map(|lock| lock.write().unwrap())
     }
 
+    /// Acquire a reader lock to a synchronized object stored in the registry, or an error naming
+    /// `T` if it didn't exist.
+    pub fn try_read_sync<T: 'static>(&self) -> Result<RwLockReadGuard<T>, DiError> {
+        self.read_sync::<T>().ok_or_else(DiError::not_found::<T>)
+    }
+
+    /// Acquire a writer lock to a synchronized object stored in the registry, or an error naming
+    /// `T` if it didn't exist.
+    pub fn try_write_sync<T: 'static>(&self) -> Result<RwLockWriteGuard<T>, DiError> {
+        self.write_sync::<T>().ok_or_else(DiError::not_found::<T>)
+    }
+
     /// Get a mutable reference to the registered object for `T`, or `None` if it didn't exist.
     pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
         let any = self.items.get_mut(&TypeId::of::<T>());
         any.map(|value| value.downcast_mut::<T>().unwrap())
     }
 
+    /// Remove the registered object for `T` from the registry, returning it if it existed.
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        let any = self.items.remove(&TypeId::of::<T>());
+        any.map(|value| *value.downcast::<T>().unwrap())
+    }
+
+    /// Replace the registered object for `T`, returning the previous one if it existed.
+    pub fn replace<T: 'static>(&mut self, item: T) -> Option<T> {
+        let any = self.items.insert(TypeId::of::<T>(), Box::new(item));
+        any.map(|value| *value.downcast::<T>().unwrap())
+    }
+
+    /// Remove a synchronized object stored with [`Self::put_sync`], returning the value inside
+    /// its lock if it existed.
+    ///
+    /// Guards returned by [`Self::read_sync`] borrow from the registry, so none can be alive
+    /// while removing. Through [`DI`](crate::DI) this means waiting for the write lock on the
+    /// whole registry, which deadlocks if the calling thread still holds a guard. Other threads
+    /// will see the type disappear the next time they look it up, so code that can run after
+    /// the removal should use [`Self::try_read_sync`] instead of unwrapping.
+    pub fn remove_sync<T: 'static>(&mut self) -> Option<T> {
+        self.remove::<RwLock<T>>()
+            .map(|lock| lock.into_inner().unwrap())
+    }
+
+    /// Replace a synchronized object stored with [`Self::put_sync`], returning the value
+    /// inside the previous lock if it existed. The new value gets a fresh lock.
+    ///
+    /// To swap the value without taking the registry mutably, and thus without blocking every
+    /// other user of the registry, write through [`Self::write_sync`] instead.
+    pub fn replace_sync<T: 'static>(&mut self, item: T) -> Option<T> {
+        self.replace(RwLock::with_name(item, std::any::type_name::<T>()))
+            .map(|lock| lock.into_inner().unwrap())
+    }
+
     /// Get the registered implementation for `dyn MyTrait`, or `None` if it didn't exist.
     pub fn get_dyn<T: ?Sized + 'static>(&self) -> Option<&T> {
         let any = self.dyn_items.get(&TypeId::of::<T>());
@@ -87,6 +170,7 @@ impl ErasedStorage {
 
 #[cfg(test)]
 mod tests {
+    use crate::storage::DiError;
     use crate::ErasedStorage;
 
     struct Foo;
@@ -114,4 +198,39 @@ mod tests {
         registry.put_dyn::<dyn MyTrait>(Foo);
         assert!(registry.get_dyn::<dyn MyTrait>().is_some());
     }
+
+    #[test]
+    fn missing_type_is_named() {
+        let mut registry = ErasedStorage::new();
+        registry.put_sync(Foo);
+        assert!(registry.try_read_sync::<Foo>().is_ok());
+        let error = registry.try_get::<Foo>().err().unwrap();
+        assert_eq!(
+            error,
+            DiError::NotFound {
+                type_name: std::any::type_name::<Foo>()
+            }
+        );
+        assert!(error.to_string().ends_with("Foo` not found in DI"));
+    }
+
+    #[test]
+    fn remove_and_replace() {
+        let mut registry = ErasedStorage::new();
+        assert_eq!(registry.replace(1u32), None);
+        assert_eq!(registry.replace(2u32), Some(1));
+        assert_eq!(registry.remove::<u32>(), Some(2));
+        assert!(registry.get::<u32>().is_none());
+        assert_eq!(registry.remove::<u32>(), None);
+    }
+
+    #[test]
+    fn remove_and_replace_sync() {
+        let mut registry = ErasedStorage::new();
+        registry.put_sync(String::from("first"));
+        assert_eq!(registry.replace_sync(String::from("second")).as_deref(), Some("first"));
+        assert_eq!(registry.read_sync::<String>().unwrap().as_str(), "second");
+        assert_eq!(registry.remove_sync::<String>().as_deref(), Some("second"));
+        assert!(registry.read_sync::<String>().is_none());
+    }
 }
@@ -1,9 +1,15 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use inject::DI;
+use log::error;
+pub use map::{actions, Binding, InputMap, INPUT_MAP_FILE};
 use scheduler::{Event, EventBus, EventContext, StoredSystem, System};
+use serde::{Deserialize, Serialize};
+
+pub mod map;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum ButtonState {
@@ -11,7 +17,7 @@ pub enum ButtonState {
     Released,
 }
 
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum MouseButton {
     Left,
     Right,
@@ -19,10 +25,59 @@ pub enum MouseButton {
     Other(u16),
 }
 
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Key {
     Shift,
+    Control,
+    Alt,
     Escape,
+    Space,
+    W,
+    A,
+    S,
+    D,
+    Q,
+    E,
+    F,
+    Left,
+    Right,
+    Up,
+    Down,
+    /// Any other key, identified by its winit key code.
+    Other(u32),
+}
+
+impl Key {
+    /// Whether this is a modifier key. The state of modifiers is reported separately from
+    /// other keys, since key events for modifiers are not reliable on every platform.
+    pub fn is_modifier(self) -> bool {
+        matches!(self, Key::Shift | Key::Control | Key::Alt)
+    }
+}
+
+impl From<winit::event::VirtualKeyCode> for Key {
+    fn from(key: winit::event::VirtualKeyCode) -> Self {
+        use winit::event::VirtualKeyCode;
+        match key {
+            VirtualKeyCode::LShift | VirtualKeyCode::RShift => Key::Shift,
+            VirtualKeyCode::LControl | VirtualKeyCode::RControl => Key::Control,
+            VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => Key::Alt,
+            VirtualKeyCode::Escape => Key::Escape,
+            VirtualKeyCode::Space => Key::Space,
+            VirtualKeyCode::W => Key::W,
+            VirtualKeyCode::A => Key::A,
+            VirtualKeyCode::S => Key::S,
+            VirtualKeyCode::D => Key::D,
+            VirtualKeyCode::Q => Key::Q,
+            VirtualKeyCode::E => Key::E,
+            VirtualKeyCode::F => Key::F,
+            VirtualKeyCode::Left => Key::Left,
+            VirtualKeyCode::Right => Key::Right,
+            VirtualKeyCode::Up => Key::Up,
+            VirtualKeyCode::Down => Key::Down,
+            other => Key::Other(other as u32),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, Copy)]
@@ -60,10 +115,42 @@ pub enum InputEvent {
     MousePosition(MousePosition),
     MouseMove(MouseDelta),
     MouseButton(MouseButtonState),
+    /// Published after a mouse button is pressed. `count` is 1 for a single click, 2 for a
+    /// double click and so on, see [`ClickSettings`].
+    MouseClick {
+        button: MouseButton,
+        count: u32,
+    },
     Button(KeyState),
     Scroll(ScrollInfo),
 }
 
+/// When consecutive presses of a mouse button count as a multi-click.
+#[derive(Debug, Clone, Copy)]
+pub struct ClickSettings {
+    /// Maximum time between two presses.
+    pub interval: Duration,
+    /// Maximum distance in pixels the mouse may move between two presses.
+    pub radius: f64,
+}
+
+impl Default for ClickSettings {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(400),
+            radius: 4.0,
+        }
+    }
+}
+
+/// The last press of a mouse button.
+#[derive(Debug, Clone, Copy)]
+struct Click {
+    time: Instant,
+    position: MousePosition,
+    count: u32,
+}
+
 impl Event for InputEvent {}
 
 impl From<winit::event::MouseButton> for MouseButton {
@@ -91,6 +178,15 @@ pub struct InputState {
     mouse: MousePosition,
     mouse_buttons: HashMap<MouseButton, ButtonState>,
     kb_buttons: HashMap<Key, ButtonState>,
+    /// Mouse movement and vertical scroll received since the start of the current frame.
+    pending_delta: MouseDelta,
+    pending_scroll: f32,
+    /// Mouse movement and vertical scroll of the previous frame, see [`InputState::new_frame`].
+    mouse_delta: MouseDelta,
+    scroll: f32,
+    map: InputMap,
+    clicks: HashMap<MouseButton, Click>,
+    click_settings: ClickSettings,
 }
 
 impl InputState {
@@ -99,9 +195,96 @@ impl InputState {
             mouse: Default::default(),
             mouse_buttons: Default::default(),
             kb_buttons: Default::default(),
+            pending_delta: Default::default(),
+            pending_scroll: 0.0,
+            mouse_delta: Default::default(),
+            scroll: 0.0,
+            map: Default::default(),
+            clicks: Default::default(),
+            click_settings: Default::default(),
         }
     }
 
+    pub fn click_settings(&self) -> ClickSettings {
+        self.click_settings
+    }
+
+    pub fn set_click_settings(&mut self, settings: ClickSettings) {
+        self.click_settings = settings;
+    }
+
+    /// Amount of clicks in the latest sequence of presses of a mouse button, 1 for a single
+    /// click and 2 for a double click. Zero if the button was never pressed.
+    pub fn last_click_count(&self, button: MouseButton) -> u32 {
+        self.clicks.get(&button).map_or(0, |click| click.count)
+    }
+
+    /// Register a press of a mouse button at the current mouse position. Returns the click
+    /// count of the press.
+    fn register_press(&mut self, button: MouseButton, time: Instant) -> u32 {
+        let position = self.mouse;
+        let settings = self.click_settings;
+        let repeats = |click: &Click| {
+            let (dx, dy) = (position.x - click.position.x, position.y - click.position.y);
+            time.saturating_duration_since(click.time) <= settings.interval
+                && dx * dx + dy * dy <= settings.radius * settings.radius
+        };
+        let count = match self.clicks.get(&button) {
+            Some(click) if repeats(click) => click.count + 1,
+            _ => 1,
+        };
+        self.clicks.insert(
+            button,
+            Click {
+                time,
+                position,
+                count,
+            },
+        );
+        count
+    }
+
+    /// Bindings of the named input actions.
+    pub fn input_map(&self) -> &InputMap {
+        &self.map
+    }
+
+    /// Change the bindings of input actions, for example to rebind them at runtime.
+    pub fn input_map_mut(&mut self) -> &mut InputMap {
+        &mut self.map
+    }
+
+    /// Whether the input an action is bound to is held down. Actions that are not bound are
+    /// never active.
+    pub fn is_action_active(&self, action: &str) -> bool {
+        let state = match self.map.binding(action) {
+            None => return false,
+            Some(Binding::Key(key)) => self.get_key(key),
+            Some(Binding::Mouse(button)) => self.get_mouse_key(button),
+        };
+        state == ButtonState::Pressed
+    }
+
+    /// Start a new frame. The mouse movement and scroll accumulated since the previous call
+    /// become the values returned by [`InputState::mouse_delta`] and
+    /// [`InputState::take_scroll`] until the next frame. Must be called exactly once per frame,
+    /// before the `Tick` event.
+    pub fn new_frame(&mut self) {
+        self.mouse_delta = std::mem::take(&mut self.pending_delta);
+        self.scroll = std::mem::take(&mut self.pending_scroll);
+    }
+
+    /// Sum of all mouse movement during the last frame.
+    pub fn mouse_delta(&self) -> MouseDelta {
+        self.mouse_delta
+    }
+
+    /// Vertical scroll accumulated during the last frame. This does not consume the scroll,
+    /// every reader in the same frame sees the same value.
+    pub fn take_scroll(&self) -> f32 {
+        self.scroll
+    }
+
     pub fn get_key(&self, key: Key) -> ButtonState {
         self.kb_buttons
             .get(&key)
@@ -131,12 +314,24 @@ impl Input {
             }
             InputEvent::MouseButton(state) => {
                 input_state.mouse_buttons.insert(state.button, state.state);
+                if state.state == ButtonState::Pressed {
+                    input_state.register_press(state.button, Instant::now());
+                }
             }
+            // Derived from the MouseButton event, which already registered the click
+            InputEvent::MouseClick {
+                ..
+            } => {}
             InputEvent::Button(state) => {
                 input_state.kb_buttons.insert(state.button, state.state);
             }
-            InputEvent::Scroll(_) => {}
-            InputEvent::MouseMove(_) => {}
+            InputEvent::Scroll(scroll) => {
+                input_state.pending_scroll += scroll.delta_y;
+            }
+            InputEvent::MouseMove(delta) => {
+                input_state.pending_delta.x += delta.x;
+                input_state.pending_delta.y += delta.y;
+            }
         };
     }
 }
@@ -163,7 +358,128 @@ fn handle_input_event(
 /// Initialize the input system
 pub fn initialize(bus: &mut EventBus<DI>) {
     bus.add_system(Input);
-    let state = InputState::new();
+    let mut state = InputState::new();
+    match InputMap::load_or_default(INPUT_MAP_FILE) {
+        Ok(map) => state.map = map,
+        Err(e) => error!("Could not load input bindings from {INPUT_MAP_FILE}: {e}"),
+    }
     let mut di = bus.data().write().unwrap();
     di.put_sync(state);
 }
+
+#[cfg(test)]
+mod tests {
+    use scheduler::TestBus;
+
+    use super::*;
+
+    #[test]
+    fn input_events_update_state() {
+        let mut bus = TestBus::new();
+        initialize(&mut bus);
+        bus.publish(InputEvent::Button(KeyState {
+            state: ButtonState::Pressed,
+            button: Key::W,
+        }))
+        .unwrap();
+        bus.publish(InputEvent::MouseButton(MouseButtonState {
+            state: ButtonState::Pressed,
+            button: MouseButton::Left,
+        }))
+        .unwrap();
+        bus.read(|input: &InputState| {
+            assert_eq!(input.get_key(Key::W), ButtonState::Pressed);
+            assert_eq!(input.get_key(Key::S), ButtonState::Released);
+            assert_eq!(input.get_mouse_key(MouseButton::Left), ButtonState::Pressed);
+        });
+        bus.publish(InputEvent::Button(KeyState {
+            state: ButtonState::Released,
+            button: Key::W,
+        }))
+        .unwrap();
+        assert_eq!(bus.read(|input: &InputState| input.get_key(Key::W)), ButtonState::Released);
+    }
+
+    #[test]
+    fn mouse_delta_and_scroll_accumulate_per_frame() {
+        let mut bus = TestBus::new();
+        initialize(&mut bus);
+        for (x, y) in [(1.0, 2.0), (3.0, -1.0)] {
+            bus.publish(InputEvent::MouseMove(MouseDelta {
+                x,
+                y,
+            }))
+            .unwrap();
+            bus.publish(InputEvent::Scroll(ScrollInfo {
+                delta_x: 0.0,
+                delta_y: 0.5,
+            }))
+            .unwrap();
+        }
+        // Nothing is visible until the frame starts
+        assert_eq!(bus.read(|input: &InputState| input.take_scroll()), 0.0);
+        bus.write(|input: &mut InputState| input.new_frame());
+        for _ in 0..2 {
+            let (delta, scroll) =
+                bus.read(|input: &InputState| (input.mouse_delta(), input.take_scroll()));
+            assert_eq!((delta.x, delta.y), (4.0, 1.0));
+            assert_eq!(scroll, 1.0);
+        }
+        bus.write(|input: &mut InputState| input.new_frame());
+        let delta = bus.read(|input: &InputState| input.mouse_delta());
+        assert_eq!((delta.x, delta.y), (0.0, 0.0));
+    }
+
+    #[test]
+    fn key_codes_are_converted() {
+        use winit::event::VirtualKeyCode;
+        assert_eq!(Key::from(VirtualKeyCode::Up), Key::Up);
+        assert_eq!(Key::from(VirtualKeyCode::RControl), Key::Control);
+        assert!(Key::from(VirtualKeyCode::LAlt).is_modifier());
+        // Unmapped keys are still distinguishable from each other
+        let f1 = Key::from(VirtualKeyCode::F1);
+        assert!(matches!(f1, Key::Other(_)));
+        assert_ne!(f1, Key::from(VirtualKeyCode::F2));
+    }
+
+    #[test]
+    fn actions_follow_their_binding() {
+        let mut bus = TestBus::new();
+        initialize(&mut bus);
+        bus.publish(InputEvent::Button(KeyState {
+            state: ButtonState::Pressed,
+            button: Key::Up,
+        }))
+        .unwrap();
+        assert!(!bus.read(|input: &InputState| input.is_action_active(actions::CAMERA_FORWARD)));
+        bus.write(|input: &mut InputState| {
+            input
+                .input_map_mut()
+                .bind(actions::CAMERA_FORWARD, Binding::Key(Key::Up))
+        });
+        assert!(bus.read(|input: &InputState| input.is_action_active(actions::CAMERA_FORWARD)));
+        assert!(!bus.read(|input: &InputState| input.is_action_active("unknown.action")));
+    }
+
+    #[test]
+    fn consecutive_presses_count_as_multi_clicks() {
+        let mut input = InputState::new();
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        assert_eq!(input.last_click_count(MouseButton::Left), 0);
+        assert_eq!(input.register_press(MouseButton::Left, start), 1);
+        assert_eq!(input.register_press(MouseButton::Left, start + ms(300)), 2);
+        assert_eq!(input.register_press(MouseButton::Left, start + ms(600)), 3);
+        assert_eq!(input.last_click_count(MouseButton::Left), 3);
+        // Buttons are counted separately
+        assert_eq!(input.register_press(MouseButton::Right, start + ms(700)), 1);
+        // Too slow
+        assert_eq!(input.register_press(MouseButton::Left, start + ms(1100)), 1);
+        // Too far away
+        input.mouse = MousePosition {
+            x: 10.0,
+            y: 0.0,
+        };
+        assert_eq!(input.register_press(MouseButton::Left, start + ms(1200)), 1);
+    }
+}
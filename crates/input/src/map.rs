@@ -0,0 +1,171 @@
+//! Named input actions, so controls can be rebound without changing the systems using them.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{Key, MouseButton};
+
+/// File custom bindings are loaded from.
+pub const INPUT_MAP_FILE: &str = "data/input_map.json";
+
+/// Names of the actions used by the application.
+pub mod actions {
+    pub const CAMERA_FORWARD: &str = "camera.forward";
+    pub const CAMERA_BACK: &str = "camera.back";
+    pub const CAMERA_LEFT: &str = "camera.left";
+    pub const CAMERA_RIGHT: &str = "camera.right";
+    pub const CAMERA_UP: &str = "camera.up";
+    pub const CAMERA_DOWN: &str = "camera.down";
+    /// Move the camera faster while flying.
+    pub const CAMERA_BOOST: &str = "camera.boost";
+    /// Rotate the camera by dragging the mouse.
+    pub const CAMERA_ROTATE: &str = "camera.rotate";
+    /// Pan the camera instead of rotating it, while [`CAMERA_ROTATE`] is held.
+    pub const CAMERA_PAN: &str = "camera.pan";
+    /// Frame the camera on the terrain.
+    pub const CAMERA_FOCUS: &str = "camera.focus";
+    /// Frame the camera on the point under the cursor by double clicking. Only mouse buttons
+    /// can be used for this action.
+    pub const CAMERA_FOCUS_POINT: &str = "camera.focus_point";
+    /// Deselect the active brush.
+    pub const BRUSH_CANCEL: &str = "brush.cancel";
+    /// Pick a value with the eyedropper when clicking.
+    pub const BRUSH_PICK: &str = "brush.pick";
+}
+
+/// Default binding of every action, as a table from action name to binding.
+const DEFAULT_BINDINGS: &str = r#"{
+    "camera.forward": { "Key": "W" },
+    "camera.back": { "Key": "S" },
+    "camera.left": { "Key": "A" },
+    "camera.right": { "Key": "D" },
+    "camera.up": { "Key": "E" },
+    "camera.down": { "Key": "Q" },
+    "camera.boost": { "Key": "Shift" },
+    "camera.rotate": { "Mouse": "Middle" },
+    "camera.pan": { "Key": "Shift" },
+    "camera.focus": { "Key": "F" },
+    "camera.focus_point": { "Mouse": "Middle" },
+    "brush.cancel": { "Key": "Escape" },
+    "brush.pick": { "Key": "Alt" }
+}"#;
+
+/// Input an action is bound to.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+/// Maps action names to the input they are bound to. Stored in [`InputState`](crate::InputState),
+/// see [`InputState::is_action_active`](crate::InputState::is_action_active).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputMap {
+    bindings: HashMap<String, Binding>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            bindings: serde_json::from_str(DEFAULT_BINDINGS).expect("Invalid default bindings"),
+        }
+    }
+}
+
+impl InputMap {
+    /// The default bindings, with the bindings in `overrides` replacing them.
+    pub fn with_overrides(overrides: HashMap<String, Binding>) -> Self {
+        let mut map = Self::default();
+        map.bindings.extend(overrides);
+        map
+    }
+
+    /// Load custom bindings from a file. Actions missing from the file keep their default.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let overrides = serde_json::from_reader(std::io::BufReader::new(file))?;
+        Ok(Self::with_overrides(overrides))
+    }
+
+    /// Load custom bindings from a file, falling back to the defaults if the file does not
+    /// exist.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self> {
+        if path.as_ref().exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Input the action is bound to, if any.
+    pub fn binding(&self, action: &str) -> Option<Binding> {
+        self.bindings.get(action).copied()
+    }
+
+    /// Bind an action to an input, replacing its previous binding.
+    pub fn bind(&mut self, action: impl Into<String>, binding: Binding) {
+        self.bindings.insert(action.into(), binding);
+    }
+
+    /// Remove the binding of an action, so it is never active.
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.remove(action);
+    }
+
+    /// All bound actions and their bindings.
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, Binding)> {
+        self.bindings
+            .iter()
+            .map(|(action, binding)| (action.as_str(), *binding))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_action_has_a_default_binding() {
+        let map = InputMap::default();
+        for action in [
+            actions::CAMERA_FORWARD,
+            actions::CAMERA_BACK,
+            actions::CAMERA_LEFT,
+            actions::CAMERA_RIGHT,
+            actions::CAMERA_UP,
+            actions::CAMERA_DOWN,
+            actions::CAMERA_BOOST,
+            actions::CAMERA_ROTATE,
+            actions::CAMERA_PAN,
+            actions::CAMERA_FOCUS,
+            actions::CAMERA_FOCUS_POINT,
+            actions::BRUSH_CANCEL,
+            actions::BRUSH_PICK,
+        ] {
+            assert!(map.binding(action).is_some(), "{action} is not bound");
+        }
+        assert_eq!(map.binding(actions::CAMERA_FORWARD), Some(Binding::Key(Key::W)));
+        assert_eq!(map.binding(actions::CAMERA_ROTATE), Some(Binding::Mouse(MouseButton::Middle)));
+    }
+
+    #[test]
+    fn overrides_replace_only_their_actions() {
+        let overrides = serde_json::from_str(r#"{ "camera.forward": { "Key": "Up" } }"#).unwrap();
+        let map = InputMap::with_overrides(overrides);
+        assert_eq!(map.binding(actions::CAMERA_FORWARD), Some(Binding::Key(Key::Up)));
+        assert_eq!(map.binding(actions::CAMERA_BACK), Some(Binding::Key(Key::S)));
+    }
+}
@@ -1,11 +1,12 @@
 use std::ops::{Add, Div, Mul, Sub};
 
 use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct Position(pub Vec3);
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct Rotation(pub Vec3);
 
 impl Rotation {
@@ -0,0 +1,270 @@
+//! Submission of work on the dedicated compute queue.
+//!
+//! Images written on the compute queue are owned by the graphics queue family the rest of the
+//! time. Every submission therefore moves through three steps, all ordered on the GPU:
+//! 1. The graphics queue releases the images to the compute queue family.
+//! 2. The compute queue acquires the images, runs the work and releases them again.
+//! 3. The graphics queue acquires the images back.
+//!
+//! Each step waits on a semaphore signaled by the previous one. The acquire in step 3 is
+//! submitted right away, so every graphics submission made afterwards, including the next
+//! frame, is ordered after the compute work without waiting for it on the CPU.
+
+use anyhow::Result;
+use gfx::SharedContext;
+use phobos::domain::{All, Compute, ExecutionDomain};
+use phobos::{vk, CommandBuffer, Fence, ImageView, IncompleteCmdBuffer, Semaphore};
+
+/// Image that is written by work on the compute queue.
+#[derive(Debug, Copy, Clone)]
+pub struct SharedImage {
+    pub image: vk::Image,
+    pub range: vk::ImageSubresourceRange,
+}
+
+impl SharedImage {
+    pub fn new(view: &ImageView) -> Self {
+        Self {
+            image: view.image(),
+            range: view.subresource_range(),
+        }
+    }
+}
+
+/// Work submitted on the compute queue that may still be executing. Owns everything the GPU
+/// uses until the fence is signaled.
+pub(crate) struct AsyncSubmission {
+    /// Signaled when the graphics queue acquired the images back, after the compute work.
+    fence: Fence,
+    /// Semaphores chaining the three steps of the submission.
+    semaphores: Vec<Semaphore>,
+    graphics_cmds: Vec<CommandBuffer<All>>,
+    compute_cmds: Vec<CommandBuffer<Compute>>,
+}
+
+impl AsyncSubmission {
+    pub fn wait(&self) -> Result<()> {
+        self.fence.wait()?;
+        Ok(())
+    }
+
+    pub fn is_done(&self, ctx: &SharedContext) -> Result<bool> {
+        // SAFETY: The fence is alive for the duration of this call.
+        Ok(unsafe { ctx.device.get_fence_status(self.fence.handle())? })
+    }
+
+    /// Free the command buffers of this submission. Must only be called once
+    /// [`AsyncSubmission::is_done`] returns true or [`AsyncSubmission::wait`] returned.
+    pub fn retire(mut self, ctx: &SharedContext) -> Result<()> {
+        // SAFETY: The fence was signaled, so the GPU no longer uses the command buffers.
+        unsafe {
+            for cmd in &mut self.graphics_cmds {
+                cmd.delete(ctx.exec.clone())?;
+            }
+            for cmd in &mut self.compute_cmds {
+                cmd.delete(ctx.exec.clone())?;
+            }
+        }
+        // The semaphores are no longer waited on either.
+        drop(self.semaphores);
+        Ok(())
+    }
+}
+
+/// Queue family indices of the graphics and compute queues.
+fn queue_families(ctx: &SharedContext) -> (u32, u32) {
+    let graphics = ctx.exec.get_queue::<All>().unwrap().family_index();
+    let compute = ctx
+        .exec
+        .get_queue::<Compute>()
+        .map(|queue| queue.family_index())
+        .unwrap_or(graphics);
+    (graphics, compute)
+}
+
+/// Queue family ownership transfer of `image`, which stays in the layout terrain textures are
+/// kept in between uses. `release` selects the half of the transfer that is recorded on the
+/// source queue.
+fn ownership_barrier(
+    image: &SharedImage,
+    src_family: u32,
+    dst_family: u32,
+    release: bool,
+) -> vk::ImageMemoryBarrier2 {
+    let barrier = vk::ImageMemoryBarrier2::builder()
+        .image(image.image)
+        .subresource_range(image.range)
+        .old_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .src_queue_family_index(src_family)
+        .dst_queue_family_index(dst_family);
+    let barrier = if release {
+        barrier
+            .src_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+            .src_access_mask(vk::AccessFlags2::MEMORY_WRITE)
+    } else {
+        barrier
+            .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+            .dst_access_mask(vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE)
+    };
+    barrier.build()
+}
+
+fn record_barriers<D: ExecutionDomain + 'static>(
+    ctx: &SharedContext,
+    barriers: &[vk::ImageMemoryBarrier2],
+) -> Result<CommandBuffer<D>> {
+    let cmd = ctx.exec.on_domain::<D, _>(None, None)?;
+    let dependency = vk::DependencyInfo::builder().image_memory_barriers(barriers);
+    // SAFETY: The command buffer is in the recording state.
+    unsafe {
+        ctx.device.cmd_pipeline_barrier2(cmd.handle(), &dependency);
+    }
+    Ok(cmd.finish()?)
+}
+
+/// Submit `cmds` on `queue`, waiting on `wait` and signaling `signal`.
+fn submit_raw(
+    ctx: &SharedContext,
+    queue: vk::Queue,
+    cmds: &[vk::CommandBuffer],
+    wait: Option<&Semaphore>,
+    signal: Option<&Semaphore>,
+    fence: Option<&Fence>,
+) -> Result<()> {
+    let cmd_infos = cmds
+        .iter()
+        .map(|&cmd| {
+            vk::CommandBufferSubmitInfo::builder()
+                .command_buffer(cmd)
+                .build()
+        })
+        .collect::<Vec<_>>();
+    // SAFETY: All handles are alive, their owners are kept until the fence is signaled.
+    unsafe {
+        let wait_infos = wait
+            .map(|semaphore| {
+                vk::SemaphoreSubmitInfo::builder()
+                    .semaphore(semaphore.handle())
+                    .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                    .build()
+            })
+            .into_iter()
+            .collect::<Vec<_>>();
+        let signal_infos = signal
+            .map(|semaphore| {
+                vk::SemaphoreSubmitInfo::builder()
+                    .semaphore(semaphore.handle())
+                    .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                    .build()
+            })
+            .into_iter()
+            .collect::<Vec<_>>();
+        let submit = vk::SubmitInfo2::builder()
+            .wait_semaphore_infos(&wait_infos)
+            .command_buffer_infos(&cmd_infos)
+            .signal_semaphore_infos(&signal_infos)
+            .build();
+        let fence = fence.map(|fence| fence.handle()).unwrap_or_default();
+        ctx.device
+            .queue_submit2(queue, std::slice::from_ref(&submit), fence)?;
+    }
+    Ok(())
+}
+
+/// Submit `cmd` on the compute queue, transferring ownership of `images` to the compute queue
+/// family for its duration. See the module documentation for the steps involved.
+pub(crate) fn submit(
+    ctx: &SharedContext,
+    cmd: CommandBuffer<Compute>,
+    images: &[SharedImage],
+) -> Result<AsyncSubmission> {
+    let (graphics_family, compute_family) = queue_families(ctx);
+    // Ownership only has to be transferred between different queue families.
+    let transfer = graphics_family != compute_family && !images.is_empty();
+    let barriers = |src, dst, release| {
+        images
+            .iter()
+            .map(|image| ownership_barrier(image, src, dst, release))
+            .collect::<Vec<_>>()
+    };
+
+    let released = Semaphore::new(ctx.device.clone())?;
+    let computed = Semaphore::new(ctx.device.clone())?;
+    let fence = Fence::new(ctx.device.clone(), false)?;
+    let mut graphics_cmds = vec![];
+    let mut compute_cmds = vec![];
+    if transfer {
+        graphics_cmds
+            .push(record_barriers::<All>(ctx, &barriers(graphics_family, compute_family, true))?);
+        compute_cmds.push(record_barriers::<Compute>(
+            ctx,
+            &barriers(graphics_family, compute_family, false),
+        )?);
+    }
+    compute_cmds.push(cmd);
+    if transfer {
+        compute_cmds.push(record_barriers::<Compute>(
+            ctx,
+            &barriers(compute_family, graphics_family, true),
+        )?);
+        graphics_cmds
+            .push(record_barriers::<All>(ctx, &barriers(compute_family, graphics_family, false))?);
+    }
+
+    // SAFETY: The command buffers are kept alive in the returned submission.
+    let (graphics_handles, compute_handles) = unsafe {
+        (
+            graphics_cmds
+                .iter()
+                .map(|cmd| cmd.handle())
+                .collect::<Vec<_>>(),
+            compute_cmds
+                .iter()
+                .map(|cmd| cmd.handle())
+                .collect::<Vec<_>>(),
+        )
+    };
+    // Either both the release and the acquire were recorded, or neither.
+    let (release, acquire) = match graphics_handles.as_slice() {
+        [release, acquire] => (std::slice::from_ref(release), std::slice::from_ref(acquire)),
+        _ => (&[][..], &[][..]),
+    };
+    // The queues are locked for each submission separately, since the compute queue may be the
+    // graphics queue.
+    {
+        // The release signals the compute submission even without barriers, which orders the
+        // compute work after all graphics work that was submitted before it.
+        let graphics = ctx.exec.get_queue::<All>().unwrap();
+        submit_raw(ctx, graphics.handle(), release, None, Some(&released), None)?;
+    }
+    {
+        // Without a dedicated compute queue, compute work goes to the graphics queue.
+        let compute = ctx
+            .exec
+            .get_queue::<Compute>()
+            .or_else(|| ctx.exec.get_queue::<All>())
+            .unwrap();
+        submit_raw(
+            ctx,
+            compute.handle(),
+            &compute_handles,
+            Some(&released),
+            Some(&computed),
+            None,
+        )?;
+    }
+    {
+        // Graphics submissions made after this one, such as the next frame, are ordered after
+        // this semaphore wait.
+        let graphics = ctx.exec.get_queue::<All>().unwrap();
+        submit_raw(ctx, graphics.handle(), acquire, Some(&computed), None, Some(&fence))?;
+    }
+
+    Ok(AsyncSubmission {
+        fence,
+        semaphores: vec![released, computed],
+        graphics_cmds,
+        compute_cmds,
+    })
+}
@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use inject::DI;
+use scheduler::{Event, EventBus, EventContext, StoredSystem, System};
+
+/// Render the current view to a PNG file at the given resolution, independent of the size of
+/// the world view. The UI is not included in the image. The renderer reports the result with
+/// a `MessageEvent`.
+#[derive(Debug, Clone)]
+pub struct RenderToFileEvent {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// Render at this multiple of the resolution and downsample the result, for example 2 or 4.
+    /// Supersampled renders bypass FSR2 and TAA for a clean reference image. A factor of 1
+    /// renders at the requested resolution directly.
+    pub supersample: u32,
+}
+
+impl Event for RenderToFileEvent {}
+
+/// Capture requested with [`RenderToFileEvent`]. The renderer takes the request before it
+/// renders the next frame. Access through DI.
+#[derive(Debug, Default)]
+pub struct FrameCapture {
+    pub request: Option<RenderToFileEvent>,
+}
+
+pub(crate) struct FrameCaptureSystem;
+
+impl System<DI> for FrameCaptureSystem {
+    fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+        event_bus.subscribe(system, handle_render_to_file);
+    }
+}
+
+/// # DI Access
+/// - Write [`FrameCapture`]
+fn handle_render_to_file(
+    _system: &mut FrameCaptureSystem,
+    event: &RenderToFileEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let mut capture = di.write_sync::<FrameCapture>().unwrap();
+    capture.request = Some(event.clone());
+    Ok(())
+}
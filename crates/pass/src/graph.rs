@@ -1,11 +1,51 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use anyhow::{anyhow, Result};
 use derivative::Derivative;
+use inject::DI;
 use phobos::graph::pass_graph::BuiltPassGraph;
-use phobos::{domain, Allocator, DefaultAllocator, Pass, PassGraph, VirtualResource};
+use phobos::{domain, Allocator, DefaultAllocator, GraphViz, Pass, PassGraph, VirtualResource};
+use scheduler::{Event, EventBus, EventContext, StoredSystem, System};
 use statistics::RendererStatistics;
 
+/// Write the frame graph of the next frame to a Graphviz DOT file. The renderer reports
+/// the result with a `MessageEvent`.
+#[derive(Debug, Clone)]
+pub struct DumpFrameGraphEvent {
+    pub path: PathBuf,
+}
+
+impl Event for DumpFrameGraphEvent {}
+
+/// Frame graph dump requested with [`DumpFrameGraphEvent`]. The renderer takes the path when
+/// it builds the next frame graph. Access through DI.
+#[derive(Debug, Default)]
+pub struct FrameGraphDump {
+    pub path: Option<PathBuf>,
+}
+
+pub(crate) struct FrameGraphDumpSystem;
+
+impl System<DI> for FrameGraphDumpSystem {
+    fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+        event_bus.subscribe(system, handle_dump_frame_graph);
+    }
+}
+
+/// # DI Access
+/// - Write [`FrameGraphDump`]
+fn handle_dump_frame_graph(
+    _system: &mut FrameGraphDumpSystem,
+    event: &DumpFrameGraphEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let mut dump = di.write_sync::<FrameGraphDump>().unwrap();
+    dump.path = Some(event.path.clone());
+    Ok(())
+}
+
 #[derive(Derivative, Default)]
 #[derivative(Debug)]
 pub struct FrameGraph<'cb, A: Allocator = DefaultAllocator> {
@@ -60,6 +100,37 @@ impl<'cb, A: Allocator> FrameGraph<'cb, A> {
             .ok_or_else(|| anyhow!("No such resource {resource:?}"))
     }
 
+    /// Builds the graph like [`FrameGraph::build`], and also returns a Graphviz DOT description
+    /// of it. Next to the passes and resource versions of the built graph, this lists the
+    /// aliases registered on this graph.
+    pub fn build_with_dot(
+        self,
+    ) -> Result<(BuiltPassGraph<'cb, domain::All, RendererStatistics, A>, String)> {
+        let mut aliases = self
+            .aliases
+            .iter()
+            .map(|(name, resource)| {
+                let resource = format!("{resource:?}").replace('"', "\\\"");
+                format!("        \"alias {name}\" [label=\"{name}\\n{resource}\" shape=note];\n")
+            })
+            .collect::<Vec<_>>();
+        // Sort for a stable output, the aliases are stored in a hash map
+        aliases.sort();
+        let graph = self.build()?;
+        let dot = format!("{}", graph.task_graph().dot()?);
+        // Insert the aliases as a separate cluster before the closing brace of the graph
+        let end = dot
+            .rfind('}')
+            .ok_or_else(|| anyhow!("Invalid DOT output for frame graph"))?;
+        let dot = format!(
+            "{}    subgraph cluster_aliases {{\n        label=\"Aliases\";\n{}    }}\n{}",
+            &dot[..end],
+            aliases.concat(),
+            &dot[end..]
+        );
+        Ok((graph, dot))
+    }
+
     pub fn build(self) -> Result<BuiltPassGraph<'cb, domain::All, RendererStatistics, A>> {
         let mut graph = PassGraph::new(Some(&self.swapchain_resource()));
         for (_, pass) in self.passes {
@@ -1,22 +1,86 @@
-use anyhow::{bail, Result};
+use std::fmt::{Display, Formatter};
+use std::sync::TryLockError;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+pub use async_compute::SharedImage;
+pub use capture::*;
+use futures::executor::block_on;
+use gfx::SharedContext;
 pub use graph::*;
 use inject::DI;
 pub use pass::*;
-use phobos::domain::All;
+use phobos::domain::{All, Compute};
 use phobos::sync::submit_batch::SubmitBatch;
+use phobos::CommandBuffer;
 use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+use util::RwLock;
+
+use crate::async_compute::AsyncSubmission;
 
+mod async_compute;
+pub mod capture;
 pub mod graph;
 pub mod pass;
 
+/// Queue that work such as brush strokes is submitted on.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkQueue {
+    /// Queue the work in the submit batch of the next frame, on the graphics queue.
+    #[default]
+    Graphics,
+    /// Submit the work right away on the dedicated compute queue, so it runs concurrently with
+    /// rendering. The graphics queue waits for it on a semaphore, so the next frame sees its
+    /// results. Without a dedicated compute queue, this shares a queue with graphics.
+    AsyncCompute,
+}
+
+impl WorkQueue {
+    pub const ALL: [WorkQueue; 2] = [WorkQueue::Graphics, WorkQueue::AsyncCompute];
+}
+
+/// Interval at which [`GpuWork::with_batch_timeout`] checks if the submit batch is available.
+const BATCH_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Returned by [`GpuWork::with_batch_timeout`] when the submit batch did not become available
+/// in time. Check for it with `downcast_ref` on the returned error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BatchTimeoutError {
+    pub timeout: Duration,
+}
+
+impl Display for BatchTimeoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no submit batch became available within {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for BatchTimeoutError {}
+
 pub struct GpuWork {
     pub batch: Option<SubmitBatch<All>>,
+    /// Queue new work should be submitted on.
+    queue: WorkQueue,
+    /// Work submitted on the compute queue that may still be executing.
+    async_work: Vec<AsyncSubmission>,
+    /// Amount of work queued in the current batch, this is submitted with the next frame.
+    pending: usize,
+    /// Amount of work handed to the GPU together with previous frames.
+    submitted: usize,
+    /// Set when the application is shutting down. No new work is accepted after this.
+    closed: bool,
 }
 
 impl GpuWork {
     fn new() -> Self {
         Self {
             batch: None,
+            queue: WorkQueue::default(),
+            async_work: vec![],
+            pending: 0,
+            submitted: 0,
+            closed: false,
         }
     }
 
@@ -25,26 +89,251 @@ impl GpuWork {
     }
 
     pub fn take_batch(&mut self) -> Option<SubmitBatch<All>> {
+        self.submitted += self.pending;
+        self.pending = 0;
         self.batch.take()
     }
 
-    pub fn with_batch<R, F: FnOnce(&mut SubmitBatch<All>) -> R>(
+    /// Amount of work that is queued in the current batch and was not submitted yet.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Total amount of work that was submitted through previous batches.
+    pub fn submitted(&self) -> usize {
+        self.submitted
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn queue(&self) -> WorkQueue {
+        self.queue
+    }
+
+    /// Amount of work submitted on the compute queue that may still be executing.
+    pub fn pending_async(&self) -> usize {
+        self.async_work.len()
+    }
+
+    /// Stop accepting new work and submit the work that is still queued in the current batch.
+    /// Returns the amount of work that was drained. The caller should still wait for the device
+    /// to become idle before tearing down.
+    /// # DI Access
+    /// - Write [`GpuWork`]
+    pub fn drain(bus: &EventBus<DI>) -> Result<usize> {
+        let batch = {
+            let di = bus.data().read().unwrap();
+            let mut this = di.write_sync::<Self>().unwrap();
+            this.closed = true;
+            let drained = this.pending;
+            this.take_batch().map(|batch| (batch, drained))
+        };
+        match batch {
+            Some((batch, drained)) if drained > 0 => {
+                block_on(batch.finish()?)?;
+                Ok(drained)
+            }
+            _ => Ok(0),
+        }
+    }
+
+    /// Submit the work queued in the current batch and wait for it to complete, replacing the
+    /// batch with a new one. Use this before reading back resources that queued work writes to.
+    /// Returns the amount of work that was flushed.
+    /// # DI Access
+    /// - Read [`SharedContext`]
+    /// - Write [`GpuWork`]
+    pub fn flush(bus: &EventBus<DI>) -> Result<usize> {
+        let batch = {
+            let di = bus.data().read().unwrap();
+            let ctx = di.get::<SharedContext>().cloned().unwrap();
+            let mut this = di.write_sync::<Self>().unwrap();
+            if this.pending == 0 {
+                return Ok(0);
+            }
+            let flushed = this.pending;
+            let batch = this.take_batch();
+            this.put_batch(ctx.exec.start_submit_batch()?);
+            batch.map(|batch| (batch, flushed))
+        };
+        match batch {
+            Some((batch, flushed)) => {
+                block_on(batch.finish()?)?;
+                Ok(flushed)
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Submit work on the compute queue right away. `images` are the images the work writes to,
+    /// their ownership is transferred to the compute queue for the duration of the work. Graphics
+    /// work submitted afterwards waits for it on the GPU. Fails after [`GpuWork::drain`] was
+    /// called.
+    /// # DI Access
+    /// - Read [`SharedContext`]
+    /// - Write [`GpuWork`]
+    pub fn submit_async(
+        bus: &EventBus<DI>,
+        cmd: CommandBuffer<Compute>,
+        images: &[SharedImage],
+    ) -> Result<()> {
+        let di = bus.data().read().unwrap();
+        let ctx = di.get::<SharedContext>().cloned().unwrap();
+        let mut this = di.write_sync::<Self>().unwrap();
+        if this.closed {
+            bail!("GPU work is no longer accepted because the application is shutting down.")
+        }
+        this.retire_async(&ctx)?;
+        let submission = async_compute::submit(&ctx, cmd, images)?;
+        this.async_work.push(submission);
+        this.submitted += 1;
+        Ok(())
+    }
+
+    /// Wait on the CPU for all work submitted on the compute queue to complete. Only needed
+    /// before reading back resources the work writes to, the graphics queue already waits for it.
+    /// Returns the amount of work that was waited for.
+    /// # DI Access
+    /// - Read [`SharedContext`]
+    /// - Write [`GpuWork`]
+    pub fn wait_async(bus: &EventBus<DI>) -> Result<usize> {
+        let (ctx, submissions) = {
+            let di = bus.data().read().unwrap();
+            let ctx = di.get::<SharedContext>().cloned().unwrap();
+            let mut this = di.write_sync::<Self>().unwrap();
+            (ctx, std::mem::take(&mut this.async_work))
+        };
+        let count = submissions.len();
+        for submission in submissions {
+            submission.wait()?;
+            submission.retire(&ctx)?;
+        }
+        Ok(count)
+    }
+
+    /// Free the resources of work on the compute queue that completed, without waiting for the
+    /// rest.
+    fn retire_async(&mut self, ctx: &SharedContext) -> Result<()> {
+        let mut pending = Vec::with_capacity(self.async_work.len());
+        for submission in std::mem::take(&mut self.async_work) {
+            if submission.is_done(ctx)? {
+                submission.retire(ctx)?;
+            } else {
+                pending.push(submission);
+            }
+        }
+        self.async_work = pending;
+        Ok(())
+    }
+
+    /// Select the queue new work is submitted on. When switching to the compute queue, work that
+    /// is still queued in the current batch is flushed first so it cannot execute after newer work.
+    /// # DI Access
+    /// - Read [`SharedContext`]
+    /// - Write [`GpuWork`]
+    pub fn select_queue(bus: &EventBus<DI>, queue: WorkQueue) -> Result<()> {
+        let di = bus.data().read().unwrap();
+        let ctx = di.get::<SharedContext>().cloned().unwrap();
+        let mut this = di.write_sync::<Self>().unwrap();
+        if this.queue == queue {
+            return Ok(());
+        }
+        this.queue = queue;
+        if queue != WorkQueue::AsyncCompute || this.pending == 0 {
+            return Ok(());
+        }
+        // Submit the batch before releasing the lock, so no compute work can be submitted
+        // ahead of it.
+        let batch = this.take_batch();
+        this.put_batch(ctx.exec.start_submit_batch()?);
+        let submitted = batch.map(|batch| batch.finish()).transpose()?;
+        drop(this);
+        if let Some(submitted) = submitted {
+            block_on(submitted)?;
+        }
+        Ok(())
+    }
+
+    /// Call `f` with the current submit batch to queue work on it. The work only counts as
+    /// pending if `f` succeeds. Fails after [`GpuWork::drain`] was called. This blocks until no
+    /// one else holds [`GpuWork`], see [`GpuWork::with_batch_timeout`] for a version that gives
+    /// up.
+    /// # DI Access
+    /// - Write [`GpuWork`]
+    pub fn with_batch<R, F: FnOnce(&mut SubmitBatch<All>) -> Result<R>>(
         bus: &EventBus<DI>,
         f: F,
     ) -> Result<R> {
         let di = bus.data().read().unwrap();
         let mut this = di.write_sync::<Self>().unwrap();
-        match &mut this.batch {
+        this.queue_on_batch(f)
+    }
+
+    /// Like [`GpuWork::with_batch`], but waits at most `timeout` for the submit batch to become
+    /// available. The batch is unavailable while the renderer is holding [`GpuWork`], or before
+    /// the first frame registered one. Fails with [`BatchTimeoutError`] when the timeout elapses,
+    /// so the caller can skip its work instead of hanging.
+    /// # DI Access
+    /// - Write [`GpuWork`]
+    pub fn with_batch_timeout<R, F: FnOnce(&mut SubmitBatch<All>) -> Result<R>>(
+        bus: &EventBus<DI>,
+        timeout: Duration,
+        f: F,
+    ) -> Result<R> {
+        // No deadline if the timeout is too large to represent
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            {
+                let di = bus.data().read().unwrap();
+                let lock = di.get::<RwLock<Self>>().unwrap();
+                match lock.try_write() {
+                    Ok(mut this) if this.closed || this.batch.is_some() => {
+                        return this.queue_on_batch(f);
+                    }
+                    Ok(_) | Err(TryLockError::WouldBlock) => {}
+                    Err(TryLockError::Poisoned(_)) => {
+                        return Err(anyhow!("Lock on GPU work was poisoned"));
+                    }
+                }
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(BatchTimeoutError {
+                    timeout,
+                }
+                .into());
+            }
+            std::thread::sleep(BATCH_POLL_INTERVAL);
+        }
+    }
+
+    fn queue_on_batch<R, F: FnOnce(&mut SubmitBatch<All>) -> Result<R>>(
+        &mut self,
+        f: F,
+    ) -> Result<R> {
+        if self.closed {
+            bail!("GPU work is no longer accepted because the application is shutting down.")
+        }
+        match &mut self.batch {
             None => {
                 bail!("No submit batch registered. This is a bug the application.")
             }
-            Some(batch) => Ok(f(batch)),
+            Some(batch) => {
+                let result = f(batch)?;
+                self.pending += 1;
+                Ok(result)
+            }
         }
     }
 }
 
 pub fn initialize(bus: &EventBus<DI>) {
+    bus.add_system(FrameGraphDumpSystem);
+    bus.add_system(FrameCaptureSystem);
     let work = GpuWork::new();
     let mut di = bus.data().write().unwrap();
     di.put_sync(work);
+    di.put_sync(FrameGraphDump::default());
+    di.put_sync(FrameCapture::default());
 }
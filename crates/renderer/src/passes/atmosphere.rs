@@ -13,6 +13,20 @@ use world::World;
 
 use crate::{ubo_struct, ubo_struct_assign};
 
+/// Inverse projection the sky rays are built from. All rays of an orthographic projection are
+/// parallel and would see the same sky color, so in orthographic mode the rays are spread around
+/// the view direction with the field of view of perspective mode.
+fn sky_inverse_projection(state: &RenderState) -> Mat4 {
+    if !state.orthographic {
+        return state.inverse_projection;
+    }
+    let aspect = state.render_size.x as f32 / state.render_size.y as f32;
+    let mut projection = Mat4::perspective_rh(state.fov, aspect, state.near, state.far);
+    // Flip y like the camera projection
+    projection.col_mut(1).y *= -1.0;
+    projection.inverse()
+}
+
 /// The atmosphere renderer is responsible for rendering the
 /// atmosphere into the frame graph.
 #[allow(dead_code)]
@@ -70,7 +84,7 @@ impl AtmosphereRenderer {
                     ifc,
                     struct Camera {
                         pv: Mat4 = state.projection_view,
-                        inv_proj: Mat4 = state.inverse_projection,
+                        inv_proj: Mat4 = sky_inverse_projection(state),
                         inv_view_rotation: Mat4 = state.inverse_view_rotation,
                         cam_pos: Vec4 = state.cam_position.xyzx(),
                     }
@@ -1,4 +1,7 @@
 pub mod atmosphere;
+pub mod ortho_depth;
+pub mod props;
+pub mod target_view;
 pub mod terrain;
 pub mod terrain_decal;
 pub mod world_position;
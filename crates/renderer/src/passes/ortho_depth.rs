@@ -0,0 +1,110 @@
+use anyhow::Result;
+use gfx::create_raw_sampler;
+use gfx::state::RenderState;
+use hot_reload::IntoDynamic;
+use inject::DI;
+use pass::FrameGraph;
+use phobos as ph;
+use phobos::{vk, Allocator, GraphicsCmdBuffer};
+use scheduler::EventBus;
+use statistics::{RendererStatistics, TimedCommandBuffer};
+
+use crate::util::targets::{RenderTargets, SizeGroup};
+
+/// Push constants of the orthographic depth conversion shader.
+/// Kept in sync with `PC` in `ortho_depth.fs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct OrthoDepthParams {
+    near: f32,
+    far: f32,
+}
+
+/// Converts the depth buffer of an orthographic projection to the depth a perspective
+/// projection with the same near and far planes writes, see
+/// [`Projection::perspective_depth`](camera::Projection::perspective_depth). FSR2 reconstructs
+/// distances from the depth buffer assuming a perspective projection.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct OrthoDepth {
+    ctx: gfx::SharedContext,
+    sampler: ph::Sampler,
+}
+
+impl OrthoDepth {
+    /// Initialize the depth conversion. Adds a new target with name [`Self::output_name()`] to
+    /// the render target database.
+    pub fn new(
+        ctx: gfx::SharedContext,
+        targets: &mut RenderTargets,
+        bus: &mut EventBus<DI>,
+    ) -> Result<Self> {
+        ph::PipelineBuilder::new("ortho_depth")
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .cull_mask(vk::CullModeFlags::NONE)
+            .depth(false, false, false, vk::CompareOp::ALWAYS)
+            .blend_attachment_none()
+            .into_dynamic()
+            .attach_shader("shaders/src/fullscreen.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader("shaders/src/ortho_depth.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .expect_binding(0, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .expect_push_constants(std::mem::size_of::<OrthoDepthParams>() as u32)
+            .build(bus, ctx.pipelines.clone())?;
+
+        targets.register_color_target(
+            Self::output_name(),
+            SizeGroup::RenderResolution,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::Format::R32_SFLOAT,
+        )?;
+
+        Ok(Self {
+            sampler: create_raw_sampler(&ctx)?,
+            ctx,
+        })
+    }
+
+    /// Get the name of the attachment the converted depth is written to.
+    pub fn output_name() -> &'static str {
+        "fsr2_depth"
+    }
+
+    /// Convert the depth buffer with the near and far planes of `state`.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - The frame graph to add the conversion pass to.
+    /// * `depth` - The depth buffer. The latest version will be queried from the graph.
+    /// * `output` - The attachment to write the converted depth to.
+    /// * `state` - Render state holding the near and far planes of the projection.
+    pub fn render<'cb, A: Allocator>(
+        &'cb self,
+        graph: &mut FrameGraph<'cb, A>,
+        depth: &ph::VirtualResource,
+        output: &ph::VirtualResource,
+        state: &RenderState,
+    ) -> Result<()> {
+        let depth = graph.latest_version(depth)?;
+        let params = OrthoDepthParams {
+            near: state.near,
+            far: state.far,
+        };
+        let pass = ph::PassBuilder::render("ortho_depth")
+            .color_attachment(output, vk::AttachmentLoadOp::DONT_CARE, None)?
+            .sample_image(&depth, ph::PipelineStage::FRAGMENT_SHADER)
+            .execute_fn(move |mut cmd, _ifc, bindings, stats: &mut RendererStatistics| {
+                cmd = cmd
+                    .begin_section(stats, "ortho_depth")?
+                    .bind_graphics_pipeline("ortho_depth")?
+                    .full_viewport_scissor()
+                    .push_constant(vk::ShaderStageFlags::FRAGMENT, 0, &params)
+                    .resolve_and_bind_sampled_image(0, 0, &depth, &self.sampler, bindings)?
+                    .draw(6, 1, 0, 0)?
+                    .end_section(stats, "ortho_depth")?;
+                Ok(cmd)
+            })
+            .build();
+        graph.add_pass(pass);
+        Ok(())
+    }
+}
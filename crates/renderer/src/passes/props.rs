@@ -0,0 +1,262 @@
+use std::f32::consts::TAU;
+
+use anyhow::Result;
+use gfx::state::RenderState;
+use glam::{Mat4, Vec3, Vec3Swizzles, Vec4};
+use hot_reload::IntoDynamic;
+use inject::DI;
+use pass::FrameGraph;
+use phobos as ph;
+use phobos::{vk, Allocator, Buffer, BufferView, DeletionQueue, GraphicsCmdBuffer, MemoryType};
+use scheduler::EventBus;
+use statistics::{RendererStatistics, TimedCommandBuffer};
+use world::{PropInstances, PropMesh, World};
+
+use crate::ubo_struct_assign;
+
+/// Vertices of a prop mesh, uploaded once.
+#[allow(dead_code)]
+#[derive(Debug)]
+struct MeshBuffer {
+    /// Owns the memory the view refers to.
+    buffer: Buffer,
+    view: BufferView,
+    vertex_count: u32,
+}
+
+/// Transforms of the props, uploaded every time the props are scattered.
+#[derive(Debug)]
+struct InstanceBuffer {
+    buffer: Buffer,
+    view: BufferView,
+    count: u32,
+    /// Version of the [`PropInstances`] the buffer was uploaded from.
+    version: u64,
+}
+
+/// Draws the props scattered over the terrain, with one instance of the prop mesh per prop.
+#[derive(Debug)]
+pub struct PropRenderer {
+    ctx: gfx::SharedContext,
+    bus: EventBus<DI>,
+    /// Meshes in the order of [`PropMesh::ALL`].
+    meshes: Vec<MeshBuffer>,
+    instances: Option<InstanceBuffer>,
+    /// Instance buffers that may still be used by frames in flight.
+    deferred_delete: DeletionQueue<Buffer>,
+}
+
+impl PropRenderer {
+    /// Create the prop pipeline and upload the prop meshes.
+    pub fn new(ctx: gfx::SharedContext, bus: &mut EventBus<DI>) -> Result<Self> {
+        ph::PipelineBuilder::new("props")
+            .vertex_input(0, vk::VertexInputRate::VERTEX)
+            .vertex_attribute(0, 0, vk::Format::R32G32B32_SFLOAT)?
+            // The transform of an instance is passed as four columns
+            .vertex_input(1, vk::VertexInputRate::INSTANCE)
+            .vertex_attribute(1, 1, vk::Format::R32G32B32A32_SFLOAT)?
+            .vertex_attribute(1, 2, vk::Format::R32G32B32A32_SFLOAT)?
+            .vertex_attribute(1, 3, vk::Format::R32G32B32A32_SFLOAT)?
+            .vertex_attribute(1, 4, vk::Format::R32G32B32A32_SFLOAT)?
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .blend_attachment_none()
+            .blend_attachment_none()
+            .depth(true, true, false, vk::CompareOp::LESS)
+            .cull_mask(vk::CullModeFlags::NONE)
+            .into_dynamic()
+            .attach_shader("shaders/src/props.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader("shaders/src/props.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .build(bus, ctx.pipelines.clone())?;
+
+        let meshes = PropMesh::ALL
+            .iter()
+            .map(|mesh| {
+                let vertices = prop_triangles(*mesh);
+                let buffer = upload_vertices(&ctx, &vertices)?;
+                Ok(MeshBuffer {
+                    view: buffer.view_full(),
+                    buffer,
+                    vertex_count: vertices.len() as u32,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            ctx,
+            bus: bus.clone(),
+            meshes,
+            instances: None,
+            deferred_delete: DeletionQueue::new(4),
+        })
+    }
+
+    /// Update the deferred deletion queue of old instance buffers.
+    pub fn next_frame(&mut self) {
+        self.deferred_delete.next_frame();
+    }
+
+    /// Upload the transforms of the props if they were scattered again since the last upload.
+    /// # DI Access
+    /// - Read [`PropInstances`]
+    fn update_instances(&mut self, world: &World) -> Result<()> {
+        let di = self.bus.data().read().unwrap();
+        let props = di.read_sync::<PropInstances>().unwrap();
+        if props.terrain.is_none() || props.terrain != world.terrain {
+            if let Some(old) = self.instances.take() {
+                self.deferred_delete.push(old.buffer);
+            }
+            return Ok(());
+        }
+        if matches!(&self.instances, Some(instances) if instances.version == props.version) {
+            return Ok(());
+        }
+        if let Some(old) = self.instances.take() {
+            self.deferred_delete.push(old.buffer);
+        }
+        if !props.transforms.is_empty() {
+            let buffer = upload_vertices(&self.ctx, &props.transforms)?;
+            self.instances = Some(InstanceBuffer {
+                view: buffer.view_full(),
+                buffer,
+                count: props.transforms.len() as u32,
+                version: props.version,
+            });
+        }
+        Ok(())
+    }
+
+    /// Render the props over the terrain. Nothing is drawn if there are no props on the
+    /// terrain of the world.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - The frame graph to add the pass to.
+    /// * `color` - The color attachment to render to. The latest version will be queried from the graph.
+    /// * `motion` - The motion vector attachment. The latest version will be queried from the graph.
+    /// * `depth` - The depth attachment to test against. The latest version will be queried from the graph.
+    /// * `world` - The world holding the prop settings.
+    /// * `state` - The render state with camera settings.
+    pub fn render<'cb, A: Allocator>(
+        &'cb mut self,
+        graph: &mut FrameGraph<'cb, A>,
+        color: &ph::VirtualResource,
+        motion: &ph::VirtualResource,
+        depth: &ph::VirtualResource,
+        world: &'cb World,
+        state: &'cb RenderState,
+    ) -> Result<()> {
+        self.update_instances(world)?;
+        let this = &*self;
+        let Some(instances) = &this.instances else { return Ok(()) };
+        let mesh_index = PropMesh::ALL
+            .iter()
+            .position(|mesh| *mesh == world.props.mesh)
+            .unwrap();
+        let mesh = &this.meshes[mesh_index];
+        let pass = ph::PassBuilder::<_, _, A>::render("props")
+            .color_attachment(&graph.latest_version(color)?, vk::AttachmentLoadOp::LOAD, None)?
+            .color_attachment(&graph.latest_version(motion)?, vk::AttachmentLoadOp::LOAD, None)?
+            .depth_attachment(&graph.latest_version(depth)?, vk::AttachmentLoadOp::LOAD, None)?
+            .execute_fn(move |cmd, ifc, _bindings, stats: &mut RendererStatistics| {
+                ubo_struct_assign!(
+                    camera,
+                    ifc,
+                    struct Camera {
+                        projection_view: Mat4 = state.projection_view,
+                        previous_pv: Mat4 = state.previous_pv,
+                        cam_position: Vec4 = state.cam_position.extend(1.0),
+                        sun_direction: Vec4 = state.sun_direction.xyzx(),
+                    }
+                );
+                let color = world.props.color.extend(1.0);
+                let cmd = cmd
+                    .begin_section(stats, "props")?
+                    .bind_graphics_pipeline("props")?
+                    .full_viewport_scissor()
+                    .bind_uniform_buffer(0, 0, &camera_buffer)?
+                    .push_constant(vk::ShaderStageFlags::FRAGMENT, 0, &color)
+                    .bind_vertex_buffer(0, &mesh.view)
+                    .bind_vertex_buffer(1, &instances.view)
+                    .draw(mesh.vertex_count, instances.count, 0, 0)?;
+                stats.end_section(cmd, "props")
+            })
+            .build();
+        graph.add_pass(pass);
+        Ok(())
+    }
+}
+
+/// Copy `data` to a new vertex buffer that is visible to the CPU.
+fn upload_vertices<T: Copy>(ctx: &gfx::SharedContext, data: &[T]) -> Result<Buffer> {
+    let mut ctx = ctx.clone();
+    let buffer = Buffer::new(
+        ctx.device.clone(),
+        &mut ctx.allocator,
+        std::mem::size_of_val(data) as u64,
+        vk::BufferUsageFlags::VERTEX_BUFFER,
+        MemoryType::CpuToGpu,
+    )?;
+    buffer
+        .view_full()
+        .mapped_slice::<T>()?
+        .copy_from_slice(data);
+    Ok(buffer)
+}
+
+/// Number of sides of the round parts of the prop meshes.
+const SIDES: usize = 8;
+
+/// Returns the triangles of a prop mesh as a list of vertices, three per triangle. Props
+/// stand on the origin and are one meter tall, so the scale of a prop is its height in meters.
+/// Both meshes reach slightly below the origin, so they do not float on a slope.
+fn prop_triangles(mesh: PropMesh) -> Vec<Vec3> {
+    match mesh {
+        PropMesh::Tree => {
+            let trunk_bottom = ring([0.04; SIDES], -0.1);
+            let trunk_top = ring([0.04; SIDES], 0.2);
+            let crown = ring([0.25; SIDES], 0.15);
+            [
+                band(&trunk_bottom, &trunk_top),
+                fan(&crown, Vec3::new(0.0, 0.15, 0.0)),
+                fan(&crown, Vec3::new(0.0, 1.0, 0.0)),
+            ]
+            .concat()
+        }
+        PropMesh::Rock => {
+            // Irregular radii so the rocks do not look like a cut gem
+            const RADII: [f32; SIDES] = [0.55, 0.45, 0.6, 0.5, 0.42, 0.58, 0.47, 0.52];
+            let base = ring(RADII, 0.0);
+            let shoulder = ring(std::array::from_fn(|i| RADII[(i + 3) % SIDES] * 0.8), 0.6);
+            [
+                fan(&base, Vec3::new(0.0, -0.3, 0.0)),
+                band(&base, &shoulder),
+                fan(&shoulder, Vec3::new(0.1, 1.0, -0.05)),
+            ]
+            .concat()
+        }
+    }
+}
+
+/// Returns a horizontal ring of points around the y axis at `height`.
+fn ring(radii: [f32; SIDES], height: f32) -> [Vec3; SIDES] {
+    std::array::from_fn(|i| {
+        let angle = i as f32 / SIDES as f32 * TAU;
+        Vec3::new(angle.cos() * radii[i], height, angle.sin() * radii[i])
+    })
+}
+
+/// Connects every edge of a ring to a single point.
+fn fan(ring: &[Vec3; SIDES], tip: Vec3) -> Vec<Vec3> {
+    (0..SIDES)
+        .flat_map(|i| [ring[i], ring[(i + 1) % SIDES], tip])
+        .collect()
+}
+
+/// Connects the edges of two rings with quads.
+fn band(bottom: &[Vec3; SIDES], top: &[Vec3; SIDES]) -> Vec<Vec3> {
+    (0..SIDES)
+        .flat_map(|i| {
+            let j = (i + 1) % SIDES;
+            [bottom[i], bottom[j], top[j], bottom[i], top[j], top[i]]
+        })
+        .collect()
+}
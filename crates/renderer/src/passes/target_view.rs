@@ -0,0 +1,145 @@
+use anyhow::Result;
+use gfx::create_raw_sampler;
+use gfx::state::RenderState;
+use hot_reload::IntoDynamic;
+use inject::DI;
+use pass::FrameGraph;
+use phobos as ph;
+use phobos::{vk, Allocator, GraphicsCmdBuffer};
+use scheduler::EventBus;
+use statistics::{RendererStatistics, TimedCommandBuffer};
+
+use crate::util::targets::{RenderTargets, SizeGroup};
+
+/// Multiplier on motion vectors in the visualization, so motion of a few pixels is visible.
+const MOTION_SCALE: f32 = 20.0;
+
+/// How a render target is visualized.
+/// Kept in sync with the `MODE_` constants in `target_view.fs.hlsl`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ViewMode {
+    /// Show the color as it is, clamped to the displayable range.
+    Color = 0,
+    /// Linearize the depth and show it on a logarithmic scale.
+    Depth = 1,
+    /// Encode motion vectors as a color.
+    Motion = 2,
+}
+
+impl ViewMode {
+    /// Pick a visualization based on the format of a target.
+    fn from_format(format: vk::Format) -> Self {
+        match format {
+            vk::Format::D16_UNORM
+            | vk::Format::D32_SFLOAT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT => ViewMode::Depth,
+            vk::Format::R16G16_SFLOAT | vk::Format::R32G32_SFLOAT => ViewMode::Motion,
+            _ => ViewMode::Color,
+        }
+    }
+}
+
+/// Push constants of the target view shader.
+/// Kept in sync with `PC` in `target_view.fs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct TargetViewParams {
+    mode: u32,
+    near: f32,
+    far: f32,
+    motion_scale: f32,
+    orthographic: u32,
+}
+
+/// Draws any render target into a displayable image, so it can be inspected in the target
+/// viewer of the GUI.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct TargetView {
+    ctx: gfx::SharedContext,
+    sampler: ph::Sampler,
+}
+
+impl TargetView {
+    /// Initialize the target view. Adds a new target with name [`Self::output_name()`] to the
+    /// render target database, and creates pipelines.
+    pub fn new(
+        ctx: gfx::SharedContext,
+        targets: &mut RenderTargets,
+        bus: &mut EventBus<DI>,
+    ) -> Result<Self> {
+        ph::PipelineBuilder::new("target_view")
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .cull_mask(vk::CullModeFlags::NONE)
+            .depth(false, false, false, vk::CompareOp::ALWAYS)
+            .blend_attachment_none()
+            .into_dynamic()
+            .attach_shader("shaders/src/fullscreen.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader("shaders/src/target_view.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .expect_binding(0, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .expect_push_constants(std::mem::size_of::<TargetViewParams>() as u32)
+            .build(bus, ctx.pipelines.clone())?;
+
+        targets.register_color_target(
+            Self::output_name(),
+            SizeGroup::OutputResolution,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::Format::R8G8B8A8_SRGB,
+        )?;
+
+        Ok(Self {
+            sampler: create_raw_sampler(&ctx)?,
+            ctx,
+        })
+    }
+
+    /// Get the name of the attachment the visualized target is drawn to.
+    pub fn output_name() -> &'static str {
+        "target_view"
+    }
+
+    /// Draw a target into [`Self::output_name()`]. Targets that are not written this frame
+    /// are skipped, so the previous image stays visible.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - The frame graph to add the pass to.
+    /// * `target` - The target to visualize. The latest version will be queried from the graph.
+    /// * `format` - Format of the target, used to pick a visualization.
+    /// * `state` - The render state, used to linearize depth.
+    pub fn render<'cb, A: Allocator>(
+        &'cb self,
+        graph: &mut FrameGraph<'cb, A>,
+        target: &ph::VirtualResource,
+        format: vk::Format,
+        state: &RenderState,
+    ) -> Result<()> {
+        let Ok(input) = graph.latest_version(target) else { return Ok(()) };
+        let output = ph::VirtualResource::image(Self::output_name());
+        let params = TargetViewParams {
+            mode: ViewMode::from_format(format) as u32,
+            near: state.near,
+            far: state.far,
+            motion_scale: MOTION_SCALE,
+            orthographic: state.orthographic as u32,
+        };
+        let pass = ph::PassBuilder::render("target_view")
+            .color_attachment(&output, vk::AttachmentLoadOp::DONT_CARE, None)?
+            .sample_image(&input, ph::PipelineStage::FRAGMENT_SHADER)
+            .execute_fn(move |mut cmd, _ifc, bindings, stats: &mut RendererStatistics| {
+                cmd = cmd
+                    .begin_section(stats, "target_view")?
+                    .bind_graphics_pipeline("target_view")?
+                    .full_viewport_scissor()
+                    .push_constant(vk::ShaderStageFlags::FRAGMENT, 0, &params)
+                    .resolve_and_bind_sampled_image(0, 0, &input, &self.sampler, bindings)?
+                    .draw(6, 1, 0, 0)?
+                    .end_section(stats, "target_view")?;
+                Ok(cmd)
+            })
+            .build();
+        graph.add_pass(pass);
+        Ok(())
+    }
+}
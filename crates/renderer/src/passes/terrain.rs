@@ -1,25 +1,42 @@
 use anyhow::Result;
 use assets::storage::AssetStorage;
+use assets::BorderMode;
 use gfx::state::RenderState;
-use gfx::{create_linear_sampler, create_raw_sampler};
+use gfx::{create_linear_sampler, create_linear_sampler_with, create_raw_sampler_with};
 use glam::{Mat4, Vec3Swizzles, Vec4};
 use hot_reload::IntoDynamic;
 use inject::DI;
 use pass::FrameGraph;
 use ph::vk;
+use phobos::domain::ExecutionDomain;
 use phobos::prelude::traits::*;
-use phobos::{prelude as ph, VirtualResource};
+use phobos::{
+    prelude as ph, GfxSupport, InFlightContext, IncompleteCommandBuffer, VirtualResource,
+};
 use scheduler::EventBus;
 use statistics::{RendererStatistics, TimedCommandBuffer};
-use world::World;
+use world::{TerrainShading, TessellationSpacing, World};
 
 use crate::{ubo_struct, ubo_struct_assign};
 
+/// Values the attachments of the terrain pass are cleared to.
+#[derive(Debug, Copy, Clone)]
+pub struct TerrainClearValues {
+    pub color: vk::ClearColorValue,
+    pub motion: vk::ClearColorValue,
+    pub depth: vk::ClearDepthStencilValue,
+}
+
 /// The terrain renderer. Stores resources it needs for rendering.
 /// This struct renders the main terrain mesh.
 #[derive(Debug)]
 pub struct TerrainRenderer {
-    heightmap_sampler: ph::Sampler,
+    /// Heightmap samplers for each border mode, in the order of [`BorderMode::ALL`].
+    heightmap_samplers: Vec<ph::Sampler>,
+    /// Samplers for the textures stretched over the terrain for each border mode, in the order
+    /// of [`BorderMode::ALL`].
+    surface_samplers: Vec<ph::Sampler>,
+    /// Sampler for the detail map, which is tiled over the terrain so it always repeats.
     linear_sampler: ph::Sampler,
     bus: EventBus<DI>,
 }
@@ -28,8 +45,108 @@ impl TerrainRenderer {
     /// Create a new terrain renderer, this will initialize some resources and create
     /// necessary pipelines.
     pub fn new(ctx: gfx::SharedContext, bus: &mut EventBus<DI>) -> Result<Self> {
-        ph::PipelineBuilder::new("terrain")
-            .depth(true, true, false, vk::CompareOp::LESS)
+        for spacing in TessellationSpacing::ALL {
+            for prepass in [false, true] {
+                Self::create_pipeline(
+                    &ctx,
+                    bus,
+                    "terrain",
+                    "shaders/src/terrain.fs.hlsl",
+                    prepass,
+                    spacing,
+                )?;
+                Self::create_pipeline(
+                    &ctx,
+                    bus,
+                    "terrain_matcap",
+                    "shaders/src/terrain_matcap.fs.hlsl",
+                    prepass,
+                    spacing,
+                )?;
+                Self::create_pipeline(
+                    &ctx,
+                    bus,
+                    "terrain_slope",
+                    "shaders/src/terrain_slope.fs.hlsl",
+                    prepass,
+                    spacing,
+                )?;
+                Self::create_pipeline(
+                    &ctx,
+                    bus,
+                    "terrain_curvature",
+                    "shaders/src/terrain_curvature.fs.hlsl",
+                    prepass,
+                    spacing,
+                )?;
+            }
+            Self::create_depth_pipeline(&ctx, bus, spacing)?;
+        }
+        Ok(Self {
+            heightmap_samplers: BorderMode::ALL
+                .iter()
+                .map(|mode| create_raw_sampler_with(&ctx, mode.address_mode()))
+                .collect::<Result<_>>()?,
+            surface_samplers: BorderMode::ALL
+                .iter()
+                .map(|mode| create_linear_sampler_with(&ctx, mode.address_mode()))
+                .collect::<Result<_>>()?,
+            linear_sampler: create_linear_sampler(&ctx)?,
+            bus: bus.clone(),
+        })
+    }
+
+    fn heightmap_sampler(&self, mode: BorderMode) -> &ph::Sampler {
+        let index = BorderMode::ALL.iter().position(|m| *m == mode).unwrap();
+        &self.heightmap_samplers[index]
+    }
+
+    fn surface_sampler(&self, mode: BorderMode) -> &ph::Sampler {
+        let index = BorderMode::ALL.iter().position(|m| *m == mode).unwrap();
+        &self.surface_samplers[index]
+    }
+
+    /// Tessellation spacing is a compile-time attribute of the hull shader, so every terrain
+    /// pipeline has a variant per spacing mode. Returns the hull shader entry point and the
+    /// suffix of the pipeline names of a spacing mode.
+    fn spacing_variant(spacing: TessellationSpacing) -> (&'static str, &'static str) {
+        match spacing {
+            TessellationSpacing::Integer => ("main", ""),
+            TessellationSpacing::FractionalEven => ("main_fractional_even", "_fractional_even"),
+            TessellationSpacing::FractionalOdd => ("main_fractional_odd", "_fractional_odd"),
+        }
+    }
+
+    /// Name of the color pipeline for a shading mode. After a depth prepass, a variant that
+    /// only draws fragments matching the prepass depth is used.
+    fn pipeline_name(
+        shading: TerrainShading,
+        prepass: bool,
+        spacing: TessellationSpacing,
+    ) -> String {
+        let (_, suffix) = Self::spacing_variant(spacing);
+        let name = match (shading, prepass) {
+            (TerrainShading::Lit, false) => "terrain",
+            (TerrainShading::Lit, true) => "terrain_after_prepass",
+            (TerrainShading::Matcap, false) => "terrain_matcap",
+            (TerrainShading::Matcap, true) => "terrain_matcap_after_prepass",
+            (TerrainShading::Slope, false) => "terrain_slope",
+            (TerrainShading::Slope, true) => "terrain_slope_after_prepass",
+            (TerrainShading::Curvature, false) => "terrain_curvature",
+            (TerrainShading::Curvature, true) => "terrain_curvature_after_prepass",
+        };
+        format!("{name}{suffix}")
+    }
+
+    /// Name of the depth-only pipeline used for the depth prepass.
+    fn depth_pipeline_name(spacing: TessellationSpacing) -> String {
+        let (_, suffix) = Self::spacing_variant(spacing);
+        format!("terrain_depth{suffix}")
+    }
+
+    /// Adds the vertex and tessellation stages shared by all terrain pipelines.
+    fn terrain_pipeline_builder(name: &str) -> Result<ph::PipelineBuilder> {
+        Ok(ph::PipelineBuilder::new(name)
             .dynamic_states(&[
                 vk::DynamicState::SCISSOR,
                 vk::DynamicState::VIEWPORT,
@@ -38,14 +155,65 @@ impl TerrainRenderer {
             .vertex_input(0, vk::VertexInputRate::VERTEX)
             .vertex_attribute(0, 0, vk::Format::R32G32_SFLOAT)?
             .vertex_attribute(0, 1, vk::Format::R32G32_SFLOAT)?
+            .tessellation(4, vk::PipelineTessellationStateCreateFlags::empty()))
+    }
+
+    /// Create a terrain pipeline. Pipelines for the different shading modes only differ in
+    /// their fragment shader. If `prepass` is set, the pipeline tests against the depth of
+    /// the depth prepass instead of writing depth.
+    fn create_pipeline(
+        ctx: &gfx::SharedContext,
+        bus: &mut EventBus<DI>,
+        name: &str,
+        fragment_shader: &str,
+        prepass: bool,
+        spacing: TessellationSpacing,
+    ) -> Result<()> {
+        let (hull_entry, suffix) = Self::spacing_variant(spacing);
+        let builder = match prepass {
+            false => Self::terrain_pipeline_builder(&format!("{name}{suffix}"))?.depth(
+                true,
+                true,
+                false,
+                vk::CompareOp::LESS,
+            ),
+            true => Self::terrain_pipeline_builder(&format!("{name}_after_prepass{suffix}"))?
+                .depth(true, false, false, vk::CompareOp::EQUAL),
+        };
+        builder
             .blend_attachment_none()
             .blend_attachment_none()
-            .tessellation(4, vk::PipelineTessellationStateCreateFlags::empty())
             .into_dynamic()
             .attach_shader("shaders/src/terrain.vs.hlsl", vk::ShaderStageFlags::VERTEX)
-            .attach_shader("shaders/src/terrain.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .attach_shader(fragment_shader, vk::ShaderStageFlags::FRAGMENT)
+            .attach_shader_entry(
+                "shaders/src/terrain.hs.hlsl",
+                hull_entry,
+                vk::ShaderStageFlags::TESSELLATION_CONTROL,
+            )
             .attach_shader(
+                "shaders/src/terrain.ds.hlsl",
+                vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+            )
+            .build(bus, ctx.pipelines.clone())?;
+        Ok(())
+    }
+
+    /// Create the depth-only pipeline used for the depth prepass. It runs the same vertex and
+    /// tessellation shaders as the color pipelines, so the resulting depth matches exactly.
+    fn create_depth_pipeline(
+        ctx: &gfx::SharedContext,
+        bus: &mut EventBus<DI>,
+        spacing: TessellationSpacing,
+    ) -> Result<()> {
+        let (hull_entry, _) = Self::spacing_variant(spacing);
+        Self::terrain_pipeline_builder(&Self::depth_pipeline_name(spacing))?
+            .depth(true, true, false, vk::CompareOp::LESS)
+            .into_dynamic()
+            .attach_shader("shaders/src/terrain.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader_entry(
                 "shaders/src/terrain.hs.hlsl",
+                hull_entry,
                 vk::ShaderStageFlags::TESSELLATION_CONTROL,
             )
             .attach_shader(
@@ -53,20 +221,188 @@ impl TerrainRenderer {
                 vk::ShaderStageFlags::TESSELLATION_EVALUATION,
             )
             .build(bus, ctx.pipelines.clone())?;
-        Ok(Self {
-            heightmap_sampler: create_raw_sampler(&ctx)?,
-            linear_sampler: create_linear_sampler(&ctx)?,
-            bus: bus.clone(),
-        })
+        Ok(())
+    }
+
+    /// Records the terrain draw with the given pipeline. If the terrain is not ready, nothing
+    /// is drawn. If `depth_only` is set, only the resources used by the vertex and
+    /// tessellation stages are bound.
+    fn record_draw<'q, D: ExecutionDomain + GfxSupport, A: Allocator>(
+        &self,
+        cmd: IncompleteCommandBuffer<'q, D, A>,
+        ifc: &mut InFlightContext<A>,
+        world: &World,
+        state: &RenderState,
+        pipeline: &str,
+        depth_only: bool,
+    ) -> Result<IncompleteCommandBuffer<'q, D, A>> {
+        let Some(terrain) = world.terrain else { return Ok(cmd) };
+        let di = self.bus.data().read().unwrap();
+        let assets = di.get::<AssetStorage>().unwrap();
+        let mut cmd = Some(cmd);
+        let result = assets
+            .with_if_ready(terrain, |terrain| {
+                let detail_map = terrain.detail_map.and_then(|detail| {
+                    assets.with_if_ready(detail, |detail| detail.image.image.view.clone())
+                });
+                let detail_normals = assets
+                    .with_if_ready(terrain.detail_normal_map, |normals| {
+                        normals.image.image.view.clone()
+                    })?;
+                let splat_map = assets
+                    .with_if_ready(terrain.splat_map, |splat| splat.image.image.view.clone())?;
+                // The debug views show one of the derived maps
+                let derived_map = match world.options.terrain_shading {
+                    TerrainShading::Slope => {
+                        Some(assets.with_if_ready(terrain.derived_maps, |maps| {
+                            maps.slope.image.view.clone()
+                        })?)
+                    }
+                    TerrainShading::Curvature => {
+                        Some(assets.with_if_ready(terrain.derived_maps, |maps| {
+                            maps.curvature.image.view.clone()
+                        })?)
+                    }
+                    TerrainShading::Lit | TerrainShading::Matcap => None,
+                };
+                terrain.with_if_ready(assets, |heightmap, normal_map, color, mesh| {
+                    ubo_struct_assign!(
+                        camera,
+                        ifc,
+                        struct Camera {
+                            projection_view: Mat4 = state.projection_view,
+                            previous_pv: Mat4 = state.previous_pv,
+                            view: Mat4 = state.view,
+                        }
+                    );
+
+                    let tess_factor: u32 = world.options.tessellation_level;
+                    // Without a detail layer we still need to bind something,
+                    // so bind the base heightmap and disable the detail layer.
+                    let (detail_view, detail_strength) = match &detail_map {
+                        None => (&heightmap.image.image.view, 0.0f32),
+                        Some(view) => (view, world.terrain_options.detail_strength),
+                    };
+                    // The depth-only pipeline has no fragment shader
+                    let detail_stages = match depth_only {
+                        true => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+                        false => {
+                            vk::ShaderStageFlags::TESSELLATION_EVALUATION
+                                | vk::ShaderStageFlags::FRAGMENT
+                        }
+                    };
+                    let cmd = cmd
+                        .take()
+                        .unwrap()
+                        .bind_graphics_pipeline(pipeline)?
+                        .full_viewport_scissor()
+                        .push_constant(vk::ShaderStageFlags::TESSELLATION_CONTROL, 0, &tess_factor)
+                        .push_constant(
+                            vk::ShaderStageFlags::TESSELLATION_CONTROL
+                                | vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+                            4,
+                            &world.terrain_options.vertical_scale,
+                        )
+                        .push_constant(
+                            vk::ShaderStageFlags::TESSELLATION_CONTROL,
+                            8,
+                            &state.render_size,
+                        )
+                        .push_constant(
+                            vk::ShaderStageFlags::TESSELLATION_CONTROL,
+                            16,
+                            &world.options.target_edge_length,
+                        )
+                        .push_constant(detail_stages, 20, &world.terrain_options.detail_tiling)
+                        .push_constant(detail_stages, 24, &detail_strength)
+                        .bind_uniform_buffer(0, 0, &camera_buffer)?
+                        .bind_sampled_image(
+                            0,
+                            1,
+                            &heightmap.image.image.view,
+                            self.heightmap_sampler(world.terrain_options.border_mode),
+                        )?
+                        .bind_sampled_image(0, 5, detail_view, &self.linear_sampler)?;
+                    let cmd = match depth_only {
+                        true => cmd,
+                        false => {
+                            let surface_sampler =
+                                self.surface_sampler(world.terrain_options.border_mode);
+                            ubo_struct_assign!(
+                                lighting,
+                                ifc,
+                                struct Lighting {
+                                    sun_direction: Vec4 = state.sun_direction.xyzx(),
+                                }
+                            );
+                            ubo_struct_assign!(
+                                materials,
+                                ifc,
+                                struct Materials {
+                                    colors: [Vec4; 3] =
+                                        world.materials.colors.map(|color| color.extend(1.0)),
+                                }
+                            );
+                            let cmd = cmd
+                                .push_constant(
+                                    vk::ShaderStageFlags::FRAGMENT,
+                                    32,
+                                    &world.terrain_options.horizontal_scale,
+                                )
+                                .bind_sampled_image(
+                                    0,
+                                    3,
+                                    &normal_map.image.image.view,
+                                    surface_sampler,
+                                )?
+                                .bind_sampled_image(0, 6, &detail_normals, surface_sampler)?;
+                            // The other shading modes do not use the sun or the materials
+                            let cmd = match world.options.terrain_shading {
+                                TerrainShading::Lit => cmd
+                                    .bind_uniform_buffer(0, 2, &lighting_buffer)?
+                                    .bind_sampled_image(0, 4, &color.image.view, surface_sampler)?
+                                    .bind_sampled_image(0, 8, &splat_map, surface_sampler)?
+                                    .bind_uniform_buffer(0, 9, &materials_buffer)?,
+                                _ => cmd,
+                            };
+                            match &derived_map {
+                                None => cmd,
+                                Some(view) => {
+                                    cmd.bind_sampled_image(0, 7, view, surface_sampler)?
+                                }
+                            }
+                        }
+                    };
+                    let cmd = cmd
+                        .set_polygon_mode(if world.options.wireframe {
+                            vk::PolygonMode::LINE
+                        } else {
+                            vk::PolygonMode::FILL
+                        })?
+                        .bind_vertex_buffer(0, &mesh.vertices_view)
+                        .bind_index_buffer(&mesh.indices_view, vk::IndexType::UINT32)
+                        .draw_indexed(mesh.index_count, 1, 0, 0, 0)?;
+                    Ok::<_, anyhow::Error>(cmd)
+                })
+            })
+            .flatten();
+        match result {
+            None => Ok(cmd.unwrap()),
+            Some(cmd) => cmd,
+        }
     }
 
     /// Render the terrain and add all relevant passes to the graph.
     ///
+    /// If the depth prepass is enabled, the terrain depth is rendered first in a depth-only
+    /// pass. The color pass then only shades the visible fragments.
+    ///
     /// # Arguments
     ///
     /// * `graph` - The frame graph to add the passes to
     /// * `color` - The name of the color attachment to render to. The latest version will be queried from the graph.
     /// * `depth` - The name of the depth attachment to use. The latest version will be queried from the graph.
+    /// * `clear` - Values to clear the attachments to.
     /// * `world` - The world state with parameters for rendering.
     /// * `state` - The render state with camera settings and global rendering options.
     pub fn render<'cb, A: Allocator>(
@@ -75,111 +411,53 @@ impl TerrainRenderer {
         color: &VirtualResource,
         depth: &VirtualResource,
         motion: &VirtualResource,
+        clear: TerrainClearValues,
         world: &'cb World,
         state: &'cb RenderState,
     ) -> Result<()> {
+        // The atmosphere fills in the sky later, otherwise we need a background color.
+        let background = match world.options.atmosphere {
+            true => clear.color,
+            false => vk::ClearColorValue {
+                float32: world.options.background.extend(1.0).to_array(),
+            },
+        };
+        // Wireframe lines do not cover the filled depth, so they cannot use the prepass.
+        let prepass = world.options.depth_prepass && !world.options.wireframe;
+        let spacing = world.options.tessellation_spacing;
+        if prepass {
+            let this = &*self;
+            let depth_pipeline = Self::depth_pipeline_name(spacing);
+            let pass = ph::PassBuilder::<_, _, A>::render("terrain_depth_prepass")
+                .depth_attachment(depth, vk::AttachmentLoadOp::CLEAR, Some(clear.depth))?
+                .execute_fn(move |cmd, ifc, _bindings, stats: &mut RendererStatistics| {
+                    let cmd = cmd.begin_section(stats, "terrain_depth_prepass")?;
+                    let cmd = this.record_draw(cmd, ifc, world, state, &depth_pipeline, true)?;
+                    stats.end_section(cmd, "terrain_depth_prepass")
+                })
+                .build();
+            graph.add_pass(pass);
+        }
+
         let pass = ph::PassBuilder::<_, _, A>::render("terrain")
-            .color_attachment(
-                color,
-                vk::AttachmentLoadOp::CLEAR,
-                Some(vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                }),
-            )?
-            .color_attachment(
-                motion,
-                vk::AttachmentLoadOp::CLEAR,
-                Some(vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                }),
-            )?
-            .depth_attachment(
-                depth,
-                vk::AttachmentLoadOp::CLEAR,
-                Some(vk::ClearDepthStencilValue {
-                    depth: 1.0,
-                    stencil: 0,
-                }),
-            )?
-            .execute_fn(|cmd, ifc, _bindings, stats: &mut RendererStatistics| {
-                let di = self.bus.data().read().unwrap();
-                let assets = di.get::<AssetStorage>().unwrap();
-                let mut cmd = Some(cmd.begin_section(stats, "terrain")?);
-                if let Some(terrain) = world.terrain {
-                    match assets
-                        .with_if_ready(terrain, |terrain| {
-                            terrain.with_if_ready(assets, |heightmap, normal_map, color, mesh| {
-                                ubo_struct_assign!(
-                                    camera,
-                                    ifc,
-                                    struct Camera {
-                                        projection_view: Mat4 = state.projection_view,
-                                        previous_pv: Mat4 = state.previous_pv,
-                                    }
-                                );
-
-                                ubo_struct_assign!(
-                                    lighting,
-                                    ifc,
-                                    struct Lighting {
-                                        sun_direction: Vec4 = state.sun_direction.xyzx(),
-                                    }
-                                );
-
-                                let tess_factor: u32 = world.options.tessellation_level;
-                                let cmd = cmd
-                                    .take()
-                                    .unwrap()
-                                    .bind_graphics_pipeline("terrain")?
-                                    .full_viewport_scissor()
-                                    .push_constant(
-                                        vk::ShaderStageFlags::TESSELLATION_CONTROL,
-                                        0,
-                                        &tess_factor,
-                                    )
-                                    .push_constant(
-                                        vk::ShaderStageFlags::TESSELLATION_EVALUATION,
-                                        4,
-                                        &world.terrain_options.vertical_scale,
-                                    )
-                                    .bind_uniform_buffer(0, 0, &camera_buffer)?
-                                    .bind_sampled_image(
-                                        0,
-                                        1,
-                                        &heightmap.image.image.view,
-                                        &self.heightmap_sampler,
-                                    )?
-                                    .bind_uniform_buffer(0, 2, &lighting_buffer)?
-                                    .bind_sampled_image(
-                                        0,
-                                        3,
-                                        &normal_map.image.image.view,
-                                        &self.linear_sampler,
-                                    )?
-                                    .bind_sampled_image(
-                                        0,
-                                        4,
-                                        &color.image.view,
-                                        &self.linear_sampler,
-                                    )?
-                                    .set_polygon_mode(if world.options.wireframe {
-                                        vk::PolygonMode::LINE
-                                    } else {
-                                        vk::PolygonMode::FILL
-                                    })?
-                                    .bind_vertex_buffer(0, &mesh.vertices_view)
-                                    .bind_index_buffer(&mesh.indices_view, vk::IndexType::UINT32)
-                                    .draw_indexed(mesh.index_count, 1, 0, 0, 0)?;
-                                Ok::<_, anyhow::Error>(cmd)
-                            })
-                        })
-                        .flatten()
-                    {
-                        None => {}
-                        Some(new_cmd) => cmd = Some(new_cmd?),
-                    }
-                }
-                let cmd = cmd.unwrap();
+            .color_attachment(color, vk::AttachmentLoadOp::CLEAR, Some(background))?
+            .color_attachment(motion, vk::AttachmentLoadOp::CLEAR, Some(clear.motion))?;
+        let pass = match prepass {
+            true => pass.depth_attachment(
+                &graph.latest_version(depth)?,
+                vk::AttachmentLoadOp::LOAD,
+                None,
+            )?,
+            false => {
+                pass.depth_attachment(depth, vk::AttachmentLoadOp::CLEAR, Some(clear.depth))?
+            }
+        };
+        let this = &*self;
+        let pipeline = Self::pipeline_name(world.options.terrain_shading, prepass, spacing);
+        let pass = pass
+            .execute_fn(move |cmd, ifc, _bindings, stats: &mut RendererStatistics| {
+                let cmd = cmd.begin_section(stats, "terrain")?;
+                let cmd = this.record_draw(cmd, ifc, world, state, &pipeline, false)?;
                 stats.end_section(cmd, "terrain")
             })
             .build();
@@ -103,9 +103,10 @@ impl TerrainDecal {
                                 let overlay = di.read_sync::<WorldOverlayInfo>().unwrap();
                                 let Some(decal) = &overlay.brush_decal else { return Ok(cmd) };
                                 let Some(pos) = mouse.world_space else { return Ok(cmd) };
-                                let decal_radius_inverse = 1.0 / decal.radius;
+                                let decal_radius = decal.radius.0;
+                                let decal_radius_inverse = 1.0 / decal_radius;
                                 let transform = Mat4::from_scale_rotation_translation(
-                                    Vec3::splat(decal.radius),
+                                    Vec3::splat(decal_radius),
                                     Quat::from_rotation_x(90.0f32.to_radians()),
                                     pos,
                                 );
@@ -124,7 +125,7 @@ impl TerrainDecal {
                                 let mut sizes = ifc.allocate_scratch_ubo(8)?;
                                 sizes
                                     .mapped_slice()?
-                                    .copy_from_slice(&[decal.radius, decal_radius_inverse]);
+                                    .copy_from_slice(&[decal_radius, decal_radius_inverse]);
                                 cmd = cmd
                                     .bind_graphics_pipeline(&pipeline)?
                                     .full_viewport_scissor()
@@ -91,7 +91,7 @@ impl WorldPositionReconstruct {
             let data = self.full_view.mapped_slice::<Vec4>()?;
             let pos = data[cur_idx as usize];
             mouse.world_space = Some(pos.xyz());
-            mouse.terrain_uv = Some(world.terrain_options.uv_at(pos.xyz()));
+            mouse.terrain_uv = Some(world.terrain_options.uv_at(pos.xyz()).0);
         }
 
         let mut pass = PassBuilder::new("world_pos_reconstruct")
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use gfx::create_linear_sampler_with;
+use hot_reload::IntoDynamic;
+use inject::DI;
+use pass::FrameGraph;
+use phobos as ph;
+use phobos::{vk, Allocator, GraphicsCmdBuffer};
+use scheduler::EventBus;
+use statistics::{RendererStatistics, TimedCommandBuffer};
+
+use crate::util::targets::{RenderTargets, SizeGroup};
+
+/// Width of the smooth transition around the bloom threshold, relative to the threshold.
+const SOFT_KNEE: f32 = 0.5;
+
+/// Push constants of the bloom downsampling shader.
+/// Kept in sync with `PC` in `bloom_downsample.fs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct DownsampleParams {
+    threshold: f32,
+    knee: f32,
+    prefilter: u32,
+}
+
+/// Adds a glow around bright parts of the HDR scene. Bright pixels are downsampled through a
+/// chain of progressively smaller targets, which are then blurred back up and added to the scene.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Bloom {
+    ctx: gfx::SharedContext,
+    sampler: ph::Sampler,
+}
+
+impl Bloom {
+    /// Number of targets in the bloom chain. The first level is half of the render resolution,
+    /// every next level halves it again.
+    pub const LEVELS: u32 = 5;
+
+    /// Initialize bloom. Adds the targets of the bloom chain to the render target database,
+    /// and creates pipelines.
+    pub fn new(
+        ctx: gfx::SharedContext,
+        targets: &mut RenderTargets,
+        bus: &mut EventBus<DI>,
+    ) -> Result<Self> {
+        ph::PipelineBuilder::new("bloom_downsample")
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .cull_mask(vk::CullModeFlags::NONE)
+            .depth(false, false, false, vk::CompareOp::ALWAYS)
+            .blend_attachment_none()
+            .into_dynamic()
+            .attach_shader("shaders/src/fullscreen.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader("shaders/src/bloom_downsample.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .expect_binding(0, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .expect_push_constants(std::mem::size_of::<DownsampleParams>() as u32)
+            .build(bus, ctx.pipelines.clone())?;
+
+        ph::PipelineBuilder::new("bloom_upsample")
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .cull_mask(vk::CullModeFlags::NONE)
+            .depth(false, false, false, vk::CompareOp::ALWAYS)
+            .blend_additive_unmasked(
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+            )
+            .into_dynamic()
+            .attach_shader("shaders/src/fullscreen.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader("shaders/src/bloom_upsample.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .expect_binding(0, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .expect_push_constants(std::mem::size_of::<f32>() as u32)
+            .build(bus, ctx.pipelines.clone())?;
+
+        for level in 1..=Self::LEVELS {
+            targets.register_color_target(
+                Self::level_name(level),
+                SizeGroup::RenderResolutionMip(level),
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                vk::Format::R16G16B16A16_SFLOAT,
+            )?;
+        }
+
+        Ok(Self {
+            sampler: create_linear_sampler_with(&ctx, vk::SamplerAddressMode::CLAMP_TO_EDGE)?,
+            ctx,
+        })
+    }
+
+    /// Get the name of the target of a level in the bloom chain.
+    fn level_name(level: u32) -> String {
+        format!("bloom_{level}")
+    }
+
+    /// Add bloom to an HDR image in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - The frame graph to add the bloom passes to.
+    /// * `target` - The HDR image to add bloom to. The latest version will be queried from the graph.
+    /// * `threshold` - Brightness above which pixels contribute to the bloom.
+    /// * `intensity` - Multiplier on the bloom that is added to the image.
+    pub fn render<'cb, A: Allocator>(
+        &'cb self,
+        graph: &mut FrameGraph<'cb, A>,
+        target: &ph::VirtualResource,
+        threshold: f32,
+        intensity: f32,
+    ) -> Result<()> {
+        // Downsample through the chain, only keeping the bright parts of the image.
+        let mut source = target.clone();
+        for level in 1..=Self::LEVELS {
+            let output = ph::VirtualResource::image(Self::level_name(level));
+            let params = DownsampleParams {
+                threshold,
+                knee: threshold * SOFT_KNEE,
+                prefilter: (level == 1) as u32,
+            };
+            self.add_pass(
+                graph,
+                format!("bloom_downsample_{level}"),
+                "bloom_downsample",
+                &source,
+                &output,
+                vk::AttachmentLoadOp::DONT_CARE,
+                &params,
+            )?;
+            source = output;
+        }
+        // Blur back up by adding every level to the next larger one, and finally to the image.
+        for level in (0..Self::LEVELS).rev() {
+            let (output, pass_intensity) = match level {
+                0 => (target.clone(), intensity),
+                _ => (ph::VirtualResource::image(Self::level_name(level)), 1.0),
+            };
+            let latest = graph.latest_version(&output)?;
+            self.add_pass(
+                graph,
+                format!("bloom_upsample_{level}"),
+                "bloom_upsample",
+                &source,
+                &latest,
+                vk::AttachmentLoadOp::LOAD,
+                &pass_intensity,
+            )?;
+            source = output;
+        }
+        Ok(())
+    }
+
+    /// Add a fullscreen pass that samples the latest version of `input` and draws into `output`.
+    #[allow(clippy::too_many_arguments)]
+    fn add_pass<'cb, A: Allocator, P: Copy + 'static>(
+        &'cb self,
+        graph: &mut FrameGraph<'cb, A>,
+        name: String,
+        pipeline: &'static str,
+        input: &ph::VirtualResource,
+        output: &ph::VirtualResource,
+        load_op: vk::AttachmentLoadOp,
+        params: &P,
+    ) -> Result<()> {
+        let input = graph.latest_version(input)?;
+        let params = *params;
+        let pass = ph::PassBuilder::render(name.clone())
+            .color_attachment(output, load_op, None)?
+            .sample_image(&input, ph::PipelineStage::FRAGMENT_SHADER)
+            .execute_fn(move |mut cmd, _ifc, bindings, stats: &mut RendererStatistics| {
+                cmd = cmd
+                    .begin_section(stats, name.as_str())?
+                    .bind_graphics_pipeline(pipeline)?
+                    .full_viewport_scissor()
+                    .push_constant(vk::ShaderStageFlags::FRAGMENT, 0, &params)
+                    .resolve_and_bind_sampled_image(0, 0, &input, &self.sampler, bindings)?
+                    .draw(6, 1, 0, 0)?
+                    .end_section(stats, &name)?;
+                Ok(cmd)
+            })
+            .build();
+        graph.add_pass(pass);
+        Ok(())
+    }
+}
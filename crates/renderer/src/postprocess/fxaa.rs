@@ -0,0 +1,123 @@
+use anyhow::Result;
+use gfx::create_linear_sampler_with;
+use hot_reload::IntoDynamic;
+use inject::DI;
+use pass::FrameGraph;
+use phobos as ph;
+use phobos::{vk, Allocator, GraphicsCmdBuffer};
+use scheduler::EventBus;
+use statistics::{RendererStatistics, TimedCommandBuffer};
+use world::FxaaQuality;
+
+use crate::util::targets::{RenderTargets, SizeGroup};
+
+/// Push constants of the FXAA shader.
+/// Kept in sync with `PC` in `fxaa.fs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct FxaaParams {
+    subpix: f32,
+    edge_threshold: f32,
+    edge_threshold_min: f32,
+    search_steps: u32,
+}
+
+impl FxaaParams {
+    /// Settings of a quality preset, these follow the presets of FXAA 3.11.
+    fn from_quality(quality: FxaaQuality) -> Self {
+        let (subpix, edge_threshold, edge_threshold_min, search_steps) = match quality {
+            FxaaQuality::Low => (0.5, 0.25, 0.0833, 4),
+            FxaaQuality::Medium => (0.75, 0.166, 0.0833, 8),
+            FxaaQuality::High => (0.75, 0.125, 0.0625, 12),
+        };
+        Self {
+            subpix,
+            edge_threshold,
+            edge_threshold_min,
+            search_steps,
+        }
+    }
+}
+
+/// Smooths edges of the tonemapped image with FXAA.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Fxaa {
+    ctx: gfx::SharedContext,
+    sampler: ph::Sampler,
+}
+
+impl Fxaa {
+    /// Initialize FXAA. Adds a new target with name [`Self::input_name()`] to the render target
+    /// database, the tonemapper renders to this target when FXAA is enabled.
+    pub fn new(
+        ctx: gfx::SharedContext,
+        targets: &mut RenderTargets,
+        bus: &mut EventBus<DI>,
+    ) -> Result<Self> {
+        ph::PipelineBuilder::new("fxaa")
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .cull_mask(vk::CullModeFlags::NONE)
+            .depth(false, false, false, vk::CompareOp::ALWAYS)
+            .blend_attachment_none()
+            .into_dynamic()
+            .attach_shader("shaders/src/fullscreen.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader("shaders/src/fxaa.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .expect_binding(0, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .expect_push_constants(std::mem::size_of::<FxaaParams>() as u32)
+            .build(bus, ctx.pipelines.clone())?;
+
+        targets.register_color_target(
+            Self::input_name(),
+            SizeGroup::OutputResolution,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::Format::R8G8B8A8_SRGB,
+        )?;
+
+        Ok(Self {
+            sampler: create_linear_sampler_with(&ctx, vk::SamplerAddressMode::CLAMP_TO_EDGE)?,
+            ctx,
+        })
+    }
+
+    /// Get the name of the attachment FXAA reads from.
+    pub fn input_name() -> &'static str {
+        "fxaa_input"
+    }
+
+    /// Apply FXAA to the input attachment and write the result to the output attachment.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - The frame graph to add the FXAA pass to.
+    /// * `input` - The tonemapped image. The latest version will be queried from the graph.
+    /// * `output` - The attachment to write the anti-aliased image to.
+    /// * `quality` - Quality preset to use.
+    pub fn render<'cb, A: Allocator>(
+        &'cb self,
+        graph: &mut FrameGraph<'cb, A>,
+        input: &ph::VirtualResource,
+        output: &ph::VirtualResource,
+        quality: FxaaQuality,
+    ) -> Result<()> {
+        let input = graph.latest_version(input)?;
+        let params = FxaaParams::from_quality(quality);
+        let pass = ph::PassBuilder::render("fxaa")
+            .color_attachment(output, vk::AttachmentLoadOp::DONT_CARE, None)?
+            .sample_image(&input, ph::PipelineStage::FRAGMENT_SHADER)
+            .execute_fn(move |mut cmd, _ifc, bindings, stats: &mut RendererStatistics| {
+                cmd = cmd
+                    .begin_section(stats, "fxaa")?
+                    .bind_graphics_pipeline("fxaa")?
+                    .full_viewport_scissor()
+                    .push_constant(vk::ShaderStageFlags::FRAGMENT, 0, &params)
+                    .resolve_and_bind_sampled_image(0, 0, &input, &self.sampler, bindings)?
+                    .draw(6, 1, 0, 0)?
+                    .end_section(stats, "fxaa")?;
+                Ok(cmd)
+            })
+            .build();
+        graph.add_pass(pass);
+        Ok(())
+    }
+}
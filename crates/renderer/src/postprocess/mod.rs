@@ -1 +1,4 @@
+pub mod bloom;
+pub mod fxaa;
+pub mod taa;
 pub mod tonemap;
@@ -0,0 +1,186 @@
+use anyhow::Result;
+use gfx::create_linear_sampler_with;
+use gfx::state::RenderState;
+use glam::Vec3;
+use hot_reload::IntoDynamic;
+use inject::DI;
+use pass::FrameGraph;
+use phobos as ph;
+use phobos::{vk, Allocator, GraphicsCmdBuffer};
+use scheduler::EventBus;
+use statistics::{RendererStatistics, TimedCommandBuffer};
+
+use crate::util::targets::{RenderTargets, SizeGroup, TargetSize};
+
+/// Weight of the current frame when blending it into the history.
+const BLEND: f32 = 0.1;
+/// Number of samples in the jitter sequence before it repeats.
+const JITTER_SAMPLES: u32 = 8;
+/// The history is discarded if the camera moves further than this in a single frame.
+const TELEPORT_DISTANCE: f32 = 100.0;
+/// The history is discarded if the camera turns more than this in a single frame, in radians.
+const TELEPORT_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+
+/// Push constants of the TAA shader.
+/// Kept in sync with `PC` in `taa.fs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct TaaParams {
+    blend: f32,
+    reset: u32,
+}
+
+/// Get an element of the Halton sequence with the given base, in the range `[0, 1)`.
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Resolves jittered frames into an anti-aliased image by accumulating them in a history buffer.
+/// Unlike FSR2, the scene is rendered at the output resolution.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Taa {
+    ctx: gfx::SharedContext,
+    sampler: ph::Sampler,
+    /// Index of the current frame, used to pick the jitter offset and history buffer.
+    frame: u32,
+    /// Whether the history buffer holds a resolved image of the previous frame.
+    history_valid: bool,
+    /// Resolution of the previous frame.
+    resolution: TargetSize,
+    /// Position and view direction of the camera in the previous frame.
+    camera: (Vec3, Vec3),
+}
+
+impl Taa {
+    /// Initialize TAA. Adds two history targets to the render target database, one is read
+    /// while the other is written each frame.
+    pub fn new(
+        ctx: gfx::SharedContext,
+        targets: &mut RenderTargets,
+        bus: &mut EventBus<DI>,
+    ) -> Result<Self> {
+        ph::PipelineBuilder::new("taa")
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .cull_mask(vk::CullModeFlags::NONE)
+            .depth(false, false, false, vk::CompareOp::ALWAYS)
+            .blend_attachment_none()
+            .blend_attachment_none()
+            .into_dynamic()
+            .attach_shader("shaders/src/fullscreen.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader("shaders/src/taa.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .expect_binding(0, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .expect_binding(0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .expect_binding(0, 2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .expect_push_constants(std::mem::size_of::<TaaParams>() as u32)
+            .build(bus, ctx.pipelines.clone())?;
+
+        for index in 0..2 {
+            targets.register_color_target(
+                Self::history_name(index),
+                SizeGroup::OutputResolution,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                vk::Format::R16G16B16A16_SFLOAT,
+            )?;
+        }
+
+        Ok(Self {
+            sampler: create_linear_sampler_with(&ctx, vk::SamplerAddressMode::CLAMP_TO_EDGE)?,
+            ctx,
+            frame: 0,
+            history_valid: false,
+            resolution: TargetSize::default(),
+            camera: (Vec3::ZERO, Vec3::ZERO),
+        })
+    }
+
+    /// Get the name of one of the two history targets.
+    fn history_name(index: u32) -> String {
+        format!("taa_history_{index}")
+    }
+
+    /// Advance to the next frame and get its jitter offset in pixels, in the range `[-0.5, 0.5)`.
+    pub fn jitter_offset(&mut self) -> (f32, f32) {
+        self.frame = self.frame.wrapping_add(1);
+        let index = self.frame % JITTER_SAMPLES + 1;
+        (halton(index, 2) - 0.5, halton(index, 3) - 0.5)
+    }
+
+    /// Discard the history, so it is not used the next time TAA is enabled.
+    pub fn invalidate(&mut self) {
+        self.history_valid = false;
+    }
+
+    /// Check if the history can be used this frame, and remember the state of this frame
+    /// for the next one. The history is discarded when the resolution changes or the
+    /// camera teleports.
+    pub fn prepare(&mut self, resolution: TargetSize, state: &RenderState) -> bool {
+        let position = state.cam_position;
+        let front = -state.inverse_view.z_axis.truncate().normalize();
+        let (last_position, last_front) = self.camera;
+        let teleported = position.distance(last_position) > TELEPORT_DISTANCE
+            || front.angle_between(last_front) > TELEPORT_ANGLE;
+        let reset = !self.history_valid || resolution != self.resolution || teleported;
+        self.history_valid = true;
+        self.resolution = resolution;
+        self.camera = (position, front);
+        reset
+    }
+
+    /// Resolve the jittered scene into an anti-aliased image.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - The frame graph to add the TAA pass to.
+    /// * `color` - The jittered scene color. The latest version will be queried from the graph.
+    /// * `motion` - Motion vectors of the scene. The latest version will be queried from the graph.
+    /// * `output` - The attachment to write the anti-aliased image to.
+    /// * `reset` - Discard the history, as returned by [`Self::prepare()`].
+    pub fn render<'cb, A: Allocator>(
+        &'cb self,
+        graph: &mut FrameGraph<'cb, A>,
+        color: &ph::VirtualResource,
+        motion: &ph::VirtualResource,
+        output: &ph::VirtualResource,
+        reset: bool,
+    ) -> Result<()> {
+        let color = graph.latest_version(color)?;
+        let motion = graph.latest_version(motion)?;
+        // The history written last frame is not produced in this graph, so it has no newer version.
+        let history = ph::VirtualResource::image(Self::history_name((self.frame + 1) % 2));
+        let next_history = ph::VirtualResource::image(Self::history_name(self.frame % 2));
+        let params = TaaParams {
+            blend: BLEND,
+            reset: reset as u32,
+        };
+        let pass = ph::PassBuilder::render("taa")
+            .color_attachment(&next_history, vk::AttachmentLoadOp::DONT_CARE, None)?
+            .color_attachment(output, vk::AttachmentLoadOp::DONT_CARE, None)?
+            .sample_image(&color, ph::PipelineStage::FRAGMENT_SHADER)
+            .sample_image(&history, ph::PipelineStage::FRAGMENT_SHADER)
+            .sample_image(&motion, ph::PipelineStage::FRAGMENT_SHADER)
+            .execute_fn(move |mut cmd, _ifc, bindings, stats: &mut RendererStatistics| {
+                cmd = cmd
+                    .begin_section(stats, "taa")?
+                    .bind_graphics_pipeline("taa")?
+                    .full_viewport_scissor()
+                    .push_constant(vk::ShaderStageFlags::FRAGMENT, 0, &params)
+                    .resolve_and_bind_sampled_image(0, 0, &color, &self.sampler, bindings)?
+                    .resolve_and_bind_sampled_image(0, 1, &history, &self.sampler, bindings)?
+                    .resolve_and_bind_sampled_image(0, 2, &motion, &self.sampler, bindings)?
+                    .draw(6, 1, 0, 0)?
+                    .end_section(stats, "taa")?;
+                Ok(cmd)
+            })
+            .build();
+        graph.add_pass(pass);
+        Ok(())
+    }
+}
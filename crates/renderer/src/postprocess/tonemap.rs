@@ -6,9 +6,19 @@ use phobos as ph;
 use phobos::{vk, Allocator, GraphicsCmdBuffer};
 use scheduler::EventBus;
 use statistics::{RendererStatistics, TimedCommandBuffer};
+use world::{DisplayTransform, TonemapOperator};
 
 use crate::util::targets::{RenderTargets, SizeGroup};
 
+/// Push constants of the tonemapping shader.
+/// Kept in sync with `PC` in `tonemap.fs.hlsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct TonemapParams {
+    curve: u32,
+    exposure: f32,
+}
+
 /// This stores all the resources and state needed for the tonemapper to work.
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -35,6 +45,16 @@ impl Tonemap {
             .attach_shader("shaders/src/tonemap.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
             .build(bus, ctx.pipelines.clone())?;
 
+        ph::PipelineBuilder::new("hdr_display")
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .cull_mask(vk::CullModeFlags::NONE)
+            .depth(false, false, false, vk::CompareOp::ALWAYS)
+            .blend_attachment_none()
+            .into_dynamic()
+            .attach_shader("shaders/src/fullscreen.vs.hlsl", vk::ShaderStageFlags::VERTEX)
+            .attach_shader("shaders/src/hdr_display.fs.hlsl", vk::ShaderStageFlags::FRAGMENT)
+            .build(bus, ctx.pipelines.clone())?;
+
         targets.register_color_target(
             Self::output_name(),
             SizeGroup::OutputResolution,
@@ -53,32 +73,53 @@ impl Tonemap {
         "tonemap_output"
     }
 
-    /// Tonemap the input attachment into the tonemapped output attachment.
+    /// Tonemap the input attachment into the output attachment.
     ///
     /// # Arguments
     ///
     /// * `graph` - The frame graph to add the tonemapper passes to.
     /// * `input` - The input resource that must be tonemapped. The latest version will be queried from the graph.
+    /// * `output` - The attachment to write the tonemapped image to. This is [`Self::output_name()`],
+    ///              unless another post-process pass runs after tonemapping.
+    /// * `clear` - Value to clear the output attachment to.
+    /// * `transform` - How HDR values are mapped to the output. With [`DisplayTransform::Exposure`],
+    ///                 the raw input is written to the output instead of the tonemapped result.
+    /// * `operator` - Tonemapping curve to apply.
+    /// * `exposure` - Exposure adjustment in stops, applied before the tonemapping curve.
+    #[allow(clippy::too_many_arguments)]
     pub fn render<'cb, A: Allocator>(
         &'cb self,
         graph: &mut FrameGraph<'cb, A>,
         input: &ph::VirtualResource,
+        output: &ph::VirtualResource,
+        clear: vk::ClearColorValue,
+        transform: DisplayTransform,
+        operator: TonemapOperator,
+        exposure: f32,
     ) -> Result<()> {
         let input = graph.latest_version(input)?;
-        let output = ph::VirtualResource::image(Self::output_name());
+        let params = TonemapParams {
+            curve: operator.shader_value(),
+            exposure,
+        };
         let pass = ph::PassBuilder::render("tonemap")
-            .color_attachment(
-                &output,
-                vk::AttachmentLoadOp::CLEAR,
-                Some(vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                }),
-            )?
+            .color_attachment(output, vk::AttachmentLoadOp::CLEAR, Some(clear))?
             .sample_image(&input, ph::PipelineStage::FRAGMENT_SHADER)
             .execute_fn(move |mut cmd, _ifc, bindings, stats: &mut RendererStatistics| {
+                cmd = cmd.begin_section(stats, "tonemap")?;
+                cmd = match transform {
+                    DisplayTransform::Tonemap => cmd
+                        .bind_graphics_pipeline("tonemap")?
+                        .push_constant(vk::ShaderStageFlags::FRAGMENT, 0, &params),
+                    DisplayTransform::Exposure {
+                        exposure,
+                    } => cmd.bind_graphics_pipeline("hdr_display")?.push_constant(
+                        vk::ShaderStageFlags::FRAGMENT,
+                        0,
+                        &exposure,
+                    ),
+                };
                 cmd = cmd
-                    .begin_section(stats, "tonemap")?
-                    .bind_graphics_pipeline("tonemap")?
                     .full_viewport_scissor()
                     .resolve_and_bind_sampled_image(0, 0, &input, &self.sampler, bindings)?
                     .draw(6, 1, 0, 0)?
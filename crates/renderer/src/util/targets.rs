@@ -5,8 +5,12 @@ use derivative::Derivative;
 use gfx::{PairedImageView, SharedContext};
 use glam::UVec2;
 use log::warn;
+use phobos::domain::All;
 use phobos::fsr2::{FfxDimensions2D, FfxFsr2QualityMode};
-use phobos::{vk, DeletionQueue, Image, ImageView, PhysicalResourceBindings};
+use phobos::{
+    vk, Buffer, DeletionQueue, Image, ImageView, IncompleteCmdBuffer, MemoryType,
+    PhysicalResourceBindings, PipelineStage,
+};
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
 pub struct TargetSize {
@@ -21,6 +25,12 @@ impl TargetSize {
             height,
         }
     }
+
+    /// Size of a mip level of a target with this size. Each level halves the size, down to
+    /// a single pixel.
+    pub fn mip(self, level: u32) -> Self {
+        TargetSize::new((self.width >> level).max(1), (self.height >> level).max(1))
+    }
 }
 
 impl From<TargetSize> for UVec2 {
@@ -36,6 +46,8 @@ impl From<TargetSize> for UVec2 {
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SizeGroup {
     RenderResolution,
+    /// The render resolution halved `level` times, see [`TargetSize::mip`].
+    RenderResolutionMip(u32),
     OutputResolution,
     Custom(TargetSize),
 }
@@ -68,11 +80,63 @@ impl From<TargetSize> for FfxDimensions2D {
     }
 }
 
+/// Value a target is cleared to when a pass loads it with [`vk::AttachmentLoadOp::CLEAR`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ClearValue {
+    Color([f32; 4]),
+    DepthStencil {
+        depth: f32,
+        stencil: u32,
+    },
+}
+
+impl ClearValue {
+    /// Default clear value for a target with the given aspect. Color targets are cleared to
+    /// transparent black, depth targets to the far plane.
+    fn default_for_aspect(aspect: vk::ImageAspectFlags) -> Self {
+        if aspect.intersects(vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL) {
+            ClearValue::DepthStencil {
+                depth: 1.0,
+                stencil: 0,
+            }
+        } else {
+            ClearValue::Color([0.0, 0.0, 0.0, 0.0])
+        }
+    }
+}
+
+/// Raw data read back from a render target using [`RenderTargets::read_region`].
+#[derive(Debug, Clone)]
+pub struct TargetReadback {
+    /// Format of the target the data was read from. Pixels are tightly packed in this format.
+    pub format: vk::Format,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Size of a single texel in bytes, for the formats used by render targets.
+fn format_texel_size(format: vk::Format) -> Result<u32> {
+    Ok(match format {
+        vk::Format::R8G8B8A8_SRGB
+        | vk::Format::R8G8B8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_SFLOAT
+        | vk::Format::D32_SFLOAT => 4,
+        vk::Format::R16G16B16A16_SFLOAT | vk::Format::R32G32_SFLOAT => 8,
+        vk::Format::R32G32B32A32_SFLOAT => 16,
+        _ => bail!("Unsupported format for readback: {format:?}"),
+    })
+}
+
 #[derive(Derivative)]
 #[derivative(Debug)]
 struct RenderTargetEntry {
     pub size_group: SizeGroup,
     pub target: PairedImageView,
+    pub clear_value: ClearValue,
     #[derivative(Debug = "ignore")]
     pub recreate: Box<dyn Fn(TargetSize) -> Result<PairedImageView>>,
 }
@@ -86,6 +150,10 @@ pub struct RenderTargets {
     output_resolution: TargetSize,
     render_resolution: TargetSize,
     upscale_quality: UpscaleQuality,
+    upscaling: bool,
+    /// Display resolution FSR2 was last configured with. Configuring FSR2 discards its history,
+    /// so this only follows the output resolution while upscaling.
+    fsr2_resolution: TargetSize,
 }
 
 impl RenderTargets {
@@ -93,6 +161,9 @@ impl RenderTargets {
         &self,
         quality: UpscaleQuality,
     ) -> Result<FfxDimensions2D> {
+        if !self.upscaling {
+            return Ok(self.output_resolution.into());
+        }
         let mut fsr2 = self.ctx.device.fsr2_context();
         fsr2.get_render_resolution(quality.into())
     }
@@ -107,9 +178,38 @@ impl RenderTargets {
             output_resolution: TargetSize::default(),
             render_resolution: TargetSize::default(),
             upscale_quality: UpscaleQuality::Quality,
+            upscaling: true,
+            fsr2_resolution: TargetSize::default(),
         })
     }
 
+    /// Enable or disable upscaling. Without upscaling, the render resolution is always equal
+    /// to the output resolution.
+    pub fn set_upscaling(&mut self, upscaling: bool) -> Result<()> {
+        if self.upscaling == upscaling {
+            return Ok(());
+        }
+        self.upscaling = upscaling;
+        self.configure_fsr2()?;
+        let resolution = self.get_render_resolution_for_quality(self.upscale_quality)?;
+        self.set_render_resolution(resolution.width, resolution.height)
+    }
+
+    /// Set the output resolution and enable or disable upscaling to it. Upscaling is disabled
+    /// before and enabled after resizing, so FSR2 keeps its history when the output is resized
+    /// without upscaling and then restored, for example for a supersampled capture.
+    pub fn configure_output(&mut self, width: u32, height: u32, upscaling: bool) -> Result<()> {
+        if !upscaling {
+            self.set_upscaling(false)?;
+        }
+        self.set_output_resolution(width, height)?;
+        self.set_upscaling(upscaling)
+    }
+
+    pub fn upscaling(&self) -> bool {
+        self.upscaling
+    }
+
     pub fn set_upscale_quality(&mut self, quality: UpscaleQuality) -> Result<()> {
         self.upscale_quality = quality;
         let resolution = self.get_render_resolution_for_quality(self.upscale_quality)?;
@@ -128,10 +228,7 @@ impl RenderTargets {
             }
         }
 
-        {
-            let mut fsr2 = self.ctx.device.fsr2_context();
-            fsr2.set_display_resolution(self.output_resolution.into(), None)?;
-        }
+        self.configure_fsr2()?;
         // If we change the output resolution we also need to change the render resolution accordingly
         let dims = self.get_render_resolution_for_quality(self.upscale_quality)?;
         self.set_render_resolution(dims.width, dims.height)?;
@@ -139,6 +236,17 @@ impl RenderTargets {
         Ok(())
     }
 
+    /// Set the display resolution of FSR2 to the output resolution if it upscales to it.
+    fn configure_fsr2(&mut self) -> Result<()> {
+        if !self.upscaling || self.fsr2_resolution == self.output_resolution {
+            return Ok(());
+        }
+        let mut fsr2 = self.ctx.device.fsr2_context();
+        fsr2.set_display_resolution(self.output_resolution.into(), None)?;
+        self.fsr2_resolution = self.output_resolution;
+        Ok(())
+    }
+
     #[allow(dead_code)]
     fn set_render_resolution(&mut self, width: u32, height: u32) -> Result<()> {
         if self.render_resolution.width == width && self.output_resolution.height == height {
@@ -149,12 +257,16 @@ impl RenderTargets {
             bail!("Cannot set render resolution above output resolution");
         }
 
-        self.render_resolution = TargetSize::new(width, height);
+        let resolution = TargetSize::new(width, height);
+        self.render_resolution = resolution;
 
         for entry in self.targets.values_mut() {
-            if entry.size_group == SizeGroup::RenderResolution {
-                Self::resize_target(&mut self.deferred_delete, entry, width, height)?;
-            }
+            let size = match entry.size_group {
+                SizeGroup::RenderResolution => resolution,
+                SizeGroup::RenderResolutionMip(level) => resolution.mip(level),
+                _ => continue,
+            };
+            Self::resize_target(&mut self.deferred_delete, entry, size.width, size.height)?;
         }
 
         Ok(())
@@ -171,20 +283,23 @@ impl RenderTargets {
     ) -> Result<()> {
         let alloc = self.ctx.allocator.clone();
         let device = self.ctx.device.clone();
-        self.register_target(name, size, move |size| {
+        let name = name.into();
+        self.register_target(name.clone(), size, move |size| {
             PairedImageView::new(
                 Image::new(
                     device.clone(),
                     &mut alloc.clone(),
                     size.width,
                     size.height,
-                    usage,
+                    // Allow reading back every target for debugging purposes
+                    usage | vk::ImageUsageFlags::TRANSFER_SRC,
                     format,
                     samples,
                 )?,
                 aspect,
             )
-        })
+        })?;
+        self.set_clear_value(&name, ClearValue::default_for_aspect(aspect))
     }
 
     pub fn register_color_target(
@@ -256,6 +371,7 @@ impl RenderTargets {
             RenderTargetEntry {
                 size_group: size,
                 target,
+                clear_value: ClearValue::Color([0.0, 0.0, 0.0, 0.0]),
                 recreate: Box::new(recreate),
             },
         );
@@ -263,11 +379,53 @@ impl RenderTargets {
         Ok(())
     }
 
+    /// Set the value the target is cleared to. Passes that clear this target should query
+    /// it with [`Self::clear_color`] or [`Self::clear_depth_stencil`].
+    pub fn set_clear_value(&mut self, name: &str, value: ClearValue) -> Result<()> {
+        let target = self
+            .targets
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("Target {name} not found"))?;
+        target.clear_value = value;
+        Ok(())
+    }
+
+    pub fn clear_color(&self, name: &str) -> Result<vk::ClearColorValue> {
+        let target = self
+            .targets
+            .get(name)
+            .ok_or_else(|| anyhow!("Target {name} not found"))?;
+        match target.clear_value {
+            ClearValue::Color(color) => Ok(vk::ClearColorValue {
+                float32: color,
+            }),
+            ClearValue::DepthStencil {
+                ..
+            } => bail!("Target {name} has a depth/stencil clear value"),
+        }
+    }
+
+    pub fn clear_depth_stencil(&self, name: &str) -> Result<vk::ClearDepthStencilValue> {
+        let target = self
+            .targets
+            .get(name)
+            .ok_or_else(|| anyhow!("Target {name} not found"))?;
+        match target.clear_value {
+            ClearValue::DepthStencil {
+                depth,
+                stencil,
+            } => Ok(vk::ClearDepthStencilValue {
+                depth,
+                stencil,
+            }),
+            ClearValue::Color(_) => bail!("Target {name} has a color clear value"),
+        }
+    }
+
     pub fn next_frame(&mut self) {
         self.deferred_delete.next_frame();
     }
 
-    #[allow(dead_code)]
     pub fn target_size(&self, name: &str) -> Result<TargetSize> {
         let target = self
             .targets
@@ -279,11 +437,17 @@ impl RenderTargets {
     pub fn size_group_resolution(&self, size_group: SizeGroup) -> TargetSize {
         match size_group {
             SizeGroup::RenderResolution => self.render_resolution,
+            SizeGroup::RenderResolutionMip(level) => self.render_resolution.mip(level),
             SizeGroup::OutputResolution => self.output_resolution,
             SizeGroup::Custom(size) => size,
         }
     }
 
+    /// Iterate over the names of all registered targets, in no particular order.
+    pub fn target_names(&self) -> impl Iterator<Item = &str> {
+        self.targets.keys().map(String::as_str)
+    }
+
     pub fn get_target_view(&self, name: &str) -> Result<ImageView> {
         Ok(self
             .targets
@@ -294,6 +458,104 @@ impl RenderTargets {
             .clone())
     }
 
+    /// Copies a region of a render target to the CPU and returns the raw texel data.
+    /// The target is expected to be in `layout`, and will be transitioned back to it after the copy.
+    /// The copy is recorded on the graphics queue, so no queue ownership transfer is needed.
+    ///
+    /// This stalls until the copy is complete, so use it sparingly.
+    pub fn read_region(
+        &self,
+        name: &str,
+        rect: vk::Rect2D,
+        layout: vk::ImageLayout,
+    ) -> Result<TargetReadback> {
+        let view = self.get_target_view(name)?;
+        let format = view.format();
+        let size = self.target_size(name)?;
+        let offset = rect.offset;
+        let extent = rect.extent;
+        if offset.x < 0
+            || offset.y < 0
+            || offset.x as u32 + extent.width > size.width
+            || offset.y as u32 + extent.height > size.height
+        {
+            bail!("Readback region {rect:?} is out of bounds for target {name} with size {size:?}");
+        }
+
+        let byte_size = (extent.width * extent.height * format_texel_size(format)?) as u64;
+        let mut ctx = self.ctx.clone();
+        let buffer = Buffer::new(
+            ctx.device.clone(),
+            &mut ctx.allocator,
+            byte_size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryType::GpuToCpu,
+        )?;
+        let mut buffer_view = buffer.view_full();
+
+        let cmd = ctx.exec.on_domain::<All, _>(None, None)?.transition_image(
+            &view,
+            PipelineStage::ALL_COMMANDS,
+            PipelineStage::TRANSFER,
+            layout,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::AccessFlags2::MEMORY_WRITE,
+            vk::AccessFlags2::TRANSFER_READ,
+        );
+        let region = vk::BufferImageCopy {
+            buffer_offset: buffer_view.offset(),
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: view.aspect(),
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D {
+                x: offset.x,
+                y: offset.y,
+                z: 0,
+            },
+            image_extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+        };
+        // SAFETY: The command buffer is in the recording state, and both the image and the
+        // buffer outlive the submission since we wait for it below.
+        unsafe {
+            ctx.device.cmd_copy_image_to_buffer(
+                cmd.handle(),
+                view.image(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer.handle(),
+                std::slice::from_ref(&region),
+            );
+        }
+        let cmd = cmd
+            .transition_image(
+                &view,
+                PipelineStage::TRANSFER,
+                PipelineStage::ALL_COMMANDS,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                layout,
+                vk::AccessFlags2::TRANSFER_READ,
+                vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            )
+            .finish()?;
+        ctx.exec.submit(cmd)?.wait()?;
+
+        let data = buffer_view.mapped_slice::<u8>()?.to_vec();
+        Ok(TargetReadback {
+            format,
+            width: extent.width,
+            height: extent.height,
+            data,
+        })
+    }
+
     pub fn bind_targets(&self, bindings: &mut PhysicalResourceBindings) {
         for (name, target) in &self.targets {
             bindings.bind_image(name.clone(), &target.target.view);
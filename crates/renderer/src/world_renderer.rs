@@ -1,9 +1,10 @@
 use anyhow::Result;
-use camera::CameraState;
+use camera::{CameraState, Projection};
 use gfx::state::RenderState;
 use gfx::SharedContext;
 use glam::{Mat3, Mat4, Vec3};
 use gui::util::image_provider::ImageProvider;
+use gui::util::target_viewer::TargetViewer;
 use hot_reload::IntoDynamic;
 use inject::DI;
 use pass::FrameGraph;
@@ -12,27 +13,42 @@ use phobos::graph::pass::Fsr2DispatchVirtualResources;
 use phobos::{image, vk, PassBuilder, PhysicalResourceBindings, PipelineBuilder, VirtualResource};
 use scheduler::EventBus;
 use time::Time;
-use world::World;
+use world::{AntiAliasing, World};
 
 use crate::passes::atmosphere::AtmosphereRenderer;
-use crate::passes::terrain::TerrainRenderer;
+use crate::passes::ortho_depth::OrthoDepth;
+use crate::passes::props::PropRenderer;
+use crate::passes::target_view::TargetView;
+use crate::passes::terrain::{TerrainClearValues, TerrainRenderer};
 use crate::passes::terrain_decal::TerrainDecal;
 use crate::passes::world_position::WorldPositionReconstruct;
+use crate::postprocess::bloom::Bloom;
+use crate::postprocess::fxaa::Fxaa;
+use crate::postprocess::taa::Taa;
 use crate::postprocess::tonemap::Tonemap;
 use crate::ui_integration::UIIntegration;
-use crate::util::targets::{RenderTargets, SizeGroup, TargetSize, UpscaleQuality};
+use crate::util::targets::{RenderTargets, SizeGroup, TargetReadback, TargetSize, UpscaleQuality};
 
 /// The world renderer is responsible for all the rendering logic
 /// of the scene.
 #[derive(Debug)]
 pub struct WorldRenderer {
     bus: EventBus<DI>,
+    bloom: Bloom,
     tonemap: Tonemap,
+    fxaa: Fxaa,
+    taa: Taa,
     atmosphere: AtmosphereRenderer,
     terrain: TerrainRenderer,
     world_pos_reconstruct: WorldPositionReconstruct,
     terrain_decal: TerrainDecal,
+    props: PropRenderer,
+    target_view: TargetView,
+    ortho_depth: OrthoDepth,
     state: RenderState,
+    /// Anti-aliasing used instead of the one in the render options, see
+    /// [`Self::set_anti_aliasing_override`].
+    anti_aliasing_override: Option<AntiAliasing>,
     ctx: SharedContext,
 }
 
@@ -88,7 +104,12 @@ impl WorldRenderer {
         )?;
 
         let state = RenderState::default();
+        let bloom = Bloom::new(ctx.clone(), &mut targets, &mut bus)?;
         let tonemap = Tonemap::new(ctx.clone(), &mut targets, &mut bus)?;
+        let fxaa = Fxaa::new(ctx.clone(), &mut targets, &mut bus)?;
+        let taa = Taa::new(ctx.clone(), &mut targets, &mut bus)?;
+        let target_view = TargetView::new(ctx.clone(), &mut targets, &mut bus)?;
+        let ortho_depth = OrthoDepth::new(ctx.clone(), &mut targets, &mut bus)?;
 
         {
             let mut inject = bus.data().write().unwrap();
@@ -96,13 +117,20 @@ impl WorldRenderer {
         }
 
         Ok(Self {
+            bloom,
             tonemap,
+            fxaa,
+            taa,
             atmosphere: AtmosphereRenderer::new(ctx.clone(), &mut bus)?,
             terrain: TerrainRenderer::new(ctx.clone(), &mut bus)?,
             world_pos_reconstruct: WorldPositionReconstruct::new(ctx.clone(), &mut bus)?,
             terrain_decal: TerrainDecal::new(ctx.clone(), bus.clone())?,
+            props: PropRenderer::new(ctx.clone(), &mut bus)?,
+            target_view,
+            ortho_depth,
             bus,
             state,
+            anti_aliasing_override: None,
             ctx,
         })
     }
@@ -113,26 +141,93 @@ impl WorldRenderer {
         Tonemap::output_name()
     }
 
-    /// Updates the output image used in the UI to have the correct size.
+    /// Updates the output image used in the UI to have the correct size, and lists the
+    /// targets that can be shown in the target viewer.
     /// # DI Access
     /// - Write [`RenderTargets`]
     /// - Write [`ImageProvider`]
-    pub fn update_output_image(&mut self, ui: &mut UIIntegration) -> Result<()> {
+    /// - Write [`TargetViewer`]
+    pub fn update_output_image(&mut self, world: &World, ui: &mut UIIntegration) -> Result<()> {
         let inject = self.bus.data().read().unwrap();
         let mut targets = inject.write_sync::<RenderTargets>().unwrap();
         let mut provider = inject.write_sync::<ImageProvider>().unwrap();
-        targets.set_output_resolution(
-            (provider.size.x() as f32 * 1.5) as u32,
-            (provider.size.y() as f32 * 1.5) as u32,
-        )?;
+        let mut viewer = inject.write_sync::<TargetViewer>().unwrap();
+        let resolution = world.options.output_resolution(
+            provider.size.x(),
+            provider.size.y(),
+            provider.pixels_per_point,
+        );
+        let upscaling = self.anti_aliasing(world).upscaling();
+        targets.configure_output(resolution.x, resolution.y, upscaling)?;
         // Then grab our color output.
         let image = targets.get_target_view(Self::output_name()).unwrap();
         // We can re-register the same image, nothing will happen.
         let handle = ui.register_texture(&image);
         provider.handle = Some(handle);
+
+        viewer.targets = targets
+            .target_names()
+            .filter(|name| *name != TargetView::output_name())
+            .map(str::to_owned)
+            .collect();
+        viewer.targets.sort();
+        if let Some(selected) = &viewer.selected {
+            if !viewer.targets.contains(selected) {
+                viewer.selected = None;
+            }
+        }
+        viewer.handle = match viewer.selected {
+            Some(_) => {
+                let image = targets.get_target_view(TargetView::output_name())?;
+                Some(ui.register_texture(&image))
+            }
+            None => None,
+        };
         Ok(())
     }
 
+    /// Render at a fixed output resolution instead of the size of the world view, for example
+    /// to capture the scene to a file. The next call to [`Self::update_output_image()`]
+    /// restores the size of the world view.
+    /// # DI Access
+    /// - Write [`RenderTargets`]
+    pub fn set_output_resolution(&mut self, world: &World, width: u32, height: u32) -> Result<()> {
+        let inject = self.bus.data().read().unwrap();
+        let mut targets = inject.write_sync::<RenderTargets>().unwrap();
+        targets.configure_output(width, height, self.anti_aliasing(world).upscaling())
+    }
+
+    /// Render with `anti_aliasing` instead of the anti-aliasing in the render options, for
+    /// example to bypass FSR2 for a supersampled capture. `None` uses the render options again.
+    /// Call [`Self::set_output_resolution()`] afterwards to update the render resolution.
+    pub fn set_anti_aliasing_override(&mut self, anti_aliasing: Option<AntiAliasing>) {
+        self.anti_aliasing_override = anti_aliasing;
+    }
+
+    /// Anti-aliasing the world is rendered with, see [`Self::set_anti_aliasing_override`].
+    pub fn anti_aliasing(&self, world: &World) -> AntiAliasing {
+        self.anti_aliasing_override
+            .unwrap_or(world.options.anti_aliasing)
+    }
+
+    /// Copy the final output of the last rendered frame to the CPU. The output is expected
+    /// to be in `layout`. This stalls until the copy is complete.
+    /// # DI Access
+    /// - Read [`RenderTargets`]
+    pub fn read_output(&self, layout: vk::ImageLayout) -> Result<TargetReadback> {
+        let inject = self.bus.data().read().unwrap();
+        let targets = inject.read_sync::<RenderTargets>().unwrap();
+        let size = targets.target_size(Self::output_name())?;
+        let rect = vk::Rect2D {
+            offset: vk::Offset2D::default(),
+            extent: vk::Extent2D {
+                width: size.width,
+                height: size.height,
+            },
+        };
+        targets.read_region(Self::output_name(), rect, layout)
+    }
+
     /// Update deferred deletion queues.
     /// # DI Access
     /// - Write [`RenderTargets`]
@@ -140,6 +235,7 @@ impl WorldRenderer {
         let inject = self.bus.data().read().unwrap();
         let mut targets = inject.write_sync::<RenderTargets>().unwrap();
         targets.next_frame();
+        self.props.next_frame();
     }
 
     /// # DI Access
@@ -174,17 +270,45 @@ impl WorldRenderer {
         self.state.near = 0.1;
         self.state.far = 10000000.0;
         self.state.view = camera.matrix();
+        // The field of view is kept in orthographic mode, since the sky is still drawn with a
+        // perspective projection and FSR2 expects one.
         self.state.fov = camera.fov().to_radians();
-        self.state.projection = Mat4::perspective_rh(
-            self.state.fov,
-            self.aspect_ratio(),
-            self.state.near,
-            self.state.far,
-        );
-        // Jitter projection matrix
-        let mut fsr2 = self.ctx.device.fsr2_context();
+        self.state.orthographic = camera.is_orthographic();
+        self.state.projection = match camera.projection() {
+            Projection::Perspective {
+                fov,
+            } => Mat4::perspective_rh(
+                fov.to_radians(),
+                self.aspect_ratio(),
+                self.state.near,
+                self.state.far,
+            ),
+            Projection::Orthographic {
+                height,
+            } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect_ratio();
+                Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.state.near,
+                    self.state.far,
+                )
+            }
+        };
+        // Jitter projection matrix. Without FSR2 or TAA there is nothing to resolve the jitter.
+        // The jitter is a translation in clip space, which works for both projections.
         let resolution = self.render_resolution();
-        let (jitter_x, jitter_y) = fsr2.jitter_offset(resolution.width)?;
+        let (jitter_x, jitter_y) = match self.anti_aliasing(world) {
+            AntiAliasing::Fsr2 => {
+                let mut fsr2 = self.ctx.device.fsr2_context();
+                fsr2.jitter_offset(resolution.width)?
+            }
+            AntiAliasing::Taa => self.taa.jitter_offset(),
+            _ => (0.0, 0.0),
+        };
         let proj_jitter_x = 2.0 * jitter_x / resolution.width as f32;
         let proj_jitter_y = -2.0 * jitter_y / resolution.height as f32;
         let jitter_translation_matrix =
@@ -210,20 +334,35 @@ impl WorldRenderer {
     /// # DI Access
     /// - Read [`RenderTargets`]
     /// - Read [`Time`]
+    /// - Read [`TargetViewer`]
+    /// - Read [`PropInstances`](world::PropInstances)
     pub fn redraw_world<'cb>(
         &'cb mut self,
         world: &'cb World,
     ) -> Result<(FrameGraph<'cb>, PhysicalResourceBindings)> {
         let mut bindings = PhysicalResourceBindings::new();
         let mut graph = FrameGraph::new();
-        {
+        let (terrain_clear, tonemap_clear, viewed_target) = {
             let inject = self.bus.data().read().unwrap();
             let targets = inject.read_sync::<RenderTargets>().unwrap();
+            let viewer = inject.read_sync::<TargetViewer>().unwrap();
             targets.bind_targets(&mut bindings);
-        }
+            let terrain_clear = TerrainClearValues {
+                color: targets.clear_color("scene_output")?,
+                motion: targets.clear_color("motion")?,
+                depth: targets.clear_depth_stencil("depth")?,
+            };
+            // Target to draw for the target viewer, with its format
+            let viewed_target = match &viewer.selected {
+                Some(name) => Some((name.clone(), targets.get_target_view(name)?.format())),
+                None => None,
+            };
+            (terrain_clear, targets.clear_color(Tonemap::output_name())?, viewed_target)
+        };
 
         let (jitter_x, jitter_y) = self.update_render_state(world)?;
         let resolution = self.render_resolution();
+        let anti_aliasing = self.anti_aliasing(world);
 
         let scene_output = image!("scene_output");
         let depth = image!("depth");
@@ -232,22 +371,59 @@ impl WorldRenderer {
         let tonemapped_output = VirtualResource::image(Tonemap::output_name());
 
         // Render terrain
-        self.terrain
-            .render(&mut graph, &scene_output, &depth, &motion, world, &self.state)?;
+        self.terrain.render(
+            &mut graph,
+            &scene_output,
+            &depth,
+            &motion,
+            terrain_clear,
+            world,
+            &self.state,
+        )?;
         // Render atmosphere
-        self.atmosphere
-            .render(&mut graph, &scene_output, &depth, world, &self.state)?;
+        if world.options.atmosphere {
+            self.atmosphere
+                .render(&mut graph, &scene_output, &depth, world, &self.state)?;
+        }
         // Render decal
-        self.terrain_decal
-            .render(&mut graph, &scene_output, &depth, world, &self.state)?;
+        if world.options.decals {
+            self.terrain_decal
+                .render(&mut graph, &scene_output, &depth, world, &self.state)?;
+        }
         // Reconstruct world position from depth
         self.world_pos_reconstruct
             .render(&world, &mut graph, &depth, &self.state)?;
+        // Render props after the world position is reconstructed, so the cursor and brushes
+        // hit the terrain below them.
+        self.props
+            .render(&mut graph, &scene_output, &motion, &depth, world, &self.state)?;
 
-        // Upscale
-        {
+        // Upscale or resolve the jittered frames. Otherwise the scene is rendered at output
+        // resolution, so it can be tonemapped directly.
+        let tonemap_input = match anti_aliasing.jittered() {
+            true => upscaled_output.clone(),
+            false => scene_output.clone(),
+        };
+        if anti_aliasing == AntiAliasing::Taa {
+            let reset = self.taa.prepare(resolution, &self.state);
+            self.taa
+                .render(&mut graph, &scene_output, &motion, &upscaled_output, reset)?;
+        } else {
+            self.taa.invalidate();
+        }
+        if anti_aliasing.upscaling() {
             let in_color = graph.latest_version(&scene_output).unwrap();
-            let in_depth = graph.latest_version(&depth).unwrap();
+            // FSR2 assumes the depth of a perspective projection, so orthographic depth is
+            // converted first. The near and far planes passed to FSR2 stay the same.
+            let in_depth = match self.state.orthographic {
+                true => {
+                    let fsr2_depth = VirtualResource::image(OrthoDepth::output_name());
+                    self.ortho_depth
+                        .render(&mut graph, &depth, &fsr2_depth, &self.state)?;
+                    graph.latest_version(&fsr2_depth).unwrap()
+                }
+                false => graph.latest_version(&depth).unwrap(),
+            };
             let in_motion = graph.latest_version(&motion).unwrap();
 
             let di = self.bus.data().read().unwrap();
@@ -264,7 +440,7 @@ impl WorldRenderer {
                 },
                 enable_sharpening: false,
                 sharpness: 0.0,
-                frametime_delta: time.delta,
+                frametime_delta: time.real_delta,
                 pre_exposure: 1.0,
                 reset: false,
                 camera_near: self.state.near,
@@ -289,8 +465,48 @@ impl WorldRenderer {
             graph.add_pass(fsr2_pass);
         }
 
-        // Apply tonemapping
-        self.tonemap.render(&mut graph, &upscaled_output)?;
+        // Add bloom to the HDR image before it is tonemapped.
+        if world.options.bloom {
+            self.bloom.render(
+                &mut graph,
+                &tonemap_input,
+                world.options.bloom_threshold,
+                world.options.bloom_intensity,
+            )?;
+        }
+
+        // Apply tonemapping. FXAA runs on the tonemapped image, so the tonemapper writes to its
+        // input instead.
+        let tonemap_output = match anti_aliasing {
+            AntiAliasing::Fxaa => VirtualResource::image(Fxaa::input_name()),
+            _ => tonemapped_output.clone(),
+        };
+        self.tonemap.render(
+            &mut graph,
+            &tonemap_input,
+            &tonemap_output,
+            tonemap_clear,
+            world.options.display_transform,
+            world.options.tonemap_operator,
+            world.options.tonemap_exposure,
+        )?;
+        if anti_aliasing == AntiAliasing::Fxaa {
+            self.fxaa.render(
+                &mut graph,
+                &tonemap_output,
+                &tonemapped_output,
+                world.options.fxaa_quality,
+            )?;
+        }
+        // Draw the target picked in the target viewer, after all passes that may write it.
+        if let Some((name, format)) = viewed_target {
+            self.target_view.render(
+                &mut graph,
+                &VirtualResource::image(name),
+                format,
+                &self.state,
+            )?;
+        }
         // Alias our final result to the expected name
         graph.alias("renderer_output", tonemapped_output);
 
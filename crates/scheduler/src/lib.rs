@@ -4,10 +4,14 @@ pub use bus::*;
 pub use caller::*;
 pub use event::*;
 pub use handler::*;
+pub use plugin::*;
 pub use system::*;
+pub use test_bus::*;
 
 pub mod bus;
 pub mod caller;
 pub mod event;
 pub mod handler;
+pub mod plugin;
 pub mod system;
+pub mod test_bus;
@@ -0,0 +1,32 @@
+use anyhow::Result;
+
+use crate::bus::EventBus;
+
+/// Extension point to add systems, pipelines or resources to an application without
+/// modifying its driver. The application passes its plugins to the driver at startup.
+///
+/// # Ordering
+/// Plugins are initialized in the order they are given, after all core systems are
+/// initialized and before the startup terrain is opened. A plugin can therefore subscribe to
+/// any event and replace or extend resources of the core systems, but not of plugins that come
+/// after it.
+///
+/// # DI Access
+/// All core resources are available when a plugin is initialized:
+/// - `SharedContext` and `Samplers` from `gfx`
+/// - `InputState` from `input`
+/// - `CameraState` from `camera`
+/// - `World` from `world`
+/// - `ShaderReload` and `PipelineRegistry` from `hot_reload`
+/// - `AssetStorage` from `assets`
+/// - `RenderTargets` from `renderer`
+/// - `GpuWork` from `pass`
+/// - `Time` and `TimeControl` from `time`
+/// - `RendererStatistics` from `statistics`
+pub trait Plugin<T> {
+    /// Name of the plugin, used to report errors during initialization.
+    fn name(&self) -> &str;
+
+    /// Called once at startup. This is where a plugin adds its systems to the bus.
+    fn initialize(&mut self, bus: &mut EventBus<T>) -> Result<()>;
+}
@@ -0,0 +1,146 @@
+//! Lightweight harness to test systems without the rest of the application.
+
+use std::ops::{Deref, DerefMut};
+
+use anyhow::Result;
+use inject::DI;
+
+use crate::bus::EventBus;
+use crate::event::Event;
+use crate::system::System;
+
+/// An event bus with its own empty DI storage. Register the systems under test and the DI
+/// state they access, publish events and inspect the resulting state. Dereferences to the
+/// [`EventBus`], so it can be passed to the `initialize` functions of other crates.
+///
+/// Events are handled synchronously on the calling thread, so the state can be inspected
+/// right after publishing.
+#[derive(Debug, Clone)]
+pub struct TestBus {
+    bus: EventBus<DI>,
+}
+
+impl Default for TestBus {
+    fn default() -> Self {
+        Self {
+            bus: EventBus::new(DI::new()),
+        }
+    }
+}
+
+impl TestBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a system to the bus.
+    pub fn with_system<S: System<DI> + 'static>(self, system: S) -> Self {
+        self.bus.add_system(system);
+        self
+    }
+
+    /// Put a value in the DI storage, replacing any previous value of the same type.
+    pub fn with<T: 'static>(self, value: T) -> Self {
+        self.bus.data().write().unwrap().put_sync(value);
+        self
+    }
+
+    /// Publish an event and return the results of its handlers.
+    pub fn publish<E: Event + 'static>(&self, event: E) -> Result<Vec<E::Result>> {
+        self.bus.publish(event)
+    }
+
+    /// Call `f` with a value in the DI storage.
+    /// # Panics
+    /// If there is no value of type `T`.
+    pub fn read<T: 'static, R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let di = self.bus.data().read().unwrap();
+        let value = di
+            .read_sync::<T>()
+            .unwrap_or_else(|| panic!("{} is not in DI", std::any::type_name::<T>()));
+        f(&value)
+    }
+
+    /// Call `f` with mutable access to a value in the DI storage.
+    /// # Panics
+    /// If there is no value of type `T`.
+    pub fn write<T: 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let di = self.bus.data().read().unwrap();
+        let mut value = di
+            .write_sync::<T>()
+            .unwrap_or_else(|| panic!("{} is not in DI", std::any::type_name::<T>()));
+        f(&mut value)
+    }
+}
+
+impl Deref for TestBus {
+    type Target = EventBus<DI>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.bus
+    }
+}
+
+impl DerefMut for TestBus {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.bus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventContext;
+    use crate::system::StoredSystem;
+
+    struct Increment(u32);
+
+    impl Event for Increment {
+        type Result = u32;
+    }
+
+    #[derive(Debug, Default)]
+    struct Counter(u32);
+
+    struct CounterSystem {
+        events: u32,
+    }
+
+    impl System<DI> for CounterSystem {
+        fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+            event_bus.subscribe(system, handle_increment);
+        }
+    }
+
+    fn handle_increment(
+        system: &mut CounterSystem,
+        event: &Increment,
+        ctx: &mut EventContext<DI>,
+    ) -> Result<u32> {
+        system.events += 1;
+        let di = ctx.read().unwrap();
+        let mut counter = di.write_sync::<Counter>().unwrap();
+        counter.0 += event.0;
+        Ok(system.events)
+    }
+
+    #[test]
+    fn published_events_update_state() {
+        let bus = TestBus::new()
+            .with(Counter::default())
+            .with_system(CounterSystem {
+                events: 0,
+            });
+        assert_eq!(bus.publish(Increment(2)).unwrap(), vec![1]);
+        assert_eq!(bus.publish(Increment(3)).unwrap(), vec![2]);
+        assert_eq!(bus.read(|counter: &Counter| counter.0), 5);
+    }
+
+    #[test]
+    fn events_without_systems_are_ignored() {
+        let bus = TestBus::new().with(Counter(1));
+        assert!(bus.publish(Increment(2)).unwrap().is_empty());
+        bus.write(|counter: &mut Counter| counter.0 = 4);
+        assert_eq!(bus.read(|counter: &Counter| counter.0), 4);
+    }
+}
@@ -1,13 +1,18 @@
 use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
 use derivative::Derivative;
+use error::{publish_error, publish_success};
 use gfx::SharedContext;
+use inject::DI;
 use phobos::domain::ExecutionDomain;
 use phobos::query_pool::{PipelineStatisticsQuery, QueryPool, QueryPoolCreateInfo, TimestampQuery};
 use phobos::wsi::frame::FRAMES_IN_FLIGHT;
 use phobos::{vk, Allocator, IncompleteCommandBuffer, PipelineStage};
+use scheduler::{Event, EventBus, EventContext, StoredSystem, System};
 use util::{RingBuffer, SafeUnwrap};
 
 #[derive(Debug, Default, Hash, Eq, PartialEq, Copy, Clone)]
@@ -17,6 +22,17 @@ struct SectionQuery {
 }
 
 const FRAMETIME_SAMPLES: usize = 256;
+/// Number of measurements of each section that are averaged.
+const SECTION_SAMPLES: usize = 16;
+
+/// Write the recorded frame times and section timings to a CSV file, see
+/// [`RendererStatistics::export_csv`]. The result is reported with a `MessageEvent`.
+#[derive(Debug, Clone)]
+pub struct DumpTimingsEvent {
+    pub path: PathBuf,
+}
+
+impl Event for DumpTimingsEvent {}
 
 #[derive(Derivative)]
 #[derivative(Debug)]
@@ -27,11 +43,19 @@ pub struct RendererStatistics {
     timings: QueryPool<TimestampQuery>,
     sections: HashMap<String, SectionQuery>,
     timing_results: HashMap<String, Duration>,
+    /// Recent measurements of each section together with the index of the measured frame, a new
+    /// one is added every measured frame.
+    section_history: HashMap<String, RingBuffer<(u64, Duration), SECTION_SAMPLES>>,
+    /// Index of the current frame, counting from the first call to [`Self::new_frame`].
+    frame: u64,
+    /// Index of the last frame that was measured.
+    measured_frame: u64,
     interval: u32,
     frames_until_measure: u32,
     last_frame: Instant,
     delta_time: Duration,
     frame_times: RingBuffer<Duration, FRAMETIME_SAMPLES>,
+    averaging_window: usize,
 }
 
 impl RendererStatistics {
@@ -58,11 +82,15 @@ impl RendererStatistics {
             timings,
             sections: Default::default(),
             timing_results: Default::default(),
+            section_history: Default::default(),
+            frame: 0,
+            measured_frame: 0,
             interval: measure_interval,
             frames_until_measure: measure_interval + 1,
             last_frame: Instant::now(),
             delta_time: Default::default(),
             frame_times: Default::default(),
+            averaging_window: FRAMETIME_SAMPLES,
         })
     }
 
@@ -106,8 +134,10 @@ impl RendererStatistics {
     }
 
     pub fn new_frame(&mut self) {
+        self.frame += 1;
         if self.frames_until_measure == 0 {
             self.frames_until_measure = self.interval;
+            self.measured_frame = self.frame;
             self.sections.clear();
             self.timings.reset();
             self.statistics.reset();
@@ -136,6 +166,9 @@ impl RendererStatistics {
             let start = *timestamps.get(queries.start_query as usize).unwrap();
             let end = *timestamps.get(queries.end_query as usize).unwrap();
             self.timing_results.insert(name.clone(), end - start);
+            let history = self.section_history.entry(name.clone()).or_default();
+            history.next();
+            *history.current_mut() = (self.measured_frame, end - start);
         }
         Ok(())
     }
@@ -148,19 +181,151 @@ impl RendererStatistics {
         &self.timing_results
     }
 
+    /// Index of the current frame, counting from the first call to [`Self::new_frame`].
+    pub fn frame_index(&self) -> u64 {
+        self.frame
+    }
+
+    /// Iterate over the recent measurements of each section, together with the index of the
+    /// measured frame, see [`Self::frame_index`].
+    pub fn section_measurements(&self) -> impl Iterator<Item = (&str, u64, Duration)> {
+        self.section_history.iter().flat_map(|(name, history)| {
+            history
+                .iter()
+                .map(move |(frame, duration)| (name.as_str(), *frame, *duration))
+        })
+    }
+
+    /// Returns the GPU time of each section in milliseconds, averaged over the last
+    /// measurements. Sections that were not measured yet are omitted.
+    pub fn average_section_timings(&self) -> HashMap<String, f64> {
+        self.section_history
+            .iter()
+            .filter(|(_, history)| !history.is_empty())
+            .map(|(name, history)| {
+                let total: Duration = history.iter().map(|(_, duration)| duration).sum();
+                let average = total.as_secs_f64() * 1000.0 / history.len() as f64;
+                (name.clone(), average)
+            })
+            .collect()
+    }
+
+    /// Write the recorded frame times to a CSV file, from the oldest to the newest frame.
+    /// Each row holds the frame index, the frame time and the GPU time of every section in
+    /// milliseconds. Sections are only measured every few frames, so their cells are empty for
+    /// frames that were not measured.
+    pub fn export_csv(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut sections = self.section_history.keys().collect::<Vec<_>>();
+        sections.sort();
+        let mut csv = String::from("frame,frame_time_ms");
+        for name in &sections {
+            write!(csv, ",{name}_ms")?;
+        }
+        csv.push('\n');
+        let first_frame = (self.frame + 1).saturating_sub(self.frame_times.len() as u64);
+        for (frame, time) in (first_frame..).zip(self.frame_times.iter()) {
+            write!(csv, "{frame},{}", time.as_secs_f64() * 1000.0)?;
+            for name in &sections {
+                csv.push(',');
+                let measurement = self.section_history[*name]
+                    .iter()
+                    .find(|(measured, _)| *measured == frame);
+                if let Some((_, duration)) = measurement {
+                    write!(csv, "{}", duration.as_secs_f64() * 1000.0)?;
+                }
+            }
+            csv.push('\n');
+        }
+        std::fs::write(path, csv)?;
+        Ok(())
+    }
+
     pub fn frame_time(&self) -> Duration {
         self.delta_time
     }
 
+    /// Returns the frame time averaged over the last [`Self::averaging_window`] frames, or
+    /// over all frames so far if fewer frames were measured.
     pub fn average_frame_time(&self) -> Duration {
-        let total: u128 = self.frame_times.iter().map(|time| time.as_nanos()).sum();
-        let average = total as f64 / FRAMETIME_SAMPLES as f64;
-        Duration::from_nanos(average as u64)
+        let times = self.frame_times.latest(self.averaging_window);
+        let count = times.len();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let total: u128 = times.map(|time| time.as_nanos()).sum();
+        Duration::from_nanos((total / count as u128) as u64)
+    }
+
+    /// Set the number of frames the frame time is averaged over. A window of one frame shows
+    /// the instantaneous frame time. The window is clamped to [`Self::frame_time_samples`].
+    pub fn set_averaging_window(&mut self, frames: usize) {
+        self.averaging_window = frames.clamp(1, FRAMETIME_SAMPLES);
+    }
+
+    pub fn averaging_window(&self) -> usize {
+        self.averaging_window
     }
 
     pub fn frame_time_samples(&self) -> usize {
         FRAMETIME_SAMPLES
     }
+
+    /// Iterate over the recorded frame times, from oldest to newest.
+    pub fn frame_times(&self) -> impl Iterator<Item = &Duration> {
+        self.frame_times.iter()
+    }
+
+    /// Returns the frame rate of the slowest 1% of recorded frames, or zero if no frames
+    /// were recorded yet.
+    pub fn one_percent_low_fps(&self) -> f32 {
+        let mut times = self.frame_times().copied().collect::<Vec<_>>();
+        if times.is_empty() {
+            return 0.0;
+        }
+        times.sort_unstable_by(|a, b| b.cmp(a));
+        let count = (times.len() / 100).max(1);
+        let slowest = times[..count].iter().sum::<Duration>() / count as u32;
+        1.0 / slowest.as_secs_f32().max(f32::EPSILON)
+    }
+}
+
+struct DumpTimingsSystem;
+
+impl System<DI> for DumpTimingsSystem {
+    fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+        event_bus.subscribe(system, handle_dump_timings);
+    }
+}
+
+/// The file is small, so it is written right away. The result is reported on a separate thread,
+/// since this event is usually published by the editor, which also handles the message.
+/// # DI Access
+/// - Read [`RendererStatistics`]
+fn handle_dump_timings(
+    _system: &mut DumpTimingsSystem,
+    event: &DumpTimingsEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let result = {
+        let di = ctx.read().unwrap();
+        let stats = di.read_sync::<RendererStatistics>().unwrap();
+        stats.export_csv(&event.path)
+    };
+    let bus = ctx.bus().clone();
+    let path = event.path.clone();
+    tokio::task::spawn_blocking(move || match result {
+        Ok(_) => {
+            publish_success!(bus, "Wrote frame timings to {}", path.display());
+        }
+        Err(e) => {
+            publish_error!(bus, "Could not write frame timings to {}: {e}", path.display());
+        }
+    });
+    Ok(())
+}
+
+pub fn initialize(bus: &EventBus<DI>) {
+    bus.add_system(DumpTimingsSystem);
 }
 
 pub trait TimedCommandBuffer {
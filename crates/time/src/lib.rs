@@ -1,16 +1,156 @@
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use events::Tick;
+use events::{FixedTick, Tick};
 use inject::DI;
+use log::warn;
 use scheduler::{EventBus, EventContext, StoredSystem, System};
 
 struct TimeSystem;
 
 #[derive(Debug, Clone)]
 pub struct Time {
+    start: Instant,
     last_time: Instant,
+    /// Time that passed for time-driven animation since the last frame. This is zero while
+    /// time is paused through [`TimeControl`].
     pub delta: Duration,
+    /// Wall clock time since the last frame. This keeps running while time is paused, and
+    /// should be used for anything interactive.
+    pub real_delta: Duration,
+    /// Wall clock time since the time system was initialized, as of the last frame.
+    pub elapsed: Duration,
+    /// Amount of frames since the time system was initialized.
+    pub total_frames: u64,
+    /// Exponential moving average of [`Time::real_delta`] in seconds, see
+    /// [`Time::smoothed_fps`].
+    smoothed_frame_time: f32,
+}
+
+impl Time {
+    /// Weight of the latest frame in the smoothed frame time. Lower values give a steadier
+    /// but slower to react frame rate.
+    pub const SMOOTHING: f32 = 0.05;
+
+    fn new(now: Instant) -> Self {
+        Self {
+            start: now,
+            last_time: now,
+            delta: Default::default(),
+            real_delta: Default::default(),
+            elapsed: Default::default(),
+            total_frames: 0,
+            smoothed_frame_time: 0.0,
+        }
+    }
+
+    /// Start a new frame at `now`, advancing animation time by `delta`.
+    fn advance(&mut self, now: Instant, control: &mut TimeControl) {
+        self.real_delta = now.saturating_duration_since(self.last_time);
+        self.delta = control.delta(self.real_delta);
+        self.last_time = now;
+        self.elapsed = now.saturating_duration_since(self.start);
+        self.total_frames += 1;
+        let frame_time = self.real_delta.as_secs_f32();
+        self.smoothed_frame_time = match self.total_frames {
+            // Start from the first frame instead of averaging in a frame time of zero
+            1 => frame_time,
+            _ => {
+                self.smoothed_frame_time + (frame_time - self.smoothed_frame_time) * Self::SMOOTHING
+            }
+        };
+    }
+
+    /// Frame time averaged over recent frames, steadier than [`Time::real_delta`].
+    pub fn smoothed_frame_time(&self) -> Duration {
+        Duration::from_secs_f32(self.smoothed_frame_time)
+    }
+
+    /// Frames per second averaged over recent frames. Zero before the first frame.
+    pub fn smoothed_fps(&self) -> f32 {
+        if self.smoothed_frame_time > 0.0 {
+            1.0 / self.smoothed_frame_time
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Controls how time advances. Pausing stops everything driven by [`Time::delta`], such as
+/// the movement of the sun and [`FixedTick`] events. Access through DI.
+#[derive(Debug, Default, Clone)]
+pub struct TimeControl {
+    /// While paused, [`Time::delta`] is zero unless a step is requested.
+    pub paused: bool,
+    /// Advances time by this amount on the next frame while paused. This is cleared once
+    /// it is applied.
+    pub step: Option<Duration>,
+}
+
+impl TimeControl {
+    /// Returns the time animation should advance by, given the wall clock time since the
+    /// last frame. Consumes a pending step.
+    fn delta(&mut self, real_delta: Duration) -> Duration {
+        let step = self.step.take();
+        match self.paused {
+            true => step.unwrap_or_default(),
+            false => real_delta,
+        }
+    }
+}
+
+/// Configures the rate of [`FixedTick`] events. Access through DI.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    /// Amount of fixed ticks per second of animation time.
+    pub rate: f32,
+    /// Maximum amount of fixed ticks in a single frame. When a frame takes longer than this
+    /// many steps, the remaining time is dropped so slow frames don't keep getting slower.
+    pub max_steps: u32,
+    accumulator: Duration,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self {
+            rate: 60.0,
+            max_steps: 8,
+            accumulator: Duration::ZERO,
+        }
+    }
+}
+
+impl FixedTimestep {
+    /// Duration of a single fixed tick, or zero if the rate is not positive.
+    pub fn dt(&self) -> Duration {
+        if self.rate > 0.0 {
+            Duration::from_secs_f32(1.0 / self.rate)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Accumulates `delta` and returns the amount of fixed ticks to run this frame. Leftover
+    /// time is carried over to the next frame. A non-positive rate disables fixed ticks.
+    fn steps(&mut self, delta: Duration) -> u32 {
+        let dt = self.dt();
+        if dt.is_zero() {
+            return 0;
+        }
+        self.accumulator += delta;
+        let steps = (self.accumulator.as_nanos() / dt.as_nanos()) as u32;
+        if steps > self.max_steps {
+            warn!(
+                "Frame needed {steps} fixed ticks, capping to {}. Fixed updates will lag behind.",
+                self.max_steps
+            );
+            self.accumulator =
+                Duration::from_nanos((self.accumulator.as_nanos() % dt.as_nanos()) as u64);
+            return self.max_steps;
+        }
+        self.accumulator -= dt * steps;
+        steps
+    }
 }
 
 impl System<DI> for TimeSystem {
@@ -19,25 +159,139 @@ impl System<DI> for TimeSystem {
     }
 }
 
+/// # DI Access
+/// - Write [`Time`]
+/// - Write [`TimeControl`]
+/// - Write [`FixedTimestep`]
 fn handle_tick_event(
     _system: &mut TimeSystem,
     _event: &Tick,
     ctx: &mut EventContext<DI>,
 ) -> Result<()> {
-    let di = ctx.read().unwrap();
-    let mut time = di.write_sync::<Time>().unwrap();
-    let now = Instant::now();
-    time.delta = now - time.last_time;
-    time.last_time = now;
+    let (steps, dt) = {
+        let di = ctx.read().unwrap();
+        let mut time = di.try_write_sync::<Time>()?;
+        let mut control = di.try_write_sync::<TimeControl>()?;
+        let mut fixed = di.try_write_sync::<FixedTimestep>()?;
+        time.advance(Instant::now(), &mut control);
+        (fixed.steps(time.delta), fixed.dt())
+    };
+    // Locks are released here, fixed tick handlers will want to access DI themselves.
+    for _ in 0..steps {
+        ctx.publish(FixedTick {
+            dt,
+        })?;
+    }
     Ok(())
 }
 
 pub fn initialize(bus: &EventBus<DI>) -> Result<()> {
     bus.add_system(TimeSystem);
     let mut di = bus.data().write().unwrap();
-    di.put_sync(Time {
-        last_time: Instant::now(),
-        delta: Default::default(),
-    });
+    di.put_sync(Time::new(Instant::now()));
+    di.put_sync(TimeControl::default());
+    di.put_sync(FixedTimestep::default());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use scheduler::TestBus;
+
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn paused_time_only_advances_by_steps() {
+        let mut control = TimeControl {
+            paused: true,
+            step: None,
+        };
+        assert_eq!(control.delta(FRAME), Duration::ZERO);
+        control.step = Some(Duration::from_millis(100));
+        assert_eq!(control.delta(FRAME), Duration::from_millis(100));
+        // A step is only applied once
+        assert_eq!(control.delta(FRAME), Duration::ZERO);
+    }
+
+    #[test]
+    fn running_time_ignores_steps() {
+        let mut control = TimeControl {
+            paused: false,
+            step: Some(Duration::from_millis(100)),
+        };
+        assert_eq!(control.delta(FRAME), FRAME);
+        assert_eq!(control.step, None);
+    }
+
+    #[test]
+    fn tick_updates_delta() {
+        let bus = TestBus::new();
+        initialize(&bus).unwrap();
+        std::thread::sleep(FRAME);
+        bus.publish(Tick).unwrap();
+        assert!(bus.read(|time: &Time| time.delta) >= FRAME);
+        assert!(bus.read(|time: &Time| time.real_delta) >= FRAME);
+    }
+
+    #[test]
+    fn tick_while_paused_does_not_advance_time() {
+        let bus = TestBus::new();
+        initialize(&bus).unwrap();
+        bus.write(|control: &mut TimeControl| control.paused = true);
+        std::thread::sleep(FRAME);
+        bus.publish(Tick).unwrap();
+        assert_eq!(bus.read(|time: &Time| time.delta), Duration::ZERO);
+        assert!(bus.read(|time: &Time| time.real_delta) >= FRAME);
+    }
+
+    #[test]
+    fn frames_are_counted_and_smoothed() {
+        let start = Instant::now();
+        let mut time = Time::new(start);
+        let mut control = TimeControl::default();
+        assert_eq!(time.smoothed_fps(), 0.0);
+        let mut now = start;
+        for frame in 0..200 {
+            // Alternate between fast and slow frames
+            now += if frame % 2 == 0 {
+                Duration::from_millis(10)
+            } else {
+                Duration::from_millis(30)
+            };
+            time.advance(now, &mut control);
+        }
+        assert_eq!(time.total_frames, 200);
+        assert_eq!(time.elapsed, Duration::from_secs(4));
+        // The instantaneous frame rate jumps between 33 and 100, the smoothed one stays
+        // close to the average of 50.
+        let fps = time.smoothed_fps();
+        assert!((fps - 50.0).abs() < 2.0, "{fps}");
+    }
+
+    #[test]
+    fn fixed_steps_carry_over_leftover_time() {
+        let mut fixed = FixedTimestep {
+            rate: 100.0,
+            ..Default::default()
+        };
+        assert_eq!(fixed.steps(Duration::from_millis(25)), 2);
+        // 5ms left over from the previous frame
+        assert_eq!(fixed.steps(Duration::from_millis(5)), 1);
+        assert_eq!(fixed.steps(Duration::from_millis(9)), 0);
+        assert_eq!(fixed.steps(Duration::from_millis(1)), 1);
+    }
+
+    #[test]
+    fn fixed_steps_are_capped() {
+        let mut fixed = FixedTimestep {
+            rate: 100.0,
+            max_steps: 4,
+            ..Default::default()
+        };
+        assert_eq!(fixed.steps(Duration::from_millis(1005)), 4);
+        // The backlog is dropped instead of being caught up on over the next frames
+        assert_eq!(fixed.steps(Duration::from_millis(5)), 1);
+    }
+}
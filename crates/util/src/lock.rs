@@ -3,15 +3,21 @@
 //! * `log-write-locks` - Log all write lock acquires and releases
 //! * `log-locks` - Enable both `log-read-locks` and `log-write-locks`
 //! * `time-locks` - Add timers to all lock operations that warn if the lock is held for too long or waiting
-//!                  on it is taking too long.
+//!   on it is taking too long.
 //! * `log-lock-backtrace` - Enables `time-locks`, also writes out a stack backtrace of the caller with the warning message.
+//! * `detect-lock-order` - Remember the order in which named locks are acquired while holding other named locks, and
+//!   warn when two locks are acquired in opposite orders, since that can deadlock.
+//!
+//! The thresholds used by `time-locks` can be changed at runtime, either for all locks with
+//! [`set_default_thresholds`] or for a single lock with [`RwLock::with_thresholds`].
 
 #[allow(unused_imports)]
 use std::backtrace::{Backtrace, BacktraceStatus};
 use std::fmt::{Display, Formatter};
 use std::ops::{Deref, DerefMut};
 use std::sync;
-use std::sync::{LockResult, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
 use std::time::Duration;
 
 #[allow(unused_imports)]
@@ -23,6 +29,24 @@ pub const RWLOCK_HOLD_WARN_TIMEOUT_MS: u64 = 100;
 /// How long a lock can block the calling thread before triggering a warning
 pub const RWLOCK_WAIT_WARN_TIMEOUT_MS: u64 = 100;
 
+static DEFAULT_HOLD_WARN_TIMEOUT_MS: AtomicU64 = AtomicU64::new(RWLOCK_HOLD_WARN_TIMEOUT_MS);
+static DEFAULT_WAIT_WARN_TIMEOUT_MS: AtomicU64 = AtomicU64::new(RWLOCK_WAIT_WARN_TIMEOUT_MS);
+
+/// Set how long locks without their own thresholds can be held and waited on, in milliseconds,
+/// before triggering a warning. Only affects locks acquired after this call.
+pub fn set_default_thresholds(hold_ms: u64, wait_ms: u64) {
+    DEFAULT_HOLD_WARN_TIMEOUT_MS.store(hold_ms, Ordering::Relaxed);
+    DEFAULT_WAIT_WARN_TIMEOUT_MS.store(wait_ms, Ordering::Relaxed);
+}
+
+/// Returns the hold and wait thresholds in milliseconds used by locks without their own thresholds.
+pub fn default_thresholds() -> (u64, u64) {
+    (
+        DEFAULT_HOLD_WARN_TIMEOUT_MS.load(Ordering::Relaxed),
+        DEFAULT_WAIT_WARN_TIMEOUT_MS.load(Ordering::Relaxed),
+    )
+}
+
 /// Wrapper around a [`RwLock`] that provides additional logging and times
 /// how long it is blocking threads. All features can be toggled using feature flags.
 /// If no features are enabled, then this is a zero-cost wrapper around RwLock.
@@ -30,6 +54,8 @@ pub const RWLOCK_WAIT_WARN_TIMEOUT_MS: u64 = 100;
 pub struct RwLock<T> {
     lock: sync::RwLock<T>,
     name: Option<String>,
+    /// Hold and wait thresholds in milliseconds that override the defaults for this lock.
+    thresholds: Option<(u64, u64)>,
 }
 
 /// A lock identifier is either some unique pointer value, or
@@ -98,6 +124,87 @@ fn log_lock_operation(identifier: LockIdentifier, operation: LockOperation, mode
     trace!("Lock: [{identifier}] [{operation}] [{mode}] from thread [{thread_name}]");
 }
 
+/// Tracks which named locks each thread holds, and the order in which locks were acquired while
+/// holding other locks. Acquiring `B` while holding `A` after `A` was once acquired while holding
+/// `B` means two threads doing this at the same time can deadlock.
+#[cfg(feature = "detect-lock-order")]
+mod lock_order {
+    use std::backtrace::Backtrace;
+    use std::cell::RefCell;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{Mutex, OnceLock};
+
+    use log::warn;
+
+    #[derive(Default)]
+    struct LockGraph {
+        /// For every observed `(held, acquired)` pair, where it was first observed.
+        edges: HashMap<(String, String), Backtrace>,
+        /// Pairs that were already reported, so each one is only warned about once.
+        reported: HashSet<(String, String)>,
+    }
+
+    thread_local! {
+        static HELD: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn graph() -> &'static Mutex<LockGraph> {
+        static GRAPH: OnceLock<Mutex<LockGraph>> = OnceLock::new();
+        GRAPH.get_or_init(Default::default)
+    }
+
+    /// Called before blocking on the lock `name`, so a warning is still logged if this
+    /// acquisition ends up deadlocking.
+    pub(super) fn check(name: &str) {
+        HELD.with(|held| {
+            let held = held.borrow();
+            if held.is_empty() {
+                return;
+            }
+            let mut graph = graph().lock().unwrap_or_else(|poison| poison.into_inner());
+            for before in held.iter().filter(|before| before.as_str() != name) {
+                let reversed = (name.to_owned(), before.clone());
+                if graph.edges.contains_key(&reversed) && graph.reported.insert(reversed.clone()) {
+                    warn!(
+                        "Lock: potential deadlock, [{name}] acquired while holding [{before}], but [{before}] was previously acquired while holding [{name}].\nThis acquisition: {}\nPrevious acquisition: {}",
+                        Backtrace::force_capture(),
+                        graph.edges[&reversed]
+                    );
+                }
+                graph
+                    .edges
+                    .entry((before.clone(), name.to_owned()))
+                    .or_insert_with(Backtrace::force_capture);
+            }
+        });
+    }
+
+    /// Whether acquiring `acquired` while holding `held` was reported as a potential deadlock.
+    #[cfg(test)]
+    pub(super) fn reported(acquired: &str, held: &str) -> bool {
+        let graph = graph().lock().unwrap_or_else(|poison| poison.into_inner());
+        graph
+            .reported
+            .contains(&(acquired.to_owned(), held.to_owned()))
+    }
+
+    /// Called once the lock `name` is held by this thread.
+    pub(super) fn push(name: &str) {
+        HELD.with(|held| held.borrow_mut().push(name.to_owned()));
+    }
+
+    /// Called when the lock `name` is released by this thread. Guards do not have to be dropped
+    /// in reverse order, so this removes the most recent entry with this name.
+    pub(super) fn pop(name: &str) {
+        HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(index) = held.iter().rposition(|held| held == name) {
+                held.remove(index);
+            }
+        });
+    }
+}
+
 /// Used to cancel timer tasks
 type Sender = tokio::sync::oneshot::Sender<()>;
 type Receiver = tokio::sync::oneshot::Receiver<()>;
@@ -129,6 +236,10 @@ impl<'a, T> Drop for RwLockReadGuard<'a, T> {
         }
         #[cfg(feature = "log-read-locks")]
         log_lock_operation(self.identifier, LockOperation::Release, LockMode::Read);
+        #[cfg(feature = "detect-lock-order")]
+        if let LockIdentifier::Name(name) = self.identifier {
+            lock_order::pop(name);
+        }
     }
 }
 
@@ -164,6 +275,10 @@ impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
         }
         #[cfg(feature = "log-write-locks")]
         log_lock_operation(self.identifier, LockOperation::Release, LockMode::Write);
+        #[cfg(feature = "detect-lock-order")]
+        if let LockIdentifier::Name(name) = self.identifier {
+            lock_order::pop(name);
+        }
     }
 }
 
@@ -235,35 +350,57 @@ impl<T> RwLock<T> {
         tx
     }
 
+    /// Get the hold and wait thresholds of this lock in milliseconds.
+    pub fn thresholds(&self) -> (u64, u64) {
+        self.thresholds.unwrap_or_else(default_thresholds)
+    }
+
     /// Spawns a timeout task for holding a lock, cancellable by sending a message through the returned channel.
     fn spawn_lock_hold_timeout_task(&self, mode: LockMode) -> Sender {
         let thread = std::thread::current();
         let thread_name = thread.name().unwrap_or("unnamed thread").to_owned();
-        let timeout = Duration::from_millis(RWLOCK_HOLD_WARN_TIMEOUT_MS);
+        let (hold_ms, _) = self.thresholds();
+        let timeout = Duration::from_millis(hold_ms);
         #[cfg(feature = "log-lock-backtrace")]
         let backtrace = Some(Backtrace::capture());
         #[cfg(not(feature = "log-lock-backtrace"))]
         let backtrace = None;
-        Self::spawn_timeout_task(timeout, format!("Lock: [{}] [{mode}] was held for over {RWLOCK_HOLD_WARN_TIMEOUT_MS}ms on thread [{thread_name}]", self.identifier()), backtrace)
+        Self::spawn_timeout_task(
+            timeout,
+            format!(
+                "Lock: [{}] [{mode}] was held for over {hold_ms}ms on thread [{thread_name}]",
+                self.identifier()
+            ),
+            backtrace,
+        )
     }
 
     /// Spawns a timeout task for waiting on a lock, cancellable by sending a message through the returned channel.
     fn spawn_lock_wait_timeout_task(&self, mode: LockMode) -> Sender {
         let thread = std::thread::current();
         let thread_name = thread.name().unwrap_or("unnamed thread").to_owned();
-        let timeout = Duration::from_millis(RWLOCK_WAIT_WARN_TIMEOUT_MS);
+        let (_, wait_ms) = self.thresholds();
+        let timeout = Duration::from_millis(wait_ms);
         #[cfg(feature = "log-lock-backtrace")]
         let backtrace = Some(Backtrace::capture());
         #[cfg(not(feature = "log-lock-backtrace"))]
         let backtrace = None;
-        Self::spawn_timeout_task(timeout, format!("Lock: [{}] [{mode}] has been waiting for over {RWLOCK_WAIT_WARN_TIMEOUT_MS}ms on thread [{thread_name}]", self.identifier()), backtrace)
+        Self::spawn_timeout_task(timeout, format!("Lock: [{}] [{mode}] has been waiting for over {wait_ms}ms on thread [{thread_name}]", self.identifier()), backtrace)
     }
 
     /// Acquire the internal reader lock, and possibly log a message for it if the feature for it is enabled.
     fn acquire_read(&self) -> LockResult<sync::RwLockReadGuard<'_, T>> {
         #[cfg(feature = "time-locks")]
         let tx = self.spawn_lock_wait_timeout_task(LockMode::Read);
+        #[cfg(feature = "detect-lock-order")]
+        if let Some(name) = &self.name {
+            lock_order::check(name);
+        }
         let result = self.lock.read();
+        #[cfg(feature = "detect-lock-order")]
+        if let Some(name) = &self.name {
+            lock_order::push(name);
+        }
         #[cfg(feature = "time-locks")]
         let _ = tx.send(());
         #[cfg(feature = "log-write-locks")]
@@ -275,7 +412,15 @@ impl<T> RwLock<T> {
     fn acquire_write(&self) -> LockResult<sync::RwLockWriteGuard<'_, T>> {
         #[cfg(feature = "time-locks")]
         let tx = self.spawn_lock_wait_timeout_task(LockMode::Write);
+        #[cfg(feature = "detect-lock-order")]
+        if let Some(name) = &self.name {
+            lock_order::check(name);
+        }
         let result = self.lock.write();
+        #[cfg(feature = "detect-lock-order")]
+        if let Some(name) = &self.name {
+            lock_order::push(name);
+        }
         #[cfg(feature = "time-locks")]
         let _ = tx.send(());
         #[cfg(feature = "log-write-locks")]
@@ -290,6 +435,7 @@ impl<T> RwLock<T> {
         Self {
             lock: sync::RwLock::new(value),
             name: None,
+            thresholds: None,
         }
     }
 
@@ -298,9 +444,26 @@ impl<T> RwLock<T> {
         Self {
             lock: sync::RwLock::new(value),
             name: Some(name.into()),
+            thresholds: None,
+        }
+    }
+
+    /// Create a new named RwLock that warns after being held for `hold_ms` or waited on for
+    /// `wait_ms` milliseconds, instead of using the default thresholds. Useful for locks that
+    /// are known to be held for a long time.
+    pub fn with_thresholds(value: T, name: impl Into<String>, hold_ms: u64, wait_ms: u64) -> Self {
+        Self {
+            lock: sync::RwLock::new(value),
+            name: Some(name.into()),
+            thresholds: Some((hold_ms, wait_ms)),
         }
     }
 
+    /// Consume the lock, returning the inner value.
+    pub fn into_inner(self) -> LockResult<T> {
+        self.lock.into_inner()
+    }
+
     /// Acquire a reader lock
     pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
         let result = self.acquire_read();
@@ -342,4 +505,87 @@ impl<T> RwLock<T> {
             })),
         }
     }
+
+    /// Attempt to acquire a reader lock without blocking. Fails with
+    /// [`TryLockError::WouldBlock`] if the lock is currently held by a writer.
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        let wrap = |guard| {
+            #[cfg(feature = "log-read-locks")]
+            log_lock_operation(self.identifier(), LockOperation::Acquire, LockMode::Read);
+            // A non-blocking acquire cannot deadlock, so it is only tracked as held
+            #[cfg(feature = "detect-lock-order")]
+            if let Some(name) = &self.name {
+                lock_order::push(name);
+            }
+            RwLockReadGuard {
+                guard,
+                identifier: self.identifier(),
+                // Only spawned on success, a failed attempt never held the lock
+                #[cfg(feature = "time-locks")]
+                release_tx: Some(self.spawn_lock_hold_timeout_task(LockMode::Read)),
+            }
+        };
+        match self.lock.try_read() {
+            Ok(guard) => Ok(wrap(guard)),
+            Err(TryLockError::Poisoned(poison)) => {
+                Err(TryLockError::Poisoned(PoisonError::new(wrap(poison.into_inner()))))
+            }
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+        }
+    }
+
+    /// Attempt to acquire a writer lock without blocking. Fails with
+    /// [`TryLockError::WouldBlock`] if the lock is currently held by anyone else.
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        let wrap = |guard| {
+            #[cfg(feature = "log-write-locks")]
+            log_lock_operation(self.identifier(), LockOperation::Acquire, LockMode::Write);
+            // A non-blocking acquire cannot deadlock, so it is only tracked as held
+            #[cfg(feature = "detect-lock-order")]
+            if let Some(name) = &self.name {
+                lock_order::push(name);
+            }
+            RwLockWriteGuard {
+                guard,
+                identifier: self.identifier(),
+                // Only spawned on success, a failed attempt never held the lock
+                #[cfg(feature = "time-locks")]
+                release_tx: Some(self.spawn_lock_hold_timeout_task(LockMode::Write)),
+            }
+        };
+        match self.lock.try_write() {
+            Ok(guard) => Ok(wrap(guard)),
+            Err(TryLockError::Poisoned(poison)) => {
+                Err(TryLockError::Poisoned(PoisonError::new(wrap(poison.into_inner()))))
+            }
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "detect-lock-order"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opposite_lock_order_is_reported() {
+        // The lock graph is shared by all tests, so these names must be unique
+        let a = RwLock::with_name(0, "lock_order_test_a");
+        let b = RwLock::with_name(0, "lock_order_test_b");
+        {
+            let _a = a.write().unwrap();
+            let _b = b.read().unwrap();
+        }
+        // Acquiring in the same order again is fine
+        {
+            let _a = a.read().unwrap();
+            let _b = b.write().unwrap();
+        }
+        assert!(!lock_order::reported("lock_order_test_b", "lock_order_test_a"));
+        {
+            let _b = b.write().unwrap();
+            let _a = a.read().unwrap();
+        }
+        assert!(lock_order::reported("lock_order_test_a", "lock_order_test_b"));
+    }
 }
@@ -8,7 +8,7 @@ pub struct WorldMousePosition {
     /// Holds a value if the mouse position is over some geometry,
     /// no value otherwise.
     pub world_space: Option<Vec3>,
-    /// If the mouse is over the terrain plane, holds the UV coordinates
-    /// of the pixel the mouse is pointing at.
+    /// If the mouse is over the terrain plane, holds the terrain UV coordinates
+    /// of the pixel the mouse is pointing at, as returned by `TerrainOptions::uv_at`.
     pub terrain_uv: Option<Vec2>,
 }
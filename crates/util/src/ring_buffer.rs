@@ -4,7 +4,7 @@ use std::marker::PhantomData;
 pub struct Iter<'a, T> {
     ptr: *const T,
     index: usize,
-    last_index: usize,
+    remaining: usize,
     size: usize,
     _marker: PhantomData<&'a T>,
 }
@@ -13,37 +13,51 @@ impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index == self.last_index {
+        if self.remaining == 0 {
             None
         } else {
-            // SAFETY: We just checked self.index is not the last index, and because of the wrapping behaviour we
-            // are always in range.
+            // SAFETY: Because of the wrapping behaviour the index is always in range, and we never
+            // yield more than `size` items.
             let item = unsafe { self.ptr.add(self.index).as_ref().unwrap() };
             self.index = (self.index + 1) % self.size;
+            self.remaining -= 1;
             Some(item)
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 }
 
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
 pub struct RingBuffer<T, const SIZE: usize> {
     buffer: [T; SIZE],
     current: usize,
+    /// Amount of values that were written to the buffer, up to `SIZE`.
+    len: usize,
 }
 
 impl<T: Default + Copy, const SIZE: usize> Default for RingBuffer<T, SIZE> {
+    /// Create an empty buffer. The current value only counts as written after the first call
+    /// to [`RingBuffer::next`].
     fn default() -> Self {
         Self {
             buffer: [T::default(); SIZE],
             current: 0,
+            len: 0,
         }
     }
 }
 
 impl<T, const SIZE: usize> RingBuffer<T, SIZE> {
+    /// Create a full buffer from initial values.
     pub fn new(values: [T; SIZE]) -> Self {
         Self {
             buffer: values,
             current: 0,
+            len: SIZE,
         }
     }
 
@@ -57,23 +71,51 @@ impl<T, const SIZE: usize> RingBuffer<T, SIZE> {
 
     pub fn next(&mut self) {
         self.current = (self.current + 1) % SIZE;
+        self.len = (self.len + 1).min(SIZE);
+    }
+
+    /// Amount of values written to the buffer, this never exceeds [`Self::capacity`].
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        SIZE
     }
 
+    /// The most recently written value, or `None` if the buffer is empty.
+    pub fn newest(&self) -> Option<&T> {
+        (!self.is_empty()).then(|| self.current())
+    }
+
+    /// The oldest value still in the buffer, or `None` if the buffer is empty.
+    pub fn oldest(&self) -> Option<&T> {
+        self.iter().next()
+    }
+
+    /// Iterate over the written values in chronological order, from the oldest value to the
+    /// current one.
     pub fn iter(&self) -> Iter<'_, T> {
-        let last = if self.current == 0 {
-            SIZE - 1
-        } else {
-            self.current - 1
-        };
         Iter {
             ptr: self.buffer.as_ptr(),
-            index: self.current,
-            last_index: last,
+            index: (self.current + 1 + SIZE - self.len) % SIZE,
+            remaining: self.len,
             size: SIZE,
             _marker: PhantomData,
         }
     }
 
+    /// Iterate over the `count` most recent values, starting at the current value and going
+    /// back in time. `count` is clamped to the amount of values written to the buffer.
+    pub fn latest(&self, count: usize) -> impl ExactSizeIterator<Item = &T> {
+        let count = count.min(self.len);
+        (0..count).map(move |offset| &self.buffer[(self.current + SIZE - offset) % SIZE])
+    }
+
     pub fn current_index(&self) -> usize {
         self.current
     }
@@ -81,25 +123,69 @@ impl<T, const SIZE: usize> RingBuffer<T, SIZE> {
     /// Iterate over the values, starting at the value that has not been returned from current()
     /// in the longest time. (So starting at old values)
     pub fn iter_fifo(&self) -> Iter<'_, T> {
-        let start = (self.current + 1) % SIZE;
-        let last = if start == 0 {
-            SIZE - 1
-        } else {
-            start - 1
-        };
-
         Iter {
             ptr: self.buffer.as_ptr(),
-            index: start,
-            last_index: last,
+            index: (self.current + 1) % SIZE,
+            remaining: SIZE - 1,
             size: SIZE,
             _marker: PhantomData,
         }
     }
 }
 
+impl<'a, T, const SIZE: usize> IntoIterator for &'a RingBuffer<T, SIZE> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
 impl<T: Debug, const SIZE: usize> Debug for RingBuffer<T, SIZE> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "RingBuffer (current = {:?}, items = {:?})", self.current, self.buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterates_oldest_to_newest() {
+        let mut buffer = RingBuffer::<u32, 4>::default();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.oldest(), None);
+        for value in 1..=2 {
+            buffer.next();
+            *buffer.current_mut() = value;
+        }
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [1, 2]);
+        for value in 3..=6 {
+            buffer.next();
+            *buffer.current_mut() = value;
+        }
+        // Older values were overwritten after wrapping around
+        assert_eq!(buffer.len(), buffer.capacity());
+        assert_eq!((&buffer).into_iter().copied().collect::<Vec<_>>(), [3, 4, 5, 6]);
+        assert_eq!(buffer.oldest(), Some(&3));
+        assert_eq!(buffer.newest(), Some(&6));
+    }
+
+    #[test]
+    fn latest_only_returns_written_values() {
+        let mut buffer = RingBuffer::<u32, 4>::default();
+        assert_eq!(buffer.latest(2).count(), 0);
+        for value in 1..=2 {
+            buffer.next();
+            *buffer.current_mut() = value;
+        }
+        assert_eq!(buffer.latest(3).copied().collect::<Vec<_>>(), [2, 1]);
+        for value in 3..=5 {
+            buffer.next();
+            *buffer.current_mut() = value;
+        }
+        assert_eq!(buffer.latest(8).copied().collect::<Vec<_>>(), [5, 4, 3, 2]);
+    }
+}
@@ -1,6 +1,7 @@
 use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
 pub struct AtmosphereInfo {
     pub planet_radius: f32,
     pub atmosphere_radius: f32,
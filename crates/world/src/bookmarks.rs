@@ -0,0 +1,83 @@
+use anyhow::Result;
+use camera::{
+    CameraState, CameraTransitionEvent, DeleteBookmarkEvent, RecallBookmarkEvent,
+    SaveBookmarkEvent,
+};
+use inject::DI;
+use scheduler::{EventBus, EventContext, StoredSystem, System};
+
+use crate::World;
+
+/// Saves, recalls and deletes the camera bookmarks of the world. The bookmarks are part of the scene,
+/// so they are saved with it.
+pub(crate) struct BookmarkSystem;
+
+impl System<DI> for BookmarkSystem {
+    fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+        event_bus.subscribe(system, handle_save_bookmark);
+        event_bus.subscribe(system, handle_recall_bookmark);
+        event_bus.subscribe(system, handle_delete_bookmark);
+    }
+}
+
+/// # DI Access
+/// - Read [`CameraState`]
+/// - Write [`World`]
+fn handle_save_bookmark(
+    _system: &mut BookmarkSystem,
+    event: &SaveBookmarkEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let pose = di.read_sync::<CameraState>().unwrap().pose();
+    let mut world = di.write_sync::<World>().unwrap();
+    world.save_camera_bookmark(event.name.clone(), pose);
+    Ok(())
+}
+
+/// # DI Access
+/// - Read [`World`]
+/// - Write [`CameraState`]
+fn handle_recall_bookmark(
+    _system: &mut BookmarkSystem,
+    event: &RecallBookmarkEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let pose = {
+        let di = ctx.read().unwrap();
+        let world = di.read_sync::<World>().unwrap();
+        match world.camera_bookmarks.get(event.index) {
+            None => return Ok(()),
+            Some(bookmark) => bookmark.pose,
+        }
+    };
+    match event.transition {
+        None => {
+            let di = ctx.read().unwrap();
+            di.write_sync::<CameraState>().unwrap().set_pose(pose);
+        }
+        // The camera advances the transition on every tick
+        Some(duration) => {
+            ctx.publish(CameraTransitionEvent {
+                target: pose,
+                duration,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// # DI Access
+/// - Write [`World`]
+fn handle_delete_bookmark(
+    _system: &mut BookmarkSystem,
+    event: &DeleteBookmarkEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let mut world = di.write_sync::<World>().unwrap();
+    if event.index < world.camera_bookmarks.len() {
+        world.camera_bookmarks.remove(event.index);
+    }
+    Ok(())
+}
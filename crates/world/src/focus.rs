@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use camera::{CameraState, CameraTransitionEvent, EnableCameraEvent, FocusEvent, FocusTarget};
+use error::publish_info;
+use glam::Vec3;
+use inject::DI;
+use input::{actions, Binding, ButtonState, InputEvent, InputState};
+use scheduler::{EventBus, EventContext, StoredSystem, System};
+use util::mouse_position::WorldMousePosition;
+
+use crate::World;
+
+/// Time the camera takes to move to the focused point.
+const TRANSITION_DURATION: Duration = Duration::from_millis(750);
+/// Distance to a focused point under the cursor, relative to the size of the terrain.
+const POINT_DISTANCE: f32 = 0.1;
+
+/// Frames the camera on the terrain or the point under the cursor, on a [`FocusEvent`] or
+/// when the focus actions are used over the world view.
+#[derive(Default)]
+pub(crate) struct FocusSystem {
+    /// Whether the camera controls are enabled, the focus actions only work while they are.
+    enabled: bool,
+}
+
+impl System<DI> for FocusSystem {
+    fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+        event_bus.subscribe(system, handle_focus_event);
+        event_bus.subscribe(system, handle_input_event);
+        event_bus.subscribe(system, handle_enabled_event);
+    }
+}
+
+/// Get the point to focus on and the distance to look at it from.
+/// The distance also sets the orthographic height, see [`CameraState::focus_pose`].
+/// # DI Access
+/// - Read [`World`]
+/// - Read [`WorldMousePosition`]
+/// - Read [`CameraState`]
+fn focus_point(bus: &EventBus<DI>, target: FocusTarget) -> Result<(Vec3, f32), &'static str> {
+    let di = bus.data().read().unwrap();
+    let world = di.read_sync::<World>().unwrap();
+    let Some((min, max)) = world.terrain_aabb() else {
+        return Err("There is no terrain to focus on.");
+    };
+    let radius = (max - min).length() / 2.0;
+    match target {
+        FocusTarget::Terrain => {
+            // Fit the bounding sphere of the terrain in the view
+            let fov = di.read_sync::<CameraState>().unwrap().fov().to_radians();
+            Ok(((min + max) / 2.0, radius / (fov / 2.0).tan()))
+        }
+        FocusTarget::Cursor => {
+            let mouse = di.read_sync::<WorldMousePosition>().unwrap();
+            match mouse.world_space {
+                None => Err("The cursor is not over the terrain."),
+                Some(point) => Ok((point, radius * 2.0 * POINT_DISTANCE)),
+            }
+        }
+    }
+}
+
+/// Move the camera to frame the target, or publish a message if there is nothing to focus on.
+/// The message is published from a blocking task, so the message sink never runs inside
+/// another handler.
+/// # DI Access
+/// - Read [`World`]
+/// - Read [`WorldMousePosition`]
+/// - Read [`CameraState`]
+fn focus(bus: &EventBus<DI>, target: FocusTarget) -> Result<()> {
+    let (point, distance) = match focus_point(bus, target) {
+        Ok(focus) => focus,
+        Err(message) => {
+            let bus = bus.clone();
+            tokio::task::spawn_blocking(move || {
+                publish_info!(bus, source = "camera", "{message}");
+            });
+            return Ok(());
+        }
+    };
+    let pose = {
+        let di = bus.data().read().unwrap();
+        let camera = di.read_sync::<CameraState>().unwrap();
+        camera.focus_pose(point, distance)
+    };
+    bus.publish(CameraTransitionEvent {
+        target: pose,
+        duration: TRANSITION_DURATION,
+    })?;
+    Ok(())
+}
+
+fn handle_focus_event(
+    _system: &mut FocusSystem,
+    event: &FocusEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    focus(ctx.bus(), event.target)
+}
+
+fn handle_enabled_event(
+    system: &mut FocusSystem,
+    event: &EnableCameraEvent,
+    _ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    system.enabled = event.enabled;
+    Ok(())
+}
+
+/// Focus directly instead of publishing a [`FocusEvent`], since this system cannot handle
+/// events it publishes while it handles one.
+/// # DI Access
+/// - Read [`InputState`]
+fn handle_input_event(
+    system: &mut FocusSystem,
+    event: &InputEvent,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    if !system.enabled {
+        return Ok(());
+    }
+    let binding = |action| {
+        let di = ctx.read().unwrap();
+        let input = di.read_sync::<InputState>().unwrap();
+        input.input_map().binding(action)
+    };
+    let target = match event {
+        InputEvent::Button(key)
+            if key.state == ButtonState::Pressed
+                && binding(actions::CAMERA_FOCUS) == Some(Binding::Key(key.button)) =>
+        {
+            FocusTarget::Terrain
+        }
+        InputEvent::MouseClick {
+            button,
+            count: 2,
+        } if binding(actions::CAMERA_FOCUS_POINT) == Some(Binding::Mouse(*button)) => {
+            FocusTarget::Cursor
+        }
+        _ => return Ok(()),
+    };
+    focus(ctx.bus(), target)
+}
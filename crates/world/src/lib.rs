@@ -1,17 +1,34 @@
 use anyhow::Result;
 pub use atmosphere::*;
 use inject::DI;
+pub use materials::*;
+pub use props::*;
 pub use render_options::*;
 use scheduler::EventBus;
 pub use world::*;
 
+use crate::bookmarks::BookmarkSystem;
+use crate::focus::FocusSystem;
+use crate::sun::SunSystem;
+
 pub mod atmosphere;
+pub mod bookmarks;
+pub mod focus;
+pub mod materials;
+pub mod props;
 pub mod render_options;
+mod sun;
 pub mod world;
 
 pub fn initialize(bus: &EventBus<DI>) -> Result<()> {
     let world = World::new();
-    let mut di = bus.data().write().unwrap();
-    di.put_sync(world);
+    {
+        let mut di = bus.data().write().unwrap();
+        di.put_sync(world);
+        di.put_sync(PropInstances::default());
+    }
+    bus.add_system(BookmarkSystem);
+    bus.add_system(FocusSystem::default());
+    bus.add_system(SunSystem);
     Ok(())
 }
@@ -0,0 +1,24 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// Materials painted on the terrain with the splat brush. The splat map of the terrain stores
+/// the weight of every layer, its first layer is the diffuse map and the other layers are
+/// these materials, in order.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerrainMaterials {
+    /// Color of each material, in linear space.
+    pub colors: [Vec3; 3],
+}
+
+impl Default for TerrainMaterials {
+    fn default() -> Self {
+        Self {
+            colors: [
+                // Rock, grass and snow
+                Vec3::new(0.18, 0.16, 0.14),
+                Vec3::new(0.06, 0.16, 0.03),
+                Vec3::new(0.8, 0.82, 0.85),
+            ],
+        }
+    }
+}
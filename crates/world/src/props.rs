@@ -0,0 +1,267 @@
+use std::f32::consts::TAU;
+
+use assets::handle::Handle;
+use assets::{HeightQuery, Terrain, TerrainOptions, Uv};
+use glam::{Mat4, Quat, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Mesh that is instanced for every prop. The meshes are built by the renderer.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PropMesh {
+    /// Cone shaped tree on a short trunk.
+    #[default]
+    Tree,
+    /// Low-poly boulder.
+    Rock,
+}
+
+impl PropMesh {
+    pub const ALL: [PropMesh; 2] = [PropMesh::Tree, PropMesh::Rock];
+}
+
+/// Settings props are scattered over the terrain with, see [`scatter_props`]. The mesh and
+/// color are applied immediately, the other settings once the props are scattered again.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PropSettings {
+    pub mesh: PropMesh,
+    /// Color of the props, in linear space.
+    pub color: Vec3,
+    /// Average number of props per hectare, before the masks are applied.
+    pub density: f32,
+    /// Height of a prop in meters.
+    pub scale: f32,
+    /// Random variation of the scale, as a fraction of it. At 0.5, props are between half
+    /// and one and a half times the scale.
+    pub scale_jitter: f32,
+    /// Steepest slope in degrees props are placed on.
+    pub max_slope: f32,
+    /// Lowest terrain height in meters props are placed at.
+    pub min_height: f32,
+    /// Highest terrain height in meters props are placed at.
+    pub max_height: f32,
+    /// Seed of the random placement. The same seed and settings give the same props.
+    pub seed: u32,
+}
+
+impl Default for PropSettings {
+    fn default() -> Self {
+        Self {
+            mesh: PropMesh::Tree,
+            color: Vec3::new(0.05, 0.2, 0.04),
+            density: 20.0,
+            scale: 8.0,
+            scale_jitter: 0.3,
+            max_slope: 35.0,
+            min_height: -1000.0,
+            max_height: 1000.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Transforms of the props that were scattered on a terrain.
+/// Access through DI.
+#[derive(Debug, Default)]
+pub struct PropInstances {
+    /// Terrain the props were scattered on, they are only drawn on this terrain.
+    pub terrain: Option<Handle<Terrain>>,
+    pub transforms: Vec<Mat4>,
+    /// Incremented every time the props change, so the renderer knows when to upload them.
+    pub version: u64,
+}
+
+impl PropInstances {
+    /// Replace the props with the ones scattered on `terrain`.
+    pub fn set(&mut self, terrain: Handle<Terrain>, transforms: Vec<Mat4>) {
+        self.terrain = Some(terrain);
+        self.transforms = transforms;
+        self.version += 1;
+    }
+
+    /// Remove all props.
+    pub fn clear(&mut self) {
+        self.terrain = None;
+        self.transforms = vec![];
+        self.version += 1;
+    }
+}
+
+/// Upper bound on the number of props, so a high density on a large terrain cannot use up
+/// all memory.
+pub const MAX_PROPS: usize = 100_000;
+
+/// Area of a hectare in square meters, [`PropSettings::density`] is given per hectare.
+const HECTARE: f32 = 10_000.0;
+
+/// Scatter props over a terrain and return their transforms. The terrain is divided into a
+/// grid with one cell per prop, and every prop is placed at a random position in its cell.
+/// Props on a slope that is too steep or outside of the height range are left out. Each prop
+/// is snapped to the height of the base heightmap, the detail layer is not taken into account.
+pub fn scatter_props(
+    settings: &PropSettings,
+    options: &TerrainOptions,
+    heights: &HeightQuery,
+) -> Vec<Mat4> {
+    let min = Vec2::new(options.min_x(), options.min_y());
+    let extent = Vec2::new(options.max_x(), options.max_y()) - min;
+    let area = extent.x * extent.y;
+    let count = (area / HECTARE * settings.density.max(0.0)).min(MAX_PROPS as f32);
+    if area <= 0.0 || count < 1.0 {
+        return vec![];
+    }
+    let cell = (area / count).sqrt();
+    let cells_x = (extent.x / cell).ceil() as u32;
+    let cells_y = (extent.y / cell).ceil() as u32;
+    let cell_size = extent / Vec2::new(cells_x as f32, cells_y as f32);
+    let jitter = settings.scale_jitter.clamp(0.0, 1.0);
+
+    let mut transforms = vec![];
+    for y in 0..cells_y {
+        for x in 0..cells_x {
+            let mut random = CellRandom::new(settings.seed, x, y);
+            let offset = Vec2::new(random.sample(), random.sample());
+            let position = min + (Vec2::new(x as f32, y as f32) + offset) * cell_size;
+            let uv = options.uv_at(Vec3::new(position.x, 0.0, position.y));
+            let height = heights.height_at(uv) * options.vertical_scale;
+            if height < settings.min_height || height > settings.max_height {
+                continue;
+            }
+            let slope = slope_at(options, heights, uv, extent);
+            if slope > settings.max_slope {
+                continue;
+            }
+            let scale = settings.scale * (1.0 + jitter * (2.0 * random.sample() - 1.0));
+            let rotation = Quat::from_rotation_y(random.sample() * TAU);
+            transforms.push(Mat4::from_scale_rotation_translation(
+                Vec3::splat(scale),
+                rotation,
+                Vec3::new(position.x, height, position.y),
+            ));
+        }
+    }
+    transforms
+}
+
+/// Returns the slope of the terrain in degrees at `uv`, from the height differences over one
+/// texel in each direction. At the edge of the terrain the differences are one-sided.
+fn slope_at(options: &TerrainOptions, heights: &HeightQuery, uv: Uv, extent: Vec2) -> f32 {
+    let texel = heights.texel_size();
+    let gradient = |axis: Vec2| {
+        let low = (uv.0 - axis * texel).clamp(Vec2::ZERO, Vec2::ONE);
+        let high = (uv.0 + axis * texel).clamp(Vec2::ZERO, Vec2::ONE);
+        let distance = ((high - low) * extent).length();
+        if distance == 0.0 {
+            return 0.0;
+        }
+        let difference = heights.height_at(Uv::new(high)) - heights.height_at(Uv::new(low));
+        difference * options.vertical_scale / distance
+    };
+    Vec2::new(gradient(Vec2::X), gradient(Vec2::Y))
+        .length()
+        .atan()
+        .to_degrees()
+}
+
+/// Random numbers for a single cell of the scatter grid. Every cell is seeded from its
+/// coordinates, so scattering again with the same settings gives the same props.
+struct CellRandom(u32);
+
+impl CellRandom {
+    fn new(seed: u32, x: u32, y: u32) -> Self {
+        Self(pcg_hash(seed ^ pcg_hash(x ^ pcg_hash(y))))
+    }
+
+    /// Returns a random number in `[0, 1)`.
+    fn sample(&mut self) -> f32 {
+        self.0 = pcg_hash(self.0);
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// PCG hash from "Hash Functions for GPU Rendering" by Jarzynski and Olano.
+fn pcg_hash(value: u32) -> u32 {
+    let state = value.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+#[cfg(test)]
+mod tests {
+    use assets::BorderMode;
+
+    use super::*;
+
+    fn options() -> TerrainOptions {
+        TerrainOptions {
+            horizontal_scale: Vec2::new(1000.0, 1000.0),
+            vertical_scale: 100.0,
+            patch_resolution: 2,
+            detail_tiling: 1.0,
+            detail_strength: 0.0,
+            normal_strength: 1.0,
+            border_mode: BorderMode::Clamp,
+        }
+    }
+
+    #[test]
+    fn props_are_snapped_to_the_terrain() {
+        // Heights rise from 0 at the left edge to 1 at the right edge
+        let heights = HeightQuery::new(2, 2, vec![0.0, 1.0, 0.0, 1.0]).unwrap();
+        let settings = PropSettings {
+            density: 1.0,
+            ..Default::default()
+        };
+        let options = options();
+        let transforms = scatter_props(&settings, &options, &heights);
+        assert!(!transforms.is_empty());
+        for transform in &transforms {
+            let position = transform.w_axis.truncate();
+            let expected = options.uv_at(position).0.x * options.vertical_scale;
+            assert!((position.y - expected).abs() < 1e-3, "{position} {expected}");
+        }
+        // Scattering again with the same settings gives the same props
+        assert_eq!(transforms, scatter_props(&settings, &options, &heights));
+    }
+
+    #[test]
+    fn masks_remove_props() {
+        // A slope of 45 degrees over the whole terrain, which is 500 meters wide
+        let heights = HeightQuery::new(2, 2, vec![0.0, 5.0, 0.0, 5.0]).unwrap();
+        let settings = PropSettings {
+            density: 1.0,
+            ..Default::default()
+        };
+        let options = options();
+        assert!(scatter_props(&settings, &options, &heights).is_empty());
+        let steep = PropSettings {
+            max_slope: 50.0,
+            ..settings
+        };
+        let all = scatter_props(&steep, &options, &heights);
+        assert!(!all.is_empty());
+        let low = PropSettings {
+            max_height: 250.0,
+            ..steep
+        };
+        let low = scatter_props(&low, &options, &heights);
+        assert!(!low.is_empty() && low.len() < all.len());
+        assert!(low.iter().all(|transform| transform.w_axis.y <= 250.0));
+    }
+
+    #[test]
+    fn density_is_limited() {
+        let heights = HeightQuery::new(1, 1, vec![0.0]).unwrap();
+        let empty = PropSettings {
+            density: 0.0,
+            ..Default::default()
+        };
+        assert!(scatter_props(&empty, &options(), &heights).is_empty());
+        let dense = PropSettings {
+            density: 1.0e6,
+            ..Default::default()
+        };
+        let count = scatter_props(&dense, &options(), &heights).len();
+        assert!(count <= MAX_PROPS + 1000, "{count}");
+    }
+}
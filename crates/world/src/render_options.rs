@@ -1,14 +1,259 @@
-#[derive(Debug)]
+use glam::{UVec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// How the terrain surface is shaded.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TerrainShading {
+    /// Diffuse texture lit by the sun.
+    #[default]
+    Lit,
+    /// Neutral clay material lit from the camera, useful to judge the shape of the terrain.
+    Matcap,
+    /// Debug view of the slope map of the terrain, from flat to vertical.
+    Slope,
+    /// Debug view of the curvature map of the terrain. Concave areas are shown in blue,
+    /// convex areas in red.
+    Curvature,
+}
+
+impl TerrainShading {
+    pub const ALL: [TerrainShading; 4] = [
+        TerrainShading::Lit,
+        TerrainShading::Matcap,
+        TerrainShading::Slope,
+        TerrainShading::Curvature,
+    ];
+}
+
+/// How the tessellator subdivides the edges of a terrain patch for a tessellation factor.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TessellationSpacing {
+    /// Factors are rounded up to a whole number of segments. Vertices pop in and out when
+    /// the factor changes.
+    #[default]
+    Integer,
+    /// Factors are rounded up to an even number of segments, the extra segments grow in
+    /// smoothly as the factor increases.
+    FractionalEven,
+    /// Factors are rounded up to an odd number of segments, the extra segments grow in
+    /// smoothly as the factor increases.
+    FractionalOdd,
+}
+
+impl TessellationSpacing {
+    pub const ALL: [TessellationSpacing; 3] = [
+        TessellationSpacing::Integer,
+        TessellationSpacing::FractionalEven,
+        TessellationSpacing::FractionalOdd,
+    ];
+}
+
+/// How the HDR scene image is mapped to the displayed image.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DisplayTransform {
+    /// Apply the tonemapping curve.
+    #[default]
+    Tonemap,
+    /// Show the raw HDR values, scaled by `exposure` stops and clipped. Useful to inspect
+    /// the scene before tonemapping.
+    Exposure {
+        exposure: f32,
+    },
+}
+
+/// Curve that maps HDR scene values to the displayed range. Kept in sync with `tonemap.fs.hlsl`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TonemapOperator {
+    /// ACES filmic curve applied to the luminance only, which preserves the hue of bright
+    /// colors.
+    #[default]
+    AcesLuminance,
+    /// ACES filmic curve applied to each color channel, which desaturates bright colors.
+    Aces,
+    /// Reinhard curve applied to the luminance.
+    Reinhard,
+    /// Clip values to the displayed range.
+    Clamp,
+}
+
+impl TonemapOperator {
+    pub const ALL: [TonemapOperator; 4] = [
+        TonemapOperator::AcesLuminance,
+        TonemapOperator::Aces,
+        TonemapOperator::Reinhard,
+        TonemapOperator::Clamp,
+    ];
+
+    /// Value identifying this operator in shaders.
+    pub fn shader_value(self) -> u32 {
+        match self {
+            TonemapOperator::AcesLuminance => 0,
+            TonemapOperator::Aces => 1,
+            TonemapOperator::Reinhard => 2,
+            TonemapOperator::Clamp => 3,
+        }
+    }
+}
+
+/// Anti-aliasing method applied to the scene.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AntiAliasing {
+    /// No anti-aliasing, the scene is rendered at the output resolution.
+    None,
+    /// Upscale from a lower render resolution with FSR2, which resolves the jittered frames
+    /// into an anti-aliased image.
+    #[default]
+    Fsr2,
+    /// Smooth edges of the tonemapped image with FXAA. The scene is rendered at the output
+    /// resolution. This is much cheaper than FSR2, but also less stable in motion.
+    Fxaa,
+    /// Resolve the jittered frames into an anti-aliased image with TAA. The scene is rendered
+    /// at the output resolution.
+    Taa,
+}
+
+impl AntiAliasing {
+    pub const ALL: [AntiAliasing; 4] =
+        [AntiAliasing::None, AntiAliasing::Fsr2, AntiAliasing::Fxaa, AntiAliasing::Taa];
+
+    /// Returns true if the scene is upscaled from a lower render resolution with FSR2.
+    /// Otherwise it is rendered at the output resolution directly.
+    pub fn upscaling(self) -> bool {
+        self == AntiAliasing::Fsr2
+    }
+
+    /// Returns true if the scene is rendered with a jittered projection, so the jitter can
+    /// be resolved over multiple frames by FSR2 or TAA.
+    pub fn jittered(self) -> bool {
+        matches!(self, AntiAliasing::Fsr2 | AntiAliasing::Taa)
+    }
+}
+
+/// Quality preset of FXAA. Higher presets search further along edges and blend more
+/// aliasing within a pixel, at a higher cost.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FxaaQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl FxaaQuality {
+    pub const ALL: [FxaaQuality; 3] = [FxaaQuality::Low, FxaaQuality::Medium, FxaaQuality::High];
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RenderOptions {
+    /// Maximum tessellation factor of a single terrain patch edge.
     pub tessellation_level: u32,
+    /// Target length of a tessellated terrain edge on screen, in pixels. Smaller values
+    /// produce more detail.
+    pub target_edge_length: f32,
+    /// How terrain patch edges are subdivided, see [`TessellationSpacing`].
+    pub tessellation_spacing: TessellationSpacing,
     pub wireframe: bool,
+    /// Multiplier applied to the size of the world view to obtain the output resolution.
+    /// The world view is measured in GUI points, so this is the number of output pixels per
+    /// point and is independent of the display scale factor.
+    pub render_scale: f32,
+    /// Render at the exact pixel size of the world view, so the scene image is displayed
+    /// without resampling. Overrides `render_scale`.
+    pub match_view_size: bool,
+    /// If set, the output resolution will never exceed this size. The aspect ratio of
+    /// the world view is preserved.
+    pub max_output_resolution: Option<UVec2>,
+    /// Anti-aliasing method, see [`AntiAliasing`].
+    pub anti_aliasing: AntiAliasing,
+    /// Quality preset used when `anti_aliasing` is [`AntiAliasing::Fxaa`].
+    pub fxaa_quality: FxaaQuality,
+    /// Render the atmosphere. If disabled, the sky is cleared to `background`.
+    pub atmosphere: bool,
+    /// Render the terrain depth in a separate pass first, so the expensive terrain shading
+    /// only runs for visible fragments. Ignored in wireframe mode.
+    pub depth_prepass: bool,
+    /// Render decals over the terrain, such as the brush decal.
+    pub decals: bool,
+    pub terrain_shading: TerrainShading,
+    /// Background color used when the atmosphere is disabled.
+    pub background: Vec3,
+    pub display_transform: DisplayTransform,
+    /// Curve used when `display_transform` is [`DisplayTransform::Tonemap`].
+    pub tonemap_operator: TonemapOperator,
+    /// Exposure adjustment in stops, applied before the tonemapping curve.
+    pub tonemap_exposure: f32,
+    /// Add a glow around bright parts of the image before it is tonemapped. Off by default.
+    pub bloom: bool,
+    /// Brightness above which pixels contribute to the bloom.
+    pub bloom_threshold: f32,
+    /// Strength of the bloom added to the image.
+    pub bloom_intensity: f32,
 }
 
 impl Default for RenderOptions {
     fn default() -> Self {
         Self {
             tessellation_level: 128,
+            target_edge_length: 8.0,
+            tessellation_spacing: TessellationSpacing::Integer,
             wireframe: false,
+            render_scale: 1.5,
+            match_view_size: false,
+            max_output_resolution: None,
+            anti_aliasing: AntiAliasing::Fsr2,
+            fxaa_quality: FxaaQuality::High,
+            depth_prepass: false,
+            atmosphere: true,
+            decals: true,
+            terrain_shading: TerrainShading::Lit,
+            background: Vec3::splat(0.18),
+            display_transform: DisplayTransform::Tonemap,
+            tonemap_operator: TonemapOperator::AcesLuminance,
+            tonemap_exposure: 0.0,
+            bloom: false,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.04,
         }
     }
 }
+
+impl RenderOptions {
+    /// Returns true if all passes except the terrain are disabled and the terrain is shaded
+    /// with a matcap.
+    pub fn is_terrain_isolated(&self) -> bool {
+        !self.atmosphere && !self.decals && self.terrain_shading == TerrainShading::Matcap
+    }
+
+    /// Hides everything except the terrain and shades it with a neutral matcap, so the
+    /// surface reads clearly while sculpting. Disabling this restores the default passes.
+    pub fn set_isolate_terrain(&mut self, isolate: bool) {
+        self.atmosphere = !isolate;
+        self.decals = !isolate;
+        self.terrain_shading = match isolate {
+            true => TerrainShading::Matcap,
+            false => TerrainShading::Lit,
+        };
+    }
+
+    /// Computes the output resolution for a world view of the given size in points.
+    /// `pixels_per_point` is the scale factor of the GUI.
+    pub fn output_resolution(
+        &self,
+        view_width: u32,
+        view_height: u32,
+        pixels_per_point: f32,
+    ) -> UVec2 {
+        let view_scale = match self.match_view_size {
+            true => pixels_per_point,
+            false => self.render_scale,
+        };
+        let width = (view_width as f32 * view_scale).round();
+        let height = (view_height as f32 * view_scale).round();
+        let scale = match self.max_output_resolution {
+            None => 1.0,
+            Some(max) => (max.x as f32 / width).min(max.y as f32 / height).min(1.0),
+        };
+        UVec2::new(((width * scale) as u32).max(1), ((height * scale) as u32).max(1))
+    }
+}
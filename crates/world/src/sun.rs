@@ -0,0 +1,31 @@
+use anyhow::Result;
+use events::Tick;
+use inject::DI;
+use scheduler::{EventBus, EventContext, StoredSystem, System};
+use time::Time;
+
+use crate::World;
+
+/// Moves the sun across the sky every frame, see [`World::sun_speed`].
+pub(crate) struct SunSystem;
+
+impl System<DI> for SunSystem {
+    fn initialize(event_bus: &EventBus<DI>, system: &StoredSystem<Self>) {
+        event_bus.subscribe(system, handle_tick_event);
+    }
+}
+
+/// # DI Access
+/// - Read [`Time`]
+/// - Write [`World`]
+fn handle_tick_event(
+    _system: &mut SunSystem,
+    _event: &Tick,
+    ctx: &mut EventContext<DI>,
+) -> Result<()> {
+    let di = ctx.read().unwrap();
+    let delta = di.read_sync::<Time>().unwrap().delta;
+    let mut world = di.write_sync::<World>().unwrap();
+    world.advance_sun(delta);
+    Ok(())
+}
@@ -1,32 +1,81 @@
+use std::f32::consts::TAU;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
 use assets::handle::Handle;
-use assets::{Terrain, TerrainOptions};
-use glam::Vec3;
+use assets::storage::AssetStorage;
+use assets::{BorderMode, HeightRange, PendingHeightRange, Terrain, TerrainOptions, TerrainSource};
+use camera::{CameraBookmark, CameraPose};
+use error::publish_warn;
+use glam::{Vec2, Vec3};
+use inject::DI;
 use math::Rotation;
+use scheduler::EventBus;
+use serde::{Deserialize, Serialize};
+
+use crate::{AtmosphereInfo, PropSettings, RenderOptions, TerrainMaterials};
 
-use crate::{AtmosphereInfo, RenderOptions};
+/// File scenes are saved to and opened from by default.
+pub const SCENE_FILE: &str = "data/scene.json";
 
-#[derive(Debug)]
+/// Scenes are saved with everything except runtime handles. The terrain is stored as the files it
+/// was last opened from or committed to, and is loaded again from those files.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct World {
     /// Direction of the sun. This is represented as a rotation for easy editing.
     pub sun_direction: Rotation,
+    /// Speed the sun moves across the sky at, in radians of pitch per second of animation
+    /// time, see [`World::advance_sun`]. Zero keeps the sun in place.
+    pub sun_speed: f32,
     pub atmosphere: AtmosphereInfo,
+    #[serde(skip)]
     pub terrain: Option<Handle<Terrain>>,
+    /// Files the terrain was last opened from or committed to.
+    pub terrain_source: Option<TerrainSource>,
     pub options: RenderOptions,
     pub terrain_options: TerrainOptions,
+    /// Saved camera poses, in the order they were saved.
+    pub camera_bookmarks: Vec<CameraBookmark>,
+    /// Pose of the camera when the scene was saved, restored when it is opened.
+    pub camera: Option<CameraPose>,
+    /// Settings props are scattered over the terrain with.
+    pub props: PropSettings,
+    /// Materials painted on the terrain through its splat map.
+    pub materials: TerrainMaterials,
+    /// Cached height range of the terrain the bounds were last computed for.
+    #[serde(skip)]
+    terrain_bounds: Option<(Handle<Terrain>, HeightRange)>,
+    /// Height range of the terrain that is still being computed on the GPU.
+    #[serde(skip)]
+    pending_bounds: Option<(Handle<Terrain>, PendingHeightRange)>,
 }
 
 impl Default for World {
     fn default() -> Self {
         Self {
             sun_direction: Rotation(Vec3::new(12.0f32.to_radians(), 0.0, 0.0)),
+            sun_speed: 0.0,
             atmosphere: AtmosphereInfo::earth(),
             terrain: None,
+            terrain_source: None,
             options: Default::default(),
             terrain_options: TerrainOptions {
-                horizontal_scale: 512.0,
+                horizontal_scale: Vec2::new(512.0, 512.0),
                 vertical_scale: 100.0,
                 patch_resolution: 32,
+                detail_tiling: 16.0,
+                detail_strength: 2.0,
+                normal_strength: 1.0,
+                border_mode: BorderMode::Clamp,
             },
+            camera_bookmarks: vec![],
+            camera: None,
+            props: PropSettings::default(),
+            materials: TerrainMaterials::default(),
+            terrain_bounds: None,
+            pending_bounds: None,
         }
     }
 }
@@ -35,4 +84,210 @@ impl World {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Returns the world-space bounding box `(min, max)` of the terrain, or `None` if there is
+    /// no terrain. While the height range of the terrain is not known yet, the full normalized
+    /// height range is assumed.
+    pub fn terrain_aabb(&self) -> Option<(Vec3, Vec3)> {
+        let terrain = self.terrain?;
+        let range = match self.terrain_bounds {
+            Some((handle, range)) if handle == terrain => range,
+            _ => HeightRange::NORMALIZED,
+        };
+        Some(self.terrain_options.aabb(range))
+    }
+
+    /// Move the sun by [`World::sun_speed`] for `delta` of animation time. This is driven by
+    /// [`Time::delta`](time::Time::delta), so the sun stands still while time is paused.
+    pub fn advance_sun(&mut self, delta: Duration) {
+        let pitch = self.sun_direction.0.x + self.sun_speed * delta.as_secs_f32();
+        self.sun_direction.0.x = pitch.rem_euclid(TAU);
+    }
+
+    /// Store a camera pose as a bookmark, replacing any bookmark with the same name.
+    pub fn save_camera_bookmark(&mut self, name: String, pose: CameraPose) {
+        let bookmark = CameraBookmark {
+            name,
+            pose,
+        };
+        match self
+            .camera_bookmarks
+            .iter_mut()
+            .find(|b| b.name == bookmark.name)
+        {
+            None => self.camera_bookmarks.push(bookmark),
+            Some(existing) => *existing = bookmark,
+        }
+    }
+
+    /// Save the world to a scene file. Edits that were not committed to the terrain files are
+    /// not part of the scene.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Load a world from a scene file, and start loading its terrain. If a file of the terrain
+    /// no longer exists, a warning is published and the rest of the scene is loaded without
+    /// a terrain.
+    ///
+    /// This publishes a [`MessageEvent`](error::MessageEvent), so it must not be called from
+    /// the editor.
+    /// # DI Access
+    /// - Read [`AssetStorage`]
+    pub fn load(path: impl AsRef<Path>, bus: &EventBus<DI>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let mut world: World = serde_json::from_reader(std::io::BufReader::new(file))?;
+        let Some(source) = &world.terrain_source else { return Ok(world) };
+        match source.missing_file() {
+            None => {
+                let di = bus.data().read().unwrap();
+                let assets = di.get::<AssetStorage>().unwrap();
+                world.terrain = Some(assets.load(source.load_info()));
+            }
+            Some(missing) => {
+                let missing = missing.display();
+                publish_warn!(
+                    bus,
+                    source = "terrain",
+                    "Could not find {missing} of the terrain in this scene, it was not loaded."
+                );
+                world.terrain_source = None;
+            }
+        }
+        Ok(world)
+    }
+
+    /// Marks the cached terrain bounds as stale, for example after the heightmap was modified.
+    /// They will be recomputed on the next call to [`update_terrain_bounds`].
+    pub fn invalidate_terrain_bounds(&mut self) {
+        self.terrain_bounds = None;
+        // A range that is still being computed may not include the latest edits
+        self.pending_bounds = None;
+    }
+}
+
+/// Fits the z extent of the terrain options to the aspect ratio of the terrain heightmap, like
+/// the terrain loader does for the mesh. This keeps brushes and UV lookups consistent with the
+/// mesh after the terrain or its options changed. Does nothing while the heightmap is loading.
+/// # DI Access
+/// - Write [`World`]
+/// - Read [`AssetStorage`]
+pub fn fit_terrain_options(bus: &EventBus<DI>) {
+    let di = bus.data().read().unwrap();
+    let Some(terrain) = di.read_sync::<World>().unwrap().terrain else { return };
+    let assets = di.get::<AssetStorage>().unwrap();
+    let size = assets
+        .with_if_ready(terrain, |terrain| terrain.height_map)
+        .and_then(|heights| {
+            assets.with_if_ready(heights, |heights| (heights.image.width(), heights.image.height()))
+        });
+    let Some((width, height)) = size else { return };
+    let mut world = di.write_sync::<World>().unwrap();
+    // The terrain may have changed while the lock was released
+    if world.terrain == Some(terrain) {
+        world.terrain_options = world.terrain_options.fit_to_heightmap(width, height);
+    }
+}
+
+/// Recomputes the height range of the terrain if it is stale and the terrain is ready. The range
+/// is computed on the GPU without waiting for it, the bounds are updated on a later call once
+/// it is done. Until then, the previous bounds are kept unless they were invalidated.
+/// # DI Access
+/// - Write [`World`]
+/// - Read [`AssetStorage`]
+pub fn update_terrain_bounds(bus: &EventBus<DI>) -> Result<()> {
+    let di = bus.data().read().unwrap();
+    let mut world = di.write_sync::<World>().unwrap();
+    let Some(terrain) = world.terrain else { return Ok(()) };
+    if matches!(world.terrain_bounds, Some((handle, _)) if handle == terrain) {
+        return Ok(());
+    }
+
+    // Borrow the fields of the world separately
+    let world = &mut *world;
+    match &mut world.pending_bounds {
+        Some((handle, pending)) if *handle == terrain => {
+            if let Some(range) = pending.poll()? {
+                world.terrain_bounds = Some((terrain, range));
+                world.pending_bounds = None;
+            }
+            return Ok(());
+        }
+        // The terrain changed while its range was computed
+        Some(_) => world.pending_bounds = None,
+        None => {}
+    }
+
+    let assets = di.get::<AssetStorage>().unwrap();
+    let heights = assets.with_if_ready(terrain, |terrain| terrain.height_map);
+    let pending = heights
+        .and_then(|heights| assets.with_if_ready(heights, |heights| heights.compute_range(bus)));
+    if let Some(pending) = pending {
+        world.pending_bounds = Some((terrain, pending?));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_world_skips_runtime_state() {
+        let mut world = World::new();
+        world.options.wireframe = true;
+        world.terrain_options.vertical_scale = 250.0;
+        let json = serde_json::to_string(&world).unwrap();
+        assert!(!json.contains("terrain_bounds"));
+        let loaded: World = serde_json::from_str(&json).unwrap();
+        assert!(loaded.options.wireframe);
+        assert_eq!(loaded.terrain_options, world.terrain_options);
+        assert!(loaded.terrain.is_none());
+    }
+
+    #[test]
+    fn camera_bookmarks_replace_by_name() {
+        let pose = |x| CameraPose {
+            position: Vec3::new(x, 0.0, 0.0),
+            rotation: Vec3::ZERO,
+            fov: 90.0,
+            ortho_height: 1000.0,
+        };
+        let mut world = World::new();
+        world.save_camera_bookmark("a".into(), pose(1.0));
+        world.save_camera_bookmark("b".into(), pose(2.0));
+        world.save_camera_bookmark("a".into(), pose(3.0));
+        let json = serde_json::to_string(&world).unwrap();
+        let loaded: World = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.camera_bookmarks.len(), 2);
+        assert_eq!(loaded.camera_bookmarks[0].name, "a");
+        assert_eq!(loaded.camera_bookmarks[0].pose, pose(3.0));
+    }
+
+    #[test]
+    fn sun_moves_with_animation_time() {
+        let mut world = World::new();
+        let start = world.sun_direction.0;
+        world.advance_sun(Duration::from_secs(1));
+        assert_eq!(world.sun_direction.0, start);
+        world.sun_speed = 0.5;
+        world.advance_sun(Duration::from_secs(2));
+        assert!((world.sun_direction.0.x - (start.x + 1.0)).abs() < 1e-5);
+        // The pitch wraps around after a full day
+        world.advance_sun(Duration::from_secs_f32(TAU * 2.0));
+        assert!((world.sun_direction.0.x - (start.x + 1.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let loaded: World = serde_json::from_str(r#"{"options": {"wireframe": true}}"#).unwrap();
+        assert!(loaded.options.wireframe);
+        assert_eq!(loaded.options.tessellation_level, RenderOptions::default().tessellation_level);
+        assert_eq!(loaded.atmosphere.planet_radius, AtmosphereInfo::earth().planet_radius);
+    }
 }
@@ -0,0 +1,43 @@
+// Border handling of base terrain textures, kept in sync with BorderMode. The modes match the
+// clamp to edge, repeat and mirrored repeat sampler address modes.
+
+static const uint BORDER_CLAMP = 0;
+static const uint BORDER_WRAP = 1;
+static const uint BORDER_MIRROR = 2;
+
+// Remainder of i / n that is never negative.
+int positive_mod(int i, int n) {
+    return ((i % n) + n) % n;
+}
+
+// Maps a texel index along an axis with n texels to the texel inside the texture it refers to.
+int border_texel_axis(int i, int n, uint mode) {
+    if (mode == BORDER_WRAP) {
+        return positive_mod(i, n);
+    }
+    if (mode == BORDER_MIRROR) {
+        int m = positive_mod(i, 2 * n);
+        return m < n ? m : 2 * n - 1 - m;
+    }
+    return clamp(i, 0, n - 1);
+}
+
+// Maps a texel that may lie outside a texture to the texel that is read instead.
+int2 border_texel(int2 texel, uint2 size, uint mode) {
+    return int2(
+        border_texel_axis(texel.x, int(size.x), mode),
+        border_texel_axis(texel.y, int(size.y), mode)
+    );
+}
+
+// Finds the texel a brush writes to when painting at a texel that may lie outside the texture.
+// Only wrapping continues strokes past the border, otherwise texels outside are skipped and
+// false is returned.
+bool brush_texel(int2 texel, uint2 size, uint mode, out int2 result) {
+    if (mode == BORDER_WRAP) {
+        result = border_texel(texel, size, mode);
+        return true;
+    }
+    result = texel;
+    return texel.x >= 0 && texel.y >= 0 && texel.x < int(size.x) && texel.y < int(size.y);
+}
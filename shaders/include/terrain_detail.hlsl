@@ -0,0 +1,52 @@
+// Perturbation of the terrain normals by its detail layers. Shared by the terrain shaders and
+// the lighting bake, so baked shading matches what is rendered.
+// Requires terrain_uv.hlsl to be included first.
+
+// The normal map is only generated from the base heightmap, so we perturb it with
+// the slope of the detail layer. horizontal_scale is the size of the terrain in world space.
+float3 apply_detail_normal(
+    float3 normal,
+    float2 uv,
+    Texture2D<half> detail_map,
+    SamplerState detail_smp,
+    float detail_tiling,
+    float detail_strength,
+    float2 horizontal_scale
+) {
+    if (detail_strength == 0.0) {
+        return normal;
+    }
+    uint width, height;
+    detail_map.GetDimensions(width, height);
+    float2 detail_uv = uv * detail_tiling;
+    float2 texel = 1.0 / float2(width, height);
+    float left = detail_map.SampleLevel(detail_smp, detail_uv - float2(texel.x, 0.0), 0.0);
+    float right = detail_map.SampleLevel(detail_smp, detail_uv + float2(texel.x, 0.0), 0.0);
+    float down = detail_map.SampleLevel(detail_smp, detail_uv - float2(0.0, texel.y), 0.0);
+    float up = detail_map.SampleLevel(detail_smp, detail_uv + float2(0.0, texel.y), 0.0);
+    // Size of a single detail texel in world space
+    float2 texel_size = horizontal_scale / (detail_tiling * float2(width, height));
+    float dx = (right - left) * detail_strength / (2.0 * texel_size.x);
+    float dz = (up - down) * detail_strength / (2.0 * texel_size.y);
+    // For a flat base this is exactly the normal of the detail heightfield, otherwise
+    // it is a close enough approximation.
+    return normalize(normal + float3(-dx, 0.0, -dz));
+}
+
+// Perturbs a world space normal with the painted detail normals. The tangent frame follows
+// the terrain uvs, so tangent x points along world x and tangent y along world z.
+float3 apply_painted_normal(
+    float3 normal,
+    float2 uv,
+    Texture2D<float4> detail_normal_map,
+    SamplerState detail_normal_smp
+) {
+    uint width, height;
+    detail_normal_map.GetDimensions(width, height);
+    float2 sample_uv = terrain_sample_uv(uv, uint2(width, height));
+    float3 perturbation = detail_normal_map.SampleLevel(detail_normal_smp, sample_uv, 0.0).rgb;
+    perturbation = perturbation * 2.0 - float3(1.0, 1.0, 1.0);
+    float3 tangent = normalize(float3(1.0, 0.0, 0.0) - normal * normal.x);
+    float3 bitangent = cross(tangent, normal);
+    return normalize(tangent * perturbation.x + bitangent * perturbation.y + normal * perturbation.z);
+}
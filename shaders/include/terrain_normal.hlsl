@@ -0,0 +1,48 @@
+// Normal derivation from the base heightmap, shared by the full normal map generation and
+// the partial recompute after a brush stroke. Kept in sync with NormalParams.
+
+struct NormalParams {
+    // Distance between two heightmap texel centers in meters, along x and z.
+    float2 texel_spacing;
+    // Height in meters of a heightmap value of 1.
+    float vertical_scale;
+    // Artistic multiplier on the slope of the normals.
+    float strength;
+    // Border mode of the terrain, see border.hlsl.
+    uint border_mode;
+    uint _padding;
+};
+
+// Calculates the slope of the terrain in world space from a 3x3 area of heights around a
+// texel using a sobel filter. The sobel filter responds with eight times the height difference
+// per texel, which we convert to meters per meter. The result points downhill.
+float2 sobel_slope(float heights[3][3], NormalParams params) {
+    float2 sobel;
+    sobel.x =
+        heights[0][0]
+        - heights[2][0]
+        + 2.0f * heights[0][1]
+        - 2.0f * heights[2][1]
+        + heights[0][2]
+        - heights[2][2];
+    sobel.y =
+        heights[0][0]
+        + 2.0f * heights[1][0]
+        + heights[2][0]
+        - heights[0][2]
+        - 2.0f * heights[1][2]
+        - heights[2][2];
+
+    return sobel * params.vertical_scale / (8.0 * params.texel_spacing);
+}
+
+// Calculates the normal from a 3x3 area of heights around a texel.
+float3 sobel_normal(float heights[3][3], NormalParams params) {
+    float2 slope = sobel_slope(heights, params) * params.strength;
+    return normalize(float3(slope.x, 1.0, slope.y));
+}
+
+// Remap normal from -1, 1 to [0, 1] before storing
+float4 encode_normal(float3 normal) {
+    return float4((normal + float3(1.0, 1.0, 1.0)) / 2.0, 0.0);
+}
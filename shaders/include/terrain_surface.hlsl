@@ -0,0 +1,62 @@
+// Shared inputs and helpers for the terrain fragment shaders.
+
+#include "terrain_uv.hlsl"
+#include "terrain_detail.hlsl"
+
+struct PS_INPUT {
+    [[vk::location(0)]] float2 UV : UV0;
+    [[vk::location(1)]] float4 ClipPos : POS0;
+    [[vk::location(2)]] float4 PrevClipPos: POS1;
+};
+
+struct PS_OUTPUT {
+    [[vk::location(0)]] float4 Color : SV_Target0;
+    [[vk::location(1)]] float2 Motion : SV_Target1;
+};
+
+[[vk::combinedImageSampler, vk::binding(3, 0)]]
+Texture2D<float4> normal_map;
+
+[[vk::combinedImageSampler, vk::binding(3, 0)]]
+SamplerState smp;
+
+[[vk::combinedImageSampler, vk::binding(5, 0)]]
+Texture2D<half> detail_map;
+
+[[vk::combinedImageSampler, vk::binding(5, 0)]]
+SamplerState detail_smp;
+
+// Painted tangent space normal perturbations, see DetailNormalMap.
+[[vk::combinedImageSampler, vk::binding(6, 0)]]
+Texture2D<float4> detail_normal_map;
+
+[[vk::combinedImageSampler, vk::binding(6, 0)]]
+SamplerState detail_normal_smp;
+
+[[vk::push_constant]]
+struct PC {
+    uint tessellation_factor;
+    float height_scaling;
+    uint2 viewport_size;
+    float target_edge_length;
+    float detail_tiling;
+    float detail_strength;
+    float2 horizontal_scale;
+} pc;
+
+// Returns the world space surface normal of the terrain, including the detail layers.
+float3 terrain_normal(float2 uv) {
+    uint width, height;
+    normal_map.GetDimensions(width, height);
+    float3 normal = normal_map.SampleLevel(smp, terrain_sample_uv(uv, uint2(width, height)), 0.0).rgb;
+    // remap back to [-1, 1]
+    normal = normal * 2.0 - float3(1.0, 1.0, 1.0);
+    normal = apply_detail_normal(
+        normal, uv, detail_map, detail_smp, pc.detail_tiling, pc.detail_strength, pc.horizontal_scale
+    );
+    return apply_painted_normal(normal, uv, detail_normal_map, detail_normal_smp);
+}
+
+float2 motion_vector(PS_INPUT input) {
+    return input.PrevClipPos.xy / input.PrevClipPos.w - input.ClipPos.xy / input.ClipPos.w;
+}
@@ -0,0 +1,17 @@
+// Terrain uv convention, kept in sync with TerrainOptions::uv_at and texel_at_uv.
+//
+// Terrain uvs run from 0 to 1 over the terrain mesh, and both ends refer to the center of
+// the edge texels of the base heightmap, normal map and diffuse map. Texel i of a texture
+// with n texels is located at uv = i / (n - 1). Base terrain textures must always be
+// sampled through terrain_sample_uv, which applies the half texel offset.
+// The detail heightmap is tiled with a repeating sampler and does not use this convention.
+
+// Converts terrain uvs to normalized texture coordinates for a texture of the given size.
+float2 terrain_sample_uv(float2 uv, uint2 size) {
+    return (uv * float2(size - 1) + 0.5) / float2(size);
+}
+
+// Normalized texture coordinates of the center of a texel.
+float2 texel_center_uv(int2 texel, uint2 size) {
+    return (float2(texel) + 0.5) / float2(size);
+}
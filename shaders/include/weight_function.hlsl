@@ -0,0 +1,30 @@
+// Falloff curves of the brushes, kept in sync with WeightFunction in height.rs.
+
+static const uint WEIGHT_GAUSSIAN = 0;
+static const uint WEIGHT_LINEAR = 1;
+static const uint WEIGHT_SMOOTHSTEP = 2;
+static const uint WEIGHT_CONSTANT = 3;
+
+// Returns the weight of a brush at x, the distance from its center relative to its radius.
+// Every curve has a weight of 1 at the center and 0 outside of the radius, where x > 1.
+// For gaussian curves param is the standard deviation, for linear and smoothstep curves it is
+// the fraction of the radius that gets the full weight.
+float weight_function(uint kind, float param, float x) {
+    if (x > 1.0) {
+        return 0.0;
+    }
+    if (kind == WEIGHT_LINEAR) {
+        return saturate((1.0 - x) / max(1.0 - param, 0.0001));
+    }
+    if (kind == WEIGHT_SMOOTHSTEP) {
+        return 1.0 - smoothstep(min(param, 0.9999), 1.0, x);
+    }
+    if (kind == WEIGHT_CONSTANT) {
+        return 1.0;
+    }
+    // Gaussian, scaled to a peak of 1 instead of normalizing its area so the brush weight
+    // does not depend on the standard deviation.
+    float sigma = param;
+    float p = (x / sigma) * (x / sigma);
+    return exp(-0.5 * p);
+}
@@ -89,6 +89,8 @@ float3 add_ray_offset(float3 p) {
     return p + float3(0, 10, 0);
 }
 
+// In orthographic mode, inv_projection is a perspective projection around the view direction,
+// since parallel rays would all see the same sky color.
 float3 camera_ray_direction(float2 uv) {
     uv = uv * 2.0 - 1.0;
     float4 target = mul(inv_projection, float4(uv.x, uv.y, 1, 1));
@@ -0,0 +1,65 @@
+#include "color_space.hlsl"
+#include "terrain_uv.hlsl"
+#include "terrain_detail.hlsl"
+
+// The color map holds sRGB encoded data, but sRGB formats cannot be used as storage images.
+// We read and write the raw encoded values and convert to linear space for shading.
+[[vk::binding(0, 0), vk::image_format("rgba8")]]
+RWTexture2D<float4> colors;
+
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+Texture2D<float4> normal_map;
+
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+SamplerState smp;
+
+// The base heightmap with a detail strength of zero if the terrain has no detail layer.
+[[vk::combinedImageSampler, vk::binding(2, 0)]]
+Texture2D<half> detail_map;
+
+[[vk::combinedImageSampler, vk::binding(2, 0)]]
+SamplerState detail_smp;
+
+[[vk::combinedImageSampler, vk::binding(3, 0)]]
+Texture2D<float4> detail_normal_map;
+
+[[vk::combinedImageSampler, vk::binding(3, 0)]]
+SamplerState detail_normal_smp;
+
+[[vk::push_constant]] struct PC {
+    // Direction the sunlight travels in, matching sun_dir in terrain.fs.hlsl
+    float3 sun_dir;
+    // Light every texel receives, so slopes facing away from the sun keep some color
+    float ambient;
+    // Detail layer settings, matching the terrain shaders
+    float detail_tiling;
+    float detail_strength;
+    float2 horizontal_scale;
+} pc;
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    colors.GetDimensions(w, h);
+    int2 texel = int2(GlobalInvocationID.xy);
+    if (texel.x >= w || texel.y >= h) {
+        return;
+    }
+
+    // Texel i of a base texture with n texels is located at uv = i / (n - 1)
+    float2 uv = float2(texel) / float2(max(w, 2) - 1, max(h, 2) - 1);
+    uint normal_w, normal_h;
+    normal_map.GetDimensions(normal_w, normal_h);
+    float2 normal_uv = terrain_sample_uv(uv, uint2(normal_w, normal_h));
+    float3 normal = normal_map.SampleLevel(smp, normal_uv, 0.0).rgb * 2.0 - float3(1.0, 1.0, 1.0);
+    // Bake the same normal the terrain is shaded with, see terrain_normal in terrain_surface.hlsl
+    normal = apply_detail_normal(
+        normalize(normal), uv, detail_map, detail_smp, pc.detail_tiling, pc.detail_strength, pc.horizontal_scale
+    );
+    normal = apply_painted_normal(normal, uv, detail_normal_map, detail_normal_smp);
+
+    float diff = max(dot(normal, -pc.sun_dir), 0.0);
+    float light = saturate(pc.ambient + diff);
+    float3 color = srgb2rgb(colors[texel].rgb) * light;
+    colors[texel] = float4(rgb2srgb(color), 1.0);
+}
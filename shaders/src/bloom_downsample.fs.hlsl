@@ -0,0 +1,71 @@
+// Downsamples the input to the next level of the bloom chain with the 13 tap filter from
+// "Next Generation Post Processing in Call of Duty: Advanced Warfare" by Jorge Jimenez.
+// The first level also removes everything below the bloom threshold.
+
+struct PS_INPUT {
+    [[vk::location(0)]] float2 UV : UV0;
+};
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+Texture2D<float4> input_image;
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+SamplerState smp;
+
+[[vk::push_constant]]
+struct PC {
+    // Brightness above which pixels contribute to the bloom.
+    float threshold;
+    // Width of the smooth transition around the threshold.
+    float knee;
+    // Nonzero if the threshold should be applied, only for the first level.
+    uint prefilter;
+} pc;
+
+// Largest value that fits in the half float bloom targets.
+static const float MAX_VALUE = 65504.0;
+
+float3 sample_at(float2 uv, float2 texel, float2 offset) {
+    return input_image.SampleLevel(smp, uv + texel * offset, 0).rgb;
+}
+
+// Keeps the part of a color above the threshold, with a quadratic curve around it.
+float3 apply_threshold(float3 color) {
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - pc.threshold + pc.knee, 0.0, 2.0 * pc.knee);
+    soft = soft * soft / (4.0 * pc.knee + 1e-5);
+    float contribution = max(soft, brightness - pc.threshold) / max(brightness, 1e-5);
+    return color * contribution;
+}
+
+float4 main(in PS_INPUT input) : SV_TARGET {
+    uint width, height;
+    input_image.GetDimensions(width, height);
+    float2 texel = 1.0 / float2(width, height);
+    float2 uv = input.UV;
+
+    float3 a = sample_at(uv, texel, float2(-2.0, -2.0));
+    float3 b = sample_at(uv, texel, float2(0.0, -2.0));
+    float3 c = sample_at(uv, texel, float2(2.0, -2.0));
+    float3 d = sample_at(uv, texel, float2(-2.0, 0.0));
+    float3 e = sample_at(uv, texel, float2(0.0, 0.0));
+    float3 f = sample_at(uv, texel, float2(2.0, 0.0));
+    float3 g = sample_at(uv, texel, float2(-2.0, 2.0));
+    float3 h = sample_at(uv, texel, float2(0.0, 2.0));
+    float3 i = sample_at(uv, texel, float2(2.0, 2.0));
+    float3 j = sample_at(uv, texel, float2(-1.0, -1.0));
+    float3 k = sample_at(uv, texel, float2(1.0, -1.0));
+    float3 l = sample_at(uv, texel, float2(-1.0, 1.0));
+    float3 m = sample_at(uv, texel, float2(1.0, 1.0));
+
+    float3 color = e * 0.125;
+    color += (a + c + g + i) * 0.03125;
+    color += (b + d + f + h) * 0.0625;
+    color += (j + k + l + m) * 0.125;
+    if (pc.prefilter != 0) {
+        color = apply_threshold(color);
+    }
+    // Keep invalid or extreme values in the scene from spreading over the whole image.
+    color = clamp(color, 0.0, MAX_VALUE);
+    return float4(color, 0.0);
+}
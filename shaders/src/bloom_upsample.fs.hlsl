@@ -0,0 +1,40 @@
+// Upsamples a level of the bloom chain with a 3x3 tent filter. The result is blended
+// additively into the next larger level, or into the scene for the last step.
+
+struct PS_INPUT {
+    [[vk::location(0)]] float2 UV : UV0;
+};
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+Texture2D<float4> input_image;
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+SamplerState smp;
+
+[[vk::push_constant]]
+struct PC {
+    // Multiplier on the upsampled color.
+    float intensity;
+} pc;
+
+float3 sample_at(float2 uv, float2 texel, float2 offset) {
+    return input_image.SampleLevel(smp, uv + texel * offset, 0).rgb;
+}
+
+float4 main(in PS_INPUT input) : SV_TARGET {
+    uint width, height;
+    input_image.GetDimensions(width, height);
+    float2 texel = 1.0 / float2(width, height);
+    float2 uv = input.UV;
+
+    float3 color = sample_at(uv, texel, float2(0.0, 0.0)) * 4.0;
+    color += sample_at(uv, texel, float2(-1.0, 0.0)) * 2.0;
+    color += sample_at(uv, texel, float2(1.0, 0.0)) * 2.0;
+    color += sample_at(uv, texel, float2(0.0, -1.0)) * 2.0;
+    color += sample_at(uv, texel, float2(0.0, 1.0)) * 2.0;
+    color += sample_at(uv, texel, float2(-1.0, -1.0));
+    color += sample_at(uv, texel, float2(1.0, -1.0));
+    color += sample_at(uv, texel, float2(-1.0, 1.0));
+    color += sample_at(uv, texel, float2(1.0, 1.0));
+    return float4(color / 16.0 * pc.intensity, 0.0);
+}
@@ -0,0 +1,63 @@
+#include "border.hlsl"
+#include "color_space.hlsl"
+#include "terrain_uv.hlsl"
+
+// The color map holds sRGB encoded data, but sRGB formats cannot be used as storage images.
+// We read and write the raw encoded values and convert to linear space for blending.
+[[vk::binding(0, 0), vk::image_format("rgba8")]]
+RWTexture2D<float4> colors;
+
+// Slope of the terrain in radians, see DerivedMaps.
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+Texture2D<half> slope_map;
+
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+SamplerState smp;
+
+[[vk::push_constant]] struct PC {
+    // Texel the brush is centered on
+    int2 center;
+    float weight;
+    uint size;
+    // Linear RGB color to paint with
+    float4 color;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+    // Steepest slope that is painted on, in radians
+    float max_slope;
+} pc;
+
+// Range of slopes in radians over which the brush fades out towards max_slope.
+static const float SLOPE_FADE = 0.05;
+
+bool inside_patch_rect(int2 center, int2 offset) {
+    return abs(offset.x) <= pc.size / 2 && abs(offset.y) <= pc.size / 2;
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    colors.GetDimensions(w, h);
+    int2 center = pc.center;
+    int2 offset = int2(GlobalInvocationID.xy) - int(pc.size / 2);
+    int2 texel;
+    if (!brush_texel(center + offset, uint2(w, h), pc.border_mode, texel)) {
+        return;
+    }
+
+    if (!inside_patch_rect(center, offset)) {
+        return;
+    }
+
+    float max_distance = pc.size / 2.0;
+    float distance_ratio = min(1.0, length(float2(offset)) / max_distance);
+    // Paint fully in the center of the brush, and fade out towards the edge
+    float falloff = 1.0 - smoothstep(0.5, 1.0, distance_ratio);
+    // The slope map may differ in size from the color map, so it is sampled in uv space
+    float slope = slope_map.SampleLevel(smp, texel_center_uv(texel, uint2(w, h)), 0.0);
+    float slope_mask = 1.0 - smoothstep(pc.max_slope - SLOPE_FADE, pc.max_slope, slope);
+    float amount = saturate(falloff * slope_mask * pc.weight);
+    float3 current = srgb2rgb(colors[texel].rgb);
+    float3 painted = lerp(current, pc.color.rgb, amount);
+    colors[texel] = float4(rgb2srgb(painted), 1.0);
+}
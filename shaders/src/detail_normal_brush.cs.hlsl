@@ -0,0 +1,71 @@
+#include "border.hlsl"
+
+// Paints fine bumps into the detail normal map by blending in the slope of a value noise
+// pattern. With a negative weight, the normals are flattened back instead.
+[[vk::binding(0, 0), vk::image_format("rgba8")]]
+RWTexture2D<float4> detail_normals;
+
+[[vk::push_constant]] struct PC {
+    // Texel the brush is centered on
+    int2 center;
+    float weight;
+    uint size;
+    // Steepness of the painted bumps
+    float strength;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+} pc;
+
+// Size of a single bump, in texels
+static const float BUMP_SIZE = 4.0;
+
+float hash(float2 p) {
+    return frac(sin(dot(p, float2(127.1, 311.7))) * 43758.5453);
+}
+
+float value_noise(float2 p) {
+    float2 cell = floor(p);
+    float2 f = frac(p);
+    float2 u = f * f * (3.0 - 2.0 * f);
+    float a = hash(cell);
+    float b = hash(cell + float2(1.0, 0.0));
+    float c = hash(cell + float2(0.0, 1.0));
+    float d = hash(cell + float2(1.0, 1.0));
+    return lerp(lerp(a, b, u.x), lerp(c, d, u.x), u.y);
+}
+
+bool inside_patch_rect(int2 center, int2 offset) {
+    return abs(offset.x) <= pc.size / 2 && abs(offset.y) <= pc.size / 2;
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    detail_normals.GetDimensions(w, h);
+    int2 center = pc.center;
+    int2 offset = int2(GlobalInvocationID.xy) - int(pc.size / 2);
+    int2 texel;
+    if (!brush_texel(center + offset, uint2(w, h), pc.border_mode, texel)) {
+        return;
+    }
+
+    if (!inside_patch_rect(center, offset)) {
+        return;
+    }
+
+    float max_distance = pc.size / 2.0;
+    float distance_ratio = min(1.0, length(float2(offset)) / max_distance);
+    float falloff = 1.0 - smoothstep(0.5, 1.0, distance_ratio);
+    float amount = saturate(falloff * abs(pc.weight));
+
+    float3 current = detail_normals[texel].rgb * 2.0 - float3(1.0, 1.0, 1.0);
+    float3 target = float3(0.0, 0.0, 1.0);
+    if (pc.weight > 0.0) {
+        float2 p = float2(texel) / BUMP_SIZE;
+        float dx = value_noise(p + float2(0.5, 0.0)) - value_noise(p - float2(0.5, 0.0));
+        float dy = value_noise(p + float2(0.0, 0.5)) - value_noise(p - float2(0.0, 0.5));
+        target = normalize(float3(-dx * pc.strength, -dy * pc.strength, 1.0));
+    }
+    float3 painted = normalize(lerp(current, target, amount));
+    detail_normals[texel] = float4(painted * 0.5 + 0.5, 0.0);
+}
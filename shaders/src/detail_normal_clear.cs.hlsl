@@ -0,0 +1,13 @@
+[[vk::binding(0, 0), vk::image_format("rgba8")]]
+RWTexture2D<float4> detail_normals;
+
+[numthreads(32, 32, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint width, height;
+    detail_normals.GetDimensions(width, height);
+    if (GlobalInvocationID.x >= width || GlobalInvocationID.y >= height) {
+        return;
+    }
+    // An unperturbed tangent space normal points straight up, remapped to [0, 1]
+    detail_normals[GlobalInvocationID.xy] = float4(0.5, 0.5, 1.0, 0.0);
+}
@@ -0,0 +1,47 @@
+#include "border.hlsl"
+
+[[vk::binding(0, 0), vk::image_format("r32f")]]
+RWTexture2D<float> tex;
+
+// Local mean height of every texel in the brush patch, written by equalize_mean.cs.hlsl
+[[vk::binding(1, 0), vk::image_format("r32f")]]
+RWTexture2D<float> means;
+
+[[vk::push_constant]] struct PC {
+    // Texel the brush is centered on
+    int2 center;
+    uint size;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+    // Fraction of the way each texel moves towards its local mean, negative when inverted
+    float weight;
+} pc;
+
+bool inside_patch_rect(int2 center, int2 offset) {
+    return abs(offset.x) <= pc.size / 2 && abs(offset.y) <= pc.size / 2;
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint width, height;
+    tex.GetDimensions(width, height);
+    int2 center = pc.center;
+    int2 offset = int2(GlobalInvocationID.xy) - int(pc.size / 2);
+    int2 texel;
+    if (!brush_texel(center + offset, uint2(width, height), pc.border_mode, texel)) {
+        return;
+    }
+
+    if (!inside_patch_rect(center, offset)) {
+        return;
+    }
+
+    float mean = means.Load(GlobalInvocationID.xy);
+    // Full strength in the inner half of the brush, fading out towards its radius
+    float distance_ratio = length(float2(offset)) / max(pc.size / 2.0, 1.0);
+    float falloff = 1.0 - smoothstep(0.5, 1.0, distance_ratio);
+    // Never overshoot the mean, and never push away by more than the distance to it
+    float amount = clamp(pc.weight * falloff, -1.0, 1.0);
+    float h = tex.Load(texel);
+    tex[texel] = h + (mean - h) * amount;
+}
@@ -0,0 +1,81 @@
+#include "border.hlsl"
+
+// Computes the local mean height of every texel in the brush patch. The brush itself blends
+// towards these means in equalize_brush.cs.hlsl. Keeping both in separate dispatches means no
+// texel reads a neighbour that was already equalized.
+
+[[vk::binding(0, 0), vk::image_format("r32f")]]
+RWTexture2D<float> tex;
+
+// One texel per invocation of the brush dispatch
+[[vk::binding(1, 0), vk::image_format("r32f")]]
+RWTexture2D<float> means;
+
+[[vk::push_constant]] struct PC {
+    // Texel the brush is centered on
+    int2 center;
+    uint size;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+} pc;
+
+bool inside_texture(int2 texel, uint width, uint height) {
+    return texel.x >= 0 && texel.y >= 0 && texel.x < int(width) && texel.y < int(height);
+}
+
+// Number of samples along each axis of the averaging kernel.
+static const int KERNEL_SAMPLES = 9;
+static const float SIGMA = float(KERNEL_SAMPLES) * 0.25;
+
+// Unnormalized, the mean is divided by the total weight of the samples it used anyway.
+float gaussian(float2 i) {
+    i = i / SIGMA;
+    return exp(-0.5 * dot(i, i));
+}
+
+// Offset of kernel sample i from the center of the kernel, in samples.
+float2 kernel_direction(int i) {
+    return float2(i % KERNEL_SAMPLES, i / KERNEL_SAMPLES) - float(KERNEL_SAMPLES - 1) / 2.0;
+}
+
+// Weighted mean height around a texel. The kernel spans a quarter of the brush diameter, so
+// larger brushes smooth out larger features.
+// When clamping, samples outside of the texture are skipped and the result is normalized by the
+// weight of the valid samples only, so the brush does not pull the edges of the terrain towards
+// zero. The other border modes read the texel the sample maps to.
+float local_mean(int2 texel, uint width, uint height) {
+    float spacing = max(float(pc.size) / float(4 * KERNEL_SAMPLES), 1.0);
+    float output = 0.0;
+    float accum = 0.0;
+    for (int i = 0; i < KERNEL_SAMPLES * KERNEL_SAMPLES; ++i) {
+        float2 direction = kernel_direction(i);
+        int2 sample_texel = texel + int2(round(direction * spacing));
+        if (pc.border_mode != BORDER_CLAMP) {
+            sample_texel = border_texel(sample_texel, uint2(width, height), pc.border_mode);
+        } else if (!inside_texture(sample_texel, width, height)) {
+            continue;
+        }
+        float weight = gaussian(direction);
+        output += tex.Load(sample_texel) * weight;
+        accum += weight;
+    }
+    // The kernel center is always inside the texture, so accum is never zero.
+    return output / accum;
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint width, height;
+    tex.GetDimensions(width, height);
+    uint means_width, means_height;
+    means.GetDimensions(means_width, means_height);
+    if (GlobalInvocationID.x >= means_width || GlobalInvocationID.y >= means_height) {
+        return;
+    }
+    int2 offset = int2(GlobalInvocationID.xy) - int(pc.size / 2);
+    int2 texel;
+    if (!brush_texel(pc.center + offset, uint2(width, height), pc.border_mode, texel)) {
+        return;
+    }
+    means[GlobalInvocationID.xy] = local_mean(texel, width, height);
+}
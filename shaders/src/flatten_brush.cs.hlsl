@@ -0,0 +1,51 @@
+#include "border.hlsl"
+#include "weight_function.hlsl"
+
+[[vk::binding(0, 0), vk::image_format("r32f")]]
+RWTexture2D<float> heights;
+
+[[vk::push_constant]] struct PC {
+    // Texel the brush is centered on
+    int2 center;
+    float weight;
+    uint size;
+    // Parameter of the falloff curve, see weight_function.hlsl
+    float weight_param1;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+    // Height the terrain is moved towards
+    float target_height;
+    // Falloff curve, see weight_function.hlsl
+    uint weight_kind;
+} pc;
+
+float calculate_weight(float distance) {
+    float max_distance = pc.size / 2.0;
+    return weight_function(pc.weight_kind, pc.weight_param1, distance / max_distance);
+}
+
+bool inside_patch_rect(int2 center, int2 offset) {
+    return abs(offset.x) <= pc.size / 2 && abs(offset.y) <= pc.size / 2;
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    heights.GetDimensions(w, h);
+    int2 center = pc.center;
+    int2 offset = int2(GlobalInvocationID.xy) - int(pc.size / 2);
+    int2 texel;
+    if (!brush_texel(center + offset, uint2(w, h), pc.border_mode, texel)) {
+        return;
+    }
+
+    if (!inside_patch_rect(center, offset)) {
+        return;
+    }
+
+    float dist = length(float2(offset));
+    // Never overshoot the target, no matter how strong the brush is
+    float amount = saturate(calculate_weight(dist) * pc.weight);
+    float height = heights.Load(int3(texel, 0));
+    heights[texel] = lerp(height, pc.target_height, amount);
+}
@@ -0,0 +1,152 @@
+// FXAA over the tonemapped image, based on the quality variant of FXAA 3.11 by Timothy Lottes.
+// The input is sampled with a linear sampler, so offset texture reads blend neighbouring pixels.
+
+struct PS_INPUT {
+    [[vk::location(0)]] float2 UV : UV0;
+};
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+Texture2D<float4> ldr_input;
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+SamplerState smp;
+
+[[vk::push_constant]]
+struct PC {
+    // Amount of sub-pixel aliasing removed, from 0 (sharp) to 1 (soft).
+    float subpix;
+    // Minimum local contrast relative to the brightest neighbour required to process a pixel.
+    float edge_threshold;
+    // Minimum absolute local contrast required to process a pixel, skips dark areas.
+    float edge_threshold_min;
+    // Amount of steps taken in both directions when searching for the end of an edge.
+    uint search_steps;
+} pc;
+
+// Perceptual luma of a linear color, the input texture is sRGB so sampling it returns linear values.
+float luma(float3 rgb) {
+    return sqrt(dot(rgb, float3(0.299, 0.587, 0.114)));
+}
+
+float luma_at(float2 uv) {
+    return luma(ldr_input.SampleLevel(smp, uv, 0).rgb);
+}
+
+// Distance between two steps of the edge search, the search speeds up further away from the pixel.
+float search_step(uint i) {
+    if (i < 4) return 1.0;
+    if (i < 8) return 2.0;
+    return 4.0;
+}
+
+float4 main(in PS_INPUT input) : SV_TARGET {
+    uint w, h;
+    ldr_input.GetDimensions(w, h);
+    float2 texel = 1.0 / float2(w, h);
+    float2 uv = input.UV;
+    float3 color = ldr_input.SampleLevel(smp, uv, 0).rgb;
+
+    // Local contrast of the direct neighbours, skip pixels that are not on an edge
+    float luma_center = luma(color);
+    float luma_n = luma_at(uv + float2(0.0, -texel.y));
+    float luma_s = luma_at(uv + float2(0.0, texel.y));
+    float luma_w = luma_at(uv + float2(-texel.x, 0.0));
+    float luma_e = luma_at(uv + float2(texel.x, 0.0));
+    float luma_min = min(luma_center, min(min(luma_n, luma_s), min(luma_w, luma_e)));
+    float luma_max = max(luma_center, max(max(luma_n, luma_s), max(luma_w, luma_e)));
+    float range = luma_max - luma_min;
+    if (range < max(pc.edge_threshold_min, luma_max * pc.edge_threshold)) {
+        return float4(color, 1.0);
+    }
+
+    float luma_nw = luma_at(uv + float2(-texel.x, -texel.y));
+    float luma_ne = luma_at(uv + float2(texel.x, -texel.y));
+    float luma_sw = luma_at(uv + float2(-texel.x, texel.y));
+    float luma_se = luma_at(uv + float2(texel.x, texel.y));
+
+    float luma_ns = luma_n + luma_s;
+    float luma_we = luma_w + luma_e;
+    float luma_n_corners = luma_nw + luma_ne;
+    float luma_s_corners = luma_sw + luma_se;
+    float luma_w_corners = luma_nw + luma_sw;
+    float luma_e_corners = luma_ne + luma_se;
+
+    // Find whether the edge runs horizontally or vertically
+    float edge_horizontal = abs(-2.0 * luma_w + luma_w_corners)
+        + abs(-2.0 * luma_center + luma_ns) * 2.0
+        + abs(-2.0 * luma_e + luma_e_corners);
+    float edge_vertical = abs(-2.0 * luma_n + luma_n_corners)
+        + abs(-2.0 * luma_center + luma_we) * 2.0
+        + abs(-2.0 * luma_s + luma_s_corners);
+    bool horizontal = edge_horizontal >= edge_vertical;
+
+    // Pick the side of the edge with the steepest gradient
+    float luma1 = horizontal ? luma_n : luma_w;
+    float luma2 = horizontal ? luma_s : luma_e;
+    float gradient1 = luma1 - luma_center;
+    float gradient2 = luma2 - luma_center;
+    bool steepest1 = abs(gradient1) >= abs(gradient2);
+    float gradient_scaled = 0.25 * max(abs(gradient1), abs(gradient2));
+    float step_length = horizontal ? texel.y : texel.x;
+    float luma_local_average;
+    if (steepest1) {
+        step_length = -step_length;
+        luma_local_average = 0.5 * (luma1 + luma_center);
+    } else {
+        luma_local_average = 0.5 * (luma2 + luma_center);
+    }
+
+    // Walk along the edge, halfway between this pixel and the pixel on the other side,
+    // until the luma differs too much from the local average in both directions
+    float2 edge_uv = uv;
+    if (horizontal) {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+    float2 offset = horizontal ? float2(texel.x, 0.0) : float2(0.0, texel.y);
+    float2 uv1 = edge_uv - offset;
+    float2 uv2 = edge_uv + offset;
+    float luma_end1 = luma_at(uv1) - luma_local_average;
+    float luma_end2 = luma_at(uv2) - luma_local_average;
+    bool reached1 = abs(luma_end1) >= gradient_scaled;
+    bool reached2 = abs(luma_end2) >= gradient_scaled;
+    for (uint i = 1; i < pc.search_steps && !(reached1 && reached2); ++i) {
+        float step = search_step(i);
+        if (!reached1) {
+            uv1 -= offset * step;
+            luma_end1 = luma_at(uv1) - luma_local_average;
+            reached1 = abs(luma_end1) >= gradient_scaled;
+        }
+        if (!reached2) {
+            uv2 += offset * step;
+            luma_end2 = luma_at(uv2) - luma_local_average;
+            reached2 = abs(luma_end2) >= gradient_scaled;
+        }
+    }
+
+    // Offset towards the edge based on the distance to the closest end of the edge
+    float distance1 = horizontal ? uv.x - uv1.x : uv.y - uv1.y;
+    float distance2 = horizontal ? uv2.x - uv.x : uv2.y - uv.y;
+    bool closest1 = distance1 < distance2;
+    float edge_length = distance1 + distance2;
+    float pixel_offset = -min(distance1, distance2) / edge_length + 0.5;
+    // Only blend if the luma at the closest end varies in the same direction as this pixel
+    bool center_smaller = luma_center < luma_local_average;
+    bool correct_variation = ((closest1 ? luma_end1 : luma_end2) < 0.0) != center_smaller;
+    float final_offset = correct_variation ? pixel_offset : 0.0;
+
+    // Sub-pixel aliasing, for features smaller than a pixel that the edge search misses
+    float luma_average = (1.0 / 12.0) * (2.0 * (luma_ns + luma_we) + luma_w_corners + luma_e_corners);
+    float subpixel = saturate(abs(luma_average - luma_center) / range);
+    subpixel = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+    final_offset = max(final_offset, subpixel * subpixel * pc.subpix);
+
+    float2 final_uv = uv;
+    if (horizontal) {
+        final_uv.y += final_offset * step_length;
+    } else {
+        final_uv.x += final_offset * step_length;
+    }
+    return float4(ldr_input.SampleLevel(smp, final_uv, 0).rgb, 1.0);
+}
@@ -0,0 +1,22 @@
+struct PS_INPUT {
+    [[vk::location(0)]] float2 UV : UV0;
+};
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+Texture2D<float4> hdr_input;
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+SamplerState smp;
+
+[[vk::push_constant]]
+struct PC {
+    // Exposure adjustment in stops.
+    float exposure;
+} pc;
+
+// Displays raw HDR values without a tonemapping curve, so they can be inspected directly.
+// Values outside of [0, 1] after exposure are clipped.
+float4 main(in PS_INPUT input) : SV_TARGET {
+    float3 color = hdr_input.Sample(smp, input.UV).rgb;
+    return float4(saturate(color * exp2(pc.exposure)), 1.0);
+}
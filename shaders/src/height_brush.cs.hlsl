@@ -1,30 +1,25 @@
-[[vk::binding(0, 0), vk::image_format("r16f")]]
+#include "border.hlsl"
+#include "weight_function.hlsl"
+
+[[vk::binding(0, 0), vk::image_format("r32f")]]
 RWTexture2D<float> heights;
 
 [[vk::push_constant]] struct PC {
-    float2 uv;
+    // Texel the brush is centered on
+    int2 center;
     float weight;
     uint size;
-    // If gaussian, this is sigma
+    // Parameter of the falloff curve, see weight_function.hlsl
     float weight_param1;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+    // Falloff curve, see weight_function.hlsl
+    uint weight_kind;
 } pc;
 
-static const float PI = 3.1415926535;
-
-// returns the weight for the brush in function of x in [0..1]
-float weight_function(float x) {
-    // Gaussian
-    float sigma = pc.weight_param1;
-    static const float SQRT2PI = 2.50662827463;
-    float w = 1.0 / (sigma * SQRT2PI);
-    float p = (x / sigma) * (x / sigma);
-    return w * exp(-0.5 * p);
-}
-
 float calculate_weight(float distance) {
     float max_distance = pc.size / 2.0;
-    float distance_ratio = min(1.0, distance / max_distance);
-    return weight_function(distance_ratio);
+    return weight_function(pc.weight_kind, pc.weight_param1, distance / max_distance);
 }
 
 bool inside_patch_rect(int2 center, int2 offset) {
@@ -35,10 +30,10 @@ bool inside_patch_rect(int2 center, int2 offset) {
 void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
     uint w, h;
     heights.GetDimensions(w, h);
-    int2 center = int2(float2(w, h) * pc.uv);
+    int2 center = pc.center;
     int2 offset = int2(GlobalInvocationID.xy) - int(pc.size / 2);
-    int2 texel = center + offset;
-    if (texel.x < 0 || texel.y < 0 || texel.x >= w || texel.y >= h) {
+    int2 texel;
+    if (!brush_texel(center + offset, uint2(w, h), pc.border_mode, texel)) {
         return;
     }
 
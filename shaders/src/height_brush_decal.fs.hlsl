@@ -1,16 +1,5 @@
 #include "decal.hlsl"
-
-static const float PI = 3.1415926535;
-
-// returns the weight for the brush in function of x in [0..1]
-float weight_function(float x) {
-    // Gaussian
-    float sigma = pc.data[0];
-    static const float SQRT2PI = 2.50662827463;
-    float w = 1.0 / (sigma * SQRT2PI);
-    float p = (x / sigma) * (x / sigma);
-    return w * exp(-0.5 * p);
-}
+#include "weight_function.hlsl"
 
 float4 main(PS_INPUT input, float4 frag_pos
 
@@ -23,8 +12,9 @@ if (distance >= 1.0) {
 return float4(0.0, 0.0, 0.0, 0.0);
 }
 
-// We will use our weight function to color the decal
-float weight = weight_function(distance);
+// We will use our weight function to color the decal. The parameter of the falloff curve
+// is in data[0], the curve itself in data[1].
+float weight = weight_function(uint(pc.data[1]), pc.data[0], distance);
 return float4(1.0, 0.0, 0.0, 1.0) *
 weight;
 }
\ No newline at end of file
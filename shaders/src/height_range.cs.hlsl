@@ -0,0 +1,30 @@
+// Computes the minimum and maximum height of a heightmap.
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+Texture2D<half> heightmap;
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+SamplerState smp;
+
+// Index 0 holds the minimum, index 1 the maximum. Heights are stored as order preserving
+// integers so they can be reduced with integer atomics.
+[[vk::binding(1, 0)]]
+RWStructuredBuffer<uint> range;
+
+uint order_preserving(float value) {
+    uint bits = asuint(value);
+    // Negative floats are ordered backwards, so flip all bits. For positive floats, setting
+    // the sign bit places them above all negative values.
+    return (bits & 0x80000000) != 0 ? ~bits : (bits | 0x80000000);
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint width, height;
+    heightmap.GetDimensions(width, height);
+    if (GlobalInvocationID.x >= width || GlobalInvocationID.y >= height) {
+        return;
+    }
+    uint value = order_preserving(heightmap.Load(int3(GlobalInvocationID.xy, 0)));
+    InterlockedMin(range[0], value);
+    InterlockedMax(range[1], value);
+}
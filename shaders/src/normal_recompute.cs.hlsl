@@ -1,3 +1,7 @@
+#include "border.hlsl"
+#include "terrain_normal.hlsl"
+#include "terrain_uv.hlsl"
+
 [[vk::binding(0, 0)]]
 RWTexture2D<float4> normals;
 
@@ -8,15 +12,15 @@ Texture2D<half> heightmap;
 SamplerState smp;
 
 [[vk::push_constant]] struct PC {
-    float2 uv;
+    NormalParams normal;
+    // Texel the brush is centered on
+    int2 center;
     uint size;
 } pc;
 
 float sample_height(int x, int y, uint width, uint height) {
-    x = (float) clamp(x, 0, width);
-    y = (float) clamp(y, 0, height);
-    float2 uv = float2(x, y) / float2((float) width, (float) height);
-    return heightmap.SampleLevel(smp, uv, 0.0);
+    int2 texel = border_texel(int2(x, y), uint2(width, height), pc.normal.border_mode);
+    return heightmap.SampleLevel(smp, texel_center_uv(texel, uint2(width, height)), 0.0);
 }
 
 bool inside_patch_rect(int2 center, int2 offset) {
@@ -27,10 +31,10 @@ bool inside_patch_rect(int2 center, int2 offset) {
 void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
     uint width, height;
     normals.GetDimensions(width, height);
-    int2 center = int2(float2(width, height) * pc.uv);
+    int2 center = pc.center;
     int2 offset = int2(GlobalInvocationID.xy) - int(pc.size / 2);
-    int2 texel = center + offset;
-    if (texel.x < 0 || texel.y < 0 || texel.x >= width || texel.y >= height) {
+    int2 texel;
+    if (!brush_texel(center + offset, uint2(width, height), pc.normal.border_mode, texel)) {
         return;
     }
 
@@ -48,26 +52,5 @@ void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
     }
 
     // Now that we have our height samples, we can calculate the normal
-    float3 normal = (float3) 0;
-    normal.x =
-        heights[0][0]
-        - heights[2][0]
-        + 2.0f * heights[0][1]
-        - 2.0f * heights[2][1]
-        + heights[0][2]
-        - heights[2][2];
-    normal.z =
-        heights[0][0]
-        + 2.0f * heights[1][0]
-        + heights[2][0]
-        - heights[0][2]
-        - 2.0f * heights[1][2]
-        - heights[2][2];
-
-    // original code has 0.25 sqrt(1 - x * x - z * z) here, which leads to NaNs
-    normal.y = 0.25;
-    normal = normalize(normal * float3(2.0, 1.0, 2.0));
-    // Remap normal from -1, 1 to [0, 1] before storing
-    normal = (normal + float3(1.0, 1.0, 1.0)) / 2.0;
-    normals[texel] = float4(normal, 0.0);
+    normals[texel] = encode_normal(sobel_normal(heights, pc.normal));
 }
\ No newline at end of file
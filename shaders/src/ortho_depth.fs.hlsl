@@ -0,0 +1,25 @@
+// Converts the depth of an orthographic projection to the depth a perspective projection with the
+// same near and far planes writes for the same point, since FSR2 expects the latter.
+// Kept in sync with `Projection::perspective_depth` in the camera crate.
+
+struct PS_INPUT {
+    [[vk::location(0)]] float2 UV : UV0;
+};
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+Texture2D<float> depth;
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+SamplerState smp;
+
+[[vk::push_constant]]
+struct PC {
+    float near;
+    float far;
+} pc;
+
+float main(in PS_INPUT input) : SV_TARGET {
+    // Orthographic depth is linear between the near and far plane
+    float ortho_depth = depth.SampleLevel(smp, input.UV, 0);
+    return pc.far * ortho_depth / (pc.near + ortho_depth * (pc.far - pc.near));
+}
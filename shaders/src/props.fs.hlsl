@@ -0,0 +1,37 @@
+struct PS_INPUT {
+    [[vk::location(0)]] float3 WorldPos : POS0;
+    [[vk::location(1)]] float4 ClipPos : POS1;
+    [[vk::location(2)]] float4 PrevClipPos : POS2;
+};
+
+struct PS_OUTPUT {
+    [[vk::location(0)]] float4 Color : SV_Target0;
+    [[vk::location(1)]] float2 Motion : SV_Target1;
+};
+
+[[vk::binding(0, 0)]]
+cbuffer Camera {
+    float4x4 projection_view;
+    float4x4 prev_pv;
+    float4 cam_position;
+    float4 sun_dir;
+};
+
+[[vk::push_constant]]
+struct PC {
+    float4 color;
+} pc;
+
+PS_OUTPUT main(PS_INPUT input) {
+    PS_OUTPUT output = (PS_OUTPUT) 0;
+    // The prop meshes have no normals, so they are flat shaded with the normal of the triangle.
+    float3 normal = normalize(cross(ddx(input.WorldPos), ddy(input.WorldPos)));
+    // Both sides of a triangle are drawn, so make the normal face the camera
+    if (dot(normal, cam_position.xyz - input.WorldPos) < 0.0) {
+        normal = -normal;
+    }
+    float diff = max(dot(normal, -sun_dir.xyz), 0.0);
+    output.Color = float4(pc.color.rgb * diff, 1.0);
+    output.Motion = input.PrevClipPos.xy / input.PrevClipPos.w - input.ClipPos.xy / input.ClipPos.w;
+    return output;
+}
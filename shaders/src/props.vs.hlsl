@@ -0,0 +1,37 @@
+struct VSInput {
+    [[vk::location(0)]] float3 Position : POSITION0;
+    // Columns of the transform of the prop
+    [[vk::location(1)]] float4 Transform0 : TRANSFORM0;
+    [[vk::location(2)]] float4 Transform1 : TRANSFORM1;
+    [[vk::location(3)]] float4 Transform2 : TRANSFORM2;
+    [[vk::location(4)]] float4 Transform3 : TRANSFORM3;
+};
+
+struct VSOutput {
+    float4 Position : SV_POSITION;
+    [[vk::location(0)]] float3 WorldPos : POS0;
+    [[vk::location(1)]] float4 ClipPos : POS1;
+    [[vk::location(2)]] float4 PrevClipPos : POS2;
+};
+
+[[vk::binding(0, 0)]]
+cbuffer Camera {
+    float4x4 projection_view;
+    float4x4 prev_pv;
+    float4 cam_position;
+    float4 sun_dir;
+};
+
+VSOutput main(VSInput input) {
+    // The constructor takes rows, so the columns are transposed into place
+    float4x4 transform = transpose(float4x4(input.Transform0, input.Transform1, input.Transform2, input.Transform3));
+    float4 world_pos = mul(transform, float4(input.Position, 1.0));
+
+    VSOutput output = (VSOutput) 0;
+    output.Position = mul(projection_view, world_pos);
+    output.WorldPos = world_pos.xyz;
+    // Props do not move, so only the camera contributes to the motion vectors
+    output.ClipPos = output.Position;
+    output.PrevClipPos = mul(prev_pv, world_pos);
+    return output;
+}
@@ -0,0 +1,64 @@
+#include "border.hlsl"
+#include "color_space.hlsl"
+
+// The set entry points write the same value to every texel inside the brush circle, fill_height
+// writes it to the entire heightmap. Only the resource used by an entry point ends up in its
+// compiled module.
+
+[[vk::binding(0, 0), vk::image_format("r32f")]]
+RWTexture2D<float> heights;
+
+// The color map holds sRGB encoded data, see color_brush.cs.hlsl
+[[vk::binding(0, 0), vk::image_format("rgba8")]]
+RWTexture2D<float4> colors;
+
+[[vk::push_constant]] struct PC {
+    // Texel the brush is centered on
+    int2 center;
+    uint size;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+    // Height in x for set_height and fill_height, linear RGB color for set_color
+    float4 value;
+} pc;
+
+// Finds the texel to write for this invocation, returns false if nothing should be written.
+bool brush_circle_texel(uint3 id, uint2 size, out int2 texel) {
+    int2 offset = int2(id.xy) - int(pc.size / 2);
+    if (!brush_texel(pc.center + offset, size, pc.border_mode, texel)) {
+        return false;
+    }
+    return length(float2(offset)) <= pc.size / 2.0;
+}
+
+[numthreads(16, 16, 1)]
+void set_height(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    heights.GetDimensions(w, h);
+    int2 texel;
+    if (!brush_circle_texel(GlobalInvocationID, uint2(w, h), texel)) {
+        return;
+    }
+    heights[texel] = pc.value.x;
+}
+
+[numthreads(16, 16, 1)]
+void set_color(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    colors.GetDimensions(w, h);
+    int2 texel;
+    if (!brush_circle_texel(GlobalInvocationID, uint2(w, h), texel)) {
+        return;
+    }
+    colors[texel] = float4(rgb2srgb(pc.value.rgb), 1.0);
+}
+
+[numthreads(16, 16, 1)]
+void fill_height(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    heights.GetDimensions(w, h);
+    if (GlobalInvocationID.x >= w || GlobalInvocationID.y >= h) {
+        return;
+    }
+    heights[GlobalInvocationID.xy] = pc.value.x;
+}
@@ -0,0 +1,63 @@
+#include "border.hlsl"
+
+// Paints the weight of one material layer into the splat map. The weights of a texel are
+// renormalized after painting, so they keep summing to one. With a negative weight, the
+// layer is removed in favor of the other layers instead.
+[[vk::binding(0, 0), vk::image_format("rgba8")]]
+RWTexture2D<float4> splat_weights;
+
+[[vk::push_constant]] struct PC {
+    // Texel the brush is centered on
+    int2 center;
+    float weight;
+    uint size;
+    // Layer to paint, one of the four channels
+    uint channel;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+} pc;
+
+bool inside_patch_rect(int2 center, int2 offset) {
+    return abs(offset.x) <= pc.size / 2 && abs(offset.y) <= pc.size / 2;
+}
+
+float weight_sum(float4 weights) {
+    return dot(weights, float4(1.0, 1.0, 1.0, 1.0));
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    splat_weights.GetDimensions(w, h);
+    int2 center = pc.center;
+    int2 offset = int2(GlobalInvocationID.xy) - int(pc.size / 2);
+    int2 texel;
+    if (!brush_texel(center + offset, uint2(w, h), pc.border_mode, texel)) {
+        return;
+    }
+
+    if (!inside_patch_rect(center, offset)) {
+        return;
+    }
+
+    float max_distance = pc.size / 2.0;
+    float distance_ratio = min(1.0, length(float2(offset)) / max_distance);
+    float falloff = 1.0 - smoothstep(0.5, 1.0, distance_ratio);
+    float amount = saturate(falloff * abs(pc.weight));
+
+    float4 current = splat_weights[texel];
+    float4 layer = float4(pc.channel == 0, pc.channel == 1, pc.channel == 2, pc.channel == 3);
+    float4 target = layer;
+    if (pc.weight < 0.0) {
+        // Spread the weight of the layer over the other layers in their current proportion.
+        // If no other layer is present, there is nothing to replace the layer with.
+        float4 others = current * (1.0 - layer);
+        float others_sum = weight_sum(others);
+        target = others_sum > 0.0 ? others / others_sum : current;
+    }
+    float4 painted = lerp(current, target, amount);
+    float sum = weight_sum(painted);
+    // Weights are stored with 8 bits, so they only sum to one approximately. Renormalizing
+    // keeps the error from building up over a stroke.
+    splat_weights[texel] = sum > 0.0 ? painted / sum : layer;
+}
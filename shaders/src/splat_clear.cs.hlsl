@@ -0,0 +1,13 @@
+[[vk::binding(0, 0), vk::image_format("rgba8")]]
+RWTexture2D<float4> splat_weights;
+
+[numthreads(32, 32, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint width, height;
+    splat_weights.GetDimensions(width, height);
+    if (GlobalInvocationID.x >= width || GlobalInvocationID.y >= height) {
+        return;
+    }
+    // All weight goes to the first layer, which is the diffuse map
+    splat_weights[GlobalInvocationID.xy] = float4(1.0, 0.0, 0.0, 0.0);
+}
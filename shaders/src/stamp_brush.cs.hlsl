@@ -0,0 +1,60 @@
+#include "border.hlsl"
+
+[[vk::binding(0, 0), vk::image_format("r32f")]]
+RWTexture2D<float> heights;
+
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+Texture2D<float> stamp;
+
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+SamplerState smp;
+
+[[vk::push_constant]] struct PC {
+    // Texel the brush is centered on
+    int2 center;
+    // Negative when inverted, which subtracts the stamp instead
+    float weight;
+    uint size;
+    // Border mode of the terrain, see border.hlsl
+    uint border_mode;
+    // Rotation of the stamp around the brush center, in radians
+    float rotation;
+    // Size of the stamp relative to the brush diameter
+    float scale;
+    // Size of the area the stamp covers, this is larger than size when scaled up
+    uint extent;
+} pc;
+
+bool inside_patch_rect(int2 center, int2 offset) {
+    return abs(offset.x) <= pc.extent / 2 && abs(offset.y) <= pc.extent / 2;
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint w, h;
+    heights.GetDimensions(w, h);
+    int2 center = pc.center;
+    int2 offset = int2(GlobalInvocationID.xy) - int(pc.extent / 2);
+    int2 texel;
+    if (!brush_texel(center + offset, uint2(w, h), pc.border_mode, texel)) {
+        return;
+    }
+
+    if (!inside_patch_rect(center, offset)) {
+        return;
+    }
+
+    // Position of the texel in the brush area, in [-1, 1] along both axes, rotated and scaled
+    // into the space of the stamp.
+    float2 p = float2(offset) / max(pc.size / 2.0, 1.0);
+    float s = sin(-pc.rotation);
+    float c = cos(-pc.rotation);
+    p = float2(c * p.x - s * p.y, s * p.x + c * p.y) / max(pc.scale, 0.0001);
+    float2 uv = p * 0.5 + 0.5;
+    if (any(uv < 0.0) || any(uv > 1.0)) {
+        return;
+    }
+
+    float value = stamp.SampleLevel(smp, uv, 0.0);
+    heights[texel] = heights.Load(int3(texel, 0)) + value * pc.weight;
+}
@@ -0,0 +1,83 @@
+// Temporal anti-aliasing. Blends the jittered frame into a history of previous frames, which is
+// reprojected with the motion vectors and clamped to the neighbourhood of the current pixel to
+// reject stale history.
+
+struct PS_INPUT {
+    [[vk::location(0)]] float2 UV : UV0;
+};
+
+struct PS_OUTPUT {
+    // History for the next frame.
+    float4 History : SV_TARGET0;
+    // Resolved image, passed on to the rest of the post-processing chain.
+    float4 Output : SV_TARGET1;
+};
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+Texture2D<float4> color_image;
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+SamplerState color_smp;
+
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+Texture2D<float4> history_image;
+
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+SamplerState history_smp;
+
+[[vk::combinedImageSampler, vk::binding(2, 0)]]
+Texture2D<float2> motion_image;
+
+[[vk::combinedImageSampler, vk::binding(2, 0)]]
+SamplerState motion_smp;
+
+[[vk::push_constant]]
+struct PC {
+    // Weight of the current frame in the blended result.
+    float blend;
+    // Nonzero if the history is invalid and must be discarded.
+    uint reset;
+} pc;
+
+float luminance(float3 color) {
+    return dot(color, float3(0.2126, 0.7152, 0.0722));
+}
+
+PS_OUTPUT main(in PS_INPUT input) {
+    PS_OUTPUT output = (PS_OUTPUT) 0;
+    uint width, height;
+    color_image.GetDimensions(width, height);
+    int2 pixel = int2(input.UV * float2(width, height));
+    int2 max_pixel = int2(width, height) - 1;
+
+    float3 current = color_image.Load(int3(pixel, 0)).rgb;
+    float3 neighbourhood_min = current;
+    float3 neighbourhood_max = current;
+    for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            int2 neighbour = clamp(pixel + int2(x, y), int2(0, 0), max_pixel);
+            float3 color = color_image.Load(int3(neighbour, 0)).rgb;
+            neighbourhood_min = min(neighbourhood_min, color);
+            neighbourhood_max = max(neighbourhood_max, color);
+        }
+    }
+
+    // Motion vectors are stored as a difference in NDC, which spans twice the UV range.
+    float2 motion = motion_image.Load(int3(pixel, 0));
+    float2 history_uv = input.UV + motion * 0.5;
+    bool offscreen = any(history_uv < 0.0) || any(history_uv > 1.0);
+
+    float3 result = current;
+    if (pc.reset == 0 && !offscreen) {
+        float3 history = history_image.SampleLevel(history_smp, history_uv, 0).rgb;
+        history = clamp(history, neighbourhood_min, neighbourhood_max);
+        // Weigh samples by inverse luminance so single bright pixels do not flicker.
+        float current_weight = pc.blend / (1.0 + luminance(current));
+        float history_weight = (1.0 - pc.blend) / (1.0 + luminance(history));
+        result = (current * current_weight + history * history_weight) / (current_weight + history_weight);
+    }
+
+    output.History = float4(result, 1.0);
+    output.Output = float4(result, 1.0);
+    return output;
+}
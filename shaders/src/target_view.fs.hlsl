@@ -0,0 +1,49 @@
+// Draws a render target in a displayable form for the target viewer. Depth is linearized and
+// motion vectors are encoded as a color, other targets are shown as they are.
+
+struct PS_INPUT {
+    [[vk::location(0)]] float2 UV : UV0;
+};
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+Texture2D<float4> input_image;
+
+[[vk::combinedImageSampler, vk::binding(0, 0)]]
+SamplerState smp;
+
+static const uint MODE_COLOR = 0;
+static const uint MODE_DEPTH = 1;
+static const uint MODE_MOTION = 2;
+
+[[vk::push_constant]]
+struct PC {
+    // How the input is visualized, one of the MODE_ constants.
+    uint mode;
+    // Camera near plane, used to linearize depth.
+    float near;
+    // Camera far plane, used to linearize depth.
+    float far;
+    // Multiplier on motion vectors, so small motion is visible.
+    float motion_scale;
+    // Non-zero if the depth comes from an orthographic projection.
+    uint orthographic;
+} pc;
+
+float4 main(in PS_INPUT input) : SV_TARGET {
+    float4 value = input_image.SampleLevel(smp, input.UV, 0);
+    if (pc.mode == MODE_DEPTH) {
+        // Orthographic projections already store linear depth.
+        float linear_depth = pc.orthographic != 0
+            ? lerp(pc.near, pc.far, value.r)
+            : pc.near * pc.far / (pc.far - value.r * (pc.far - pc.near));
+        // The depth range is huge, so show it on a logarithmic scale.
+        float shade = log(linear_depth / pc.near) / log(pc.far / pc.near);
+        return float4(shade.xxx, 1.0);
+    }
+    if (pc.mode == MODE_MOTION) {
+        // Still pixels are gray, the red and green channels show horizontal and vertical motion.
+        float2 motion = saturate(value.rg * pc.motion_scale * 0.5 + 0.5);
+        return float4(motion, 0.5, 1.0);
+    }
+    return float4(saturate(value.rgb), 1.0);
+}
@@ -1,3 +1,5 @@
+#include "terrain_uv.hlsl"
+
 [[vk::binding(0, 0)]]
 cbuffer Camera {
     float4x4 projection_view;
@@ -29,6 +31,11 @@ struct PC
 {
     uint tessellation_factor;
     float height_scaling;
+    uint2 viewport_size;
+    float target_edge_length;
+    float detail_tiling;
+    float detail_strength;
+    float2 horizontal_scale;
 } pc;
 
 
@@ -38,6 +45,12 @@ Texture2D<half> heightmap;
 [[vk::combinedImageSampler, vk::binding(1, 0)]]
 SamplerState smp;
 
+[[vk::combinedImageSampler, vk::binding(5, 0)]]
+Texture2D<half> detail_map;
+
+[[vk::combinedImageSampler, vk::binding(5, 0)]]
+SamplerState detail_smp;
+
 [domain("quad")]
 DSOutput main(ConstantsHSOutput input, float2 TessCoord : SV_DomainLocation, const OutputPatch<HSOutput, 4> patch) {
     DSOutput output = (DSOutput) 0;
@@ -50,7 +63,12 @@ DSOutput main(ConstantsHSOutput input, float2 TessCoord : SV_DomainLocation, con
     float2 uv1 = lerp(patch[3].UV, patch[2].UV, TessCoord.x);
     float2 uv = lerp(uv0, uv1, TessCoord.y);
     
-    position.y = heightmap.SampleLevel(smp, uv, 0.0) * pc.height_scaling;
+    uint width, height;
+    heightmap.GetDimensions(width, height);
+    float2 height_uv = terrain_sample_uv(uv, uint2(width, height));
+    position.y = heightmap.SampleLevel(smp, height_uv, 0.0) * pc.height_scaling;
+    // The detail map is sampled with a repeating sampler, so it tiles over the terrain.
+    position.y += detail_map.SampleLevel(detail_smp, uv * pc.detail_tiling, 0.0) * pc.detail_strength;
     output.Position = mul(projection_view, position);
     output.ClipPos = output.Position;
     output.PrevClipPos = mul(prev_pv, position);
@@ -1,44 +1,49 @@
-struct PS_INPUT {
-    [[vk::location(0)]] float2 UV : UV0;
-    [[vk::location(1)]] float4 ClipPos : POS0;
-    [[vk::location(2)]] float4 PrevClipPos: POS1;
-};
-
-struct PS_OUTPUT {
-    [[vk::location(0)]] float4 Color : SV_Target0;
-    [[vk::location(1)]] float2 Motion : SV_Target1;
-};
+#include "terrain_surface.hlsl"
+#include "color_space.hlsl"
 
 [[vk::binding(2, 0)]]
 cbuffer Lighting {
     float4 sun_dir;
 };
 
-[[vk::combinedImageSampler, vk::binding(3, 0)]]
-Texture2D<float4> normal_map;
-
-[[vk::combinedImageSampler, vk::binding(3, 0)]]
-SamplerState smp;
-
 [[vk::combinedImageSampler, vk::binding(4, 0)]]
 Texture2D<float4> diffuse_map;
 
 [[vk::combinedImageSampler, vk::binding(4, 0)]]
 SamplerState color_smp;
 
-PS_OUTPUT main(PS_INPUT input) {
-    PS_OUTPUT output = (PS_OUTPUT) 0;
+// Weights of the material layers, see SplatMap. The first layer is the diffuse map.
+[[vk::combinedImageSampler, vk::binding(8, 0)]]
+Texture2D<float4> splat_map;
 
-    // Assumption: The normal map has the same resolution as the heightmap
-    uint width, height;
-    normal_map.GetDimensions(width, height);
+[[vk::combinedImageSampler, vk::binding(8, 0)]]
+SamplerState splat_smp;
+
+// Linear colors of the other layers, see TerrainMaterials.
+[[vk::binding(9, 0)]]
+cbuffer Materials {
+    float4 material_colors[3];
+};
 
-    float3 normal = normal_map.SampleLevel(smp, input.UV, 0.0).rgb;
-    // remap back to [-1, 1]
-    normal = normal * 2.0 - float3(1.0, 1.0, 1.0);
+PS_OUTPUT main(PS_INPUT input) {
+    PS_OUTPUT output = (PS_OUTPUT) 0;
+    float3 normal = terrain_normal(input.UV);
     float diff = max(dot(normal, -sun_dir), 0.0);
-    float4 color = diffuse_map.Sample(color_smp, input.UV).rgba;
+    uint width, height;
+    diffuse_map.GetDimensions(width, height);
+    float4 color = diffuse_map.Sample(color_smp, terrain_sample_uv(input.UV, uint2(width, height))).rgba;
+    // The diffuse map stores sRGB data in a UNORM image, so we decode it ourselves.
+    color.rgb = srgb2rgb(color.rgb);
+    splat_map.GetDimensions(width, height);
+    float4 weights = splat_map.Sample(splat_smp, terrain_sample_uv(input.UV, uint2(width, height)));
+    // Painted weights sum to one, but rounding to 8 bits and filtering may leave them slightly
+    // off, which would brighten or darken the terrain.
+    weights /= max(dot(weights, float4(1.0, 1.0, 1.0, 1.0)), 0.0001);
+    color.rgb = color.rgb * weights.r
+        + material_colors[0].rgb * weights.g
+        + material_colors[1].rgb * weights.b
+        + material_colors[2].rgb * weights.a;
     output.Color = float4(color.rgb * diff, 1.0);
-    output.Motion = input.PrevClipPos.xy / input.PrevClipPos.w - input.ClipPos.xy / input.ClipPos.w;
+    output.Motion = motion_vector(input);
     return output;
-}
\ No newline at end of file
+}
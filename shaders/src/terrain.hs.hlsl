@@ -1,3 +1,11 @@
+#include "terrain_uv.hlsl"
+
+[[vk::binding(0, 0)]]
+cbuffer Camera {
+    float4x4 projection_view;
+    float4x4 prev_pv;
+};
+
 struct VSOutput {
     float4 Position : SV_POSITION;
     float2 UV : UV0;
@@ -15,24 +23,73 @@ struct ConstantsHSOutput {
 
 [[vk::push_constant]]
 struct PC {
+    // Maximum tessellation factor for a single edge
     uint tessellation_factor;
     float height_scaling;
+    uint2 viewport_size;
+    // Desired length of a tessellated edge on screen, in pixels
+    float target_edge_length;
+    float detail_tiling;
+    float detail_strength;
+    float2 horizontal_scale;
 } pc;
 
-// TODO: Tessellation factor based on screen size? 
-// (See https://github.com/SaschaWillems/Vulkan/blob/fa0e3485d00c73b0637b914e6510d142ee2e5fdf/data/shaders/hlsl/terraintessellation/terrain.tesc#L41)
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+Texture2D<half> heightmap;
+
+[[vk::combinedImageSampler, vk::binding(1, 0)]]
+SamplerState smp;
+
+float4 displaced_position(VSOutput vertex) {
+    float4 position = vertex.Position;
+    uint width, height;
+    heightmap.GetDimensions(width, height);
+    float2 uv = terrain_sample_uv(vertex.UV, uint2(width, height));
+    position.y = heightmap.SampleLevel(smp, uv, 0.0) * pc.height_scaling;
+    return position;
+}
+
+// Computes the tessellation factor for an edge by projecting both endpoints to the screen,
+// and subdividing until each segment is roughly target_edge_length pixels long.
+float edge_tess_factor(float4 p0, float4 p1) {
+    float4 clip0 = mul(projection_view, p0);
+    float4 clip1 = mul(projection_view, p1);
+    // If the edge crosses the camera plane its projected length is meaningless, so we
+    // fall back to full detail.
+    if (clip0.w <= 0.0 || clip1.w <= 0.0) {
+        return pc.tessellation_factor;
+    }
+    float2 screen0 = (clip0.xy / clip0.w) * 0.5 * float2(pc.viewport_size);
+    float2 screen1 = (clip1.xy / clip1.w) * 0.5 * float2(pc.viewport_size);
+    float pixels = distance(screen0, screen1);
+    return clamp(pixels / max(pc.target_edge_length, 1.0), 1.0, float(pc.tessellation_factor));
+}
 
 ConstantsHSOutput HSConstants(InputPatch<VSOutput, 4> patch, uint InvocationID : SV_PrimitiveID) {
     ConstantsHSOutput output = (ConstantsHSOutput)0;
-    output.TessLevelOuter[0] = pc.tessellation_factor;
-    output.TessLevelOuter[1] = pc.tessellation_factor;
-    output.TessLevelOuter[2] = pc.tessellation_factor;
-    output.TessLevelOuter[3] = pc.tessellation_factor;
+    float4 p0 = displaced_position(patch[0]);
+    float4 p1 = displaced_position(patch[1]);
+    float4 p2 = displaced_position(patch[2]);
+    float4 p3 = displaced_position(patch[3]);
+    output.TessLevelOuter[0] = edge_tess_factor(p3, p0);
+    output.TessLevelOuter[1] = edge_tess_factor(p0, p1);
+    output.TessLevelOuter[2] = edge_tess_factor(p1, p2);
+    output.TessLevelOuter[3] = edge_tess_factor(p2, p3);
     output.TessLevelInner[0] = lerp(output.TessLevelOuter[0], output.TessLevelOuter[3], 0.5);
     output.TessLevelInner[1] = lerp(output.TessLevelOuter[2], output.TessLevelOuter[1], 0.5);
     return output;
 }
 
+HSOutput pass_through(InputPatch<VSOutput, 4> patch, uint InvocationID) {
+    HSOutput output = (HSOutput) 0;
+    output.Position = patch[InvocationID].Position;
+    output.UV = patch[InvocationID].UV;
+    return output;
+}
+
+// The partitioning mode is part of the entry point, so there is one entry point per spacing
+// mode. Fractional spacing morphs vertices smoothly as the factors change instead of popping.
+
 [domain("quad")]
 [partitioning("integer")]
 [outputtopology("triangle_ccw")]
@@ -40,8 +97,25 @@ ConstantsHSOutput HSConstants(InputPatch<VSOutput, 4> patch, uint InvocationID :
 [patchconstantfunc("HSConstants")]
 [maxtessfactor(128.0f)]
 HSOutput main(InputPatch<VSOutput, 4> patch, uint InvocationID : SV_OutputControlPointID) {
-    HSOutput output = (HSOutput) 0;
-    output.Position = patch[InvocationID].Position;
-    output.UV = patch[InvocationID].UV;
-    return output;
-}
\ No newline at end of file
+    return pass_through(patch, InvocationID);
+}
+
+[domain("quad")]
+[partitioning("fractional_even")]
+[outputtopology("triangle_ccw")]
+[outputcontrolpoints(4)]
+[patchconstantfunc("HSConstants")]
+[maxtessfactor(128.0f)]
+HSOutput main_fractional_even(InputPatch<VSOutput, 4> patch, uint InvocationID : SV_OutputControlPointID) {
+    return pass_through(patch, InvocationID);
+}
+
+[domain("quad")]
+[partitioning("fractional_odd")]
+[outputtopology("triangle_ccw")]
+[outputcontrolpoints(4)]
+[patchconstantfunc("HSConstants")]
+[maxtessfactor(128.0f)]
+HSOutput main_fractional_odd(InputPatch<VSOutput, 4> patch, uint InvocationID : SV_OutputControlPointID) {
+    return pass_through(patch, InvocationID);
+}
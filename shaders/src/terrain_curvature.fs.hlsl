@@ -0,0 +1,25 @@
+#include "terrain_surface.hlsl"
+
+// Debug view of the curvature map, see DerivedMaps.
+
+[[vk::combinedImageSampler, vk::binding(7, 0)]]
+Texture2D<half> curvature_map;
+
+[[vk::combinedImageSampler, vk::binding(7, 0)]]
+SamplerState curvature_smp;
+
+PS_OUTPUT main(PS_INPUT input) {
+    PS_OUTPUT output = (PS_OUTPUT) 0;
+    uint width, height;
+    curvature_map.GetDimensions(width, height);
+    float curvature = curvature_map.Sample(curvature_smp, terrain_sample_uv(input.UV, uint2(width, height)));
+    // A curvature of 0.05/m, which is a radius of 20 meters, maps to the full color.
+    float t = saturate(abs(curvature) * 20.0);
+    float3 tint = curvature > 0.0 ? float3(0.1, 0.2, 0.9) : float3(0.9, 0.15, 0.1);
+    float3 color = lerp(float3(0.5, 0.5, 0.5), tint, t);
+    // Keep some shading so the shape of the terrain remains readable.
+    float3 normal = terrain_normal(input.UV);
+    output.Color = float4(color * (0.6 + 0.4 * normal.y), 1.0);
+    output.Motion = motion_vector(input);
+    return output;
+}
@@ -0,0 +1,53 @@
+#include "border.hlsl"
+#include "terrain_normal.hlsl"
+#include "terrain_uv.hlsl"
+
+// Derives the slope and curvature maps from the base heightmap, see DerivedMaps.
+
+[[vk::binding(0, 0), vk::image_format("r16f")]]
+RWTexture2D<float> slope_map;
+
+[[vk::binding(1, 0), vk::image_format("r16f")]]
+RWTexture2D<float> curvature_map;
+
+[[vk::combinedImageSampler, vk::binding(2, 0)]]
+Texture2D<half> heightmap;
+
+[[vk::combinedImageSampler, vk::binding(2, 0)]]
+SamplerState smp;
+
+[[vk::push_constant]] struct PC {
+    NormalParams normal;
+} pc;
+
+float sample_height(int x, int y, uint width, uint height) {
+    int2 texel = border_texel(int2(x, y), uint2(width, height), pc.normal.border_mode);
+    return heightmap.SampleLevel(smp, texel_center_uv(texel, uint2(width, height)), 0.0);
+}
+
+[numthreads(32, 32, 1)]
+void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
+    uint width, height;
+    slope_map.GetDimensions(width, height);
+    if (GlobalInvocationID.x >= width)
+        return;
+    if (GlobalInvocationID.y >= height)
+        return;
+
+    float heights[3][3];
+    for (int hx = -1; hx <= 1; hx++) {
+        for (int hy = -1; hy <= 1; hy++) {
+            heights[hx + 1][hy + 1] = sample_height(GlobalInvocationID.x + hx, GlobalInvocationID.y + hy, width, height);
+        }
+    }
+
+    // Angle between the terrain and the horizontal plane, in radians.
+    float2 slope = sobel_slope(heights, pc.normal);
+    slope_map[GlobalInvocationID.xy] = atan(length(slope));
+
+    // Laplacian of the height in world space. This is positive where the terrain is concave.
+    float2 spacing_sq = pc.normal.texel_spacing * pc.normal.texel_spacing;
+    float dxx = (heights[0][1] - 2.0 * heights[1][1] + heights[2][1]) / spacing_sq.x;
+    float dyy = (heights[1][0] - 2.0 * heights[1][1] + heights[1][2]) / spacing_sq.y;
+    curvature_map[GlobalInvocationID.xy] = (dxx + dyy) * pc.normal.vertical_scale;
+}
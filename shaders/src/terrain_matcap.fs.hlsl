@@ -0,0 +1,27 @@
+#include "terrain_surface.hlsl"
+
+[[vk::binding(0, 0)]]
+cbuffer Camera {
+    float4x4 projection_view;
+    float4x4 prev_pv;
+    float4x4 view;
+};
+
+// Procedural clay matcap. Since the light is fixed relative to the camera, the shape of
+// the terrain reads the same from every angle.
+float3 matcap(float3 view_normal) {
+    float3 light = normalize(float3(-0.4, 0.6, 0.7));
+    float diffuse = saturate(dot(view_normal, light));
+    float rim = pow(1.0 - saturate(view_normal.z), 3.0);
+    float3 clay = float3(0.55, 0.52, 0.5);
+    return clay * (0.2 + 0.8 * diffuse) + rim * 0.1;
+}
+
+PS_OUTPUT main(PS_INPUT input) {
+    PS_OUTPUT output = (PS_OUTPUT) 0;
+    float3 normal = terrain_normal(input.UV);
+    float3 view_normal = normalize(mul((float3x3) view, normal));
+    output.Color = float4(matcap(view_normal), 1.0);
+    output.Motion = motion_vector(input);
+    return output;
+}
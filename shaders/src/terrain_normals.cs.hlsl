@@ -1,3 +1,7 @@
+#include "border.hlsl"
+#include "terrain_normal.hlsl"
+#include "terrain_uv.hlsl"
+
 [[vk::binding(0, 0)]]
 RWTexture2D<float4> normals;
 
@@ -7,11 +11,13 @@ Texture2D<half> heightmap;
 [[vk::combinedImageSampler, vk::binding(1, 0)]]
 SamplerState smp;
 
+[[vk::push_constant]] struct PC {
+    NormalParams normal;
+} pc;
+
 float sample_height(int x, int y, uint width, uint height) {
-    x = (float) clamp(x, 0, width);
-    y = (float) clamp(y, 0, height);
-    float2 uv = float2(x, y) / float2((float) width, (float) height);
-    return heightmap.SampleLevel(smp, uv, 0.0);
+    int2 texel = border_texel(int2(x, y), uint2(width, height), pc.normal.border_mode);
+    return heightmap.SampleLevel(smp, texel_center_uv(texel, uint2(width, height)), 0.0);
 }
 
 [numthreads(32, 32, 1)]
@@ -34,26 +40,5 @@ void main(uint3 GlobalInvocationID : SV_DispatchThreadID) {
     }
 
     // Now that we have our height samples, we can calculate the normal
-    float3 normal = (float3) 0;
-    normal.x = 
-        heights[0][0] 
-        - heights[2][0] 
-        + 2.0f * heights[0][1] 
-        - 2.0f * heights[2][1] 
-        + heights[0][2] 
-        - heights[2][2];
-    normal.z = 
-        heights[0][0] 
-        + 2.0f * heights[1][0] 
-        + heights[2][0] 
-        - heights[0][2] 
-        - 2.0f * heights[1][2] 
-        - heights[2][2];
-    
-    // original code has 0.25 sqrt(1 - x * x - z * z) here, which leads to NaNs
-    normal.y = 0.25;
-    normal = normalize(normal * float3(2.0, 1.0, 2.0));
-    // Remap normal from -1, 1 to [0, 1] before storing
-    normal = (normal + float3(1.0, 1.0, 1.0)) / 2.0;
-    normals[GlobalInvocationID.xy] = float4(normal, 0.0);
+    normals[GlobalInvocationID.xy] = encode_normal(sobel_normal(heights, pc.normal));
 }
\ No newline at end of file
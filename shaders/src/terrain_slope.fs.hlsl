@@ -0,0 +1,24 @@
+#include "terrain_surface.hlsl"
+
+// Debug view of the slope map, see DerivedMaps.
+
+[[vk::combinedImageSampler, vk::binding(7, 0)]]
+Texture2D<half> slope_map;
+
+[[vk::combinedImageSampler, vk::binding(7, 0)]]
+SamplerState slope_smp;
+
+PS_OUTPUT main(PS_INPUT input) {
+    PS_OUTPUT output = (PS_OUTPUT) 0;
+    uint width, height;
+    slope_map.GetDimensions(width, height);
+    float slope = slope_map.Sample(slope_smp, terrain_sample_uv(input.UV, uint2(width, height)));
+    // Map the angle from flat to vertical onto a green to red ramp.
+    float t = saturate(slope / (3.14159265 / 2.0));
+    float3 color = lerp(float3(0.1, 0.6, 0.1), float3(0.8, 0.1, 0.1), t);
+    // Keep some shading so the shape of the terrain remains readable.
+    float3 normal = terrain_normal(input.UV);
+    output.Color = float4(color * (0.6 + 0.4 * normal.y), 1.0);
+    output.Motion = motion_vector(input);
+    return output;
+}
@@ -63,16 +63,38 @@ float aces_tonemap(float x) {
     return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
 }
 
-float4 main(in PS_INPUT input)
-
-: SV_TARGET {
-float3 color = hdr_input.Sample(smp, input.UV).rgb;
-float3 xyY = rgb2xyY(color);
-float lum = xyY.b;
-lum = aces_tonemap(lum);
-xyY.
-b = lum;
-return
-float4(xyY2rgb(xyY),
-1.0);
-}
\ No newline at end of file
+// Values of TonemapOperator
+static const uint CURVE_ACES_LUMINANCE = 0;
+static const uint CURVE_ACES = 1;
+static const uint CURVE_REINHARD = 2;
+static const uint CURVE_CLAMP = 3;
+
+[[vk::push_constant]]
+struct PC {
+    uint curve;
+    // Exposure adjustment in stops, applied before the curve.
+    float exposure;
+} pc;
+
+// Applies a tonemapping curve to the luminance of a color, keeping its chromaticity.
+float3 tonemap_luminance(float3 color, uint curve) {
+    float3 xyY = rgb2xyY(color);
+    if (curve == CURVE_REINHARD) {
+        xyY.b = xyY.b / (1.0 + xyY.b);
+    } else {
+        xyY.b = aces_tonemap(xyY.b);
+    }
+    return xyY2rgb(xyY);
+}
+
+float4 main(in PS_INPUT input) : SV_TARGET {
+    float3 color = hdr_input.Sample(smp, input.UV).rgb * exp2(pc.exposure);
+    switch (pc.curve) {
+        case CURVE_ACES:
+            return float4(aces_tonemap(color), 1.0);
+        case CURVE_CLAMP:
+            return float4(saturate(color), 1.0);
+        default:
+            return float4(tonemap_luminance(color, pc.curve), 1.0);
+    }
+}